@@ -0,0 +1,120 @@
+use std::time::Instant;
+
+use actix_web::{HttpRequest, HttpResponse, ResponseError, body::BodySize, body::MessageBody};
+use tracing::{field, info};
+
+use crate::{cache::X_CACHE, errors::AppError, proxy::ProxyRequest};
+
+// Log target of the access log, for filtering (`RUST_LOG=access_log=info`).
+pub const TARGET: &str = "access_log";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    // A validator answered with a 2xx.
+    Success,
+    // A validator answered with another status.
+    UpstreamError,
+    CacheHit,
+    // Served by another caller's identical in-flight request.
+    Coalesced,
+    // Refused by the proxy before any validator was asked.
+    Rejected,
+    // No usable answer (unreachable validators, oversized response, ...).
+    Failed,
+}
+
+impl Outcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::UpstreamError => "upstream_error",
+            Self::CacheHit => "cache_hit",
+            Self::Coalesced => "coalesced",
+            Self::Rejected => "rejected",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+// Overrides the outcome `record` would infer, for responses the proxy
+// produced itself.
+pub(crate) fn mark(mut response: HttpResponse, outcome: Outcome) -> HttpResponse {
+    response.extensions_mut().insert(outcome);
+    response
+}
+
+// Errors before any validator was asked are the proxy refusing the request.
+pub(crate) fn failure(request: &ProxyRequest<'_>) -> Outcome {
+    if request.attempted().is_empty() {
+        Outcome::Rejected
+    } else {
+        Outcome::Failed
+    }
+}
+
+// One line per proxied request. The field set is part of the documented
+// interface; add fields rather than renaming them.
+pub(crate) fn record(
+    req: &HttpRequest,
+    request: &ProxyRequest<'_>,
+    started: Instant,
+    result: &Result<HttpResponse, AppError>,
+) {
+    let upstream = request.last_upstream();
+    let (status, response_bytes, outcome) = match result {
+        Ok(response) => {
+            let bytes = match response.body().size() {
+                BodySize::Sized(bytes) => Some(bytes),
+                BodySize::None => Some(0),
+                BodySize::Stream => None,
+            };
+            (response.status(), bytes, outcome(response, request))
+        }
+        Err(err) => (err.status_code(), None, failure(request)),
+    };
+
+    info!(
+        target: TARGET,
+        client_ip = req.peer_addr().map(|addr| field::display(addr.ip())),
+        request_id = request.request_id.as_ref().map(field::display),
+        key_id = request.key_id.as_deref(),
+        rpc_method = request.rpc_method.as_ref().map(field::display),
+        path = req.path(),
+        status = status.as_u16(),
+        validator = upstream.as_ref().map(|attempt| attempt.validator.as_str()),
+        location = upstream.as_ref().map(|attempt| attempt.location.as_str()),
+        upstream_status = upstream
+            .as_ref()
+            .and_then(|attempt| attempt.status)
+            .map(|status| status.as_u16()),
+        upstream_ms = upstream
+            .as_ref()
+            .map(|attempt| attempt.elapsed.as_millis() as u64),
+        total_ms = started.elapsed().as_millis() as u64,
+        response_bytes,
+        retries = request.attempted().len().saturating_sub(1),
+        outcome = outcome.as_str(),
+        "access"
+    );
+}
+
+fn outcome(response: &HttpResponse, request: &ProxyRequest<'_>) -> Outcome {
+    if let Some(outcome) = response.extensions().get::<Outcome>() {
+        return *outcome;
+    }
+    if response
+        .headers()
+        .get(X_CACHE)
+        .is_some_and(|value| value == "HIT")
+    {
+        return Outcome::CacheHit;
+    }
+
+    match request.last_upstream() {
+        None => Outcome::Coalesced,
+        Some(attempt) if attempt.status.is_some_and(|status| status.is_success()) => {
+            Outcome::Success
+        }
+        Some(_) => Outcome::UpstreamError,
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde_json::{Value, json};
+
+use crate::cache::CacheCounters;
+use crate::commitment::Commitment;
+use crate::config::Settings;
+use crate::jsonrpc::RpcMethod;
+
+// Config fields that only select which view of the account is returned.
+const KEYED_CONFIG_FIELDS: &[&str] = &["encoding", "dataSlice", "commitment"];
+
+// The per-account entries a cacheable request reads or fills.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountLookup {
+    keys: Vec<String>,
+    multiple: bool,
+}
+
+struct AccountEntry {
+    value: Value,
+    context_slot: u64,
+    stored_at: Instant,
+    last_used: u64,
+}
+
+struct AccountEntries {
+    tick: u64,
+    entries: HashMap<String, AccountEntry>,
+}
+
+// `getAccountInfo`/`getMultipleAccounts` values per (pubkey, encoding,
+// dataSlice, commitment), so a multi-account read can be answered from
+// entries filled by single reads and vice versa.
+pub struct AccountCache {
+    enabled: bool,
+    ttl: Duration,
+    max_slot_lag: u64,
+    max_entries: usize,
+    cache_processed: bool,
+    state: Mutex<AccountEntries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl AccountCache {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            enabled: settings.account_cache,
+            ttl: settings.account_cache_ttl,
+            max_slot_lag: settings.account_cache_max_slot_lag,
+            max_entries: settings.account_cache_max_entries,
+            cache_processed: settings.account_cache_processed,
+            state: Mutex::new(AccountEntries {
+                tick: 0,
+                entries: HashMap::new(),
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    // A pinned `server` gets its own entries, like in the response cache.
+    // Requests with other config fields (e.g. `minContextSlot`) are not cached.
+    pub fn lookup(
+        &self,
+        method: &RpcMethod,
+        server: Option<&str>,
+        body: &[u8],
+    ) -> Option<AccountLookup> {
+        if !self.enabled || self.max_entries == 0 {
+            return None;
+        }
+        let multiple = match method.name()? {
+            "getAccountInfo" => false,
+            "getMultipleAccounts" => true,
+            _ => return None,
+        };
+
+        let Value::Object(request) = serde_json::from_slice::<Value>(body).ok()? else {
+            return None;
+        };
+        let params = request.get("params")?;
+        let (target, config) = match params.as_array()?.as_slice() {
+            [target] => (target, None),
+            [target, Value::Object(config)] => (target, Some(config)),
+            _ => return None,
+        };
+        if config.is_some_and(|config| {
+            config
+                .keys()
+                .any(|name| !KEYED_CONFIG_FIELDS.contains(&name.as_str()))
+        }) {
+            return None;
+        }
+
+        let commitment = Commitment::from_params(Some(params));
+        if commitment == Commitment::Processed && !self.cache_processed {
+            return None;
+        }
+        let field = |name: &str| {
+            config
+                .and_then(|config| config.get(name))
+                .map(Value::to_string)
+                .unwrap_or_default()
+        };
+        let server = server
+            .map(|value| value.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+        let suffix = format!(
+            "{}|{}|{commitment:?}",
+            field("encoding"),
+            field("dataSlice")
+        );
+
+        let pubkeys: Vec<&str> = if multiple {
+            target
+                .as_array()?
+                .iter()
+                .map(Value::as_str)
+                .collect::<Option<_>>()?
+        } else {
+            vec![target.as_str()?]
+        };
+        if pubkeys.is_empty() {
+            return None;
+        }
+
+        Some(AccountLookup {
+            keys: pubkeys
+                .into_iter()
+                .map(|pubkey| format!("{server}|{pubkey}|{suffix}"))
+                .collect(),
+            multiple,
+        })
+    }
+
+    // Builds the `result` from cached values when every account is fresh.
+    // Its `context.slot` is the oldest slot among them.
+    pub fn get(&self, lookup: &AccountLookup, cluster_slot: Option<u64>) -> Option<Value> {
+        let now = Instant::now();
+        let mut state = self.state.lock().expect("account cache lock poisoned");
+        state.tick += 1;
+        let tick = state.tick;
+
+        let mut values = Vec::with_capacity(lookup.keys.len());
+        let mut slot = u64::MAX;
+        for key in &lookup.keys {
+            let fresh = state
+                .entries
+                .get(key)
+                .is_some_and(|entry| self.is_fresh(entry, now, cluster_slot));
+            if !fresh {
+                state.entries.remove(key);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+            if let Some(entry) = state.entries.get_mut(key) {
+                entry.last_used = tick;
+                slot = slot.min(entry.context_slot);
+                values.push(entry.value.clone());
+            }
+        }
+
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        let value = if lookup.multiple {
+            Value::Array(values)
+        } else {
+            values.pop().unwrap_or(Value::Null)
+        };
+        Some(json!({"context": {"slot": slot}, "value": value}))
+    }
+
+    fn is_fresh(&self, entry: &AccountEntry, now: Instant, cluster_slot: Option<u64>) -> bool {
+        now.duration_since(entry.stored_at) < self.ttl
+            && cluster_slot
+                .is_none_or(|slot| slot.saturating_sub(entry.context_slot) <= self.max_slot_lag)
+    }
+
+    // Only successful results with a context slot are kept.
+    pub fn insert(&self, lookup: &AccountLookup, body: &[u8]) {
+        let Ok(Value::Object(mut response)) = serde_json::from_slice::<Value>(body) else {
+            return;
+        };
+        let Some(mut result) = response.remove("result") else {
+            return;
+        };
+        let Some(context_slot) = result.pointer("/context/slot").and_then(Value::as_u64) else {
+            return;
+        };
+        let values = match (lookup.multiple, result["value"].take()) {
+            (true, Value::Array(values)) if values.len() == lookup.keys.len() => values,
+            (false, value) => vec![value],
+            _ => return,
+        };
+
+        let now = Instant::now();
+        let mut state = self.state.lock().expect("account cache lock poisoned");
+        state.tick += 1;
+        let tick = state.tick;
+        for (key, value) in lookup.keys.iter().zip(values) {
+            if state
+                .entries
+                .get(key)
+                .is_some_and(|entry| entry.context_slot > context_slot)
+            {
+                continue;
+            }
+            if !state.entries.contains_key(key) && state.entries.len() >= self.max_entries {
+                let oldest = state
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    state.entries.remove(&oldest);
+                }
+            }
+            state.entries.insert(
+                key.clone(),
+                AccountEntry {
+                    value,
+                    context_slot,
+                    stored_at: now,
+                    last_used: tick,
+                },
+            );
+        }
+    }
+
+    pub fn counters(&self) -> CacheCounters {
+        CacheCounters {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.state
+            .lock()
+            .expect("account cache lock poisoned")
+            .entries
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
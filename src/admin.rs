@@ -0,0 +1,90 @@
+use std::fmt;
+
+use actix_web::{
+    Error, HttpRequest,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header,
+    middleware::Next,
+    web::Data,
+};
+
+use crate::{app_state::AppState, errors::AppError};
+
+// The operator credential for admin calls, never one a client holds.
+#[derive(Clone, PartialEq, Eq)]
+pub struct AdminToken(String);
+
+impl AdminToken {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self(token.into())
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for AdminToken {
+    fn from(token: &str) -> Self {
+        Self::new(token)
+    }
+}
+
+// Keeps the token out of logged settings.
+impl fmt::Debug for AdminToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AdminToken(<redacted>)")
+    }
+}
+
+// Admin calls carry `Authorization: Bearer <ADMIN_TOKEN>`. Without a
+// configured token they are refused outright, and a client's API key is
+// refused even when it is otherwise valid.
+fn authorize(req: &HttpRequest, state: &AppState) -> Result<(), AppError> {
+    let Some(expected) = &state.settings().admin_token else {
+        return Err(AppError::Forbidden(
+            "admin endpoints are disabled; set ADMIN_TOKEN".to_string(),
+        ));
+    };
+
+    let presented = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+    match presented {
+        Some(token) if constant_time_eq(token.as_bytes(), expected.as_str().as_bytes()) => Ok(()),
+        Some(token) if is_client_key(state, token) => Err(AppError::Forbidden(
+            "API keys cannot call admin endpoints".to_string(),
+        )),
+        _ => Err(AppError::Unauthorized("invalid admin token".to_string())),
+    }
+}
+
+fn is_client_key(state: &AppState, token: &str) -> bool {
+    state
+        .api_keys()
+        .current()
+        .is_some_and(|keys| keys.find(token).is_some())
+}
+
+// Wrapped around the `/admin` scope and `/stats/reset`, so every admin
+// route needs the admin token whatever client auth is configured.
+pub async fn require_token(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    if let Some(state) = req.app_data::<Data<AppState>>()
+        && let Err(err) = authorize(req.request(), state)
+    {
+        return Ok(req.error_response(err).map_into_right_body());
+    }
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+// Compares every byte so the time taken does not reveal the matching prefix.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
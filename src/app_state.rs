@@ -1,35 +1,450 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
-use awc::Client;
+use actix_tls::connect::{Connector as TcpConnector, Resolver};
+use awc::{Client, Connector};
+use serde_json::Value;
+use thiserror::Error;
+use url::Url;
 
-use crate::validators::ValidatorRegistry;
+use crate::account_cache::AccountCache;
+use crate::cache::ResponseCache;
+use crate::client_limit::ClientLimiters;
+use crate::coalesce::Coalescer;
+use crate::concurrency::ConcurrencyLimit;
+use crate::config::{ConfigError, Settings, SettingsBuilder};
+use crate::dns::{CachedResolver, DnsCache, HostResolver, SystemResolver};
+#[cfg(feature = "sentry")]
+use crate::error_reporting::FailureSpikes;
+use crate::fleet::{PING, Throttle, TimedCache};
+use crate::ip_filter::Rejections;
+use crate::jwt::Jwks;
+use crate::key_store::{self, KeyStore};
+use crate::key_usage::KeyUsageTable;
+use crate::rate_limit::{KeyRateLimiter, MethodRateLimit, MethodRateLimiter, MethodRateLimits};
+use crate::registry_store::{self, RegistryStore};
+use crate::snapshot;
+use crate::stats::StatsStore;
+#[cfg(feature = "statsd")]
+use crate::statsd::{self, Statsd};
+use crate::validators::{RegistryError, Validator, ValidatorRegistry};
+
+static NEXT_STATE_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // awc clients are tied to their worker thread. Each state keeps one per
+    // upstream host there, so connections are reused across requests and
+    // `UPSTREAM_MAX_CONNECTIONS_PER_HOST` bounds each host's pool.
+    static UPSTREAM_CLIENTS: RefCell<HashMap<u64, UpstreamClients>> = RefCell::default();
+}
+
+// One state's clients on one worker, by `scheme://host:port`.
+#[derive(Default)]
+struct UpstreamClients {
+    // The registry they were last pruned against.
+    registry: u64,
+    // The DNS answers they connected to; clients are rebuilt once those
+    // change, so connections to the previous addresses are not reused.
+    dns: u64,
+    by_host: HashMap<String, Client>,
+}
 
 #[derive(Clone)]
 pub struct AppState {
-    registry: Arc<ValidatorRegistry>,
-    request_timeout: Duration,
+    // Tells apart the clients of states sharing a thread, as tests do.
+    id: u64,
+    registry: Arc<RegistryStore>,
+    settings: Arc<Settings>,
+    dns: Arc<DnsCache>,
+    stats: Arc<StatsStore>,
+    coalescer: Arc<Coalescer>,
+    response_cache: Arc<ResponseCache>,
+    account_cache: Arc<AccountCache>,
+    method_limiter: Arc<MethodRateLimiter>,
+    ping_limiter: Arc<MethodRateLimiter>,
+    key_limiter: Arc<KeyRateLimiter>,
+    client_limiters: Arc<ClientLimiters>,
+    concurrency_limit: Option<Arc<ConcurrencyLimit>>,
+    ip_rejections: Arc<Rejections>,
+    jwks: Arc<Jwks>,
+    api_keys: Arc<KeyStore>,
+    fleet_throttle: Arc<Throttle>,
+    cluster_nodes: Arc<TimedCache<Value>>,
+    started_at: Instant,
+    #[cfg(feature = "statsd")]
+    statsd: Option<Arc<Statsd>>,
+    #[cfg(feature = "sentry")]
+    upstream_failures: Arc<FailureSpikes>,
+    open_listeners: Arc<HashSet<SocketAddr>>,
+    // Set once shutdown began; new proxied requests are turned away.
+    draining: Arc<AtomicBool>,
 }
 
 impl AppState {
+    pub fn builder() -> AppStateBuilder {
+        AppStateBuilder::default()
+    }
+
     pub fn new(registry: ValidatorRegistry) -> Self {
+        Self::from_settings(registry, Settings::default())
+    }
+
+    // Takes `settings` as they are; `builder` checks them first.
+    pub fn from_settings(registry: ValidatorRegistry, settings: Settings) -> Self {
+        let dns = DnsCache::new(Arc::new(SystemResolver), settings.dns_cache_ttl);
+        let coalescer = Coalescer::new(
+            settings.coalesce_requests,
+            &settings.coalesce_methods,
+            settings.coalesce_max_age,
+        );
+        let stats = StatsStore::with_method_cap(settings.method_stats_max_methods)
+            .with_latency_buckets(&settings.latency_buckets_ms)
+            .with_key_usage(KeyUsageTable::new(
+                settings.key_usage_max_keys,
+                settings.key_usage_retention,
+                settings.key_metrics_max_keys,
+            ));
+        let response_cache = ResponseCache::new(&settings);
+        let account_cache = AccountCache::new(&settings);
+        let method_limiter = MethodRateLimiter::new(
+            settings.method_rate_limits.clone(),
+            settings.rate_limit_max_clients,
+        );
+        // Pings hit a validator on a client's behalf; each client gets its
+        // own budget so the proxy cannot be used to flood one.
+        let ping_limiter = MethodRateLimiter::new(
+            MethodRateLimits::new(vec![MethodRateLimit {
+                method: PING.to_string(),
+                per_second: settings.ping_rate_limit,
+                burst: None,
+            }])
+            .expect("PING_RATE_LIMIT is validated when loaded"),
+            settings.rate_limit_max_clients,
+        );
+        let key_limiter = KeyRateLimiter::new(settings.rate_limit_max_clients);
+        let api_keys = KeyStore::new(settings.api_keys.clone());
+        let client_limiters = ClientLimiters::new(settings.rate_limit_max_clients);
+        let concurrency_limit = settings.max_concurrent_requests.map(|max| {
+            ConcurrencyLimit::new(
+                max,
+                settings.concurrency_queue_timeout,
+                settings.concurrency_queue_depth,
+                settings.concurrency_fair_queues,
+            )
+        });
+        let fleet_throttle = Throttle::new(settings.fleet_min_interval);
+        let cluster_nodes = TimedCache::new(settings.cluster_nodes_cache_ttl);
+        // Metrics are best effort; a bad agent address must not stop the proxy.
+        #[cfg(feature = "statsd")]
+        let statsd = Statsd::from_settings(&settings)
+            .unwrap_or_else(|err| {
+                tracing::warn!(error = %err, "statsd export disabled");
+                None
+            })
+            .map(Arc::new);
         Self {
-            registry: Arc::new(registry),
-            request_timeout: Duration::from_secs(15),
+            id: NEXT_STATE_ID.fetch_add(1, Ordering::Relaxed),
+            registry: Arc::new(RegistryStore::new(registry, &settings)),
+            settings: Arc::new(settings),
+            dns: Arc::new(dns),
+            stats: Arc::new(stats),
+            coalescer: Arc::new(coalescer),
+            response_cache: Arc::new(response_cache),
+            account_cache: Arc::new(account_cache),
+            method_limiter: Arc::new(method_limiter),
+            ping_limiter: Arc::new(ping_limiter),
+            key_limiter: Arc::new(key_limiter),
+            client_limiters: Arc::new(client_limiters),
+            concurrency_limit: concurrency_limit.map(Arc::new),
+            ip_rejections: Arc::default(),
+            jwks: Arc::default(),
+            api_keys: Arc::new(api_keys),
+            fleet_throttle: Arc::new(fleet_throttle),
+            cluster_nodes: Arc::new(cluster_nodes),
+            started_at: Instant::now(),
+            #[cfg(feature = "statsd")]
+            statsd,
+            #[cfg(feature = "sentry")]
+            upstream_failures: Arc::default(),
+            open_listeners: Arc::default(),
+            draining: Arc::default(),
         }
     }
 
-    pub fn registry(&self) -> &ValidatorRegistry {
+    // Sockets bound for listeners with `auth=false`.
+    pub fn with_open_listeners(mut self, addrs: impl IntoIterator<Item = SocketAddr>) -> Self {
+        self.open_listeners = Arc::new(addrs.into_iter().collect());
+        self
+    }
+
+    // Whether a request that came in on `local_addr` skips client auth.
+    pub fn is_open_listener(&self, local_addr: SocketAddr) -> bool {
+        self.open_listeners.contains(&local_addr)
+    }
+
+    pub fn with_resolver(mut self, resolver: Arc<dyn HostResolver>) -> Self {
+        self.dns = Arc::new(DnsCache::new(resolver, self.settings.dns_cache_ttl));
+        // Clients made so far resolve through the old cache.
+        self.id = NEXT_STATE_ID.fetch_add(1, Ordering::Relaxed);
+        self
+    }
+
+    // The registry in use now. A request should hold on to one snapshot
+    // rather than call this again, since a reload may swap it meanwhile.
+    pub fn registry(&self) -> Arc<ValidatorRegistry> {
+        self.registry.current()
+    }
+
+    pub fn registry_store(&self) -> &RegistryStore {
         self.registry.as_ref()
     }
 
+    pub fn settings(&self) -> &Settings {
+        self.settings.as_ref()
+    }
+
+    pub fn dns(&self) -> &Arc<DnsCache> {
+        &self.dns
+    }
+
+    pub fn stats(&self) -> &StatsStore {
+        self.stats.as_ref()
+    }
+
+    pub fn coalescer(&self) -> &Coalescer {
+        self.coalescer.as_ref()
+    }
+
+    pub fn response_cache(&self) -> &ResponseCache {
+        self.response_cache.as_ref()
+    }
+
+    pub fn account_cache(&self) -> &AccountCache {
+        self.account_cache.as_ref()
+    }
+
+    pub fn method_limiter(&self) -> &MethodRateLimiter {
+        self.method_limiter.as_ref()
+    }
+
+    // Keyed by client like `method_limiter`; check with `RpcMethod::Single(PING)`.
+    pub fn ping_limiter(&self) -> &MethodRateLimiter {
+        self.ping_limiter.as_ref()
+    }
+
+    // One bucket per API key id, sized by the key's own limit.
+    pub fn key_limiter(&self) -> &KeyRateLimiter {
+        self.key_limiter.as_ref()
+    }
+
+    pub fn client_limiters(&self) -> &ClientLimiters {
+        self.client_limiters.as_ref()
+    }
+
+    pub fn concurrency_limit(&self) -> Option<&Arc<ConcurrencyLimit>> {
+        self.concurrency_limit.as_ref()
+    }
+
+    pub fn ip_rejections(&self) -> &Rejections {
+        self.ip_rejections.as_ref()
+    }
+
+    pub fn jwks(&self) -> &Jwks {
+        &self.jwks
+    }
+
+    // The keys in use; `settings().api_keys` only holds those read at
+    // startup.
+    pub fn api_keys(&self) -> &KeyStore {
+        self.api_keys.as_ref()
+    }
+
+    pub fn fleet_throttle(&self) -> &Throttle {
+        self.fleet_throttle.as_ref()
+    }
+
+    // Last `/fleet/cluster-nodes` report.
+    pub fn cluster_nodes(&self) -> &TimedCache<Value> {
+        self.cluster_nodes.as_ref()
+    }
+
+    // Monotonic, so wall-clock adjustments don't move it.
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    #[cfg(feature = "statsd")]
+    pub fn statsd(&self) -> Option<&Arc<Statsd>> {
+        self.statsd.as_ref()
+    }
+
+    #[cfg(feature = "sentry")]
+    pub fn upstream_failures(&self) -> &FailureSpikes {
+        self.upstream_failures.as_ref()
+    }
+
     pub fn request_timeout(&self) -> Duration {
-        self.request_timeout
+        self.settings.upstream_timeout
     }
 
+    pub fn spawn_background_tasks(&self) {
+        self.dns.spawn_refresh(self.registry().dns_hosts());
+        #[cfg(feature = "statsd")]
+        if let Some(statsd) = &self.statsd {
+            statsd::spawn_flush(self.clone(), Arc::clone(statsd));
+        }
+        snapshot::spawn_persist(self.clone());
+        key_store::spawn_watch(self.clone());
+        registry_store::spawn_watch(self.clone());
+    }
+
+    // A client of its own, for sweeps across the fleet whose concurrency is
+    // bounded by the caller.
     pub fn build_client(&self) -> Client {
+        self.new_client(0)
+    }
+
+    // The pooled client for `url`'s host on this worker thread.
+    pub fn client_for(&self, url: &Url) -> Client {
+        UPSTREAM_CLIENTS.with(|clients| {
+            let mut clients = clients.borrow_mut();
+            let clients = clients.entry(self.id).or_default();
+            // Hosts a reload removed drop their clients, and with them the
+            // pooled connections, the first time this worker sees the swap.
+            let generation = self.registry.generation();
+            if clients.registry != generation {
+                let registry = self.registry();
+                let hosts: HashSet<String> = registry
+                    .validators()
+                    .iter()
+                    .map(|validator| pool_key(validator.rpc_url()))
+                    .collect();
+                clients.by_host.retain(|host, _| hosts.contains(host));
+                clients.registry = generation;
+            }
+            let dns = self.dns.generation();
+            if clients.dns != dns {
+                clients.by_host.clear();
+                clients.dns = dns;
+            }
+            clients
+                .by_host
+                .entry(pool_key(url))
+                .or_insert_with(|| self.new_client(self.settings.upstream_max_connections_per_host))
+                .clone()
+        })
+    }
+
+    fn new_client(&self, limit: usize) -> Client {
+        let settings = self.settings();
+        let resolver = Resolver::custom(CachedResolver(Arc::clone(&self.dns)));
+        let connector = Connector::new()
+            .connector(TcpConnector::new(resolver).service())
+            .limit(limit)
+            .conn_keep_alive(settings.upstream_keepalive)
+            .conn_lifetime(settings.upstream_conn_lifetime)
+            .disconnect_timeout(settings.upstream_disconnect_timeout);
+
         Client::builder()
-            .timeout(self.request_timeout)
+            .connector(connector)
+            .timeout(self.request_timeout())
             .finish()
     }
 }
+
+fn pool_key(url: &Url) -> String {
+    format!(
+        "{}://{}:{}",
+        url.scheme(),
+        url.host_str().unwrap_or_default(),
+        url.port_or_known_default().unwrap_or_default()
+    )
+}
+
+#[derive(Debug, Error)]
+pub enum BuildError {
+    #[error("no validator registry given")]
+    MissingRegistry,
+    #[error(transparent)]
+    Registry(#[from] RegistryError),
+    #[error(transparent)]
+    Settings(#[from] ConfigError),
+}
+
+// A state without going through the environment: a registry, settings from
+// `Settings::builder` or shortcuts for the common ones, and the pieces
+// `main` otherwise wires in afterwards.
+#[derive(Default)]
+pub struct AppStateBuilder {
+    registry: Option<Result<ValidatorRegistry, RegistryError>>,
+    settings: SettingsBuilder,
+    resolver: Option<Arc<dyn HostResolver>>,
+    open_listeners: Vec<SocketAddr>,
+}
+
+impl AppStateBuilder {
+    pub fn registry(mut self, registry: ValidatorRegistry) -> Self {
+        self.registry = Some(Ok(registry));
+        self
+    }
+
+    // The registry's own checks run on `build`.
+    pub fn validators(mut self, validators: Vec<Validator>) -> Self {
+        self.registry = Some(ValidatorRegistry::new(validators));
+        self
+    }
+
+    // Replaces every setting, shortcuts given so far included.
+    pub fn settings(mut self, settings: impl Into<SettingsBuilder>) -> Self {
+        self.settings = settings.into();
+        self
+    }
+
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.settings = self.settings.upstream_timeout(timeout);
+        self
+    }
+
+    pub fn max_retries(mut self, retries: usize) -> Self {
+        self.settings = self.settings.max_retries(retries);
+        self
+    }
+
+    // Bytes an upstream response may have, for methods without a limit of
+    // their own.
+    pub fn max_upstream_body(mut self, bytes: usize) -> Self {
+        self.settings = self.settings.response_size_limit_bytes(bytes);
+        self
+    }
+
+    pub fn resolver(mut self, resolver: Arc<dyn HostResolver>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    pub fn open_listeners(mut self, addrs: impl IntoIterator<Item = SocketAddr>) -> Self {
+        self.open_listeners = addrs.into_iter().collect();
+        self
+    }
+
+    pub fn build(self) -> Result<AppState, BuildError> {
+        let registry = self.registry.ok_or(BuildError::MissingRegistry)??;
+        let mut state = AppState::from_settings(registry, self.settings.build()?);
+        if let Some(resolver) = self.resolver {
+            state = state.with_resolver(resolver);
+        }
+        Ok(state.with_open_listeners(self.open_listeners))
+    }
+}
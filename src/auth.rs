@@ -0,0 +1,601 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+use actix_web::{
+    Error, HttpMessage, HttpRequest,
+    body::{EitherBody, MessageBody},
+    dev::{Payload, RequestHead, ServiceRequest, ServiceResponse},
+    error::PayloadError,
+    http::{Method, header},
+    middleware::Next,
+    web::{Bytes, Data},
+};
+use futures_util::{Stream, future::ready, stream};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::{
+    admin::constant_time_eq,
+    app_state::AppState,
+    errors::AppError,
+    jsonrpc::RpcMethod,
+    jwt, key_usage,
+    quota::{QuotaPeriod, RequestQuota},
+    rate_limit::{self, KeyRateLimit},
+    routing::best_pattern_match,
+    signature,
+    validators::Validator,
+};
+
+// Endpoints that `API_KEYS_PUBLIC` may leave open; `/` only for GET, since
+// POST `/` is the proxy itself.
+pub const OPTIONAL_PATHS: [&str; 3] = ["/health", "/", "/validators"];
+
+// Where a key reads its own usage; not counted against its quota.
+pub const USAGE_PATH: &str = "/usage";
+
+// One issued key. Only the SHA-256 of the key is kept, here and on disk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiKey {
+    pub id: String,
+    hash: [u8; 32],
+    pub revoked: bool,
+    // `API_KEY_RATE_LIMIT_RPS` applies when unset.
+    pub limit: Option<KeyRateLimit>,
+    pub scope: KeyScope,
+    pub quota: Option<RequestQuota>,
+}
+
+impl ApiKey {
+    // `sha256` is the hex digest of the key, as printed by `sha256sum`.
+    pub fn new(id: impl Into<String>, sha256: &str, revoked: bool) -> Option<Self> {
+        let id = id.into();
+        if id.is_empty() {
+            return None;
+        }
+        Some(Self {
+            id,
+            hash: decode_hash(sha256)?,
+            revoked,
+            limit: None,
+            scope: KeyScope::default(),
+            quota: None,
+        })
+    }
+
+    pub fn with_limit(mut self, limit: Option<KeyRateLimit>) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn with_scope(mut self, scope: KeyScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    // What `signature::sign` keys its HMAC with; never the stored hash.
+    pub(crate) fn signing_secret(&self, hmac_secret: &str) -> String {
+        signature::derive_secret(hmac_secret, &self.hash)
+    }
+
+    pub fn with_quota(mut self, quota: Option<RequestQuota>) -> Self {
+        self.quota = quota;
+        self
+    }
+}
+
+// What a key may reach. An empty list leaves that dimension open; names
+// and locations match case-insensitively, methods may be globs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyScope {
+    pub locations: Vec<String>,
+    pub validators: Vec<String>,
+    pub methods: Vec<String>,
+}
+
+impl KeyScope {
+    pub fn is_unrestricted(&self) -> bool {
+        self.locations.is_empty() && self.validators.is_empty() && self.methods.is_empty()
+    }
+
+    pub fn allows_method(&self, method: &str) -> bool {
+        self.methods.is_empty()
+            || best_pattern_match(&self.methods, String::as_str, method).is_some()
+    }
+
+    pub fn allows_validator(&self, validator: &Validator) -> bool {
+        listed(&self.validators, validator.name()) && listed(&self.locations, validator.location())
+    }
+
+    // Every method called must be within the scope, each element of a batch
+    // included.
+    pub fn check_method(&self, method: &RpcMethod) -> Result<(), AppError> {
+        let names: Vec<&str> = match method {
+            RpcMethod::Single(name) => vec![name],
+            RpcMethod::Batch(names) => names.iter().flatten().map(String::as_str).collect(),
+            RpcMethod::Invalid if !self.methods.is_empty() => {
+                return Err(violation("a request without a method"));
+            }
+            RpcMethod::Invalid => Vec::new(),
+        };
+        if let Some(name) = names.into_iter().find(|name| !self.allows_method(name)) {
+            return Err(violation(&format!("method '{name}'")));
+        }
+        Ok(())
+    }
+
+    // Before selection: the validator or location the client asked for.
+    pub fn check_selection(
+        &self,
+        validator: Option<&str>,
+        location: Option<&str>,
+    ) -> Result<(), AppError> {
+        if let Some(name) = validator.filter(|name| !name.is_empty())
+            && !listed(&self.validators, name)
+        {
+            return Err(violation(&format!("validator '{name}'")));
+        }
+        if let Some(location) = location.filter(|location| !location.is_empty())
+            && !listed(&self.locations, location)
+        {
+            return Err(violation(&format!("location '{location}'")));
+        }
+        Ok(())
+    }
+
+    // Within selection: the candidates left once the client's own
+    // constraints are applied, cut down to the ones the key may use.
+    pub fn narrow(&self, candidates: Vec<Validator>) -> Result<Vec<Validator>, AppError> {
+        let candidates: Vec<Validator> = candidates
+            .into_iter()
+            .filter(|validator| self.allows_validator(validator))
+            .collect();
+        if candidates.is_empty() {
+            return Err(violation("any of the selected validators"));
+        }
+        Ok(candidates)
+    }
+}
+
+fn listed(allowed: &[String], name: &str) -> bool {
+    allowed.is_empty() || allowed.iter().any(|item| item.eq_ignore_ascii_case(name))
+}
+
+fn violation(what: &str) -> AppError {
+    AppError::KeyScopeViolation(format!("API key may not use {what}"))
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ApiKeys {
+    // The first `inline` come from `API_KEYS`, the rest from `file`.
+    keys: Vec<ApiKey>,
+    inline: usize,
+    file: Option<PathBuf>,
+}
+
+impl ApiKeys {
+    pub fn new(keys: Vec<ApiKey>) -> Self {
+        Self {
+            inline: keys.len(),
+            keys,
+            file: None,
+        }
+    }
+
+    // Adds the keys of `path` (`API_KEYS_FILE`), replacing those of any
+    // file read before; `KeyStore::reload` reads it again.
+    pub fn with_file(&self, path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let mut keys = self.keys[..self.inline].to_vec();
+        keys.extend(Self::load_file(&path)?);
+        Ok(Self {
+            keys,
+            inline: self.inline,
+            file: Some(path),
+        })
+    }
+
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    pub fn keys(&self) -> &[ApiKey] {
+        &self.keys
+    }
+
+    // `id:sha256[:requests_per_second[:burst]]` entries, comma separated
+    // (`API_KEYS`).
+    pub fn parse_inline(raw: &str) -> Result<Vec<ApiKey>, String> {
+        raw.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| {
+                let fields: Vec<&str> = item.split(':').map(str::trim).collect();
+                match fields.as_slice() {
+                    [id, hash, limit @ ..] if limit.len() <= 2 => {
+                        parse_limit(limit.first(), limit.get(1)).and_then(|limit| {
+                            ApiKey::new(*id, hash, false).map(|key| key.with_limit(limit))
+                        })
+                    }
+                    _ => None,
+                }
+                .ok_or_else(|| item.to_string())
+            })
+            .collect()
+    }
+
+    // One `id,sha256[,revoked[,requests_per_second[,burst[,locations
+    // [,validators[,methods[,quota]]]]]]]` line per key (`API_KEYS_FILE`),
+    // the scope columns `;`-separated lists and the quota `N[/day|/month]`. Unused columns are left empty;
+    // blank lines and `#` comments are skipped.
+    pub fn parse_file(contents: &str) -> Result<Vec<ApiKey>, String> {
+        contents
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| (idx + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(number, line)| {
+                let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+                parse_line(&fields).map_err(|reason| match fields.as_slice() {
+                    [id, _, ..] if !id.is_empty() => format!("line {number} (key {id}): {reason}"),
+                    _ => format!("line {number}: {reason}"),
+                })
+            })
+            .collect()
+    }
+
+    // A JSON array of `{"id", "sha256", "enabled", "rps", "burst",
+    // "locations", "validators", "methods", "quota"}` objects, all but the
+    // first two optional.
+    pub fn parse_json(contents: &str) -> Result<Vec<ApiKey>, String> {
+        let entries: Vec<KeyEntry> =
+            serde_json::from_str(contents).map_err(|err| err.to_string())?;
+        entries
+            .into_iter()
+            .enumerate()
+            .map(|(idx, entry)| {
+                let limit = match entry.rps {
+                    Some(rps) => KeyRateLimit::new(rps, entry.burst).map(Some),
+                    None => entry.burst.is_none().then_some(None),
+                };
+                let quota = match &entry.quota {
+                    Some(quota) => quota.parse().ok().map(Some),
+                    None => Some(None),
+                };
+                let scope = KeyScope {
+                    locations: entry.locations,
+                    validators: entry.validators,
+                    methods: entry.methods,
+                };
+                limit
+                    .zip(quota)
+                    .and_then(|(limit, quota)| {
+                        ApiKey::new(entry.id.clone(), &entry.sha256, !entry.enabled)
+                            .map(|key| key.with_limit(limit).with_scope(scope).with_quota(quota))
+                    })
+                    .ok_or_else(|| format!("key {}: {}", idx + 1, entry.id))
+            })
+            .collect()
+    }
+
+    // JSON when the file starts with `[`, the line format otherwise.
+    pub fn load_file(path: &Path) -> Result<Vec<ApiKey>, String> {
+        let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        if contents.trim_start().starts_with('[') {
+            Self::parse_json(&contents)
+        } else {
+            Self::parse_file(&contents)
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ApiKey> {
+        self.keys.iter().find(|key| key.id == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    // Every stored hash is compared, so the time taken reveals neither
+    // which key matched nor how much of it did.
+    pub fn authenticate(&self, presented: &str) -> Result<&ApiKey, AppError> {
+        match self.find(presented) {
+            Some(key) if key.revoked => Err(AppError::Unauthorized(
+                "API key has been revoked".to_string(),
+            )),
+            Some(key) => Ok(key),
+            None => Err(AppError::Unauthorized("invalid API key".to_string())),
+        }
+    }
+
+    // The key `presented` hashes to, revoked or not.
+    pub fn find(&self, presented: &str) -> Option<&ApiKey> {
+        let hash = Sha256::digest(presented.as_bytes());
+        let mut found = None;
+        for key in &self.keys {
+            if constant_time_eq(&hash, &key.hash) {
+                found = Some(key);
+            }
+        }
+        found
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyEntry {
+    id: String,
+    sha256: String,
+    #[serde(default = "enabled")]
+    enabled: bool,
+    rps: Option<f64>,
+    burst: Option<u32>,
+    #[serde(default)]
+    locations: Vec<String>,
+    #[serde(default)]
+    validators: Vec<String>,
+    #[serde(default)]
+    methods: Vec<String>,
+    quota: Option<String>,
+}
+
+type BoxedPayloadStream = Pin<Box<dyn Stream<Item = Result<Bytes, PayloadError>>>>;
+
+fn enabled() -> bool {
+    true
+}
+
+// The id of the key a request was authenticated with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyId(pub String);
+
+pub fn key_id(req: &HttpRequest) -> Option<String> {
+    req.extensions().get::<KeyId>().map(|id| id.0.clone())
+}
+
+// Set alongside `KeyId` when the key is restricted.
+pub fn key_scope(req: &HttpRequest) -> Option<KeyScope> {
+    req.extensions().get::<KeyScope>().cloned()
+}
+
+// `Authorization: Bearer <key>`, else `?api_key=`. Any other Authorization
+// header is rejected rather than ignored.
+pub(crate) fn presented_key(head: &RequestHead) -> Result<Option<String>, AppError> {
+    if let Some(value) = head.headers().get(header::AUTHORIZATION) {
+        return value
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(|key| Some(key.to_string()))
+            .ok_or_else(|| {
+                AppError::Unauthorized(
+                    "malformed Authorization header; expected 'Bearer <key>'".to_string(),
+                )
+            });
+    }
+    Ok(
+        url::form_urlencoded::parse(head.uri.query().unwrap_or_default().as_bytes())
+            .find(|(name, value)| name == "api_key" && !value.is_empty())
+            .map(|(_, value)| value.into_owned()),
+    )
+}
+
+fn is_public(req: &ServiceRequest, public: &[String]) -> bool {
+    req.method() == Method::GET && public.iter().any(|path| path == req.path())
+}
+
+// Wrapped around the proxy, the convenience endpoints and the optional
+// ones; a no-op unless API keys or JWTs are configured. A key's rate limit
+// bucket is reported to `rate_limit::headers`, 429s included. Requests
+// within the rate limit then count against the key's quota.
+pub async fn require_key(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    if let Some(state) = req.app_data::<Data<AppState>>().cloned() {
+        let settings = state.settings();
+        if (settings.api_keys.is_some() || settings.jwt.is_some())
+            && !is_public(&req, &settings.api_keys_public)
+            && !state.is_open_listener(req.request().app_config().local_addr())
+        {
+            let identified = if signature::is_signed(req.headers()) {
+                identify_signed(&state, &mut req).await
+            } else {
+                identify(&state, req.head()).await
+            };
+            let key = match identified {
+                Ok(key) => key,
+                Err(err) => return Ok(req.error_response(err).map_into_right_body()),
+            };
+            if let Some(limit) = key.limit.or(settings.api_key_rate_limit) {
+                let checked = state.key_limiter().check(&key.id, limit);
+                rate_limit::observe(&req, checked);
+                if let Some(retry_after) = checked.retry_after {
+                    let response = req.error_response(AppError::RateLimited {
+                        message: "API key rate limit exceeded".to_string(),
+                        retry_after,
+                    });
+                    return Ok(response.map_into_right_body());
+                }
+            }
+            if req.path() != USAGE_PATH
+                && let Err(usage) = state.stats().consume_quota(&key.id, key.quota)
+            {
+                let period = match usage.period {
+                    QuotaPeriod::Day => "day",
+                    QuotaPeriod::Month => "month",
+                };
+                let response = req.error_response(AppError::QuotaExceeded {
+                    message: format!(
+                        "API key quota of {} requests per {period} exceeded",
+                        usage.limit.unwrap_or_default()
+                    ),
+                    retry_after: usage.retry_after(key_usage::now_secs()),
+                });
+                return Ok(response.map_into_right_body());
+            }
+            req.extensions_mut().insert(KeyId(key.id));
+            if !key.scope.is_unrestricted() {
+                req.extensions_mut().insert(key.scope);
+            }
+        }
+    }
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+// Who a request was made by: an issued key, or the client claim of a JWT,
+// which gets the defaults.
+struct Caller {
+    id: String,
+    limit: Option<KeyRateLimit>,
+    scope: KeyScope,
+    quota: Option<RequestQuota>,
+}
+
+// With both configured, JWT-shaped tokens are checked as JWTs and anything
+// else as an API key.
+async fn identify(state: &AppState, head: &RequestHead) -> Result<Caller, AppError> {
+    let settings = state.settings();
+    let presented = presented_key(head)?;
+    if let (Some(jwt), Some(token)) = (&settings.jwt, &presented)
+        && jwt::looks_like_jwt(token)
+    {
+        let id = jwt::authenticate(jwt, state.jwks(), token, key_usage::now_secs()).await?;
+        debug!(key_id = %id, "jwt accepted");
+        return Ok(Caller {
+            id,
+            limit: None,
+            scope: KeyScope::default(),
+            quota: None,
+        });
+    }
+    let Some(keys) = state.api_keys().current() else {
+        return Err(AppError::Unauthorized(match presented {
+            Some(_) => "malformed token; expected a JWT".to_string(),
+            None => "missing token; send 'Authorization: Bearer <token>'".to_string(),
+        }));
+    };
+    let presented = presented.ok_or_else(|| {
+        AppError::Unauthorized(
+            "missing API key; send 'Authorization: Bearer <key>' or '?api_key='".to_string(),
+        )
+    })?;
+    let key = keys.authenticate(&presented)?;
+    debug!(key_id = %key.id, "api key accepted");
+    Ok(Caller {
+        id: key.id.clone(),
+        limit: key.limit,
+        scope: key.scope.clone(),
+        quota: key.quota,
+    })
+}
+
+// HMAC-signed requests, checked against the same keys as bearer ones. The
+// body is read to be hashed, then put back for the handler.
+async fn identify_signed(state: &AppState, req: &mut ServiceRequest) -> Result<Caller, AppError> {
+    let Some(keys) = state.api_keys().current() else {
+        return Err(AppError::Unauthorized(
+            "signed requests need API keys to be configured".to_string(),
+        ));
+    };
+    let body = req
+        .extract::<Bytes>()
+        .await
+        .map_err(|err| AppError::BadRequest(err.to_string()))?;
+    let target = req
+        .uri()
+        .path_and_query()
+        .map_or_else(|| req.path().to_string(), ToString::to_string);
+    let key = signature::verify(
+        &keys,
+        state.settings(),
+        req.headers(),
+        req.method().as_str(),
+        &target,
+        &body,
+        key_usage::now_secs(),
+    )?;
+    let replay: BoxedPayloadStream = Box::pin(stream::once(ready(Ok(body))));
+    req.set_payload(Payload::from(replay));
+    debug!(key_id = %key.id, "signed request accepted");
+    Ok(Caller {
+        id: key.id.clone(),
+        limit: key.limit,
+        scope: key.scope.clone(),
+        quota: key.quota,
+    })
+}
+
+// The columns of one `API_KEYS_FILE` line. Errors say what is wrong
+// without repeating the line, which holds the key's hash.
+fn parse_line(fields: &[&str]) -> Result<ApiKey, &'static str> {
+    let [id, hash, rest @ ..] = fields else {
+        return Err("expected at least an id and a sha256");
+    };
+    if rest.len() > 7 {
+        return Err("too many columns");
+    }
+    if id.is_empty() {
+        return Err("missing key id");
+    }
+    let revoked = match rest.first() {
+        None | Some(&"") => false,
+        Some(&"revoked") => true,
+        Some(_) => return Err("the third column must be empty or 'revoked'"),
+    };
+    let limit = parse_limit(rest.get(1), rest.get(2)).ok_or("invalid rate limit")?;
+    let quota = match rest.get(6).filter(|quota| !quota.is_empty()) {
+        Some(quota) => Some(quota.parse().map_err(|_| "invalid quota")?),
+        None => None,
+    };
+    let scope = KeyScope {
+        locations: scope_list(rest.get(3)),
+        validators: scope_list(rest.get(4)),
+        methods: scope_list(rest.get(5)),
+    };
+    let key = ApiKey::new(*id, hash, revoked).ok_or("sha256 must be 64 hex digits")?;
+    Ok(key.with_limit(limit).with_scope(scope).with_quota(quota))
+}
+
+fn scope_list(raw: Option<&&str>) -> Vec<String> {
+    raw.map(|raw| {
+        raw.split(';')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(String::from)
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+// `Some(None)` when no rate is given; `None` when it is invalid.
+fn parse_limit(rps: Option<&&str>, burst: Option<&&str>) -> Option<Option<KeyRateLimit>> {
+    let Some(rps) = rps.filter(|rps| !rps.is_empty()) else {
+        return burst.is_none_or(|burst| burst.is_empty()).then_some(None);
+    };
+    let burst = match burst.filter(|burst| !burst.is_empty()) {
+        Some(burst) => Some(burst.parse().ok()?),
+        None => None,
+    };
+    KeyRateLimit::new(rps.parse().ok()?, burst).map(Some)
+}
+
+fn decode_hash(hex: &str) -> Option<[u8; 32]> {
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut hash = [0u8; 32];
+    for (idx, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[idx * 2..idx * 2 + 2], 16).ok()?;
+    }
+    Some(hash)
+}
@@ -0,0 +1,60 @@
+use thiserror::Error;
+
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Base58Error {
+    #[error("is empty")]
+    Empty,
+    #[error("has non-base58 character '{character}' at position {position}")]
+    InvalidCharacter { character: char, position: usize },
+    #[error("decodes to {actual} bytes, expected {expected}")]
+    WrongLength { expected: usize, actual: usize },
+}
+
+// Bitcoin-alphabet decoding as used for Solana pubkeys and signatures;
+// each leading '1' stands for a zero byte.
+pub fn decode(input: &str) -> Result<Vec<u8>, Base58Error> {
+    if input.is_empty() {
+        return Err(Base58Error::Empty);
+    }
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(input.len());
+    for (position, character) in input.chars().enumerate() {
+        let digit = ALPHABET
+            .iter()
+            .position(|symbol| char::from(*symbol) == character)
+            .ok_or(Base58Error::InvalidCharacter {
+                character,
+                position,
+            })?;
+
+        // `bytes` holds the value so far, little-endian; multiply by 58 and add.
+        let mut carry = digit as u32;
+        for byte in bytes.iter_mut() {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    let zeros = input.bytes().take_while(|byte| *byte == b'1').count();
+    bytes.extend(std::iter::repeat_n(0, zeros));
+    bytes.reverse();
+    Ok(bytes)
+}
+
+pub fn decode_exact(input: &str, expected: usize) -> Result<Vec<u8>, Base58Error> {
+    let bytes = decode(input)?;
+    if bytes.len() != expected {
+        return Err(Base58Error::WrongLength {
+            expected,
+            actual: bytes.len(),
+        });
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,113 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+use actix_web::web::Bytes;
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BatchIdCheck {
+    #[default]
+    Off,
+    // Mismatches are logged and the response is relayed as received.
+    Log,
+    // Mismatches fail the request.
+    Strict,
+}
+
+impl FromStr for BatchIdCheck {
+    type Err = ();
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "log" => Ok(Self::Log),
+            "strict" => Ok(Self::Strict),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BatchIdError {
+    #[error("response is not a batch")]
+    NotABatch,
+    #[error("batch ids do not match the request: {0}")]
+    Mismatch(IdMismatch),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdMismatch {
+    pub missing: Vec<Value>,
+    pub unexpected: Vec<Value>,
+}
+
+impl fmt::Display for IdMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |ids: &[Value]| {
+            ids.iter()
+                .map(Value::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        write!(
+            f,
+            "missing [{}], unexpected [{}]",
+            list(&self.missing),
+            list(&self.unexpected)
+        )
+    }
+}
+
+// The ids a batch expects answers for, in request order. Notifications (no
+// `id`) get no answer; entries that are not objects are answered with a null id.
+pub fn request_ids(body: &[u8]) -> Option<Vec<Value>> {
+    let Value::Array(entries) = serde_json::from_slice::<Value>(body).ok()? else {
+        return None;
+    };
+    Some(
+        entries
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Value::Object(mut request) => request.remove("id"),
+                _ => Some(Value::Null),
+            })
+            .collect(),
+    )
+}
+
+// Checks that `response` answers exactly `ids` and returns it in the same
+// order. Ids are compared by their JSON text, so `1` and `"1"` differ.
+pub fn reorder(ids: &[Value], response: &[u8]) -> Result<Bytes, BatchIdError> {
+    let Ok(Value::Array(entries)) = serde_json::from_slice::<Value>(response) else {
+        return Err(BatchIdError::NotABatch);
+    };
+
+    let mut by_id: HashMap<String, VecDeque<Value>> = HashMap::new();
+    for entry in entries {
+        let id = entry.get("id").cloned().unwrap_or(Value::Null);
+        by_id.entry(id.to_string()).or_default().push_back(entry);
+    }
+
+    let mut mismatch = IdMismatch::default();
+    let mut ordered = Vec::with_capacity(ids.len());
+    for id in ids {
+        match by_id.get_mut(&id.to_string()).and_then(VecDeque::pop_front) {
+            Some(entry) => ordered.push(entry),
+            None => mismatch.missing.push(id.clone()),
+        }
+    }
+    for entry in by_id.into_values().flatten() {
+        mismatch
+            .unexpected
+            .push(entry.get("id").cloned().unwrap_or(Value::Null));
+    }
+
+    if !mismatch.missing.is_empty() || !mismatch.unexpected.is_empty() {
+        return Err(BatchIdError::Mismatch(mismatch));
+    }
+    serde_json::to_vec(&ordered)
+        .map(Bytes::from)
+        .map_err(|_| BatchIdError::NotABatch)
+}
@@ -0,0 +1,230 @@
+use std::str::FromStr;
+
+use serde_json::Value;
+use tracing::{Level, debug, enabled};
+
+use crate::app_state::AppState;
+use crate::jsonrpc::RpcMethod;
+use crate::proxy::ProxyRequest;
+
+// Log target of body events (`RUST_LOG=body_log=debug`).
+pub const TARGET: &str = "body_log";
+
+pub const REDACTED: &str = "[REDACTED]";
+
+// Always applied, whatever `LOG_BODY_REDACT` adds.
+pub const DEFAULT_RULES: &[&str] = &[
+    "sendTransaction:params[0]",
+    "simulateTransaction:params[0]",
+    "authorization",
+];
+
+pub fn default_rules() -> Vec<RedactRule> {
+    DEFAULT_RULES
+        .iter()
+        .map(|rule| rule.parse().expect("valid default redaction rule"))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+// `[method:]path`. A bare name (`authorization`) matches that key at any
+// depth, case-insensitively; a path (`params[0]`, `result.value.data`)
+// starts at the top of each JSON-RPC object. With a method, the rule only
+// applies to that method's request and response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedactRule {
+    method: Option<String>,
+    path: Vec<Segment>,
+}
+
+impl FromStr for RedactRule {
+    type Err = ();
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let (method, path) = match raw.split_once(':') {
+            Some((method, path)) if !method.trim().is_empty() => {
+                (Some(method.trim().to_string()), path.trim())
+            }
+            Some(_) => return Err(()),
+            None => (None, raw.trim()),
+        };
+        Ok(Self {
+            method,
+            path: parse_path(path).ok_or(())?,
+        })
+    }
+}
+
+impl RedactRule {
+    fn anywhere(&self) -> Option<&str> {
+        match self.path.as_slice() {
+            [Segment::Key(name)] if !name.is_empty() => Some(name),
+            _ => None,
+        }
+    }
+
+    fn applies_to(&self, method: Option<&str>) -> bool {
+        self.method
+            .as_deref()
+            .is_none_or(|wanted| method == Some(wanted))
+    }
+}
+
+fn parse_path(raw: &str) -> Option<Vec<Segment>> {
+    let mut segments = Vec::new();
+    for part in raw.split('.') {
+        let (key, mut rest) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if key.contains(']') {
+            return None;
+        }
+        if !key.is_empty() {
+            segments.push(Segment::Key(key.to_string()));
+        }
+        while !rest.is_empty() {
+            let end = rest.find(']')?;
+            segments.push(Segment::Index(rest.get(1..end)?.parse().ok()?));
+            rest = &rest[end + 1..];
+            if !rest.is_empty() && !rest.starts_with('[') {
+                return None;
+            }
+        }
+        if key.is_empty() && !part.starts_with('[') {
+            return None;
+        }
+    }
+    (!segments.is_empty()).then_some(segments)
+}
+
+// Parsed bodies are redacted element by element for batches, then cut to
+// `max_bytes`; bodies that are not JSON are only cut.
+pub fn render(body: &[u8], rules: &[RedactRule], max_bytes: usize) -> String {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(mut value) => {
+            match &mut value {
+                Value::Array(items) => {
+                    for item in items {
+                        redact(item, rules);
+                    }
+                }
+                item => redact(item, rules),
+            }
+            truncate(&value.to_string(), max_bytes)
+        }
+        Err(_) => truncate(&String::from_utf8_lossy(body), max_bytes),
+    }
+}
+
+// Like `render`, with every object treated as belonging to `method`.
+pub fn render_for_method(
+    body: &[u8],
+    method: &str,
+    rules: &[RedactRule],
+    max_bytes: usize,
+) -> String {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(mut value) => {
+            redact_for(&mut value, Some(method), rules);
+            truncate(&value.to_string(), max_bytes)
+        }
+        Err(_) => truncate(&String::from_utf8_lossy(body), max_bytes),
+    }
+}
+
+// Redacts one JSON-RPC object (request or response).
+fn redact(value: &mut Value, rules: &[RedactRule]) {
+    let method = value
+        .get("method")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    redact_for(value, method.as_deref(), rules);
+}
+
+fn redact_for(value: &mut Value, method: Option<&str>, rules: &[RedactRule]) {
+    for rule in rules.iter().filter(|rule| rule.applies_to(method)) {
+        match rule.anywhere() {
+            Some(name) => redact_key(value, name),
+            None => {
+                if let Some(target) = lookup(value, &rule.path) {
+                    *target = Value::from(REDACTED);
+                }
+            }
+        }
+    }
+}
+
+fn lookup<'a>(value: &'a mut Value, path: &[Segment]) -> Option<&'a mut Value> {
+    path.iter().try_fold(value, |value, segment| match segment {
+        Segment::Key(key) => value.get_mut(key.as_str()),
+        Segment::Index(idx) => value.get_mut(*idx),
+    })
+}
+
+fn redact_key(value: &mut Value, name: &str) {
+    match value {
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                if key.eq_ignore_ascii_case(name) {
+                    *item = Value::from(REDACTED);
+                } else {
+                    redact_key(item, name);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                redact_key(item, name);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn truncate(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...[truncated {} bytes]", &text[..end], text.len() - end)
+}
+
+pub(crate) fn request(state: &AppState, request: &ProxyRequest<'_>) {
+    log(state, request, "request", &request.body);
+}
+
+pub(crate) fn response(state: &AppState, request: &ProxyRequest<'_>, body: &[u8]) {
+    log(state, request, "response", body);
+}
+
+fn log(state: &AppState, request: &ProxyRequest<'_>, direction: &str, body: &[u8]) {
+    let settings = state.settings();
+    if !settings.log_bodies || !enabled!(target: TARGET, Level::DEBUG) {
+        return;
+    }
+    let rendered = match &request.rpc_method {
+        // A response carries no method; redact it as the request's.
+        Some(RpcMethod::Single(method)) if direction == "response" => render_for_method(
+            body,
+            method,
+            &settings.log_body_redact,
+            settings.log_body_max_bytes,
+        ),
+        _ => render(body, &settings.log_body_redact, settings.log_body_max_bytes),
+    };
+    debug!(
+        target: TARGET,
+        direction,
+        request_id = request.request_id.as_ref().map(tracing::field::display),
+        rpc_method = request.rpc_method.as_ref().map(tracing::field::display),
+        bytes = body.len(),
+        body = %rendered,
+        "body"
+    );
+}
@@ -0,0 +1,276 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::coalesce::BufferedResponse;
+use crate::config::Settings;
+use crate::jsonrpc::{self, RpcMethod};
+
+pub const X_CACHE: &str = "x-cache";
+
+// `getRecentBlockhash` is deprecated but still widely called.
+const BLOCKHASH_METHODS: &[&str] = &["getLatestBlockhash", "getRecentBlockhash"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey {
+    key: String,
+    ttl: Duration,
+    slot_bound: bool,
+}
+
+impl CacheKey {
+    pub fn as_str(&self) -> &str {
+        &self.key
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+}
+
+struct CacheEntry {
+    expires_at: Instant,
+    response: BufferedResponse,
+    context_slot: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheCounters {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+pub struct ResponseCache {
+    enabled: bool,
+    immutable_methods: HashSet<String>,
+    immutable_ttl: Duration,
+    short_methods: HashSet<String>,
+    short_ttl: Duration,
+    blockhash_ttl: Duration,
+    blockhash_max_slot_lag: u64,
+    latest_slot: AtomicU64,
+    max_entries: usize,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            enabled: settings.response_cache,
+            immutable_methods: settings.cache_immutable_methods.iter().cloned().collect(),
+            immutable_ttl: settings.cache_immutable_ttl,
+            short_methods: settings.cache_short_methods.iter().cloned().collect(),
+            short_ttl: settings.cache_short_ttl,
+            blockhash_ttl: settings.cache_blockhash_ttl,
+            blockhash_max_slot_lag: settings.cache_blockhash_max_slot_lag,
+            latest_slot: AtomicU64::new(0),
+            max_entries: settings.cache_max_entries,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    // Results can differ between validators (e.g. `getVersion`), so a pinned
+    // `server` gets its own entry. Poll-heavy methods are also scoped per
+    // location so region-pinned clients never see another region's slot.
+    pub fn key(
+        &self,
+        method: &RpcMethod,
+        server: Option<&str>,
+        location: Option<&str>,
+        body: &[u8],
+    ) -> Option<CacheKey> {
+        if !self.enabled || self.max_entries == 0 {
+            return None;
+        }
+
+        let method = method.name()?;
+        let normalize = |value: Option<&str>| {
+            value
+                .map(|value| value.trim().to_ascii_lowercase())
+                .unwrap_or_default()
+        };
+        let server = normalize(server);
+
+        if self.immutable_methods.contains(method) {
+            let params = request_params(body)?;
+            return Some(CacheKey {
+                key: format!("{server}|{method}|{params}"),
+                ttl: self.immutable_ttl,
+                slot_bound: false,
+            });
+        }
+
+        if self.short_methods.contains(method) && has_default_params(&request_params(body)?) {
+            let location = normalize(location);
+            return Some(CacheKey {
+                key: format!("{server}|{location}|{method}|[]"),
+                ttl: self.short_ttl,
+                slot_bound: false,
+            });
+        }
+
+        if BLOCKHASH_METHODS.contains(&method) {
+            let commitment = blockhash_commitment(&request_params(body)?)?;
+            return Some(CacheKey {
+                key: format!("{server}|{method}|{commitment}"),
+                ttl: self.blockhash_ttl,
+                slot_bound: true,
+            });
+        }
+
+        None
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<BufferedResponse> {
+        let mut entries = self.entries.lock().expect("response cache lock poisoned");
+        let cached = match entries.get(key.as_str()) {
+            Some(entry) if entry.expires_at > Instant::now() && !self.is_behind(entry) => {
+                Some(entry.response.clone())
+            }
+            Some(_) => {
+                entries.remove(key.as_str());
+                None
+            }
+            None => None,
+        };
+
+        let counter = if cached.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        cached
+    }
+
+    // A blockhash is dropped once its context slot trails the newest slot seen
+    // in any upstream response, so it is refreshed well before it expires.
+    fn is_behind(&self, entry: &CacheEntry) -> bool {
+        entry.context_slot.is_some_and(|slot| {
+            self.latest_slot
+                .load(Ordering::Relaxed)
+                .saturating_sub(slot)
+                > self.blockhash_max_slot_lag
+        })
+    }
+
+    pub fn observe_slot(&self, method: &RpcMethod, body: &[u8]) {
+        if !self.enabled {
+            return;
+        }
+        if let Some(slot) = jsonrpc::observed_slot(method, body) {
+            self.latest_slot.fetch_max(slot, Ordering::Relaxed);
+        }
+    }
+
+    pub fn counters(&self) -> CacheCounters {
+        CacheCounters {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    // Only successful JSON-RPC results are kept; errors are always re-fetched.
+    pub fn insert(&self, key: CacheKey, response: BufferedResponse) {
+        if !response.status.is_success() || !jsonrpc::is_result(&response.body) {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut entries = self.entries.lock().expect("response cache lock poisoned");
+        if entries.len() >= self.max_entries && !entries.contains_key(key.as_str()) {
+            entries.retain(|_, entry| entry.expires_at > now);
+        }
+        if entries.len() >= self.max_entries
+            && !entries.contains_key(key.as_str())
+            && let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires_at)
+                .map(|(key, _)| key.clone())
+        {
+            entries.remove(&oldest);
+        }
+
+        let context_slot = if key.slot_bound {
+            let Some(slot) = jsonrpc::context_slot(&response.body) else {
+                return;
+            };
+            self.latest_slot.fetch_max(slot, Ordering::Relaxed);
+            Some(slot)
+        } else {
+            None
+        };
+
+        entries.insert(
+            key.key,
+            CacheEntry {
+                expires_at: now + key.ttl,
+                response,
+                context_slot,
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .expect("response cache lock poisoned")
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Missing and empty params are equivalent for caching purposes.
+// Only the default commitment may be cached; anything else (a different
+// commitment, `minContextSlot`, ...) changes what the validator answers.
+fn has_default_params(params: &Value) -> bool {
+    match params {
+        Value::Array(items) => match items.as_slice() {
+            [] => true,
+            [Value::Object(config)] => config
+                .iter()
+                .all(|(name, value)| name == "commitment" && value.as_str() == Some("finalized")),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn blockhash_commitment(params: &Value) -> Option<String> {
+    let Value::Array(items) = params else {
+        return None;
+    };
+
+    match items.as_slice() {
+        [] => Some("finalized".to_string()),
+        [Value::Object(config)] if config.keys().all(|name| name == "commitment") => {
+            match config.get("commitment") {
+                None => Some("finalized".to_string()),
+                Some(value) => value.as_str().map(str::to_string),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn request_params(body: &[u8]) -> Option<Value> {
+    let Value::Object(mut request) = serde_json::from_slice::<Value>(body).ok()? else {
+        return None;
+    };
+
+    match request.remove("params") {
+        None | Some(Value::Null) => Some(Value::Array(Vec::new())),
+        Some(params) => Some(params),
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+// A flag standing for one setting: `--bind 0.0.0.0:8080` is
+// `BIND_ADDRESS=0.0.0.0:8080`, ahead of the environment and the config file.
+struct SettingFlag {
+    flag: &'static str,
+    var: &'static str,
+    value: &'static str,
+    help: &'static str,
+}
+
+const SETTING_FLAGS: &[SettingFlag] = &[
+    SettingFlag {
+        flag: "--bind",
+        var: "BIND_ADDRESS",
+        value: "ADDR",
+        help: "Address to listen on",
+    },
+    SettingFlag {
+        flag: "--validators",
+        var: "VALIDATORS_CSV",
+        value: "PATH",
+        help: "Validator registry CSV file",
+    },
+    SettingFlag {
+        flag: "--validators-inline",
+        var: "VALIDATORS_INLINE",
+        value: "CSV",
+        help: "Validator registry given as CSV text, header included",
+    },
+    SettingFlag {
+        flag: "--timeout-ms",
+        var: "UPSTREAM_TIMEOUT_MS",
+        value: "MS",
+        help: "Timeout for each upstream request",
+    },
+    SettingFlag {
+        flag: "--max-retries",
+        var: "MAX_RETRIES",
+        value: "N",
+        help: "Other validators tried after a failed attempt",
+    },
+    SettingFlag {
+        flag: "--log-format",
+        var: "LOG_FORMAT",
+        value: "FORMAT",
+        help: "Log output: compact, pretty or json",
+    },
+];
+
+// Flags `probe` takes on top of the server's.
+const PROBE_FLAGS: &[SettingFlag] = &[
+    SettingFlag {
+        flag: "--node-timeout-ms",
+        var: "FLEET_NODE_TIMEOUT_MS",
+        value: "MS",
+        help: "Timeout for each validator",
+    },
+    SettingFlag {
+        flag: "--concurrency",
+        var: "FLEET_CONCURRENCY",
+        value: "N",
+        help: "Validators probed at once",
+    },
+];
+
+// Flags that cannot be given together.
+const CONFLICTS: &[(&str, &str)] = &[("--validators", "--validators-inline")];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CliError {
+    #[error("unknown argument '{0}'; see --help")]
+    UnknownArgument(String),
+    #[error("{0} needs a value")]
+    MissingValue(String),
+    #[error("{0} given more than once")]
+    Repeated(String),
+    #[error("{0} and {1} cannot be used together")]
+    Conflict(String, String),
+    #[error("invalid value '{value}' for {flag}")]
+    InvalidValue { flag: String, value: String },
+}
+
+// A subcommand run instead of the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    // Checks a registry file and exits; `file` falls back to
+    // `VALIDATORS_CSV`, then the default path.
+    Validate {
+        file: Option<PathBuf>,
+        json: bool,
+    },
+    // Health-checks every validator in the configured registry and exits;
+    // fails when fewer than `min_healthy` are ok, all of them by default.
+    Probe {
+        json: bool,
+        min_healthy: Option<usize>,
+    },
+    // Prints the resolved settings, each with where it came from, and
+    // exits; JSON unless `toml`.
+    PrintConfig {
+        toml: bool,
+    },
+}
+
+// The command line, parsed but not yet merged into `Settings`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Args {
+    // Setting values by environment variable name.
+    pub settings: BTreeMap<String, String>,
+    pub config: Option<PathBuf>,
+    // Read instead of `./.env`.
+    pub env_file: Option<PathBuf>,
+    pub command: Option<Command>,
+    pub help: bool,
+    pub version: bool,
+}
+
+impl Args {
+    // Everything after the program name. Flags take their value as the
+    // next argument or after `=`.
+    pub fn parse<I, S>(args: I) -> Result<Self, CliError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut args = args.into_iter().map(Into::into).peekable();
+        let (probe, print_config) = match args.peek().map(String::as_str) {
+            Some("validate") => {
+                args.next();
+                return Self::parse_validate(args);
+            }
+            Some("probe") => {
+                args.next();
+                (true, false)
+            }
+            Some("print-config") => {
+                args.next();
+                (false, true)
+            }
+            _ => (false, false),
+        };
+        let flags = SETTING_FLAGS
+            .iter()
+            .chain(if probe { PROBE_FLAGS } else { &[] });
+
+        let mut parsed = Self::default();
+        let mut seen = Vec::new();
+        let (mut json, mut min_healthy, mut toml) = (false, None, false);
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = |flag: &str| {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| CliError::MissingValue(flag.to_string()))
+            };
+            if flag != "--set" {
+                if seen.contains(&flag) {
+                    return Err(CliError::Repeated(flag));
+                }
+                seen.push(flag.clone());
+            }
+            match flag.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "-V" | "--version" => parsed.version = true,
+                "--strict-config" => {
+                    parsed
+                        .settings
+                        .insert("CONFIG_STRICT".to_string(), "true".to_string());
+                }
+                "--config" => parsed.config = Some(PathBuf::from(value(&flag)?)),
+                "--env-file" => parsed.env_file = Some(PathBuf::from(value(&flag)?)),
+                "--json" if probe => json = true,
+                "--toml" if print_config => toml = true,
+                "--min-healthy" if probe => {
+                    let raw = value(&flag)?;
+                    let count = raw.parse().map_err(|_| CliError::InvalidValue {
+                        flag: flag.clone(),
+                        value: raw,
+                    })?;
+                    min_healthy = Some(count);
+                }
+                "--set" => {
+                    let raw = value(&flag)?;
+                    let invalid = || CliError::InvalidValue {
+                        flag: flag.clone(),
+                        value: raw.clone(),
+                    };
+                    let (name, setting) = raw.split_once('=').ok_or_else(invalid)?;
+                    let name = name.trim().to_ascii_uppercase();
+                    if name.is_empty() || name == "CONFIG_FILE" {
+                        return Err(invalid());
+                    }
+                    if parsed
+                        .settings
+                        .insert(name.clone(), setting.to_string())
+                        .is_some()
+                    {
+                        return Err(CliError::Repeated(name));
+                    }
+                }
+                _ => {
+                    let Some(setting) = flags.clone().find(|known| known.flag == flag) else {
+                        return Err(CliError::UnknownArgument(arg));
+                    };
+                    let raw = value(&flag)?;
+                    // The registry is only read from disk.
+                    if setting.var == "VALIDATORS_CSV" && raw.contains("://") {
+                        return Err(CliError::InvalidValue { flag, value: raw });
+                    }
+                    if parsed
+                        .settings
+                        .insert(setting.var.to_string(), raw)
+                        .is_some()
+                    {
+                        return Err(CliError::Repeated(setting.var.to_string()));
+                    }
+                }
+            }
+        }
+        if let Some((first, second)) = CONFLICTS.iter().find(|(first, second)| {
+            seen.iter().any(|flag| flag == first) && seen.iter().any(|flag| flag == second)
+        }) {
+            return Err(CliError::Conflict(first.to_string(), second.to_string()));
+        }
+        if probe {
+            parsed.command = Some(Command::Probe { json, min_healthy });
+        }
+        if print_config {
+            parsed.command = Some(Command::PrintConfig { toml });
+        }
+        Ok(parsed)
+    }
+
+    // `validate` takes only its own flags: server settings play no part.
+    fn parse_validate(mut args: impl Iterator<Item = String>) -> Result<Self, CliError> {
+        let mut parsed = Self::default();
+        let mut file = None;
+        let mut json = false;
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            match flag.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "--json" if !json => json = true,
+                "--file" if file.is_none() => {
+                    let value = inline
+                        .or_else(|| args.next())
+                        .ok_or_else(|| CliError::MissingValue(flag.clone()))?;
+                    file = Some(PathBuf::from(value));
+                }
+                "--json" | "--file" => return Err(CliError::Repeated(flag)),
+                _ => return Err(CliError::UnknownArgument(arg)),
+            }
+        }
+        parsed.command = Some(Command::Validate { file, json });
+        Ok(parsed)
+    }
+}
+
+// Built from the same table the parser reads, so it lists every flag.
+pub fn help() -> String {
+    let mut help = format!(
+        "the-solana-api {}\n\n\
+         Usage: the-solana-api [OPTIONS]\n       \
+         the-solana-api validate [--file <PATH>] [--json]\n       \
+         the-solana-api probe [OPTIONS] [--json] [--min-healthy <N>]\n       \
+         the-solana-api print-config [OPTIONS] [--toml]\n\n\
+         Every setting is taken from, in order: these flags, its environment\n\
+         variable, the config file, its default.\n\nOptions:\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    let mut line = |flag: String, text: &str| {
+        let _ = writeln!(help, "  {flag:<28} {text}");
+    };
+    line(
+        "--config <FILE>".to_string(),
+        "TOML config file [env: CONFIG_FILE]",
+    );
+    line(
+        "--env-file <FILE>".to_string(),
+        "NAME=VALUE lines under the environment [default: .env]",
+    );
+    line(
+        "--strict-config".to_string(),
+        "Fail on unknown config file keys [env: CONFIG_STRICT]",
+    );
+    for setting in SETTING_FLAGS {
+        line(
+            format!("{} <{}>", setting.flag, setting.value),
+            &format!("{} [env: {}]", setting.help, setting.var),
+        );
+    }
+    line(
+        "--set <NAME=VALUE>".to_string(),
+        "Any setting by its environment variable name; repeatable",
+    );
+    line("-h, --help".to_string(), "Print this help");
+    line("-V, --version".to_string(), "Print the version");
+    help.push_str("\nCommands:\n");
+    let mut line = |flag: &str, text: &str| {
+        let _ = writeln!(help, "  {flag:<28} {text}");
+    };
+    line(
+        "validate",
+        "Check a registry file offline and exit non-zero on errors",
+    );
+    line(
+        "  --file <PATH>",
+        "Registry CSV file [default: VALIDATORS_CSV or config/validators.csv]",
+    );
+    line("  --json", "Print the report as JSON");
+    line(
+        "probe",
+        "Health-check every validator, taking the options above, and exit",
+    );
+    for setting in PROBE_FLAGS {
+        line(
+            &format!("  {} <{}>", setting.flag, setting.value),
+            &format!("{} [env: {}]", setting.help, setting.var),
+        );
+    }
+    line(
+        "  --min-healthy <N>",
+        "Validators that must be ok [default: all]",
+    );
+    line("  --json", "Print the report as JSON");
+    line(
+        "print-config",
+        "Print every setting, taking the options above, with its source",
+    );
+    line("  --toml", "Print TOML instead of JSON");
+    help
+}
@@ -0,0 +1,204 @@
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
+
+use actix_web::{
+    Error, HttpMessage,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{Method, header::HeaderMap},
+    middleware::Next,
+    web::Data,
+};
+
+use crate::{
+    app_state::AppState,
+    auth::KeyId,
+    errors::AppError,
+    rate_limit::{self, KeyRateLimit, KeyRateLimiter},
+};
+
+// Kept out of the global ceiling so monitoring keeps working under load;
+// `MONITORING_RATE_LIMIT_RPS` gives them a per-client budget of their own.
+pub const MONITORING_PATHS: [&str; 2] = ["/health", "/validators"];
+
+const FORWARDED_FOR: &str = "x-forwarded-for";
+
+// An address or a CIDR block, as listed in `TRUSTED_PROXIES`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                mask(u128::from(net.to_bits()), self.prefix, 32)
+                    == mask(u128::from(ip.to_bits()), self.prefix, 32)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                mask(net.to_bits(), self.prefix, 128) == mask(ip.to_bits(), self.prefix, 128)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = ();
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match raw.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (raw.trim(), None),
+        };
+        let addr = addr.parse::<IpAddr>().map_err(|_| ())?.to_canonical();
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().map_err(|_| ())?,
+            None => bits,
+        };
+        if prefix > bits {
+            return Err(());
+        }
+        Ok(Self { addr, prefix })
+    }
+}
+
+fn mask(bits: u128, prefix: u8, width: u8) -> u128 {
+    match prefix {
+        0 => 0,
+        prefix => bits >> (width - prefix),
+    }
+}
+
+// The peer address, or, when the peer is a trusted proxy, the nearest
+// `X-Forwarded-For` entry that is not one. Entries further left are the
+// client's own claim and are never believed.
+pub fn client_ip(
+    peer: Option<SocketAddr>,
+    headers: &HeaderMap,
+    trusted: &[IpRange],
+) -> Option<IpAddr> {
+    let mut ip = peer?.ip().to_canonical();
+    if trusted.is_empty() {
+        return Some(ip);
+    }
+    let forwarded = headers
+        .get_all(FORWARDED_FOR)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    for hop in forwarded.into_iter().rev() {
+        if !trusted.iter().any(|range| range.contains(ip)) {
+            break;
+        }
+        match hop.parse::<IpAddr>() {
+            Ok(hop) => ip = hop.to_canonical(),
+            Err(_) => break,
+        }
+    }
+    Some(ip)
+}
+
+// IPv4-mapped addresses count as IPv4, and IPv6 clients are grouped by /64
+// since that is what a single subscriber is usually handed.
+pub fn normalize(ip: IpAddr) -> IpAddr {
+    match ip.to_canonical() {
+        IpAddr::V6(ip) => IpAddr::V6(Ipv6Addr::from_bits(ip.to_bits() & (!0u128 << 64))),
+        ip => ip,
+    }
+}
+
+// The key used for per-client limits.
+pub fn client_key(peer: Option<SocketAddr>, headers: &HeaderMap, trusted: &[IpRange]) -> String {
+    client_ip(peer, headers, trusted)
+        .map(|ip| normalize(ip).to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+// Buckets for anonymous traffic: one for the whole proxy, one per client
+// and one per client for the monitoring endpoints.
+pub struct ClientLimiters {
+    global: KeyRateLimiter,
+    clients: KeyRateLimiter,
+    monitoring: KeyRateLimiter,
+}
+
+impl ClientLimiters {
+    const GLOBAL: &str = "global";
+
+    pub fn new(max_clients: usize) -> Self {
+        Self {
+            global: KeyRateLimiter::new(1),
+            clients: KeyRateLimiter::new(max_clients),
+            monitoring: KeyRateLimiter::new(max_clients),
+        }
+    }
+
+    pub fn tracked_clients(&self) -> usize {
+        self.clients.tracked_keys()
+    }
+}
+
+fn is_monitoring(req: &ServiceRequest) -> bool {
+    req.method() == Method::GET && MONITORING_PATHS.contains(&req.path())
+}
+
+// Wrapped inside `auth::require_key`, so requests made with a key skip the
+// per-client bucket (their key has one) but still count globally.
+pub async fn limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    if let Some(state) = req.app_data::<Data<AppState>>().cloned() {
+        let settings = state.settings();
+        let limiters = state.client_limiters();
+        let client = || client_key(req.peer_addr(), req.headers(), &settings.trusted_proxies);
+        let mut checks: Vec<(&KeyRateLimiter, String, KeyRateLimit, bool)> = Vec::new();
+        if is_monitoring(&req) {
+            if let Some(limit) = settings.monitoring_rate_limit {
+                checks.push((&limiters.monitoring, client(), limit, true));
+            }
+        } else {
+            // The client's own bucket goes first, so one client over its
+            // budget does not use up the global one for everybody else.
+            if let Some(limit) = settings.client_rate_limit
+                && req.extensions().get::<KeyId>().is_none()
+            {
+                checks.push((&limiters.clients, client(), limit, true));
+            }
+            if let Some(limit) = settings.global_rate_limit {
+                checks.push((
+                    &limiters.global,
+                    ClientLimiters::GLOBAL.to_string(),
+                    limit,
+                    false,
+                ));
+            }
+        }
+        for (limiter, key, limit, per_client) in checks {
+            let quota = limiter.check(&key, limit);
+            rate_limit::observe(&req, quota);
+            if let Some(retry_after) = quota.retry_after {
+                return Ok(reject(req, retry_after, per_client).map_into_right_body());
+            }
+        }
+    }
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+fn reject(req: ServiceRequest, retry_after: Duration, per_client: bool) -> ServiceResponse {
+    let message = if per_client {
+        "client rate limit exceeded"
+    } else {
+        "proxy is over its global rate limit"
+    };
+    req.error_response(AppError::RateLimited {
+        message: message.to_string(),
+        retry_after,
+    })
+}
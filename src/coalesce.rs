@@ -0,0 +1,135 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::http::{StatusCode, header::HeaderMap};
+use actix_web::web::Bytes;
+use serde_json::Value;
+use tokio::sync::watch;
+
+use crate::errors::AppError;
+
+pub type SharedResult = Result<BufferedResponse, AppError>;
+
+#[derive(Debug, Clone)]
+pub struct BufferedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+struct Inflight {
+    started: Instant,
+    result: watch::Receiver<Option<SharedResult>>,
+}
+
+pub struct Coalescer {
+    enabled: bool,
+    methods: HashSet<String>,
+    max_age: Duration,
+    inflight: Arc<Mutex<HashMap<String, Inflight>>>,
+}
+
+pub enum Role {
+    Leader(LeaderGuard),
+    Follower(watch::Receiver<Option<SharedResult>>),
+}
+
+// Removes the in-flight entry when dropped, so a failed or cancelled leader
+// never leaves followers attached to a result that will not arrive.
+pub struct LeaderGuard {
+    key: String,
+    sender: watch::Sender<Option<SharedResult>>,
+    inflight: Arc<Mutex<HashMap<String, Inflight>>>,
+}
+
+impl LeaderGuard {
+    pub fn complete(self, result: SharedResult) {
+        let _ = self.sender.send(Some(result));
+    }
+}
+
+impl Drop for LeaderGuard {
+    fn drop(&mut self) {
+        let mut inflight = self.inflight.lock().expect("coalescer lock poisoned");
+        if inflight
+            .get(&self.key)
+            .is_some_and(|entry| entry.result.same_channel(&self.sender.subscribe()))
+        {
+            inflight.remove(&self.key);
+        }
+    }
+}
+
+impl Coalescer {
+    pub fn new(enabled: bool, methods: &[String], max_age: Duration) -> Self {
+        Self {
+            enabled,
+            methods: methods.iter().cloned().collect(),
+            max_age,
+            inflight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Only single requests for configured methods are coalesced; the `id` is
+    // dropped from the key so callers with different ids share one upstream call.
+    pub fn key(&self, server: Option<&str>, location: Option<&str>, body: &[u8]) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+
+        let Value::Object(mut request) = serde_json::from_slice::<Value>(body).ok()? else {
+            return None;
+        };
+
+        let method = request.get("method")?.as_str()?;
+        if !self.methods.contains(method) {
+            return None;
+        }
+
+        request.remove("id");
+        let normalize = |value: Option<&str>| value.map(|v| v.trim().to_ascii_lowercase());
+        Some(format!(
+            "{}|{}|{}",
+            normalize(server).unwrap_or_default(),
+            normalize(location).unwrap_or_default(),
+            Value::Object(request)
+        ))
+    }
+
+    pub fn join(&self, key: String) -> Role {
+        let mut inflight = self.inflight.lock().expect("coalescer lock poisoned");
+        inflight.retain(|_, entry| entry.started.elapsed() < self.max_age);
+
+        if let Some(entry) = inflight.get(&key) {
+            return Role::Follower(entry.result.clone());
+        }
+
+        let (sender, receiver) = watch::channel(None);
+        inflight.insert(
+            key.clone(),
+            Inflight {
+                started: Instant::now(),
+                result: receiver,
+            },
+        );
+
+        Role::Leader(LeaderGuard {
+            key,
+            sender,
+            inflight: Arc::clone(&self.inflight),
+        })
+    }
+
+    pub fn inflight_len(&self) -> usize {
+        self.inflight.lock().expect("coalescer lock poisoned").len()
+    }
+}
+
+pub async fn wait(mut receiver: watch::Receiver<Option<SharedResult>>) -> Option<SharedResult> {
+    receiver
+        .wait_for(Option::is_some)
+        .await
+        .ok()
+        .and_then(|result| result.clone())
+}
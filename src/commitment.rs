@@ -0,0 +1,103 @@
+use serde_json::{Map, Value};
+
+use crate::config::Settings;
+use crate::stats::StatsStore;
+use crate::validators::Validator;
+
+// Ordered from least to most settled, so the minimum of a batch is the
+// level with the tightest lag requirement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Commitment {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl Commitment {
+    // Accepts the deprecated names older clients still send.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "processed" | "recent" => Some(Self::Processed),
+            "confirmed" | "single" | "singleGossip" => Some(Self::Confirmed),
+            "finalized" | "root" | "max" => Some(Self::Finalized),
+            _ => None,
+        }
+    }
+
+    // Nodes default to `finalized` when no commitment is given.
+    pub fn from_params(params: Option<&Value>) -> Self {
+        let config = match params {
+            Some(Value::Array(items)) => items.iter().rev().find_map(|item| {
+                item.as_object()
+                    .filter(|object| object.contains_key("commitment"))
+            }),
+            Some(Value::Object(named)) => Some(
+                named
+                    .get("config")
+                    .and_then(Value::as_object)
+                    .unwrap_or(named),
+            ),
+            _ => None,
+        };
+
+        config.and_then(commitment_field).unwrap_or(Self::Finalized)
+    }
+
+    pub fn for_request(body: &[u8]) -> Self {
+        match serde_json::from_slice::<Value>(body) {
+            Ok(Value::Object(request)) => Self::from_params(request.get("params")),
+            Ok(Value::Array(requests)) => requests
+                .iter()
+                .filter_map(Value::as_object)
+                .map(|request| Self::from_params(request.get("params")))
+                .min()
+                .unwrap_or(Self::Finalized),
+            _ => Self::Finalized,
+        }
+    }
+
+    pub fn max_slot_lag(self, settings: &Settings) -> Option<u64> {
+        match self {
+            Self::Processed => Some(settings.commitment_max_slot_lag_processed),
+            Self::Confirmed => Some(settings.commitment_max_slot_lag_confirmed),
+            Self::Finalized => settings.commitment_max_slot_lag_finalized,
+        }
+    }
+}
+
+fn commitment_field(config: &Map<String, Value>) -> Option<Commitment> {
+    config
+        .get("commitment")
+        .and_then(Value::as_str)
+        .and_then(Commitment::parse)
+}
+
+// Drops validators lagging further behind the highest observed slot than the
+// commitment tolerates. Validators without slot data are kept, and the list
+// is left alone when filtering would empty it.
+pub fn filter_by_lag(
+    stats: &StatsStore,
+    max_lag: Option<u64>,
+    candidates: Vec<Validator>,
+) -> Vec<Validator> {
+    let (Some(max_lag), Some(highest)) = (max_lag, stats.highest_slot()) else {
+        return candidates;
+    };
+
+    let within: Vec<Validator> = candidates
+        .iter()
+        .filter(|validator| {
+            stats
+                .validator(validator.name())
+                .latest_slot()
+                .is_none_or(|slot| highest.saturating_sub(slot) <= max_lag)
+        })
+        .cloned()
+        .collect();
+
+    if within.is_empty() {
+        candidates
+    } else {
+        within
+    }
+}
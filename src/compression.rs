@@ -0,0 +1,71 @@
+use std::io::{self, Write};
+
+use actix_web::http::header::{self, ContentEncoding, HeaderMap};
+use actix_web::web::Bytes;
+use flate2::{Compression, write::GzEncoder};
+
+// Picks the client's preferred encoding among the ones we produce; `None` means identity.
+pub fn negotiate(headers: &HeaderMap) -> Option<ContentEncoding> {
+    let mut best: Option<(ContentEncoding, f32)> = None;
+
+    for value in headers.get_all(header::ACCEPT_ENCODING) {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+
+        for item in value.split(',') {
+            let mut parts = item.split(';');
+            let coding = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            let candidates: &[ContentEncoding] = match coding.as_str() {
+                "br" => &[ContentEncoding::Brotli],
+                "gzip" | "x-gzip" => &[ContentEncoding::Gzip],
+                "*" => &[ContentEncoding::Brotli, ContentEncoding::Gzip],
+                _ => &[],
+            };
+
+            for candidate in candidates {
+                if quality <= 0.0 {
+                    continue;
+                }
+                let better = match best {
+                    None => true,
+                    Some((current, best_quality)) => {
+                        quality > best_quality
+                            || (quality == best_quality
+                                && current == ContentEncoding::Gzip
+                                && *candidate == ContentEncoding::Brotli)
+                    }
+                };
+                if better {
+                    best = Some((*candidate, quality));
+                }
+            }
+        }
+    }
+
+    best.map(|(encoding, _)| encoding)
+}
+
+pub fn compress(encoding: ContentEncoding, body: &[u8]) -> io::Result<Bytes> {
+    match encoding {
+        ContentEncoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body)?;
+            Ok(Bytes::from(encoder.finish()?))
+        }
+        ContentEncoding::Brotli => {
+            let mut output = Vec::new();
+            {
+                let mut writer = brotli::CompressorWriter::new(&mut output, 4096, 5, 22);
+                writer.write_all(body)?;
+            }
+            Ok(Bytes::from(output))
+        }
+        _ => Ok(Bytes::copy_from_slice(body)),
+    }
+}
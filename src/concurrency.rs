@@ -0,0 +1,367 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::ops::Bound;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use actix_web::{
+    Error,
+    body::{BodySize, BoxBody, EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web::{Bytes, Data, Query},
+};
+use serde::Deserialize;
+use tokio::sync::oneshot;
+
+use crate::app_state::AppState;
+use crate::errors::AppError;
+use crate::validators::normalize_key;
+
+// Slots free up as fast as requests finish, so a shed client is told to
+// come back soon.
+const SHED_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+// The queue of requests that name no known location, and the only queue
+// unless `CONCURRENCY_FAIR_QUEUES` is on.
+pub const ANY_LOCATION: &str = "any";
+
+// One location's queue, as reported in `/metrics`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QueueStats {
+    pub depth: usize,
+    // Requests that waited and got a slot, and how long they waited in all.
+    pub served: u64,
+    pub wait_total: Duration,
+    pub shed: u64,
+}
+
+struct Waiter {
+    ticket: u64,
+    grant: oneshot::Sender<()>,
+}
+
+struct Slots {
+    free: usize,
+    next_ticket: u64,
+    // Only non-empty queues are kept.
+    queues: BTreeMap<String, VecDeque<Waiter>>,
+    // The location granted a slot last; the next grant goes to the one
+    // after it.
+    last_served: Option<String>,
+    // One entry per location ever queued for, which the registry bounds.
+    stats: BTreeMap<String, QueueStats>,
+}
+
+impl Slots {
+    // Round-robin across the non-empty queues, first come first served
+    // within one.
+    fn next_waiter(&mut self) -> Option<Waiter> {
+        let after = self.last_served.as_deref().unwrap_or_default();
+        let location = self
+            .queues
+            .range::<str, _>((Bound::Excluded(after), Bound::Unbounded))
+            .next()
+            .or_else(|| self.queues.iter().next())
+            .map(|(location, _)| location.clone())?;
+        let queue = self.queues.get_mut(&location)?;
+        let waiter = queue.pop_front();
+        if queue.is_empty() {
+            self.queues.remove(&location);
+        }
+        self.last_served = Some(location);
+        waiter
+    }
+
+    fn remove(&mut self, location: &str, ticket: u64) -> bool {
+        let Some(queue) = self.queues.get_mut(location) else {
+            return false;
+        };
+        let before = queue.len();
+        queue.retain(|waiter| waiter.ticket != ticket);
+        let removed = queue.len() < before;
+        if queue.is_empty() {
+            self.queues.remove(location);
+        }
+        removed
+    }
+}
+
+// The ceiling on requests being proxied at once. Past it a request queues
+// for up to `queue_timeout`, or is shed at once when that is zero. Each
+// location has its own queue of at most `queue_depth` requests when queues
+// are fair, so a saturated region cannot hold up the others; otherwise all
+// share one.
+pub struct ConcurrencyLimit {
+    max: usize,
+    queue_timeout: Duration,
+    queue_depth: usize,
+    fair: bool,
+    slots: Mutex<Slots>,
+    shed: AtomicU64,
+}
+
+// A held slot, handed to the next waiter or freed when dropped.
+pub struct Permit(Arc<ConcurrencyLimit>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+// A place in a queue. Dropped before its grant arrived, say because the
+// client went away, it leaves the queue; dropped just after, it passes the
+// slot on.
+struct Waiting<'a> {
+    limit: &'a ConcurrencyLimit,
+    location: &'a str,
+    ticket: u64,
+    grant: oneshot::Receiver<()>,
+    granted: bool,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if self.granted {
+            return;
+        }
+        let mut slots = self.limit.lock();
+        if slots.remove(self.location, self.ticket) {
+            return;
+        }
+        drop(slots);
+        self.limit.release();
+    }
+}
+
+impl ConcurrencyLimit {
+    pub fn new(max: usize, queue_timeout: Duration, queue_depth: usize, fair: bool) -> Self {
+        Self {
+            max,
+            queue_timeout,
+            queue_depth,
+            fair,
+            slots: Mutex::new(Slots {
+                free: max,
+                next_ticket: 0,
+                queues: BTreeMap::new(),
+                last_served: None,
+                stats: BTreeMap::new(),
+            }),
+            shed: AtomicU64::new(0),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Slots> {
+        self.slots.lock().expect("concurrency lock poisoned")
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.max - self.lock().free
+    }
+
+    pub fn queued(&self) -> u64 {
+        self.lock()
+            .queues
+            .values()
+            .map(VecDeque::len)
+            .sum::<usize>() as u64
+    }
+
+    pub fn shed(&self) -> u64 {
+        self.shed.load(Ordering::Relaxed)
+    }
+
+    // Per location queued for so far.
+    pub fn queues(&self) -> Vec<(String, QueueStats)> {
+        let slots = self.lock();
+        slots
+            .stats
+            .iter()
+            .map(|(location, stats)| {
+                let depth = slots.queues.get(location).map_or(0, VecDeque::len);
+                (location.clone(), QueueStats { depth, ..*stats })
+            })
+            .collect()
+    }
+
+    // `None` once the request is to be shed.
+    pub async fn acquire(self: &Arc<Self>, location: &str) -> Option<Permit> {
+        let location = if self.fair { location } else { ANY_LOCATION };
+        let (ticket, grant) = {
+            let mut slots = self.lock();
+            if slots.free > 0 {
+                slots.free -= 1;
+                return Some(Permit(self.clone()));
+            }
+            let depth = slots.queues.get(location).map_or(0, VecDeque::len);
+            if self.queue_timeout.is_zero() || depth >= self.queue_depth {
+                slots.stats.entry(location.to_string()).or_default().shed += 1;
+                drop(slots);
+                self.shed.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+            let (sender, grant) = oneshot::channel();
+            slots.next_ticket += 1;
+            let ticket = slots.next_ticket;
+            slots.stats.entry(location.to_string()).or_default();
+            slots
+                .queues
+                .entry(location.to_string())
+                .or_default()
+                .push_back(Waiter {
+                    ticket,
+                    grant: sender,
+                });
+            (ticket, grant)
+        };
+
+        let started = Instant::now();
+        let mut waiting = Waiting {
+            limit: self,
+            location,
+            ticket,
+            grant,
+            granted: false,
+        };
+        let granted = matches!(
+            tokio::time::timeout(self.queue_timeout, &mut waiting.grant).await,
+            Ok(Ok(()))
+        );
+        waiting.granted = granted;
+        drop(waiting);
+
+        let mut slots = self.lock();
+        let stats = slots.stats.entry(location.to_string()).or_default();
+        if granted {
+            stats.served += 1;
+            stats.wait_total += started.elapsed();
+            return Some(Permit(self.clone()));
+        }
+        stats.shed += 1;
+        drop(slots);
+        self.shed.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    fn release(&self) {
+        let mut slots = self.lock();
+        while let Some(waiter) = slots.next_waiter() {
+            if waiter.grant.send(()).is_ok() {
+                return;
+            }
+        }
+        slots.free += 1;
+    }
+}
+
+// A response body that keeps its request's slot until it is fully sent or
+// dropped, so streamed answers count for as long as they run.
+pub struct Held {
+    body: BoxBody,
+    _permit: Permit,
+}
+
+impl MessageBody for Held {
+    type Error = <BoxBody as MessageBody>::Error;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        Pin::new(&mut self.get_mut().body).poll_next(cx)
+    }
+}
+
+// Holds a slot of `MAX_CONCURRENT_REQUESTS` for the whole request. The
+// permit is dropped with the request's future, so panics and clients that
+// go away give it back too.
+pub async fn limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<Held, BoxBody>>, Error> {
+    let limit = req.app_data::<Data<AppState>>().and_then(|state| {
+        let limit = state.concurrency_limit()?.clone();
+        Some((limit, queue_location(state, &req)))
+    });
+    let Some((limit, location)) = limit else {
+        return Ok(next
+            .call(req)
+            .await?
+            .map_into_boxed_body()
+            .map_into_right_body());
+    };
+    let Some(permit) = limit.acquire(&location).await else {
+        let err = AppError::Overloaded {
+            message: "proxy is at its concurrent request limit".to_string(),
+            retry_after: SHED_RETRY_AFTER,
+        };
+        return Ok(req.error_response(err).map_into_right_body());
+    };
+    let response = next.call(req).await?;
+    Ok(response
+        .map_body(|_, body| Held {
+            body: body.boxed(),
+            _permit: permit,
+        })
+        .map_into_left_body())
+}
+
+// Wrapped around everything that reaches a validator: once shutdown began,
+// new work is refused while requests in flight finish.
+pub async fn refuse_while_draining(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let draining = req
+        .app_data::<Data<AppState>>()
+        .is_some_and(|state| state.is_draining());
+    if draining {
+        let err = AppError::Overloaded {
+            message: "server is shutting down".to_string(),
+            retry_after: SHED_RETRY_AFTER,
+        };
+        return Ok(req.error_response(err));
+    }
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+#[derive(Default, Deserialize)]
+struct Target {
+    #[serde(alias = "server")]
+    validator: Option<String>,
+    #[serde(alias = "region")]
+    location: Option<String>,
+}
+
+// The location a request asks for, directly or through its validator.
+// Anything the registry does not know shares `ANY_LOCATION`, so callers
+// cannot make up queues.
+fn queue_location(state: &AppState, req: &ServiceRequest) -> String {
+    let target = Query::<Target>::from_query(req.query_string())
+        .map(Query::into_inner)
+        .unwrap_or_default();
+    let registry = state.registry();
+    if let Some(location) = target
+        .location
+        .filter(|location| registry.has_location(location))
+    {
+        return normalize_key(&location);
+    }
+    target
+        .validator
+        .and_then(|name| registry.get_by_name(&name))
+        .map(|validator| normalize_key(validator.location()))
+        .unwrap_or_else(|| ANY_LOCATION.to_string())
+}
@@ -1,34 +1,1929 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
+use actix_web::http::header::{HeaderName, HeaderValue};
+use serde::Serialize;
 use thiserror::Error;
 
+use crate::admin::AdminToken;
+use crate::auth::{self, ApiKeys};
+use crate::batch::BatchIdCheck;
+use crate::body_log::{self, RedactRule};
+use crate::cli::Args;
+use crate::client_limit::IpRange;
+use crate::cors::{AllowedOrigins, CorsSettings};
+use crate::env_file::{self, EnvFile};
+use crate::errors::ErrorFormat;
+use crate::histogram;
+use crate::ip_filter::IpFilter;
+use crate::jwt::{JwtKey, JwtSettings};
+use crate::listeners::{self, Listener};
+use crate::log_file::Rotation;
+use crate::rate_limit::{KeyRateLimit, MethodRateLimits, RateLimitHeaders};
+use crate::routing::{RoutingError, RoutingRules};
+use crate::size_limits::ResponseSizeLimits;
+use crate::status_policy::{StatusPolicies, StatusPolicy};
+use crate::telemetry::LogFormat;
+use crate::tls::TlsSettings;
+use crate::validators::{RegistryError, ValidatorRegistry};
+
 #[derive(Debug, Clone)]
 pub struct Settings {
+    // As given; `listeners` is what it describes.
     pub bind_address: String,
+    pub listeners: Vec<Listener>,
+    // HTTP worker threads, each with its own upstream connection pools.
+    pub workers: usize,
+    // How long requests in flight get to finish once shutdown began.
+    pub shutdown_timeout: Duration,
+    // HTTPS on `bind_address` when both `TLS_CERT_FILE` and `TLS_KEY_FILE`
+    // are set.
+    pub tls: Option<TlsSettings>,
+    // A plaintext listener serving only `/health`, for load balancers.
+    pub health_bind_address: Option<String>,
     pub validators_csv: PathBuf,
+    // Registry CSV text, used instead of `validators_csv` when set.
+    pub validators_inline: Option<String>,
+    // How often `validators_csv` is checked for changes; zero turns the
+    // watch off.
+    pub validators_reload_interval: Duration,
+    pub dns_cache_ttl: Duration,
+    pub max_retries: usize,
+    pub upstream_timeout: Duration,
+    // Upstream connection pool, per upstream host and worker thread.
+    pub upstream_max_connections_per_host: usize,
+    pub upstream_keepalive: Duration,
+    pub upstream_conn_lifetime: Duration,
+    // How long a TLS connection being closed may take to shut down cleanly.
+    pub upstream_disconnect_timeout: Duration,
+    pub retry_on_429: bool,
+    pub cooldown_on_429: bool,
+    pub default_429_cooldown: Duration,
+    pub shed_when_cooling_down: bool,
+    // Outbound budget per validator unless its `rate_limit_rps` column sets
+    // one; off when neither does.
+    pub upstream_rate_limit: Option<KeyRateLimit>,
+    // How long a request naming its validator waits for that budget before
+    // a `429`; zero refuses at once.
+    pub upstream_rate_limit_wait: Duration,
+    pub forward_retry_after: bool,
+    pub upstream_error_policy: StatusPolicies,
+    pub upstream_user_agent: HeaderValue,
+    pub proxied_by: Option<HeaderValue>,
+    pub preserve_client_user_agent: bool,
+    pub rewrite_host: bool,
+    pub enforce_json_content_type: bool,
+    pub lenient_content_type: bool,
+    pub compression_enabled: bool,
+    pub compression_min_bytes: usize,
+    pub coalesce_requests: bool,
+    pub coalesce_methods: Vec<String>,
+    pub coalesce_max_age: Duration,
+    pub routing_rules: RoutingRules,
+    // Where requests naming neither validator nor location go, while it has
+    // a validator that is not cooling down.
+    pub default_location: Option<String>,
+    pub response_cache: bool,
+    pub cache_immutable_methods: Vec<String>,
+    pub cache_immutable_ttl: Duration,
+    pub cache_short_methods: Vec<String>,
+    pub cache_short_ttl: Duration,
+    pub cache_blockhash_ttl: Duration,
+    pub cache_blockhash_max_slot_lag: u64,
+    pub cache_max_entries: usize,
+    pub jsonrpc_errors: bool,
+    pub error_format: ErrorFormat,
+    pub method_rate_limits: MethodRateLimits,
+    pub rate_limit_max_clients: usize,
+    // Whole-proxy ceiling, and per-client buckets for requests made without
+    // an API key; both off when unset.
+    pub global_rate_limit: Option<KeyRateLimit>,
+    pub client_rate_limit: Option<KeyRateLimit>,
+    // Per client on `/health` and `/validators`, which skip the two above.
+    pub monitoring_rate_limit: Option<KeyRateLimit>,
+    pub rate_limit_headers: RateLimitHeaders,
+    // Requests proxied at once; off when unset. Past it requests queue for
+    // `concurrency_queue_timeout`, or are shed at once when that is zero.
+    pub max_concurrent_requests: Option<usize>,
+    pub concurrency_queue_timeout: Duration,
+    // Waiting requests allowed per queue: one per location when queues are
+    // fair, else a single one.
+    pub concurrency_queue_depth: usize,
+    pub concurrency_fair_queues: bool,
+    // Peers whose `X-Forwarded-For` is believed when keying clients.
+    pub trusted_proxies: Vec<IpRange>,
+    pub ip_filter: IpFilter,
+    // Off unless `CORS_ALLOWED_ORIGINS` is set.
+    pub cors: Option<CorsSettings>,
+    pub guardrail_enabled: bool,
+    pub guardrail_allowlist: Vec<String>,
+    pub guardrail_bypass_header: Option<HeaderName>,
+    pub guardrail_bypass_value: Option<HeaderValue>,
+    pub response_size_limits: ResponseSizeLimits,
+    pub method_stats_max_methods: usize,
+    pub commitment_routing: bool,
+    pub commitment_max_slot_lag_processed: u64,
+    pub commitment_max_slot_lag_confirmed: u64,
+    pub commitment_max_slot_lag_finalized: Option<u64>,
+    pub quorum_max_validators: usize,
+    pub shadow_sample_rate: f64,
+    pub shadow_ignore_fields: Vec<String>,
+    pub fleet_concurrency: usize,
+    pub fleet_node_timeout: Duration,
+    pub fleet_min_interval: Duration,
+    pub cluster_nodes_sample: usize,
+    pub cluster_nodes_cache_ttl: Duration,
+    pub account_default_slice_bytes: usize,
+    pub batch_id_check: BatchIdCheck,
+    pub strict_responses: bool,
+    pub strict_response_max_bytes: usize,
+    pub account_cache: bool,
+    pub account_cache_ttl: Duration,
+    pub account_cache_max_slot_lag: u64,
+    pub account_cache_max_entries: usize,
+    pub account_cache_processed: bool,
+    pub admin_token: Option<AdminToken>,
+    pub api_keys: Option<ApiKeys>,
+    pub api_keys_public: Vec<String>,
+    // How often `API_KEYS_FILE` is checked for changes; zero never does.
+    pub api_keys_reload_interval: Duration,
+    // For keys without a limit of their own, and for JWT callers; `None`
+    // leaves them unlimited.
+    pub api_key_rate_limit: Option<KeyRateLimit>,
+    // How far a signed request's `X-Timestamp` may be from our clock.
+    pub hmac_max_skew: Duration,
+    // What each key's signing secret is derived with; signed requests are
+    // refused without it.
+    pub hmac_secret: Option<String>,
+    // Off unless `JWT_HS256_SECRET` or `JWT_JWKS_URL` is set.
+    pub jwt: Option<JwtSettings>,
+    pub metrics_enabled: bool,
+    pub latency_buckets_ms: Vec<f64>,
+    pub slow_request_threshold: Option<Duration>,
+    pub log_format: LogFormat,
+    pub statsd_address: Option<String>,
+    pub statsd_prefix: String,
+    pub statsd_tags: Vec<String>,
+    pub statsd_interval: Duration,
+    pub sentry_dsn: Option<String>,
+    pub access_log_path: Option<PathBuf>,
+    pub access_log_rotation: Rotation,
+    pub access_log_max_bytes: u64,
+    pub access_log_max_files: usize,
+    pub log_bodies: bool,
+    pub log_body_max_bytes: usize,
+    pub log_body_redact: Vec<RedactRule>,
+    pub key_usage_max_keys: usize,
+    pub key_usage_retention: Duration,
+    pub key_metrics_max_keys: usize,
+    pub ping_rate_limit: f64,
+    pub stats_snapshot_path: Option<PathBuf>,
+    pub stats_snapshot_interval: Duration,
+    pub stats_snapshot_max_age: Duration,
+    // `CONFIG_FILE` keys no setting reads, for a warning at startup.
+    pub unknown_config_keys: Vec<String>,
+    // Lines of the env file that were skipped, likewise.
+    pub env_file_warnings: Vec<String>,
+    // Where each variable read was given; those left out took the default.
+    pub sources: BTreeMap<String, Source>,
+}
+
+// Where a setting's value came from, lowest precedence first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    Default,
+    File,
+    Env,
+    Flag,
 }
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error("validators csv file not found at {0}")]
     MissingValidatorsCsv(String),
+    #[error("invalid value '{value}' for {name}")]
+    InvalidValue { name: String, value: String },
+    #[error(transparent)]
+    RoutingRules(#[from] RoutingError),
+    #[error("invalid config file {path}: {message}")]
+    ConfigFile { path: String, message: String },
+    #[error("unknown keys in config file: {}", .0.join(", "))]
+    UnknownConfigKeys(Vec<String>),
+    #[error("invalid bind address '{input}' for {name} (read as '{attempted}')")]
+    InvalidBindAddress {
+        name: String,
+        input: String,
+        attempted: String,
+    },
+    #[error("cannot read env file {path}: {message}")]
+    EnvFile { path: String, message: String },
+    #[error("invalid value '{value}' for {name}: expected {expected}")]
+    InvalidNumber {
+        name: String,
+        value: String,
+        expected: String,
+    },
+    #[error("{name} conflicts with {other}: {reason}")]
+    Conflict {
+        name: String,
+        other: String,
+        reason: String,
+    },
+    // Everything wrong with one load, one problem per line.
+    #[error(
+        "{} problems in the configuration:{}",
+        .0.len(),
+        .0.iter().map(|problem| format!("\n  {problem}")).collect::<String>()
+    )]
+    Multiple(Vec<ConfigError>),
+}
+
+impl ConfigError {
+    // Each problem on its own, whether or not there were several.
+    pub fn problems(&self) -> &[ConfigError] {
+        match self {
+            Self::Multiple(problems) => problems,
+            problem => std::slice::from_ref(problem),
+        }
+    }
+
+    // The variable at fault, when there is a single one.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::InvalidValue { name, .. }
+            | Self::InvalidNumber { name, .. }
+            | Self::InvalidBindAddress { name, .. }
+            | Self::Conflict { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    fn from_problems(mut problems: Vec<ConfigError>) -> Result<(), ConfigError> {
+        match problems.len() {
+            0 => Ok(()),
+            1 => Err(problems.remove(0)),
+            _ => Err(Self::Multiple(problems)),
+        }
+    }
+
+    fn into_problems(self) -> Vec<ConfigError> {
+        match self {
+            Self::Multiple(problems) => problems,
+            problem => vec![problem],
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            bind_address: "0.0.0.0:80".to_string(),
+            listeners: vec![Listener {
+                address: "0.0.0.0:80".to_string(),
+                tls: false,
+                auth: true,
+            }],
+            workers: std::thread::available_parallelism().map_or(1, usize::from),
+            shutdown_timeout: Duration::from_secs(30),
+            tls: None,
+            health_bind_address: None,
+            validators_csv: PathBuf::from("config/validators.csv"),
+            validators_inline: None,
+            validators_reload_interval: Duration::ZERO,
+            dns_cache_ttl: Duration::from_secs(60),
+            max_retries: 2,
+            upstream_timeout: Duration::from_secs(15),
+            upstream_max_connections_per_host: 100,
+            upstream_keepalive: Duration::from_secs(15),
+            upstream_conn_lifetime: Duration::from_secs(75),
+            upstream_disconnect_timeout: Duration::from_millis(3000),
+            retry_on_429: true,
+            cooldown_on_429: true,
+            default_429_cooldown: Duration::from_secs(10),
+            shed_when_cooling_down: false,
+            upstream_rate_limit: None,
+            upstream_rate_limit_wait: Duration::ZERO,
+            forward_retry_after: true,
+            upstream_error_policy: StatusPolicies::default(),
+            upstream_user_agent: HeaderValue::from_static(concat!(
+                "the-solana-api/",
+                env!("CARGO_PKG_VERSION")
+            )),
+            proxied_by: None,
+            preserve_client_user_agent: false,
+            rewrite_host: true,
+            enforce_json_content_type: false,
+            lenient_content_type: false,
+            compression_enabled: true,
+            compression_min_bytes: 1024,
+            coalesce_requests: false,
+            coalesce_methods: [
+                "getSlot",
+                "getEpochInfo",
+                "getBlockHeight",
+                "getLatestBlockhash",
+                "getHealth",
+                "getVersion",
+            ]
+            .map(String::from)
+            .to_vec(),
+            coalesce_max_age: Duration::from_secs(5),
+            routing_rules: RoutingRules::default(),
+            default_location: None,
+            response_cache: false,
+            cache_immutable_methods: ["getGenesisHash", "getVersion"].map(String::from).to_vec(),
+            cache_immutable_ttl: Duration::from_secs(3600),
+            cache_short_methods: ["getSlot", "getEpochInfo", "getBlockHeight"]
+                .map(String::from)
+                .to_vec(),
+            cache_short_ttl: Duration::from_millis(300),
+            cache_blockhash_ttl: Duration::from_millis(400),
+            cache_blockhash_max_slot_lag: 10,
+            cache_max_entries: 1024,
+            jsonrpc_errors: false,
+            error_format: ErrorFormat::Plain,
+            method_rate_limits: MethodRateLimits::default(),
+            rate_limit_max_clients: 10_000,
+            global_rate_limit: None,
+            client_rate_limit: None,
+            monitoring_rate_limit: None,
+            rate_limit_headers: RateLimitHeaders::default(),
+            max_concurrent_requests: None,
+            concurrency_queue_timeout: Duration::ZERO,
+            concurrency_queue_depth: 1000,
+            concurrency_fair_queues: false,
+            trusted_proxies: Vec::new(),
+            ip_filter: IpFilter::default(),
+            cors: None,
+            guardrail_enabled: false,
+            guardrail_allowlist: Vec::new(),
+            guardrail_bypass_header: None,
+            guardrail_bypass_value: None,
+            response_size_limits: ResponseSizeLimits::default(),
+            method_stats_max_methods: 128,
+            commitment_routing: false,
+            commitment_max_slot_lag_processed: 2,
+            commitment_max_slot_lag_confirmed: 5,
+            commitment_max_slot_lag_finalized: None,
+            quorum_max_validators: 5,
+            shadow_sample_rate: 0.0,
+            shadow_ignore_fields: ["context.slot", "context.apiVersion"]
+                .map(String::from)
+                .to_vec(),
+            fleet_concurrency: 16,
+            fleet_node_timeout: Duration::from_secs(2),
+            fleet_min_interval: Duration::from_secs(5),
+            cluster_nodes_sample: 1,
+            cluster_nodes_cache_ttl: Duration::from_secs(300),
+            account_default_slice_bytes: 64 * 1024,
+            batch_id_check: BatchIdCheck::Off,
+            strict_responses: false,
+            strict_response_max_bytes: 1024 * 1024,
+            account_cache: false,
+            account_cache_ttl: Duration::from_secs(2),
+            account_cache_max_slot_lag: 4,
+            account_cache_max_entries: 10_000,
+            account_cache_processed: false,
+            admin_token: None,
+            api_keys: None,
+            api_keys_public: auth::OPTIONAL_PATHS.map(String::from).to_vec(),
+            api_keys_reload_interval: Duration::from_secs(5),
+            api_key_rate_limit: None,
+            hmac_max_skew: Duration::from_secs(300),
+            hmac_secret: None,
+            jwt: None,
+            metrics_enabled: false,
+            latency_buckets_ms: histogram::DEFAULT_BUCKETS_MS.to_vec(),
+            slow_request_threshold: None,
+            log_format: LogFormat::Compact,
+            statsd_address: None,
+            statsd_prefix: "solana_api".to_string(),
+            statsd_tags: Vec::new(),
+            statsd_interval: Duration::from_secs(10),
+            sentry_dsn: None,
+            access_log_path: None,
+            access_log_rotation: Rotation::Daily,
+            access_log_max_bytes: 100 * 1024 * 1024,
+            access_log_max_files: 7,
+            log_bodies: false,
+            log_body_max_bytes: 4096,
+            log_body_redact: body_log::default_rules(),
+            key_usage_max_keys: 10_000,
+            key_usage_retention: Duration::from_secs(86_400),
+            key_metrics_max_keys: 100,
+            ping_rate_limit: 1.0,
+            stats_snapshot_path: None,
+            stats_snapshot_interval: Duration::from_secs(60),
+            stats_snapshot_max_age: Duration::from_secs(3600),
+            unknown_config_keys: Vec::new(),
+            env_file_warnings: Vec::new(),
+            sources: BTreeMap::new(),
+        }
+    }
 }
 
 impl Settings {
-    pub fn from_env() -> Result<Self, ConfigError> {
-        let bind_address = env::var("BIND_ADDRESS").unwrap_or_else(|_| "0.0.0.0:80".to_string());
-        let csv_path =
-            env::var("VALIDATORS_CSV").unwrap_or_else(|_| "config/validators.csv".to_string());
+    pub fn load_registry(&self) -> Result<ValidatorRegistry, RegistryError> {
+        match &self.validators_inline {
+            Some(csv) => ValidatorRegistry::from_reader(csv.as_bytes()),
+            None => ValidatorRegistry::from_csv(&self.validators_csv),
+        }
+    }
+
+    // Where `load_registry` reads from, for logs and `/version`.
+    pub fn registry_source(&self) -> String {
+        match &self.validators_inline {
+            Some(_) => "inline".to_string(),
+            None => self.validators_csv.display().to_string(),
+        }
+    }
+
+    // From the process environment and `CONFIG_FILE` alone.
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::load_with(&Args::default())
+    }
+
+    // The command line over the process environment, itself over the env
+    // file, with `--config` or else `CONFIG_FILE` naming the config file.
+    // `./.env` is only read when it exists; a file named by `--env-file`
+    // has to.
+    pub fn load_with(args: &Args) -> Result<Self, ConfigError> {
+        let env_path = args
+            .env_file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(env_file::DEFAULT_PATH));
+        let env_file = match EnvFile::read(&env_path) {
+            Err(err) if args.env_file.is_some() || err.kind() != io::ErrorKind::NotFound => {
+                return Err(ConfigError::EnvFile {
+                    path: env_path.display().to_string(),
+                    message: err.to_string(),
+                });
+            }
+            env_file => env_file.unwrap_or_default(),
+        };
+        let mut env = with_prefix(&env_file.vars);
+        env.extend(process_env());
+        let file = args.config.clone().or_else(|| {
+            env.get("CONFIG_FILE")
+                .filter(|path| !path.trim().is_empty())
+                .map(|path| PathBuf::from(path.trim()))
+        });
+        let mut settings = Self::from_sources(args, &env, file.as_deref())?;
+        settings.env_file_warnings = env_file.warnings;
+        Ok(settings)
+    }
+
+    // Each setting is taken from, in order: the command line, `env` (under
+    // its `APP_ENV_PREFIX` name first), the TOML `file`, the default. Keys
+    // in the file that no setting reads are returned in
+    // `unknown_config_keys`, or fail the load when `CONFIG_STRICT` is on.
+    pub fn from_sources(
+        args: &Args,
+        env: &BTreeMap<String, String>,
+        file: Option<&Path>,
+    ) -> Result<Self, ConfigError> {
+        let vars = Vars {
+            args: args.settings.clone(),
+            env: with_prefix(env),
+            file: file.map(Vars::read_file).transpose()?.unwrap_or_default(),
+            read: RefCell::default(),
+            sources: RefCell::default(),
+            problems: RefCell::default(),
+        };
+        let mut settings = Self::from_vars(&vars);
+        let strict = bool_env(&vars, "CONFIG_STRICT", false).or_report(&vars);
+        let unknown = vars.unknown();
+        if strict && !unknown.is_empty() {
+            vars.report(ConfigError::UnknownConfigKeys(unknown.clone()));
+        }
+        // A variable that failed to load stands at its type's default, which
+        // `check` may find fault with again.
+        let mut problems = vars.problems.take();
+        if let Err(err) = settings.check() {
+            for problem in err.into_problems() {
+                if !problems
+                    .iter()
+                    .any(|known| known.name().is_some() && known.name() == problem.name())
+                {
+                    problems.push(problem);
+                }
+            }
+        }
+        ConfigError::from_problems(problems)?;
+        settings.unknown_config_keys = unknown;
+        settings.sources = vars.sources.into_inner();
+        Ok(settings)
+    }
+
+    // Problems are left in `vars`, with the loaders going on past them.
+    fn from_vars(vars: &Vars) -> Self {
+        let defaults = Self::default();
+
+        let bind_address = vars.var("BIND_ADDRESS").unwrap_or(defaults.bind_address);
+        let csv_path = vars
+            .var("VALIDATORS_CSV")
+            .unwrap_or_else(|_| "config/validators.csv".to_string());
         let validators_csv = PathBuf::from(csv_path.clone());
+        // The inline registry wins over a path from the same place or a
+        // weaker one, and gives way to a path set more directly.
+        let validators_inline = vars
+            .var("VALIDATORS_INLINE")
+            .ok()
+            .filter(|csv| !csv.trim().is_empty())
+            .filter(|_| vars.source("VALIDATORS_INLINE") >= vars.source("VALIDATORS_CSV"));
 
-        if !validators_csv.exists() {
-            return Err(ConfigError::MissingValidatorsCsv(csv_path));
+        if validators_inline.is_none() && !validators_csv.exists() {
+            vars.report(ConfigError::MissingValidatorsCsv(csv_path));
         }
 
-        Ok(Self {
+        let tls = tls_env(vars).or_report(vars);
+        Self {
+            workers: positive_env(vars, "WORKERS", defaults.workers as u64).or_report(vars)
+                as usize,
+            shutdown_timeout: secs_env(vars, "SHUTDOWN_TIMEOUT_SECS", defaults.shutdown_timeout)
+                .or_report(vars),
+            listeners: listeners_env(&bind_address, tls.as_ref()).or_report(vars),
             bind_address,
+            tls,
+            health_bind_address: vars
+                .var("HEALTH_BIND_ADDRESS")
+                .ok()
+                .filter(|addr| !addr.trim().is_empty())
+                .map(|addr| bind_address_env("HEALTH_BIND_ADDRESS", &addr))
+                .transpose()
+                .or_report(vars),
             validators_csv,
+            validators_inline,
+            validators_reload_interval: secs_env(
+                vars,
+                "VALIDATORS_RELOAD_SECS",
+                defaults.validators_reload_interval,
+            )
+            .or_report(vars),
+            dns_cache_ttl: secs_env(vars, "DNS_CACHE_TTL_SECS", defaults.dns_cache_ttl)
+                .or_report(vars),
+            max_retries: number_env(vars, "MAX_RETRIES")
+                .or_report(vars)
+                .unwrap_or(defaults.max_retries),
+            upstream_timeout: timeout_env(vars, "UPSTREAM_TIMEOUT_MS", defaults.upstream_timeout)
+                .or_report(vars),
+            upstream_max_connections_per_host: positive_env(
+                vars,
+                "UPSTREAM_MAX_CONNECTIONS_PER_HOST",
+                defaults.upstream_max_connections_per_host as u64,
+            )
+            .or_report(vars) as usize,
+            upstream_keepalive: secs_env(
+                vars,
+                "UPSTREAM_KEEPALIVE_SECS",
+                defaults.upstream_keepalive,
+            )
+            .or_report(vars),
+            upstream_conn_lifetime: Duration::from_secs(
+                positive_env(
+                    vars,
+                    "UPSTREAM_CONN_LIFETIME_SECS",
+                    defaults.upstream_conn_lifetime.as_secs(),
+                )
+                .or_report(vars),
+            ),
+            upstream_disconnect_timeout: timeout_env(
+                vars,
+                "UPSTREAM_DISCONNECT_TIMEOUT_MS",
+                defaults.upstream_disconnect_timeout,
+            )
+            .or_report(vars),
+            retry_on_429: bool_env(vars, "RETRY_ON_429", defaults.retry_on_429).or_report(vars),
+            cooldown_on_429: bool_env(vars, "COOLDOWN_ON_429", defaults.cooldown_on_429)
+                .or_report(vars),
+            default_429_cooldown: secs_env(
+                vars,
+                "DEFAULT_429_COOLDOWN_SECS",
+                defaults.default_429_cooldown,
+            )
+            .or_report(vars),
+            shed_when_cooling_down: bool_env(
+                vars,
+                "SHED_WHEN_COOLING_DOWN",
+                defaults.shed_when_cooling_down,
+            )
+            .or_report(vars),
+            upstream_rate_limit: rate_limit_env(vars, "UPSTREAM_RATE_LIMIT").or_report(vars),
+            upstream_rate_limit_wait: millis_env(
+                vars,
+                "UPSTREAM_RATE_LIMIT_WAIT_MS",
+                defaults.upstream_rate_limit_wait,
+            )
+            .or_report(vars),
+            forward_retry_after: bool_env(
+                vars,
+                "FORWARD_RETRY_AFTER",
+                defaults.forward_retry_after,
+            )
+            .or_report(vars),
+            upstream_error_policy: status_policies_env(
+                vars,
+                parse_env(vars, "UPSTREAM_ERROR_POLICY")
+                    .or_report(vars)
+                    .unwrap_or(defaults.upstream_error_policy.default_policy()),
+            )
+            .or_report(vars),
+            upstream_user_agent: header_env(vars, "UPSTREAM_USER_AGENT")
+                .or_report(vars)
+                .unwrap_or(defaults.upstream_user_agent),
+            proxied_by: header_env(vars, "UPSTREAM_PROXIED_BY").or_report(vars),
+            preserve_client_user_agent: bool_env(
+                vars,
+                "PRESERVE_CLIENT_USER_AGENT",
+                defaults.preserve_client_user_agent,
+            )
+            .or_report(vars),
+            rewrite_host: bool_env(vars, "REWRITE_HOST", defaults.rewrite_host).or_report(vars),
+            enforce_json_content_type: bool_env(
+                vars,
+                "ENFORCE_JSON_CONTENT_TYPE",
+                defaults.enforce_json_content_type,
+            )
+            .or_report(vars),
+            lenient_content_type: bool_env(
+                vars,
+                "LENIENT_CONTENT_TYPE",
+                defaults.lenient_content_type,
+            )
+            .or_report(vars),
+            compression_enabled: bool_env(
+                vars,
+                "COMPRESSION_ENABLED",
+                defaults.compression_enabled,
+            )
+            .or_report(vars),
+            compression_min_bytes: number_env(vars, "COMPRESSION_MIN_BYTES")
+                .or_report(vars)
+                .unwrap_or(defaults.compression_min_bytes),
+            coalesce_requests: bool_env(vars, "COALESCE_REQUESTS", defaults.coalesce_requests)
+                .or_report(vars),
+            coalesce_methods: list_env(vars, "COALESCE_METHODS")
+                .unwrap_or(defaults.coalesce_methods),
+            coalesce_max_age: millis_env(vars, "COALESCE_MAX_AGE_MS", defaults.coalesce_max_age)
+                .or_report(vars),
+            routing_rules: routing_rules_env(vars)
+                .or_report(vars)
+                .unwrap_or(defaults.routing_rules),
+            default_location: vars
+                .var("DEFAULT_LOCATION")
+                .ok()
+                .map(|location| location.trim().to_string())
+                .filter(|location| !location.is_empty()),
+            response_cache: bool_env(vars, "RESPONSE_CACHE", defaults.response_cache)
+                .or_report(vars),
+            cache_immutable_methods: list_env(vars, "CACHE_IMMUTABLE_METHODS")
+                .unwrap_or(defaults.cache_immutable_methods),
+            cache_immutable_ttl: secs_env(
+                vars,
+                "CACHE_IMMUTABLE_TTL_SECS",
+                defaults.cache_immutable_ttl,
+            )
+            .or_report(vars),
+            cache_short_methods: list_env(vars, "CACHE_SHORT_METHODS")
+                .unwrap_or(defaults.cache_short_methods),
+            cache_short_ttl: millis_env(vars, "CACHE_SHORT_TTL_MS", defaults.cache_short_ttl)
+                .or_report(vars),
+            cache_blockhash_ttl: millis_env(
+                vars,
+                "CACHE_BLOCKHASH_TTL_MS",
+                defaults.cache_blockhash_ttl,
+            )
+            .or_report(vars),
+            cache_blockhash_max_slot_lag: number_env(vars, "CACHE_BLOCKHASH_MAX_SLOT_LAG")
+                .or_report(vars)
+                .unwrap_or(defaults.cache_blockhash_max_slot_lag),
+            cache_max_entries: number_env(vars, "CACHE_MAX_ENTRIES")
+                .or_report(vars)
+                .unwrap_or(defaults.cache_max_entries),
+            jsonrpc_errors: bool_env(vars, "JSONRPC_ERRORS", defaults.jsonrpc_errors)
+                .or_report(vars),
+            error_format: parse_env(vars, "ERROR_FORMAT")
+                .or_report(vars)
+                .unwrap_or(defaults.error_format),
+            method_rate_limits: method_rate_limits_env(vars, "METHOD_RATE_LIMITS")
+                .or_report(vars)
+                .unwrap_or(defaults.method_rate_limits),
+            rate_limit_max_clients: number_env(vars, "RATE_LIMIT_MAX_CLIENTS")
+                .or_report(vars)
+                .unwrap_or(defaults.rate_limit_max_clients),
+            global_rate_limit: rate_limit_env(vars, "GLOBAL_RATE_LIMIT").or_report(vars),
+            client_rate_limit: rate_limit_env(vars, "CLIENT_RATE_LIMIT").or_report(vars),
+            monitoring_rate_limit: rate_limit_env(vars, "MONITORING_RATE_LIMIT").or_report(vars),
+            rate_limit_headers: parse_env(vars, "RATE_LIMIT_HEADERS")
+                .or_report(vars)
+                .unwrap_or(defaults.rate_limit_headers),
+            max_concurrent_requests: number_env(vars, "MAX_CONCURRENT_REQUESTS")
+                .or_report(vars)
+                .filter(|max| *max > 0),
+            concurrency_queue_timeout: millis_env(
+                vars,
+                "CONCURRENCY_QUEUE_TIMEOUT_MS",
+                defaults.concurrency_queue_timeout,
+            )
+            .or_report(vars),
+            concurrency_queue_depth: number_env(vars, "CONCURRENCY_QUEUE_DEPTH")
+                .or_report(vars)
+                .unwrap_or(defaults.concurrency_queue_depth),
+            concurrency_fair_queues: bool_env(
+                vars,
+                "CONCURRENCY_FAIR_QUEUES",
+                defaults.concurrency_fair_queues,
+            )
+            .or_report(vars),
+            trusted_proxies: ip_ranges_env(vars, "TRUSTED_PROXIES").or_report(vars),
+            cors: cors_env(vars).or_report(vars),
+            ip_filter: IpFilter {
+                allow: ip_ranges_env(vars, "IP_ALLOWLIST").or_report(vars),
+                deny: ip_ranges_env(vars, "IP_DENYLIST").or_report(vars),
+                unlisted: parse_env(vars, "IP_ALLOWLIST_UNLISTED")
+                    .or_report(vars)
+                    .unwrap_or(defaults.ip_filter.unlisted),
+            },
+            guardrail_enabled: bool_env(vars, "GUARDRAIL_ENABLED", defaults.guardrail_enabled)
+                .or_report(vars),
+            guardrail_allowlist: list_env(vars, "GUARDRAIL_ALLOWLIST")
+                .unwrap_or(defaults.guardrail_allowlist),
+            guardrail_bypass_header: header_name_env(vars, "GUARDRAIL_BYPASS_HEADER")
+                .or_report(vars),
+            guardrail_bypass_value: header_env(vars, "GUARDRAIL_BYPASS_VALUE").or_report(vars),
+            response_size_limits: response_size_limits_env(
+                vars,
+                number_env(vars, "RESPONSE_SIZE_LIMIT_BYTES")
+                    .or_report(vars)
+                    .unwrap_or(defaults.response_size_limits.default_limit()),
+            )
+            .or_report(vars),
+            method_stats_max_methods: number_env(vars, "METHOD_STATS_MAX_METHODS")
+                .or_report(vars)
+                .unwrap_or(defaults.method_stats_max_methods),
+            commitment_routing: bool_env(vars, "COMMITMENT_ROUTING", defaults.commitment_routing)
+                .or_report(vars),
+            commitment_max_slot_lag_processed: number_env(
+                vars,
+                "COMMITMENT_MAX_SLOT_LAG_PROCESSED",
+            )
+            .or_report(vars)
+            .unwrap_or(defaults.commitment_max_slot_lag_processed),
+            commitment_max_slot_lag_confirmed: number_env(
+                vars,
+                "COMMITMENT_MAX_SLOT_LAG_CONFIRMED",
+            )
+            .or_report(vars)
+            .unwrap_or(defaults.commitment_max_slot_lag_confirmed),
+            commitment_max_slot_lag_finalized: number_env(
+                vars,
+                "COMMITMENT_MAX_SLOT_LAG_FINALIZED",
+            )
+            .or_report(vars),
+            quorum_max_validators: number_env(vars, "QUORUM_MAX_VALIDATORS")
+                .or_report(vars)
+                .unwrap_or(defaults.quorum_max_validators),
+            shadow_sample_rate: rate_env(vars, "SHADOW_SAMPLE_RATE", defaults.shadow_sample_rate)
+                .or_report(vars),
+            shadow_ignore_fields: list_env(vars, "SHADOW_IGNORE_FIELDS")
+                .unwrap_or(defaults.shadow_ignore_fields),
+            fleet_concurrency: number_env(vars, "FLEET_CONCURRENCY")
+                .or_report(vars)
+                .unwrap_or(defaults.fleet_concurrency),
+            fleet_node_timeout: millis_env(
+                vars,
+                "FLEET_NODE_TIMEOUT_MS",
+                defaults.fleet_node_timeout,
+            )
+            .or_report(vars),
+            fleet_min_interval: millis_env(
+                vars,
+                "FLEET_MIN_INTERVAL_MS",
+                defaults.fleet_min_interval,
+            )
+            .or_report(vars),
+            cluster_nodes_sample: number_env(vars, "CLUSTER_NODES_SAMPLE")
+                .or_report(vars)
+                .unwrap_or(defaults.cluster_nodes_sample),
+            cluster_nodes_cache_ttl: secs_env(
+                vars,
+                "CLUSTER_NODES_CACHE_SECS",
+                defaults.cluster_nodes_cache_ttl,
+            )
+            .or_report(vars),
+            account_default_slice_bytes: number_env(vars, "ACCOUNT_DEFAULT_SLICE_BYTES")
+                .or_report(vars)
+                .unwrap_or(defaults.account_default_slice_bytes),
+            batch_id_check: parse_env(vars, "BATCH_ID_CHECK")
+                .or_report(vars)
+                .unwrap_or(defaults.batch_id_check),
+            strict_responses: bool_env(vars, "STRICT_RESPONSES", defaults.strict_responses)
+                .or_report(vars),
+            strict_response_max_bytes: number_env(vars, "STRICT_RESPONSE_MAX_BYTES")
+                .or_report(vars)
+                .unwrap_or(defaults.strict_response_max_bytes),
+            account_cache: bool_env(vars, "ACCOUNT_CACHE", defaults.account_cache).or_report(vars),
+            account_cache_ttl: millis_env(vars, "ACCOUNT_CACHE_TTL_MS", defaults.account_cache_ttl)
+                .or_report(vars),
+            account_cache_max_slot_lag: number_env(vars, "ACCOUNT_CACHE_MAX_SLOT_LAG")
+                .or_report(vars)
+                .unwrap_or(defaults.account_cache_max_slot_lag),
+            account_cache_max_entries: number_env(vars, "ACCOUNT_CACHE_MAX_ENTRIES")
+                .or_report(vars)
+                .unwrap_or(defaults.account_cache_max_entries),
+            account_cache_processed: bool_env(
+                vars,
+                "ACCOUNT_CACHE_PROCESSED",
+                defaults.account_cache_processed,
+            )
+            .or_report(vars),
+            admin_token: admin_token_env(vars).or_report(vars),
+            api_keys: api_keys_env(vars).or_report(vars),
+            api_keys_public: api_keys_public_env(vars)
+                .or_report(vars)
+                .unwrap_or(defaults.api_keys_public),
+            api_keys_reload_interval: secs_env(
+                vars,
+                "API_KEYS_RELOAD_SECS",
+                defaults.api_keys_reload_interval,
+            )
+            .or_report(vars),
+            api_key_rate_limit: rate_limit_env(vars, "API_KEY_RATE_LIMIT").or_report(vars),
+            hmac_max_skew: secs_env(vars, "HMAC_MAX_SKEW_SECS", defaults.hmac_max_skew)
+                .or_report(vars),
+            hmac_secret: vars
+                .var("HMAC_SECRET")
+                .ok()
+                .filter(|secret| !secret.trim().is_empty()),
+            jwt: jwt_env(vars).or_report(vars),
+            metrics_enabled: bool_env(vars, "METRICS_ENABLED", defaults.metrics_enabled)
+                .or_report(vars),
+            latency_buckets_ms: buckets_env(vars, "LATENCY_BUCKETS_MS")
+                .or_report(vars)
+                .unwrap_or(defaults.latency_buckets_ms),
+            slow_request_threshold: number_env(vars, "SLOW_REQUEST_THRESHOLD_MS")
+                .or_report(vars)
+                .map(Duration::from_millis),
+            log_format: parse_env(vars, "LOG_FORMAT")
+                .or_report(vars)
+                .unwrap_or(defaults.log_format),
+            statsd_address: vars
+                .var("STATSD_ADDRESS")
+                .ok()
+                .map(|address| address.trim().to_string())
+                .filter(|address| !address.is_empty()),
+            statsd_prefix: vars
+                .var("STATSD_PREFIX")
+                .map(|prefix| prefix.trim().to_string())
+                .unwrap_or(defaults.statsd_prefix),
+            statsd_tags: statsd_tags_env(vars, "STATSD_TAGS")
+                .or_report(vars)
+                .unwrap_or(defaults.statsd_tags),
+            statsd_interval: millis_env(vars, "STATSD_INTERVAL_MS", defaults.statsd_interval)
+                .or_report(vars),
+            sentry_dsn: vars
+                .var("SENTRY_DSN")
+                .ok()
+                .map(|dsn| dsn.trim().to_string())
+                .filter(|dsn| !dsn.is_empty()),
+            access_log_path: vars
+                .var("ACCESS_LOG_PATH")
+                .ok()
+                .filter(|path| !path.trim().is_empty())
+                .map(|path| PathBuf::from(path.trim())),
+            access_log_rotation: parse_env(vars, "ACCESS_LOG_ROTATION")
+                .or_report(vars)
+                .unwrap_or(defaults.access_log_rotation),
+            access_log_max_bytes: number_env(vars, "ACCESS_LOG_MAX_BYTES")
+                .or_report(vars)
+                .unwrap_or(defaults.access_log_max_bytes),
+            access_log_max_files: number_env(vars, "ACCESS_LOG_MAX_FILES")
+                .or_report(vars)
+                .unwrap_or(defaults.access_log_max_files),
+            log_bodies: bool_env(vars, "LOG_BODIES", defaults.log_bodies).or_report(vars),
+            log_body_max_bytes: number_env(vars, "LOG_BODY_MAX_BYTES")
+                .or_report(vars)
+                .unwrap_or(defaults.log_body_max_bytes),
+            log_body_redact: redact_rules_env(vars, "LOG_BODY_REDACT", defaults.log_body_redact)
+                .or_report(vars),
+            key_usage_max_keys: number_env(vars, "KEY_USAGE_MAX_KEYS")
+                .or_report(vars)
+                .unwrap_or(defaults.key_usage_max_keys),
+            key_usage_retention: secs_env(
+                vars,
+                "KEY_USAGE_RETENTION_SECS",
+                defaults.key_usage_retention,
+            )
+            .or_report(vars),
+            key_metrics_max_keys: number_env(vars, "KEY_METRICS_MAX_KEYS")
+                .or_report(vars)
+                .unwrap_or(defaults.key_metrics_max_keys),
+            ping_rate_limit: number_env(vars, "PING_RATE_LIMIT")
+                .or_report(vars)
+                .unwrap_or(defaults.ping_rate_limit),
+            stats_snapshot_path: vars
+                .var("STATS_SNAPSHOT_PATH")
+                .ok()
+                .filter(|path| !path.trim().is_empty())
+                .map(|path| PathBuf::from(path.trim())),
+            stats_snapshot_interval: secs_env(
+                vars,
+                "STATS_SNAPSHOT_INTERVAL_SECS",
+                defaults.stats_snapshot_interval,
+            )
+            .or_report(vars),
+            stats_snapshot_max_age: secs_env(
+                vars,
+                "STATS_SNAPSHOT_MAX_AGE_SECS",
+                defaults.stats_snapshot_max_age,
+            )
+            .or_report(vars),
+            unknown_config_keys: Vec::new(),
+            env_file_warnings: Vec::new(),
+            sources: BTreeMap::new(),
+        }
+    }
+
+    pub fn builder() -> SettingsBuilder {
+        SettingsBuilder::default()
+    }
+
+    // What no single variable can be checked for alone, plus the bounds the
+    // loaders enforce, for settings that did not come through them. Every
+    // problem found is returned.
+    fn check(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        let mut number = |name: &str, value: String, expected: &str| {
+            problems.push(ConfigError::InvalidNumber {
+                name: name.to_string(),
+                value,
+                expected: expected.to_string(),
+            })
+        };
+        for (name, value) in [
+            ("WORKERS", self.workers),
+            (
+                "UPSTREAM_MAX_CONNECTIONS_PER_HOST",
+                self.upstream_max_connections_per_host,
+            ),
+        ] {
+            if value == 0 {
+                number(name, "0".to_string(), AT_LEAST_ONE);
+            }
+        }
+        if self.upstream_conn_lifetime.is_zero() {
+            number("UPSTREAM_CONN_LIFETIME_SECS", "0".to_string(), AT_LEAST_ONE);
+        }
+        if !(0.0..=1.0).contains(&self.shadow_sample_rate) {
+            number(
+                "SHADOW_SAMPLE_RATE",
+                self.shadow_sample_rate.to_string(),
+                FRACTION,
+            );
+        }
+        if !(self.ping_rate_limit > 0.0 && self.ping_rate_limit.is_finite()) {
+            number(
+                "PING_RATE_LIMIT",
+                self.ping_rate_limit.to_string(),
+                "a positive number",
+            );
+        }
+        if self.access_log_max_bytes == 0 {
+            number("ACCESS_LOG_MAX_BYTES", "0".to_string(), AT_LEAST_ONE);
+        }
+        if self.listeners.is_empty() {
+            problems.push(ConfigError::InvalidValue {
+                name: "BIND_ADDRESS".to_string(),
+                value: "(no listen address)".to_string(),
+            });
+        }
+        if self.tls.is_none()
+            && let Some(listener) = self.listeners.iter().find(|listener| listener.tls)
+        {
+            problems.push(tls_listener_conflict(&listener.address));
+        }
+        // An admin token that is also a client key would let that client
+        // administer the proxy.
+        if let (Some(token), Some(keys)) = (&self.admin_token, &self.api_keys)
+            && keys.find(token.as_str()).is_some()
+        {
+            problems.push(ConfigError::Conflict {
+                name: "ADMIN_TOKEN".to_string(),
+                other: "API_KEYS".to_string(),
+                reason: "the admin token is also an API key".to_string(),
+            });
+        }
+        ConfigError::from_problems(problems)
+    }
+}
+
+// Programmatic configuration, for embedders and tests: defaults, then the
+// settings given, checked on `build` as loading from the environment would.
+#[derive(Debug, Clone, Default)]
+pub struct SettingsBuilder {
+    settings: Settings,
+    // Parsed into `listeners` on `build`, once TLS is known.
+    bind_address: Option<String>,
+}
+
+impl From<Settings> for SettingsBuilder {
+    fn from(settings: Settings) -> Self {
+        Self {
+            settings,
+            bind_address: None,
+        }
+    }
+}
+
+// One setter per field; those for optional settings take the value itself.
+macro_rules! setters {
+    ($($field:ident: Some($ty:ty)),* $(,)?) => {
+        $(
+            pub fn $field(mut self, value: $ty) -> Self {
+                self.settings.$field = Some(value);
+                self
+            }
+        )*
+    };
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        $(
+            pub fn $field(mut self, value: $ty) -> Self {
+                self.settings.$field = value;
+                self
+            }
+        )*
+    };
+}
+
+impl SettingsBuilder {
+    // In `BIND_ADDRESS` syntax, listener options included.
+    pub fn bind_address(mut self, address: impl Into<String>) -> Self {
+        self.bind_address = Some(address.into());
+        self
+    }
+
+    pub fn listeners(mut self, listeners: Vec<Listener>) -> Self {
+        self.bind_address = None;
+        self.settings.listeners = listeners;
+        self
+    }
+
+    setters! {
+        workers: usize,
+        shutdown_timeout: Duration,
+        validators_csv: PathBuf,
+        validators_reload_interval: Duration,
+        dns_cache_ttl: Duration,
+        max_retries: usize,
+        upstream_timeout: Duration,
+        upstream_max_connections_per_host: usize,
+        upstream_keepalive: Duration,
+        upstream_conn_lifetime: Duration,
+        upstream_disconnect_timeout: Duration,
+        retry_on_429: bool,
+        cooldown_on_429: bool,
+        default_429_cooldown: Duration,
+        shed_when_cooling_down: bool,
+        upstream_rate_limit_wait: Duration,
+        forward_retry_after: bool,
+        upstream_error_policy: StatusPolicies,
+        upstream_user_agent: HeaderValue,
+        preserve_client_user_agent: bool,
+        rewrite_host: bool,
+        enforce_json_content_type: bool,
+        lenient_content_type: bool,
+        compression_enabled: bool,
+        compression_min_bytes: usize,
+        coalesce_requests: bool,
+        coalesce_methods: Vec<String>,
+        coalesce_max_age: Duration,
+        routing_rules: RoutingRules,
+        response_cache: bool,
+        cache_immutable_methods: Vec<String>,
+        cache_immutable_ttl: Duration,
+        cache_short_methods: Vec<String>,
+        cache_short_ttl: Duration,
+        cache_blockhash_ttl: Duration,
+        cache_blockhash_max_slot_lag: u64,
+        cache_max_entries: usize,
+        jsonrpc_errors: bool,
+        error_format: ErrorFormat,
+        method_rate_limits: MethodRateLimits,
+        rate_limit_max_clients: usize,
+        rate_limit_headers: RateLimitHeaders,
+        concurrency_queue_timeout: Duration,
+        concurrency_queue_depth: usize,
+        concurrency_fair_queues: bool,
+        trusted_proxies: Vec<IpRange>,
+        ip_filter: IpFilter,
+        guardrail_enabled: bool,
+        guardrail_allowlist: Vec<String>,
+        response_size_limits: ResponseSizeLimits,
+        method_stats_max_methods: usize,
+        commitment_routing: bool,
+        commitment_max_slot_lag_processed: u64,
+        commitment_max_slot_lag_confirmed: u64,
+        quorum_max_validators: usize,
+        shadow_sample_rate: f64,
+        shadow_ignore_fields: Vec<String>,
+        fleet_concurrency: usize,
+        fleet_node_timeout: Duration,
+        fleet_min_interval: Duration,
+        cluster_nodes_sample: usize,
+        cluster_nodes_cache_ttl: Duration,
+        account_default_slice_bytes: usize,
+        batch_id_check: BatchIdCheck,
+        strict_responses: bool,
+        strict_response_max_bytes: usize,
+        account_cache: bool,
+        account_cache_ttl: Duration,
+        account_cache_max_slot_lag: u64,
+        account_cache_max_entries: usize,
+        account_cache_processed: bool,
+        api_keys_public: Vec<String>,
+        api_keys_reload_interval: Duration,
+        hmac_max_skew: Duration,
+        metrics_enabled: bool,
+        latency_buckets_ms: Vec<f64>,
+        log_format: LogFormat,
+        statsd_prefix: String,
+        statsd_tags: Vec<String>,
+        statsd_interval: Duration,
+        access_log_rotation: Rotation,
+        access_log_max_bytes: u64,
+        access_log_max_files: usize,
+        log_bodies: bool,
+        log_body_max_bytes: usize,
+        log_body_redact: Vec<RedactRule>,
+        key_usage_max_keys: usize,
+        key_usage_retention: Duration,
+        key_metrics_max_keys: usize,
+        ping_rate_limit: f64,
+        stats_snapshot_interval: Duration,
+        stats_snapshot_max_age: Duration,
+    }
+
+    // `RESPONSE_SIZE_LIMIT_BYTES`: the limit for methods without one in
+    // `response_size_limits`.
+    pub fn response_size_limit_bytes(mut self, bytes: usize) -> Self {
+        let limits = &mut self.settings.response_size_limits;
+        *limits = limits.clone().with_default(bytes);
+        self
+    }
+
+    setters! {
+        tls: Some(TlsSettings),
+        health_bind_address: Some(String),
+        default_location: Some(String),
+        validators_inline: Some(String),
+        upstream_rate_limit: Some(KeyRateLimit),
+        proxied_by: Some(HeaderValue),
+        global_rate_limit: Some(KeyRateLimit),
+        client_rate_limit: Some(KeyRateLimit),
+        monitoring_rate_limit: Some(KeyRateLimit),
+        max_concurrent_requests: Some(usize),
+        cors: Some(CorsSettings),
+        guardrail_bypass_header: Some(HeaderName),
+        guardrail_bypass_value: Some(HeaderValue),
+        commitment_max_slot_lag_finalized: Some(u64),
+        admin_token: Some(AdminToken),
+        api_keys: Some(ApiKeys),
+        api_key_rate_limit: Some(KeyRateLimit),
+        hmac_secret: Some(String),
+        jwt: Some(JwtSettings),
+        slow_request_threshold: Some(Duration),
+        statsd_address: Some(String),
+        sentry_dsn: Some(String),
+        access_log_path: Some(PathBuf),
+        stats_snapshot_path: Some(PathBuf),
+    }
+
+    pub fn build(self) -> Result<Settings, ConfigError> {
+        let mut settings = self.settings;
+        if let Some(bind_address) = self.bind_address {
+            settings.listeners = listeners_env(&bind_address, settings.tls.as_ref())?;
+            settings.bind_address = bind_address;
+        }
+        settings.check()?;
+        Ok(settings)
+    }
+}
+
+// Setting values by environment variable name: the command line first, then
+// the environment, then the config file, whose keys are the same names in
+// lower case. Tables nest with `_`, so `[upstream] timeout_ms` is
+// `UPSTREAM_TIMEOUT_MS`.
+#[derive(Default)]
+struct Vars {
+    args: BTreeMap<String, String>,
+    env: BTreeMap<String, String>,
+    file: BTreeMap<String, String>,
+    // Every name asked for, to tell the file's unknown keys apart.
+    read: RefCell<BTreeSet<String>>,
+    // The names found, and where.
+    sources: RefCell<BTreeMap<String, Source>>,
+    // Everything wrong so far.
+    problems: RefCell<Vec<ConfigError>>,
+}
+
+// Loading goes on past a bad value, its type's default standing in, so that
+// every problem is reported at once.
+trait OrReport<T> {
+    fn or_report(self, vars: &Vars) -> T;
+}
+
+impl<T: Default> OrReport<T> for Result<T, ConfigError> {
+    fn or_report(self, vars: &Vars) -> T {
+        self.unwrap_or_else(|err| {
+            vars.report(err);
+            T::default()
         })
     }
 }
+
+impl Vars {
+    fn read_file(path: &Path) -> Result<BTreeMap<String, String>, ConfigError> {
+        let invalid = |message: String| ConfigError::ConfigFile {
+            path: path.display().to_string(),
+            message,
+        };
+        let contents = fs::read_to_string(path).map_err(|err| invalid(err.to_string()))?;
+        let table = contents
+            .parse::<toml::Table>()
+            .map_err(|err| invalid(err.to_string().trim_end().to_string()))?;
+        let mut file = BTreeMap::new();
+        flatten("", &table, &mut file).map_err(invalid)?;
+        Ok(file)
+    }
+
+    // Where a name already read was found.
+    fn source(&self, name: &str) -> Option<Source> {
+        self.sources.borrow().get(name).copied()
+    }
+
+    fn var(&self, name: &str) -> Result<String, env::VarError> {
+        self.read.borrow_mut().insert(name.to_string());
+        let (source, value) = [
+            (Source::Flag, &self.args),
+            (Source::Env, &self.env),
+            (Source::File, &self.file),
+        ]
+        .into_iter()
+        .find_map(|(source, values)| Some((source, values.get(name)?)))
+        .ok_or(env::VarError::NotPresent)?;
+        self.sources.borrow_mut().insert(name.to_string(), source);
+        Ok(value.clone())
+    }
+
+    fn report(&self, problem: ConfigError) {
+        self.problems.borrow_mut().push(problem);
+    }
+
+    // Names only read once another setting is present, but valid anyway.
+    fn declare(&self, names: &[&str]) {
+        let mut read = self.read.borrow_mut();
+        read.extend(names.iter().map(|name| name.to_string()));
+    }
+
+    // In the file's spelling.
+    fn unknown(&self) -> Vec<String> {
+        let read = self.read.borrow();
+        self.file
+            .keys()
+            .filter(|name| !read.contains(*name))
+            .map(|name| name.to_ascii_lowercase())
+            .collect()
+    }
+}
+
+// The process environment, prefixed names applied. Names or values that
+// are not Unicode are left out, as if unset.
+pub fn process_env() -> BTreeMap<String, String> {
+    with_prefix(
+        &env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+            .collect(),
+    )
+}
+
+// With `APP_ENV_PREFIX=TSA`, `TSA_WORKERS` stands for `WORKERS` and wins
+// over it when both are set; the bare names are still read otherwise.
+fn with_prefix(env: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    let prefix = match env.get("APP_ENV_PREFIX").map(|prefix| prefix.trim()) {
+        None | Some("") => return env.clone(),
+        Some(prefix) if prefix.ends_with('_') => prefix.to_string(),
+        Some(prefix) => format!("{prefix}_"),
+    };
+    let mut resolved = env.clone();
+    for (name, value) in env {
+        if let Some(name) = name.strip_prefix(&prefix)
+            && !name.is_empty()
+        {
+            resolved.insert(name.to_string(), value.clone());
+        }
+    }
+    resolved
+}
+
+// Scalars as their environment variable would spell them, and arrays of
+// scalars comma separated. `[[listeners]]` tables are the one array of
+// tables: they spell out `bind_address`, one `key=value` entry per table.
+fn flatten(
+    prefix: &str,
+    table: &toml::Table,
+    out: &mut BTreeMap<String, String>,
+) -> Result<(), String> {
+    for (key, value) in table {
+        let mut name = format!("{prefix}{}", key.to_ascii_uppercase());
+        let listeners = name == "LISTENERS";
+        if listeners {
+            name = "BIND_ADDRESS".to_string();
+        }
+        let unsupported = || format!("unsupported value for {key}");
+        let raw = match value {
+            toml::Value::Table(table) => {
+                flatten(&format!("{name}_"), table, out)?;
+                continue;
+            }
+            toml::Value::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    toml::Value::Table(entry) if listeners => entry
+                        .iter()
+                        .map(|(key, value)| Some(format!("{key}={}", scalar(value)?)))
+                        .collect::<Option<Vec<_>>>()
+                        .map(|pairs| pairs.join(" "))
+                        .ok_or_else(unsupported),
+                    item => scalar(item).ok_or_else(unsupported),
+                })
+                .collect::<Result<Vec<_>, _>>()?
+                .join(","),
+            value => scalar(value).ok_or_else(unsupported)?,
+        };
+        if out.insert(name.clone(), raw).is_some() {
+            return Err(format!("{} is set twice", name.to_ascii_lowercase()));
+        }
+    }
+    Ok(())
+}
+
+fn scalar(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(raw) => Some(raw.clone()),
+        toml::Value::Integer(raw) => Some(raw.to_string()),
+        toml::Value::Float(raw) => Some(raw.to_string()),
+        toml::Value::Boolean(raw) => Some(raw.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_env<T: FromStr>(vars: &Vars, name: &str) -> Result<Option<T>, ConfigError> {
+    match vars.var(name) {
+        Ok(raw) => raw
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| ConfigError::InvalidValue {
+                name: name.to_string(),
+                value: raw,
+            }),
+        Err(_) => Ok(None),
+    }
+}
+
+const AT_LEAST_ONE: &str = "a whole number of at least 1";
+const FRACTION: &str = "a number between 0 and 1";
+
+// What `number_env` reads, and how a value it cannot parse is described.
+trait Number: FromStr {
+    const EXPECTED: &'static str;
+}
+
+impl Number for u32 {
+    const EXPECTED: &'static str = "a whole number";
+}
+
+impl Number for u64 {
+    const EXPECTED: &'static str = "a whole number";
+}
+
+impl Number for usize {
+    const EXPECTED: &'static str = "a whole number";
+}
+
+impl Number for f64 {
+    const EXPECTED: &'static str = "a number";
+}
+
+fn number_env<T: Number>(vars: &Vars, name: &str) -> Result<Option<T>, ConfigError> {
+    match vars.var(name) {
+        Ok(raw) => raw
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| ConfigError::InvalidNumber {
+                name: name.to_string(),
+                value: raw,
+                expected: T::EXPECTED.to_string(),
+            }),
+        Err(_) => Ok(None),
+    }
+}
+
+fn secs_env(vars: &Vars, name: &str, default: Duration) -> Result<Duration, ConfigError> {
+    Ok(number_env(vars, name)?
+        .map(Duration::from_secs)
+        .unwrap_or(default))
+}
+
+fn millis_env(vars: &Vars, name: &str, default: Duration) -> Result<Duration, ConfigError> {
+    Ok(number_env(vars, name)?
+        .map(Duration::from_millis)
+        .unwrap_or(default))
+}
+
+// Zero would fail every call at once, and past the cap a stuck validator
+// holds its caller for longer than any client waits.
+fn timeout_env(vars: &Vars, name: &str, default: Duration) -> Result<Duration, ConfigError> {
+    const MAX_TIMEOUT: Duration = Duration::from_secs(600);
+    let timeout = millis_env(vars, name, default)?;
+    if timeout.is_zero() || timeout > MAX_TIMEOUT {
+        return Err(ConfigError::InvalidNumber {
+            name: name.to_string(),
+            value: timeout.as_millis().to_string(),
+            expected: format!("between 1 and {} ms", MAX_TIMEOUT.as_millis()),
+        });
+    }
+    Ok(timeout)
+}
+
+// For counts and lifetimes where zero would leave nothing usable.
+fn positive_env(vars: &Vars, name: &str, default: u64) -> Result<u64, ConfigError> {
+    let value = number_env(vars, name)?.unwrap_or(default);
+    if value == 0 {
+        return Err(ConfigError::InvalidNumber {
+            name: name.to_string(),
+            value: "0".to_string(),
+            expected: AT_LEAST_ONE.to_string(),
+        });
+    }
+    Ok(value)
+}
+
+fn list_env(vars: &Vars, name: &str) -> Option<Vec<String>> {
+    vars.var(name).ok().map(|raw| {
+        raw.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(String::from)
+            .collect()
+    })
+}
+
+// `key:value` or bare tags; `|` and `#` would break the datagram format.
+fn statsd_tags_env(vars: &Vars, name: &str) -> Result<Option<Vec<String>>, ConfigError> {
+    let Some(tags) = list_env(vars, name) else {
+        return Ok(None);
+    };
+    if let Some(tag) = tags.iter().find(|tag| tag.contains(['|', '#', '\n'])) {
+        return Err(ConfigError::InvalidValue {
+            name: name.to_string(),
+            value: tag.clone(),
+        });
+    }
+    Ok(Some(tags))
+}
+
+// Extra rules on top of the defaults, which cannot be switched off.
+fn redact_rules_env(
+    vars: &Vars,
+    name: &str,
+    mut rules: Vec<RedactRule>,
+) -> Result<Vec<RedactRule>, ConfigError> {
+    for raw in list_env(vars, name).unwrap_or_default() {
+        let rule = raw.parse().map_err(|_| ConfigError::InvalidValue {
+            name: name.to_string(),
+            value: raw.clone(),
+        })?;
+        rules.push(rule);
+    }
+    Ok(rules)
+}
+
+// Inline JSON in ROUTING_RULES takes precedence over ROUTING_RULES_FILE.
+fn routing_rules_env(vars: &Vars) -> Result<Option<RoutingRules>, ConfigError> {
+    let file = vars.var("ROUTING_RULES_FILE");
+    if let Ok(raw) = vars.var("ROUTING_RULES")
+        && !raw.trim().is_empty()
+    {
+        return Ok(Some(RoutingRules::from_json(&raw)?));
+    }
+
+    match file {
+        Ok(path) if !path.trim().is_empty() => {
+            Ok(Some(RoutingRules::from_file(Path::new(path.trim()))?))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn method_rate_limits_env(
+    vars: &Vars,
+    name: &str,
+) -> Result<Option<MethodRateLimits>, ConfigError> {
+    match vars.var(name) {
+        Ok(raw) if raw.trim().is_empty() => Ok(None),
+        Ok(raw) => {
+            MethodRateLimits::from_json(&raw)
+                .map(Some)
+                .map_err(|_| ConfigError::InvalidValue {
+                    name: name.to_string(),
+                    value: raw,
+                })
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+fn response_size_limits_env(
+    vars: &Vars,
+    default: usize,
+) -> Result<ResponseSizeLimits, ConfigError> {
+    match vars.var("RESPONSE_SIZE_LIMITS") {
+        Ok(raw) if !raw.trim().is_empty() => {
+            ResponseSizeLimits::from_json(default, &raw).map_err(|_| ConfigError::InvalidValue {
+                name: "RESPONSE_SIZE_LIMITS".to_string(),
+                value: raw,
+            })
+        }
+        _ => Ok(ResponseSizeLimits::new(default, Vec::new())),
+    }
+}
+
+// Keys from `API_KEYS` and `API_KEYS_FILE` together; auth stays off when
+// neither is set.
+fn api_keys_env(vars: &Vars) -> Result<Option<ApiKeys>, ConfigError> {
+    let inline = vars
+        .var("API_KEYS")
+        .ok()
+        .filter(|raw| !raw.trim().is_empty());
+    let file = vars
+        .var("API_KEYS_FILE")
+        .ok()
+        .filter(|path| !path.trim().is_empty());
+    if inline.is_none() && file.is_none() {
+        return Ok(None);
+    }
+
+    let keys = match inline {
+        Some(raw) => ApiKeys::new(ApiKeys::parse_inline(&raw).map_err(|value| {
+            ConfigError::InvalidValue {
+                name: "API_KEYS".to_string(),
+                value,
+            }
+        })?),
+        None => ApiKeys::default(),
+    };
+    match file {
+        Some(path) => {
+            keys.with_file(path.trim())
+                .map(Some)
+                .map_err(|err| ConfigError::InvalidValue {
+                    name: "API_KEYS_FILE".to_string(),
+                    value: format!("{path}: {err}"),
+                })
+        }
+        None => Ok(Some(keys)),
+    }
+}
+
+fn api_keys_public_env(vars: &Vars) -> Result<Option<Vec<String>>, ConfigError> {
+    let Some(paths) = list_env(vars, "API_KEYS_PUBLIC") else {
+        return Ok(None);
+    };
+    if let Some(path) = paths
+        .iter()
+        .find(|path| !auth::OPTIONAL_PATHS.contains(&path.as_str()))
+    {
+        return Err(ConfigError::InvalidValue {
+            name: "API_KEYS_PUBLIC".to_string(),
+            value: path.clone(),
+        });
+    }
+    Ok(Some(paths))
+}
+
+// `ADMIN_ENABLED` defaults to whether `ADMIN_TOKEN` is set; turning it on
+// without one is a startup error rather than an open admin API.
+fn admin_token_env(vars: &Vars) -> Result<Option<AdminToken>, ConfigError> {
+    let token = vars
+        .var("ADMIN_TOKEN")
+        .ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+        .map(AdminToken::new);
+    match (bool_env(vars, "ADMIN_ENABLED", token.is_some())?, token) {
+        (true, None) => Err(ConfigError::Conflict {
+            name: "ADMIN_ENABLED".to_string(),
+            other: "ADMIN_TOKEN".to_string(),
+            reason: "admin endpoints are enabled without a token".to_string(),
+        }),
+        (true, token) => Ok(token),
+        (false, _) => Ok(None),
+    }
+}
+
+// One of `JWT_HS256_SECRET` and `JWT_JWKS_URL`, plus the claim checks.
+fn jwt_env(vars: &Vars) -> Result<Option<JwtSettings>, ConfigError> {
+    let secret = vars
+        .var("JWT_HS256_SECRET")
+        .ok()
+        .filter(|secret| !secret.is_empty());
+    let jwks = vars
+        .var("JWT_JWKS_URL")
+        .ok()
+        .filter(|url| !url.trim().is_empty());
+    vars.declare(&[
+        "JWT_ISSUER",
+        "JWT_AUDIENCE",
+        "JWT_LEEWAY_SECS",
+        "JWT_CLIENT_CLAIM",
+        "JWT_JWKS_REFRESH_SECS",
+    ]);
+    let key = match (secret, jwks) {
+        (None, None) => return Ok(None),
+        (Some(secret), None) => JwtKey::Hs256(secret.into_bytes()),
+        (None, Some(raw)) => match url::Url::parse(raw.trim()) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => JwtKey::Jwks(url),
+            _ => {
+                return Err(ConfigError::InvalidValue {
+                    name: "JWT_JWKS_URL".to_string(),
+                    value: raw,
+                });
+            }
+        },
+        (Some(_), Some(_)) => {
+            return Err(ConfigError::Conflict {
+                name: "JWT_JWKS_URL".to_string(),
+                other: "JWT_HS256_SECRET".to_string(),
+                reason: "only one may be set".to_string(),
+            });
+        }
+    };
+    let mut jwt = JwtSettings::new(key);
+    jwt.issuer = vars.var("JWT_ISSUER").ok().filter(|iss| !iss.is_empty());
+    jwt.audience = vars.var("JWT_AUDIENCE").ok().filter(|aud| !aud.is_empty());
+    jwt.leeway = secs_env(vars, "JWT_LEEWAY_SECS", jwt.leeway)?;
+    if let Ok(claim) = vars.var("JWT_CLIENT_CLAIM") {
+        if claim.trim().is_empty() {
+            return Err(ConfigError::InvalidValue {
+                name: "JWT_CLIENT_CLAIM".to_string(),
+                value: claim,
+            });
+        }
+        jwt.client_claim = claim.trim().to_string();
+    }
+    jwt.jwks_refresh = secs_env(vars, "JWT_JWKS_REFRESH_SECS", jwt.jwks_refresh)?;
+    Ok(Some(jwt))
+}
+
+fn listeners_env(raw: &str, tls: Option<&TlsSettings>) -> Result<Vec<Listener>, ConfigError> {
+    let invalid = |err: String| ConfigError::InvalidValue {
+        name: "BIND_ADDRESS".to_string(),
+        value: format!("{raw} ({err})"),
+    };
+    let mut listeners = listeners::parse(raw, tls.is_some()).map_err(invalid)?;
+    for listener in &mut listeners {
+        listener.address = bind_address_env("BIND_ADDRESS", &listener.address)?;
+    }
+    if tls.is_none()
+        && let Some(listener) = listeners.iter().find(|listener| listener.tls)
+    {
+        return Err(tls_listener_conflict(&listener.address));
+    }
+    Ok(listeners)
+}
+
+fn tls_listener_conflict(address: &str) -> ConfigError {
+    ConfigError::Conflict {
+        name: "BIND_ADDRESS".to_string(),
+        other: "TLS_CERT_FILE".to_string(),
+        reason: format!("{address} asks for TLS without TLS_CERT_FILE and TLS_KEY_FILE"),
+    }
+}
+
+fn bind_address_env(name: &str, raw: &str) -> Result<String, ConfigError> {
+    listeners::normalize_address(raw).map_err(|attempted| ConfigError::InvalidBindAddress {
+        name: name.to_string(),
+        input: raw.to_string(),
+        attempted,
+    })
+}
+
+// Both files or neither; the files themselves are read by `tls` at startup.
+fn tls_env(vars: &Vars) -> Result<Option<TlsSettings>, ConfigError> {
+    let path = |name| {
+        vars.var(name)
+            .ok()
+            .filter(|path| !path.trim().is_empty())
+            .map(PathBuf::from)
+    };
+    vars.declare(&["TLS_RELOAD_SECS"]);
+    let (cert_file, key_file) = match (path("TLS_CERT_FILE"), path("TLS_KEY_FILE")) {
+        (None, None) => return Ok(None),
+        (Some(cert_file), Some(key_file)) => (cert_file, key_file),
+        (Some(_), None) => {
+            return Err(ConfigError::Conflict {
+                name: "TLS_CERT_FILE".to_string(),
+                other: "TLS_KEY_FILE".to_string(),
+                reason: "set without TLS_KEY_FILE; give both or neither".to_string(),
+            });
+        }
+        (None, Some(_)) => {
+            return Err(ConfigError::Conflict {
+                name: "TLS_KEY_FILE".to_string(),
+                other: "TLS_CERT_FILE".to_string(),
+                reason: "set without TLS_CERT_FILE; give both or neither".to_string(),
+            });
+        }
+    };
+    let mut tls = TlsSettings::new(cert_file, key_file);
+    tls.reload_interval = secs_env(vars, "TLS_RELOAD_SECS", tls.reload_interval)?;
+    Ok(Some(tls))
+}
+
+// `{prefix}_RPS`, with `{prefix}_BURST` only alongside it.
+fn rate_limit_env(vars: &Vars, prefix: &str) -> Result<Option<KeyRateLimit>, ConfigError> {
+    let (rps_name, burst_name) = (format!("{prefix}_RPS"), format!("{prefix}_BURST"));
+    let burst = number_env::<u32>(vars, &burst_name)?;
+    let Some(rps) = number_env::<f64>(vars, &rps_name)? else {
+        return match burst {
+            Some(_) => Err(ConfigError::Conflict {
+                name: burst_name,
+                other: rps_name,
+                reason: "a burst needs a rate".to_string(),
+            }),
+            None => Ok(None),
+        };
+    };
+    if burst == Some(0) {
+        return Err(ConfigError::InvalidNumber {
+            name: burst_name,
+            value: "0".to_string(),
+            expected: AT_LEAST_ONE.to_string(),
+        });
+    }
+    KeyRateLimit::new(rps, burst)
+        .map(Some)
+        .ok_or_else(|| ConfigError::InvalidValue {
+            name: rps_name,
+            value: rps.to_string(),
+        })
+}
+
+fn cors_env(vars: &Vars) -> Result<Option<CorsSettings>, ConfigError> {
+    vars.declare(&[
+        "CORS_ALLOWED_HEADERS",
+        "CORS_MAX_AGE_SECS",
+        "CORS_ALLOW_CREDENTIALS",
+    ]);
+    let Ok(raw) = vars.var("CORS_ALLOWED_ORIGINS") else {
+        return Ok(None);
+    };
+    if raw.trim().is_empty() {
+        return Ok(None);
+    }
+    let origins = AllowedOrigins::parse(&raw).ok_or_else(|| ConfigError::InvalidValue {
+        name: "CORS_ALLOWED_ORIGINS".to_string(),
+        value: raw.clone(),
+    })?;
+    let mut cors = CorsSettings::new(origins);
+    for name in list_env(vars, "CORS_ALLOWED_HEADERS").unwrap_or_default() {
+        let name = name.to_ascii_lowercase();
+        if HeaderName::from_bytes(name.as_bytes()).is_err() {
+            return Err(ConfigError::InvalidValue {
+                name: "CORS_ALLOWED_HEADERS".to_string(),
+                value: name,
+            });
+        }
+        if !cors.allowed_headers.contains(&name) {
+            cors.allowed_headers.push(name);
+        }
+    }
+    cors.max_age = secs_env(vars, "CORS_MAX_AGE_SECS", cors.max_age)?;
+    cors.allow_credentials = bool_env(vars, "CORS_ALLOW_CREDENTIALS", false)?;
+    cors.validate().map_err(|reason| ConfigError::Conflict {
+        name: "CORS_ALLOW_CREDENTIALS".to_string(),
+        other: "CORS_ALLOWED_ORIGINS".to_string(),
+        reason,
+    })?;
+    Ok(Some(cors))
+}
+
+// Addresses or CIDR blocks, comma separated.
+fn ip_ranges_env(vars: &Vars, name: &str) -> Result<Vec<IpRange>, ConfigError> {
+    list_env(vars, name)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|range| {
+            range.parse().map_err(|_| ConfigError::InvalidValue {
+                name: name.to_string(),
+                value: range,
+            })
+        })
+        .collect()
+}
+
+fn status_policies_env(vars: &Vars, default: StatusPolicy) -> Result<StatusPolicies, ConfigError> {
+    let raw = vars
+        .var("UPSTREAM_ERROR_POLICY_OVERRIDES")
+        .unwrap_or_default();
+    StatusPolicies::parse_overrides(&raw)
+        .map(|overrides| StatusPolicies::new(default, overrides))
+        .ok_or_else(|| ConfigError::InvalidValue {
+            name: "UPSTREAM_ERROR_POLICY_OVERRIDES".to_string(),
+            value: raw,
+        })
+}
+
+fn header_name_env(vars: &Vars, name: &str) -> Result<Option<HeaderName>, ConfigError> {
+    match vars.var(name) {
+        Ok(raw) if raw.trim().is_empty() => Ok(None),
+        Ok(raw) => HeaderName::from_bytes(raw.trim().as_bytes())
+            .map(Some)
+            .map_err(|_| ConfigError::InvalidValue {
+                name: name.to_string(),
+                value: raw,
+            }),
+        Err(_) => Ok(None),
+    }
+}
+
+fn header_env(vars: &Vars, name: &str) -> Result<Option<HeaderValue>, ConfigError> {
+    match vars.var(name) {
+        Ok(raw) if raw.trim().is_empty() => Ok(None),
+        Ok(raw) => {
+            HeaderValue::from_str(raw.trim())
+                .map(Some)
+                .map_err(|_| ConfigError::InvalidValue {
+                    name: name.to_string(),
+                    value: raw,
+                })
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+// A fraction in [0, 1].
+fn rate_env(vars: &Vars, name: &str, default: f64) -> Result<f64, ConfigError> {
+    match number_env::<f64>(vars, name)? {
+        Some(rate) if (0.0..=1.0).contains(&rate) => Ok(rate),
+        Some(rate) => Err(ConfigError::InvalidNumber {
+            name: name.to_string(),
+            value: rate.to_string(),
+            expected: FRACTION.to_string(),
+        }),
+        None => Ok(default),
+    }
+}
+
+// Comma-separated, strictly increasing upper bounds in milliseconds.
+fn buckets_env(vars: &Vars, name: &str) -> Result<Option<Vec<f64>>, ConfigError> {
+    let Ok(raw) = vars.var(name) else {
+        return Ok(None);
+    };
+    let invalid = || ConfigError::InvalidValue {
+        name: name.to_string(),
+        value: raw.clone(),
+    };
+    let bounds = raw
+        .split(',')
+        .map(|item| item.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid())?;
+    if !histogram::valid_buckets(&bounds) {
+        return Err(invalid());
+    }
+    Ok(Some(bounds))
+}
+
+fn bool_env(vars: &Vars, name: &str, default: bool) -> Result<bool, ConfigError> {
+    match vars.var(name) {
+        Ok(raw) => match raw.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok(true),
+            "0" | "false" | "no" | "off" => Ok(false),
+            _ => Err(ConfigError::InvalidValue {
+                name: name.to_string(),
+                value: raw,
+            }),
+        },
+        Err(_) => Ok(default),
+    }
+}
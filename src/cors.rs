@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use actix_web::{
+    Error, HttpResponse,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        Method,
+        header::{self, HeaderMap, HeaderValue},
+    },
+    middleware::Next,
+    web::Data,
+};
+
+use crate::{
+    app_state::AppState, errors::AppError, proxy, request_id::X_REQUEST_ID, rest,
+    trace_context::TRACEPARENT,
+};
+
+// Always allowed on top of `CORS_ALLOWED_HEADERS`: the JSON body, the API
+// key and the headers that steer selection and retries.
+pub const DEFAULT_HEADERS: [&str; 6] = [
+    "content-type",
+    "authorization",
+    X_REQUEST_ID,
+    TRACEPARENT,
+    rest::X_VALIDATOR,
+    proxy::X_NO_RETRY,
+];
+
+// Response headers a page may read.
+const EXPOSED_HEADERS: &str = "x-request-id, x-validator, x-cache, x-retries, retry-after, \
+    x-location-defaulted, x-ratelimit-limit, x-ratelimit-remaining, x-ratelimit-reset, \
+    ratelimit-limit, ratelimit-remaining, ratelimit-reset";
+
+const ALLOWED_METHODS: &str = "GET, POST, OPTIONS";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AllowedOrigins {
+    Any,
+    List(Vec<String>),
+}
+
+impl AllowedOrigins {
+    // `*`, or origins such as `https://app.example`, comma separated.
+    pub fn parse(raw: &str) -> Option<Self> {
+        let origins: Vec<String> = raw
+            .split(',')
+            .map(|origin| origin.trim().trim_end_matches('/').to_ascii_lowercase())
+            .filter(|origin| !origin.is_empty())
+            .collect();
+        match origins.as_slice() {
+            [] => None,
+            [any] if any == "*" => Some(Self::Any),
+            _ if origins
+                .iter()
+                .any(|origin| origin == "*" || !origin.contains("://")) =>
+            {
+                None
+            }
+            _ => Some(Self::List(origins)),
+        }
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        match self {
+            Self::Any => true,
+            Self::List(origins) => origins
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(origin)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsSettings {
+    pub origins: AllowedOrigins,
+    // Lowercase, `DEFAULT_HEADERS` included.
+    pub allowed_headers: Vec<String>,
+    pub max_age: Duration,
+    pub allow_credentials: bool,
+}
+
+impl CorsSettings {
+    pub fn new(origins: AllowedOrigins) -> Self {
+        Self {
+            origins,
+            allowed_headers: DEFAULT_HEADERS.map(String::from).to_vec(),
+            max_age: Duration::from_secs(600),
+            allow_credentials: false,
+        }
+    }
+
+    // Browsers refuse credentials with a wildcard origin, so the pair is a
+    // configuration mistake rather than something to paper over.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.allow_credentials && self.origins == AllowedOrigins::Any {
+            return Err("credentials cannot be allowed for every origin".to_string());
+        }
+        Ok(())
+    }
+
+    fn insert_origin(&self, headers: &mut HeaderMap, origin: &HeaderValue) {
+        let allowed = match self.origins {
+            AllowedOrigins::Any => HeaderValue::from_static("*"),
+            AllowedOrigins::List(_) => origin.clone(),
+        };
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allowed);
+        if self.allow_credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+        if let AllowedOrigins::List(_) = self.origins {
+            headers.append(header::VARY, HeaderValue::from_static("Origin"));
+        }
+    }
+}
+
+fn preflight(cors: &CorsSettings, origin: &HeaderValue) -> HttpResponse {
+    let mut response = HttpResponse::NoContent();
+    response
+        .insert_header((header::ACCESS_CONTROL_ALLOW_METHODS, ALLOWED_METHODS))
+        .insert_header((
+            header::ACCESS_CONTROL_ALLOW_HEADERS,
+            cors.allowed_headers.join(", "),
+        ))
+        .insert_header((header::ACCESS_CONTROL_MAX_AGE, cors.max_age.as_secs()));
+    let mut response = response.finish();
+    cors.insert_origin(response.headers_mut(), origin);
+    response
+}
+
+// Wrapped outermost on the CORS-enabled resources, so a preflight is
+// answered before auth, limits or validator selection, and rejections still
+// carry the headers a page needs to read them.
+pub async fn handle(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let cors = req
+        .app_data::<Data<AppState>>()
+        .and_then(|state| state.settings().cors.clone());
+    let origin = req.headers().get(header::ORIGIN).cloned();
+    let (Some(cors), Some(origin)) = (cors, origin) else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    };
+    let allowed = origin
+        .to_str()
+        .is_ok_and(|origin| cors.origins.allows(origin));
+
+    let is_preflight = req.method() == Method::OPTIONS
+        && req
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    if is_preflight && !allowed {
+        let err = AppError::Forbidden("origin not allowed".to_string());
+        return Ok(req.error_response(err).map_into_right_body());
+    }
+    if is_preflight {
+        let response = preflight(&cors, &origin);
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+
+    let mut response = next.call(req).await?;
+    if allowed {
+        let headers = response.headers_mut();
+        cors.insert_origin(headers, &origin);
+        headers.insert(
+            header::ACCESS_CONTROL_EXPOSE_HEADERS,
+            HeaderValue::from_static(EXPOSED_HEADERS),
+        );
+    }
+    Ok(response.map_into_left_body())
+}
@@ -0,0 +1,178 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error as StdError;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use actix_tls::connect::Resolve;
+use tracing::{debug, info, warn};
+
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output = io::Result<Vec<SocketAddr>>> + Send + 'a>>;
+
+pub trait HostResolver: Send + Sync {
+    fn resolve<'a>(&'a self, host: &'a str, port: u16) -> ResolveFuture<'a>;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemResolver;
+
+impl HostResolver for SystemResolver {
+    fn resolve<'a>(&'a self, host: &'a str, port: u16) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((host, port)).await?;
+            Ok(addrs.collect())
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedAddrs {
+    addrs: Vec<SocketAddr>,
+    resolved_at: Instant,
+}
+
+pub struct DnsCache {
+    resolver: Arc<dyn HostResolver>,
+    ttl: Duration,
+    entries: RwLock<HashMap<(String, u16), CachedAddrs>>,
+    generation: AtomicU64,
+}
+
+impl DnsCache {
+    pub fn new(resolver: Arc<dyn HostResolver>, ttl: Duration) -> Self {
+        Self {
+            resolver,
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    // Bumped whenever a refresh observes a changed address set; upstream
+    // clients are rebuilt when it moves, dropping pooled connections to the
+    // previous addresses.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    pub async fn lookup(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        if !self.ttl.is_zero()
+            && let Some(cached) = self.cached(host, port)
+            && cached.resolved_at.elapsed() < self.ttl
+        {
+            return Ok(cached.addrs);
+        }
+
+        match self.resolve_and_store(host, port).await {
+            Ok(addrs) => Ok(addrs),
+            Err(err) => match self.cached(host, port) {
+                // A stale answer beats no answer while the resolver is flaky.
+                Some(stale) => {
+                    warn!(host, error = %err, "dns lookup failed, serving stale addresses");
+                    Ok(stale.addrs)
+                }
+                None => Err(err),
+            },
+        }
+    }
+
+    pub async fn refresh(&self, hosts: &[(String, u16)]) {
+        for (host, port) in hosts {
+            if let Err(err) = self.resolve_and_store(host, *port).await {
+                warn!(host = %host, error = %err, "dns refresh failed");
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, Vec<SocketAddr>> {
+        let entries = self.entries.read().expect("dns cache lock poisoned");
+        entries
+            .iter()
+            .map(|((host, port), cached)| (format!("{host}:{port}"), cached.addrs.clone()))
+            .collect()
+    }
+
+    pub fn resolved(&self, host: &str, port: u16) -> Vec<SocketAddr> {
+        self.cached(host, port)
+            .map(|cached| cached.addrs)
+            .unwrap_or_default()
+    }
+
+    pub fn spawn_refresh(self: &Arc<Self>, hosts: Vec<(String, u16)>) {
+        if self.ttl.is_zero() || hosts.is_empty() {
+            return;
+        }
+
+        let cache = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(cache.ttl);
+            loop {
+                interval.tick().await;
+                cache.refresh(&hosts).await;
+            }
+        });
+    }
+
+    fn cached(&self, host: &str, port: u16) -> Option<CachedAddrs> {
+        let entries = self.entries.read().expect("dns cache lock poisoned");
+        entries.get(&(host.to_ascii_lowercase(), port)).cloned()
+    }
+
+    async fn resolve_and_store(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        let mut addrs = self.resolver.resolve(host, port).await?;
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no addresses found for {host}"),
+            ));
+        }
+        addrs.sort();
+        addrs.dedup();
+
+        let key = (host.to_ascii_lowercase(), port);
+        let mut entries = self.entries.write().expect("dns cache lock poisoned");
+        let previous = entries.insert(
+            key,
+            CachedAddrs {
+                addrs: addrs.clone(),
+                resolved_at: Instant::now(),
+            },
+        );
+
+        match previous {
+            Some(previous) if previous.addrs != addrs => {
+                info!(host, ?addrs, previous = ?previous.addrs, "validator addresses changed");
+                self.generation.fetch_add(1, Ordering::AcqRel);
+            }
+            None => debug!(host, ?addrs, "validator addresses resolved"),
+            _ => {}
+        }
+
+        Ok(addrs)
+    }
+}
+
+pub(crate) struct CachedResolver(pub(crate) Arc<DnsCache>);
+
+impl Resolve for CachedResolver {
+    fn lookup<'a>(
+        &'a self,
+        host: &'a str,
+        port: u16,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<SocketAddr>, Box<dyn StdError>>> + 'a>> {
+        Box::pin(async move {
+            self.0
+                .lookup(host, port)
+                .await
+                .map_err(|err| Box::new(err) as Box<dyn StdError>)
+        })
+    }
+}
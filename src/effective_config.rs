@@ -0,0 +1,330 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use actix_web::http::header::{HeaderName, HeaderValue};
+use serde_json::{Map, Value, json};
+
+use crate::auth::ApiKeys;
+use crate::batch::BatchIdCheck;
+use crate::body_log::RedactRule;
+use crate::client_limit::IpRange;
+use crate::config::{Settings, Source};
+use crate::cors::CorsSettings;
+use crate::errors::ErrorFormat;
+use crate::ip_filter::IpFilter;
+use crate::jwt::JwtSettings;
+use crate::listeners::Listener;
+use crate::log_file::Rotation;
+use crate::rate_limit::{KeyRateLimit, MethodRateLimits, RateLimitHeaders};
+use crate::routing::RoutingRules;
+use crate::size_limits::ResponseSizeLimits;
+use crate::status_policy::StatusPolicies;
+use crate::telemetry::LogFormat;
+use crate::tls::TlsSettings;
+
+const REDACTED: &str = "<redacted>";
+
+// Shown as set or not, never as given: tokens, key hashes, the Sentry DSN's
+// key, the bypass secret, and inline registries whose URLs may carry
+// credentials. `JwtSettings` keeps its own secret out of `Debug`.
+const SECRETS: &[&str] = &[
+    "admin_token",
+    "api_keys",
+    "guardrail_bypass_value",
+    "hmac_secret",
+    "sentry_dsn",
+    "validators_inline",
+];
+
+// A setting's resolved value, as `print-config` and `/admin/config` show it.
+trait Effective {
+    fn effective(&self) -> Value;
+}
+
+macro_rules! plain {
+    ($($ty:ty),*) => {
+        $(impl Effective for $ty {
+            fn effective(&self) -> Value {
+                json!(self)
+            }
+        })*
+    };
+}
+
+// Types without a spelling of their own are shown as they are logged.
+macro_rules! debug {
+    ($($ty:ty),*) => {
+        $(impl Effective for $ty {
+            fn effective(&self) -> Value {
+                json!(format!("{self:?}"))
+            }
+        })*
+    };
+}
+
+plain!(bool, usize, u64, f64, String);
+debug!(
+    ApiKeys,
+    BatchIdCheck,
+    CorsSettings,
+    Duration,
+    ErrorFormat,
+    IpFilter,
+    IpRange,
+    JwtSettings,
+    KeyRateLimit,
+    Listener,
+    LogFormat,
+    MethodRateLimits,
+    RateLimitHeaders,
+    RedactRule,
+    ResponseSizeLimits,
+    Rotation,
+    RoutingRules,
+    StatusPolicies,
+    TlsSettings
+);
+
+impl Effective for PathBuf {
+    fn effective(&self) -> Value {
+        json!(self.display().to_string())
+    }
+}
+
+impl Effective for HeaderValue {
+    fn effective(&self) -> Value {
+        json!(String::from_utf8_lossy(self.as_bytes()))
+    }
+}
+
+impl Effective for HeaderName {
+    fn effective(&self) -> Value {
+        json!(self.as_str())
+    }
+}
+
+impl Effective for crate::admin::AdminToken {
+    fn effective(&self) -> Value {
+        json!(REDACTED)
+    }
+}
+
+impl<T: Effective> Effective for Option<T> {
+    fn effective(&self) -> Value {
+        self.as_ref().map_or(Value::Null, Effective::effective)
+    }
+}
+
+impl<T: Effective> Effective for Vec<T> {
+    fn effective(&self) -> Value {
+        Value::Array(self.iter().map(Effective::effective).collect())
+    }
+}
+
+// Each field with the variables it is read from. The source shown is the
+// strongest among them.
+macro_rules! fields {
+    ($settings:expr; $($field:ident: [$($var:literal),+ $(,)?]),* $(,)?) => {{
+        let settings: &Settings = $settings;
+        let mut fields = Map::new();
+        $(
+            fields.insert(
+                stringify!($field).to_string(),
+                entry(
+                    stringify!($field),
+                    settings.$field.effective(),
+                    &[$($var),+],
+                    &settings.sources,
+                ),
+            );
+        )*
+        fields
+    }};
+}
+
+fn entry(field: &str, value: Value, vars: &[&str], sources: &BTreeMap<String, Source>) -> Value {
+    let source = vars
+        .iter()
+        .filter_map(|var| sources.get(*var))
+        .max()
+        .copied()
+        .unwrap_or(Source::Default);
+    let value = if SECRETS.contains(&field) && !value.is_null() {
+        json!(REDACTED)
+    } else {
+        value
+    };
+    json!({"value": value, "source": source, "variables": vars})
+}
+
+// Every setting by field name, with its value, where that came from and the
+// variables that set it.
+pub fn render(settings: &Settings) -> Value {
+    Value::Object(fields! { settings;
+        listeners: ["BIND_ADDRESS"],
+        workers: ["WORKERS"],
+        shutdown_timeout: ["SHUTDOWN_TIMEOUT_SECS"],
+        tls: ["TLS_CERT_FILE", "TLS_KEY_FILE", "TLS_RELOAD_SECS"],
+        health_bind_address: ["HEALTH_BIND_ADDRESS"],
+        validators_csv: ["VALIDATORS_CSV"],
+        validators_inline: ["VALIDATORS_INLINE"],
+        validators_reload_interval: ["VALIDATORS_RELOAD_SECS"],
+        dns_cache_ttl: ["DNS_CACHE_TTL_SECS"],
+        max_retries: ["MAX_RETRIES"],
+        upstream_timeout: ["UPSTREAM_TIMEOUT_MS"],
+        upstream_max_connections_per_host: ["UPSTREAM_MAX_CONNECTIONS_PER_HOST"],
+        upstream_keepalive: ["UPSTREAM_KEEPALIVE_SECS"],
+        upstream_conn_lifetime: ["UPSTREAM_CONN_LIFETIME_SECS"],
+        upstream_disconnect_timeout: ["UPSTREAM_DISCONNECT_TIMEOUT_MS"],
+        retry_on_429: ["RETRY_ON_429"],
+        cooldown_on_429: ["COOLDOWN_ON_429"],
+        default_429_cooldown: ["DEFAULT_429_COOLDOWN_SECS"],
+        shed_when_cooling_down: ["SHED_WHEN_COOLING_DOWN"],
+        upstream_rate_limit: ["UPSTREAM_RATE_LIMIT_RPS", "UPSTREAM_RATE_LIMIT_BURST"],
+        upstream_rate_limit_wait: ["UPSTREAM_RATE_LIMIT_WAIT_MS"],
+        forward_retry_after: ["FORWARD_RETRY_AFTER"],
+        upstream_error_policy: ["UPSTREAM_ERROR_POLICY", "UPSTREAM_ERROR_POLICY_OVERRIDES"],
+        upstream_user_agent: ["UPSTREAM_USER_AGENT"],
+        proxied_by: ["UPSTREAM_PROXIED_BY"],
+        preserve_client_user_agent: ["PRESERVE_CLIENT_USER_AGENT"],
+        rewrite_host: ["REWRITE_HOST"],
+        enforce_json_content_type: ["ENFORCE_JSON_CONTENT_TYPE"],
+        lenient_content_type: ["LENIENT_CONTENT_TYPE"],
+        compression_enabled: ["COMPRESSION_ENABLED"],
+        compression_min_bytes: ["COMPRESSION_MIN_BYTES"],
+        coalesce_requests: ["COALESCE_REQUESTS"],
+        coalesce_methods: ["COALESCE_METHODS"],
+        coalesce_max_age: ["COALESCE_MAX_AGE_MS"],
+        routing_rules: ["ROUTING_RULES", "ROUTING_RULES_FILE"],
+        default_location: ["DEFAULT_LOCATION"],
+        response_cache: ["RESPONSE_CACHE"],
+        cache_immutable_methods: ["CACHE_IMMUTABLE_METHODS"],
+        cache_immutable_ttl: ["CACHE_IMMUTABLE_TTL_SECS"],
+        cache_short_methods: ["CACHE_SHORT_METHODS"],
+        cache_short_ttl: ["CACHE_SHORT_TTL_MS"],
+        cache_blockhash_ttl: ["CACHE_BLOCKHASH_TTL_MS"],
+        cache_blockhash_max_slot_lag: ["CACHE_BLOCKHASH_MAX_SLOT_LAG"],
+        cache_max_entries: ["CACHE_MAX_ENTRIES"],
+        jsonrpc_errors: ["JSONRPC_ERRORS"],
+        error_format: ["ERROR_FORMAT"],
+        method_rate_limits: ["METHOD_RATE_LIMITS"],
+        rate_limit_max_clients: ["RATE_LIMIT_MAX_CLIENTS"],
+        global_rate_limit: ["GLOBAL_RATE_LIMIT_RPS", "GLOBAL_RATE_LIMIT_BURST"],
+        client_rate_limit: ["CLIENT_RATE_LIMIT_RPS", "CLIENT_RATE_LIMIT_BURST"],
+        monitoring_rate_limit: ["MONITORING_RATE_LIMIT_RPS", "MONITORING_RATE_LIMIT_BURST"],
+        rate_limit_headers: ["RATE_LIMIT_HEADERS"],
+        max_concurrent_requests: ["MAX_CONCURRENT_REQUESTS"],
+        concurrency_queue_timeout: ["CONCURRENCY_QUEUE_TIMEOUT_MS"],
+        concurrency_queue_depth: ["CONCURRENCY_QUEUE_DEPTH"],
+        concurrency_fair_queues: ["CONCURRENCY_FAIR_QUEUES"],
+        trusted_proxies: ["TRUSTED_PROXIES"],
+        ip_filter: ["IP_ALLOWLIST", "IP_DENYLIST", "IP_ALLOWLIST_UNLISTED"],
+        cors: [
+            "CORS_ALLOWED_ORIGINS",
+            "CORS_ALLOWED_HEADERS",
+            "CORS_MAX_AGE_SECS",
+            "CORS_ALLOW_CREDENTIALS",
+        ],
+        guardrail_enabled: ["GUARDRAIL_ENABLED"],
+        guardrail_allowlist: ["GUARDRAIL_ALLOWLIST"],
+        guardrail_bypass_header: ["GUARDRAIL_BYPASS_HEADER"],
+        guardrail_bypass_value: ["GUARDRAIL_BYPASS_VALUE"],
+        response_size_limits: ["RESPONSE_SIZE_LIMIT_BYTES", "RESPONSE_SIZE_LIMITS"],
+        method_stats_max_methods: ["METHOD_STATS_MAX_METHODS"],
+        commitment_routing: ["COMMITMENT_ROUTING"],
+        commitment_max_slot_lag_processed: ["COMMITMENT_MAX_SLOT_LAG_PROCESSED"],
+        commitment_max_slot_lag_confirmed: ["COMMITMENT_MAX_SLOT_LAG_CONFIRMED"],
+        commitment_max_slot_lag_finalized: ["COMMITMENT_MAX_SLOT_LAG_FINALIZED"],
+        quorum_max_validators: ["QUORUM_MAX_VALIDATORS"],
+        shadow_sample_rate: ["SHADOW_SAMPLE_RATE"],
+        shadow_ignore_fields: ["SHADOW_IGNORE_FIELDS"],
+        fleet_concurrency: ["FLEET_CONCURRENCY"],
+        fleet_node_timeout: ["FLEET_NODE_TIMEOUT_MS"],
+        fleet_min_interval: ["FLEET_MIN_INTERVAL_MS"],
+        cluster_nodes_sample: ["CLUSTER_NODES_SAMPLE"],
+        cluster_nodes_cache_ttl: ["CLUSTER_NODES_CACHE_SECS"],
+        account_default_slice_bytes: ["ACCOUNT_DEFAULT_SLICE_BYTES"],
+        batch_id_check: ["BATCH_ID_CHECK"],
+        strict_responses: ["STRICT_RESPONSES"],
+        strict_response_max_bytes: ["STRICT_RESPONSE_MAX_BYTES"],
+        account_cache: ["ACCOUNT_CACHE"],
+        account_cache_ttl: ["ACCOUNT_CACHE_TTL_MS"],
+        account_cache_max_slot_lag: ["ACCOUNT_CACHE_MAX_SLOT_LAG"],
+        account_cache_max_entries: ["ACCOUNT_CACHE_MAX_ENTRIES"],
+        account_cache_processed: ["ACCOUNT_CACHE_PROCESSED"],
+        admin_token: ["ADMIN_TOKEN", "ADMIN_ENABLED"],
+        api_keys: ["API_KEYS", "API_KEYS_FILE"],
+        api_keys_public: ["API_KEYS_PUBLIC"],
+        api_keys_reload_interval: ["API_KEYS_RELOAD_SECS"],
+        api_key_rate_limit: ["API_KEY_RATE_LIMIT_RPS", "API_KEY_RATE_LIMIT_BURST"],
+        hmac_max_skew: ["HMAC_MAX_SKEW_SECS"],
+        hmac_secret: ["HMAC_SECRET"],
+        jwt: [
+            "JWT_HS256_SECRET",
+            "JWT_JWKS_URL",
+            "JWT_ISSUER",
+            "JWT_AUDIENCE",
+            "JWT_LEEWAY_SECS",
+            "JWT_CLIENT_CLAIM",
+            "JWT_JWKS_REFRESH_SECS",
+        ],
+        metrics_enabled: ["METRICS_ENABLED"],
+        latency_buckets_ms: ["LATENCY_BUCKETS_MS"],
+        slow_request_threshold: ["SLOW_REQUEST_THRESHOLD_MS"],
+        log_format: ["LOG_FORMAT"],
+        statsd_address: ["STATSD_ADDRESS"],
+        statsd_prefix: ["STATSD_PREFIX"],
+        statsd_tags: ["STATSD_TAGS"],
+        statsd_interval: ["STATSD_INTERVAL_MS"],
+        sentry_dsn: ["SENTRY_DSN"],
+        access_log_path: ["ACCESS_LOG_PATH"],
+        access_log_rotation: ["ACCESS_LOG_ROTATION"],
+        access_log_max_bytes: ["ACCESS_LOG_MAX_BYTES"],
+        access_log_max_files: ["ACCESS_LOG_MAX_FILES"],
+        log_bodies: ["LOG_BODIES"],
+        log_body_max_bytes: ["LOG_BODY_MAX_BYTES"],
+        log_body_redact: ["LOG_BODY_REDACT"],
+        key_usage_max_keys: ["KEY_USAGE_MAX_KEYS"],
+        key_usage_retention: ["KEY_USAGE_RETENTION_SECS"],
+        key_metrics_max_keys: ["KEY_METRICS_MAX_KEYS"],
+        ping_rate_limit: ["PING_RATE_LIMIT"],
+        stats_snapshot_path: ["STATS_SNAPSHOT_PATH"],
+        stats_snapshot_interval: ["STATS_SNAPSHOT_INTERVAL_SECS"],
+        stats_snapshot_max_age: ["STATS_SNAPSHOT_MAX_AGE_SECS"],
+    })
+}
+
+// The same as TOML: a table per setting, with unset values left out since
+// TOML has no null.
+pub fn render_toml(settings: &Settings) -> String {
+    let Value::Object(fields) = render(settings) else {
+        unreachable!("render returns an object");
+    };
+    let table: toml::Table = fields
+        .into_iter()
+        .filter_map(|(field, entry)| Some((field, to_toml(entry)?)))
+        .collect();
+    table.to_string()
+}
+
+fn to_toml(value: Value) -> Option<toml::Value> {
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(value) => toml::Value::Boolean(value),
+        Value::Number(number) => match number.as_i64() {
+            Some(int) => toml::Value::Integer(int),
+            None => toml::Value::Float(number.as_f64()?),
+        },
+        Value::String(value) => toml::Value::String(value),
+        Value::Array(values) => {
+            toml::Value::Array(values.into_iter().filter_map(to_toml).collect())
+        }
+        Value::Object(fields) => toml::Value::Table(
+            fields
+                .into_iter()
+                .filter_map(|(key, value)| Some((key, to_toml(value)?)))
+                .collect(),
+        ),
+    })
+}
@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Read when `--env-file` names no other file.
+pub const DEFAULT_PATH: &str = ".env";
+
+// A dotenv file: `NAME=VALUE` lines, read under the real environment.
+#[derive(Debug, Default)]
+pub struct EnvFile {
+    pub vars: BTreeMap<String, String>,
+    // One per line that was skipped. They name the line but never quote
+    // it, since the line may hold a secret.
+    pub warnings: Vec<String>,
+}
+
+impl EnvFile {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        Ok(Self::parse(
+            &path.display().to_string(),
+            &fs::read_to_string(path)?,
+        ))
+    }
+
+    // Blank lines and `#` comments are skipped, and so is a leading
+    // `export `. Values may be wrapped in single or double quotes, which
+    // are taken off; there are no escapes or multi-line values.
+    pub fn parse(source: &str, contents: &str) -> Self {
+        let mut file = Self::default();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let Some((name, value)) = line.split_once('=') else {
+                file.warnings
+                    .push(format!("{source}:{}: expected NAME=VALUE", index + 1));
+                continue;
+            };
+            let name = name.trim();
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                file.warnings
+                    .push(format!("{source}:{}: invalid variable name", index + 1));
+                continue;
+            }
+            let value = value.trim();
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|quote| {
+                    value
+                        .strip_prefix(*quote)
+                        .and_then(|value| value.strip_suffix(*quote))
+                })
+                .unwrap_or(value);
+            file.vars.insert(name.to_string(), value.to_string());
+        }
+        file
+    }
+}
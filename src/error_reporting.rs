@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::HttpResponse;
+use sentry::protocol::Level;
+use sentry::{ClientInitGuard, ClientOptions, MaxRequestBodySize};
+use tracing::warn;
+
+use crate::app_state::AppState;
+use crate::config::Settings;
+use crate::errors::AppError;
+use crate::proxy::ProxyRequest;
+
+// Failed calls to one validator within `SPIKE_WINDOW` that produce one
+// Sentry event.
+pub const SPIKE_THRESHOLD: u32 = 5;
+pub const SPIKE_WINDOW: Duration = Duration::from_secs(60);
+
+// Without `SENTRY_DSN` nothing is initialized and every capture is a no-op.
+pub fn init(settings: &Settings) -> Option<ClientInitGuard> {
+    let dsn = settings.sentry_dsn.as_deref()?;
+    let dsn = match dsn.parse() {
+        Ok(dsn) => dsn,
+        Err(err) => {
+            warn!(error = %err, "invalid SENTRY_DSN, error reporting disabled");
+            return None;
+        }
+    };
+    let mut options = client_options();
+    options.dsn = Some(dsn);
+    Some(sentry::init(options))
+}
+
+// Request bodies and client details stay out of events.
+pub fn client_options() -> ClientOptions {
+    let mut options = ClientOptions::default()
+        .send_default_pii(false)
+        .max_request_body_size(MaxRequestBodySize::None);
+    options.release = sentry::release_name!();
+    options
+}
+
+// Gives each request its own hub. Server errors are not captured here:
+// most are upstream failures, which are only reported as spikes.
+pub fn middleware() -> sentry_actix::Sentry {
+    sentry_actix::Sentry::builder()
+        .capture_server_errors(false)
+        .finish()
+}
+
+// Reports internal errors; other variants are expected failures.
+pub fn capture(err: &AppError) {
+    if matches!(err, AppError::Internal(_) | AppError::Panicked) {
+        sentry::capture_error(err);
+    }
+}
+
+// Tags the request's scope, then counts an upstream failure against the
+// validator that was tried last.
+pub(crate) fn observe(
+    state: &AppState,
+    request: &ProxyRequest<'_>,
+    result: &Result<HttpResponse, AppError>,
+) {
+    let upstream = request.last_upstream();
+    sentry::configure_scope(|scope| {
+        if let Some(method) = &request.rpc_method {
+            scope.set_tag("rpc_method", method);
+        }
+        if let Some(upstream) = &upstream {
+            scope.set_tag("validator", &upstream.validator);
+        }
+        if let Some(id) = &request.request_id {
+            scope.set_tag("request_id", id);
+        }
+    });
+
+    if let (Err(AppError::Upstream { message, .. }), Some(upstream)) = (result, &upstream) {
+        let failures = state.upstream_failures().record(&upstream.validator);
+        if failures == SPIKE_THRESHOLD {
+            sentry::capture_message(
+                &format!(
+                    "{failures} upstream failures for validator '{}' within {}s: {message}",
+                    upstream.validator,
+                    SPIKE_WINDOW.as_secs()
+                ),
+                Level::Warning,
+            );
+        }
+    }
+}
+
+// Failures per validator in the current window.
+#[derive(Debug, Default)]
+pub struct FailureSpikes {
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl FailureSpikes {
+    // Returns the failures counted so far in the validator's window.
+    pub fn record(&self, validator: &str) -> u32 {
+        let mut windows = self.windows.lock().expect("failure spikes lock poisoned");
+        let now = Instant::now();
+        let (started, failures) = windows.entry(validator.to_string()).or_insert((now, 0));
+        if now.duration_since(*started) >= SPIKE_WINDOW {
+            *started = now;
+            *failures = 0;
+        }
+        *failures += 1;
+        *failures
+    }
+}
@@ -0,0 +1,192 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+// Minutes covered by the error rates in `/validators` and the byte rates
+// in `/stats`.
+pub const WINDOW_MINUTES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    Connect,
+    Timeout,
+    ServerError,
+    BodyLimit,
+}
+
+// Counts of one minute; also the unit stats snapshots persist.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MinuteCounts {
+    pub minute: u64,
+    pub requests: u64,
+    pub errors: [u64; 4],
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+// A ring of per-minute buckets; a bucket is reused once its minute has
+// left the window, so memory stays fixed.
+#[derive(Debug, Default)]
+pub struct ErrorWindow {
+    buckets: [MinuteCounts; WINDOW_MINUTES],
+}
+
+impl ErrorWindow {
+    pub fn record_request(&mut self, minute: u64) {
+        self.bucket(minute).requests += 1;
+    }
+
+    pub fn record_error(&mut self, minute: u64, category: ErrorCategory) {
+        self.bucket(minute).errors[category as usize] += 1;
+    }
+
+    pub fn record_bytes(&mut self, minute: u64, sent: u64, received: u64) {
+        let bucket = self.bucket(minute);
+        bucket.bytes_sent = bucket.bytes_sent.saturating_add(sent);
+        bucket.bytes_received = bucket.bytes_received.saturating_add(received);
+    }
+
+    fn bucket(&mut self, minute: u64) -> &mut MinuteCounts {
+        let bucket = &mut self.buckets[minute as usize % WINDOW_MINUTES];
+        if bucket.minute != minute {
+            *bucket = MinuteCounts {
+                minute,
+                ..MinuteCounts::default()
+            };
+        }
+        bucket
+    }
+
+    // Buckets that saw traffic, oldest first.
+    pub fn minutes(&self) -> Vec<MinuteCounts> {
+        let mut minutes: Vec<MinuteCounts> = self
+            .buckets
+            .iter()
+            .filter(|bucket| bucket.requests > 0)
+            .copied()
+            .collect();
+        minutes.sort_by_key(|bucket| bucket.minute);
+        minutes
+    }
+
+    // A saved minute never overwrites a newer one sharing its slot.
+    pub fn restore(&mut self, minutes: &[MinuteCounts]) {
+        for counts in minutes {
+            let bucket = &mut self.buckets[counts.minute as usize % WINDOW_MINUTES];
+            if bucket.minute <= counts.minute {
+                *bucket = *counts;
+            }
+        }
+    }
+
+    // Buckets of the `WINDOW_MINUTES` minutes ending with `minute`.
+    fn live(&self, minute: u64) -> impl Iterator<Item = &MinuteCounts> {
+        let oldest = minute.saturating_sub(WINDOW_MINUTES as u64 - 1);
+        self.buckets
+            .iter()
+            .filter(move |bucket| bucket.minute >= oldest && bucket.minute <= minute)
+    }
+
+    pub fn rates(&self, minute: u64) -> ErrorRates {
+        let mut requests = 0;
+        let mut errors = [0u64; 4];
+        for bucket in self.live(minute) {
+            requests += bucket.requests;
+            for (total, count) in errors.iter_mut().zip(bucket.errors) {
+                *total += count;
+            }
+        }
+
+        let failed: u64 = errors.iter().sum();
+        ErrorRates {
+            window_secs: WINDOW_MINUTES as u64 * 60,
+            requests,
+            errors: ErrorCounts {
+                connect: errors[ErrorCategory::Connect as usize],
+                timeout: errors[ErrorCategory::Timeout as usize],
+                server_error: errors[ErrorCategory::ServerError as usize],
+                body_limit: errors[ErrorCategory::BodyLimit as usize],
+            },
+            error_rate: if requests == 0 {
+                0.0
+            } else {
+                failed as f64 / requests as f64
+            },
+        }
+    }
+
+    // Averaged over the whole window, including minutes without traffic.
+    pub fn byte_rates(&self, minute: u64) -> ByteRates {
+        let (sent, received) = self
+            .live(minute)
+            .fold((0u64, 0u64), |(sent, received), bucket| {
+                (
+                    sent.saturating_add(bucket.bytes_sent),
+                    received.saturating_add(bucket.bytes_received),
+                )
+            });
+        let window_secs = WINDOW_MINUTES as u64 * 60;
+        ByteRates {
+            window_secs,
+            sent: sent as f64 / window_secs as f64,
+            received: received as f64 / window_secs as f64,
+        }
+    }
+
+    // Sliding estimate over the 60 seconds up to `second`: this minute's
+    // requests plus the share of the previous minute still inside the span.
+    pub fn requests_last_minute(&self, second: u64) -> u64 {
+        let minute = second / 60;
+        let requests = |minute| {
+            self.buckets
+                .iter()
+                .find(|bucket| bucket.minute == minute)
+                .map_or(0, |bucket| bucket.requests)
+        };
+        let previous = match minute.checked_sub(1) {
+            Some(previous) => requests(previous) * (60 - second % 60) / 60,
+            None => 0,
+        };
+        requests(minute) + previous
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+pub fn current_minute() -> u64 {
+    current_second() / 60
+}
+
+pub fn current_second() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ErrorRates {
+    pub window_secs: u64,
+    pub requests: u64,
+    pub errors: ErrorCounts,
+    // Errors of the listed categories per request, 0 without traffic.
+    pub error_rate: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ErrorCounts {
+    pub connect: u64,
+    pub timeout: u64,
+    pub server_error: u64,
+    pub body_limit: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ByteRates {
+    pub window_secs: u64,
+    pub sent: f64,
+    pub received: f64,
+}
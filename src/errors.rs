@@ -1,56 +1,664 @@
-use actix_web::{HttpResponse, ResponseError, http::StatusCode};
+use std::future::Future;
+use std::io;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::{
+    HttpResponse, ResponseError,
+    http::{StatusCode, header},
+};
+use awc::error::{ConnectError, PayloadError, SendRequestError};
 use serde::Serialize;
+use serde_json::{Value, json};
 use thiserror::Error;
 
-#[derive(Debug, Error)]
+use crate::jsonrpc;
+use crate::request_id::RequestId;
+use crate::stats::FailureKind;
+use crate::validators::{RegistryError, SelectionError};
+
+// Stable identifiers for every error body's `code`; clients branch on these
+// rather than on the wording of `error`. Listed in the `/` index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    UnsupportedMediaType,
+    Unauthorized,
+    Forbidden,
+    KeyScopeViolation,
+    NotFound,
+    UnknownValidator,
+    UnknownLocation,
+    SelectionFailed,
+    UpstreamConnectFailed,
+    UpstreamDnsFailed,
+    UpstreamTlsFailed,
+    UpstreamTimeout,
+    UpstreamRateLimited,
+    UpstreamBadStatus,
+    UpstreamInvalidResponse,
+    UpstreamBodyReadFailed,
+    PayloadTooLarge,
+    QuorumNotReached,
+    InvalidRegistry,
+    RateLimited,
+    QuotaExceeded,
+    Overloaded,
+    InternalError,
+    InternalPanic,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 25] = [
+        ErrorCode::BadRequest,
+        ErrorCode::UnsupportedMediaType,
+        ErrorCode::Unauthorized,
+        ErrorCode::Forbidden,
+        ErrorCode::KeyScopeViolation,
+        ErrorCode::NotFound,
+        ErrorCode::UnknownValidator,
+        ErrorCode::UnknownLocation,
+        ErrorCode::SelectionFailed,
+        ErrorCode::UpstreamConnectFailed,
+        ErrorCode::UpstreamDnsFailed,
+        ErrorCode::UpstreamTlsFailed,
+        ErrorCode::UpstreamTimeout,
+        ErrorCode::UpstreamRateLimited,
+        ErrorCode::UpstreamBadStatus,
+        ErrorCode::UpstreamInvalidResponse,
+        ErrorCode::UpstreamBodyReadFailed,
+        ErrorCode::PayloadTooLarge,
+        ErrorCode::QuorumNotReached,
+        ErrorCode::InvalidRegistry,
+        ErrorCode::RateLimited,
+        ErrorCode::QuotaExceeded,
+        ErrorCode::Overloaded,
+        ErrorCode::InternalError,
+        ErrorCode::InternalPanic,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "bad_request",
+            ErrorCode::UnsupportedMediaType => "unsupported_media_type",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::Forbidden => "forbidden",
+            ErrorCode::KeyScopeViolation => "key_scope_violation",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::UnknownValidator => "unknown_validator",
+            ErrorCode::UnknownLocation => "unknown_location",
+            ErrorCode::SelectionFailed => "selection_failed",
+            ErrorCode::UpstreamConnectFailed => "upstream_connect_failed",
+            ErrorCode::UpstreamDnsFailed => "upstream_dns_failed",
+            ErrorCode::UpstreamTlsFailed => "upstream_tls_failed",
+            ErrorCode::UpstreamTimeout => "upstream_timeout",
+            ErrorCode::UpstreamRateLimited => "upstream_rate_limited",
+            ErrorCode::UpstreamBadStatus => "upstream_bad_status",
+            ErrorCode::UpstreamInvalidResponse => "upstream_invalid_response",
+            ErrorCode::UpstreamBodyReadFailed => "upstream_body_read_failed",
+            ErrorCode::PayloadTooLarge => "payload_too_large",
+            ErrorCode::QuorumNotReached => "quorum_not_reached",
+            ErrorCode::InvalidRegistry => "invalid_registry",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::QuotaExceeded => "quota_exceeded",
+            ErrorCode::Overloaded => "overloaded",
+            ErrorCode::InternalError => "internal_error",
+            ErrorCode::InternalPanic => "internal_panic",
+        }
+    }
+}
+
+impl From<UpstreamErrorKind> for ErrorCode {
+    fn from(kind: UpstreamErrorKind) -> Self {
+        match kind {
+            UpstreamErrorKind::ConnectRefused => ErrorCode::UpstreamConnectFailed,
+            UpstreamErrorKind::DnsFailure => ErrorCode::UpstreamDnsFailed,
+            UpstreamErrorKind::TlsError => ErrorCode::UpstreamTlsFailed,
+            UpstreamErrorKind::Timeout => ErrorCode::UpstreamTimeout,
+            UpstreamErrorKind::ResponseTooLarge => ErrorCode::PayloadTooLarge,
+            UpstreamErrorKind::BodyReadError => ErrorCode::UpstreamBodyReadFailed,
+            UpstreamErrorKind::BadStatus(429) => ErrorCode::UpstreamRateLimited,
+            UpstreamErrorKind::BadStatus(_) => ErrorCode::UpstreamBadStatus,
+            UpstreamErrorKind::InvalidResponse => ErrorCode::UpstreamInvalidResponse,
+        }
+    }
+}
+
+// What went wrong with a validator, read off the awc error variants and our
+// own checks rather than their wording, which changes between versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpstreamErrorKind {
+    // No usable connection: refused, reset or closed mid-request.
+    ConnectRefused,
+    DnsFailure,
+    TlsError,
+    Timeout,
+    ResponseTooLarge,
+    BodyReadError,
+    BadStatus(u16),
+    // A 2xx answer whose body we turned down.
+    InvalidResponse,
+}
+
+impl UpstreamErrorKind {
+    pub const COUNT: usize = 8;
+
+    // Metric labels, indexed by `index()`.
+    pub const LABELS: [&'static str; Self::COUNT] = [
+        "connect_refused",
+        "dns_failure",
+        "tls_error",
+        "timeout",
+        "response_too_large",
+        "body_read_error",
+        "bad_status",
+        "invalid_response",
+    ];
+
+    pub fn index(self) -> usize {
+        match self {
+            UpstreamErrorKind::ConnectRefused => 0,
+            UpstreamErrorKind::DnsFailure => 1,
+            UpstreamErrorKind::TlsError => 2,
+            UpstreamErrorKind::Timeout => 3,
+            UpstreamErrorKind::ResponseTooLarge => 4,
+            UpstreamErrorKind::BodyReadError => 5,
+            UpstreamErrorKind::BadStatus(_) => 6,
+            UpstreamErrorKind::InvalidResponse => 7,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        Self::LABELS[self.index()]
+    }
+
+    // The coarser kind validator health is kept by.
+    pub fn failure(self) -> FailureKind {
+        match self {
+            UpstreamErrorKind::ConnectRefused
+            | UpstreamErrorKind::DnsFailure
+            | UpstreamErrorKind::TlsError => FailureKind::Unavailable,
+            UpstreamErrorKind::Timeout => FailureKind::Timeout,
+            UpstreamErrorKind::ResponseTooLarge => FailureKind::TooLarge,
+            UpstreamErrorKind::BodyReadError => FailureKind::Unreadable,
+            UpstreamErrorKind::BadStatus(429) => FailureKind::RateLimited,
+            UpstreamErrorKind::BadStatus(_) => FailureKind::HttpStatus,
+            UpstreamErrorKind::InvalidResponse => FailureKind::InvalidResponse,
+        }
+    }
+
+    // `None` for a 2xx.
+    pub fn from_status(status: StatusCode) -> Option<Self> {
+        (!status.is_success()).then_some(UpstreamErrorKind::BadStatus(status.as_u16()))
+    }
+}
+
+#[derive(Debug, Clone, Error)]
 pub enum AppError {
     #[error("{0}")]
     BadRequest(String),
-    #[error("validator selection failed: {0}")]
-    Selection(String),
-    #[error("upstream request failed: {0}")]
-    Upstream(String),
+    #[error("{0}")]
+    PayloadTooLarge(String),
+    #[error("{0}")]
+    UnsupportedMediaType(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    // The API key may not use the method, validator or location asked for.
+    #[error("{0}")]
+    KeyScopeViolation(String),
+    // Unknown validators and locations are 404s with close matches; other
+    // selection failures keep the `validator selection failed:` prefix.
+    #[error("{}", selection_message(.0))]
+    Selection(#[from] SelectionError),
+    #[error("upstream request failed: {message}")]
+    Upstream {
+        // The validator that failed, when a single one is to blame.
+        validator: Option<String>,
+        kind: UpstreamErrorKind,
+        message: String,
+        // Every validator tried for the request, once failover is over.
+        trail: Option<AttemptTrail>,
+        // The start of a wrapped non-2xx upstream body.
+        excerpt: Option<String>,
+    },
+    // A validator registry that could not be loaded: 422 when the file is
+    // at fault, 500 when it could not be read.
+    #[error("{}", registry_message(.0))]
+    Registry(#[source] Arc<RegistryError>),
     #[error("internal error: {0}")]
     Internal(String),
+    // Caught by `panic_guard`; the details only go to the logs.
+    #[error("internal error: the request handler panicked")]
+    Panicked,
+    // Load shedding: a client limit (429) or no capacity upstream (503).
+    // `retry_after` is when the limiting component frees up.
+    #[error("{message}")]
+    RateLimited {
+        message: String,
+        retry_after: Duration,
+    },
+    // A key's request quota is used up until `retry_after`, when its
+    // period rolls over.
+    #[error("{message}")]
+    QuotaExceeded {
+        message: String,
+        retry_after: Duration,
+    },
+    #[error("{message}")]
+    Overloaded {
+        message: String,
+        retry_after: Duration,
+    },
 }
 
-#[derive(Debug, Serialize)]
-struct ErrorResponse {
-    error: String,
+impl AppError {
+    pub fn upstream(
+        validator: impl Into<String>,
+        kind: UpstreamErrorKind,
+        message: impl Into<String>,
+    ) -> Self {
+        AppError::Upstream {
+            validator: Some(validator.into()),
+            kind,
+            message: message.into(),
+            trail: None,
+            excerpt: None,
+        }
+    }
+
+    // Attaches the attempts behind an upstream failure; the last one is
+    // blamed on this error unless it already failed on its own.
+    pub fn with_attempts(mut self, mut attempts: Vec<AttemptDetail>, elapsed: Duration) -> Self {
+        if let AppError::Upstream { kind, trail, .. } = &mut self {
+            if let Some(last) = attempts.last_mut() {
+                last.error.get_or_insert(kind.failure());
+            }
+            *trail = Some(AttemptTrail { attempts, elapsed });
+        }
+        self
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::BadRequest(_) => ErrorCode::BadRequest,
+            AppError::PayloadTooLarge(_) => ErrorCode::PayloadTooLarge,
+            AppError::UnsupportedMediaType(_) => ErrorCode::UnsupportedMediaType,
+            AppError::Unauthorized(_) => ErrorCode::Unauthorized,
+            AppError::Forbidden(_) => ErrorCode::Forbidden,
+            AppError::KeyScopeViolation(_) => ErrorCode::KeyScopeViolation,
+            AppError::Selection(SelectionError::UnknownValidator { .. }) => {
+                ErrorCode::UnknownValidator
+            }
+            AppError::Selection(SelectionError::UnknownLocation { .. }) => {
+                ErrorCode::UnknownLocation
+            }
+            AppError::Selection(_) => ErrorCode::SelectionFailed,
+            AppError::Upstream { kind, .. } => ErrorCode::from(*kind),
+            AppError::Registry(err) => match err.as_ref() {
+                RegistryError::Io(_) => ErrorCode::InternalError,
+                _ => ErrorCode::InvalidRegistry,
+            },
+            AppError::Internal(_) => ErrorCode::InternalError,
+            AppError::Panicked => ErrorCode::InternalPanic,
+            AppError::RateLimited { .. } => ErrorCode::RateLimited,
+            AppError::QuotaExceeded { .. } => ErrorCode::QuotaExceeded,
+            AppError::Overloaded { .. } => ErrorCode::Overloaded,
+        }
+    }
+
+    pub fn rpc_code(&self) -> i64 {
+        match self {
+            AppError::BadRequest(_) | AppError::PayloadTooLarge(_) => jsonrpc::INVALID_REQUEST,
+            AppError::UnsupportedMediaType(_) => jsonrpc::UNSUPPORTED_MEDIA_TYPE,
+            AppError::Unauthorized(_) => jsonrpc::UNAUTHORIZED,
+            AppError::Forbidden(_) | AppError::KeyScopeViolation(_) => jsonrpc::FORBIDDEN,
+            AppError::Selection(err) if err.suggestions().is_some() => jsonrpc::NOT_FOUND,
+            AppError::Selection(_) => jsonrpc::SELECTION_FAILED,
+            AppError::Upstream {
+                kind: UpstreamErrorKind::Timeout,
+                ..
+            } => jsonrpc::UPSTREAM_TIMEOUT,
+            AppError::Upstream { .. } => jsonrpc::UPSTREAM_UNAVAILABLE,
+            AppError::Registry(err) => match err.as_ref() {
+                RegistryError::Io(_) => jsonrpc::INTERNAL_ERROR,
+                _ => jsonrpc::INVALID_REGISTRY,
+            },
+            AppError::Internal(_) | AppError::Panicked => jsonrpc::INTERNAL_ERROR,
+            AppError::RateLimited { .. } | AppError::QuotaExceeded { .. } => jsonrpc::RATE_LIMITED,
+            AppError::Overloaded { .. } => jsonrpc::OVERLOADED,
+        }
+    }
+
+    // An error actix raised before a handler ran (oversized body, bad query
+    // string, ...), or `None` for statuses without a matching variant.
+    pub fn from_framework(status: StatusCode, message: String) -> Option<Self> {
+        match status {
+            StatusCode::BAD_REQUEST => Some(AppError::BadRequest(message)),
+            StatusCode::PAYLOAD_TOO_LARGE => Some(AppError::PayloadTooLarge(message)),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => Some(AppError::UnsupportedMediaType(message)),
+            StatusCode::INTERNAL_SERVER_ERROR => Some(AppError::Internal(message)),
+            _ => None,
+        }
+    }
+
+    // A validator that did not answer in time; reads may try another one.
+    pub fn is_timeout(&self) -> bool {
+        matches!(
+            self,
+            AppError::Upstream {
+                kind: UpstreamErrorKind::Timeout,
+                ..
+            }
+        )
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            AppError::RateLimited { retry_after, .. }
+            | AppError::QuotaExceeded { retry_after, .. }
+            | AppError::Overloaded { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+
+    // The error in `format`, for the request being handled.
+    pub fn body(&self, format: ErrorFormat) -> Value {
+        match format {
+            ErrorFormat::Plain => {
+                let mut body = error_body(self.code(), self.to_string());
+                match self {
+                    AppError::Selection(err) if err.suggestions().is_some() => {
+                        body["suggestions"] = json!(err.suggestions())
+                    }
+                    AppError::Upstream {
+                        validator,
+                        kind,
+                        trail,
+                        excerpt,
+                        ..
+                    } if validator.is_some() || trail.is_some() => {
+                        let mut details = json!({ "validator": validator });
+                        if let UpstreamErrorKind::BadStatus(status) = kind {
+                            details["status"] = json!(status);
+                        }
+                        if let Some(excerpt) = excerpt {
+                            details["body_excerpt"] = json!(excerpt);
+                        }
+                        if let Some(trail) = trail {
+                            details["attempts"] = json!(trail.attempts);
+                            details["elapsed_ms"] = json!(trail.elapsed.as_millis() as u64);
+                        }
+                        body["details"] = details;
+                    }
+                    AppError::Registry(err) => {
+                        if let Some(details) = registry_details(err) {
+                            body["details"] = details;
+                        }
+                    }
+                    _ => {}
+                }
+                if let Some(retry_after) = self.retry_after() {
+                    body["retry_after_ms"] = json!(retry_after.as_millis() as u64);
+                }
+                body
+            }
+            ErrorFormat::JsonRpc => {
+                let mut data = json!({
+                    "code": self.code(),
+                    "requestId": RequestId::current().map(|id| id.as_str().to_string()),
+                });
+                match self {
+                    AppError::Upstream {
+                        validator,
+                        kind,
+                        trail,
+                        excerpt,
+                        ..
+                    } => {
+                        data["validator"] = json!(validator);
+                        if let UpstreamErrorKind::BadStatus(status) = kind {
+                            data["upstreamStatus"] = json!(status);
+                        }
+                        if let Some(excerpt) = excerpt {
+                            data["bodyExcerpt"] = json!(excerpt);
+                        }
+                        if let Some(trail) = trail {
+                            trail.annotate(&mut data);
+                        }
+                    }
+                    AppError::Selection(err) if err.suggestions().is_some() => {
+                        data["suggestions"] = json!(err.suggestions())
+                    }
+                    AppError::Registry(err) => {
+                        if let Some(Value::Object(details)) = registry_details(err) {
+                            for (key, value) in details {
+                                data[key] = value;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                if let Some(retry_after) = self.retry_after() {
+                    data["retryAfterMs"] = json!(retry_after.as_millis() as u64);
+                }
+                jsonrpc::error_object(Value::Null, self.rpc_code(), &self.to_string(), data)
+            }
+        }
+    }
+}
+
+// One call to a validator, by registry name only: never its URL.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AttemptDetail {
+    pub validator: String,
+    // `None` for a 2xx answer, unless its body was rejected later on.
+    pub error: Option<FailureKind>,
+    pub status: Option<u16>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttemptTrail {
+    pub attempts: Vec<AttemptDetail>,
+    pub elapsed: Duration,
+}
+
+impl AttemptTrail {
+    // Adds the attempts to a JSON-RPC `error.data`.
+    pub fn annotate(&self, data: &mut Value) {
+        data["attemptDetails"] = json!(self.attempts);
+        data["elapsedMs"] = json!(self.elapsed.as_millis() as u64);
+    }
+}
+
+tokio::task_local! {
+    static FORMAT: ErrorFormat;
+}
+
+// How `AppError` bodies are rendered (`ERROR_FORMAT`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorFormat {
+    // `{"error": message, "code": ..., "request_id": ...}`
+    #[default]
+    Plain,
+    // A JSON-RPC error object with a null `id`; the HTTP status is kept.
+    JsonRpc,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = ();
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.to_ascii_lowercase().as_str() {
+            "plain" => Ok(Self::Plain),
+            "jsonrpc" | "json-rpc" => Ok(Self::JsonRpc),
+            _ => Err(()),
+        }
+    }
+}
+
+impl ErrorFormat {
+    // The format of the request handled by the current task; set by the
+    // request id middleware.
+    pub fn current() -> Self {
+        FORMAT.try_with(|format| *format).unwrap_or_default()
+    }
+
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        FORMAT.scope(self, future).await
+    }
+}
+
+// Whole seconds for the `Retry-After` header, never 0.
+pub fn retry_after_secs(retry_after: Duration) -> u64 {
+    retry_after.as_secs_f64().ceil().max(1.0) as u64
+}
+
+// `{"error": message, "code": code}`, plus the `request_id` of the request
+// being handled.
+pub fn error_body(code: ErrorCode, message: impl Into<String>) -> Value {
+    let mut body = json!({ "error": message.into(), "code": code });
+    if let Some(id) = RequestId::current() {
+        body["request_id"] = Value::from(id.as_str());
+    }
+    body
 }
 
 impl ResponseError for AppError {
     fn status_code(&self) -> StatusCode {
         match self {
+            AppError::Selection(err) if err.suggestions().is_some() => StatusCode::NOT_FOUND,
             AppError::BadRequest(_) | AppError::Selection(_) => StatusCode::BAD_REQUEST,
-            AppError::Upstream(_) => StatusCode::BAD_GATEWAY,
-            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) | AppError::KeyScopeViolation(_) => StatusCode::FORBIDDEN,
+            AppError::Upstream {
+                kind: UpstreamErrorKind::Timeout,
+                ..
+            } => StatusCode::GATEWAY_TIMEOUT,
+            AppError::Upstream { .. } => StatusCode::BAD_GATEWAY,
+            AppError::Registry(err) => match err.as_ref() {
+                RegistryError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            },
+            AppError::Internal(_) | AppError::Panicked => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::RateLimited { .. } | AppError::QuotaExceeded { .. } => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
+            AppError::Overloaded { .. } => StatusCode::SERVICE_UNAVAILABLE,
         }
     }
 
     fn error_response(&self) -> HttpResponse {
-        let payload = ErrorResponse {
-            error: self.to_string(),
-        };
+        #[cfg(feature = "sentry")]
+        crate::error_reporting::capture(self);
+        let mut response = HttpResponse::build(self.status_code());
+        if let Some(retry_after) = self.retry_after() {
+            response.insert_header((header::RETRY_AFTER, retry_after_secs(retry_after)));
+        }
+        response.json(self.body(ErrorFormat::current()))
+    }
+}
 
-        HttpResponse::build(self.status_code()).json(payload)
+// Timeouts, including connect timeouts, are told apart from refused
+// connections and TLS failures so they can map to 504 rather than 502.
+pub fn send_failure(err: &SendRequestError) -> UpstreamErrorKind {
+    match err {
+        SendRequestError::Timeout | SendRequestError::Connect(ConnectError::Timeout) => {
+            UpstreamErrorKind::Timeout
+        }
+        SendRequestError::Send(err) | SendRequestError::Connect(ConnectError::Io(err))
+            if err.kind() == io::ErrorKind::TimedOut =>
+        {
+            UpstreamErrorKind::Timeout
+        }
+        SendRequestError::Connect(
+            ConnectError::Resolver(_) | ConnectError::NoRecords | ConnectError::Unresolved,
+        ) => UpstreamErrorKind::DnsFailure,
+        // rustls reports failed handshakes as `InvalidData`.
+        SendRequestError::Connect(ConnectError::SslIsNotSupported) => UpstreamErrorKind::TlsError,
+        SendRequestError::Connect(ConnectError::Io(err))
+            if err.kind() == io::ErrorKind::InvalidData =>
+        {
+            UpstreamErrorKind::TlsError
+        }
+        SendRequestError::Response(_) => UpstreamErrorKind::InvalidResponse,
+        _ => UpstreamErrorKind::ConnectRefused,
     }
 }
 
-impl From<awc::error::SendRequestError> for AppError {
-    fn from(value: awc::error::SendRequestError) -> Self {
-        AppError::Upstream(value.to_string())
+pub fn payload_failure(err: &PayloadError) -> UpstreamErrorKind {
+    match err {
+        PayloadError::Overflow => UpstreamErrorKind::ResponseTooLarge,
+        PayloadError::Io(err) if err.kind() == io::ErrorKind::TimedOut => {
+            UpstreamErrorKind::Timeout
+        }
+        _ => UpstreamErrorKind::BodyReadError,
     }
 }
 
-impl From<awc::error::PayloadError> for AppError {
-    fn from(value: awc::error::PayloadError) -> Self {
-        AppError::Upstream(value.to_string())
+impl From<SendRequestError> for AppError {
+    fn from(value: SendRequestError) -> Self {
+        AppError::Upstream {
+            validator: None,
+            kind: send_failure(&value),
+            message: value.to_string(),
+            trail: None,
+            excerpt: None,
+        }
     }
 }
 
-impl From<crate::validators::SelectionError> for AppError {
-    fn from(value: crate::validators::SelectionError) -> Self {
-        AppError::Selection(value.to_string())
+impl From<PayloadError> for AppError {
+    fn from(value: PayloadError) -> Self {
+        AppError::Upstream {
+            validator: None,
+            kind: payload_failure(&value),
+            message: value.to_string(),
+            trail: None,
+            excerpt: None,
+        }
+    }
+}
+
+impl From<RegistryError> for AppError {
+    fn from(value: RegistryError) -> Self {
+        AppError::Registry(Arc::new(value))
+    }
+}
+
+fn registry_message(err: &RegistryError) -> String {
+    match err {
+        RegistryError::Io(_) => format!("could not read the validator registry: {err}"),
+        RegistryError::Empty => {
+            "invalid validator registry: the file lists no validators".to_string()
+        }
+        _ => format!("invalid validator registry: {err}"),
+    }
+}
+
+// `{"row": ..., "field": ...}`, with whichever of the two is known.
+fn registry_details(err: &RegistryError) -> Option<Value> {
+    let mut details = serde_json::Map::new();
+    if let Some(row) = err.row() {
+        details.insert("row".to_string(), json!(row));
+    }
+    if let Some(field) = err.field() {
+        details.insert("field".to_string(), json!(field));
+    }
+    (!details.is_empty()).then_some(Value::Object(details))
+}
+
+fn selection_message(err: &SelectionError) -> String {
+    match err {
+        SelectionError::UnknownValidator { .. } | SelectionError::UnknownLocation { .. } => {
+            err.to_string()
+        }
+        _ => format!("validator selection failed: {err}"),
     }
 }
@@ -0,0 +1,640 @@
+use std::fmt::Write;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use awc::Client;
+use futures_util::{StreamExt, stream};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use url::Host;
+
+use crate::dns::DnsCache;
+use crate::validators::Validator;
+
+// JSON-RPC error code nodes use for "Node is behind by N slots".
+const NODE_BEHIND: i64 = -32005;
+// awc's own default body limit.
+const REPLY_LIMIT: usize = 2 * 1024 * 1024;
+
+// Probes per `/validators/{name}/ping` call, by default and at most.
+pub const DEFAULT_PINGS: usize = 3;
+pub const MAX_PINGS: usize = 10;
+// Name the ping rate limit is tracked under.
+pub const PING: &str = "ping";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FanOutError {
+    Timeout,
+    Failed(String),
+}
+
+pub struct NodeOutcome<T> {
+    pub validator: Validator,
+    pub latency: Duration,
+    pub result: Result<T, FanOutError>,
+}
+
+// Runs `call` against every validator with at most `concurrency` in flight,
+// giving each `timeout`. A slow or failing node only affects its own entry;
+// results keep the input order.
+pub async fn fan_out<T, F, Fut>(
+    validators: &[Validator],
+    concurrency: usize,
+    timeout: Duration,
+    call: F,
+) -> Vec<NodeOutcome<T>>
+where
+    F: Fn(Validator) -> Fut,
+    Fut: Future<Output = Result<T, String>>,
+{
+    stream::iter(validators.iter().cloned())
+        .map(|validator| {
+            let call = call(validator.clone());
+            async move {
+                let (latency, result) = timed(timeout, call).await;
+                NodeOutcome {
+                    validator,
+                    latency,
+                    result,
+                }
+            }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+async fn timed<T>(
+    timeout: Duration,
+    call: impl Future<Output = Result<T, String>>,
+) -> (Duration, Result<T, FanOutError>) {
+    let started = Instant::now();
+    let result = match tokio::time::timeout(timeout, call).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(err)) => Err(FanOutError::Failed(err)),
+        Err(_) => Err(FanOutError::Timeout),
+    };
+    (started.elapsed(), result)
+}
+
+// Posts a JSON-RPC body straight to `validator` and decodes the reply. The
+// probes below go through here rather than the proxy path, so they never
+// retry, fail over or touch the stats.
+async fn call_rpc<T: DeserializeOwned>(
+    client: &Client,
+    validator: &Validator,
+    body: &Value,
+    limit: usize,
+) -> Result<T, String> {
+    let mut response = client
+        .post(validator.rpc_url().as_str())
+        .send_json(body)
+        .await
+        .map_err(|err| err.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("upstream returned {}", response.status()));
+    }
+    response
+        .json()
+        .limit(limit)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PingProbe {
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Round-trip figures cover the successful probes only; `None` when all
+// failed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PingReport {
+    pub validator: String,
+    pub location: String,
+    pub count: usize,
+    pub successes: usize,
+    pub min_ms: Option<f64>,
+    pub avg_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+    pub probes: Vec<PingProbe>,
+}
+
+// Sends `getVersion` `count` times, one after the other, each bounded by
+// `timeout`.
+pub async fn ping(
+    client: &Client,
+    validator: &Validator,
+    count: usize,
+    timeout: Duration,
+) -> PingReport {
+    let body = json!({"jsonrpc": "2.0", "id": 1, "method": "getVersion"});
+    let mut probes = Vec::with_capacity(count);
+    for _ in 0..count {
+        let (latency, result) = timed(timeout, async {
+            let reply: Value = call_rpc(client, validator, &body, REPLY_LIMIT).await?;
+            match reply.get("error") {
+                Some(error) => Err(format!("rpc error: {error}")),
+                None => Ok(()),
+            }
+        })
+        .await;
+        probes.push(PingProbe {
+            latency_ms: latency.as_secs_f64() * 1000.0,
+            error: result.err().map(|err| match err {
+                FanOutError::Timeout => "timed out".to_string(),
+                FanOutError::Failed(err) => err,
+            }),
+        });
+    }
+
+    let latencies: Vec<f64> = probes
+        .iter()
+        .filter(|probe| probe.error.is_none())
+        .map(|probe| probe.latency_ms)
+        .collect();
+    PingReport {
+        validator: validator.name().to_string(),
+        location: validator.location().to_string(),
+        count,
+        successes: latencies.len(),
+        min_ms: latencies.iter().copied().reduce(f64::min),
+        avg_ms: (!latencies.is_empty())
+            .then(|| latencies.iter().sum::<f64>() / latencies.len() as f64),
+        max_ms: latencies.iter().copied().reduce(f64::max),
+        probes,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    Behind,
+    Unhealthy,
+    Timeout,
+    Unreachable,
+}
+
+impl HealthStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HealthStatus::Ok => "ok",
+            HealthStatus::Behind => "behind",
+            HealthStatus::Unhealthy => "unhealthy",
+            HealthStatus::Timeout => "timeout",
+            HealthStatus::Unreachable => "unreachable",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeHealth {
+    pub status: HealthStatus,
+    pub slot: Option<u64>,
+    pub slots_behind: Option<u64>,
+    // `solana-core` from `getVersion`.
+    pub version: Option<String>,
+    pub error: Option<String>,
+}
+
+// `getHealth`, `getSlot` and `getVersion` go out as one batch so a single
+// round trip gives the verdict, the node's position and its release.
+pub async fn probe_health(client: &Client, validator: &Validator) -> Result<NodeHealth, String> {
+    #[derive(Deserialize)]
+    struct Reply {
+        id: u64,
+        #[serde(default)]
+        result: Option<Value>,
+        #[serde(default)]
+        error: Option<RpcError>,
+    }
+
+    #[derive(Deserialize)]
+    struct RpcError {
+        code: i64,
+        #[serde(default)]
+        message: String,
+        #[serde(default)]
+        data: Option<Value>,
+    }
+
+    let body = json!([
+        {"jsonrpc": "2.0", "id": 1, "method": "getHealth"},
+        {"jsonrpc": "2.0", "id": 2, "method": "getSlot", "params": [{"commitment": "processed"}]},
+        {"jsonrpc": "2.0", "id": 3, "method": "getVersion"},
+    ]);
+    let replies: Vec<Reply> = call_rpc(client, validator, &body, REPLY_LIMIT).await?;
+
+    let slot = replies
+        .iter()
+        .find(|reply| reply.id == 2)
+        .and_then(|reply| reply.result.as_ref())
+        .and_then(Value::as_u64);
+    let version = replies
+        .iter()
+        .find(|reply| reply.id == 3)
+        .and_then(|reply| reply.result.as_ref())
+        .and_then(|result| result.get("solana-core"))
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let health = replies
+        .into_iter()
+        .find(|reply| reply.id == 1)
+        .ok_or_else(|| "no getHealth reply".to_string())?;
+
+    Ok(match health.error {
+        None if health.result.is_some() => NodeHealth {
+            status: HealthStatus::Ok,
+            slot,
+            slots_behind: None,
+            version,
+            error: None,
+        },
+        None => NodeHealth {
+            status: HealthStatus::Unhealthy,
+            slot,
+            slots_behind: None,
+            version,
+            error: Some("empty getHealth reply".to_string()),
+        },
+        Some(error) => NodeHealth {
+            status: if error.code == NODE_BEHIND {
+                HealthStatus::Behind
+            } else {
+                HealthStatus::Unhealthy
+            },
+            slot,
+            slots_behind: error
+                .data
+                .as_ref()
+                .and_then(|data| data.get("numSlotsBehind"))
+                .and_then(Value::as_u64),
+            version,
+            error: Some(error.message),
+        },
+    })
+}
+
+impl From<FanOutError> for NodeHealth {
+    fn from(value: FanOutError) -> Self {
+        let (status, error) = match value {
+            FanOutError::Timeout => (HealthStatus::Timeout, "timed out".to_string()),
+            FanOutError::Failed(err) => (HealthStatus::Unreachable, err),
+        };
+        Self {
+            status,
+            slot: None,
+            slots_behind: None,
+            version: None,
+            error: Some(error),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FleetHealth {
+    pub summary: HealthSummary,
+    pub validators: Vec<NodeHealthReport>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct HealthSummary {
+    pub total: usize,
+    pub ok: usize,
+    pub behind: usize,
+    pub unhealthy: usize,
+    pub timeout: usize,
+    pub unreachable: usize,
+}
+
+impl HealthSummary {
+    fn count(&mut self, status: HealthStatus) {
+        self.total += 1;
+        match status {
+            HealthStatus::Ok => self.ok += 1,
+            HealthStatus::Behind => self.behind += 1,
+            HealthStatus::Unhealthy => self.unhealthy += 1,
+            HealthStatus::Timeout => self.timeout += 1,
+            HealthStatus::Unreachable => self.unreachable += 1,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NodeHealthReport {
+    pub name: String,
+    pub location: String,
+    pub status: HealthStatus,
+    pub latency_ms: u64,
+    pub slot: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slots_behind: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl NodeHealthReport {
+    // It answered, whatever it said.
+    pub fn reachable(&self) -> bool {
+        !matches!(
+            self.status,
+            HealthStatus::Timeout | HealthStatus::Unreachable
+        )
+    }
+}
+
+// `probe_health` across `validators`, shared by `/fleet/health` and the
+// `probe` subcommand.
+pub async fn check_health(
+    client: &Client,
+    validators: &[Validator],
+    concurrency: usize,
+    timeout: Duration,
+) -> FleetHealth {
+    let outcomes = fan_out(validators, concurrency, timeout, |validator| {
+        let client = client.clone();
+        async move { probe_health(&client, &validator).await }
+    })
+    .await;
+
+    let mut summary = HealthSummary::default();
+    let validators = outcomes
+        .into_iter()
+        .map(|outcome| {
+            let health = outcome.result.unwrap_or_else(NodeHealth::from);
+            summary.count(health.status);
+            NodeHealthReport {
+                name: outcome.validator.name().to_string(),
+                location: outcome.validator.location().to_string(),
+                status: health.status,
+                latency_ms: outcome.latency.as_millis() as u64,
+                slot: health.slot,
+                slots_behind: health.slots_behind,
+                version: health.version,
+                error: health.error,
+            }
+        })
+        .collect();
+    FleetHealth {
+        summary,
+        validators,
+    }
+}
+
+impl FleetHealth {
+    // A table with a line per node, then the totals.
+    pub fn render(&self) -> String {
+        let headers = [
+            "NAME", "LOCATION", "STATUS", "LATENCY", "VERSION", "SLOT", "ERROR",
+        ];
+        let rows: Vec<[String; 7]> = self
+            .validators
+            .iter()
+            .map(|node| {
+                [
+                    node.name.clone(),
+                    node.location.clone(),
+                    node.status.as_str().to_string(),
+                    format!("{}ms", node.latency_ms),
+                    node.version.clone().unwrap_or_else(|| "-".to_string()),
+                    node.slot
+                        .map_or_else(|| "-".to_string(), |slot| slot.to_string()),
+                    node.error.clone().unwrap_or_default(),
+                ]
+            })
+            .collect();
+        let widths: Vec<usize> = (0..headers.len())
+            .map(|col| {
+                rows.iter()
+                    .map(|row| row[col].len())
+                    .chain([headers[col].len()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let mut out = String::new();
+        let mut line = |cells: [&str; 7]| {
+            let cells: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect();
+            let _ = writeln!(out, "{}", cells.join("  ").trim_end());
+        };
+        line(headers);
+        for row in &rows {
+            line(row.each_ref().map(String::as_str));
+        }
+        let summary = &self.summary;
+        let _ = writeln!(
+            out,
+            "{} validators: {} ok, {} behind, {} unhealthy, {} timeout, {} unreachable",
+            summary.total,
+            summary.ok,
+            summary.behind,
+            summary.unhealthy,
+            summary.timeout,
+            summary.unreachable
+        );
+        out
+    }
+}
+
+// Allows one call per `min_interval`; live fan-outs hit every validator, so
+// they must not be triggerable in a tight loop.
+pub struct Throttle {
+    min_interval: Duration,
+    last: Mutex<Option<Instant>>,
+}
+
+impl Throttle {
+    pub fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            last: Mutex::new(None),
+        }
+    }
+
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut last = self.last.lock().expect("throttle lock poisoned");
+        if let Some(previous) = *last {
+            let elapsed = now.duration_since(previous);
+            if elapsed < self.min_interval {
+                return Err(self.min_interval - elapsed);
+            }
+        }
+        *last = Some(now);
+        Ok(())
+    }
+}
+
+// Keeps one value for `ttl`, for expensive fleet-wide lookups.
+pub struct TimedCache<T> {
+    ttl: Duration,
+    value: Mutex<Option<(Instant, T)>>,
+}
+
+impl<T: Clone> TimedCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            value: Mutex::new(None),
+        }
+    }
+
+    pub fn get(&self) -> Option<T> {
+        let value = self.value.lock().expect("cache lock poisoned");
+        value
+            .as_ref()
+            .filter(|(stored_at, _)| stored_at.elapsed() < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    pub fn put(&self, value: T) {
+        *self.value.lock().expect("cache lock poisoned") = Some((Instant::now(), value));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ClusterNode {
+    pub pubkey: String,
+    #[serde(default)]
+    pub gossip: Option<String>,
+    #[serde(default)]
+    pub rpc: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+impl ClusterNode {
+    fn rpc_addr(&self) -> Option<SocketAddr> {
+        self.rpc.as_deref()?.parse().ok()
+    }
+
+    fn gossip_addr(&self) -> Option<SocketAddr> {
+        self.gossip.as_deref()?.parse().ok()
+    }
+}
+
+pub async fn cluster_nodes(
+    client: &Client,
+    validator: &Validator,
+) -> Result<Vec<ClusterNode>, String> {
+    #[derive(Deserialize)]
+    struct Reply {
+        result: Vec<ClusterNode>,
+    }
+
+    let body = json!({"jsonrpc": "2.0", "id": 1, "method": "getClusterNodes"});
+    // Mainnet lists thousands of nodes; allow well beyond the usual limit.
+    let reply: Reply = call_rpc(client, validator, &body, 64 * 1024 * 1024).await?;
+    Ok(reply.result)
+}
+
+// Addresses a configured validator is reachable at: its URL's IP, or what
+// its hostname resolved to.
+pub async fn validator_addrs(dns: &DnsCache, validator: &Validator) -> Vec<SocketAddr> {
+    let url = validator.rpc_url();
+    let Some(port) = url.port_or_known_default() else {
+        return Vec::new();
+    };
+    match url.host() {
+        Some(Host::Ipv4(ip)) => vec![SocketAddr::new(ip.into(), port)],
+        Some(Host::Ipv6(ip)) => vec![SocketAddr::new(ip.into(), port)],
+        Some(Host::Domain(host)) => dns.lookup(host, port).await.unwrap_or_default(),
+        None => Vec::new(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfiguredNode {
+    pub name: String,
+    pub rpc_url: String,
+    pub in_gossip: bool,
+    pub pubkey: Option<String>,
+    pub advertised_rpc: Option<String>,
+    pub rpc_matches: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnconfiguredNode {
+    pub pubkey: String,
+    pub rpc: String,
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClusterReport {
+    pub configured: Vec<ConfiguredNode>,
+    pub unconfigured_rpc_nodes: Vec<UnconfiguredNode>,
+}
+
+// Matches configured validators to gossip entries by IP: the advertised RPC
+// address first, then the gossip address for nodes that don't advertise RPC
+// where we expect it. `rpc_matches` requires the advertised RPC IP and port
+// to be one we connect to.
+pub fn cross_check(
+    validators: &[(Validator, Vec<SocketAddr>)],
+    nodes: &[ClusterNode],
+) -> ClusterReport {
+    let mut matched = vec![false; nodes.len()];
+
+    let configured = validators
+        .iter()
+        .map(|(validator, addrs)| {
+            let ips: Vec<IpAddr> = addrs.iter().map(SocketAddr::ip).collect();
+            let by_rpc = nodes
+                .iter()
+                .position(|node| node.rpc_addr().is_some_and(|rpc| ips.contains(&rpc.ip())));
+            let found = by_rpc.or_else(|| {
+                nodes.iter().position(|node| {
+                    node.gossip_addr()
+                        .is_some_and(|gossip| ips.contains(&gossip.ip()))
+                })
+            });
+
+            let node = found.map(|idx| {
+                matched[idx] = true;
+                &nodes[idx]
+            });
+            ConfiguredNode {
+                name: validator.name().to_string(),
+                rpc_url: validator.rpc_url().to_string(),
+                in_gossip: node.is_some(),
+                pubkey: node.map(|node| node.pubkey.clone()),
+                advertised_rpc: node.and_then(|node| node.rpc.clone()),
+                rpc_matches: node
+                    .and_then(ClusterNode::rpc_addr)
+                    .is_some_and(|rpc| addrs.contains(&rpc)),
+            }
+        })
+        .collect();
+
+    let unconfigured_rpc_nodes = nodes
+        .iter()
+        .zip(matched)
+        .filter(|(_, matched)| !matched)
+        .filter_map(|(node, _)| {
+            Some(UnconfiguredNode {
+                pubkey: node.pubkey.clone(),
+                rpc: node.rpc.clone()?,
+                version: node.version.clone(),
+            })
+        })
+        .collect();
+
+    ClusterReport {
+        configured,
+        unconfigured_rpc_nodes,
+    }
+}
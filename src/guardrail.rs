@@ -0,0 +1,102 @@
+use serde_json::{Map, Value};
+
+use crate::jsonrpc::{self, RpcMethod};
+
+const GUARDED_METHODS: &[&str] = &["getProgramAccounts", "getTokenAccountsByOwner"];
+
+pub fn applies_to(method: &RpcMethod) -> bool {
+    match method {
+        RpcMethod::Single(name) => GUARDED_METHODS.contains(&name.as_str()),
+        RpcMethod::Batch(names) => names
+            .iter()
+            .flatten()
+            .any(|name| GUARDED_METHODS.contains(&name.as_str())),
+        RpcMethod::Invalid => false,
+    }
+}
+
+// Returns why the call would be too expensive to serve, if it would be.
+pub fn violation(method: &str, params: Option<&Value>) -> Option<String> {
+    match method {
+        "getProgramAccounts" => {
+            let config = config_object(params, 1);
+            let has_filters = config
+                .and_then(|config| config.get("filters"))
+                .and_then(Value::as_array)
+                .is_some_and(|filters| !filters.is_empty());
+            let has_slice = config.is_some_and(|config| config.contains_key("dataSlice"));
+
+            (!has_filters && !has_slice).then(|| {
+                "getProgramAccounts requires `filters` (memcmp/dataSize) or a `dataSlice` in its config"
+                    .to_string()
+            })
+        }
+        "getTokenAccountsByOwner" => {
+            let config = config_object(params, 2);
+            let json_parsed = config
+                .and_then(|config| config.get("encoding"))
+                .and_then(Value::as_str)
+                == Some("jsonParsed");
+            let has_slice = config.is_some_and(|config| config.contains_key("dataSlice"));
+
+            (json_parsed && !has_slice).then(|| {
+                "getTokenAccountsByOwner with jsonParsed encoding requires a `dataSlice`, or use base64 encoding"
+                    .to_string()
+            })
+        }
+        _ => None,
+    }
+}
+
+// Builds the error payload for a request with at least one guarded call, or
+// `None` when everything may go through. A batch is rejected as a whole.
+pub fn reject(body: &[u8]) -> Option<Value> {
+    match serde_json::from_slice::<Value>(body).ok()? {
+        Value::Object(request) => {
+            let message = request_violation(&request)?;
+            Some(error(&request, &message))
+        }
+        Value::Array(requests) => {
+            let violations: Vec<Option<String>> = requests
+                .iter()
+                .map(|request| request.as_object().and_then(request_violation))
+                .collect();
+            let offending = violations.iter().position(Option::is_some)?;
+
+            let responses = requests
+                .iter()
+                .zip(violations)
+                .map(|(request, violation)| {
+                    let empty = Map::new();
+                    let request = request.as_object().unwrap_or(&empty);
+                    let message = violation.unwrap_or_else(|| {
+                        format!("batch rejected because entry {offending} is not allowed")
+                    });
+                    error(request, &message)
+                })
+                .collect();
+            Some(Value::Array(responses))
+        }
+        _ => None,
+    }
+}
+
+fn request_violation(request: &Map<String, Value>) -> Option<String> {
+    let method = request.get("method")?.as_str()?;
+    violation(method, request.get("params"))
+}
+
+fn error(request: &Map<String, Value>, message: &str) -> Value {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    jsonrpc::error_object(id, jsonrpc::INVALID_PARAMS, message, Value::Null)
+}
+
+// Positional params keep the config at a fixed index; by-name params may nest
+// it under `config` or spread its fields next to the named arguments.
+fn config_object(params: Option<&Value>, position: usize) -> Option<&Map<String, Value>> {
+    match params? {
+        Value::Array(items) => items.get(position)?.as_object(),
+        Value::Object(named) => named.get("config").map_or(Some(named), Value::as_object),
+        _ => None,
+    }
+}
@@ -0,0 +1,154 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+// Upper bounds in milliseconds; the last bucket is open-ended.
+pub const DEFAULT_BUCKETS_MS: &[f64] = &[
+    5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0,
+];
+
+// Fixed-bucket latency histogram. Recording is a couple of atomic adds, so
+// it needs no lock on the request path.
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    bounds_us: Vec<u64>,
+    // One more than `bounds_us`, for latencies above the last bound.
+    counts: Vec<AtomicU64>,
+    sum_us: AtomicU64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUCKETS_MS)
+    }
+}
+
+impl LatencyHistogram {
+    // Bounds are expected sorted and positive (see `valid_buckets`).
+    pub fn new(bounds_ms: &[f64]) -> Self {
+        Self {
+            bounds_us: bounds_ms
+                .iter()
+                .map(|ms| (ms * 1000.0).round() as u64)
+                .collect(),
+            counts: (0..=bounds_ms.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum_us: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, latency: Duration) {
+        let micros = latency.as_micros().min(u64::MAX as u128) as u64;
+        let bucket = self.bounds_us.partition_point(|bound| *bound < micros);
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        for count in &self.counts {
+            count.store(0, Ordering::Relaxed);
+        }
+        self.sum_us.store(0, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            bounds_ms: self
+                .bounds_us
+                .iter()
+                .map(|us| *us as f64 / 1000.0)
+                .collect(),
+            counts: self
+                .counts
+                .iter()
+                .map(|count| count.load(Ordering::Relaxed))
+                .collect(),
+            sum_ms: self.sum_us.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+
+    // Only applies a snapshot taken with the same bounds; returns whether
+    // it did.
+    pub fn restore(&self, snapshot: &HistogramSnapshot) -> bool {
+        if snapshot.bounds_ms != self.snapshot().bounds_ms
+            || snapshot.counts.len() != self.counts.len()
+        {
+            return false;
+        }
+        for (count, saved) in self.counts.iter().zip(&snapshot.counts) {
+            count.store(*saved, Ordering::Relaxed);
+        }
+        self.sum_us
+            .store((snapshot.sum_ms * 1000.0).round() as u64, Ordering::Relaxed);
+        true
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistogramSnapshot {
+    pub bounds_ms: Vec<f64>,
+    // Per bucket, not cumulative; the last entry is above every bound.
+    pub counts: Vec<u64>,
+    pub sum_ms: f64,
+}
+
+impl HistogramSnapshot {
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    // Interpolates linearly inside the bucket holding the rank, like
+    // Prometheus' `histogram_quantile`. Ranks in the open-ended bucket
+    // return the last bound.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let total = self.count();
+        if total == 0 {
+            return None;
+        }
+        let rank = q.clamp(0.0, 1.0) * total as f64;
+
+        let mut seen = 0u64;
+        for (idx, count) in self.counts.iter().enumerate() {
+            if *count == 0 || ((seen + count) as f64) < rank {
+                seen += count;
+                continue;
+            }
+            let Some(upper) = self.bounds_ms.get(idx) else {
+                return self.bounds_ms.last().copied();
+            };
+            let lower = if idx == 0 {
+                0.0
+            } else {
+                self.bounds_ms[idx - 1]
+            };
+            let within = (rank - seen as f64) / *count as f64;
+            return Some(lower + (upper - lower) * within);
+        }
+        self.bounds_ms.last().copied()
+    }
+
+    pub fn summary(&self) -> HistogramSummary {
+        HistogramSummary {
+            count: self.count(),
+            p50: self.quantile(0.5),
+            p90: self.quantile(0.9),
+            p99: self.quantile(0.99),
+        }
+    }
+}
+
+// Estimates since start (or the last reset); `None` before any sample.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HistogramSummary {
+    pub count: u64,
+    pub p50: Option<f64>,
+    pub p90: Option<f64>,
+    pub p99: Option<f64>,
+}
+
+pub fn valid_buckets(bounds_ms: &[f64]) -> bool {
+    !bounds_ms.is_empty()
+        && bounds_ms.iter().all(|ms| ms.is_finite() && *ms > 0.0)
+        && bounds_ms.windows(2).all(|pair| pair[0] < pair[1])
+}
@@ -0,0 +1,125 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::{
+    Error,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web::Data,
+};
+use tracing::warn;
+
+use crate::{
+    app_state::AppState,
+    client_limit::{self, IpRange},
+    errors::AppError,
+};
+
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+// What happens to an address on neither list once an allowlist is set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Unlisted {
+    #[default]
+    Deny,
+    Allow,
+}
+
+impl FromStr for Unlisted {
+    type Err = ();
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "deny" => Ok(Self::Deny),
+            "allow" => Ok(Self::Allow),
+            _ => Err(()),
+        }
+    }
+}
+
+// `IP_DENYLIST` wins over `IP_ALLOWLIST`; with neither set every address
+// gets through.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IpFilter {
+    pub allow: Vec<IpRange>,
+    pub deny: Vec<IpRange>,
+    pub unlisted: Unlisted,
+}
+
+impl IpFilter {
+    pub fn is_active(&self) -> bool {
+        !self.allow.is_empty() || !self.deny.is_empty()
+    }
+
+    // A request without a known address only passes when there is no
+    // allowlist to be on.
+    pub fn permits(&self, ip: Option<IpAddr>) -> bool {
+        let Some(ip) = ip else {
+            return self.allow.is_empty();
+        };
+        if self.deny.iter().any(|range| range.contains(ip)) {
+            return false;
+        }
+        self.allow.is_empty()
+            || self.allow.iter().any(|range| range.contains(ip))
+            || self.unlisted == Unlisted::Allow
+    }
+}
+
+// One warning per `LOG_INTERVAL`, so a blocked scanner cannot flood the logs.
+#[derive(Default)]
+pub struct Rejections {
+    state: Mutex<RejectionLog>,
+}
+
+#[derive(Default)]
+struct RejectionLog {
+    last_logged: Option<Instant>,
+    suppressed: u64,
+}
+
+impl Rejections {
+    fn record(&self, ip: Option<IpAddr>, path: &str) {
+        let mut log = self.state.lock().expect("ip filter lock poisoned");
+        if log
+            .last_logged
+            .is_some_and(|at| at.elapsed() < LOG_INTERVAL)
+        {
+            log.suppressed += 1;
+            return;
+        }
+        warn!(
+            client_ip = ip.map(tracing::field::display),
+            path,
+            suppressed = log.suppressed,
+            "request from a blocked address"
+        );
+        log.last_logged = Some(Instant::now());
+        log.suppressed = 0;
+    }
+}
+
+// Wrapped around the whole app, inside `request_id::assign`.
+pub async fn enforce(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    if let Some(state) = req.app_data::<Data<AppState>>().cloned() {
+        let settings = state.settings();
+        if settings.ip_filter.is_active() {
+            let ip =
+                client_limit::client_ip(req.peer_addr(), req.headers(), &settings.trusted_proxies);
+            if !settings.ip_filter.permits(ip) {
+                state.ip_rejections().record(ip, req.path());
+                let err = AppError::Forbidden("client address is not allowed".to_string());
+                return Ok(req.error_response(err).map_into_right_body());
+            }
+        }
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
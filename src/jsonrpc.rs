@@ -0,0 +1,312 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+use actix_web::web::Bytes;
+use serde::Deserialize;
+use serde::de::{self, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_json::{Value, json};
+
+// Server-error range codes for failures that originate in the proxy itself.
+pub const UPSTREAM_UNAVAILABLE: i64 = -32010;
+pub const SELECTION_FAILED: i64 = -32011;
+pub const RATE_LIMITED: i64 = -32012;
+pub const UNSUPPORTED_MEDIA_TYPE: i64 = -32013;
+pub const UNAUTHORIZED: i64 = -32014;
+pub const FORBIDDEN: i64 = -32015;
+pub const NOT_FOUND: i64 = -32016;
+pub const OVERLOADED: i64 = -32017;
+pub const UPSTREAM_TIMEOUT: i64 = -32018;
+pub const INVALID_REGISTRY: i64 = -32019;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcMethod {
+    Single(String),
+    // One entry per batch element; elements without a method are `None`.
+    Batch(Vec<Option<String>>),
+    Invalid,
+}
+
+impl RpcMethod {
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Single(method) => Some(method),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RpcMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Single(method) => f.write_str(method),
+            Self::Batch(methods) => write!(f, "batch[{}]", methods.len()),
+            Self::Invalid => f.write_str("invalid"),
+        }
+    }
+}
+
+// Walks the body without materializing `params`; only `method` is kept.
+struct MethodOnly(Option<String>);
+
+impl<'de> Deserialize<'de> for MethodOnly {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MethodVisitor;
+
+        impl<'de> Visitor<'de> for MethodVisitor {
+            type Value = MethodOnly;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a JSON-RPC request")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<MethodOnly, A::Error> {
+                let mut method = None;
+                while let Some(key) = map.next_key::<Cow<'de, str>>()? {
+                    if key == "method" {
+                        method = map.next_value::<Option<String>>().ok().flatten();
+                    } else {
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                }
+                Ok(MethodOnly(method))
+            }
+
+            // Anything that is not an object still counts as a (malformed) batch entry.
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<MethodOnly, A::Error> {
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(MethodOnly(None))
+            }
+
+            fn visit_bool<E: de::Error>(self, _: bool) -> Result<MethodOnly, E> {
+                Ok(MethodOnly(None))
+            }
+
+            fn visit_i64<E: de::Error>(self, _: i64) -> Result<MethodOnly, E> {
+                Ok(MethodOnly(None))
+            }
+
+            fn visit_u64<E: de::Error>(self, _: u64) -> Result<MethodOnly, E> {
+                Ok(MethodOnly(None))
+            }
+
+            fn visit_f64<E: de::Error>(self, _: f64) -> Result<MethodOnly, E> {
+                Ok(MethodOnly(None))
+            }
+
+            fn visit_str<E: de::Error>(self, _: &str) -> Result<MethodOnly, E> {
+                Ok(MethodOnly(None))
+            }
+
+            fn visit_unit<E: de::Error>(self) -> Result<MethodOnly, E> {
+                Ok(MethodOnly(None))
+            }
+        }
+
+        deserializer.deserialize_any(MethodVisitor)
+    }
+}
+
+pub fn extract_method(body: &[u8]) -> RpcMethod {
+    let is_batch = body
+        .iter()
+        .find(|byte| !byte.is_ascii_whitespace())
+        .is_some_and(|byte| *byte == b'[');
+
+    if is_batch {
+        match serde_json::from_slice::<Vec<MethodOnly>>(body) {
+            Ok(entries) => RpcMethod::Batch(entries.into_iter().map(|entry| entry.0).collect()),
+            Err(_) => RpcMethod::Invalid,
+        }
+    } else {
+        match serde_json::from_slice::<MethodOnly>(body) {
+            Ok(MethodOnly(Some(method))) => RpcMethod::Single(method),
+            _ => RpcMethod::Invalid,
+        }
+    }
+}
+
+pub fn request_id(body: &[u8]) -> Option<Value> {
+    match serde_json::from_slice::<Value>(body).ok()? {
+        Value::Object(mut request) => request.remove("id"),
+        _ => None,
+    }
+}
+
+pub fn error_object(id: Value, code: i64, message: &str, data: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": {
+            "code": code,
+            "message": message,
+            "data": data,
+        },
+        "id": id,
+    })
+}
+
+// Calls with side effects must never be replayed on another validator.
+pub fn is_idempotent(method: &str) -> bool {
+    !matches!(method, "sendTransaction" | "requestAirdrop")
+}
+
+pub fn is_method_unavailable(body: &[u8]) -> bool {
+    #[derive(Deserialize)]
+    struct Response {
+        error: Error,
+    }
+
+    #[derive(Deserialize)]
+    struct Error {
+        code: i64,
+        #[serde(default)]
+        message: String,
+    }
+
+    serde_json::from_slice::<Response>(body).is_ok_and(|response| {
+        let message = response.error.message.to_ascii_lowercase();
+        response.error.code == METHOD_NOT_FOUND
+            || (message.contains("method") && message.contains("disabled"))
+    })
+}
+
+pub fn is_result(body: &[u8]) -> bool {
+    #[derive(Deserialize)]
+    struct Outcome {
+        #[serde(default)]
+        result: Option<IgnoredAny>,
+        #[serde(default)]
+        error: Option<IgnoredAny>,
+    }
+
+    matches!(
+        serde_json::from_slice::<Outcome>(body),
+        Ok(Outcome {
+            result: Some(_),
+            error: None,
+        })
+    )
+}
+
+// A single response or a non-empty batch where every entry carries a
+// `result` or an `error`. Values are skipped, not materialized.
+pub fn is_well_formed(body: &[u8]) -> bool {
+    type Entry = HashMap<String, IgnoredAny>;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Shape {
+        Single(Entry),
+        Batch(Vec<Entry>),
+    }
+
+    let answers = |entry: &Entry| entry.contains_key("result") || entry.contains_key("error");
+    match serde_json::from_slice::<Shape>(body) {
+        Ok(Shape::Single(entry)) => answers(&entry),
+        Ok(Shape::Batch(entries)) => !entries.is_empty() && entries.iter().all(answers),
+        Err(_) => false,
+    }
+}
+
+// The `result` of a response with the dotted `ignore` paths removed. Paths
+// apply to every element when they cross an array.
+pub fn normalized_result<S: AsRef<str>>(body: &[u8], ignore: &[S]) -> Option<Value> {
+    let mut response: Value = serde_json::from_slice(body).ok()?;
+    let mut result = response.get_mut("result")?.take();
+    for path in ignore {
+        let segments: Vec<&str> = path.as_ref().split('.').collect();
+        remove_path(&mut result, &segments);
+    }
+    Some(result)
+}
+
+fn remove_path(value: &mut Value, segments: &[&str]) {
+    match value {
+        Value::Object(object) => match segments {
+            [] => {}
+            [last] => {
+                object.remove(*last);
+            }
+            [first, rest @ ..] => {
+                if let Some(child) = object.get_mut(*first) {
+                    remove_path(child, rest);
+                }
+            }
+        },
+        Value::Array(items) => {
+            for item in items {
+                remove_path(item, segments);
+            }
+        }
+        _ => {}
+    }
+}
+
+pub fn context_slot(body: &[u8]) -> Option<u64> {
+    #[derive(Deserialize)]
+    struct Response {
+        result: ContextResult,
+    }
+
+    #[derive(Deserialize)]
+    struct ContextResult {
+        context: Context,
+    }
+
+    #[derive(Deserialize)]
+    struct Context {
+        slot: u64,
+    }
+
+    serde_json::from_slice::<Response>(body)
+        .ok()
+        .map(|response| response.result.context.slot)
+}
+
+// The newest slot a response reveals: `getSlot` results or any `context.slot`.
+pub fn observed_slot(method: &RpcMethod, body: &[u8]) -> Option<u64> {
+    #[derive(Deserialize)]
+    struct SlotResponse {
+        result: u64,
+    }
+
+    match method {
+        RpcMethod::Single(name) if name == "getSlot" => {
+            serde_json::from_slice::<SlotResponse>(body)
+                .ok()
+                .map(|response| response.result)
+        }
+        RpcMethod::Single(_) => context_slot(body),
+        RpcMethod::Batch(_) => serde_json::from_slice::<Vec<Value>>(body)
+            .ok()?
+            .iter()
+            .filter_map(|response| {
+                response
+                    .pointer("/result/context/slot")
+                    .and_then(Value::as_u64)
+            })
+            .max(),
+        RpcMethod::Invalid => None,
+    }
+}
+
+// Rewrites the `id` of a single JSON-RPC response, leaving batches and
+// unparsable bodies untouched.
+pub fn with_id(body: &Bytes, id: &Value) -> Bytes {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(Value::Object(mut response)) => {
+            if response.get("id") == Some(id) {
+                return body.clone();
+            }
+            response.insert("id".to_string(), id.clone());
+            serde_json::to_vec(&Value::Object(response))
+                .map(Bytes::from)
+                .unwrap_or_else(|_| body.clone())
+        }
+        _ => body.clone(),
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use ring::{hmac, signature};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tracing::{info, warn};
+use url::Url;
+
+use crate::errors::AppError;
+
+// The key set is refetched at most this often, so made-up `kid`s cannot be
+// used to flood the identity provider.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+const JWKS_TIMEOUT: Duration = Duration::from_secs(5);
+const JWKS_MAX_BYTES: usize = 1 << 20;
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum JwtKey {
+    // Shared secret for HS256 tokens.
+    Hs256(Vec<u8>),
+    // Key set for RS256 tokens.
+    Jwks(Url),
+}
+
+// Keeps the secret out of logged settings.
+impl fmt::Debug for JwtKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hs256(_) => f.write_str("Hs256(<redacted>)"),
+            Self::Jwks(url) => f.debug_tuple("Jwks").field(&url.as_str()).finish(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JwtSettings {
+    pub key: JwtKey,
+    // Checked when set; `exp` always is.
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    // Clock skew allowed on `exp` and `nbf`.
+    pub leeway: Duration,
+    // Names the caller in rate limits, stats and logs.
+    pub client_claim: String,
+    pub jwks_refresh: Duration,
+}
+
+impl JwtSettings {
+    pub fn new(key: JwtKey) -> Self {
+        Self {
+            key,
+            issuer: None,
+            audience: None,
+            leeway: Duration::from_secs(60),
+            client_claim: "sub".to_string(),
+            jwks_refresh: Duration::from_secs(300),
+        }
+    }
+}
+
+// Compact JWTs have three dot-separated parts; API keys have none.
+pub fn looks_like_jwt(token: &str) -> bool {
+    token.split('.').count() == 3
+}
+
+#[derive(Deserialize)]
+struct Header {
+    alg: String,
+    kid: Option<String>,
+}
+
+struct Token<'a> {
+    header: Header,
+    claims: Map<String, Value>,
+    signed: &'a str,
+    signature: Vec<u8>,
+}
+
+fn decode(token: &str) -> Result<Token<'_>, AppError> {
+    let malformed = || unauthorized("malformed token");
+    let (signed, signature) = token.rsplit_once('.').ok_or_else(malformed)?;
+    let (header, claims) = signed.split_once('.').ok_or_else(malformed)?;
+    let part = |raw: &str| URL_SAFE_NO_PAD.decode(raw).map_err(|_| malformed());
+    Ok(Token {
+        header: serde_json::from_slice(&part(header)?).map_err(|_| malformed())?,
+        claims: serde_json::from_slice(&part(claims)?).map_err(|_| malformed())?,
+        signed,
+        signature: part(signature)?,
+    })
+}
+
+// Verifies the token and returns the caller it names.
+pub async fn authenticate(
+    settings: &JwtSettings,
+    jwks: &Jwks,
+    token: &str,
+    now: u64,
+) -> Result<String, AppError> {
+    let token = decode(token)?;
+    match &settings.key {
+        JwtKey::Hs256(secret) => {
+            expect_alg(&token, "HS256")?;
+            let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+            hmac::verify(&key, token.signed.as_bytes(), &token.signature)
+                .map_err(|_| unauthorized("invalid token signature"))?;
+        }
+        JwtKey::Jwks(url) => {
+            expect_alg(&token, "RS256")?;
+            let key = jwks
+                .key(url, token.header.kid.as_deref(), settings.jwks_refresh)
+                .await?;
+            key.verify(
+                &signature::RSA_PKCS1_2048_8192_SHA256,
+                token.signed.as_bytes(),
+                &token.signature,
+            )
+            .map_err(|_| unauthorized("invalid token signature"))?;
+        }
+    }
+    check_claims(settings, &token.claims, now)
+}
+
+fn expect_alg(token: &Token<'_>, alg: &str) -> Result<(), AppError> {
+    if token.header.alg == alg {
+        Ok(())
+    } else {
+        Err(unauthorized(&format!(
+            "unsupported token algorithm '{}'",
+            token.header.alg
+        )))
+    }
+}
+
+// `exp` is required; `nbf`, `iss` and `aud` are checked when present or
+// configured. `now` is in Unix seconds.
+pub fn check_claims(
+    settings: &JwtSettings,
+    claims: &Map<String, Value>,
+    now: u64,
+) -> Result<String, AppError> {
+    let leeway = settings.leeway.as_secs();
+    let time = |name: &str| claims.get(name).map(|value| value.as_f64());
+    match time("exp") {
+        None => return Err(unauthorized("token has no 'exp' claim")),
+        Some(None) => return Err(unauthorized("token 'exp' claim is not a number")),
+        Some(Some(exp)) if (now as f64) >= exp + leeway as f64 => {
+            return Err(unauthorized("token has expired"));
+        }
+        Some(Some(_)) => {}
+    }
+    match time("nbf") {
+        Some(None) => return Err(unauthorized("token 'nbf' claim is not a number")),
+        Some(Some(nbf)) if ((now + leeway) as f64) < nbf => {
+            return Err(unauthorized("token is not valid yet"));
+        }
+        _ => {}
+    }
+    if let Some(issuer) = &settings.issuer
+        && claims.get("iss").and_then(Value::as_str) != Some(issuer.as_str())
+    {
+        return Err(unauthorized("token issuer is not accepted"));
+    }
+    if let Some(audience) = &settings.audience {
+        let matches = match claims.get("aud") {
+            Some(Value::String(aud)) => aud == audience,
+            Some(Value::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(audience)),
+            _ => false,
+        };
+        if !matches {
+            return Err(unauthorized("token audience is not accepted"));
+        }
+    }
+    match claims.get(&settings.client_claim) {
+        Some(Value::String(client)) if !client.is_empty() => Ok(client.clone()),
+        Some(Value::Number(client)) => Ok(client.to_string()),
+        _ => Err(unauthorized(&format!(
+            "token has no '{}' claim",
+            settings.client_claim
+        ))),
+    }
+}
+
+fn unauthorized(message: &str) -> AppError {
+    AppError::Unauthorized(message.to_string())
+}
+
+type RsaKey = signature::RsaPublicKeyComponents<Vec<u8>>;
+
+// RS256 keys from `JWT_JWKS_URL`, by `kid`. Refetched once they are older
+// than `JWT_JWKS_REFRESH_SECS`, or early when a token names an unknown key;
+// a failed fetch keeps the keys already known.
+pub struct Jwks {
+    state: Mutex<JwksState>,
+    min_refresh_interval: Duration,
+}
+
+impl Default for Jwks {
+    fn default() -> Self {
+        Self::with_min_refresh_interval(MIN_REFRESH_INTERVAL)
+    }
+}
+
+#[derive(Default)]
+struct JwksState {
+    keys: HashMap<String, RsaKey>,
+    fetched_at: Option<Instant>,
+    attempted_at: Option<Instant>,
+}
+
+#[derive(Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Deserialize)]
+struct Jwk {
+    kty: String,
+    #[serde(default)]
+    kid: String,
+    alg: Option<String>,
+    #[serde(rename = "use")]
+    usage: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+impl Jwks {
+    pub fn with_min_refresh_interval(min_refresh_interval: Duration) -> Self {
+        Self {
+            state: Mutex::default(),
+            min_refresh_interval,
+        }
+    }
+
+    async fn key(&self, url: &Url, kid: Option<&str>, ttl: Duration) -> Result<RsaKey, AppError> {
+        let stale = {
+            let state = self.state.lock().expect("jwks lock poisoned");
+            state.fetched_at.is_none_or(|at| at.elapsed() >= ttl)
+        };
+        if stale {
+            self.refresh(url).await;
+        }
+        if let Some(key) = self.lookup(kid) {
+            return Ok(key);
+        }
+        if !stale {
+            self.refresh(url).await;
+            if let Some(key) = self.lookup(kid) {
+                return Ok(key);
+            }
+        }
+        if self
+            .state
+            .lock()
+            .expect("jwks lock poisoned")
+            .keys
+            .is_empty()
+        {
+            return Err(AppError::Internal(
+                "token signing keys are unavailable".to_string(),
+            ));
+        }
+        Err(unauthorized(&format!(
+            "unknown token key '{}'",
+            kid.unwrap_or_default()
+        )))
+    }
+
+    // A token without a `kid` may use the only key in the set.
+    fn lookup(&self, kid: Option<&str>) -> Option<RsaKey> {
+        let state = self.state.lock().expect("jwks lock poisoned");
+        match kid {
+            Some(kid) => state.keys.get(kid).cloned(),
+            None if state.keys.len() == 1 => state.keys.values().next().cloned(),
+            None => None,
+        }
+    }
+
+    async fn refresh(&self, url: &Url) {
+        {
+            let mut state = self.state.lock().expect("jwks lock poisoned");
+            if state
+                .attempted_at
+                .is_some_and(|at| at.elapsed() < self.min_refresh_interval)
+            {
+                return;
+            }
+            state.attempted_at = Some(Instant::now());
+        }
+        match fetch(url).await {
+            Ok(keys) => {
+                info!(url = %url, keys = keys.len(), "fetched JWKS");
+                let mut state = self.state.lock().expect("jwks lock poisoned");
+                state.keys = keys;
+                state.fetched_at = Some(Instant::now());
+            }
+            Err(err) => warn!(url = %url, error = %err, "could not fetch JWKS"),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().expect("jwks lock poisoned").keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Only RSA signing keys are kept; others are skipped rather than refused.
+async fn fetch(url: &Url) -> Result<HashMap<String, RsaKey>, String> {
+    let client = awc::Client::builder().timeout(JWKS_TIMEOUT).finish();
+    let mut response = client
+        .get(url.as_str())
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("status {}", response.status()));
+    }
+    let set: JwkSet = response
+        .json()
+        .limit(JWKS_MAX_BYTES)
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(set
+        .keys
+        .into_iter()
+        .filter(|jwk| jwk.kty == "RSA")
+        .filter(|jwk| jwk.alg.as_deref().is_none_or(|alg| alg == "RS256"))
+        .filter(|jwk| jwk.usage.as_deref().is_none_or(|usage| usage == "sig"))
+        .filter_map(|jwk| {
+            let n = URL_SAFE_NO_PAD.decode(jwk.n?).ok()?;
+            let e = URL_SAFE_NO_PAD.decode(jwk.e?).ok()?;
+            Some((jwk.kid, RsaKey { n, e }))
+        })
+        .collect())
+}
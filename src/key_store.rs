@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+
+use tracing::{error, info};
+
+use crate::app_state::AppState;
+use crate::auth::ApiKeys;
+
+// What a reload changed, by key id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyChanges {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl KeyChanges {
+    fn between(old: &ApiKeys, new: &ApiKeys) -> Self {
+        let old: HashMap<&str, _> = old
+            .keys()
+            .iter()
+            .map(|key| (key.id.as_str(), key))
+            .collect();
+        let mut changes = Self::default();
+        for key in new.keys() {
+            match old.get(key.id.as_str()) {
+                None => changes.added.push(key.id.clone()),
+                Some(before) if *before != key => changes.changed.push(key.id.clone()),
+                Some(_) => {}
+            }
+        }
+        changes.removed = old
+            .keys()
+            .filter(|id| new.get(id).is_none())
+            .map(|id| id.to_string())
+            .collect();
+        changes.removed.sort();
+        changes
+    }
+}
+
+// The key set in use, behind a handle that reloads swap whole: a request
+// authenticates against the old set or the new one, never a mix, and
+// requests already past auth are left alone.
+pub struct KeyStore {
+    current: RwLock<Option<Arc<ApiKeys>>>,
+    // Of the key file when last read, to notice it changed.
+    seen: Mutex<Option<(SystemTime, u64)>>,
+}
+
+impl KeyStore {
+    pub fn new(keys: Option<ApiKeys>) -> Self {
+        let seen = keys.as_ref().and_then(ApiKeys::file).and_then(file_version);
+        Self {
+            current: RwLock::new(keys.map(Arc::new)),
+            seen: Mutex::new(seen),
+        }
+    }
+
+    pub fn current(&self) -> Option<Arc<ApiKeys>> {
+        self.current
+            .read()
+            .expect("key store lock poisoned")
+            .clone()
+    }
+
+    // Reads `API_KEYS_FILE` again. A file that no longer parses leaves the
+    // keys in use alone.
+    pub fn reload(&self) -> Result<KeyChanges, String> {
+        let Some(current) = self.current() else {
+            return Err("API keys are not configured".to_string());
+        };
+        let Some(path) = current.file() else {
+            return Err("API_KEYS_FILE is not set".to_string());
+        };
+        let version = file_version(path);
+        let reloaded = match current.with_file(path) {
+            Ok(reloaded) => reloaded,
+            Err(err) => {
+                error!(
+                    path = %path.display(),
+                    error = %err,
+                    "API key file reload failed; keeping the previous keys"
+                );
+                // Not retried until the file changes again.
+                *self.seen.lock().expect("key store lock poisoned") = version;
+                return Err(err);
+            }
+        };
+        let changes = KeyChanges::between(&current, &reloaded);
+        info!(
+            path = %path.display(),
+            keys = reloaded.len(),
+            added = ?changes.added,
+            changed = ?changes.changed,
+            removed = ?changes.removed,
+            "reloaded API keys"
+        );
+        *self.current.write().expect("key store lock poisoned") = Some(Arc::new(reloaded));
+        *self.seen.lock().expect("key store lock poisoned") = version;
+        Ok(changes)
+    }
+
+    // Reloads when the key file's modification time or size moved.
+    pub fn reload_if_changed(&self) -> Option<Result<KeyChanges, String>> {
+        let current = self.current()?;
+        let version = file_version(current.file()?);
+        if version == *self.seen.lock().expect("key store lock poisoned") {
+            return None;
+        }
+        Some(self.reload())
+    }
+}
+
+fn file_version(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+pub fn spawn_watch(state: AppState) {
+    let period = state.settings().api_keys_reload_interval;
+    let has_file = state
+        .api_keys()
+        .current()
+        .is_some_and(|keys| keys.file().is_some());
+    if !has_file || period.is_zero() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let state = state.clone();
+            let _ = tokio::task::spawn_blocking(move || state.api_keys().reload_if_changed()).await;
+        }
+    });
+}
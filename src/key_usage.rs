@@ -0,0 +1,358 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::HttpResponse;
+use actix_web::body::{BodySize, MessageBody};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::app_state::AppState;
+use crate::errors::AppError;
+use crate::jsonrpc::RpcMethod;
+use crate::proxy::ProxyRequest;
+use crate::quota::QuotaUsage;
+use crate::stats::{BATCH_METHOD, OTHER_METHOD};
+
+// Hourly buckets kept per key.
+pub const USAGE_HOURS: usize = 24;
+// Metrics label shared by keys past the cardinality cap.
+pub const OTHER_KEY: &str = "other";
+
+// Method names tracked per key before the rest share `other`.
+const METHOD_CAP: usize = 32;
+const SWEEP_INTERVAL_SECS: u64 = 60;
+
+// Stats, logs and metrics only ever see this digest of a key.
+pub fn key_id(key: &str) -> String {
+    Sha256::digest(key.as_bytes())[..8]
+        .iter()
+        .fold(String::new(), |mut id, byte| {
+            let _ = write!(id, "{byte:02x}");
+            id
+        })
+}
+
+// Counts a finished proxy request against the caller's key, if it sent one.
+pub(crate) fn record(
+    state: &AppState,
+    request: &ProxyRequest<'_>,
+    result: &Result<HttpResponse, AppError>,
+) {
+    let Some(key_id) = &request.key_id else {
+        return;
+    };
+    let method = match &request.rpc_method {
+        Some(RpcMethod::Single(name)) => name.as_str(),
+        Some(RpcMethod::Batch(_)) => BATCH_METHOD,
+        Some(RpcMethod::Invalid) | None => OTHER_METHOD,
+    };
+    let (success, response_bytes) = match result {
+        Ok(response) => {
+            let bytes = match response.body().size() {
+                BodySize::Sized(bytes) => bytes,
+                BodySize::None | BodySize::Stream => 0,
+            };
+            (response.status().is_success(), bytes)
+        }
+        Err(_) => (false, 0),
+    };
+    state.stats().record_key_usage(
+        key_id,
+        method,
+        success,
+        request.body.len() as u64,
+        response_bytes,
+    );
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Counts {
+    requests: u64,
+    errors: u64,
+    request_bytes: u64,
+    response_bytes: u64,
+    methods: BTreeMap<String, u64>,
+}
+
+impl Counts {
+    fn add(&mut self, method: &str, success: bool, request_bytes: u64, response_bytes: u64) {
+        self.requests += 1;
+        if !success {
+            self.errors += 1;
+        }
+        self.request_bytes = self.request_bytes.saturating_add(request_bytes);
+        self.response_bytes = self.response_bytes.saturating_add(response_bytes);
+        let method = if self.methods.contains_key(method) || self.methods.len() < METHOD_CAP {
+            method
+        } else {
+            OTHER_METHOD
+        };
+        *self.methods.entry(method.to_string()).or_default() += 1;
+    }
+
+    fn summary(&self) -> UsageSummary {
+        UsageSummary {
+            requests: self.requests,
+            errors: self.errors,
+            error_rate: if self.requests == 0 {
+                0.0
+            } else {
+                self.errors as f64 / self.requests as f64
+            },
+            request_bytes: self.request_bytes,
+            response_bytes: self.response_bytes,
+            methods: self.methods.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct KeyUsage {
+    first_seen: u64,
+    last_seen: u64,
+    // Whether the key has its own label in `/metrics`; kept for the key's
+    // lifetime so its series never moves to `other`.
+    labeled: bool,
+    total: Counts,
+    hours: Vec<(u64, Counts)>,
+}
+
+impl KeyUsage {
+    fn hour(&mut self, hour: u64) -> &mut Counts {
+        let slot = hour as usize % USAGE_HOURS;
+        if self.hours.len() < USAGE_HOURS {
+            self.hours.resize_with(USAGE_HOURS, Default::default);
+        }
+        let (bucket_hour, counts) = &mut self.hours[slot];
+        if *bucket_hour != hour {
+            *bucket_hour = hour;
+            *counts = Counts::default();
+        }
+        counts
+    }
+}
+
+// Usage per key id, bounded by `max_keys`. Keys idle for longer than
+// `retention` are dropped; when the table is still full, the least recently
+// seen key makes room.
+#[derive(Debug)]
+pub struct KeyUsageTable {
+    keys: HashMap<String, KeyUsage>,
+    max_keys: usize,
+    retention_secs: u64,
+    labeled_keys: usize,
+    max_labeled_keys: usize,
+    last_sweep: u64,
+}
+
+impl Default for KeyUsageTable {
+    fn default() -> Self {
+        Self::new(10_000, Duration::from_secs(86_400), 100)
+    }
+}
+
+impl KeyUsageTable {
+    pub fn new(max_keys: usize, retention: Duration, max_labeled_keys: usize) -> Self {
+        Self {
+            keys: HashMap::new(),
+            max_keys,
+            retention_secs: retention.as_secs(),
+            labeled_keys: 0,
+            max_labeled_keys,
+            last_sweep: 0,
+        }
+    }
+
+    pub fn record(
+        &mut self,
+        key_id: &str,
+        method: &str,
+        success: bool,
+        request_bytes: u64,
+        response_bytes: u64,
+        now: u64,
+    ) {
+        if now.saturating_sub(self.last_sweep) >= SWEEP_INTERVAL_SECS {
+            self.evict_idle(now);
+        }
+        if !self.keys.contains_key(key_id) {
+            if self.max_keys == 0 {
+                return;
+            }
+            if self.keys.len() >= self.max_keys {
+                self.evict_idle(now);
+            }
+            if self.keys.len() >= self.max_keys
+                && let Some(oldest) = self
+                    .keys
+                    .iter()
+                    .min_by_key(|(_, usage)| usage.last_seen)
+                    .map(|(id, _)| id.clone())
+            {
+                self.remove(&oldest);
+            }
+            let labeled = self.labeled_keys < self.max_labeled_keys;
+            self.labeled_keys += usize::from(labeled);
+            self.keys.insert(
+                key_id.to_string(),
+                KeyUsage {
+                    first_seen: now,
+                    last_seen: now,
+                    labeled,
+                    total: Counts::default(),
+                    hours: Vec::new(),
+                },
+            );
+        }
+
+        let usage = self.keys.get_mut(key_id).expect("key usage just inserted");
+        usage.last_seen = usage.last_seen.max(now);
+        usage
+            .total
+            .add(method, success, request_bytes, response_bytes);
+        usage
+            .hour(now / 3600)
+            .add(method, success, request_bytes, response_bytes);
+    }
+
+    // Returns how many keys were dropped.
+    pub fn evict_idle(&mut self, now: u64) -> usize {
+        self.last_sweep = now;
+        let idle: Vec<String> = self
+            .keys
+            .iter()
+            .filter(|(_, usage)| now.saturating_sub(usage.last_seen) > self.retention_secs)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &idle {
+            self.remove(id);
+        }
+        idle.len()
+    }
+
+    fn remove(&mut self, key_id: &str) {
+        if let Some(usage) = self.keys.remove(key_id)
+            && usage.labeled
+        {
+            self.labeled_keys -= 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.labeled_keys = 0;
+    }
+
+    // Totals plus the hours of the last `USAGE_HOURS` that saw traffic,
+    // oldest first.
+    pub fn usage(&self, key_id: &str, now: u64) -> Option<KeyUsageReport> {
+        let usage = self.keys.get(key_id)?;
+        let current = now / 3600;
+        let mut hours: Vec<HourlyUsage> = usage
+            .hours
+            .iter()
+            .filter(|(hour, counts)| {
+                counts.requests > 0 && current.saturating_sub(*hour) < USAGE_HOURS as u64
+            })
+            .map(|(hour, counts)| HourlyUsage {
+                hour_start_unix_ms: hour * 3_600_000,
+                usage: counts.summary(),
+            })
+            .collect();
+        hours.sort_by_key(|hour| hour.hour_start_unix_ms);
+        Some(KeyUsageReport {
+            key_id: key_id.to_string(),
+            first_seen_unix_ms: usage.first_seen * 1000,
+            last_seen_unix_ms: usage.last_seen * 1000,
+            total: usage.total.summary(),
+            hours,
+            quota: None,
+        })
+    }
+
+    // Totals per metrics label, sorted; keys past the label cap are summed
+    // under `other`.
+    pub fn metric_totals(&self) -> Vec<(String, UsageSummary)> {
+        let mut labeled: BTreeMap<String, Counts> = BTreeMap::new();
+        let mut other: Option<Counts> = None;
+        for (id, usage) in &self.keys {
+            let counts = if usage.labeled {
+                labeled.entry(id.clone()).or_default()
+            } else {
+                other.get_or_insert_with(Counts::default)
+            };
+            counts.requests += usage.total.requests;
+            counts.errors += usage.total.errors;
+            counts.request_bytes = counts
+                .request_bytes
+                .saturating_add(usage.total.request_bytes);
+            counts.response_bytes = counts
+                .response_bytes
+                .saturating_add(usage.total.response_bytes);
+        }
+        labeled
+            .into_iter()
+            .chain(other.map(|counts| (OTHER_KEY.to_string(), counts)))
+            .map(|(id, counts)| (id, counts.summary()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageSummary {
+    pub requests: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    pub request_bytes: u64,
+    pub response_bytes: u64,
+    pub methods: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HourlyUsage {
+    pub hour_start_unix_ms: u64,
+    #[serde(flatten)]
+    pub usage: UsageSummary,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeyUsageReport {
+    pub key_id: String,
+    pub first_seen_unix_ms: u64,
+    pub last_seen_unix_ms: u64,
+    pub total: UsageSummary,
+    pub hours: Vec<HourlyUsage>,
+    // Set when API keys are configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota: Option<QuotaUsage>,
+}
+
+impl KeyUsageReport {
+    // For a key with no traffic since startup.
+    pub fn empty(key_id: &str) -> Self {
+        Self {
+            key_id: key_id.to_string(),
+            first_seen_unix_ms: 0,
+            last_seen_unix_ms: 0,
+            total: Counts::default().summary(),
+            hours: Vec::new(),
+            quota: None,
+        }
+    }
+}
@@ -1,8 +1,66 @@
+pub mod access_log;
+pub mod account_cache;
+pub mod admin;
 pub mod app_state;
+pub mod auth;
+pub mod base58;
+pub mod batch;
+pub mod body_log;
+pub mod cache;
+pub mod cli;
+pub mod client_limit;
+pub mod coalesce;
+pub mod commitment;
+pub mod compression;
+pub mod concurrency;
 pub mod config;
+pub mod cors;
+pub mod dns;
+pub mod effective_config;
+pub mod env_file;
+#[cfg(feature = "sentry")]
+pub mod error_reporting;
+pub mod error_window;
 pub mod errors;
+pub mod fleet;
+pub mod guardrail;
+pub mod histogram;
+pub mod ip_filter;
+pub mod jsonrpc;
+pub mod jwt;
+pub mod key_store;
+pub mod key_usage;
+pub mod listeners;
+pub mod log_file;
+pub mod metrics;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod panic_guard;
+pub mod proxy;
+pub mod quorum;
+pub mod quota;
+pub mod rate_limit;
+pub mod registry_check;
+pub mod registry_store;
+pub mod request_id;
+pub mod rest;
 pub mod routes;
+pub mod routing;
+pub mod shadow;
+pub mod signature;
+pub mod size_limits;
+pub mod slow_request;
+pub mod snapshot;
+pub mod stats;
+#[cfg(feature = "statsd")]
+pub mod statsd;
+pub mod status_policy;
+pub mod subscriptions;
+pub mod telemetry;
+pub mod tls;
+pub mod trace_context;
 pub mod validators;
+pub mod version;
 
 pub use app_state::AppState;
 pub use config::Settings;
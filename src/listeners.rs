@@ -0,0 +1,124 @@
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, TcpListener};
+
+// One address the server accepts connections on. `BIND_ADDRESS` lists them
+// comma separated, each an address followed by optional `tls=` and `auth=`
+// options:
+//
+//   0.0.0.0:443 tls=true, 10.0.0.5:8080 tls=false auth=false
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listener {
+    pub address: String,
+    pub tls: bool,
+    // Off leaves client auth out on this listener, for trusted networks.
+    pub auth: bool,
+}
+
+// `tls` is what a listener without a `tls=` option gets: on whenever a
+// certificate is configured. The address may also be given as
+// `address=...`, the spelling of a config file's `[[listeners]]` table.
+pub fn parse(raw: &str, tls: bool) -> Result<Vec<Listener>, String> {
+    let listeners = raw
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| parse_entry(entry, tls))
+        .collect::<Result<Vec<_>, _>>()?;
+    if listeners.is_empty() {
+        return Err("no listen address".to_string());
+    }
+    Ok(listeners)
+}
+
+fn parse_entry(entry: &str, tls: bool) -> Result<Listener, String> {
+    let mut listener = Listener {
+        address: String::new(),
+        tls,
+        auth: true,
+    };
+    for token in entry.split_whitespace() {
+        let flag = |value: &str| {
+            value
+                .parse()
+                .map_err(|_| format!("'{token}' in '{entry}' is not true or false"))
+        };
+        match token.split_once('=') {
+            None | Some(("address", _)) if !listener.address.is_empty() => {
+                return Err(format!("'{entry}' names more than one address"));
+            }
+            None => listener.address = token.to_string(),
+            Some(("address", address)) => listener.address = address.to_string(),
+            Some(("tls", value)) => listener.tls = flag(value)?,
+            Some(("auth", value)) => listener.auth = flag(value)?,
+            Some((option, _)) => {
+                return Err(format!(
+                    "unknown option '{option}' in '{entry}'; expected tls or auth"
+                ));
+            }
+        }
+    }
+    if listener.address.is_empty() {
+        return Err(format!("'{entry}' has no address"));
+    }
+    Ok(listener)
+}
+
+// What a shorthand lacks: `8080` and `:8080` listen on every IPv4
+// interface, and a host or IPv6 address alone on port 80.
+const DEFAULT_HOST: &str = "0.0.0.0";
+const DEFAULT_PORT: u16 = 80;
+
+// Spells out a shorthand as `host:port`, checking the result. On failure,
+// the `host:port` the input was read as.
+pub fn normalize_address(raw: &str) -> Result<String, String> {
+    let raw = raw.trim();
+    let port = raw.strip_prefix(':').unwrap_or(raw);
+    let address = if !port.is_empty() && port.bytes().all(|byte| byte.is_ascii_digit()) {
+        format!("{DEFAULT_HOST}:{port}")
+    } else if let Ok(ip) = raw.parse::<Ipv6Addr>() {
+        format!("[{ip}]:{DEFAULT_PORT}")
+    } else if (raw.starts_with('[') && raw.ends_with(']')) || !raw.contains(':') {
+        format!("{raw}:{DEFAULT_PORT}")
+    } else {
+        raw.to_string()
+    };
+    let Some((host, port)) = address.rsplit_once(':') else {
+        return Err(address);
+    };
+    let valid_host = match host.strip_prefix('[') {
+        Some(ip) => ip
+            .strip_suffix(']')
+            .is_some_and(|ip| ip.parse::<Ipv6Addr>().is_ok()),
+        None => {
+            host.parse::<Ipv4Addr>().is_ok()
+                || (!host.is_empty()
+                    && !host.starts_with(['-', '.'])
+                    && host
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.'))
+        }
+    };
+    if valid_host && port.parse::<u16>().is_ok() {
+        Ok(address)
+    } else {
+        Err(address)
+    }
+}
+
+// Binds every listener before any serves, so a taken port or a bad address
+// stops startup with the address named.
+pub fn bind_all(listeners: &[Listener]) -> io::Result<Vec<(Listener, TcpListener)>> {
+    listeners
+        .iter()
+        .map(|listener| {
+            TcpListener::bind(&listener.address)
+                .map(|socket| (listener.clone(), socket))
+                .map_err(|err| {
+                    io::Error::new(
+                        err.kind(),
+                        format!("cannot listen on {}: {err}", listener.address),
+                    )
+                })
+        })
+        .collect()
+}
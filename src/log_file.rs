@@ -0,0 +1,153 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use tracing_appender::rolling::{self, RollingFileAppender};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rotation {
+    // Time-based files get a `.YYYY-MM-DD` (or `-HH`) suffix.
+    #[default]
+    Daily,
+    Hourly,
+    // The current file keeps its name; older ones become `.1`, `.2`, ...
+    Size,
+    Never,
+}
+
+impl FromStr for Rotation {
+    type Err = ();
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.to_ascii_lowercase().as_str() {
+            "daily" => Ok(Self::Daily),
+            "hourly" => Ok(Self::Hourly),
+            "size" => Ok(Self::Size),
+            "never" => Ok(Self::Never),
+            _ => Err(()),
+        }
+    }
+}
+
+// Opens `path` for appending. `max_files` bounds the rotated files kept
+// (0 keeps them all); `max_bytes` only applies to `Rotation::Size`.
+pub fn open(
+    path: &Path,
+    rotation: Rotation,
+    max_bytes: u64,
+    max_files: usize,
+) -> io::Result<Box<dyn Write + Send>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| invalid(format!("{} does not name a file", path.display())))?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if !dir.is_dir() {
+        return Err(invalid(format!(
+            "directory {} does not exist",
+            dir.display()
+        )));
+    }
+
+    let rotation = match rotation {
+        Rotation::Size => {
+            return Ok(Box::new(SizeRotatingFile::open(
+                path.to_path_buf(),
+                max_bytes,
+                max_files,
+            )?));
+        }
+        Rotation::Daily => rolling::Rotation::DAILY,
+        Rotation::Hourly => rolling::Rotation::HOURLY,
+        Rotation::Never => rolling::Rotation::NEVER,
+    };
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(file_name);
+    if max_files > 0 {
+        builder = builder.max_log_files(max_files);
+    }
+    let appender = builder.build(dir).map_err(io::Error::other)?;
+    Ok(Box::new(appender))
+}
+
+// Rolls over before a write would take the file past `max_bytes`. A single
+// write larger than that still goes to a fresh file whole.
+pub struct SizeRotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl SizeRotatingFile {
+    pub fn open(path: PathBuf, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        let file = append(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files,
+            file,
+            written,
+        })
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{index}"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files > 0 {
+            let _ = fs::remove_file(self.rotated(self.max_files));
+        }
+        let mut index = if self.max_files > 0 {
+            self.max_files - 1
+        } else {
+            // Keeping everything: shift past the highest existing index.
+            (1..)
+                .find(|index| !self.rotated(*index).exists())
+                .unwrap_or(1)
+                - 1
+        };
+        while index > 0 {
+            let from = self.rotated(index);
+            if from.exists() {
+                fs::rename(&from, self.rotated(index + 1))?;
+            }
+            index -= 1;
+        }
+        fs::rename(&self.path, self.rotated(1))?;
+        self.file = append(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
@@ -1,53 +1,268 @@
-use std::io::{Error as IoError, ErrorKind};
+use std::env;
+use std::io::Error as IoError;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
 
-use actix_web::{App, HttpServer, middleware::Logger, web::Data};
-use tracing::info;
-use tracing_subscriber::{EnvFilter, fmt};
+use actix_web::{App, HttpServer, dev::ServerHandle, middleware::from_fn, web::Data};
+use tracing::{info, warn};
 
-use the_solana_api::{AppState, Settings, ValidatorRegistry, routes};
+use the_solana_api::{
+    AppState, Settings,
+    cli::{self, Args, Command},
+    config, effective_config, fleet, ip_filter, listeners, panic_guard,
+    registry_check::RegistryReport,
+    request_id, routes, snapshot, telemetry,
+    tls::{self, CertStore},
+    version,
+};
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    init_tracing();
+fn main() -> std::io::Result<ExitCode> {
+    let args = Args::parse(env::args().skip(1)).map_err(to_io_error)?;
+    if args.help {
+        print!("{}", cli::help());
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.version {
+        println!("the-solana-api {}", version::VERSION);
+        return Ok(ExitCode::SUCCESS);
+    }
+    match args.command {
+        Some(Command::Validate { file, json }) => validate(file, json),
+        Some(Command::Probe { json, min_healthy }) => {
+            actix_web::rt::System::new().block_on(probe(&args, json, min_healthy))
+        }
+        Some(Command::PrintConfig { toml }) => print_config(&args, toml),
+        None => actix_web::rt::System::new()
+            .block_on(serve(args))
+            .map(|()| ExitCode::SUCCESS),
+    }
+}
+
+// Needs nothing but the registry file, so CI can run it before deploying.
+fn validate(file: Option<PathBuf>, json: bool) -> std::io::Result<ExitCode> {
+    let file = file
+        .or_else(|| {
+            config::process_env()
+                .remove("VALIDATORS_CSV")
+                .map(PathBuf::from)
+        })
+        .unwrap_or_else(|| Settings::default().validators_csv);
+    let report = RegistryReport::from_file(&file);
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).map_err(to_io_error)?
+        );
+    } else {
+        print!("{}", report.render());
+    }
+    Ok(if report.valid {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+// Secrets are redacted, so the output can be pasted into a bug report.
+fn print_config(args: &Args, toml: bool) -> std::io::Result<ExitCode> {
+    let settings = Settings::load_with(args).map_err(to_io_error)?;
+    if toml {
+        print!("{}", effective_config::render_toml(&settings));
+    } else {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&effective_config::render(&settings))
+                .map_err(to_io_error)?
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// The `/fleet/health` check, run once from the command line.
+async fn probe(args: &Args, json: bool, min_healthy: Option<usize>) -> std::io::Result<ExitCode> {
+    let settings = Settings::load_with(args).map_err(to_io_error)?;
+    let registry = settings.load_registry().map_err(to_io_error)?;
+    let state = AppState::from_settings(registry, settings);
+    let report = fleet::check_health(
+        &state.build_client(),
+        state.registry().validators(),
+        state.settings().fleet_concurrency,
+        state.settings().fleet_node_timeout,
+    )
+    .await;
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).map_err(to_io_error)?
+        );
+    } else {
+        print!("{}", report.render());
+    }
+    let required = min_healthy.unwrap_or(report.summary.total);
+    Ok(if report.summary.ok >= required {
+        ExitCode::SUCCESS
+    } else {
+        if !json {
+            println!("{} ok, {required} required", report.summary.ok);
+        }
+        ExitCode::FAILURE
+    })
+}
 
-    let settings = Settings::from_env().map_err(to_io_error)?;
-    let registry =
-        ValidatorRegistry::from_csv(settings.validators_csv.as_path()).map_err(to_io_error)?;
+async fn serve(args: Args) -> std::io::Result<()> {
+    let settings = Settings::load_with(&args).map_err(to_io_error)?;
+    let tracing_guard = telemetry::init_tracing(&settings)?;
+    for key in &settings.unknown_config_keys {
+        warn!(key, "unknown key in CONFIG_FILE ignored");
+    }
+    for line in &settings.env_file_warnings {
+        warn!(line, "malformed env file line ignored");
+    }
+    #[cfg(feature = "sentry")]
+    let _sentry = the_solana_api::error_reporting::init(&settings);
 
-    let state = AppState::new(registry);
-    let bind_address = settings.bind_address.clone();
+    let registry = settings.load_registry().map_err(to_io_error)?;
+    settings
+        .routing_rules
+        .validate(&registry)
+        .map_err(to_io_error)?;
+    if let Some(location) = &settings.default_location
+        && !registry.has_location(location)
+    {
+        warn!(
+            location,
+            "no validator in DEFAULT_LOCATION, the whole fleet is used"
+        );
+    }
+    let certs = match &settings.tls {
+        Some(tls) => Some(Arc::new(CertStore::load(tls.clone()).map_err(to_io_error)?)),
+        None => None,
+    };
 
+    let sockets = listeners::bind_all(&settings.listeners)?;
+    let open_listeners = sockets
+        .iter()
+        .filter(|(listener, _)| !listener.auth)
+        .map(|(_, socket)| socket.local_addr())
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let health_bind_address = settings.health_bind_address.clone();
+    let registry_source = settings.registry_source();
+    let state = AppState::from_settings(registry, settings).with_open_listeners(open_listeners);
+    snapshot::restore_on_startup(&state);
+    state.spawn_background_tasks();
+
+    let workers = state.settings().workers;
     info!(
-        %bind_address,
-        csv = %settings.validators_csv.display(),
+        listeners = sockets.len(),
+        health_bind_address = health_bind_address.as_deref(),
+        workers,
+        registry = %registry_source,
         validators = state.registry().validators().len(),
         "starting server"
     );
+    info!(
+        max_connections_per_host = state.settings().upstream_max_connections_per_host,
+        keepalive_secs = state.settings().upstream_keepalive.as_secs(),
+        conn_lifetime_secs = state.settings().upstream_conn_lifetime.as_secs(),
+        disconnect_timeout_ms = state.settings().upstream_disconnect_timeout.as_millis() as u64,
+        "upstream connection pool"
+    );
 
     let app_state = state.clone();
 
-    HttpServer::new(move || {
-        App::new()
-            .wrap(Logger::default())
-            .app_data(Data::new(app_state.clone()))
+    let mut server = HttpServer::new(move || {
+        let app = App::new()
+            .wrap(from_fn(ip_filter::enforce))
+            .wrap(from_fn(panic_guard::catch))
+            .wrap(from_fn(request_id::assign))
+            .wrap(telemetry::request_logger());
+        #[cfg(feature = "sentry")]
+        let app = app.wrap(the_solana_api::error_reporting::middleware());
+        app.app_data(Data::new(app_state.clone()))
             .configure(routes::configure)
     })
-    .bind(bind_address)?
-    .run()
-    .await
+    .workers(workers)
+    .shutdown_timeout(state.settings().shutdown_timeout.as_secs())
+    // `drain_on_signal` stops the server, once readiness is withdrawn.
+    .disable_signals();
+    if let Some(certs) = &certs {
+        tls::spawn_watch(certs.clone());
+    }
+    for (listener, socket) in sockets {
+        info!(
+            address = %socket.local_addr()?,
+            tls = listener.tls,
+            auth = listener.auth,
+            "listening"
+        );
+        server = match (&certs, listener.tls) {
+            (Some(certs), true) => server.listen_rustls_0_23(socket, certs.server_config())?,
+            _ => server.listen(socket)?,
+        };
+    }
+
+    let health = match health_bind_address {
+        Some(address) => {
+            let health_state = state.clone();
+            let server = HttpServer::new(move || {
+                App::new()
+                    .app_data(Data::new(health_state.clone()))
+                    .configure(routes::configure_health)
+            })
+            .workers(1)
+            .bind(&address)
+            .map_err(|err| IoError::new(err.kind(), format!("cannot listen on {address}: {err}")))?
+            .run();
+            let handle = server.handle();
+            actix_web::rt::spawn(server);
+            Some(handle)
+        }
+        None => None,
+    };
+
+    let server = server.run();
+    actix_web::rt::spawn(drain_on_signal(state.clone(), server.handle()));
+    server.await?;
+    if let Some(health) = health {
+        health.stop(true).await;
+    }
+
+    snapshot::persist(&state);
+    telemetry::shutdown();
+    drop(tracing_guard);
+    Ok(())
 }
 
-fn init_tracing() {
-    let env_filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new("info,the_solana_api=info"));
+// On SIGTERM or Ctrl-C: `/readyz` turns 503 and new proxied requests are
+// refused, while those in flight get `SHUTDOWN_TIMEOUT_SECS` to finish.
+async fn drain_on_signal(state: AppState, server: ServerHandle) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = terminate.recv() => {}
+                _ = tokio::signal::ctrl_c() => {}
+            },
+            Err(_) => {
+                warn!("cannot listen for SIGTERM, draining on Ctrl-C only");
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
 
-    let _ = fmt()
-        .with_env_filter(env_filter)
-        .with_target(false)
-        .compact()
-        .try_init();
+    info!(
+        timeout_secs = state.settings().shutdown_timeout.as_secs(),
+        "shutting down, draining requests in flight"
+    );
+    state.start_draining();
+    server.stop(true).await;
 }
 
 fn to_io_error<E: std::error::Error>(error: E) -> IoError {
-    IoError::new(ErrorKind::Other, error.to_string())
+    IoError::other(error.to_string())
 }
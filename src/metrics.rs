@@ -0,0 +1,235 @@
+use std::fmt::Write;
+
+use crate::app_state::AppState;
+use crate::stats::TrafficSnapshot;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+const LATENCY: &str = "solana_api_upstream_latency_seconds";
+const BYTES_SENT: &str = "solana_api_upstream_bytes_sent_total";
+const BYTES_RECEIVED: &str = "solana_api_upstream_bytes_received_total";
+const UPSTREAM_ERRORS: &str = "solana_api_upstream_errors_total";
+const KEY_REQUESTS: &str = "solana_api_key_requests_total";
+const KEY_ERRORS: &str = "solana_api_key_errors_total";
+const KEY_REQUEST_BYTES: &str = "solana_api_key_request_bytes_total";
+const KEY_RESPONSE_BYTES: &str = "solana_api_key_response_bytes_total";
+const PANICS: &str = "solana_api_panics_total";
+const IN_FLIGHT: &str = "solana_api_requests_in_flight";
+const QUEUED: &str = "solana_api_requests_queued";
+const SHED: &str = "solana_api_requests_shed_total";
+const QUEUE_DEPTH: &str = "solana_api_queue_depth";
+const QUEUE_WAIT: &str = "solana_api_queue_wait_seconds";
+const QUEUE_SHED: &str = "solana_api_queue_shed_total";
+
+// Prometheus text exposition of the per-validator stats.
+pub fn render(state: &AppState) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# HELP {LATENCY} Time until an upstream validator's response headers arrived."
+    );
+    let _ = writeln!(out, "# TYPE {LATENCY} histogram");
+
+    for validator in state.registry().validators() {
+        let name = label_value(validator.name());
+        let snapshot = state
+            .stats()
+            .validator(validator.name())
+            .latency_histogram();
+        let mut cumulative = 0;
+        for (idx, count) in snapshot.counts.iter().enumerate() {
+            cumulative += count;
+            let le = snapshot
+                .bounds_ms
+                .get(idx)
+                .map(|ms| (ms / 1000.0).to_string())
+                .unwrap_or_else(|| "+Inf".to_string());
+            let _ = writeln!(
+                out,
+                "{LATENCY}_bucket{{validator=\"{name}\",le=\"{le}\"}} {cumulative}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{LATENCY}_sum{{validator=\"{name}\"}} {}",
+            snapshot.sum_ms / 1000.0
+        );
+        let _ = writeln!(out, "{LATENCY}_count{{validator=\"{name}\"}} {cumulative}");
+    }
+
+    let traffic: Vec<(String, TrafficSnapshot)> = state
+        .registry()
+        .validators()
+        .iter()
+        .map(|validator| {
+            let stats = state.stats().validator(validator.name());
+            (label_value(validator.name()), stats.traffic())
+        })
+        .collect();
+    counter(
+        &mut out,
+        BYTES_SENT,
+        "Request body bytes sent to an upstream validator.",
+        "validator",
+        traffic
+            .iter()
+            .map(|(name, traffic)| (name, traffic.bytes_sent)),
+    );
+    counter(
+        &mut out,
+        BYTES_RECEIVED,
+        "Response body bytes received from an upstream validator.",
+        "validator",
+        traffic
+            .iter()
+            .map(|(name, traffic)| (name, traffic.bytes_received)),
+    );
+
+    let _ = writeln!(
+        out,
+        "# HELP {UPSTREAM_ERRORS} Failed upstream attempts by validator and kind."
+    );
+    let _ = writeln!(out, "# TYPE {UPSTREAM_ERRORS} counter");
+    for validator in state.registry().validators() {
+        let name = label_value(validator.name());
+        let stats = state.stats().validator(validator.name());
+        for (kind, count) in stats.upstream_errors_by_kind() {
+            let _ = writeln!(
+                out,
+                "{UPSTREAM_ERRORS}{{validator=\"{name}\",kind=\"{kind}\"}} {count}"
+            );
+        }
+    }
+
+    // Keys past `KEY_METRICS_MAX_KEYS` share the `other` series.
+    let keys: Vec<_> = state
+        .stats()
+        .key_metric_totals()
+        .into_iter()
+        .map(|(id, usage)| (label_value(&id), usage))
+        .collect();
+    counter(
+        &mut out,
+        KEY_REQUESTS,
+        "Proxied requests per API key.",
+        "key_id",
+        keys.iter().map(|(id, usage)| (id, usage.requests)),
+    );
+    counter(
+        &mut out,
+        KEY_ERRORS,
+        "Proxied requests per API key that did not get a 2xx.",
+        "key_id",
+        keys.iter().map(|(id, usage)| (id, usage.errors)),
+    );
+    counter(
+        &mut out,
+        KEY_REQUEST_BYTES,
+        "Request body bytes received per API key.",
+        "key_id",
+        keys.iter().map(|(id, usage)| (id, usage.request_bytes)),
+    );
+    counter(
+        &mut out,
+        KEY_RESPONSE_BYTES,
+        "Response body bytes sent per API key.",
+        "key_id",
+        keys.iter().map(|(id, usage)| (id, usage.response_bytes)),
+    );
+
+    let _ = writeln!(out, "# HELP {PANICS} Requests whose handler panicked.");
+    let _ = writeln!(out, "# TYPE {PANICS} counter");
+    let _ = writeln!(out, "{PANICS} {}", state.stats().panics());
+
+    if let Some(limit) = state.concurrency_limit() {
+        for (metric, kind, help, value) in [
+            (
+                IN_FLIGHT,
+                "gauge",
+                "Requests holding a MAX_CONCURRENT_REQUESTS slot.",
+                limit.in_flight() as u64,
+            ),
+            (
+                QUEUED,
+                "gauge",
+                "Requests waiting for a slot.",
+                limit.queued(),
+            ),
+            (
+                SHED,
+                "counter",
+                "Requests refused for want of a slot.",
+                limit.shed(),
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {metric} {help}");
+            let _ = writeln!(out, "# TYPE {metric} {kind}");
+            let _ = writeln!(out, "{metric} {value}");
+        }
+
+        let queues: Vec<_> = limit
+            .queues()
+            .into_iter()
+            .map(|(location, stats)| (label_value(&location), stats))
+            .collect();
+        let _ = writeln!(
+            out,
+            "# HELP {QUEUE_DEPTH} Requests waiting for a slot, by target location."
+        );
+        let _ = writeln!(out, "# TYPE {QUEUE_DEPTH} gauge");
+        for (location, stats) in &queues {
+            let _ = writeln!(
+                out,
+                "{QUEUE_DEPTH}{{location=\"{location}\"}} {}",
+                stats.depth
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP {QUEUE_WAIT} Time queued requests waited for a slot, by target location."
+        );
+        let _ = writeln!(out, "# TYPE {QUEUE_WAIT} summary");
+        for (location, stats) in &queues {
+            let _ = writeln!(
+                out,
+                "{QUEUE_WAIT}_sum{{location=\"{location}\"}} {}",
+                stats.wait_total.as_secs_f64()
+            );
+            let _ = writeln!(
+                out,
+                "{QUEUE_WAIT}_count{{location=\"{location}\"}} {}",
+                stats.served
+            );
+        }
+        counter(
+            &mut out,
+            QUEUE_SHED,
+            "Requests refused for want of a slot, by target location.",
+            "location",
+            queues
+                .iter()
+                .map(|(location, stats)| (location, stats.shed)),
+        );
+    }
+    out
+}
+
+fn counter<'a>(
+    out: &mut String,
+    metric: &str,
+    help: &str,
+    label: &str,
+    values: impl Iterator<Item = (&'a String, u64)>,
+) {
+    let _ = writeln!(out, "# HELP {metric} {help}");
+    let _ = writeln!(out, "# TYPE {metric} counter");
+    for (label_value, value) in values {
+        let _ = writeln!(out, "{metric}{{{label}=\"{label_value}\"}} {value}");
+    }
+}
+
+fn label_value(raw: &str) -> String {
+    raw.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,99 @@
+use std::sync::OnceLock;
+
+use actix_web::http::header::HeaderMap;
+use opentelemetry::Context;
+use opentelemetry::trace::{
+    SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState, TracerProvider as _,
+};
+use opentelemetry_sdk::{Resource, trace::SdkTracer, trace::SdkTracerProvider};
+use tracing::{Span, Subscriber, warn};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::trace_context::{TRACEPARENT, TraceParent};
+
+const SERVICE_NAME: &str = "the-solana-api";
+
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+// Exports spans over OTLP, configured by the standard `OTEL_EXPORTER_OTLP_*`
+// and `OTEL_SERVICE_NAME`/`OTEL_RESOURCE_ATTRIBUTES` variables. Returns
+// `None` when `OTEL_SDK_DISABLED=true` or the exporter cannot be built.
+pub fn layer<S>() -> Option<OpenTelemetryLayer<S, SdkTracer>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    if std::env::var("OTEL_SDK_DISABLED").is_ok_and(|value| value.eq_ignore_ascii_case("true")) {
+        return None;
+    }
+    let exporter = match opentelemetry_otlp::SpanExporter::builder().build() {
+        Ok(exporter) => exporter,
+        Err(err) => {
+            eprintln!("opentelemetry exporter disabled: {err}");
+            return None;
+        }
+    };
+
+    let mut resource = Resource::builder();
+    if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+        resource = resource.with_service_name(SERVICE_NAME);
+    }
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource.build())
+        .build();
+    let tracer = provider.tracer(SERVICE_NAME);
+    let _ = PROVIDER.set(provider);
+
+    Some(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+// Flushes the spans still buffered by the batch exporter.
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get()
+        && let Err(err) = provider.shutdown()
+    {
+        warn!(error = %err, "opentelemetry shutdown failed");
+    }
+}
+
+// Makes the inbound `traceparent` the remote parent of `span`. Must run
+// before the span is first entered.
+pub fn continue_trace(span: &Span, headers: &HeaderMap) {
+    let Some(inbound) = headers
+        .get(TRACEPARENT)
+        .and_then(|value| value.to_str().ok())
+        .and_then(TraceParent::parse)
+    else {
+        return;
+    };
+
+    let (trace_id, parent_id) = inbound.ids();
+    let flags = if inbound.sampled() {
+        TraceFlags::SAMPLED
+    } else {
+        TraceFlags::default()
+    };
+    let remote = SpanContext::new(
+        TraceId::from_bytes(trace_id),
+        SpanId::from_bytes(parent_id),
+        flags,
+        true,
+        TraceState::NONE,
+    );
+    let _ = span.set_parent(Context::new().with_remote_span_context(remote));
+}
+
+// The exported span's ids, or `None` when no OpenTelemetry layer is installed.
+pub fn span_trace(span: &Span) -> Option<TraceParent> {
+    let context = span.context();
+    let span_context = context.span().span_context().clone();
+    if !span_context.is_valid() {
+        return None;
+    }
+    TraceParent::from_ids(
+        span_context.trace_id().to_bytes(),
+        span_context.span_id().to_bytes(),
+        span_context.is_sampled(),
+    )
+}
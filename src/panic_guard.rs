@@ -0,0 +1,88 @@
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+use actix_web::{
+    Error, ResponseError,
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    error::InternalError,
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    web::Data,
+};
+use futures_util::FutureExt;
+use tracing::error;
+
+use crate::app_state::AppState;
+use crate::errors::AppError;
+use crate::request_id::{RequestId, X_REQUEST_ID};
+
+thread_local! {
+    // Filled by the panic hook; a handler panics on the thread polling it,
+    // so `catch` finds the trace here once the unwind reaches it.
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+static HOOK: Once = Once::new();
+
+// Turns a panicking handler into a JSON 500 (code `internal_panic`) instead
+// of a dropped connection. Must run inside `request_id::assign` for the
+// body and the log line to carry the request id. The request was consumed
+// by the handler, so the response travels as an error that actix renders
+// as-is.
+pub async fn catch(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    install_hook();
+    let path = req.path().to_string();
+    let state = req.app_data::<Data<AppState>>().cloned();
+
+    match AssertUnwindSafe(next.call(req)).catch_unwind().await {
+        Ok(result) => result.map(ServiceResponse::map_into_boxed_body),
+        Err(payload) => {
+            let backtrace = BACKTRACE.with(|slot| slot.borrow_mut().take());
+            error!(
+                request_id = RequestId::current().as_ref().map(RequestId::as_str),
+                path,
+                panic = panic_message(payload.as_ref()),
+                backtrace = backtrace.map(|trace| trace.to_string()),
+                "request handler panicked"
+            );
+            if let Some(state) = state {
+                state.stats().record_panic();
+            }
+            let mut response = AppError::Panicked.error_response();
+            if let Some(id) = RequestId::current()
+                && let Ok(value) = HeaderValue::from_str(id.as_str())
+            {
+                response
+                    .headers_mut()
+                    .insert(HeaderName::from_static(X_REQUEST_ID), value);
+            }
+            Err(InternalError::from_response(AppError::Panicked, response).into())
+        }
+    }
+}
+
+// Chains onto the existing hook, so panics are still printed as before.
+fn install_hook() {
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            BACKTRACE.with(|slot| *slot.borrow_mut() = Some(Backtrace::force_capture()));
+            previous(info);
+        }));
+    });
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload")
+}
@@ -0,0 +1,1067 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use actix_web::{
+    HttpResponse,
+    dev::{Decompress, Payload, RequestHead},
+    http::{
+        StatusCode,
+        header::{self, ContentEncoding, HeaderMap, HeaderValue, HttpDate},
+    },
+    web::{Bytes, BytesMut},
+};
+use awc::ClientResponse;
+use awc::error::PayloadError;
+use futures_util::{Stream, StreamExt};
+use tracing::{Instrument, field, info, info_span, warn};
+use url::Url;
+
+use crate::{
+    app_state::AppState,
+    auth,
+    batch::{self, BatchIdCheck, BatchIdError},
+    body_log,
+    coalesce::{self, BufferedResponse, Role},
+    compression,
+    errors::{self, AppError, AttemptDetail, UpstreamErrorKind},
+    jsonrpc::{self, RpcMethod},
+    key_usage,
+    quorum::{self, QuorumVote},
+    request_id::{RequestId, X_REQUEST_ID},
+    signature::{X_API_KEY_ID, X_SIGNATURE, X_TIMESTAMP},
+    size_limits::DEFAULT_MAX_RESPONSE_BYTES,
+    stats::ValidatorStats,
+    status_policy,
+    trace_context::{TRACEPARENT, TraceParent},
+    validators::Validator,
+};
+
+type UpstreamStream = ClientResponse<Decompress<Payload>>;
+
+const X_PROXIED_BY: &str = "x-proxied-by";
+const X_ORIGINAL_USER_AGENT: &str = "x-original-user-agent";
+
+pub const X_NO_RETRY: &str = "x-no-retry";
+pub const X_RETRIES: &str = "x-retries";
+
+const PROXY_PARAMS: &[&str] = &[
+    "server",
+    "validator",
+    "location",
+    "region",
+    "retries",
+    "quorum",
+    "api_key",
+];
+
+// How the client proved itself to this proxy; no business of the validator's.
+const CLIENT_CREDENTIALS: &[&str] = &["authorization", X_API_KEY_ID, X_TIMESTAMP, X_SIGNATURE];
+
+const RELAYED_HEADERS: &[header::HeaderName] = &[
+    header::CONTENT_TYPE,
+    header::CONTENT_DISPOSITION,
+    header::ETAG,
+    header::LAST_MODIFIED,
+];
+
+pub(crate) struct ProxyRequest<'a> {
+    pub head: &'a RequestHead,
+    pub body: Bytes,
+    pub trace: TraceParent,
+    pub request_id: Option<RequestId>,
+    pub sub_path: Option<SubPath>,
+    pub stream_response: bool,
+    pub force_json_content_type: bool,
+    pub max_retries: usize,
+    pub accept_encoding: Option<ContentEncoding>,
+    pub rpc_method: Option<RpcMethod>,
+    // Digest of the caller's API key; see `key_usage::key_id`. Replaced by
+    // the key's id once `auth::require_key` accepted it.
+    pub key_id: Option<String>,
+    // Per-method cap; without one, buffered bodies fall back to the global
+    // default and streams are unbounded.
+    pub max_response_bytes: Option<usize>,
+    // The caller named its validator, so an exhausted outbound budget is
+    // waited for rather than spilled over.
+    pub pinned: bool,
+    attempts: RefCell<Vec<AttemptDetail>>,
+    last_upstream: RefCell<Option<UpstreamAttempt>>,
+}
+
+// The most recent call to a validator; `status` is `None` when it failed
+// before answering. `elapsed` runs until the response headers arrived.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UpstreamAttempt {
+    pub validator: String,
+    pub location: String,
+    pub status: Option<StatusCode>,
+    pub elapsed: Duration,
+}
+
+impl<'a> ProxyRequest<'a> {
+    pub fn new(head: &'a RequestHead, body: Bytes, trace: TraceParent, max_retries: usize) -> Self {
+        Self {
+            head,
+            body,
+            trace,
+            request_id: RequestId::current(),
+            sub_path: None,
+            stream_response: false,
+            force_json_content_type: false,
+            max_retries,
+            accept_encoding: compression::negotiate(head.headers()),
+            rpc_method: None,
+            // Read as auth read it, so a header auth refused counts for no key.
+            key_id: auth::presented_key(head)
+                .ok()
+                .flatten()
+                .map(|key| key_usage::key_id(&key)),
+            max_response_bytes: None,
+            pinned: false,
+            attempts: RefCell::new(Vec::new()),
+            last_upstream: RefCell::new(None),
+        }
+    }
+
+    fn size_limit_error(&self, limit: usize) -> AppError {
+        let method = self
+            .rpc_method
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_else(|| "request".to_string());
+        AppError::Upstream {
+            validator: self
+                .attempts
+                .borrow()
+                .last()
+                .map(|attempt| attempt.validator.clone()),
+            kind: UpstreamErrorKind::ResponseTooLarge,
+            message: format!("response for '{method}' exceeds the {limit} byte limit"),
+            trail: None,
+            excerpt: None,
+        }
+    }
+
+    // Validators tried so far, in order.
+    pub fn attempted(&self) -> Vec<String> {
+        self.attempts
+            .borrow()
+            .iter()
+            .map(|attempt| attempt.validator.clone())
+            .collect()
+    }
+
+    pub fn attempts(&self) -> Vec<AttemptDetail> {
+        self.attempts.borrow().clone()
+    }
+
+    pub fn last_upstream(&self) -> Option<UpstreamAttempt> {
+        self.last_upstream.borrow().clone()
+    }
+
+    fn target(&self, validator: &Validator) -> Url {
+        let mut url = validator.rpc_url().clone();
+        if let Some(sub_path) = &self.sub_path {
+            let base = url.path().trim_end_matches('/').to_string();
+            url.set_path(&format!("{base}{}", sub_path.path));
+            url.set_query(sub_path.query.as_deref());
+        }
+        url
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubPath {
+    pub path: String,
+    pub query: Option<String>,
+}
+
+impl SubPath {
+    pub fn parse(raw_path: &str, raw_query: &str) -> Result<Self, AppError> {
+        Ok(Self {
+            path: normalize_sub_path(raw_path)?,
+            query: strip_proxy_params(raw_query),
+        })
+    }
+}
+
+pub(crate) enum UpstreamBody {
+    Buffered(Bytes),
+    // Received bytes are counted against the validator as they are relayed.
+    Streaming(UpstreamStream, Arc<ValidatorStats>),
+}
+
+pub(crate) struct UpstreamResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: UpstreamBody,
+}
+
+impl UpstreamResponse {
+    // Serves a response produced for another caller, re-keyed to this request's id.
+    pub fn replay(shared: BufferedResponse, request_body: &[u8]) -> Self {
+        let body = match jsonrpc::request_id(request_body) {
+            Some(id) => jsonrpc::with_id(&shared.body, &id),
+            None => shared.body,
+        };
+        Self {
+            status: shared.status,
+            headers: shared.headers,
+            body: UpstreamBody::Buffered(body),
+        }
+    }
+
+    // An answer produced locally rather than by a validator.
+    pub fn json(value: &serde_json::Value) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        Self {
+            status: StatusCode::OK,
+            headers,
+            body: UpstreamBody::Buffered(Bytes::from(value.to_string())),
+        }
+    }
+
+    pub fn buffered_body(&self) -> Option<&Bytes> {
+        match &self.body {
+            UpstreamBody::Buffered(body) => Some(body),
+            UpstreamBody::Streaming(..) => None,
+        }
+    }
+
+    pub fn buffered(&self) -> Option<BufferedResponse> {
+        match &self.body {
+            UpstreamBody::Buffered(body) => Some(BufferedResponse {
+                status: self.status,
+                headers: self.headers.clone(),
+                body: body.clone(),
+            }),
+            UpstreamBody::Streaming(..) => None,
+        }
+    }
+
+    pub fn into_response(self, state: &AppState, request: &ProxyRequest<'_>) -> HttpResponse {
+        let settings = state.settings();
+        let mut builder = HttpResponse::build(self.status);
+        builder.insert_header((X_RETRIES, request.max_retries));
+
+        for name in RELAYED_HEADERS {
+            if let Some(value) = self.headers.get(name) {
+                builder.insert_header((name.clone(), value.clone()));
+            }
+        }
+
+        if settings.forward_retry_after
+            && let Some(retry_after) = self.headers.get(header::RETRY_AFTER)
+        {
+            builder.insert_header((header::RETRY_AFTER, retry_after.clone()));
+        }
+
+        match self.body {
+            // Buffered bodies were decoded by the client, so compressing them
+            // here can never double-encode.
+            UpstreamBody::Buffered(body) => {
+                body_log::response(state, request, &body);
+                if settings.compression_enabled
+                    && body.len() >= settings.compression_min_bytes
+                    && let Some(encoding) = request.accept_encoding
+                {
+                    match compression::compress(encoding, &body) {
+                        Ok(compressed) => {
+                            builder.insert_header((header::CONTENT_ENCODING, encoding));
+                            builder.insert_header((header::VARY, "accept-encoding"));
+                            return builder.body(compressed);
+                        }
+                        Err(err) => warn!(error = %err, "response compression failed"),
+                    }
+                }
+                builder.body(body)
+            }
+            // Streams are relayed undecoded with the upstream's own encoding.
+            UpstreamBody::Streaming(stream, stats) => {
+                if let Some(encoding) = self.headers.get(header::CONTENT_ENCODING) {
+                    builder.insert_header((header::CONTENT_ENCODING, encoding.clone()));
+                }
+                let stream = count_stream(stream, stats);
+                match request.max_response_bytes {
+                    Some(limit) => builder.streaming(limit_stream(stream, limit)),
+                    None => builder.streaming(stream),
+                }
+            }
+        }
+    }
+}
+
+pub(crate) async fn forward_coalesced(
+    state: &AppState,
+    request: &ProxyRequest<'_>,
+    candidates: Vec<Validator>,
+    key: String,
+) -> Result<UpstreamResponse, AppError> {
+    match state.coalescer().join(key) {
+        Role::Leader(guard) => {
+            let response = forward(state, request, candidates).await;
+            match &response {
+                Ok(upstream) => {
+                    if let Some(buffered) = upstream.buffered() {
+                        guard.complete(Ok(buffered));
+                    }
+                }
+                Err(err) => guard.complete(Err(err.clone())),
+            }
+            response
+        }
+        Role::Follower(receiver) => match coalesce::wait(receiver).await {
+            Some(Ok(shared)) => Ok(UpstreamResponse::replay(shared, &request.body)),
+            Some(Err(err)) => Err(err),
+            // The leader was cancelled before finishing; go upstream ourselves.
+            None => forward(state, request, candidates).await,
+        },
+    }
+}
+
+// `response` is only set when the validators agreed.
+pub(crate) struct QuorumOutcome {
+    pub response: Option<UpstreamResponse>,
+    pub votes: Vec<QuorumVote>,
+}
+
+// Sends the request to `size` validators at once, without retries, and only
+// answers with a result that all of them agree on.
+pub(crate) async fn forward_quorum(
+    state: &AppState,
+    request: &ProxyRequest<'_>,
+    candidates: Vec<Validator>,
+    size: usize,
+) -> Result<QuorumOutcome, AppError> {
+    let method = request.rpc_method.as_ref().and_then(RpcMethod::name);
+    let mut funded = Vec::with_capacity(size);
+    let mut throttled = None;
+    for validator in order_candidates(state, candidates, method) {
+        if funded.len() == size {
+            break;
+        }
+        match take_outbound(state, request, &validator).await {
+            Ok(()) => funded.push(validator),
+            Err(wait) => {
+                throttled = Some(throttled.map_or(wait, |soonest: Duration| soonest.min(wait)));
+            }
+        }
+    }
+    if funded.len() < size
+        && let Some(retry_after) = throttled
+    {
+        return Err(AppError::RateLimited {
+            message: "too few candidate validators are within their outbound rate limit"
+                .to_string(),
+            retry_after,
+        });
+    }
+    let validators = quorum::select_quorum(funded, size)?;
+
+    let replies = futures_util::future::join_all(validators.iter().enumerate().map(
+        |(attempt, validator)| async move {
+            let started = Instant::now();
+            let reply = match send(state, request, validator, attempt).await {
+                Ok(mut upstream_resp) => {
+                    let status = upstream_resp.status();
+                    let headers = upstream_resp.headers().clone();
+                    read_body(state, &mut upstream_resp, validator, request)
+                        .await
+                        .map(|body| (status, headers, body))
+                }
+                Err(err) => Err(err),
+            };
+            if let Some(method) = &request.rpc_method {
+                let success = reply
+                    .as_ref()
+                    .is_ok_and(|(status, _, _)| status.is_success());
+                state
+                    .stats()
+                    .record_call(method, validator.name(), started.elapsed(), success);
+            }
+            reply
+        },
+    ))
+    .await;
+
+    let votes: Vec<QuorumVote> = validators
+        .iter()
+        .zip(&replies)
+        .map(|(validator, reply)| match reply {
+            Ok((status, _, body)) if status.is_success() => QuorumVote {
+                validator: validator.name().to_string(),
+                digest: quorum::result_digest(body),
+                error: None,
+            },
+            Ok((status, _, _)) => QuorumVote {
+                validator: validator.name().to_string(),
+                digest: None,
+                error: Some(format!("upstream returned {status}")),
+            },
+            Err(err) => QuorumVote {
+                validator: validator.name().to_string(),
+                digest: None,
+                error: Some(err.to_string()),
+            },
+        })
+        .collect();
+
+    let Some(winner) = quorum::agreement(&votes, size) else {
+        warn!(
+            method = request.rpc_method.as_ref().map(field::display),
+            trace_id = %request.trace.trace_id(),
+            "quorum read disagreed"
+        );
+        return Ok(QuorumOutcome {
+            response: None,
+            votes,
+        });
+    };
+
+    let (status, headers, body) = replies
+        .into_iter()
+        .nth(winner)
+        .and_then(Result::ok)
+        .ok_or_else(|| AppError::Internal("quorum winner has no response".to_string()))?;
+    Ok(QuorumOutcome {
+        response: Some(UpstreamResponse {
+            status,
+            headers,
+            body: UpstreamBody::Buffered(body),
+        }),
+        votes,
+    })
+}
+
+pub(crate) async fn forward(
+    state: &AppState,
+    request: &ProxyRequest<'_>,
+    candidates: Vec<Validator>,
+) -> Result<UpstreamResponse, AppError> {
+    let started = Instant::now();
+    let result = match forward_with_failover(state, request, candidates).await {
+        Ok(upstream)
+            if state
+                .settings()
+                .upstream_error_policy
+                .wraps(upstream.status) =>
+        {
+            Err(wrap_status(request, upstream).await)
+        }
+        result => result,
+    };
+    result.map_err(|err| err.with_attempts(request.attempts(), started.elapsed()))
+}
+
+// Turns a non-2xx answer into our own error under the `wrap` policy. The
+// validator was already charged with the status when it answered.
+async fn wrap_status(request: &ProxyRequest<'_>, upstream: UpstreamResponse) -> AppError {
+    let body = match upstream.body {
+        UpstreamBody::Buffered(body) => body,
+        UpstreamBody::Streaming(stream, stats) => {
+            let mut stream = std::pin::pin!(count_stream(stream, stats));
+            let mut body = BytesMut::new();
+            while body.len() <= status_policy::EXCERPT_BYTES
+                && let Some(Ok(chunk)) = stream.next().await
+            {
+                body.extend_from_slice(&chunk);
+            }
+            body.freeze()
+        }
+    };
+    let validator = request.attempted().pop().unwrap_or_default();
+    AppError::Upstream {
+        message: format!("node '{validator}' returned {}", upstream.status),
+        validator: Some(validator),
+        kind: UpstreamErrorKind::BadStatus(upstream.status.as_u16()),
+        trail: None,
+        excerpt: Some(status_policy::excerpt(&body)),
+    }
+}
+
+async fn forward_with_failover(
+    state: &AppState,
+    request: &ProxyRequest<'_>,
+    candidates: Vec<Validator>,
+) -> Result<UpstreamResponse, AppError> {
+    let settings = state.settings();
+    if settings.shed_when_cooling_down
+        && let Some(retry_after) = cooldown_remaining(state, &candidates)
+    {
+        return Err(AppError::Overloaded {
+            message: "every candidate validator is cooling down".to_string(),
+            retry_after,
+        });
+    }
+    let max_attempts = request.max_retries + 1;
+    let mut attempts = 0;
+    let mut fallback = None;
+    let mut timed_out = None;
+    let mut throttled = None;
+    let method = match &request.rpc_method {
+        Some(RpcMethod::Single(method)) => Some(method.as_str()),
+        _ => None,
+    };
+
+    for validator in order_candidates(state, candidates, method) {
+        if attempts == max_attempts {
+            break;
+        }
+        // Spill over to the next candidate while this one is out of budget.
+        if let Err(wait) = take_outbound(state, request, &validator).await {
+            throttled = Some(throttled.map_or(wait, |soonest: Duration| soonest.min(wait)));
+            continue;
+        }
+        let attempt = attempts;
+        attempts += 1;
+        let started = Instant::now();
+        let sent = send(state, request, &validator, attempt).await;
+        if let Some(method) = &request.rpc_method {
+            let success = sent
+                .as_ref()
+                .is_ok_and(|response| response.status().is_success());
+            state
+                .stats()
+                .record_call(method, validator.name(), started.elapsed(), success);
+        }
+        let mut upstream_resp = match sent {
+            Ok(resp) => resp,
+            // Another validator may well answer a read in time.
+            Err(err) if err.is_timeout() && method.is_some_and(jsonrpc::is_idempotent) => {
+                timed_out = Some(err);
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        let status = upstream_resp.status();
+        let headers = upstream_resp.headers().clone();
+
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let cooldown = retry_after(&headers).unwrap_or(settings.default_429_cooldown);
+            warn!(
+                validator = validator.name(),
+                trace_id = %request.trace.trace_id(),
+                cooldown_ms = cooldown.as_millis() as u64,
+                "upstream rate limited request"
+            );
+
+            if settings.cooldown_on_429 {
+                state
+                    .stats()
+                    .validator(validator.name())
+                    .cool_down(cooldown);
+            }
+
+            if settings.retry_on_429 {
+                let body = read_body(state, &mut upstream_resp, &validator, request).await?;
+                fallback = Some(UpstreamResponse {
+                    status,
+                    headers,
+                    body: UpstreamBody::Buffered(body),
+                });
+                continue;
+            }
+        }
+
+        let body = if request.stream_response {
+            if let Some(limit) = request.max_response_bytes
+                && content_length(&headers).is_some_and(|length| length > limit)
+            {
+                return Err(request.size_limit_error(limit));
+            }
+            UpstreamBody::Streaming(upstream_resp, state.stats().validator(validator.name()))
+        } else {
+            let body = read_body(state, &mut upstream_resp, &validator, request).await?;
+            check_well_formed(state, request, &validator, status, &body)?;
+
+            // Light nodes disable some methods; another candidate may serve it.
+            if let Some(method) = method
+                && jsonrpc::is_method_unavailable(&body)
+            {
+                warn!(
+                    validator = validator.name(),
+                    method,
+                    trace_id = %request.trace.trace_id(),
+                    "upstream does not serve method"
+                );
+                state
+                    .stats()
+                    .validator(validator.name())
+                    .mark_unsupported(method);
+
+                if jsonrpc::is_idempotent(method) {
+                    fallback = Some(UpstreamResponse {
+                        status,
+                        headers,
+                        body: UpstreamBody::Buffered(body),
+                    });
+                    continue;
+                }
+            }
+
+            let body = verify_batch_ids(state, request, &validator, body)?;
+
+            if let Some(slot) = request
+                .rpc_method
+                .as_ref()
+                .and_then(|method| jsonrpc::observed_slot(method, &body))
+            {
+                state.stats().validator(validator.name()).observe_slot(slot);
+            }
+
+            UpstreamBody::Buffered(body)
+        };
+
+        return Ok(UpstreamResponse {
+            status,
+            headers,
+            body,
+        });
+    }
+
+    match (fallback, timed_out, throttled) {
+        (Some(fallback), _, _) => Ok(fallback),
+        (None, Some(err), _) => Err(err),
+        (None, None, Some(retry_after)) => Err(AppError::RateLimited {
+            message: "every candidate validator is at its outbound rate limit".to_string(),
+            retry_after,
+        }),
+        (None, None, None) => Err(AppError::Internal("no validator attempted".to_string())),
+    }
+}
+
+// Spends one request of the validator's outbound budget, or says when the
+// next is due. A pinned request waits up to `UPSTREAM_RATE_LIMIT_WAIT_MS`.
+async fn take_outbound(
+    state: &AppState,
+    request: &ProxyRequest<'_>,
+    validator: &Validator,
+) -> Result<(), Duration> {
+    let settings = state.settings();
+    let Some(limit) = validator.rate_limit().or(settings.upstream_rate_limit) else {
+        return Ok(());
+    };
+    let stats = state.stats().validator(validator.name());
+    let deadline = Instant::now() + settings.upstream_rate_limit_wait;
+    loop {
+        match stats.take_outbound(limit) {
+            Err(wait) if request.pinned && Instant::now() + wait <= deadline => {
+                tokio::time::sleep(wait).await;
+            }
+            taken => return taken,
+        }
+    }
+}
+
+// In strict mode a 2xx JSON-RPC answer must parse and carry `result` or
+// `error`; bodies above the size threshold are relayed unchecked.
+fn check_well_formed(
+    state: &AppState,
+    request: &ProxyRequest<'_>,
+    validator: &Validator,
+    status: StatusCode,
+    body: &[u8],
+) -> Result<(), AppError> {
+    let settings = state.settings();
+    if !settings.strict_responses
+        || request.rpc_method.is_none()
+        || !status.is_success()
+        || body.len() > settings.strict_response_max_bytes
+        || jsonrpc::is_well_formed(body)
+    {
+        return Ok(());
+    }
+
+    warn!(
+        validator = validator.name(),
+        trace_id = %request.trace.trace_id(),
+        bytes = body.len(),
+        "upstream returned a malformed json-rpc response"
+    );
+    let err = AppError::upstream(
+        validator.name(),
+        UpstreamErrorKind::InvalidResponse,
+        format!(
+            "node '{}' returned a malformed JSON-RPC response",
+            validator.name()
+        ),
+    );
+    state
+        .stats()
+        .validator(validator.name())
+        .reject_response(UpstreamErrorKind::InvalidResponse, err.to_string());
+    Err(err)
+}
+
+// Batch answers are put back in request order. Unless the check is strict,
+// answers whose ids do not match the request are only logged.
+fn verify_batch_ids(
+    state: &AppState,
+    request: &ProxyRequest<'_>,
+    validator: &Validator,
+    body: Bytes,
+) -> Result<Bytes, AppError> {
+    let check = state.settings().batch_id_check;
+    if check == BatchIdCheck::Off || !matches!(request.rpc_method, Some(RpcMethod::Batch(_))) {
+        return Ok(body);
+    }
+    let Some(ids) = batch::request_ids(&request.body) else {
+        return Ok(body);
+    };
+
+    match batch::reorder(&ids, &body) {
+        Ok(ordered) => Ok(ordered),
+        // Whole-batch errors come back as a single object.
+        Err(BatchIdError::NotABatch) => Ok(body),
+        Err(BatchIdError::Mismatch(mismatch)) => {
+            warn!(
+                validator = validator.name(),
+                trace_id = %request.trace.trace_id(),
+                %mismatch,
+                "upstream batch ids do not match the request"
+            );
+            if check == BatchIdCheck::Strict {
+                return Err(AppError::upstream(
+                    validator.name(),
+                    UpstreamErrorKind::InvalidResponse,
+                    format!(
+                        "node '{}' returned mismatched batch ids: {mismatch}",
+                        validator.name()
+                    ),
+                ));
+            }
+            Ok(body)
+        }
+    }
+}
+
+async fn send(
+    state: &AppState,
+    request: &ProxyRequest<'_>,
+    validator: &Validator,
+    attempt: usize,
+) -> Result<UpstreamStream, AppError> {
+    let head = request.head;
+    let trace = &request.trace;
+    let target = request.target(validator);
+    request.attempts.borrow_mut().push(AttemptDetail {
+        validator: validator.name().to_string(),
+        error: None,
+        status: None,
+        duration_ms: 0,
+    });
+
+    info!(
+        validator = validator.name(),
+        location = validator.location(),
+        path = target.path(),
+        method = request.rpc_method.as_ref().map(field::display),
+        attempt,
+        trace_id = %trace.trace_id(),
+        request_id = request.request_id.as_ref().map(field::display),
+        "forwarding json-rpc request"
+    );
+
+    let client = state.client_for(&target);
+
+    let mut forward_req = client.request_from(target.as_str(), head);
+    forward_req.headers_mut().remove(X_NO_RETRY);
+    for name in CLIENT_CREDENTIALS {
+        forward_req.headers_mut().remove(*name);
+    }
+    // Only ever the proxy's own, whatever the client sent under these names.
+    forward_req.headers_mut().remove(X_PROXIED_BY);
+    forward_req.headers_mut().remove(X_ORIGINAL_USER_AGENT);
+    if request.stream_response {
+        forward_req = forward_req.no_decompress();
+    }
+
+    let settings = state.settings();
+
+    // Without rewriting, the inbound Host is already copied over by `request_from`;
+    // HTTP/1.0 clients may not send one, so fall back to the URL-derived host.
+    let rewrite_host = validator.rewrite_host().unwrap_or(settings.rewrite_host);
+    if (rewrite_host || !head.headers().contains_key(header::HOST))
+        && let Some(host) = validator.host_header()
+    {
+        forward_req = forward_req.insert_header((header::HOST, host));
+    }
+
+    if settings.preserve_client_user_agent
+        && let Some(client_agent) = head.headers().get(header::USER_AGENT)
+    {
+        forward_req = forward_req.insert_header((X_ORIGINAL_USER_AGENT, client_agent.clone()));
+    }
+    forward_req =
+        forward_req.insert_header((header::USER_AGENT, settings.upstream_user_agent.clone()));
+    if let Some(proxied_by) = &settings.proxied_by {
+        forward_req = forward_req.insert_header((X_PROXIED_BY, proxied_by.clone()));
+    }
+    // One span per attempt; with the `otel` feature it is exported as a client
+    // span and the validator sees it as its parent. Integers are recorded as
+    // i64, which OpenTelemetry exports as numbers.
+    let span = info_span!(
+        "upstream",
+        otel.kind = "client",
+        validator = validator.name(),
+        host = target.host_str(),
+        attempt = attempt as i64,
+        status = field::Empty,
+        otel.status_code = field::Empty,
+    );
+    #[cfg(feature = "otel")]
+    let traceparent = crate::otel::span_trace(&span).unwrap_or(*trace);
+    #[cfg(not(feature = "otel"))]
+    let traceparent = *trace;
+    forward_req = forward_req.insert_header((TRACEPARENT, traceparent.to_string()));
+    if let Some(id) = &request.request_id {
+        forward_req = forward_req.insert_header((X_REQUEST_ID, id.as_str()));
+    }
+    if request.force_json_content_type {
+        forward_req = forward_req.insert_header((header::CONTENT_TYPE, "application/json"));
+    }
+
+    let validator_stats = state.stats().validator(validator.name());
+    let _in_flight = validator_stats.begin(request.body.len());
+    let started = Instant::now();
+    let sent = forward_req
+        .send_body(request.body.clone())
+        .instrument(span.clone())
+        .await;
+    match &sent {
+        Ok(resp) => span.record("status", i64::from(resp.status().as_u16())),
+        Err(_) => span.record("otel.status_code", "error"),
+    };
+    #[cfg(feature = "statsd")]
+    if let (Ok(_), Some(statsd)) = (&sent, state.statsd()) {
+        statsd.timing("upstream.latency", started.elapsed(), validator.name());
+    }
+    match &sent {
+        Ok(resp) => validator_stats.record_response(resp.status(), started.elapsed()),
+        Err(err) => validator_stats.record_no_response(errors::send_failure(err), err.to_string()),
+    }
+    if let Some(attempt) = request.attempts.borrow_mut().last_mut() {
+        attempt.duration_ms = started.elapsed().as_millis() as u64;
+        match &sent {
+            Ok(resp) => {
+                attempt.status = Some(resp.status().as_u16());
+                attempt.error =
+                    UpstreamErrorKind::from_status(resp.status()).map(|kind| kind.failure());
+            }
+            Err(err) => attempt.error = Some(errors::send_failure(err).failure()),
+        }
+    }
+    *request.last_upstream.borrow_mut() = Some(UpstreamAttempt {
+        validator: validator.name().to_string(),
+        location: validator.location().to_string(),
+        status: sent.as_ref().ok().map(|resp| resp.status()),
+        elapsed: started.elapsed(),
+    });
+
+    match sent {
+        Ok(resp) => Ok(resp),
+        Err(e) => Err(AppError::upstream(
+            validator.name(),
+            errors::send_failure(&e),
+            format!("node '{}' is unavailable: {}", validator.name(), e),
+        )),
+    }
+}
+
+async fn read_body(
+    state: &AppState,
+    upstream_resp: &mut UpstreamStream,
+    validator: &Validator,
+    request: &ProxyRequest<'_>,
+) -> Result<Bytes, AppError> {
+    let limit = request
+        .max_response_bytes
+        .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES);
+    let stats = state.stats().validator(validator.name());
+    let (kind, err) = match upstream_resp.body().limit(limit).await {
+        Ok(p) => {
+            stats.record_received(p.len());
+            return Ok(p);
+        }
+        Err(PayloadError::Overflow) => (
+            UpstreamErrorKind::ResponseTooLarge,
+            request.size_limit_error(limit),
+        ),
+        Err(e) => {
+            let kind = errors::payload_failure(&e);
+            let err = AppError::upstream(
+                validator.name(),
+                kind,
+                format!("node '{}' is unavailable: {}", validator.name(), e),
+            );
+            (kind, err)
+        }
+    };
+    // Non-2xx answers were already counted as failures.
+    if upstream_resp.status().is_success() {
+        stats.reject_response(kind, err.to_string());
+    }
+    Err(err)
+}
+
+fn content_length(headers: &HeaderMap) -> Option<usize> {
+    headers
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+fn count_stream(
+    stream: UpstreamStream,
+    stats: Arc<ValidatorStats>,
+) -> impl Stream<Item = Result<Bytes, PayloadError>> {
+    stream.inspect(move |chunk| {
+        if let Ok(chunk) = chunk {
+            stats.record_received(chunk.len());
+        }
+    })
+}
+
+// Aborts the relayed stream once it grows past `limit`; the status line has
+// already been sent by then, so the client sees a truncated body.
+fn limit_stream(
+    stream: impl Stream<Item = Result<Bytes, PayloadError>>,
+    limit: usize,
+) -> impl Stream<Item = Result<Bytes, PayloadError>> {
+    let mut seen = 0usize;
+    stream.map(move |chunk| {
+        let chunk = chunk?;
+        seen += chunk.len();
+        if seen > limit {
+            return Err(PayloadError::Overflow);
+        }
+        Ok(chunk)
+    })
+}
+
+// Validators known not to serve the method go last, then those cooling down
+// after a 429; the shuffled order is kept otherwise.
+fn order_candidates(
+    state: &AppState,
+    candidates: Vec<Validator>,
+    method: Option<&str>,
+) -> Vec<Validator> {
+    let mut candidates = candidates;
+    candidates.sort_by_cached_key(|validator| {
+        let stats = state.stats().validator(validator.name());
+        let unsupported = method.is_some_and(|method| !stats.supports(method));
+        (unsupported, stats.is_cooling_down())
+    });
+    candidates
+}
+
+// Until the first candidate comes out of its cooldown, if all are in one.
+fn cooldown_remaining(state: &AppState, candidates: &[Validator]) -> Option<Duration> {
+    candidates
+        .iter()
+        .map(|validator| {
+            state
+                .stats()
+                .validator(validator.name())
+                .cooldown_remaining()
+        })
+        .try_fold(None, |soonest: Option<Duration>, remaining| {
+            let remaining = remaining?;
+            Some(Some(
+                soonest.map_or(remaining, |soonest| soonest.min(remaining)),
+            ))
+        })
+        .flatten()
+}
+
+// Clients can only lower the retry budget: the server limit, the query value
+// and the `X-No-Retry` header are combined by taking the most conservative.
+pub fn retry_budget(
+    server_max: usize,
+    no_retry_header: Option<&HeaderValue>,
+    query_retries: Option<usize>,
+) -> usize {
+    let no_retry = no_retry_header
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            matches!(
+                value.trim().to_ascii_lowercase().as_str(),
+                "true" | "1" | "yes"
+            )
+        })
+        .unwrap_or(false);
+
+    if no_retry {
+        return 0;
+    }
+
+    query_retries.map_or(server_max, |requested| requested.min(server_max))
+}
+
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(header::RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date: HttpDate = value.parse().ok()?;
+    SystemTime::from(date)
+        .duration_since(SystemTime::now())
+        .ok()
+}
+
+// Works on the raw, still percent-encoded path so encoded separators can't
+// sneak past segment checks and be decoded by the upstream.
+pub fn normalize_sub_path(raw: &str) -> Result<String, AppError> {
+    let mut segments = Vec::new();
+
+    for segment in raw.split('/').filter(|segment| !segment.is_empty()) {
+        let lowered = segment.to_ascii_lowercase();
+        if lowered.contains("%2f") || lowered.contains("%5c") || lowered.contains('\\') {
+            return Err(AppError::BadRequest(
+                "encoded path separators are not allowed".to_string(),
+            ));
+        }
+
+        let decoded_dots = lowered.replace("%2e", ".");
+        if decoded_dots == "." || decoded_dots == ".." {
+            return Err(AppError::BadRequest(
+                "relative path segments are not allowed".to_string(),
+            ));
+        }
+
+        segments.push(segment);
+    }
+
+    let mut path = format!("/{}", segments.join("/"));
+    if raw.ends_with('/') && !segments.is_empty() {
+        path.push('/');
+    }
+
+    Ok(path)
+}
+
+fn strip_proxy_params(raw_query: &str) -> Option<String> {
+    let kept: Vec<(String, String)> = url::form_urlencoded::parse(raw_query.as_bytes())
+        .filter(|(key, _)| !PROXY_PARAMS.contains(&key.as_ref()))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+
+    if kept.is_empty() {
+        return None;
+    }
+
+    Some(
+        url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(kept)
+            .finish(),
+    )
+}
@@ -0,0 +1,58 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::jsonrpc;
+use crate::validators::{SelectionError, Validator};
+use serde::Serialize;
+
+pub const X_QUORUM_VALIDATORS: &str = "x-quorum-validators";
+
+// Takes the first `size` candidates; they arrive shuffled and ordered by
+// preference, and are distinct by construction.
+pub fn select_quorum(
+    candidates: Vec<Validator>,
+    size: usize,
+) -> Result<Vec<Validator>, SelectionError> {
+    if candidates.len() < size {
+        return Err(SelectionError::NotEnoughValidators {
+            required: size,
+            available: candidates.len(),
+        });
+    }
+
+    let mut candidates = candidates;
+    candidates.truncate(size);
+    Ok(candidates)
+}
+
+// Digest of the JSON-RPC `result` with `context.slot` removed, since nodes
+// agreeing on the data may still answer from slightly different slots.
+// Object keys serialize sorted, so equal results hash equally.
+pub fn result_digest(body: &[u8]) -> Option<String> {
+    let result = jsonrpc::normalized_result(body, &["context.slot"])?;
+
+    let mut hasher = DefaultHasher::new();
+    result.to_string().hash(&mut hasher);
+    Some(format!("{:016x}", hasher.finish()))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QuorumVote {
+    pub validator: String,
+    pub digest: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Index of a vote whose digest is shared by at least `required` votes.
+pub fn agreement(votes: &[QuorumVote], required: usize) -> Option<usize> {
+    votes.iter().position(|vote| {
+        vote.digest.as_ref().is_some_and(|digest| {
+            votes
+                .iter()
+                .filter(|other| other.digest.as_ref() == Some(digest))
+                .count()
+                >= required
+        })
+    })
+}
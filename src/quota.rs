@@ -0,0 +1,191 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Serialize;
+
+const DAY_SECS: u64 = 86_400;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaPeriod {
+    #[default]
+    Day,
+    Month,
+}
+
+impl QuotaPeriod {
+    // First and one-past-last UTC day of the period holding `day`.
+    fn bounds(self, day: u64) -> (u64, u64) {
+        match self {
+            Self::Day => (day, day + 1),
+            Self::Month => {
+                let (year, month, _) = civil_from_days(day);
+                let next = if month == 12 {
+                    days_from_civil(year + 1, 1, 1)
+                } else {
+                    days_from_civil(year, month + 1, 1)
+                };
+                (days_from_civil(year, month, 1), next)
+            }
+        }
+    }
+}
+
+// A hard cap on the requests a key may make per UTC day or month.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestQuota {
+    pub limit: u64,
+    pub period: QuotaPeriod,
+}
+
+// `10000`, `10000/day` or `300000/month`.
+impl FromStr for RequestQuota {
+    type Err = ();
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let (limit, period) = match raw.trim().split_once('/') {
+            None => (raw, QuotaPeriod::Day),
+            Some((limit, period)) => match period.trim().to_ascii_lowercase().as_str() {
+                "day" => (limit, QuotaPeriod::Day),
+                "month" => (limit, QuotaPeriod::Month),
+                _ => return Err(()),
+            },
+        };
+        match limit.trim().parse() {
+            Ok(limit) if limit > 0 => Ok(Self { limit, period }),
+            _ => Err(()),
+        }
+    }
+}
+
+// Where a key stands in its current period. Keys without a quota are
+// reported per day, with no limit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QuotaUsage {
+    pub period: QuotaPeriod,
+    pub limit: Option<u64>,
+    pub consumed: u64,
+    pub remaining: Option<u64>,
+    pub resets_at_unix_ms: u64,
+}
+
+impl QuotaUsage {
+    pub fn retry_after(&self, now: u64) -> Duration {
+        Duration::from_millis(self.resets_at_unix_ms.saturating_sub(now * 1000))
+    }
+}
+
+// Requests per key id and UTC day, kept back to the start of the current
+// month so both periods can be summed. Time never runs backwards here: a
+// clock stepped back is held at the latest time seen, so it cannot reopen
+// a period that was already used up.
+#[derive(Debug, Default)]
+pub struct QuotaTable {
+    keys: HashMap<String, BTreeMap<u64, u64>>,
+    latest: u64,
+}
+
+impl QuotaTable {
+    fn clock(&mut self, now: u64) -> u64 {
+        self.latest = self.latest.max(now);
+        self.latest
+    }
+
+    // Counts one request unless the quota is used up; the usage is the one
+    // after this request, or the exhausted one when it was refused.
+    pub fn consume(
+        &mut self,
+        key_id: &str,
+        quota: Option<RequestQuota>,
+        now: u64,
+    ) -> Result<QuotaUsage, QuotaUsage> {
+        let now = self.clock(now);
+        let today = now / DAY_SECS;
+        let days = self.keys.entry(key_id.to_string()).or_default();
+        let (month_start, _) = QuotaPeriod::Month.bounds(today);
+        days.retain(|day, _| *day >= month_start);
+
+        let usage = period_usage(days, quota, today);
+        if usage.remaining == Some(0) {
+            return Err(usage);
+        }
+        *days.entry(today).or_default() += 1;
+        Ok(self.usage(key_id, quota, now))
+    }
+
+    pub fn usage(&mut self, key_id: &str, quota: Option<RequestQuota>, now: u64) -> QuotaUsage {
+        let today = self.clock(now) / DAY_SECS;
+        match self.keys.get(key_id) {
+            Some(days) => period_usage(days, quota, today),
+            None => period_usage(&BTreeMap::new(), quota, today),
+        }
+    }
+
+    pub fn is_tracked(&self, key_id: &str) -> bool {
+        self.keys.contains_key(key_id)
+    }
+
+    // Per key, per UTC day since the epoch.
+    pub fn days(&self) -> BTreeMap<String, BTreeMap<u64, u64>> {
+        self.keys
+            .iter()
+            .map(|(id, days)| (id.clone(), days.clone()))
+            .collect()
+    }
+
+    // Replaces the counts of the keys in `saved`. A saved day after the
+    // local clock moves the clock forward, as if no time had been lost.
+    pub fn restore(&mut self, saved: &BTreeMap<String, BTreeMap<u64, u64>>) {
+        for (id, days) in saved {
+            if let Some(last) = days.keys().next_back() {
+                self.latest = self.latest.max(last * DAY_SECS);
+            }
+            self.keys.insert(id.clone(), days.clone());
+        }
+    }
+}
+
+fn period_usage(days: &BTreeMap<u64, u64>, quota: Option<RequestQuota>, today: u64) -> QuotaUsage {
+    let period = quota.map(|quota| quota.period).unwrap_or_default();
+    let (start, end) = period.bounds(today);
+    let consumed = days.range(start..end).map(|(_, count)| count).sum();
+    let limit = quota.map(|quota| quota.limit);
+    QuotaUsage {
+        period,
+        limit,
+        consumed,
+        remaining: limit.map(|limit| limit.saturating_sub(consumed)),
+        resets_at_unix_ms: end * DAY_SECS * 1000,
+    }
+}
+
+// Days since 1970-01-01 and back, in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    (era * 146_097 + day_of_era - 719_468).max(0) as u64
+}
+
+fn civil_from_days(days: u64) -> (i64, u32, u32) {
+    let days = days as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
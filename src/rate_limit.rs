@@ -0,0 +1,461 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::{
+    Error, HttpMessage,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    web::Data,
+};
+use serde::Deserialize;
+
+use crate::app_state::AppState;
+use crate::jsonrpc::RpcMethod;
+use crate::routing::best_pattern_match;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MethodRateLimit {
+    pub method: String,
+    pub per_second: f64,
+    #[serde(default)]
+    pub burst: Option<u32>,
+}
+
+impl MethodRateLimit {
+    fn capacity(&self) -> f64 {
+        self.burst
+            .map(f64::from)
+            .unwrap_or_else(|| self.per_second.ceil().max(1.0))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MethodRateLimits {
+    limits: Vec<MethodRateLimit>,
+}
+
+impl MethodRateLimits {
+    pub fn new(limits: Vec<MethodRateLimit>) -> Result<Self, String> {
+        if let Some(limit) = limits
+            .iter()
+            .find(|limit| !(limit.per_second > 0.0 && limit.per_second.is_finite()))
+        {
+            return Err(format!(
+                "rate limit for '{}' must be a positive number of requests per second",
+                limit.method
+            ));
+        }
+        if let Some(limit) = limits.iter().find(|limit| limit.burst == Some(0)) {
+            return Err(format!("burst for '{}' must be at least 1", limit.method));
+        }
+
+        Ok(Self { limits })
+    }
+
+    pub fn from_json(raw: &str) -> Result<Self, String> {
+        let limits = serde_json::from_str(raw).map_err(|err| err.to_string())?;
+        Self::new(limits)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.limits.is_empty()
+    }
+
+    pub fn limits(&self) -> &[MethodRateLimit] {
+        &self.limits
+    }
+
+    pub fn best_match(&self, method: &str) -> Option<usize> {
+        best_pattern_match(&self.limits, |limit| &limit.method, method)
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+struct ClientBuckets {
+    last_seen: u64,
+    buckets: HashMap<usize, Bucket>,
+}
+
+struct LimiterState {
+    tick: u64,
+    clients: HashMap<String, ClientBuckets>,
+}
+
+// Token buckets per (client, limit), with the least recently seen clients
+// evicted once `max_clients` is reached.
+pub struct MethodRateLimiter {
+    limits: MethodRateLimits,
+    max_clients: usize,
+    state: Mutex<LimiterState>,
+}
+
+impl MethodRateLimiter {
+    pub fn new(limits: MethodRateLimits, max_clients: usize) -> Self {
+        Self {
+            limits,
+            max_clients: max_clients.max(1),
+            state: Mutex::new(LimiterState {
+                tick: 0,
+                clients: HashMap::new(),
+            }),
+        }
+    }
+
+    // Every call in a batch counts against its own method's bucket. Tokens
+    // are only taken when all buckets involved can pay, so a rejected batch
+    // costs nothing. Reports the tightest bucket involved, or the one with
+    // the longest wait when refused; `None` when no limit applies.
+    pub fn check(&self, client: &str, method: &RpcMethod) -> Result<Option<KeyQuota>, KeyQuota> {
+        if self.limits.is_empty() {
+            return Ok(None);
+        }
+
+        let names: Vec<&str> = match method {
+            RpcMethod::Single(name) => vec![name],
+            RpcMethod::Batch(names) => names.iter().flatten().map(String::as_str).collect(),
+            RpcMethod::Invalid => Vec::new(),
+        };
+
+        let mut costs: HashMap<usize, f64> = HashMap::new();
+        for name in names {
+            if let Some(idx) = self.limits.best_match(name) {
+                *costs.entry(idx).or_default() += 1.0;
+            }
+        }
+        if costs.is_empty() {
+            return Ok(None);
+        }
+
+        let now = Instant::now();
+        let mut state = self.state.lock().expect("rate limiter lock poisoned");
+        state.tick += 1;
+        let tick = state.tick;
+
+        if !state.clients.contains_key(client) && state.clients.len() >= self.max_clients {
+            let oldest = state
+                .clients
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_seen)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                state.clients.remove(&oldest);
+            }
+        }
+
+        let entry = state
+            .clients
+            .entry(client.to_string())
+            .or_insert_with(|| ClientBuckets {
+                last_seen: tick,
+                buckets: HashMap::new(),
+            });
+        entry.last_seen = tick;
+
+        let mut refused: Option<KeyQuota> = None;
+        for (idx, cost) in &costs {
+            let limit = &self.limits.limits[*idx];
+            let bucket = entry.buckets.entry(*idx).or_insert_with(|| Bucket {
+                tokens: limit.capacity(),
+                refilled_at: now,
+            });
+
+            let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * limit.per_second).min(limit.capacity());
+            bucket.refilled_at = now;
+
+            if bucket.tokens < *cost {
+                let missing = cost - bucket.tokens;
+                let wait = Duration::from_secs_f64(missing / limit.per_second);
+                if refused.is_none_or(|refused| refused.retry_after < Some(wait)) {
+                    refused = Some(KeyQuota::of(
+                        limit.capacity(),
+                        limit.per_second,
+                        bucket.tokens,
+                        Some(wait),
+                    ));
+                }
+            }
+        }
+
+        if let Some(refused) = refused {
+            return Err(refused);
+        }
+
+        let mut tightest: Option<KeyQuota> = None;
+        for (idx, cost) in costs {
+            let limit = &self.limits.limits[idx];
+            if let Some(bucket) = entry.buckets.get_mut(&idx) {
+                bucket.tokens -= cost;
+                let quota = KeyQuota::of(limit.capacity(), limit.per_second, bucket.tokens, None);
+                if tightest.is_none_or(|tightest| quota.is_tighter_than(&tightest)) {
+                    tightest = Some(quota);
+                }
+            }
+        }
+        Ok(tightest)
+    }
+
+    pub fn tracked_clients(&self) -> usize {
+        self.state
+            .lock()
+            .expect("rate limiter lock poisoned")
+            .clients
+            .len()
+    }
+}
+
+// A key's budget: `requests_per_second` sustained, up to `burst` at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyRateLimit {
+    pub requests_per_second: f64,
+    pub burst: u32,
+}
+
+impl KeyRateLimit {
+    // `None` unless the rate is positive and the burst at least 1; a missing
+    // burst allows one second's worth.
+    pub fn new(requests_per_second: f64, burst: Option<u32>) -> Option<Self> {
+        if !(requests_per_second > 0.0 && requests_per_second.is_finite()) {
+            return None;
+        }
+        let burst = burst.unwrap_or_else(|| requests_per_second.ceil() as u32);
+        (burst > 0).then_some(Self {
+            requests_per_second,
+            burst,
+        })
+    }
+}
+
+// What the rate limit headers report after a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyQuota {
+    pub limit: u32,
+    pub remaining: u32,
+    // Until the bucket is full again.
+    pub reset: Duration,
+    // Set when the request was refused.
+    pub retry_after: Option<Duration>,
+}
+
+impl KeyQuota {
+    fn of(capacity: f64, per_second: f64, tokens: f64, retry_after: Option<Duration>) -> Self {
+        Self {
+            limit: capacity as u32,
+            remaining: tokens.max(0.0).floor() as u32,
+            reset: Duration::from_secs_f64((capacity - tokens).max(0.0) / per_second),
+            retry_after,
+        }
+    }
+
+    // A refusal first, then fewer requests left, then a longer wait for them.
+    pub fn is_tighter_than(&self, other: &Self) -> bool {
+        let rank = |quota: &Self| {
+            (
+                quota.retry_after.is_none(),
+                quota.remaining,
+                Reverse(quota.reset),
+            )
+        };
+        rank(self) < rank(other)
+    }
+}
+
+struct KeyBucket {
+    bucket: Bucket,
+    limit: KeyRateLimit,
+    last_seen: u64,
+}
+
+impl KeyBucket {
+    fn is_full_at(&self, now: Instant) -> bool {
+        let elapsed = now
+            .saturating_duration_since(self.bucket.refilled_at)
+            .as_secs_f64();
+        self.bucket.tokens + elapsed * self.limit.requests_per_second >= f64::from(self.limit.burst)
+    }
+}
+
+struct KeyLimiterState {
+    tick: u64,
+    swept_at: Instant,
+    keys: HashMap<String, KeyBucket>,
+}
+
+// One token bucket per key: an API key id, or a client address for
+// `client_limit`. Buckets idle long enough to have refilled
+// are dropped, since a fresh one is the same; past `max_keys` the least
+// recently seen key goes too.
+pub struct KeyRateLimiter {
+    max_keys: usize,
+    state: Mutex<KeyLimiterState>,
+}
+
+impl KeyRateLimiter {
+    const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+    pub fn new(max_keys: usize) -> Self {
+        Self {
+            max_keys: max_keys.max(1),
+            state: Mutex::new(KeyLimiterState {
+                tick: 0,
+                swept_at: Instant::now(),
+                keys: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn check(&self, key_id: &str, limit: KeyRateLimit) -> KeyQuota {
+        self.check_at(key_id, limit, Instant::now())
+    }
+
+    pub fn check_at(&self, key_id: &str, limit: KeyRateLimit, now: Instant) -> KeyQuota {
+        let capacity = f64::from(limit.burst);
+        let mut state = self.state.lock().expect("rate limiter lock poisoned");
+        state.tick += 1;
+        let tick = state.tick;
+
+        if now.duration_since(state.swept_at) >= Self::SWEEP_INTERVAL {
+            state.swept_at = now;
+            state.keys.retain(|_, entry| !entry.is_full_at(now));
+        }
+        if !state.keys.contains_key(key_id) && state.keys.len() >= self.max_keys {
+            let oldest = state
+                .keys
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_seen)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                state.keys.remove(&oldest);
+            }
+        }
+
+        let entry = state
+            .keys
+            .entry(key_id.to_string())
+            .or_insert_with(|| KeyBucket {
+                bucket: Bucket {
+                    tokens: capacity,
+                    refilled_at: now,
+                },
+                limit,
+                last_seen: tick,
+            });
+        entry.last_seen = tick;
+        entry.limit = limit;
+        let bucket = &mut entry.bucket;
+        let elapsed = now
+            .saturating_duration_since(bucket.refilled_at)
+            .as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.requests_per_second).min(capacity);
+        bucket.refilled_at = now;
+
+        let retry_after = if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / limit.requests_per_second,
+            ))
+        };
+        KeyQuota::of(
+            capacity,
+            limit.requests_per_second,
+            bucket.tokens,
+            retry_after,
+        )
+    }
+
+    pub fn tracked_keys(&self) -> usize {
+        self.state
+            .lock()
+            .expect("rate limiter lock poisoned")
+            .keys
+            .len()
+    }
+}
+
+// How `headers` reports the tightest bucket, set by `RATE_LIMIT_HEADERS`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RateLimitHeaders {
+    // `X-RateLimit-Limit`, `-Remaining` and `-Reset`.
+    #[default]
+    Legacy,
+    // `RateLimit-Limit`, `-Remaining` and `-Reset`, as in the IETF draft.
+    Draft,
+    Off,
+}
+
+impl FromStr for RateLimitHeaders {
+    type Err = ();
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.to_ascii_lowercase().as_str() {
+            "legacy" | "x-ratelimit" => Ok(Self::Legacy),
+            "draft" | "ratelimit" => Ok(Self::Draft),
+            "off" | "none" => Ok(Self::Off),
+            _ => Err(()),
+        }
+    }
+}
+
+// The tightest bucket the limiters a request went through reported.
+#[derive(Clone, Copy)]
+struct Tightest(KeyQuota);
+
+// Called by every limiter after its check, refusals included.
+pub fn observe(req: &impl HttpMessage, quota: KeyQuota) {
+    let mut extensions = req.extensions_mut();
+    match extensions.get_mut::<Tightest>() {
+        Some(Tightest(tightest)) if !quota.is_tighter_than(tightest) => {}
+        Some(Tightest(tightest)) => *tightest = quota,
+        None => {
+            extensions.insert(Tightest(quota));
+        }
+    }
+}
+
+// Wrapped outside every limiter of a resource, so answers from any of them,
+// and from the handler, carry the tightest bucket observed.
+pub async fn headers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let style = req
+        .app_data::<Data<AppState>>()
+        .map(|state| state.settings().rate_limit_headers)
+        .unwrap_or_default();
+    let mut response = next.call(req).await?;
+    let tightest = response.request().extensions().get::<Tightest>().copied();
+    if let Some(Tightest(quota)) = tightest {
+        insert_quota_headers(response.headers_mut(), style, &quota);
+    }
+    Ok(response)
+}
+
+// `Limit` (the burst), `Remaining` and `Reset` (whole seconds until the
+// bucket is full), under the names `style` picks.
+pub fn insert_quota_headers(headers: &mut HeaderMap, style: RateLimitHeaders, quota: &KeyQuota) {
+    let names = match style {
+        RateLimitHeaders::Legacy => [
+            "x-ratelimit-limit",
+            "x-ratelimit-remaining",
+            "x-ratelimit-reset",
+        ],
+        RateLimitHeaders::Draft => ["ratelimit-limit", "ratelimit-remaining", "ratelimit-reset"],
+        RateLimitHeaders::Off => return,
+    };
+    let reset = quota.reset.as_secs_f64().ceil() as u64;
+    let values = [u64::from(quota.limit), u64::from(quota.remaining), reset];
+    for (name, value) in names.into_iter().zip(values) {
+        headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::validators::{RegistryError, ValidatorRegistry, normalize_key};
+
+// The outcome of `the-solana-api validate`: every row that parsed, plus what
+// is wrong or suspicious about the file.
+#[derive(Debug, Serialize)]
+pub struct RegistryReport {
+    pub valid: bool,
+    pub validators: Vec<CheckedValidator>,
+    pub warnings: Vec<Finding>,
+    pub errors: Vec<Finding>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckedValidator {
+    pub row: usize,
+    pub name: String,
+    pub location: String,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Finding {
+    // The CSV line, header included, when the problem has one.
+    pub row: Option<usize>,
+    pub message: String,
+}
+
+impl Finding {
+    fn at(row: usize, message: String) -> Self {
+        Self {
+            row: Some(row),
+            message,
+        }
+    }
+}
+
+impl From<RegistryError> for Finding {
+    fn from(err: RegistryError) -> Self {
+        let message = match &err {
+            RegistryError::InvalidRecord { field, message, .. } => format!("{field}: {message}"),
+            _ => err.to_string(),
+        };
+        Self {
+            row: err.row(),
+            message,
+        }
+    }
+}
+
+impl RegistryReport {
+    pub fn from_file(path: &Path) -> Self {
+        match File::open(path) {
+            Ok(file) => Self::from_reader(file),
+            Err(err) => {
+                Self::from_findings(Vec::new(), Vec::new(), vec![RegistryError::Io(err).into()])
+            }
+        }
+    }
+
+    // Runs the rows through the same parser the server uses, then applies
+    // the registry-wide rules to what parsed.
+    pub fn from_reader<R: Read>(reader: R) -> Self {
+        let inspection = ValidatorRegistry::inspect(reader);
+        let mut warnings = Vec::new();
+        let mut errors: Vec<Finding> = inspection.errors.into_iter().map(Finding::from).collect();
+
+        let mut names = HashMap::new();
+        let mut urls = HashMap::new();
+        let mut validators = Vec::new();
+        for inspected in &inspection.rows {
+            let (row, validator) = (inspected.row, &inspected.validator);
+            if let Some(first) = names.insert(normalize_key(validator.name()), row) {
+                errors.push(Finding::at(
+                    row,
+                    format!(
+                        "name: duplicate validator name '{}' (first on row {first})",
+                        validator.name()
+                    ),
+                ));
+            }
+            if let Some(first) = urls.insert(validator.rpc_url().as_str(), row) {
+                warnings.push(Finding::at(
+                    row,
+                    format!(
+                        "duplicate url {} (first on row {first})",
+                        validator.rpc_url()
+                    ),
+                ));
+            }
+            if inspected.missing_location {
+                warnings.push(Finding::at(
+                    row,
+                    format!("no location; using '{}'", validator.location()),
+                ));
+            }
+            if inspected.generated_name {
+                warnings.push(Finding::at(
+                    row,
+                    format!("no name; generated '{}'", validator.name()),
+                ));
+            }
+            validators.push(CheckedValidator {
+                row,
+                name: validator.name().to_string(),
+                location: validator.location().to_string(),
+                url: validator.rpc_url().to_string(),
+            });
+        }
+        if inspection.rows.is_empty() && errors.is_empty() {
+            errors.push(RegistryError::Empty.into());
+        }
+        errors.sort_by_key(|finding| finding.row);
+        warnings.sort_by_key(|finding| finding.row);
+        Self::from_findings(validators, warnings, errors)
+    }
+
+    fn from_findings(
+        validators: Vec<CheckedValidator>,
+        warnings: Vec<Finding>,
+        errors: Vec<Finding>,
+    ) -> Self {
+        Self {
+            valid: errors.is_empty(),
+            validators,
+            warnings,
+            errors,
+        }
+    }
+
+    // A table of the validators, then one line per warning and error.
+    pub fn render(&self) -> String {
+        let headers = ["ROW", "NAME", "LOCATION", "URL"];
+        let rows: Vec<[String; 4]> = self
+            .validators
+            .iter()
+            .map(|v| {
+                [
+                    v.row.to_string(),
+                    v.name.clone(),
+                    v.location.clone(),
+                    v.url.clone(),
+                ]
+            })
+            .collect();
+        let widths: Vec<usize> = (0..headers.len())
+            .map(|col| {
+                rows.iter()
+                    .map(|row| row[col].len())
+                    .chain([headers[col].len()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let mut out = String::new();
+        let mut line = |cells: [&str; 4]| {
+            let cells: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect();
+            let _ = writeln!(out, "{}", cells.join("  ").trim_end());
+        };
+        line(headers);
+        for row in &rows {
+            line(row.each_ref().map(String::as_str));
+        }
+
+        for (label, findings) in [("warning", &self.warnings), ("error", &self.errors)] {
+            for finding in findings {
+                let _ = match finding.row {
+                    Some(row) => writeln!(out, "{label}: row {row}: {}", finding.message),
+                    None => writeln!(out, "{label}: {}", finding.message),
+                };
+            }
+        }
+        let _ = writeln!(
+            out,
+            "{} validators, {} warnings, {} errors",
+            self.validators.len(),
+            self.warnings.len(),
+            self.errors.len()
+        );
+        out
+    }
+}
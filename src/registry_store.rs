@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+
+use serde::Serialize;
+use tracing::{error, info};
+
+use crate::app_state::AppState;
+use crate::config::Settings;
+use crate::registry_check::{Finding, RegistryReport};
+use crate::validators::{ValidatorRegistry, normalize_key};
+
+// What a reload changed, by validator name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RegistryChanges {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl RegistryChanges {
+    fn between(old: &ValidatorRegistry, new: &ValidatorRegistry) -> Self {
+        let old_by_name: HashMap<String, _> = old
+            .validators()
+            .iter()
+            .map(|validator| (normalize_key(validator.name()), validator))
+            .collect();
+        let mut changes = Self::default();
+        for validator in new.validators() {
+            match old_by_name.get(&normalize_key(validator.name())) {
+                None => changes.added.push(validator.name().to_string()),
+                Some(before) if *before != validator => {
+                    changes.changed.push(validator.name().to_string())
+                }
+                Some(_) => {}
+            }
+        }
+        changes.removed = old
+            .validators()
+            .iter()
+            .filter(|validator| new.get_by_name(validator.name()).is_none())
+            .map(|validator| validator.name().to_string())
+            .collect();
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+// The registry in use, behind a handle that reloads swap whole: a request
+// selects from the old registry or the new one, never a mix, and requests
+// already sent on keep the validator they were given.
+pub struct RegistryStore {
+    current: RwLock<Arc<ValidatorRegistry>>,
+    // Bumped on every swap, so per-worker state built for the previous
+    // registry can be brought up to date.
+    generation: AtomicU64,
+    // Of the registry file when last read, to notice it changed.
+    seen: Mutex<Option<FileVersion>>,
+    // A change not yet read: the file as first seen changed, read once it
+    // has stayed that way for a whole check interval.
+    pending: Mutex<Option<FileVersion>>,
+}
+
+type FileVersion = (SystemTime, u64);
+
+impl RegistryStore {
+    // `settings` name the file a watch compares against.
+    pub fn new(registry: ValidatorRegistry, settings: &Settings) -> Self {
+        let seen = match settings.validators_inline {
+            Some(_) => None,
+            None => file_version(&settings.validators_csv),
+        };
+        Self {
+            current: RwLock::new(Arc::new(registry)),
+            generation: AtomicU64::new(0),
+            seen: Mutex::new(seen),
+            pending: Mutex::new(None),
+        }
+    }
+
+    pub fn current(&self) -> Arc<ValidatorRegistry> {
+        self.current
+            .read()
+            .expect("registry store lock poisoned")
+            .clone()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    // Reads the registry source again and checks it as startup does. On any
+    // error the registry in use stays, and every problem found is returned.
+    pub fn reload(&self, settings: &Settings) -> Result<RegistryChanges, Vec<Finding>> {
+        let source = settings.registry_source();
+        if settings.validators_inline.is_none() {
+            // Not retried by the watch until the file changes again, even
+            // when it does not load.
+            *self.seen.lock().expect("registry store lock poisoned") =
+                file_version(&settings.validators_csv);
+        }
+        let contents = match &settings.validators_inline {
+            Some(csv) => csv.clone().into_bytes(),
+            None => fs::read(&settings.validators_csv).map_err(|err| {
+                vec![Finding {
+                    row: None,
+                    message: format!("cannot read {source}: {err}"),
+                }]
+            })?,
+        };
+        let reloaded = ValidatorRegistry::from_reader(contents.as_slice())
+            .map_err(|err| {
+                // The report carries on past the first bad row.
+                let errors = RegistryReport::from_reader(contents.as_slice()).errors;
+                if errors.is_empty() {
+                    vec![err.into()]
+                } else {
+                    errors
+                }
+            })
+            .and_then(|registry| {
+                settings
+                    .routing_rules
+                    .validate(&registry)
+                    .map(|()| registry)
+                    .map_err(|err| {
+                        vec![Finding {
+                            row: None,
+                            message: err.to_string(),
+                        }]
+                    })
+            });
+        let reloaded = match reloaded {
+            Ok(reloaded) => reloaded,
+            Err(findings) => {
+                error!(
+                    source,
+                    errors = findings.len(),
+                    "validator registry reload failed; keeping the previous registry"
+                );
+                return Err(findings);
+            }
+        };
+        let changes = RegistryChanges::between(&self.current(), &reloaded);
+        info!(
+            source,
+            validators = reloaded.validators().len(),
+            added = ?changes.added,
+            changed = ?changes.changed,
+            removed = ?changes.removed,
+            "reloaded validator registry"
+        );
+        *self.current.write().expect("registry store lock poisoned") = Arc::new(reloaded);
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        Ok(changes)
+    }
+
+    // Reloads once the registry file's modification time or size moved and
+    // then held still for a check, so a file written in several goes is
+    // read whole. A file gone missing is left until it is back.
+    pub fn reload_if_changed(
+        &self,
+        settings: &Settings,
+    ) -> Option<Result<RegistryChanges, Vec<Finding>>> {
+        if settings.validators_inline.is_some() {
+            return None;
+        }
+        let version = file_version(&settings.validators_csv)?;
+        let mut pending = self.pending.lock().expect("registry store lock poisoned");
+        if Some(version) == *self.seen.lock().expect("registry store lock poisoned") {
+            *pending = None;
+            return None;
+        }
+        if pending.replace(version) != Some(version) {
+            return None;
+        }
+        *pending = None;
+        drop(pending);
+        Some(self.reload(settings))
+    }
+}
+
+fn file_version(path: &Path) -> Option<FileVersion> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+// Checks the registry file every `VALIDATORS_RELOAD_SECS` until the server
+// starts shutting down.
+pub fn spawn_watch(state: AppState) {
+    let period = state.settings().validators_reload_interval;
+    if state.settings().validators_inline.is_some() || period.is_zero() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if state.is_draining() {
+                break;
+            }
+            let state = state.clone();
+            let _ = tokio::task::spawn_blocking(move || {
+                state.registry_store().reload_if_changed(state.settings())
+            })
+            .await;
+        }
+    });
+}
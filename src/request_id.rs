@@ -0,0 +1,127 @@
+use std::fmt;
+
+use actix_web::{
+    Error, HttpMessage, ResponseError,
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+};
+use rand::RngCore;
+use tracing::{Instrument, info_span};
+
+use crate::app_state::AppState;
+use crate::errors::AppError;
+
+pub const X_REQUEST_ID: &str = "x-request-id";
+
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    // A random (version 4) UUID.
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+        let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        Self(format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        ))
+    }
+
+    // Inbound ids must be printable ASCII without spaces; longer ones are cut
+    // to `MAX_LEN` characters.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_graphic()) {
+            return None;
+        }
+        Some(Self(value[..value.len().min(MAX_LEN)].to_string()))
+    }
+
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        headers
+            .get(X_REQUEST_ID)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Self::parse)
+            .unwrap_or_else(Self::generate)
+    }
+
+    // The id of the request handled by the current task, for code that has
+    // no access to the request (e.g. `AppError::error_response`).
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Also scopes the configured `ErrorFormat` to the request. Boxes the body so
+// the app keeps its plain `ServiceResponse` type.
+pub async fn assign(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let id = RequestId::from_headers(req.headers());
+    req.extensions_mut().insert(id.clone());
+
+    let span = info_span!("request", request_id = %id);
+    #[cfg(feature = "otel")]
+    crate::otel::continue_trace(&span, req.headers());
+    let format = req
+        .app_data::<actix_web::web::Data<AppState>>()
+        .map(|state| state.settings().error_format)
+        .unwrap_or_default();
+    let call = async move {
+        let response = next.call(req).await?.map_into_boxed_body();
+        Ok::<_, Error>(render_framework_error(response))
+    };
+    let mut response = CURRENT
+        .scope(id.clone(), format.scope(call))
+        .instrument(span)
+        .await?;
+    if let Ok(value) = HeaderValue::from_str(id.as_str()) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static(X_REQUEST_ID), value);
+    }
+
+    Ok(response)
+}
+
+// Actix renders its own errors as plain text; these are re-rendered as
+// `AppError` bodies, while the request id is still in scope.
+fn render_framework_error(response: ServiceResponse<BoxBody>) -> ServiceResponse<BoxBody> {
+    let Some(err) = response.response().error() else {
+        return response;
+    };
+    if err.as_error::<AppError>().is_some() {
+        return response;
+    }
+    match AppError::from_framework(response.status(), err.to_string()) {
+        Some(err) => response.into_response(err.error_response()),
+        None => response,
+    }
+}
@@ -0,0 +1,519 @@
+use actix_web::{
+    HttpRequest, HttpResponse,
+    dev::RequestHead,
+    http::{
+        Method,
+        header::{HeaderName, HeaderValue},
+    },
+    web::{self, Bytes},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+
+use crate::{
+    app_state::AppState,
+    auth, base58,
+    commitment::{self, Commitment},
+    errors::{AppError, ErrorCode, UpstreamErrorKind, error_body},
+    jsonrpc::{self, RpcMethod},
+    proxy::{self, ProxyRequest},
+    routes::{cache_bypassed, request_trace},
+};
+
+// Serving validator, for endpoints that return the upstream result as-is.
+pub const X_VALIDATOR: &str = "x-validator";
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct RestQuery {
+    #[serde(alias = "server")]
+    validator: Option<String>,
+    #[serde(alias = "region")]
+    location: Option<String>,
+    commitment: Option<String>,
+}
+
+impl RestQuery {
+    // Config fields for the call; without `commitment` the node default applies.
+    fn config(&self) -> Result<Map<String, Value>, AppError> {
+        let mut config = Map::new();
+        if let Some(raw) = self.commitment.as_deref() {
+            if Commitment::parse(raw).is_none() {
+                return Err(AppError::BadRequest(format!(
+                    "unknown commitment '{raw}', expected processed, confirmed or finalized"
+                )));
+            }
+            config.insert("commitment".to_string(), json!(raw));
+        }
+        Ok(config)
+    }
+}
+
+pub(crate) struct RpcCall {
+    pub result: Value,
+    pub validator: String,
+}
+
+// A raw JSON-RPC reply and the validator that produced it.
+pub(crate) struct RpcReply {
+    pub body: Bytes,
+    pub validator: String,
+}
+
+impl RpcReply {
+    pub fn into_call(self) -> Result<RpcCall, AppError> {
+        let validator = self.validator;
+        let mut response: Value = serde_json::from_slice(&self.body).map_err(|_| {
+            AppError::upstream(
+                &validator,
+                UpstreamErrorKind::InvalidResponse,
+                format!("node '{validator}' returned an invalid response"),
+            )
+        })?;
+        if let Some(error) = response.get("error") {
+            let message = error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error");
+            return Err(AppError::upstream(
+                &validator,
+                UpstreamErrorKind::InvalidResponse,
+                format!("node '{validator}' returned an error: {message}"),
+            ));
+        }
+
+        match response.get_mut("result").map(Value::take) {
+            Some(result) => Ok(RpcCall { result, validator }),
+            None => Err(AppError::upstream(
+                &validator,
+                UpstreamErrorKind::InvalidResponse,
+                format!("node '{validator}' returned no result"),
+            )),
+        }
+    }
+}
+
+// Builds a JSON-RPC call and sends it through the same selection, routing
+// rules and retry loop as proxied requests, returning the parsed `result`.
+// With `prefer_archive`, archive validators are tried first unless the
+// client picked a validator or location.
+pub(crate) async fn call_rpc(
+    state: &AppState,
+    req: &HttpRequest,
+    query: &RestQuery,
+    method: &str,
+    params: Value,
+    prefer_archive: bool,
+) -> Result<RpcCall, AppError> {
+    send_rpc(state, req, query, method, params, prefer_archive)
+        .await?
+        .into_call()
+}
+
+// Like `call_rpc`, but hands back JSON-RPC errors untouched. Responses go
+// through the response cache like proxied ones, remembering which validator
+// served them.
+pub(crate) async fn send_rpc(
+    state: &AppState,
+    req: &HttpRequest,
+    query: &RestQuery,
+    method: &str,
+    params: Value,
+    prefer_archive: bool,
+) -> Result<RpcReply, AppError> {
+    let rpc_method = RpcMethod::Single(method.to_string());
+    let scope = auth::key_scope(req);
+    if let Some(scope) = &scope {
+        scope.check_method(&rpc_method)?;
+        scope.check_selection(query.validator.as_deref(), query.location.as_deref())?;
+    }
+    let body = Bytes::from(
+        json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params}).to_string(),
+    );
+
+    let cache_key = state.response_cache().key(
+        &rpc_method,
+        query.validator.as_deref(),
+        query.location.as_deref(),
+        &body,
+    );
+    if let Some(key) = &cache_key
+        && !cache_bypassed(req)
+        && let Some(cached) = state.response_cache().get(key)
+    {
+        let validator = cached
+            .headers
+            .get(X_VALIDATOR)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        return Ok(RpcReply {
+            body: cached.body,
+            validator,
+        });
+    }
+
+    let settings = state.settings();
+    let candidates = state
+        .registry()
+        .candidates(query.validator.as_deref(), query.location.as_deref())?;
+    let candidates = match &scope {
+        Some(scope) => scope.narrow(candidates)?,
+        None => candidates,
+    };
+    let client_constrained = query.validator.is_some() || query.location.is_some();
+    let candidates = settings
+        .routing_rules
+        .apply(&rpc_method, client_constrained, candidates)?;
+    let mut candidates = if settings.commitment_routing {
+        let max_lag = Commitment::for_request(&body).max_slot_lag(settings);
+        commitment::filter_by_lag(state.stats(), max_lag, candidates)
+    } else {
+        candidates
+    };
+    if prefer_archive && !client_constrained {
+        candidates.sort_by_key(|validator| !validator.is_archive());
+    }
+
+    // The inbound request is a GET; upstream only sees a fresh POST.
+    let mut head = RequestHead::default();
+    head.method = Method::POST;
+    let max_retries = proxy::retry_budget(
+        settings.max_retries,
+        req.headers().get(proxy::X_NO_RETRY),
+        None,
+    );
+    let mut request = ProxyRequest::new(&head, body, request_trace(req), max_retries);
+    request.force_json_content_type = true;
+    request.key_id = auth::key_id(req);
+    request.pinned = query.validator.is_some();
+    request.max_response_bytes = Some(settings.response_size_limits.for_method(&rpc_method));
+    request.rpc_method = Some(rpc_method);
+
+    let upstream = proxy::forward(state, &request, candidates).await?;
+    let validator = request.attempted().pop().unwrap_or_default();
+    if !upstream.status.is_success() {
+        return Err(AppError::upstream(
+            &validator,
+            UpstreamErrorKind::BadStatus(upstream.status.as_u16()),
+            format!("node '{validator}' returned {}", upstream.status),
+        ));
+    }
+
+    let Some(mut buffered) = upstream.buffered() else {
+        return Err(AppError::Internal(
+            "unexpected streamed response".to_string(),
+        ));
+    };
+    if let Some(method) = &request.rpc_method {
+        state.response_cache().observe_slot(method, &buffered.body);
+    }
+    if let Some(key) = cache_key {
+        if let Ok(value) = HeaderValue::from_str(&validator) {
+            buffered
+                .headers
+                .insert(HeaderName::from_static(X_VALIDATOR), value);
+        }
+        state.response_cache().insert(key, buffered.clone());
+    }
+
+    Ok(RpcReply {
+        body: buffered.body,
+        validator,
+    })
+}
+
+const PUBKEY_BYTES: usize = 32;
+const SIGNATURE_BYTES: usize = 64;
+const LAMPORTS_PER_SOL: f64 = 1_000_000_000.0;
+
+// Checked before any upstream call so typos never cost a round trip.
+fn check_pubkey(pubkey: &str) -> Result<(), AppError> {
+    base58::decode_exact(pubkey, PUBKEY_BYTES)
+        .map(drop)
+        .map_err(|err| AppError::BadRequest(format!("invalid pubkey '{pubkey}': {err}")))
+}
+
+// Positional params with the config object last, omitted when empty.
+fn check_signature(signature: &str) -> Result<(), AppError> {
+    base58::decode_exact(signature, SIGNATURE_BYTES)
+        .map(drop)
+        .map_err(|err| AppError::BadRequest(format!("invalid signature '{signature}': {err}")))
+}
+
+fn not_found(what: String) -> HttpResponse {
+    HttpResponse::NotFound().json(error_body(ErrorCode::NotFound, format!("{what} not found")))
+}
+
+fn params(leading: Vec<Value>, config: Map<String, Value>) -> Value {
+    let config = (!config.is_empty()).then_some(Value::Object(config));
+    Value::Array(leading.into_iter().chain(config).collect())
+}
+
+#[derive(Debug, Serialize)]
+struct SlotResponse {
+    slot: u64,
+    validator: String,
+}
+
+pub(crate) async fn slot(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<RestQuery>,
+) -> Result<HttpResponse, AppError> {
+    let params = params(Vec::new(), query.config()?);
+    let call = call_rpc(&state, &req, &query, "getSlot", params, false).await?;
+    let slot = call.result.as_u64().ok_or_else(|| {
+        AppError::upstream(
+            &call.validator,
+            UpstreamErrorKind::InvalidResponse,
+            format!("node '{}' returned a non-numeric slot", call.validator),
+        )
+    })?;
+
+    Ok(HttpResponse::Ok().json(SlotResponse {
+        slot,
+        validator: call.validator,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+struct BalanceResponse {
+    lamports: u64,
+    sol: f64,
+    slot: Option<u64>,
+    validator: String,
+}
+
+pub(crate) async fn balance(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    pubkey: web::Path<String>,
+    query: web::Query<RestQuery>,
+) -> Result<HttpResponse, AppError> {
+    check_pubkey(&pubkey)?;
+    let params = params(vec![json!(*pubkey)], query.config()?);
+    let call = call_rpc(&state, &req, &query, "getBalance", params, false).await?;
+
+    let lamports = call.result["value"].as_u64().ok_or_else(|| {
+        AppError::upstream(
+            &call.validator,
+            UpstreamErrorKind::InvalidResponse,
+            format!("node '{}' returned a malformed balance", call.validator),
+        )
+    })?;
+    Ok(HttpResponse::Ok().json(BalanceResponse {
+        lamports,
+        sol: lamports as f64 / LAMPORTS_PER_SOL,
+        slot: call.result["context"]["slot"].as_u64(),
+        validator: call.validator,
+    }))
+}
+
+const TRANSACTION_ENCODINGS: &[&str] = &["json", "jsonParsed", "base64", "base58"];
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TransactionQuery {
+    encoding: Option<String>,
+}
+
+// Old transactions only live on archive nodes, so those are tried first.
+pub(crate) async fn transaction(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    signature: web::Path<String>,
+    query: web::Query<RestQuery>,
+    tx_query: web::Query<TransactionQuery>,
+) -> Result<HttpResponse, AppError> {
+    check_signature(&signature)?;
+    let encoding = tx_query.encoding.as_deref().unwrap_or("json");
+    if !TRANSACTION_ENCODINGS.contains(&encoding) {
+        return Err(AppError::BadRequest(format!(
+            "unsupported encoding '{encoding}', expected one of {}",
+            TRANSACTION_ENCODINGS.join(", ")
+        )));
+    }
+    if query.commitment.as_deref() == Some("processed") {
+        return Err(AppError::BadRequest(
+            "getTransaction does not support processed commitment".to_string(),
+        ));
+    }
+
+    let mut config = query.config()?;
+    config.insert("encoding".to_string(), json!(encoding));
+    // Without it, nodes reject any versioned transaction.
+    config.insert("maxSupportedTransactionVersion".to_string(), json!(0));
+    let params = params(vec![json!(*signature)], config);
+    let call = call_rpc(&state, &req, &query, "getTransaction", params, true).await?;
+
+    if call.result.is_null() {
+        return Ok(not_found(format!("transaction '{signature}'")));
+    }
+    Ok(HttpResponse::Ok()
+        .insert_header((X_VALIDATOR, call.validator))
+        .json(call.result))
+}
+
+const ACCOUNT_ENCODINGS: &[&str] = &["base58", "base64", "base64+zstd", "jsonParsed"];
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AccountQuery {
+    encoding: Option<String>,
+    #[serde(rename = "dataSlice.offset")]
+    data_slice_offset: Option<usize>,
+    #[serde(rename = "dataSlice.length")]
+    data_slice_length: Option<usize>,
+}
+
+struct DataSlice {
+    offset: usize,
+    length: usize,
+    defaulted: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct AccountResponse {
+    value: Value,
+    slot: Option<u64>,
+    validator: String,
+    // Set when the default slice cut the account data short.
+    truncated: bool,
+}
+
+pub(crate) async fn account(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    pubkey: web::Path<String>,
+    query: web::Query<RestQuery>,
+    account_query: web::Query<AccountQuery>,
+) -> Result<HttpResponse, AppError> {
+    check_pubkey(&pubkey)?;
+    let encoding = account_query.encoding.as_deref().unwrap_or("base64");
+    if !ACCOUNT_ENCODINGS.contains(&encoding) {
+        return Err(AppError::BadRequest(format!(
+            "unsupported encoding '{encoding}', expected one of {}",
+            ACCOUNT_ENCODINGS.join(", ")
+        )));
+    }
+
+    let mut config = query.config()?;
+    config.insert("encoding".to_string(), json!(encoding));
+
+    // Parsed accounts can't be sliced; binary ones get a default slice so a
+    // large account doesn't come back whole by accident.
+    let default_slice = state.settings().account_default_slice_bytes;
+    let slice = match (
+        account_query.data_slice_offset,
+        account_query.data_slice_length,
+    ) {
+        (None, None) if encoding != "jsonParsed" && default_slice > 0 => Some(DataSlice {
+            offset: 0,
+            length: default_slice,
+            defaulted: true,
+        }),
+        (None, None) => None,
+        (offset, Some(length)) => Some(DataSlice {
+            offset: offset.unwrap_or(0),
+            length,
+            defaulted: false,
+        }),
+        (Some(_), None) => {
+            return Err(AppError::BadRequest(
+                "dataSlice.offset requires dataSlice.length".to_string(),
+            ));
+        }
+    };
+    if let Some(slice) = &slice {
+        if encoding == "jsonParsed" {
+            return Err(AppError::BadRequest(
+                "dataSlice is not supported with jsonParsed encoding".to_string(),
+            ));
+        }
+        config.insert(
+            "dataSlice".to_string(),
+            json!({ "offset": slice.offset, "length": slice.length }),
+        );
+    }
+
+    let params = params(vec![json!(*pubkey)], config);
+    let mut call = call_rpc(&state, &req, &query, "getAccountInfo", params, false).await?;
+
+    let value = call.result["value"].take();
+    if value.is_null() {
+        return Ok(not_found(format!("account '{pubkey}'")));
+    }
+    let truncated = slice.is_some_and(|slice| {
+        slice.defaulted
+            && value["space"]
+                .as_u64()
+                .is_some_and(|space| space > slice.length as u64)
+    });
+    Ok(HttpResponse::Ok().json(AccountResponse {
+        value,
+        slot: call.result["context"]["slot"].as_u64(),
+        validator: call.validator,
+        truncated,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BlockhashResponse {
+    blockhash: String,
+    last_valid_block_height: Option<u64>,
+    slot: Option<u64>,
+    validator: String,
+}
+
+// Nodes older than 1.9 only know `getRecentBlockhash`, which has no
+// `lastValidBlockHeight`; it is asked of the same validator that turned the
+// modern call down.
+pub(crate) async fn blockhash(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<RestQuery>,
+) -> Result<HttpResponse, AppError> {
+    let config = query.config()?;
+    let reply = send_rpc(
+        &state,
+        &req,
+        &query,
+        "getLatestBlockhash",
+        params(Vec::new(), config.clone()),
+        false,
+    )
+    .await?;
+
+    let call = if jsonrpc::is_method_unavailable(&reply.body) {
+        let pinned = RestQuery {
+            validator: Some(reply.validator),
+            ..query.into_inner()
+        };
+        send_rpc(
+            &state,
+            &req,
+            &pinned,
+            "getRecentBlockhash",
+            params(Vec::new(), config),
+            false,
+        )
+        .await?
+        .into_call()?
+    } else {
+        reply.into_call()?
+    };
+
+    let value = &call.result["value"];
+    let blockhash = value["blockhash"].as_str().ok_or_else(|| {
+        AppError::upstream(
+            &call.validator,
+            UpstreamErrorKind::InvalidResponse,
+            format!("node '{}' returned a malformed blockhash", call.validator),
+        )
+    })?;
+    Ok(HttpResponse::Ok().json(BlockhashResponse {
+        blockhash: blockhash.to_string(),
+        last_valid_block_height: value["lastValidBlockHeight"].as_u64(),
+        slot: call.result["context"]["slot"].as_u64(),
+        validator: call.validator,
+    }))
+}
@@ -1,55 +1,596 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
 use actix_web::{
-    HttpRequest, HttpResponse,
-    http::header,
+    HttpMessage, HttpRequest, HttpResponse,
+    http::header::{self, HeaderName, HeaderValue},
+    middleware::from_fn,
     web::{self, Bytes},
 };
 use serde::{Deserialize, Serialize};
-use tracing::info;
-
-use crate::{app_state::AppState, errors::AppError, validators::ValidatorSummary};
+use serde_json::{Value, json};
 
-const MAX_UPSTREAM_BODY: usize = 32 * 1024 * 1024; // 32 MiB
+use crate::{
+    access_log::{self, Outcome},
+    admin,
+    app_state::AppState,
+    auth, body_log,
+    cache::{CacheCounters, X_CACHE},
+    client_limit,
+    commitment::{self, Commitment},
+    concurrency, cors, effective_config,
+    error_window::ErrorRates,
+    errors::{self, AppError, ErrorCode, UpstreamErrorKind, error_body},
+    fleet::{self, ClusterNode, FanOutError},
+    guardrail,
+    jsonrpc::{self, RpcMethod},
+    key_usage, metrics,
+    proxy::{self, ProxyRequest, QuorumOutcome, SubPath, UpstreamResponse},
+    quorum::X_QUORUM_VALIDATORS,
+    rate_limit,
+    request_id::RequestId,
+    rest, shadow, slow_request,
+    stats::{MethodStatsSnapshot, ShadowPairSnapshot, TrafficSnapshot},
+    trace_context::{self, TraceParent},
+    validators::{Validator, ValidatorSummary},
+    version::VersionInfo,
+};
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/health")
+            .wrap(from_fn(client_limit::limit))
+            .wrap(from_fn(auth::require_key))
+            .wrap(from_fn(rate_limit::headers))
+            .wrap(from_fn(cors::handle))
+            .route(web::get().to(health_check)),
+    )
+    .service(web::resource("/readyz").route(web::get().to(readiness)))
+    .service(web::resource("/version").route(web::get().to(version)))
+    .service(
+        web::resource("/validators")
+            .wrap(from_fn(client_limit::limit))
+            .wrap(from_fn(auth::require_key))
+            .wrap(from_fn(rate_limit::headers))
+            .wrap(from_fn(cors::handle))
+            .route(web::get().to(list_validators)),
+    )
+    .service(
+        web::resource("/validators/{name}/ping")
+            .wrap(from_fn(concurrency::refuse_while_draining))
+            .wrap(from_fn(client_limit::limit))
+            .wrap(from_fn(auth::require_key))
+            .wrap(from_fn(rate_limit::headers))
+            .wrap(from_fn(cors::handle))
+            .route(web::get().to(ping_validator)),
+    )
+    .service(web::resource("/stats").route(web::get().to(stats)))
+    .service(
+        web::resource("/stats/reset")
+            .wrap(from_fn(admin::require_token))
+            .route(web::post().to(reset_stats)),
+    )
+    .service(web::resource("/metrics").route(web::get().to(metrics)))
+    .service(
+        web::scope("/admin")
+            .wrap(from_fn(admin::require_token))
+            .service(web::resource("/config").route(web::get().to(admin_config)))
+            .service(web::resource("/reload").route(web::post().to(reload_registry)))
+            .service(web::resource("/keys/{id}/usage").route(web::get().to(key_usage))),
+    )
+    .service(
+        web::resource(auth::USAGE_PATH)
+            .wrap(from_fn(client_limit::limit))
+            .wrap(from_fn(auth::require_key))
+            .wrap(from_fn(rate_limit::headers))
+            .wrap(from_fn(cors::handle))
+            .route(web::get().to(own_usage)),
+    )
+    .service(
+        web::resource("/fleet/health")
+            .wrap(from_fn(concurrency::refuse_while_draining))
+            .route(web::get().to(fleet_health)),
+    )
+    .service(
+        web::resource("/fleet/cluster-nodes")
+            .wrap(from_fn(concurrency::refuse_while_draining))
+            .route(web::get().to(fleet_cluster_nodes)),
+    )
+    .service(
+        web::resource("/")
+            .wrap(from_fn(concurrency::limit))
+            .wrap(from_fn(concurrency::refuse_while_draining))
+            .wrap(from_fn(trace_context::propagate))
+            .wrap(from_fn(client_limit::limit))
+            .wrap(from_fn(auth::require_key))
+            .wrap(from_fn(rate_limit::headers))
+            .wrap(from_fn(cors::handle))
+            .route(web::get().to(index_info))
+            .route(web::post().to(proxy_rpc)),
+    )
+    .service(
+        web::resource("/slot")
+            .wrap(from_fn(concurrency::limit))
+            .wrap(from_fn(concurrency::refuse_while_draining))
+            .wrap(from_fn(trace_context::propagate))
+            .wrap(from_fn(client_limit::limit))
+            .wrap(from_fn(auth::require_key))
+            .wrap(from_fn(rate_limit::headers))
+            .wrap(from_fn(cors::handle))
+            .route(web::get().to(rest::slot)),
+    )
+    .service(
+        web::resource("/balance/{pubkey}")
+            .wrap(from_fn(concurrency::limit))
+            .wrap(from_fn(concurrency::refuse_while_draining))
+            .wrap(from_fn(trace_context::propagate))
+            .wrap(from_fn(client_limit::limit))
+            .wrap(from_fn(auth::require_key))
+            .wrap(from_fn(rate_limit::headers))
+            .wrap(from_fn(cors::handle))
+            .route(web::get().to(rest::balance)),
+    )
+    .service(
+        web::resource("/tx/{signature}")
+            .wrap(from_fn(concurrency::limit))
+            .wrap(from_fn(concurrency::refuse_while_draining))
+            .wrap(from_fn(trace_context::propagate))
+            .wrap(from_fn(client_limit::limit))
+            .wrap(from_fn(auth::require_key))
+            .wrap(from_fn(rate_limit::headers))
+            .wrap(from_fn(cors::handle))
+            .route(web::get().to(rest::transaction)),
+    )
+    .service(
+        web::resource("/account/{pubkey}")
+            .wrap(from_fn(concurrency::limit))
+            .wrap(from_fn(concurrency::refuse_while_draining))
+            .wrap(from_fn(trace_context::propagate))
+            .wrap(from_fn(client_limit::limit))
+            .wrap(from_fn(auth::require_key))
+            .wrap(from_fn(rate_limit::headers))
+            .wrap(from_fn(cors::handle))
+            .route(web::get().to(rest::account)),
+    )
+    .service(
+        web::resource("/blockhash")
+            .wrap(from_fn(concurrency::limit))
+            .wrap(from_fn(concurrency::refuse_while_draining))
+            .wrap(from_fn(trace_context::propagate))
+            .wrap(from_fn(client_limit::limit))
+            .wrap(from_fn(auth::require_key))
+            .wrap(from_fn(rate_limit::headers))
+            .wrap(from_fn(cors::handle))
+            .route(web::get().to(rest::blockhash)),
+    )
+    .service(
+        web::resource("/upstream/{tail:.*}")
+            .wrap(from_fn(concurrency::limit))
+            .wrap(from_fn(concurrency::refuse_while_draining))
+            .wrap(from_fn(trace_context::propagate))
+            .wrap(from_fn(client_limit::limit))
+            .wrap(from_fn(auth::require_key))
+            .wrap(from_fn(rate_limit::headers))
+            .wrap(from_fn(cors::handle))
+            .route(web::get().to(proxy_sub_path))
+            .route(web::post().to(proxy_sub_path)),
+    );
+}
+
+// The plaintext listener beside an HTTPS one: `/health` and `/readyz`
+// alone, without client auth, so load balancers can probe it.
+pub fn configure_health(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/health").route(web::get().to(health_check)))
-            .service(web::resource("/validators").route(web::get().to(list_validators)))
-            .service(web::resource("/")
-                .route(web::get().to(index_info))
-                .route(web::post().to(proxy_rpc))
-            );
+        .service(web::resource("/readyz").route(web::get().to(readiness)));
 }
 
-async fn health_check() -> HttpResponse {
-    HttpResponse::Ok().json(HealthResponse { status: "ok" })
+// Not ready once shutdown began, so load balancers move traffic away while
+// requests in flight finish.
+async fn readiness(state: web::Data<AppState>) -> HttpResponse {
+    if state.is_draining() {
+        HttpResponse::ServiceUnavailable().json(json!({"status": "draining"}))
+    } else {
+        HttpResponse::Ok().json(json!({"status": "ready"}))
     }
+}
+
+// In-memory reads only; `status` stays "ok" whatever the validators do.
+async fn health_check(state: web::Data<AppState>) -> HttpResponse {
+    let registry = state.registry();
+    let validators_available = registry
+        .validators()
+        .iter()
+        .filter(|validator| !state.stats().validator(validator.name()).is_cooling_down())
+        .count();
+    HttpResponse::Ok().json(HealthResponse {
+        status: "ok",
+        uptime_secs: state.uptime().as_secs(),
+        registry_loaded_at_unix_ms: registry.loaded_at_unix_ms(),
+        validators: registry.validators().len(),
+        validators_available,
+    })
+}
+
+async fn version(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(VersionInfo::new(&state))
+}
 
 async fn index_info() -> HttpResponse {
     HttpResponse::Ok().json(IndexInfo {
         name: "TheSolanaApi",
         description: "Provides a single, stable access point to a fleet of Solana validators. The API accepts standard Solana JSON-RPC requests and routes them to an available validator based on your selection criteria.",
         docs: "https://github.com/BastienGimbert/TheSolanaApi",
-        usage: "POST /?server=<name>, /?location=<region>, or / for a random location with a Solana JSON-RPC body. See /validators for options.",
+        usage: "POST /?server=<name>, /?location=<region>, or / for a random location with a Solana JSON-RPC body. See /validators for options. Other validator paths are reachable under /upstream/<path>.",
         health: "/health",
+        version: "/version",
         validators: "/validators",
+        error_codes: &ErrorCode::ALL,
         example: "curl -X POST 'http://thesolanaapi.com/?server=frankfurt-1' -H 'Content-Type: application/json' -d '{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"getVersion\",\"params\":[]}'",
     })
 }
 
-    #[derive(serde::Serialize)]
-    struct IndexInfo {
-        name: &'static str,
-        description: &'static str,
-        docs: &'static str,
-        usage: &'static str,
-        health: &'static str,
-        validators: &'static str,
-        example: &'static str,
+#[derive(serde::Serialize)]
+struct IndexInfo {
+    name: &'static str,
+    description: &'static str,
+    docs: &'static str,
+    usage: &'static str,
+    health: &'static str,
+    version: &'static str,
+    validators: &'static str,
+    // Every `code` an error body may carry.
+    error_codes: &'static [ErrorCode],
+    example: &'static str,
+}
+
+const VALIDATOR_SORTS: &[&str] = &["error_rate", "latency", "name"];
+
+// Set on answers sent to `DEFAULT_LOCATION` for want of a `location`.
+pub const X_LOCATION_DEFAULTED: &str = "x-location-defaulted";
+
+async fn list_validators(
+    state: web::Data<AppState>,
+    query: web::Query<ValidatorsQuery>,
+) -> Result<HttpResponse, AppError> {
+    let sort = query.sort.as_deref();
+    if let Some(sort) = sort
+        && !VALIDATOR_SORTS.contains(&sort)
+    {
+        return Err(AppError::BadRequest(format!(
+            "unsupported sort '{sort}', expected one of {}",
+            VALIDATOR_SORTS.join(", ")
+        )));
     }
 
+    let mut entries: Vec<(ValidatorSummary, ValidatorDetail)> = state
+        .registry()
+        .summaries()
+        .into_iter()
+        .map(|summary| {
+            let stats = state.stats().validator(&summary.name);
+            let detail = ValidatorDetail {
+                errors: stats.error_rates(),
+                latency_p50_ms: stats.traffic().latency_ms.p50,
+            };
+            (summary, detail)
+        })
+        .collect();
+
+    // Worst first for error rate and latency; ties keep name order.
+    match sort {
+        Some("error_rate") => entries.sort_by(|(a, a_detail), (b, b_detail)| {
+            b_detail
+                .errors
+                .error_rate
+                .total_cmp(&a_detail.errors.error_rate)
+                .then_with(|| a.name.cmp(&b.name))
+        }),
+        Some("latency") => entries.sort_by(|(a, a_detail), (b, b_detail)| {
+            let latency = |detail: &ValidatorDetail| detail.latency_p50_ms.unwrap_or(-1.0);
+            latency(b_detail)
+                .total_cmp(&latency(a_detail))
+                .then_with(|| a.name.cmp(&b.name))
+        }),
+        Some(_) => entries.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name)),
+        None => {}
+    }
 
-async fn list_validators(state: web::Data<AppState>) -> HttpResponse {
-    let validators = state.registry().summaries();
-    HttpResponse::Ok().json(ValidatorsResponse { validators })
+    let detail = query.detail.unwrap_or(false);
+    let live = query.stats.unwrap_or(false);
+    let validators = entries
+        .into_iter()
+        .map(|(summary, entry_detail)| {
+            let live = live.then(|| {
+                let stats = state.stats().validator(&summary.name);
+                ValidatorLiveStats {
+                    requests_total: stats.requests_total(),
+                    requests_last_minute: stats.requests_last_minute(),
+                    in_flight: stats.in_flight(),
+                }
+            });
+            ValidatorEntry {
+                summary,
+                detail: detail.then_some(entry_detail),
+                live,
+            }
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(ValidatorsResponse { validators }))
+}
+
+// Measures one validator directly, bypassing selection and retries.
+async fn ping_validator(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+    query: web::Query<PingQuery>,
+) -> Result<HttpResponse, AppError> {
+    let Some(validator) = state.registry().get_by_name(&name) else {
+        return Ok(HttpResponse::NotFound().json(error_body(
+            ErrorCode::UnknownValidator,
+            format!("unknown validator '{name}'"),
+        )));
+    };
+    let ping = RpcMethod::Single(fleet::PING.to_string());
+    match state.ping_limiter().check(&client_key(&req), &ping) {
+        Ok(quota) => {
+            if let Some(quota) = quota {
+                rate_limit::observe(&req, quota);
+            }
+        }
+        Err(quota) => {
+            rate_limit::observe(&req, quota);
+            return Err(AppError::RateLimited {
+                message: "ping rate limit exceeded".to_string(),
+                retry_after: quota.retry_after.unwrap_or_default(),
+            });
+        }
+    }
+
+    let count = query
+        .count
+        .unwrap_or(fleet::DEFAULT_PINGS)
+        .clamp(1, fleet::MAX_PINGS);
+    let report = fleet::ping(
+        &state.client_for(validator.rpc_url()),
+        &validator,
+        count,
+        state.settings().fleet_node_timeout,
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+async fn stats(state: web::Data<AppState>) -> HttpResponse {
+    let validators = state
+        .registry()
+        .validators()
+        .iter()
+        .map(|validator| {
+            let stats = state.stats().validator(validator.name());
+            ValidatorStats {
+                name: validator.name().to_string(),
+                location: validator.location().to_string(),
+                resolved_addresses: validator
+                    .dns_host()
+                    .map(|(host, port)| state.dns().resolved(&host, port))
+                    .unwrap_or_default(),
+                state: if stats.is_cooling_down() {
+                    "cooling_down"
+                } else {
+                    "available"
+                },
+                cooldown_remaining_ms: stats
+                    .cooldown_remaining()
+                    .map(|remaining| remaining.as_millis() as u64),
+                unsupported_methods: stats.unsupported_methods(),
+                latest_slot: stats.latest_slot(),
+                invalid_responses: stats.invalid_responses(),
+                traffic: stats.traffic(),
+            }
+        })
+        .collect();
+
+    HttpResponse::Ok().json(StatsResponse {
+        validators,
+        methods: state.stats().method_snapshots(),
+        cache: state.response_cache().counters(),
+        account_cache: state.account_cache().counters(),
+        shadow: state.stats().shadow_snapshots(),
+    })
+}
+
+async fn reset_stats(state: web::Data<AppState>) -> HttpResponse {
+    state.stats().reset();
+    HttpResponse::NoContent().finish()
+}
+
+// The same report as `print-config`, for the settings this process runs with.
+async fn admin_config(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(effective_config::render(state.settings()))
+}
+
+// Swaps in the registry as its source now reads. Requests already past
+// validator selection finish on the validators they were given.
+async fn reload_registry(state: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    let reloading = state.clone();
+    let reloaded = web::block(move || reloading.registry_store().reload(reloading.settings()))
+        .await
+        .map_err(|err| AppError::Internal(err.to_string()))?;
+    Ok(match reloaded {
+        Ok(changes) => HttpResponse::Ok().json(json!({
+            "validators": state.registry().validators().len(),
+            "added": changes.added,
+            "changed": changes.changed,
+            "removed": changes.removed,
+        })),
+        Err(errors) => {
+            let mut body = error_body(
+                ErrorCode::InvalidRegistry,
+                "validator registry reload failed; the previous registry stays in use",
+            );
+            body["errors"] = json!(errors);
+            HttpResponse::UnprocessableEntity().json(body)
+        }
+    })
+}
+
+async fn key_usage(state: web::Data<AppState>, id: web::Path<String>) -> HttpResponse {
+    match usage_report(&state, &id, false) {
+        Some(report) => HttpResponse::Ok().json(report),
+        None => HttpResponse::NotFound().json(error_body(
+            ErrorCode::NotFound,
+            format!("no usage for key '{id}'"),
+        )),
+    }
+}
+
+// The caller's own usage, for the key it authenticated with.
+async fn own_usage(req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    match auth::key_id(&req).and_then(|id| usage_report(&state, &id, true)) {
+        Some(report) => HttpResponse::Ok().json(report),
+        None => HttpResponse::NotFound().json(error_body(
+            ErrorCode::NotFound,
+            "API keys are not configured",
+        )),
+    }
+}
+
+// Traffic since startup, plus the quota period once auth is on. Issued keys
+// and the caller itself always have a report, even before a first request.
+fn usage_report(state: &AppState, id: &str, is_caller: bool) -> Option<key_usage::KeyUsageReport> {
+    let report = state.stats().key_usage(id);
+    let settings = state.settings();
+    if settings.api_keys.is_none() && settings.jwt.is_none() {
+        return report;
+    }
+    let keys = state.api_keys().current();
+    let key = keys.as_ref().and_then(|keys| keys.get(id));
+    if report.is_none() && key.is_none() && !is_caller && !state.stats().is_quota_tracked(id) {
+        return None;
+    }
+    let mut report = report.unwrap_or_else(|| key_usage::KeyUsageReport::empty(id));
+    report.quota = Some(state.stats().quota_usage(id, key.and_then(|key| key.quota)));
+    Some(report)
+}
+
+async fn metrics(state: web::Data<AppState>) -> HttpResponse {
+    if !state.settings().metrics_enabled {
+        return HttpResponse::NotFound()
+            .json(error_body(ErrorCode::NotFound, "metrics are disabled"));
+    }
+    HttpResponse::Ok()
+        .content_type(metrics::CONTENT_TYPE)
+        .body(metrics::render(&state))
+}
+
+// Live fan-out to every validator, throttled since each call costs one
+// request per node.
+async fn fleet_health(state: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    if let Err(retry_after) = state.fleet_throttle().try_acquire() {
+        return Err(AppError::RateLimited {
+            message: "fleet health was checked too recently".to_string(),
+            retry_after,
+        });
+    }
+
+    let settings = state.settings();
+    let report = fleet::check_health(
+        &state.build_client(),
+        state.registry().validators(),
+        settings.fleet_concurrency,
+        settings.fleet_node_timeout,
+    )
+    .await;
+    for node in &report.validators {
+        if let Some(slot) = node.slot {
+            state.stats().validator(&node.name).observe_slot(slot);
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// Gossip as seen by a sample of validators, compared with the registry. The
+// report is cached since `getClusterNodes` is heavy.
+async fn fleet_cluster_nodes(state: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    if let Some(report) = state.cluster_nodes().get() {
+        return Ok(HttpResponse::Ok().json(report));
+    }
+
+    let settings = state.settings();
+    let sample: Vec<Validator> = state
+        .registry()
+        .candidates(None, None)?
+        .into_iter()
+        .take(settings.cluster_nodes_sample.max(1))
+        .collect();
+
+    let client = state.build_client();
+    let outcomes = fleet::fan_out(
+        &sample,
+        settings.fleet_concurrency,
+        state.request_timeout(),
+        |validator| {
+            let client = client.clone();
+            async move { fleet::cluster_nodes(&client, &validator).await }
+        },
+    )
+    .await;
+
+    let mut sources = Vec::new();
+    let mut nodes: Vec<ClusterNode> = Vec::new();
+    let mut seen = HashSet::new();
+    let mut all_timed_out = true;
+    for outcome in outcomes {
+        all_timed_out &= matches!(outcome.result, Err(FanOutError::Timeout));
+        let error = match outcome.result {
+            Ok(found) => {
+                for node in found {
+                    if seen.insert(node.pubkey.clone()) {
+                        nodes.push(node);
+                    }
+                }
+                None
+            }
+            Err(FanOutError::Timeout) => Some("timed out".to_string()),
+            Err(FanOutError::Failed(err)) => Some(err),
+        };
+        sources.push(json!({ "validator": outcome.validator.name(), "error": error }));
+    }
+    if sources.iter().all(|source| !source["error"].is_null()) {
+        return Err(AppError::Upstream {
+            validator: None,
+            kind: if all_timed_out {
+                UpstreamErrorKind::Timeout
+            } else {
+                UpstreamErrorKind::ConnectRefused
+            },
+            message: format!(
+                "getClusterNodes failed on every sampled validator: {}",
+                Value::Array(sources)
+            ),
+            trail: None,
+            excerpt: None,
+        });
+    }
+
+    let mut validators = Vec::new();
+    for validator in state.registry().validators() {
+        let addrs = fleet::validator_addrs(state.dns(), validator).await;
+        validators.push((validator.clone(), addrs));
+    }
+    let report = fleet::cross_check(&validators, &nodes);
+
+    let body = json!({
+        "sources": sources,
+        "gossip_nodes": nodes.len(),
+        "configured": report.configured,
+        "unconfigured_rpc_nodes": report.unconfigured_rpc_nodes,
+    });
+    state.cluster_nodes().put(body.clone());
+    Ok(HttpResponse::Ok().json(body))
 }
 
 async fn proxy_rpc(
@@ -58,53 +599,486 @@ async fn proxy_rpc(
     body: Bytes,
     query: web::Query<ProxyQuery>,
 ) -> Result<HttpResponse, AppError> {
-    let selected = state
+    let started = Instant::now();
+    let max_retries = request_retry_budget(&state, &req, &query);
+    let mut request = ProxyRequest::new(req.head(), body, request_trace(&req), max_retries);
+    if let Some(id) = auth::key_id(&req) {
+        request.key_id = Some(id);
+    }
+
+    let result = relay_rpc(&state, &req, &query, &mut request).await;
+    #[cfg(feature = "sentry")]
+    crate::error_reporting::observe(&state, &request, &result);
+    let result = match result {
+        Err(err) if state.settings().jsonrpc_errors => Ok(access_log::mark(
+            rpc_error_response(&err, &request),
+            access_log::failure(&request),
+        )),
+        result => result,
+    };
+    access_log::record(&req, &request, started, &result);
+    key_usage::record(&state, &request, &result);
+    slow_request::check(&state, &request, started, &result);
+    result
+}
+
+async fn relay_rpc(
+    state: &AppState,
+    req: &HttpRequest,
+    query: &ProxyQuery,
+    request: &mut ProxyRequest<'_>,
+) -> Result<HttpResponse, AppError> {
+    let body = request.body.clone();
+    request.force_json_content_type = check_content_type(state, req, &body)?;
+
+    let rpc_method = jsonrpc::extract_method(&body);
+    request.rpc_method = Some(rpc_method.clone());
+    request.pinned = query.validator.is_some();
+    body_log::request(state, request);
+    let scope = auth::key_scope(req);
+    if let Some(scope) = &scope {
+        scope.check_method(&rpc_method)?;
+        scope.check_selection(query.validator.as_deref(), query.location.as_deref())?;
+    }
+    match state.method_limiter().check(&client_key(req), &rpc_method) {
+        Ok(quota) => {
+            if let Some(quota) = quota {
+                rate_limit::observe(req, quota);
+            }
+        }
+        Err(quota) => {
+            rate_limit::observe(req, quota);
+            let retry_after = quota.retry_after.unwrap_or_default();
+            return Ok(access_log::mark(
+                rate_limited_response(request, &rpc_method, retry_after),
+                Outcome::Rejected,
+            ));
+        }
+    }
+
+    if guardrail::applies_to(&rpc_method)
+        && !guardrail_bypassed(state, req)
+        && let Some(rejection) = guardrail::reject(&body)
+    {
+        return Ok(access_log::mark(
+            HttpResponse::Ok().json(rejection),
+            Outcome::Rejected,
+        ));
+    }
+
+    let candidates = state
         .registry()
-        .select(query.validator.as_deref(), query.location.as_deref())?;
+        .candidates(query.validator.as_deref(), query.location.as_deref())?;
+    let candidates = match &scope {
+        Some(scope) => scope.narrow(candidates)?,
+        None => candidates,
+    };
+    let client_constrained = query.validator.is_some() || query.location.is_some();
+    let candidates =
+        state
+            .settings()
+            .routing_rules
+            .apply(&rpc_method, client_constrained, candidates)?;
+    let candidates = if state.settings().commitment_routing {
+        let max_lag = Commitment::for_request(&body).max_slot_lag(state.settings());
+        commitment::filter_by_lag(state.stats(), max_lag, candidates)
+    } else {
+        candidates
+    };
 
-    info!(
-        validator = selected.name(),
-        location = selected.location(),
-        "forwarding json-rpc request"
+    request.max_response_bytes = Some(
+        state
+            .settings()
+            .response_size_limits
+            .for_method(&rpc_method),
     );
 
-    let client = state.build_client();
+    if let Some(size) = query.quorum {
+        check_quorum(state, &rpc_method, size)?;
+        return relay_quorum(state, request, candidates, size).await;
+    }
 
-    let mut forward_req = client.request_from(selected.rpc_url().as_str(), req.head());
+    let (candidates, location_defaulted) = if client_constrained {
+        (candidates, false)
+    } else {
+        default_location(state, candidates)
+    };
+
+    let coalesce_key =
+        state
+            .coalescer()
+            .key(query.validator.as_deref(), query.location.as_deref(), &body);
+
+    let cache_key = state.response_cache().key(
+        &rpc_method,
+        query.validator.as_deref(),
+        query.location.as_deref(),
+        &body,
+    );
 
-    if let Some(host) = selected.host_header() {
-        forward_req = forward_req.insert_header((header::HOST, host));
+    let account_lookup =
+        state
+            .account_cache()
+            .lookup(&rpc_method, query.validator.as_deref(), &body);
+
+    let request = &*request;
+
+    if let Some(key) = &cache_key
+        && !cache_bypassed(req)
+        && let Some(cached) = state.response_cache().get(key)
+    {
+        let upstream = UpstreamResponse::replay(cached, &request.body);
+        let mut response = upstream.into_response(state, request);
+        response.headers_mut().insert(
+            HeaderName::from_static(X_CACHE),
+            HeaderValue::from_static("HIT"),
+        );
+        return Ok(response);
     }
-    
-    let mut upstream_resp = match forward_req.send_body(body).await {
-        Ok(resp) => resp,
-        Err(e) => {
-            return Err(AppError::Upstream(format!(
-                "node '{}' is unavailable: {}",
-                selected.name(), e
-            )));
+
+    if let Some(lookup) = &account_lookup
+        && !cache_bypassed(req)
+        && let Some(result) = state
+            .account_cache()
+            .get(lookup, state.stats().highest_slot())
+    {
+        let id = jsonrpc::request_id(&request.body).unwrap_or(Value::Null);
+        let upstream =
+            UpstreamResponse::json(&json!({"jsonrpc": "2.0", "result": result, "id": id}));
+        let mut response = upstream.into_response(state, request);
+        response.headers_mut().insert(
+            HeaderName::from_static(X_CACHE),
+            HeaderValue::from_static("HIT"),
+        );
+        return Ok(response);
+    }
+
+    let upstream = match coalesce_key {
+        Some(key) => proxy::forward_coalesced(state, request, candidates, key).await?,
+        None => proxy::forward(state, request, candidates).await?,
+    };
+
+    if let (Some(method), Some(buffered)) = (&request.rpc_method, upstream.buffered_body()) {
+        state.response_cache().observe_slot(method, buffered);
+
+        // Followers of a coalesced request never reached a validator themselves.
+        if upstream.status.is_success()
+            && let Some(primary) = request.attempted().pop()
+            && shadow::should_sample(state, method)
+        {
+            shadow::spawn_audit(
+                state,
+                method.to_string(),
+                primary,
+                request.body.clone(),
+                buffered.clone(),
+            );
         }
+    }
+
+    if let Some(lookup) = &account_lookup
+        && upstream.status.is_success()
+        && let Some(buffered) = upstream.buffered_body()
+    {
+        state.account_cache().insert(lookup, buffered);
+    }
+
+    let cacheable = cache_key.is_some() || account_lookup.is_some();
+    if let Some(key) = cache_key
+        && let Some(buffered) = upstream.buffered()
+    {
+        state.response_cache().insert(key, buffered);
+    }
+    let mut response = upstream.into_response(state, request);
+    if cacheable {
+        response.headers_mut().insert(
+            HeaderName::from_static(X_CACHE),
+            HeaderValue::from_static("MISS"),
+        );
+    }
+    if location_defaulted {
+        response.headers_mut().insert(
+            HeaderName::from_static(X_LOCATION_DEFAULTED),
+            HeaderValue::from_static("true"),
+        );
+    }
+    Ok(response)
+}
+
+// For a request naming neither validator nor location: the candidates in
+// `DEFAULT_LOCATION`, as long as one of them is not cooling down. Otherwise
+// the whole pool is kept.
+fn default_location(state: &AppState, candidates: Vec<Validator>) -> (Vec<Validator>, bool) {
+    let Some(location) = &state.settings().default_location else {
+        return (candidates, false);
     };
+    let local: Vec<Validator> = candidates
+        .iter()
+        .filter(|validator| validator.location().eq_ignore_ascii_case(location))
+        .cloned()
+        .collect();
+    let healthy = local
+        .iter()
+        .any(|validator| !state.stats().validator(validator.name()).is_cooling_down());
+    if healthy {
+        (local, true)
+    } else {
+        (candidates, false)
+    }
+}
 
-    let status = upstream_resp.status();
+async fn proxy_sub_path(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    body: Bytes,
+    query: web::Query<ProxyQuery>,
+) -> Result<HttpResponse, AppError> {
+    let started = Instant::now();
+    let max_retries = request_retry_budget(&state, &req, &query);
+    let mut request = ProxyRequest::new(req.head(), body, request_trace(&req), max_retries);
+    if let Some(id) = auth::key_id(&req) {
+        request.key_id = Some(id);
+    }
+    request.stream_response = true;
+
+    let result = relay_sub_path(&state, &req, &query, &mut request).await;
+    access_log::record(&req, &request, started, &result);
+    key_usage::record(&state, &request, &result);
+    result
+}
+
+async fn relay_sub_path(
+    state: &AppState,
+    req: &HttpRequest,
+    query: &ProxyQuery,
+    request: &mut ProxyRequest<'_>,
+) -> Result<HttpResponse, AppError> {
+    let raw_tail = req
+        .uri()
+        .path()
+        .strip_prefix("/upstream")
+        .unwrap_or_default();
+    request.sub_path = Some(SubPath::parse(raw_tail, req.query_string())?);
+    request.pinned = query.validator.is_some();
+
+    let candidates = state
+        .registry()
+        .candidates(query.validator.as_deref(), query.location.as_deref())?;
+    // Sub-paths are not JSON-RPC calls, so a method-scoped key cannot use them.
+    let candidates = match auth::key_scope(req) {
+        Some(scope) if !scope.methods.is_empty() => {
+            return Err(AppError::KeyScopeViolation(
+                "API key may not use /upstream sub-paths".to_string(),
+            ));
+        }
+        Some(scope) => {
+            scope.check_selection(query.validator.as_deref(), query.location.as_deref())?;
+            scope.narrow(candidates)?
+        }
+        None => candidates,
+    };
+    let upstream = proxy::forward(state, request, candidates).await?;
+
+    Ok(upstream.into_response(state, request))
+}
+
+fn rpc_error_response(err: &AppError, request: &ProxyRequest<'_>) -> HttpResponse {
+    #[cfg(feature = "sentry")]
+    crate::error_reporting::capture(err);
+    let attempted = request.attempted();
+    let mut data = json!({
+        "code": err.code(),
+        "validator": attempted.last(),
+        "attempts": attempted.len(),
+        "maxRetries": request.max_retries,
+        "requestId": request.request_id.as_ref().map(RequestId::as_str),
+    });
+    if let AppError::Upstream {
+        trail: Some(trail), ..
+    } = err
+    {
+        trail.annotate(&mut data);
+    }
+    let id = jsonrpc::request_id(&request.body).unwrap_or(Value::Null);
 
-    let mut response_builder = HttpResponse::build(status);
+    let mut response = HttpResponse::Ok();
+    if let Some(retry_after) = err.retry_after() {
+        data["retryAfterMs"] = json!(retry_after.as_millis() as u64);
+        response.insert_header((header::RETRY_AFTER, errors::retry_after_secs(retry_after)));
+    }
+    response.json(jsonrpc::error_object(
+        id,
+        err.rpc_code(),
+        &err.to_string(),
+        data,
+    ))
+}
+
+// Quorum reads fan out without retries, so they are limited to idempotent
+// single calls and a bounded number of validators.
+fn check_quorum(state: &AppState, method: &RpcMethod, size: usize) -> Result<(), AppError> {
+    let max = state.settings().quorum_max_validators;
+    if size == 0 || size > max {
+        return Err(AppError::BadRequest(format!(
+            "quorum must be between 1 and {max}"
+        )));
+    }
 
-    if let Some(content_type) = upstream_resp.headers().get(header::CONTENT_TYPE) {
-        response_builder.insert_header((header::CONTENT_TYPE, content_type.clone()));
+    match method.name() {
+        Some(name) if jsonrpc::is_idempotent(name) => Ok(()),
+        Some(name) => Err(AppError::BadRequest(format!(
+            "quorum reads are not allowed for '{name}'"
+        ))),
+        None => Err(AppError::BadRequest(
+            "quorum reads need a single JSON-RPC call".to_string(),
+        )),
     }
+}
 
-    let payload = match upstream_resp.body().limit(MAX_UPSTREAM_BODY).await {
-        Ok(p) => p,
-        Err(e) => {
-            return Err(AppError::Upstream(format!(
-                "node '{}' is unavailable: {}",
-                selected.name(), e
-            )));
+async fn relay_quorum(
+    state: &AppState,
+    request: &ProxyRequest<'_>,
+    candidates: Vec<Validator>,
+    size: usize,
+) -> Result<HttpResponse, AppError> {
+    let QuorumOutcome { response, votes } =
+        proxy::forward_quorum(state, request, candidates, size).await?;
+    let mut response = match response {
+        Some(upstream) => upstream.into_response(state, request),
+        None => {
+            let mut body = error_body(
+                ErrorCode::QuorumNotReached,
+                format!("validators did not reach a quorum of {size}"),
+            );
+            body["votes"] = json!(votes);
+            access_log::mark(HttpResponse::BadGateway().json(body), Outcome::Failed)
         }
     };
 
-    Ok(response_builder.body(payload))
+    let validators = votes
+        .iter()
+        .map(|vote| vote.validator.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if let Ok(value) = HeaderValue::from_str(&validators) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static(X_QUORUM_VALIDATORS), value);
+    }
+    Ok(response)
+}
+
+fn rate_limited_response(
+    request: &ProxyRequest<'_>,
+    method: &RpcMethod,
+    retry_after: Duration,
+) -> HttpResponse {
+    let retry_after_secs = errors::retry_after_secs(retry_after);
+    let id = jsonrpc::request_id(&request.body).unwrap_or(Value::Null);
+    let body = jsonrpc::error_object(
+        id,
+        jsonrpc::RATE_LIMITED,
+        &format!("rate limit exceeded for {method}"),
+        json!({
+            "code": ErrorCode::RateLimited,
+            "retryAfterSecs": retry_after_secs,
+            "retryAfterMs": retry_after.as_millis() as u64,
+            "requestId": request.request_id.as_ref().map(RequestId::as_str),
+        }),
+    );
+
+    HttpResponse::TooManyRequests()
+        .insert_header((header::RETRY_AFTER, retry_after_secs))
+        .json(body)
+}
+
+fn guardrail_bypassed(state: &AppState, req: &HttpRequest) -> bool {
+    let settings = state.settings();
+    if !settings.guardrail_enabled {
+        return true;
+    }
+
+    if settings.guardrail_allowlist.contains(&client_key(req)) {
+        return true;
+    }
+
+    settings
+        .guardrail_bypass_header
+        .as_ref()
+        .and_then(|name| req.headers().get(name))
+        .is_some_and(|value| {
+            settings
+                .guardrail_bypass_value
+                .as_ref()
+                .is_none_or(|expected| expected == value)
+        })
+}
+
+// Until clients authenticate, limits are tracked per client address, taken
+// from `X-Forwarded-For` behind a trusted proxy.
+fn client_key(req: &HttpRequest) -> String {
+    let trusted = req
+        .app_data::<web::Data<AppState>>()
+        .map(|state| state.settings().trusted_proxies.as_slice())
+        .unwrap_or_default();
+    client_limit::client_ip(req.peer_addr(), req.headers(), trusted)
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+// Returns whether the body was accepted leniently and should be relabelled as JSON upstream.
+fn check_content_type(state: &AppState, req: &HttpRequest, body: &Bytes) -> Result<bool, AppError> {
+    let settings = state.settings();
+    if !settings.enforce_json_content_type {
+        return Ok(false);
+    }
+
+    let essence = req
+        .mime_type()
+        .ok()
+        .flatten()
+        .map(|mime| mime.essence_str().to_string());
+
+    match essence.as_deref() {
+        Some("application/json") => Ok(false),
+        // Some tools send text/plain when no content type is given.
+        Some("text/plain")
+            if settings.lenient_content_type
+                && serde_json::from_slice::<serde::de::IgnoredAny>(body).is_ok() =>
+        {
+            Ok(true)
+        }
+        other => Err(AppError::UnsupportedMediaType(format!(
+            "unsupported content type '{}', expected 'application/json'",
+            other.unwrap_or("none")
+        ))),
+    }
+}
+
+pub(crate) fn cache_bypassed(req: &HttpRequest) -> bool {
+    req.headers()
+        .get_all(header::CACHE_CONTROL)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|directive| {
+            let directive = directive.trim();
+            directive.eq_ignore_ascii_case("no-cache") || directive.eq_ignore_ascii_case("no-store")
+        })
+}
+
+fn request_retry_budget(state: &AppState, req: &HttpRequest, query: &ProxyQuery) -> usize {
+    proxy::retry_budget(
+        state.settings().max_retries,
+        req.headers().get(proxy::X_NO_RETRY),
+        query.retries,
+    )
+}
+
+pub(crate) fn request_trace(req: &HttpRequest) -> TraceParent {
+    req.extensions()
+        .get::<TraceParent>()
+        .copied()
+        .unwrap_or_else(|| TraceParent::from_headers(req.headers()))
 }
 
 #[derive(Debug, Deserialize)]
@@ -113,14 +1087,80 @@ struct ProxyQuery {
     validator: Option<String>,
     #[serde(alias = "region")]
     location: Option<String>,
+    retries: Option<usize>,
+    quorum: Option<usize>,
 }
 
 #[derive(Debug, Serialize)]
 struct HealthResponse {
     status: &'static str,
+    uptime_secs: u64,
+    registry_loaded_at_unix_ms: u64,
+    validators: usize,
+    // Validators not in a failure cooldown.
+    validators_available: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct ValidatorsQuery {
+    detail: Option<bool>,
+    stats: Option<bool>,
+    sort: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PingQuery {
+    count: Option<usize>,
 }
 
 #[derive(Debug, Serialize)]
 struct ValidatorsResponse {
-    validators: Vec<ValidatorSummary>,
+    validators: Vec<ValidatorEntry>,
+}
+
+#[derive(Debug, Serialize)]
+struct ValidatorEntry {
+    #[serde(flatten)]
+    summary: ValidatorSummary,
+    #[serde(flatten)]
+    detail: Option<ValidatorDetail>,
+    #[serde(flatten)]
+    live: Option<ValidatorLiveStats>,
+}
+
+#[derive(Debug, Serialize)]
+struct ValidatorDetail {
+    errors: ErrorRates,
+    latency_p50_ms: Option<f64>,
+}
+
+// Upstream calls to the validator, as counted in `/stats`.
+#[derive(Debug, Serialize)]
+struct ValidatorLiveStats {
+    requests_total: u64,
+    requests_last_minute: u64,
+    in_flight: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct StatsResponse {
+    validators: Vec<ValidatorStats>,
+    methods: Vec<MethodStatsSnapshot>,
+    cache: CacheCounters,
+    account_cache: CacheCounters,
+    shadow: Vec<ShadowPairSnapshot>,
+}
+
+#[derive(Debug, Serialize)]
+struct ValidatorStats {
+    name: String,
+    location: String,
+    resolved_addresses: Vec<SocketAddr>,
+    state: &'static str,
+    cooldown_remaining_ms: Option<u64>,
+    unsupported_methods: Vec<String>,
+    latest_slot: Option<u64>,
+    invalid_responses: u64,
+    #[serde(flatten)]
+    traffic: TrafficSnapshot,
 }
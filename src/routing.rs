@@ -0,0 +1,219 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::errors::AppError;
+use crate::jsonrpc::RpcMethod;
+use crate::validators::{Validator, ValidatorRegistry, normalize_key};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RoutingRule {
+    pub method: String,
+    #[serde(default)]
+    pub validators: Vec<String>,
+    #[serde(default)]
+    pub locations: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub archive: Option<bool>,
+}
+
+impl RoutingRule {
+    pub fn matches(&self, method: &str) -> bool {
+        glob_match(self.method.as_bytes(), method.as_bytes())
+    }
+
+    pub fn allows(&self, validator: &Validator) -> bool {
+        let matches_any = |list: &[String], value: &str| {
+            list.is_empty()
+                || list
+                    .iter()
+                    .any(|item| normalize_key(item) == normalize_key(value))
+        };
+
+        matches_any(&self.validators, validator.name())
+            && matches_any(&self.locations, validator.location())
+            && self.tags.iter().all(|tag| validator.has_tag(tag))
+            && self
+                .archive
+                .is_none_or(|archive| validator.is_archive() == archive)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RoutingRules {
+    rules: Vec<RoutingRule>,
+}
+
+#[derive(Debug, Error)]
+pub enum RoutingError {
+    #[error("invalid routing rules: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("failed to read routing rules: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("routing rule for '{method}' references unknown validator '{validator}'")]
+    UnknownValidator { method: String, validator: String },
+    #[error("routing rule for '{method}' references unknown location '{location}'")]
+    UnknownLocation { method: String, location: String },
+    #[error("routing rule for '{method}' references tag '{tag}' that no validator has")]
+    UnknownTag { method: String, tag: String },
+    #[error("routing rule for '{0}' matches no validator")]
+    NoMatch(String),
+}
+
+impl RoutingRules {
+    pub fn new(rules: Vec<RoutingRule>) -> Self {
+        Self { rules }
+    }
+
+    pub fn from_json(raw: &str) -> Result<Self, RoutingError> {
+        Ok(Self::new(serde_json::from_str(raw)?))
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, RoutingError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn rules(&self) -> &[RoutingRule] {
+        &self.rules
+    }
+
+    pub fn validate(&self, registry: &ValidatorRegistry) -> Result<(), RoutingError> {
+        for rule in &self.rules {
+            let method = rule.method.clone();
+
+            if let Some(validator) = rule
+                .validators
+                .iter()
+                .find(|name| registry.get_by_name(name).is_none())
+            {
+                return Err(RoutingError::UnknownValidator {
+                    method,
+                    validator: validator.clone(),
+                });
+            }
+
+            if let Some(location) = rule
+                .locations
+                .iter()
+                .find(|location| !registry.has_location(location))
+            {
+                return Err(RoutingError::UnknownLocation {
+                    method,
+                    location: location.clone(),
+                });
+            }
+
+            if let Some(tag) = rule.tags.iter().find(|tag| {
+                !registry
+                    .validators()
+                    .iter()
+                    .any(|validator| validator.has_tag(tag))
+            }) {
+                return Err(RoutingError::UnknownTag {
+                    method,
+                    tag: tag.clone(),
+                });
+            }
+
+            if !registry.validators().iter().any(|v| rule.allows(v)) {
+                return Err(RoutingError::NoMatch(method));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn best_match(&self, method: &str) -> Option<&RoutingRule> {
+        best_pattern_match(&self.rules, |rule| &rule.method, method).map(|idx| &self.rules[idx])
+    }
+
+    // A batch has to satisfy the rule of every method it contains.
+    pub fn rules_for(&self, method: &RpcMethod) -> Vec<&RoutingRule> {
+        let names: Vec<&str> = match method {
+            RpcMethod::Single(name) => vec![name],
+            RpcMethod::Batch(names) => names.iter().flatten().map(String::as_str).collect(),
+            RpcMethod::Invalid => Vec::new(),
+        };
+
+        let mut matched: Vec<&RoutingRule> = Vec::new();
+        for rule in names.into_iter().filter_map(|name| self.best_match(name)) {
+            if !matched.iter().any(|seen| std::ptr::eq(*seen, rule)) {
+                matched.push(rule);
+            }
+        }
+        matched
+    }
+
+    // Rules are hard constraints; `server`/`location` from the client can only
+    // narrow the rule's candidates, and a selection outside them is rejected.
+    pub fn apply(
+        &self,
+        method: &RpcMethod,
+        client_constrained: bool,
+        candidates: Vec<Validator>,
+    ) -> Result<Vec<Validator>, AppError> {
+        let rules = self.rules_for(method);
+        if rules.is_empty() {
+            return Ok(candidates);
+        }
+
+        let allowed: Vec<Validator> = candidates
+            .into_iter()
+            .filter(|validator| rules.iter().all(|rule| rule.allows(validator)))
+            .collect();
+
+        if allowed.is_empty() {
+            let reason = if client_constrained {
+                "conflicts with the requested server or location"
+            } else {
+                "matches no validator"
+            };
+            return Err(AppError::BadRequest(format!(
+                "routing rule for method '{method}' {reason}"
+            )));
+        }
+
+        Ok(allowed)
+    }
+}
+
+// Exact names beat any glob; among globs the one with more literal
+// characters wins, and list order breaks remaining ties.
+pub(crate) fn best_pattern_match<T>(
+    items: &[T],
+    pattern: impl Fn(&T) -> &str,
+    method: &str,
+) -> Option<usize> {
+    items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| glob_match(pattern(item).as_bytes(), method.as_bytes()))
+        .max_by_key(|(idx, item)| (specificity(pattern(item)), std::cmp::Reverse(*idx)))
+        .map(|(idx, _)| idx)
+}
+
+fn specificity(pattern: &str) -> (bool, usize) {
+    let wildcards = pattern.chars().filter(|c| matches!(c, '*' | '?')).count();
+    (wildcards == 0, pattern.len() - wildcards)
+}
+
+fn glob_match(pattern: &[u8], value: &[u8]) -> bool {
+    match (pattern.first(), value.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob_match(&pattern[1..], value)
+                || (!value.is_empty() && glob_match(pattern, &value[1..]))
+        }
+        (Some(b'?'), Some(_)) => glob_match(&pattern[1..], &value[1..]),
+        (Some(p), Some(v)) if p == v => glob_match(&pattern[1..], &value[1..]),
+        _ => false,
+    }
+}
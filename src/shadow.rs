@@ -0,0 +1,86 @@
+use actix_web::{http::header, web::Bytes};
+use rand::Rng;
+use rand::seq::SliceRandom;
+use tracing::{debug, warn};
+
+use crate::app_state::AppState;
+use crate::jsonrpc::{self, RpcMethod};
+use crate::validators::Validator;
+
+pub fn should_sample(state: &AppState, method: &RpcMethod) -> bool {
+    let rate = state.settings().shadow_sample_rate;
+    rate > 0.0
+        && method.name().is_some_and(jsonrpc::is_idempotent)
+        && rand::thread_rng().gen_bool(rate.min(1.0))
+}
+
+// Replays an already answered request against one other validator and
+// counts whether the results match. Runs detached from the client request.
+pub fn spawn_audit(
+    state: &AppState,
+    method: String,
+    primary: String,
+    request_body: Bytes,
+    primary_body: Bytes,
+) {
+    let registry = state.registry();
+    let candidates: Vec<&Validator> = registry
+        .validators()
+        .iter()
+        .filter(|validator| validator.name() != primary)
+        .collect();
+    let Some(shadow) = candidates.choose(&mut rand::thread_rng()).copied().cloned() else {
+        return;
+    };
+
+    let state = state.clone();
+    actix_web::rt::spawn(async move {
+        let client = state.client_for(shadow.rpc_url());
+        let sent = client
+            .post(shadow.rpc_url().as_str())
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .insert_header((
+                header::USER_AGENT,
+                state.settings().upstream_user_agent.clone(),
+            ))
+            .send_body(request_body)
+            .await;
+
+        let shadow_body = match sent {
+            Ok(mut response) if response.status().is_success() => {
+                let limit = state.settings().response_size_limits.limit_for(&method);
+                match response.body().limit(limit).await {
+                    Ok(body) => body,
+                    Err(err) => {
+                        debug!(shadow = shadow.name(), error = %err, "shadow read failed");
+                        return;
+                    }
+                }
+            }
+            Ok(response) => {
+                debug!(shadow = shadow.name(), status = %response.status(), "shadow read failed");
+                return;
+            }
+            Err(err) => {
+                debug!(shadow = shadow.name(), error = %err, "shadow read failed");
+                return;
+            }
+        };
+
+        let ignore = &state.settings().shadow_ignore_fields;
+        let expected = jsonrpc::normalized_result(&primary_body, ignore);
+        let actual = jsonrpc::normalized_result(&shadow_body, ignore);
+        let diverged = expected != actual;
+        if diverged {
+            warn!(
+                primary = %primary,
+                shadow = shadow.name(),
+                method = %method,
+                "shadow read diverged"
+            );
+        }
+        state
+            .stats()
+            .record_shadow_check(&primary, shadow.name(), diverged);
+    });
+}
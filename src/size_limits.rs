@@ -0,0 +1,65 @@
+use serde::Deserialize;
+
+use crate::jsonrpc::RpcMethod;
+use crate::routing::best_pattern_match;
+
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 32 * 1024 * 1024; // 32 MiB
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MethodSizeLimit {
+    pub method: String,
+    pub max_bytes: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseSizeLimits {
+    default: usize,
+    limits: Vec<MethodSizeLimit>,
+}
+
+impl Default for ResponseSizeLimits {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_RESPONSE_BYTES, Vec::new())
+    }
+}
+
+impl ResponseSizeLimits {
+    pub fn new(default: usize, limits: Vec<MethodSizeLimit>) -> Self {
+        Self { default, limits }
+    }
+
+    pub fn from_json(default: usize, raw: &str) -> Result<Self, serde_json::Error> {
+        Ok(Self::new(default, serde_json::from_str(raw)?))
+    }
+
+    pub fn with_default(mut self, default: usize) -> Self {
+        self.default = default;
+        self
+    }
+
+    pub fn default_limit(&self) -> usize {
+        self.default
+    }
+
+    pub fn limit_for(&self, method: &str) -> usize {
+        best_pattern_match(&self.limits, |limit| &limit.method, method)
+            .map(|idx| self.limits[idx].max_bytes)
+            .unwrap_or(self.default)
+    }
+
+    // A batch response may be as large as its most generous member allows.
+    pub fn for_method(&self, method: &RpcMethod) -> usize {
+        match method {
+            RpcMethod::Single(name) => self.limit_for(name),
+            RpcMethod::Batch(names) => names
+                .iter()
+                .map(|name| {
+                    name.as_deref()
+                        .map_or(self.default, |name| self.limit_for(name))
+                })
+                .max()
+                .unwrap_or(self.default),
+            RpcMethod::Invalid => self.default,
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::time::Instant;
+
+use actix_web::{HttpResponse, ResponseError};
+use tracing::{field, warn};
+
+use crate::{app_state::AppState, errors::AppError, proxy::ProxyRequest};
+
+// Log target of the slow-request warnings, kept apart from the access log.
+pub const TARGET: &str = "slow_request";
+
+// Warns when a proxied call took longer than `SLOW_REQUEST_THRESHOLD_MS`,
+// whether it ended in an answer or an error. The last validator asked is
+// charged with it.
+pub(crate) fn check(
+    state: &AppState,
+    request: &ProxyRequest<'_>,
+    started: Instant,
+    result: &Result<HttpResponse, AppError>,
+) {
+    let Some(threshold) = state.settings().slow_request_threshold else {
+        return;
+    };
+    let elapsed = started.elapsed();
+    if elapsed < threshold {
+        return;
+    }
+
+    let attempted = request.attempted();
+    let upstream = request.last_upstream();
+    if let Some(attempt) = &upstream {
+        state
+            .stats()
+            .validator(&attempt.validator)
+            .record_slow_request();
+    }
+
+    let status = match result {
+        Ok(response) => response.status(),
+        Err(err) => err.status_code(),
+    };
+    warn!(
+        target: TARGET,
+        validator = upstream.as_ref().map(|attempt| attempt.validator.as_str()),
+        rpc_method = request.rpc_method.as_ref().map(field::display),
+        duration_ms = elapsed.as_millis() as u64,
+        threshold_ms = threshold.as_millis() as u64,
+        upstream_ms = upstream
+            .as_ref()
+            .map(|attempt| attempt.elapsed.as_millis() as u64),
+        request_bytes = request.body.len(),
+        status = status.as_u16(),
+        failed = result.is_err() || !status.is_success(),
+        attempt = attempted.len().saturating_sub(1),
+        attempted = (attempted.len() > 1).then(|| attempted.join(",")),
+        request_id = request.request_id.as_ref().map(field::display),
+        "slow request"
+    );
+}
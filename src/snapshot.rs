@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::app_state::AppState;
+use crate::stats::ValidatorSnapshot;
+
+// Per-validator stats saved to `STATS_SNAPSHOT_PATH`. Unknown fields are
+// ignored and missing ones default, so older and newer files both load.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatsSnapshot {
+    pub saved_at_unix_ms: u64,
+    pub validators: BTreeMap<String, ValidatorSnapshot>,
+    // Requests per API key id and UTC day, for quotas.
+    pub quotas: BTreeMap<String, BTreeMap<u64, u64>>,
+}
+
+impl StatsSnapshot {
+    pub fn capture(state: &AppState) -> Self {
+        Self {
+            saved_at_unix_ms: unix_ms(),
+            validators: state.stats().validator_snapshots(),
+            quotas: state.stats().quota_days(),
+        }
+    }
+
+    pub fn age(&self) -> Duration {
+        Duration::from_millis(unix_ms().saturating_sub(self.saved_at_unix_ms))
+    }
+}
+
+// Writes next to `path` and renames over it, so a crash mid-write never
+// leaves a truncated snapshot behind.
+pub fn save(state: &AppState, path: &Path) -> io::Result<()> {
+    let body = serde_json::to_vec(&StatsSnapshot::capture(state))?;
+    let tmp = tmp_path(path);
+    let mut file = File::create(&tmp)?;
+    file.write_all(&body)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+// Best effort: a missing, unreadable, corrupt or stale file yields `None`.
+pub fn load(path: &Path, max_age: Duration) -> Option<StatsSnapshot> {
+    let snapshot = read(path)?;
+    let age = snapshot.age();
+    if age > max_age {
+        info!(
+            path = %path.display(),
+            age_secs = age.as_secs(),
+            "ignoring stale stats snapshot"
+        );
+        return None;
+    }
+    Some(snapshot)
+}
+
+// Like `load`, whatever the snapshot's age.
+pub fn read(path: &Path) -> Option<StatsSnapshot> {
+    let body = match fs::read(path) {
+        Ok(body) => body,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+        Err(err) => {
+            warn!(path = %path.display(), error = %err, "could not read stats snapshot");
+            return None;
+        }
+    };
+    match serde_json::from_slice(&body) {
+        Ok(snapshot) => Some(snapshot),
+        Err(err) => {
+            warn!(path = %path.display(), error = %err, "ignoring corrupt stats snapshot");
+            None
+        }
+    }
+}
+
+// Validators no longer in the registry are skipped. Quota counts are dated,
+// so they are restored from snapshots of any age; see `restore_on_startup`.
+pub fn restore(state: &AppState, snapshot: &StatsSnapshot) -> usize {
+    state.stats().restore_quotas(&snapshot.quotas);
+    let mut restored = 0;
+    for validator in state.registry().validators() {
+        if let Some(saved) = snapshot.validators.get(validator.name()) {
+            state.stats().validator(validator.name()).restore(saved);
+            restored += 1;
+        }
+    }
+    restored
+}
+
+// Called once before the server starts taking traffic.
+pub fn restore_on_startup(state: &AppState) {
+    let settings = state.settings();
+    let Some(path) = &settings.stats_snapshot_path else {
+        return;
+    };
+    let Some(snapshot) = read(path) else {
+        return;
+    };
+    let age = snapshot.age();
+    if age > settings.stats_snapshot_max_age {
+        state.stats().restore_quotas(&snapshot.quotas);
+        info!(
+            path = %path.display(),
+            age_secs = age.as_secs(),
+            keys = snapshot.quotas.len(),
+            "restored key quotas from a stale stats snapshot"
+        );
+        return;
+    }
+    let validators = restore(state, &snapshot);
+    info!(path = %path.display(), validators, "restored stats snapshot");
+}
+
+// Saves to `STATS_SNAPSHOT_PATH`, if set; failures are only logged.
+pub fn persist(state: &AppState) {
+    if let Some(path) = &state.settings().stats_snapshot_path
+        && let Err(err) = save(state, path)
+    {
+        warn!(path = %path.display(), error = %err, "could not save stats snapshot");
+    }
+}
+
+pub fn spawn_persist(state: AppState) {
+    let period = state.settings().stats_snapshot_interval;
+    if state.settings().stats_snapshot_path.is_none() || period.is_zero() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        // The first tick fires at once; nothing new to save yet.
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let state = state.clone();
+            let _ = tokio::task::spawn_blocking(move || persist(&state)).await;
+        }
+    });
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,896 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use actix_web::http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::error_window::{self, ByteRates, ErrorCategory, ErrorRates, ErrorWindow, MinuteCounts};
+use crate::errors::UpstreamErrorKind;
+use crate::histogram::{self, HistogramSnapshot, HistogramSummary, LatencyHistogram};
+use crate::jsonrpc::RpcMethod;
+use crate::key_usage::{self, KeyUsageReport, KeyUsageTable, UsageSummary};
+use crate::quota::{QuotaTable, QuotaUsage, RequestQuota};
+use crate::rate_limit::KeyRateLimit;
+
+pub const OTHER_METHOD: &str = "other";
+pub const BATCH_METHOD: &str = "batch";
+
+const DEFAULT_METHOD_CAP: usize = 128;
+// Latency samples kept per validator for the percentiles in `/stats`.
+const LATENCY_WINDOW: usize = 1024;
+
+type MethodTable = HashMap<String, HashMap<String, Arc<MethodStats>>>;
+type ShadowTable = HashMap<(String, String), ShadowCounts>;
+
+#[derive(Debug)]
+pub struct StatsStore {
+    validators: RwLock<HashMap<String, Arc<ValidatorStats>>>,
+    methods: RwLock<MethodTable>,
+    method_cap: usize,
+    latency_buckets: Vec<f64>,
+    shadow: Mutex<ShadowTable>,
+    keys: Mutex<KeyUsageTable>,
+    quotas: Mutex<QuotaTable>,
+    // Requests whose handler panicked; never reset, like a process counter.
+    panics: AtomicU64,
+}
+
+impl Default for StatsStore {
+    fn default() -> Self {
+        Self::with_method_cap(DEFAULT_METHOD_CAP)
+    }
+}
+
+impl StatsStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_method_cap(method_cap: usize) -> Self {
+        Self {
+            validators: RwLock::new(HashMap::new()),
+            methods: RwLock::new(HashMap::new()),
+            method_cap,
+            latency_buckets: histogram::DEFAULT_BUCKETS_MS.to_vec(),
+            shadow: Mutex::new(HashMap::new()),
+            keys: Mutex::default(),
+            quotas: Mutex::default(),
+            panics: AtomicU64::new(0),
+        }
+    }
+
+    // Applies to validators first seen after the call.
+    pub fn with_latency_buckets(mut self, bounds_ms: &[f64]) -> Self {
+        self.latency_buckets = bounds_ms.to_vec();
+        self
+    }
+
+    pub fn with_key_usage(self, keys: KeyUsageTable) -> Self {
+        Self {
+            keys: Mutex::new(keys),
+            ..self
+        }
+    }
+
+    pub fn record_call(
+        &self,
+        method: &RpcMethod,
+        validator: &str,
+        latency: Duration,
+        success: bool,
+    ) {
+        self.method(method, validator).record(latency, success);
+    }
+
+    // Names past the cap share the `other` bucket so arbitrary client input
+    // cannot grow the table without bound.
+    fn method(&self, method: &RpcMethod, validator: &str) -> Arc<MethodStats> {
+        let name = match method {
+            RpcMethod::Single(name) => name.as_str(),
+            RpcMethod::Batch(_) => BATCH_METHOD,
+            RpcMethod::Invalid => OTHER_METHOD,
+        };
+
+        if let Some(stats) = self
+            .methods
+            .read()
+            .expect("stats lock poisoned")
+            .get(name)
+            .and_then(|validators| validators.get(validator))
+        {
+            return Arc::clone(stats);
+        }
+
+        let mut methods = self.methods.write().expect("stats lock poisoned");
+        let name = if methods.contains_key(name) || methods.len() < self.method_cap {
+            name
+        } else {
+            OTHER_METHOD
+        };
+        Arc::clone(
+            methods
+                .entry(name.to_string())
+                .or_default()
+                .entry(validator.to_string())
+                .or_default(),
+        )
+    }
+
+    pub fn method_snapshots(&self) -> Vec<MethodStatsSnapshot> {
+        let methods = self.methods.read().expect("stats lock poisoned");
+        let mut snapshots: Vec<_> = methods
+            .iter()
+            .flat_map(|(method, validators)| {
+                validators
+                    .iter()
+                    .map(|(validator, stats)| stats.snapshot(method, validator))
+            })
+            .collect();
+        snapshots.sort_by(|a, b| (&a.method, &a.validator).cmp(&(&b.method, &b.validator)));
+        snapshots
+    }
+
+    // Keyed by (primary, shadow) pair, which is bounded by the registry size.
+    pub fn record_shadow_check(&self, primary: &str, shadow: &str, diverged: bool) {
+        let mut table = self.shadow.lock().expect("stats lock poisoned");
+        let counts = table
+            .entry((primary.to_string(), shadow.to_string()))
+            .or_default();
+        counts.checks += 1;
+        if diverged {
+            counts.divergences += 1;
+        }
+    }
+
+    pub fn shadow_snapshots(&self) -> Vec<ShadowPairSnapshot> {
+        let table = self.shadow.lock().expect("stats lock poisoned");
+        let mut snapshots: Vec<_> = table
+            .iter()
+            .map(|((primary, shadow), counts)| ShadowPairSnapshot {
+                primary: primary.clone(),
+                shadow: shadow.clone(),
+                checks: counts.checks,
+                divergences: counts.divergences,
+            })
+            .collect();
+        snapshots.sort_by(|a, b| (&a.primary, &a.shadow).cmp(&(&b.primary, &b.shadow)));
+        snapshots
+    }
+
+    pub fn record_key_usage(
+        &self,
+        key_id: &str,
+        method: &str,
+        success: bool,
+        request_bytes: u64,
+        response_bytes: u64,
+    ) {
+        self.keys.lock().expect("stats lock poisoned").record(
+            key_id,
+            method,
+            success,
+            request_bytes,
+            response_bytes,
+            key_usage::now_secs(),
+        );
+    }
+
+    pub fn key_usage(&self, key_id: &str) -> Option<KeyUsageReport> {
+        self.keys
+            .lock()
+            .expect("stats lock poisoned")
+            .usage(key_id, key_usage::now_secs())
+    }
+
+    pub fn key_metric_totals(&self) -> Vec<(String, UsageSummary)> {
+        self.keys
+            .lock()
+            .expect("stats lock poisoned")
+            .metric_totals()
+    }
+
+    // Counts a request against the key's daily and monthly totals, unless
+    // its quota is used up.
+    pub fn consume_quota(
+        &self,
+        key_id: &str,
+        quota: Option<RequestQuota>,
+    ) -> Result<QuotaUsage, QuotaUsage> {
+        self.quotas.lock().expect("stats lock poisoned").consume(
+            key_id,
+            quota,
+            key_usage::now_secs(),
+        )
+    }
+
+    pub fn quota_usage(&self, key_id: &str, quota: Option<RequestQuota>) -> QuotaUsage {
+        self.quotas
+            .lock()
+            .expect("stats lock poisoned")
+            .usage(key_id, quota, key_usage::now_secs())
+    }
+
+    pub fn is_quota_tracked(&self, key_id: &str) -> bool {
+        self.quotas
+            .lock()
+            .expect("stats lock poisoned")
+            .is_tracked(key_id)
+    }
+
+    pub fn quota_days(&self) -> BTreeMap<String, BTreeMap<u64, u64>> {
+        self.quotas.lock().expect("stats lock poisoned").days()
+    }
+
+    pub fn restore_quotas(&self, saved: &BTreeMap<String, BTreeMap<u64, u64>>) {
+        self.quotas
+            .lock()
+            .expect("stats lock poisoned")
+            .restore(saved);
+    }
+
+    pub fn validator(&self, name: &str) -> Arc<ValidatorStats> {
+        if let Some(stats) = self
+            .validators
+            .read()
+            .expect("stats lock poisoned")
+            .get(name)
+        {
+            return Arc::clone(stats);
+        }
+
+        let mut validators = self.validators.write().expect("stats lock poisoned");
+        Arc::clone(
+            validators
+                .entry(name.to_string())
+                .or_insert_with(|| Arc::new(ValidatorStats::new(&self.latency_buckets))),
+        )
+    }
+
+    // Clears the counters behind `/stats`. Live state (cooldowns, slots,
+    // method capabilities, in-flight requests) is kept since routing uses it,
+    // and so are key quotas, which are enforced.
+    pub fn record_panic(&self) {
+        self.panics.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn panics(&self) -> u64 {
+        self.panics.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.methods.write().expect("stats lock poisoned").clear();
+        self.shadow.lock().expect("stats lock poisoned").clear();
+        self.keys.lock().expect("stats lock poisoned").clear();
+        for stats in self
+            .validators
+            .read()
+            .expect("stats lock poisoned")
+            .values()
+        {
+            stats.reset();
+        }
+    }
+
+    pub fn validator_snapshots(&self) -> BTreeMap<String, ValidatorSnapshot> {
+        self.validators
+            .read()
+            .expect("stats lock poisoned")
+            .iter()
+            .map(|(name, stats)| (name.clone(), stats.snapshot()))
+            .collect()
+    }
+
+    pub fn highest_slot(&self) -> Option<u64> {
+        self.validators
+            .read()
+            .expect("stats lock poisoned")
+            .values()
+            .filter_map(|stats| stats.latest_slot())
+            .max()
+    }
+}
+
+// How long a validator is avoided for a method it reported as unavailable.
+const CAPABILITY_MISS_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    // No response: connection refused or reset.
+    Unavailable,
+    Timeout,
+    RateLimited,
+    // Any other non-2xx status.
+    HttpStatus,
+    // The remaining kinds reject a 2xx answer once its body was read.
+    InvalidResponse,
+    TooLarge,
+    Unreadable,
+}
+
+impl FailureKind {
+    const COUNT: usize = 7;
+}
+
+// Upstream attempts that get a response; `successes` are the 2xx ones.
+#[derive(Debug, Default)]
+struct Traffic {
+    requests: AtomicU64,
+    successes: AtomicU64,
+    failures: [AtomicU64; FailureKind::COUNT],
+    // The same failures by `UpstreamErrorKind`, for the metrics.
+    errors: [AtomicU64; UpstreamErrorKind::COUNT],
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    slow_requests: AtomicU64,
+    latencies: Mutex<LatencyWindow>,
+    last_error: Mutex<Option<LastError>>,
+}
+
+// The most recent `LATENCY_WINDOW` samples, in microseconds.
+#[derive(Debug, Default)]
+struct LatencyWindow {
+    samples: Vec<u64>,
+    next: usize,
+}
+
+impl LatencyWindow {
+    fn push(&mut self, micros: u64) {
+        if self.samples.len() < LATENCY_WINDOW {
+            self.samples.push(micros);
+        } else {
+            self.samples[self.next] = micros;
+        }
+        self.next = (self.next + 1) % LATENCY_WINDOW;
+    }
+
+    // Oldest first.
+    fn samples(&self) -> Vec<u64> {
+        self.samples[self.next..]
+            .iter()
+            .chain(&self.samples[..self.next])
+            .copied()
+            .collect()
+    }
+
+    fn summary(&self) -> LatencySummary {
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        // Nearest-rank percentile.
+        let percentile = |p: f64| {
+            let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+            sorted
+                .get(rank.saturating_sub(1))
+                .map(|micros| *micros as f64 / 1000.0)
+        };
+        LatencySummary {
+            samples: sorted.len(),
+            p50: percentile(50.0),
+            p95: percentile(95.0),
+            p99: percentile(99.0),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ValidatorStats {
+    cooldown_until: Mutex<Option<Instant>>,
+    unsupported_methods: RwLock<HashMap<String, Instant>>,
+    latest_slot: AtomicU64,
+    in_flight: AtomicU64,
+    traffic: Traffic,
+    latency_histogram: LatencyHistogram,
+    error_window: Mutex<ErrorWindow>,
+    // Outbound token bucket: tokens left, and when they were counted.
+    outbound: Mutex<Option<(f64, Instant)>>,
+}
+
+// Counts an upstream call as in flight until dropped.
+// Byte totals stick at `u64::MAX` rather than wrapping to zero.
+fn add_saturating(counter: &AtomicU64, amount: u64) {
+    let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+        Some(current.saturating_add(amount))
+    });
+}
+
+pub struct InFlight(Arc<ValidatorStats>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl ValidatorStats {
+    pub fn new(latency_buckets_ms: &[f64]) -> Self {
+        Self {
+            latency_histogram: LatencyHistogram::new(latency_buckets_ms),
+            ..Self::default()
+        }
+    }
+
+    pub fn cool_down(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        let mut current = self.cooldown_until.lock().expect("cooldown lock poisoned");
+        if current.is_none_or(|existing| existing < until) {
+            *current = Some(until);
+        }
+    }
+
+    pub fn cooldown_remaining(&self) -> Option<Duration> {
+        let current = self.cooldown_until.lock().expect("cooldown lock poisoned");
+        current
+            .and_then(|until| until.checked_duration_since(Instant::now()))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    pub fn is_cooling_down(&self) -> bool {
+        self.cooldown_remaining().is_some()
+    }
+
+    // Takes a token from the validator's outbound bucket, or says how long
+    // until one is due.
+    pub fn take_outbound(&self, limit: KeyRateLimit) -> Result<(), Duration> {
+        let now = Instant::now();
+        let capacity = f64::from(limit.burst);
+        let mut bucket = self.outbound.lock().expect("outbound lock poisoned");
+        let (tokens, counted_at) = bucket.get_or_insert((capacity, now));
+        let elapsed = now.saturating_duration_since(*counted_at).as_secs_f64();
+        *tokens = (*tokens + elapsed * limit.requests_per_second).min(capacity);
+        *counted_at = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - *tokens) / limit.requests_per_second,
+            ))
+        }
+    }
+
+    pub fn observe_slot(&self, slot: u64) {
+        self.latest_slot.fetch_max(slot, Ordering::Relaxed);
+    }
+
+    pub fn latest_slot(&self) -> Option<u64> {
+        Some(self.latest_slot.load(Ordering::Relaxed)).filter(|slot| *slot > 0)
+    }
+
+    pub fn begin(self: &Arc<Self>, bytes_sent: usize) -> InFlight {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        add_saturating(&self.traffic.bytes_sent, bytes_sent as u64);
+        self.record_in_window(|window, minute| window.record_bytes(minute, bytes_sent as u64, 0));
+        InFlight(Arc::clone(self))
+    }
+
+    pub fn record_response(&self, status: StatusCode, latency: Duration) {
+        let traffic = &self.traffic;
+        traffic.requests.fetch_add(1, Ordering::Relaxed);
+        self.latency_histogram.record(latency);
+        self.record_in_window(|window, minute| {
+            window.record_request(minute);
+            if status.is_server_error() {
+                window.record_error(minute, ErrorCategory::ServerError);
+            }
+        });
+        traffic
+            .latencies
+            .lock()
+            .expect("stats lock poisoned")
+            .push(latency.as_micros().min(u64::MAX as u128) as u64);
+
+        match UpstreamErrorKind::from_status(status) {
+            Some(kind) => self.record_failure(kind, format!("upstream returned {status}")),
+            None => {
+                traffic.successes.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // A send that got no response at all: connect errors and timeouts.
+    pub fn record_no_response(&self, kind: UpstreamErrorKind, message: impl Into<String>) {
+        self.traffic.requests.fetch_add(1, Ordering::Relaxed);
+        self.record_in_window(|window, minute| window.record_request(minute));
+        self.record_failure(kind, message);
+    }
+
+    fn record_in_window(&self, update: impl FnOnce(&mut ErrorWindow, u64)) {
+        let mut window = self.error_window.lock().expect("stats lock poisoned");
+        update(&mut window, error_window::current_minute());
+    }
+
+    // Connect, timeout, 5xx and body-limit errors over the last minutes.
+    pub fn error_rates(&self) -> ErrorRates {
+        self.error_window
+            .lock()
+            .expect("stats lock poisoned")
+            .rates(error_window::current_minute())
+    }
+
+    pub fn requests_last_minute(&self) -> u64 {
+        self.error_window
+            .lock()
+            .expect("stats lock poisoned")
+            .requests_last_minute(error_window::current_second())
+    }
+
+    pub fn requests_total(&self) -> u64 {
+        self.traffic.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn record_slow_request(&self) {
+        self.traffic.slow_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Called per chunk for streamed bodies.
+    pub fn record_received(&self, bytes: usize) {
+        add_saturating(&self.traffic.bytes_received, bytes as u64);
+        self.record_in_window(|window, minute| window.record_bytes(minute, 0, bytes as u64));
+    }
+
+    // A 2xx answer turned down after reading its body moves from the
+    // successes to the failures.
+    pub fn reject_response(&self, kind: UpstreamErrorKind, message: impl Into<String>) {
+        let _ = self
+            .traffic
+            .successes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+        self.record_failure(kind, message);
+    }
+
+    fn record_failure(&self, error: UpstreamErrorKind, message: impl Into<String>) {
+        let kind = error.failure();
+        self.traffic.failures[kind as usize].fetch_add(1, Ordering::Relaxed);
+        self.traffic.errors[error.index()].fetch_add(1, Ordering::Relaxed);
+        let category = match kind {
+            FailureKind::Unavailable => Some(ErrorCategory::Connect),
+            FailureKind::Timeout => Some(ErrorCategory::Timeout),
+            FailureKind::TooLarge => Some(ErrorCategory::BodyLimit),
+            _ => None,
+        };
+        if let Some(category) = category {
+            self.record_in_window(|window, minute| window.record_error(minute, category));
+        }
+        *self.traffic.last_error.lock().expect("stats lock poisoned") = Some(LastError {
+            kind,
+            message: message.into(),
+            at_unix_ms: unix_ms(),
+        });
+    }
+
+    pub fn invalid_responses(&self) -> u64 {
+        self.failures(FailureKind::InvalidResponse)
+    }
+
+    pub fn failures(&self, kind: FailureKind) -> u64 {
+        self.traffic.failures[kind as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn upstream_errors(&self, kind: UpstreamErrorKind) -> u64 {
+        self.traffic.errors[kind.index()].load(Ordering::Relaxed)
+    }
+
+    // Metric labels with their counts; not kept in snapshots.
+    pub fn upstream_errors_by_kind(&self) -> [(&'static str, u64); UpstreamErrorKind::COUNT] {
+        std::array::from_fn(|idx| {
+            (
+                UpstreamErrorKind::LABELS[idx],
+                self.traffic.errors[idx].load(Ordering::Relaxed),
+            )
+        })
+    }
+
+    pub fn latency_histogram(&self) -> HistogramSnapshot {
+        self.latency_histogram.snapshot()
+    }
+
+    pub fn in_flight(&self) -> u64 {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    pub fn traffic(&self) -> TrafficSnapshot {
+        let traffic = &self.traffic;
+        let failure = |kind| self.failures(kind);
+        TrafficSnapshot {
+            requests: traffic.requests.load(Ordering::Relaxed),
+            successes: traffic.successes.load(Ordering::Relaxed),
+            failures: FailureCounts {
+                unavailable: failure(FailureKind::Unavailable),
+                timeout: failure(FailureKind::Timeout),
+                rate_limited: failure(FailureKind::RateLimited),
+                http_status: failure(FailureKind::HttpStatus),
+                invalid_response: failure(FailureKind::InvalidResponse),
+                too_large: failure(FailureKind::TooLarge),
+                unreadable: failure(FailureKind::Unreadable),
+            },
+            latency_ms: traffic
+                .latencies
+                .lock()
+                .expect("stats lock poisoned")
+                .summary(),
+            latency_histogram_ms: self.latency_histogram.snapshot().summary(),
+            bytes_sent: traffic.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: traffic.bytes_received.load(Ordering::Relaxed),
+            bytes_per_sec: self
+                .error_window
+                .lock()
+                .expect("stats lock poisoned")
+                .byte_rates(error_window::current_minute()),
+            slow_requests: traffic.slow_requests.load(Ordering::Relaxed),
+            in_flight: self.in_flight(),
+            last_error: traffic
+                .last_error
+                .lock()
+                .expect("stats lock poisoned")
+                .clone(),
+        }
+    }
+
+    fn reset(&self) {
+        let traffic = &self.traffic;
+        for counter in [
+            &traffic.requests,
+            &traffic.successes,
+            &traffic.bytes_sent,
+            &traffic.bytes_received,
+            &traffic.slow_requests,
+        ]
+        .into_iter()
+        .chain(&traffic.failures)
+        .chain(&traffic.errors)
+        {
+            counter.store(0, Ordering::Relaxed);
+        }
+        *traffic.latencies.lock().expect("stats lock poisoned") = LatencyWindow::default();
+        self.latency_histogram.reset();
+        *traffic.last_error.lock().expect("stats lock poisoned") = None;
+        self.error_window
+            .lock()
+            .expect("stats lock poisoned")
+            .reset();
+    }
+
+    pub fn snapshot(&self) -> ValidatorSnapshot {
+        let traffic = self.traffic();
+        ValidatorSnapshot {
+            requests: traffic.requests,
+            successes: traffic.successes,
+            failures: traffic.failures,
+            bytes_sent: traffic.bytes_sent,
+            bytes_received: traffic.bytes_received,
+            slow_requests: traffic.slow_requests,
+            latency_samples_us: self
+                .traffic
+                .latencies
+                .lock()
+                .expect("stats lock poisoned")
+                .samples(),
+            latency_histogram: Some(self.latency_histogram.snapshot()),
+            minutes: self
+                .error_window
+                .lock()
+                .expect("stats lock poisoned")
+                .minutes(),
+            latest_slot: self.latest_slot(),
+            cooldown_until_unix_ms: self
+                .cooldown_remaining()
+                .map(|remaining| unix_ms() + remaining.as_millis() as u64),
+            last_error: traffic.last_error,
+        }
+    }
+
+    // Replaces the counters with a saved snapshot, for stats that have not
+    // seen traffic yet. A histogram saved with other buckets is dropped.
+    pub fn restore(&self, snapshot: &ValidatorSnapshot) {
+        let traffic = &self.traffic;
+        for (counter, value) in [
+            (&traffic.requests, snapshot.requests),
+            (&traffic.successes, snapshot.successes),
+            (&traffic.bytes_sent, snapshot.bytes_sent),
+            (&traffic.bytes_received, snapshot.bytes_received),
+            (&traffic.slow_requests, snapshot.slow_requests),
+        ] {
+            counter.store(value, Ordering::Relaxed);
+        }
+        for (counter, (_, value)) in traffic.failures.iter().zip(snapshot.failures.by_kind()) {
+            counter.store(value, Ordering::Relaxed);
+        }
+        let mut latencies = LatencyWindow::default();
+        for micros in &snapshot.latency_samples_us {
+            latencies.push(*micros);
+        }
+        *traffic.latencies.lock().expect("stats lock poisoned") = latencies;
+        if let Some(histogram) = &snapshot.latency_histogram {
+            self.latency_histogram.restore(histogram);
+        }
+        self.error_window
+            .lock()
+            .expect("stats lock poisoned")
+            .restore(&snapshot.minutes);
+        if let Some(slot) = snapshot.latest_slot {
+            self.observe_slot(slot);
+        }
+        if let Some(until) = snapshot.cooldown_until_unix_ms
+            && let Some(remaining) = until.checked_sub(unix_ms()).filter(|ms| *ms > 0)
+        {
+            self.cool_down(Duration::from_millis(remaining));
+        }
+        *traffic.last_error.lock().expect("stats lock poisoned") = snapshot.last_error.clone();
+    }
+
+    pub fn mark_unsupported(&self, method: &str) {
+        self.unsupported_methods
+            .write()
+            .expect("capability lock poisoned")
+            .insert(method.to_string(), Instant::now() + CAPABILITY_MISS_TTL);
+    }
+
+    pub fn supports(&self, method: &str) -> bool {
+        self.unsupported_methods
+            .read()
+            .expect("capability lock poisoned")
+            .get(method)
+            .is_none_or(|until| *until <= Instant::now())
+    }
+
+    pub fn unsupported_methods(&self) -> Vec<String> {
+        let now = Instant::now();
+        let mut methods: Vec<String> = self
+            .unsupported_methods
+            .read()
+            .expect("capability lock poisoned")
+            .iter()
+            .filter(|(_, until)| **until > now)
+            .map(|(method, _)| method.clone())
+            .collect();
+        methods.sort();
+        methods
+    }
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Default)]
+pub struct MethodStats {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    latency_total_us: AtomicU64,
+    latency_max_us: AtomicU64,
+}
+
+impl MethodStats {
+    pub fn record(&self, latency: Duration, success: bool) {
+        let micros = latency.as_micros().min(u64::MAX as u128) as u64;
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if !success {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.latency_total_us.fetch_add(micros, Ordering::Relaxed);
+        self.latency_max_us.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self, method: &str, validator: &str) -> MethodStatsSnapshot {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let total = self.latency_total_us.load(Ordering::Relaxed);
+        MethodStatsSnapshot {
+            method: method.to_string(),
+            validator: validator.to_string(),
+            requests,
+            errors: self.errors.load(Ordering::Relaxed),
+            mean_latency_ms: if requests == 0 {
+                0.0
+            } else {
+                total as f64 / requests as f64 / 1000.0
+            },
+            max_latency_ms: self.latency_max_us.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MethodStatsSnapshot {
+    pub method: String,
+    pub validator: String,
+    pub requests: u64,
+    pub errors: u64,
+    pub mean_latency_ms: f64,
+    pub max_latency_ms: f64,
+}
+
+#[derive(Debug, Default)]
+struct ShadowCounts {
+    checks: u64,
+    divergences: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ShadowPairSnapshot {
+    pub primary: String,
+    pub shadow: String,
+    pub checks: u64,
+    pub divergences: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrafficSnapshot {
+    pub requests: u64,
+    pub successes: u64,
+    pub failures: FailureCounts,
+    pub latency_ms: LatencySummary,
+    pub latency_histogram_ms: HistogramSummary,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub bytes_per_sec: ByteRates,
+    pub slow_requests: u64,
+    pub in_flight: u64,
+    pub last_error: Option<LastError>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailureCounts {
+    pub unavailable: u64,
+    pub timeout: u64,
+    pub rate_limited: u64,
+    pub http_status: u64,
+    pub invalid_response: u64,
+    pub too_large: u64,
+    pub unreadable: u64,
+}
+
+impl FailureCounts {
+    // Serialized names with their counts.
+    pub fn by_kind(&self) -> [(&'static str, u64); FailureKind::COUNT] {
+        [
+            ("unavailable", self.unavailable),
+            ("timeout", self.timeout),
+            ("rate_limited", self.rate_limited),
+            ("http_status", self.http_status),
+            ("invalid_response", self.invalid_response),
+            ("too_large", self.too_large),
+            ("unreadable", self.unreadable),
+        ]
+    }
+}
+
+// Percentiles over the latency window; `None` until a response arrived.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub samples: usize,
+    pub p50: Option<f64>,
+    pub p95: Option<f64>,
+    pub p99: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastError {
+    pub kind: FailureKind,
+    pub message: String,
+    pub at_unix_ms: u64,
+}
+
+// What a stats snapshot keeps of one validator. Every field has a default
+// so snapshots written before a field existed still load.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValidatorSnapshot {
+    pub requests: u64,
+    pub successes: u64,
+    pub failures: FailureCounts,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub slow_requests: u64,
+    pub latency_samples_us: Vec<u64>,
+    pub latency_histogram: Option<HistogramSnapshot>,
+    pub minutes: Vec<MinuteCounts>,
+    pub latest_slot: Option<u64>,
+    pub cooldown_until_unix_ms: Option<u64>,
+    pub last_error: Option<LastError>,
+}
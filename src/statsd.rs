@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use crate::app_state::AppState;
+use crate::config::Settings;
+use crate::stats::{FailureCounts, TrafficSnapshot};
+
+// Keeps batched datagrams under a typical MTU.
+const MAX_DATAGRAM: usize = 1432;
+const ERROR_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+// DogStatsD over UDP. Sends never block: the socket is non-blocking and a
+// full buffer or unreachable agent only drops the metric.
+pub struct Statsd {
+    socket: UdpSocket,
+    prefix: String,
+    tags: Vec<String>,
+    errors: Mutex<SendErrors>,
+    // Totals at the last flush, to turn the stats store's counters into
+    // StatsD count increments.
+    flushed: Mutex<HashMap<String, Totals>>,
+}
+
+#[derive(Default)]
+struct SendErrors {
+    last_logged: Option<Instant>,
+    suppressed: u64,
+}
+
+#[derive(Default, Clone)]
+struct Totals {
+    requests: u64,
+    successes: u64,
+    failures: FailureCounts,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+impl Totals {
+    fn from_traffic(traffic: &TrafficSnapshot) -> Self {
+        Self {
+            requests: traffic.requests,
+            successes: traffic.successes,
+            failures: traffic.failures.clone(),
+            bytes_sent: traffic.bytes_sent,
+            bytes_received: traffic.bytes_received,
+        }
+    }
+}
+
+impl Statsd {
+    pub fn from_settings(settings: &Settings) -> io::Result<Option<Self>> {
+        let Some(address) = &settings.statsd_address else {
+            return Ok(None);
+        };
+        let target = address.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no addresses found for {address}"),
+            )
+        })?;
+        let local: SocketAddr = if target.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.set_nonblocking(true)?;
+        socket.connect(target)?;
+        Ok(Some(Self {
+            socket,
+            prefix: settings.statsd_prefix.clone(),
+            tags: settings.statsd_tags.clone(),
+            errors: Mutex::default(),
+            flushed: Mutex::default(),
+        }))
+    }
+
+    pub fn timing(&self, name: &str, latency: Duration, validator: &str) {
+        let ms = latency.as_secs_f64() * 1000.0;
+        let line = self.line(name, &format!("{ms:.3}"), "ms", &[("validator", validator)]);
+        self.send(&line);
+    }
+
+    // Sends what the counters gained since the previous flush, plus
+    // in-flight gauges.
+    pub fn flush(&self, state: &AppState) {
+        let mut flushed = self.flushed.lock().expect("statsd lock poisoned");
+        let mut lines = Vec::new();
+        for validator in state.registry().validators() {
+            let name = validator.name();
+            let traffic = state.stats().validator(name).traffic();
+            let current = Totals::from_traffic(&traffic);
+            // A `/stats/reset` lowers the totals; count from the new base.
+            let previous = flushed
+                .insert(name.to_string(), current.clone())
+                .unwrap_or_default();
+            let delta = |now: u64, before: u64| now.saturating_sub(before);
+            let tags = [("validator", name)];
+
+            for (metric, value) in [
+                (
+                    "upstream.requests",
+                    delta(current.requests, previous.requests),
+                ),
+                (
+                    "upstream.successes",
+                    delta(current.successes, previous.successes),
+                ),
+                (
+                    "upstream.bytes_sent",
+                    delta(current.bytes_sent, previous.bytes_sent),
+                ),
+                (
+                    "upstream.bytes_received",
+                    delta(current.bytes_received, previous.bytes_received),
+                ),
+            ] {
+                if value > 0 {
+                    lines.push(self.line(metric, &value.to_string(), "c", &tags));
+                }
+            }
+            let failures = current.failures.by_kind().into_iter();
+            for ((kind, now), (_, before)) in failures.zip(previous.failures.by_kind()) {
+                let value = delta(now, before);
+                if value > 0 {
+                    lines.push(self.line(
+                        "upstream.failures",
+                        &value.to_string(),
+                        "c",
+                        &[("validator", name), ("kind", kind)],
+                    ));
+                }
+            }
+            lines.push(self.line(
+                "upstream.in_flight",
+                &traffic.in_flight.to_string(),
+                "g",
+                &tags,
+            ));
+        }
+        drop(flushed);
+        if let Some(limit) = state.concurrency_limit() {
+            lines.push(self.line(
+                "requests.in_flight",
+                &limit.in_flight().to_string(),
+                "g",
+                &[],
+            ));
+            lines.push(self.line("requests.queued", &limit.queued().to_string(), "g", &[]));
+        }
+
+        let mut datagram = String::new();
+        for line in lines {
+            if !datagram.is_empty() && datagram.len() + 1 + line.len() > MAX_DATAGRAM {
+                self.send(&datagram);
+                datagram.clear();
+            }
+            if !datagram.is_empty() {
+                datagram.push('\n');
+            }
+            datagram.push_str(&line);
+        }
+        if !datagram.is_empty() {
+            self.send(&datagram);
+        }
+    }
+
+    fn line(&self, name: &str, value: &str, kind: &str, tags: &[(&str, &str)]) -> String {
+        let mut line = format!("{}.{name}:{value}|{kind}", self.prefix);
+        let mut all = self
+            .tags
+            .iter()
+            .cloned()
+            .chain(
+                tags.iter()
+                    .map(|(key, value)| format!("{key}:{}", tag_value(value))),
+            )
+            .peekable();
+        if all.peek().is_some() {
+            line.push_str("|#");
+            line.push_str(&all.collect::<Vec<_>>().join(","));
+        }
+        line
+    }
+
+    fn send(&self, datagram: &str) {
+        let Err(err) = self.socket.send(datagram.as_bytes()) else {
+            return;
+        };
+        let mut errors = self.errors.lock().expect("statsd lock poisoned");
+        if errors
+            .last_logged
+            .is_some_and(|at| at.elapsed() < ERROR_LOG_INTERVAL)
+        {
+            errors.suppressed += 1;
+            return;
+        }
+        debug!(
+            error = %err,
+            suppressed = errors.suppressed,
+            "statsd send failed"
+        );
+        errors.last_logged = Some(Instant::now());
+        errors.suppressed = 0;
+    }
+}
+
+// Validator names end up in tags, where these characters are separators.
+fn tag_value(raw: &str) -> String {
+    raw.replace(['|', '#', ',', '\n'], "_")
+}
+
+pub fn spawn_flush(state: AppState, statsd: Arc<Statsd>) {
+    let period = state.settings().statsd_interval;
+    if period.is_zero() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            statsd.flush(&state);
+        }
+    });
+}
@@ -0,0 +1,88 @@
+use std::str::FromStr;
+
+use actix_web::http::StatusCode;
+
+// Upstream body bytes kept in a wrapped error.
+pub const EXCERPT_BYTES: usize = 256;
+
+// What becomes of an upstream answer with a status of 400 or more.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatusPolicy {
+    // Relayed as received.
+    #[default]
+    Passthrough,
+    // Replaced by our own error body, code `upstream_bad_status`.
+    Wrap,
+}
+
+impl FromStr for StatusPolicy {
+    type Err = ();
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "passthrough" => Ok(Self::Passthrough),
+            "wrap" => Ok(Self::Wrap),
+            _ => Err(()),
+        }
+    }
+}
+
+// `UPSTREAM_ERROR_POLICY`, with per-status exceptions such as `429:passthrough`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatusPolicies {
+    default: StatusPolicy,
+    overrides: Vec<(StatusCode, StatusPolicy)>,
+}
+
+impl StatusPolicies {
+    pub fn new(default: StatusPolicy, overrides: Vec<(StatusCode, StatusPolicy)>) -> Self {
+        Self { default, overrides }
+    }
+
+    // `status:policy` pairs, comma separated; `None` if one is malformed.
+    pub fn parse_overrides(raw: &str) -> Option<Vec<(StatusCode, StatusPolicy)>> {
+        raw.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| {
+                let (status, policy) = item.split_once(':')?;
+                let status = status
+                    .trim()
+                    .parse::<u16>()
+                    .ok()
+                    .and_then(|code| StatusCode::from_u16(code).ok())
+                    .filter(|status| status.as_u16() >= 400)?;
+                Some((status, policy.parse().ok()?))
+            })
+            .collect()
+    }
+
+    pub fn default_policy(&self) -> StatusPolicy {
+        self.default
+    }
+
+    // Answers below 400 are always relayed.
+    pub fn for_status(&self, status: StatusCode) -> StatusPolicy {
+        if status.as_u16() < 400 {
+            return StatusPolicy::Passthrough;
+        }
+        self.overrides
+            .iter()
+            .find(|(code, _)| *code == status)
+            .map_or(self.default, |(_, policy)| *policy)
+    }
+
+    pub fn wraps(&self, status: StatusCode) -> bool {
+        self.for_status(status) == StatusPolicy::Wrap
+    }
+}
+
+// The start of an upstream body as text, marked when cut short.
+pub fn excerpt(body: &[u8]) -> String {
+    let cut = body.len().min(EXCERPT_BYTES);
+    let mut text = String::from_utf8_lossy(&body[..cut]).trim().to_string();
+    if body.len() > cut {
+        text.push('…');
+    }
+    text
+}
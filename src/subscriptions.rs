@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+// Bookkeeping for sharing upstream pubsub subscriptions between clients.
+// It does no I/O: the WebSocket relay opens and closes upstream
+// subscriptions when told to, and routes notifications through `fan_out`.
+
+pub type ClientId = u64;
+
+// Identical subscriptions share one upstream subscription. Params carry the
+// commitment; object keys serialize sorted, so equal configs compare equal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SubscriptionKey {
+    method: String,
+    params: String,
+    validator: Option<String>,
+}
+
+impl SubscriptionKey {
+    pub fn new(method: &str, params: &Value, validator: Option<&str>) -> Self {
+        Self {
+            method: method.to_string(),
+            params: params.to_string(),
+            validator: validator.map(|name| name.trim().to_ascii_lowercase()),
+        }
+    }
+
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+}
+
+// A client's handle on a shared subscription.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Member {
+    pub client: ClientId,
+    pub subscription: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attach {
+    // First member: the caller opens the upstream subscription and reports
+    // its id through `confirm`.
+    Open,
+    // Another member already opened (or is opening) it.
+    Shared,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subscribed {
+    pub subscription: u64,
+    pub attach: Attach,
+}
+
+// Subscriptions whose upstream connection dropped, with the clients to tell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resubscribe {
+    pub key: SubscriptionKey,
+    pub members: Vec<Member>,
+}
+
+#[derive(Debug, Default)]
+struct Group {
+    upstream_id: Option<u64>,
+    members: Vec<Member>,
+}
+
+#[derive(Debug, Default)]
+struct HubState {
+    next_subscription: u64,
+    groups: HashMap<SubscriptionKey, Group>,
+    by_member: HashMap<Member, SubscriptionKey>,
+    by_upstream: HashMap<u64, SubscriptionKey>,
+}
+
+impl HubState {
+    // Drops the member and returns the upstream id to close when it was the
+    // last one.
+    fn detach(&mut self, member: Member) -> Option<Option<u64>> {
+        let key = self.by_member.remove(&member)?;
+        let group = self.groups.get_mut(&key)?;
+        group.members.retain(|existing| *existing != member);
+        if !group.members.is_empty() {
+            return Some(None);
+        }
+
+        let upstream_id = self.groups.remove(&key).and_then(|group| group.upstream_id);
+        if let Some(id) = upstream_id {
+            self.by_upstream.remove(&id);
+        }
+        Some(upstream_id)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SubscriptionHub {
+    state: Mutex<HubState>,
+}
+
+impl SubscriptionHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Client-facing subscription ids are unique across all clients.
+    pub fn subscribe(&self, client: ClientId, key: SubscriptionKey) -> Subscribed {
+        let mut state = self.state.lock().expect("subscription lock poisoned");
+        state.next_subscription += 1;
+        let member = Member {
+            client,
+            subscription: state.next_subscription,
+        };
+
+        let group = state.groups.entry(key.clone()).or_default();
+        let attach = if group.members.is_empty() {
+            Attach::Open
+        } else {
+            Attach::Shared
+        };
+        group.members.push(member);
+        state.by_member.insert(member, key);
+
+        Subscribed {
+            subscription: member.subscription,
+            attach,
+        }
+    }
+
+    // Records the upstream id once the validator accepted the subscription.
+    // Returns false when every member left in the meantime; the caller then
+    // closes the upstream subscription right away.
+    pub fn confirm(&self, key: &SubscriptionKey, upstream_id: u64) -> bool {
+        let mut state = self.state.lock().expect("subscription lock poisoned");
+        let Some(group) = state.groups.get_mut(key) else {
+            return false;
+        };
+        group.upstream_id = Some(upstream_id);
+        state.by_upstream.insert(upstream_id, key.clone());
+        true
+    }
+
+    // The upstream refused the subscription; every member gets the error.
+    pub fn fail(&self, key: &SubscriptionKey) -> Vec<Member> {
+        let mut state = self.state.lock().expect("subscription lock poisoned");
+        let Some(group) = state.groups.remove(key) else {
+            return Vec::new();
+        };
+        for member in &group.members {
+            state.by_member.remove(member);
+        }
+        if let Some(id) = group.upstream_id {
+            state.by_upstream.remove(&id);
+        }
+        group.members
+    }
+
+    // `None` for an unknown subscription, `Some(Some(id))` when the upstream
+    // subscription `id` must be closed because the last member left.
+    pub fn unsubscribe(&self, client: ClientId, subscription: u64) -> Option<Option<u64>> {
+        self.state
+            .lock()
+            .expect("subscription lock poisoned")
+            .detach(Member {
+                client,
+                subscription,
+            })
+    }
+
+    // Detaches everything a closed client connection held; returns the
+    // upstream subscriptions left without members.
+    pub fn disconnect(&self, client: ClientId) -> Vec<u64> {
+        let mut state = self.state.lock().expect("subscription lock poisoned");
+        let members: Vec<Member> = state
+            .by_member
+            .keys()
+            .filter(|member| member.client == client)
+            .copied()
+            .collect();
+        members
+            .into_iter()
+            .filter_map(|member| state.detach(member).flatten())
+            .collect()
+    }
+
+    // Forgets the upstream ids served by `validator` (all of them for
+    // `None`) so each group can be opened again.
+    pub fn upstream_lost(&self, validator: Option<&str>) -> Vec<Resubscribe> {
+        let mut state = self.state.lock().expect("subscription lock poisoned");
+        let validator = validator.map(|name| name.trim().to_ascii_lowercase());
+        let HubState {
+            groups,
+            by_upstream,
+            ..
+        } = &mut *state;
+
+        let mut lost: Vec<Resubscribe> = groups
+            .iter_mut()
+            .filter(|(key, group)| {
+                group.upstream_id.is_some() && (validator.is_none() || key.validator == validator)
+            })
+            .map(|(key, group)| {
+                if let Some(id) = group.upstream_id.take() {
+                    by_upstream.remove(&id);
+                }
+                Resubscribe {
+                    key: key.clone(),
+                    members: group.members.clone(),
+                }
+            })
+            .collect();
+        lost.sort_by(|a, b| (&a.key.method, &a.key.params).cmp(&(&b.key.method, &b.key.params)));
+        lost
+    }
+
+    // One copy of an upstream notification per member, with
+    // `params.subscription` rewritten to that member's id.
+    pub fn fan_out(&self, notification: &Value) -> Vec<(ClientId, Value)> {
+        let Some(upstream_id) = notification
+            .pointer("/params/subscription")
+            .and_then(Value::as_u64)
+        else {
+            return Vec::new();
+        };
+
+        let state = self.state.lock().expect("subscription lock poisoned");
+        let Some(group) = state
+            .by_upstream
+            .get(&upstream_id)
+            .and_then(|key| state.groups.get(key))
+        else {
+            return Vec::new();
+        };
+
+        group
+            .members
+            .iter()
+            .map(|member| {
+                let mut copy = notification.clone();
+                copy["params"]["subscription"] = Value::from(member.subscription);
+                (member.client, copy)
+            })
+            .collect()
+    }
+
+    pub fn upstream_subscriptions(&self) -> usize {
+        self.state
+            .lock()
+            .expect("subscription lock poisoned")
+            .groups
+            .len()
+    }
+
+    pub fn client_subscriptions(&self) -> usize {
+        self.state
+            .lock()
+            .expect("subscription lock poisoned")
+            .by_member
+            .len()
+    }
+}
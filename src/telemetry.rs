@@ -0,0 +1,171 @@
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+use actix_web::dev::ServiceRequest;
+use actix_web::middleware::Logger;
+use tracing::{Level, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{
+    EnvFilter, Layer,
+    filter::{FilterExt, Targets, filter_fn},
+    fmt,
+    fmt::MakeWriter,
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+};
+
+use crate::access_log;
+use crate::config::Settings;
+use crate::log_file;
+
+const DEFAULT_FILTER: &str = "info,the_solana_api=info";
+
+// `Logger::default()` with the request line rebuilt so `?api_key=` never
+// reaches the logs.
+const REQUEST_LOG_FORMAT: &str = r#"%a "%{request}xi" %s %b "%{Referer}i" "%{User-Agent}i" %T"#;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Compact,
+    Pretty,
+    // One JSON object per line, event fields at the top level.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.to_ascii_lowercase().as_str() {
+            "compact" => Ok(Self::Compact),
+            "pretty" => Ok(Self::Pretty),
+            "json" => Ok(Self::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+impl LogFormat {
+    // Colors only for the human formats, and only on a terminal.
+    pub fn ansi(self) -> bool {
+        self != Self::Json && std::io::stdout().is_terminal()
+    }
+}
+
+// `RUST_LOG` when set, otherwise `info`.
+pub fn env_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER))
+}
+
+// The formatting layer `init_tracing` installs, for embedders that build
+// their own subscriber.
+pub fn fmt_layer<S, W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = fmt::layer().with_writer(writer).with_ansi(ansi);
+    match format {
+        LogFormat::Compact => layer.with_target(false).compact().boxed(),
+        LogFormat::Pretty => layer.pretty().boxed(),
+        // Targets stay in so pipelines can pick out e.g. `access_log`.
+        LogFormat::Json => layer
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+    }
+}
+
+// Logs to stdout, plus the OpenTelemetry exporter with the `otel` feature.
+// With `ACCESS_LOG_PATH` the access log goes to that file only, as JSON and
+// regardless of `RUST_LOG`. Keep the guard until the server has stopped so
+// buffered lines get written.
+pub fn init_tracing(settings: &Settings) -> io::Result<TracingGuard> {
+    let (access_log, guard) = match &settings.access_log_path {
+        Some(path) => {
+            let file = log_file::open(
+                path,
+                settings.access_log_rotation,
+                settings.access_log_max_bytes,
+                settings.access_log_max_files,
+            )
+            .map_err(|err| {
+                io::Error::new(
+                    err.kind(),
+                    format!("cannot open access log {}: {err}", path.display()),
+                )
+            })?;
+            let (writer, guard) = tracing_appender::non_blocking(file);
+            (Some(access_log_layer(writer)), Some(guard))
+        }
+        None => (None, None),
+    };
+    let to_file = access_log.is_some();
+    let stdout_filter = env_filter().and(filter_fn(move |meta| {
+        !(to_file && meta.target() == access_log::TARGET)
+    }));
+
+    let format = settings.log_format;
+    let subscriber = tracing_subscriber::registry()
+        .with(fmt_layer(format, std::io::stdout, format.ansi()).with_filter(stdout_filter))
+        .with(access_log);
+    #[cfg(feature = "otel")]
+    let subscriber = subscriber.with(crate::otel::layer().with_filter(env_filter()));
+    let _ = subscriber.try_init();
+    Ok(TracingGuard { _access_log: guard })
+}
+
+// Flushes the access log file when dropped.
+pub struct TracingGuard {
+    _access_log: Option<WorkerGuard>,
+}
+
+// JSON lines of `access_log` events alone.
+pub fn access_log_layer<S, W>(writer: W) -> impl Layer<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    fmt::layer()
+        .json()
+        .flatten_event(true)
+        .with_current_span(false)
+        .with_span_list(false)
+        .with_ansi(false)
+        .with_writer(writer)
+        .with_filter(Targets::new().with_target(access_log::TARGET, Level::INFO))
+}
+
+// The per-request line actix logs, with the `api_key` query value redacted.
+pub fn request_logger() -> Logger {
+    Logger::new(REQUEST_LOG_FORMAT).custom_request_replace("request", request_line)
+}
+
+fn request_line(req: &ServiceRequest) -> String {
+    let path = match req.query_string() {
+        "" => req.path().to_string(),
+        query => format!("{}?{}", req.path(), redact_query(query)),
+    };
+    format!("{} {path} {:?}", req.method(), req.version())
+}
+
+fn redact_query(query: &str) -> String {
+    query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some(("api_key", _)) => "api_key=<redacted>",
+            _ => pair,
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+// Flushes exporters before the process exits.
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    crate::otel::shutdown();
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use rustls::ServerConfig;
+use rustls::crypto::ring;
+use rustls::pki_types::pem::{self, PemObject};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use thiserror::Error;
+use tracing::{error, info};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsSettings {
+    pub cert_file: PathBuf,
+    pub key_file: PathBuf,
+    // How often the files are checked for a renewed certificate; zero
+    // turns reloading off.
+    pub reload_interval: Duration,
+}
+
+impl TlsSettings {
+    pub fn new(cert_file: impl Into<PathBuf>, key_file: impl Into<PathBuf>) -> Self {
+        Self {
+            cert_file: cert_file.into(),
+            key_file: key_file.into(),
+            reload_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("cannot read {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{} is not valid PEM: {message}", path.display())]
+    Pem { path: PathBuf, message: String },
+    #[error("{} holds no CERTIFICATE block", .0.display())]
+    NoCertificate(PathBuf),
+    #[error("{} holds no PRIVATE KEY block (PKCS#8, PKCS#1 or SEC1)", .0.display())]
+    NoPrivateKey(PathBuf),
+    #[error("TLS_KEY_FILE does not match the certificate in TLS_CERT_FILE")]
+    KeyMismatch,
+    #[error("unusable certificate or key: {0}")]
+    Invalid(String),
+}
+
+// Reads and checks the pair, so a bad file is reported before the listener
+// starts or a running one gives up its working certificate.
+pub fn load_certified_key(settings: &TlsSettings) -> Result<CertifiedKey, TlsError> {
+    let (cert_file, key_file) = (&settings.cert_file, &settings.key_file);
+    let certs = CertificateDer::pem_slice_iter(&read(cert_file)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| bad_pem(cert_file, err))?;
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificate(cert_file.clone()));
+    }
+    let key = match PrivateKeyDer::from_pem_slice(&read(key_file)?) {
+        Ok(key) => key,
+        Err(pem::Error::NoItemsFound) => return Err(TlsError::NoPrivateKey(key_file.clone())),
+        Err(err) => return Err(bad_pem(key_file, err)),
+    };
+
+    CertifiedKey::from_der(certs, key, &ring::default_provider()).map_err(|err| match err {
+        rustls::Error::InconsistentKeys(_) => TlsError::KeyMismatch,
+        err => TlsError::Invalid(err.to_string()),
+    })
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, TlsError> {
+    fs::read(path).map_err(|source| TlsError::Read {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn bad_pem(path: &Path, err: pem::Error) -> TlsError {
+    TlsError::Pem {
+        path: path.to_path_buf(),
+        message: err.to_string(),
+    }
+}
+
+// The certificate in use. Handshakes pick up a reloaded one at once, while
+// connections already open keep theirs.
+pub struct CertStore {
+    settings: TlsSettings,
+    current: RwLock<Arc<CertifiedKey>>,
+    // Of both files when last read, to notice a renewal.
+    seen: Mutex<Option<[(SystemTime, u64); 2]>>,
+}
+
+impl fmt::Debug for CertStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertStore")
+            .field("settings", &self.settings)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CertStore {
+    pub fn load(settings: TlsSettings) -> Result<Self, TlsError> {
+        let seen = files_version(&settings);
+        let key = load_certified_key(&settings)?;
+        Ok(Self {
+            settings,
+            current: RwLock::new(Arc::new(key)),
+            seen: Mutex::new(seen),
+        })
+    }
+
+    pub fn current(&self) -> Arc<CertifiedKey> {
+        self.current
+            .read()
+            .expect("cert store lock poisoned")
+            .clone()
+    }
+
+    // Reads both files again when either moved. A pair that no longer loads
+    // leaves the certificate in use alone and is not retried until the
+    // files change again.
+    pub fn reload_if_changed(&self) -> Option<Result<(), TlsError>> {
+        let version = files_version(&self.settings);
+        let mut seen = self.seen.lock().expect("cert store lock poisoned");
+        if version == *seen {
+            return None;
+        }
+        *seen = version;
+        drop(seen);
+
+        let result = load_certified_key(&self.settings).map(|key| {
+            *self.current.write().expect("cert store lock poisoned") = Arc::new(key);
+        });
+        match &result {
+            Ok(()) => info!(
+                cert = %self.settings.cert_file.display(),
+                "reloaded TLS certificate"
+            ),
+            Err(err) => error!(
+                error = %err,
+                "TLS certificate reload failed; keeping the previous certificate"
+            ),
+        }
+        Some(result)
+    }
+
+    pub fn server_config(self: &Arc<Self>) -> ServerConfig {
+        ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .expect("ring supports the default protocol versions")
+            .with_no_client_auth()
+            .with_cert_resolver(self.clone())
+    }
+}
+
+impl ResolvesServerCert for CertStore {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+fn files_version(settings: &TlsSettings) -> Option<[(SystemTime, u64); 2]> {
+    let version = |path: &Path| {
+        let metadata = fs::metadata(path).ok()?;
+        Some((metadata.modified().ok()?, metadata.len()))
+    };
+    Some([version(&settings.cert_file)?, version(&settings.key_file)?])
+}
+
+pub fn spawn_watch(store: Arc<CertStore>) {
+    let period = store.settings.reload_interval;
+    if period.is_zero() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let store = store.clone();
+            let _ = tokio::task::spawn_blocking(move || store.reload_if_changed()).await;
+        }
+    });
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+
+use actix_web::{
+    Error, HttpMessage,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+};
+use rand::RngCore;
+
+pub const TRACEPARENT: &str = "traceparent";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceParent {
+    trace_id: [u8; 16],
+    parent_id: [u8; 8],
+    flags: u8,
+}
+
+impl TraceParent {
+    pub fn generate() -> Self {
+        let mut rng = rand::thread_rng();
+        let mut trace_id = [0u8; 16];
+        let mut parent_id = [0u8; 8];
+        while trace_id == [0; 16] {
+            rng.fill_bytes(&mut trace_id);
+        }
+        while parent_id == [0; 8] {
+            rng.fill_bytes(&mut parent_id);
+        }
+
+        Self {
+            trace_id,
+            parent_id,
+            flags: 0x01,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.trim().split('-');
+        let version = decode_hex::<1>(parts.next()?)?[0];
+        let trace_id = decode_hex::<16>(parts.next()?)?;
+        let parent_id = decode_hex::<8>(parts.next()?)?;
+        let flags = decode_hex::<1>(parts.next()?)?[0];
+
+        // Version 00 has exactly four fields; later versions may append more.
+        let has_extra_fields = parts.next().is_some();
+        if version == 0xff || (version == 0 && has_extra_fields) {
+            return None;
+        }
+
+        if trace_id == [0; 16] || parent_id == [0; 8] {
+            return None;
+        }
+
+        Some(Self {
+            trace_id,
+            parent_id,
+            flags,
+        })
+    }
+
+    // Falls back to a fresh context when the inbound header is absent or malformed.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        headers
+            .get(TRACEPARENT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Self::parse)
+            .map(|inbound| inbound.child())
+            .unwrap_or_else(Self::generate)
+    }
+
+    pub fn child(&self) -> Self {
+        let mut child = Self::generate();
+        child.trace_id = self.trace_id;
+        child.flags = self.flags;
+        child
+    }
+
+    // Rejects the all-zero ids W3C reserves as invalid.
+    pub fn from_ids(trace_id: [u8; 16], parent_id: [u8; 8], sampled: bool) -> Option<Self> {
+        if trace_id == [0; 16] || parent_id == [0; 8] {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            parent_id,
+            flags: u8::from(sampled),
+        })
+    }
+
+    pub fn ids(&self) -> ([u8; 16], [u8; 8]) {
+        (self.trace_id, self.parent_id)
+    }
+
+    pub fn trace_id(&self) -> String {
+        encode_hex(&self.trace_id)
+    }
+
+    pub fn parent_id(&self) -> String {
+        encode_hex(&self.parent_id)
+    }
+
+    pub fn sampled(&self) -> bool {
+        self.flags & 0x01 == 0x01
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            self.trace_id(),
+            self.parent_id(),
+            self.flags
+        )
+    }
+}
+
+fn decode_hex<const N: usize>(value: &str) -> Option<[u8; N]> {
+    if value.len() != N * 2
+        || !value
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    {
+        return None;
+    }
+
+    let mut bytes = [0u8; N];
+    for (idx, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&value[idx * 2..idx * 2 + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub async fn propagate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    // With the `otel` feature the ids come from the exported request span.
+    #[cfg(feature = "otel")]
+    let trace = crate::otel::span_trace(&tracing::Span::current())
+        .unwrap_or_else(|| TraceParent::from_headers(req.headers()));
+    #[cfg(not(feature = "otel"))]
+    let trace = TraceParent::from_headers(req.headers());
+    req.extensions_mut().insert(trace);
+
+    let mut response = next.call(req).await?;
+    if let Ok(value) = HeaderValue::from_str(&trace.to_string()) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static(TRACEPARENT), value);
+    }
+
+    Ok(response)
+}
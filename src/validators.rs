@@ -1,18 +1,25 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use url::Url;
 
-#[derive(Debug, Clone)]
+use crate::rate_limit::KeyRateLimit;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Validator {
     name: String,
     location: String,
     rpc_url: Url,
+    rewrite_host: Option<bool>,
+    tags: Vec<String>,
+    archive: bool,
+    rate_limit: Option<KeyRateLimit>,
 }
 
 impl Validator {
@@ -21,9 +28,42 @@ impl Validator {
             name,
             location,
             rpc_url,
+            rewrite_host: None,
+            tags: Vec::new(),
+            archive: false,
+            rate_limit: None,
         }
     }
 
+    pub fn with_tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.tags = tags
+            .into_iter()
+            .map(|tag| normalize_key(tag.as_ref()))
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        self
+    }
+
+    pub fn with_archive(mut self, archive: bool) -> Self {
+        self.archive = archive;
+        self
+    }
+
+    // Outbound budget for this validator, in place of the default.
+    pub fn with_rate_limit(mut self, rate_limit: Option<KeyRateLimit>) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    pub fn with_rewrite_host(mut self, rewrite_host: bool) -> Self {
+        self.rewrite_host = Some(rewrite_host);
+        self
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -36,6 +76,27 @@ impl Validator {
         &self.rpc_url
     }
 
+    pub fn rewrite_host(&self) -> Option<bool> {
+        self.rewrite_host
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = normalize_key(tag);
+        self.tags.contains(&tag)
+    }
+
+    pub fn is_archive(&self) -> bool {
+        self.archive
+    }
+
+    pub fn rate_limit(&self) -> Option<KeyRateLimit> {
+        self.rate_limit
+    }
+
     pub fn host_header(&self) -> Option<String> {
         let host = self.rpc_url.host()?;
         let mut host = host.to_string();
@@ -45,6 +106,15 @@ impl Validator {
         Some(host)
     }
 
+    pub fn dns_host(&self) -> Option<(String, u16)> {
+        match self.rpc_url.host()? {
+            url::Host::Domain(domain) => {
+                Some((domain.to_string(), self.rpc_url.port_or_known_default()?))
+            }
+            _ => None,
+        }
+    }
+
     pub fn summary(&self) -> ValidatorSummary {
         ValidatorSummary {
             name: self.name.clone(),
@@ -64,6 +134,7 @@ pub struct ValidatorRegistry {
     validators: Vec<Validator>,
     index_by_name: HashMap<String, usize>,
     index_by_location: HashMap<String, Vec<usize>>,
+    loaded_at: SystemTime,
 }
 
 impl ValidatorRegistry {
@@ -90,6 +161,44 @@ impl ValidatorRegistry {
         Self::new(validators)
     }
 
+    // Parses every row, carrying on past bad ones, so an offline check can
+    // report all of them. Registry-wide rules are left to the caller.
+    pub fn inspect<R: Read>(reader: R) -> Inspection {
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+
+        let mut inspection = Inspection::default();
+        for (row_idx, result) in csv_reader.deserialize::<ValidatorCsvRecord>().enumerate() {
+            let row = row_idx + 2; // account for header row
+            let record = match result {
+                Ok(record) => record,
+                Err(err) => {
+                    // Nothing more can be read after an I/O failure.
+                    let fatal = matches!(err.kind(), csv::ErrorKind::Io(_));
+                    inspection.errors.push(err.into());
+                    if fatal {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            let generated_name = non_empty(record.name.as_deref()).is_none();
+            let missing_location = non_empty(record.location.as_deref()).is_none();
+            match Validator::try_from_record(record, row, inspection.rows.len() + 1) {
+                Ok(validator) => inspection.rows.push(InspectedRow {
+                    row,
+                    validator,
+                    generated_name,
+                    missing_location,
+                }),
+                Err(err) => inspection.errors.push(err),
+            }
+        }
+        inspection
+    }
+
     pub fn new(validators: Vec<Validator>) -> Result<Self, RegistryError> {
         if validators.is_empty() {
             return Err(RegistryError::Empty);
@@ -112,6 +221,7 @@ impl ValidatorRegistry {
             validators,
             index_by_name,
             index_by_location,
+            loaded_at: SystemTime::now(),
         })
     }
 
@@ -119,6 +229,28 @@ impl ValidatorRegistry {
         &self.validators
     }
 
+    pub fn loaded_at(&self) -> SystemTime {
+        self.loaded_at
+    }
+
+    pub fn loaded_at_unix_ms(&self) -> u64 {
+        self.loaded_at
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default()
+    }
+
+    pub fn dns_hosts(&self) -> Vec<(String, u16)> {
+        let mut hosts: Vec<_> = self
+            .validators
+            .iter()
+            .filter_map(|v| v.dns_host())
+            .collect();
+        hosts.sort();
+        hosts.dedup();
+        hosts
+    }
+
     pub fn summaries(&self) -> Vec<ValidatorSummary> {
         self.validators.iter().map(|v| v.summary()).collect()
     }
@@ -128,33 +260,65 @@ impl ValidatorRegistry {
         name: Option<&str>,
         location: Option<&str>,
     ) -> Result<Validator, SelectionError> {
-        if let Some(name) = name.and_then(|value| {
-            let trimmed = value.trim();
-            if trimmed.is_empty() {
-                None
-            } else {
-                Some(trimmed)
-            }
-        }) {
+        let mut candidates = self.candidates(name, location)?;
+        Ok(candidates.swap_remove(0))
+    }
+
+    pub fn candidates(
+        &self,
+        name: Option<&str>,
+        location: Option<&str>,
+    ) -> Result<Vec<Validator>, SelectionError> {
+        if let Some(name) = non_empty(name) {
             return self
                 .get_by_name(name)
-                .ok_or_else(|| SelectionError::UnknownValidator(name.to_string()));
+                .map(|validator| vec![validator])
+                .ok_or_else(|| SelectionError::UnknownValidator {
+                    name: name.to_string(),
+                    suggestions: suggestions(name, self.validators.iter().map(Validator::name)),
+                });
         }
 
-        if let Some(location) = location.and_then(|value| {
-            let trimmed = value.trim();
-            if trimmed.is_empty() {
-                None
-            } else {
-                Some(trimmed)
+        let mut rng = rand::thread_rng();
+
+        if let Some(location) = non_empty(location) {
+            let mut candidates: Vec<Validator> = self
+                .index_by_location
+                .get(&normalize_key(location))
+                .map(|indexes| {
+                    indexes
+                        .iter()
+                        .map(|idx| self.validators[*idx].clone())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            if candidates.is_empty() {
+                return Err(SelectionError::UnknownLocation {
+                    location: location.to_string(),
+                    suggestions: suggestions(
+                        location,
+                        self.validators.iter().map(Validator::location),
+                    ),
+                });
             }
-        }) {
-            return self
-                .random_in_location(location)
-                .ok_or_else(|| SelectionError::UnknownLocation(location.to_string()));
+
+            candidates.shuffle(&mut rng);
+            return Ok(candidates);
         }
 
-        self.random().ok_or(SelectionError::Empty)
+        if self.validators.is_empty() {
+            return Err(SelectionError::Empty);
+        }
+
+        let mut candidates = self.validators.clone();
+        candidates.shuffle(&mut rng);
+        Ok(candidates)
+    }
+
+    pub fn has_location(&self, location: &str) -> bool {
+        self.index_by_location
+            .contains_key(&normalize_key(location))
     }
 
     pub fn get_by_name(&self, name: &str) -> Option<Validator> {
@@ -184,28 +348,99 @@ impl ValidatorRegistry {
     }
 }
 
+// What `ValidatorRegistry::inspect` found, row by row.
+#[derive(Debug, Default)]
+pub struct Inspection {
+    pub rows: Vec<InspectedRow>,
+    pub errors: Vec<RegistryError>,
+}
+
+#[derive(Debug)]
+pub struct InspectedRow {
+    pub row: usize,
+    pub validator: Validator,
+    // The name was made up from the location.
+    pub generated_name: bool,
+    // The location column was empty or absent.
+    pub missing_location: bool,
+}
+
 #[derive(Debug, Error)]
 pub enum RegistryError {
     #[error("csv error: {0}")]
     Csv(#[from] csv::Error),
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
-    #[error("invalid record at row {0}: {1}")]
-    InvalidRecord(usize, String),
+    #[error("invalid record at row {row}: {message}")]
+    InvalidRecord {
+        row: usize,
+        // The CSV column at fault.
+        field: &'static str,
+        message: String,
+        #[source]
+        source: Option<url::ParseError>,
+    },
     #[error("duplicate validator name '{0}'")]
     DuplicateName(String),
     #[error("no validators configured")]
     Empty,
 }
 
-#[derive(Debug, Error)]
+impl RegistryError {
+    fn invalid(row: usize, field: &'static str, message: impl Into<String>) -> Self {
+        RegistryError::InvalidRecord {
+            row,
+            field,
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    // The CSV line at fault, header included, when known.
+    pub fn row(&self) -> Option<usize> {
+        match self {
+            RegistryError::InvalidRecord { row, .. } => Some(*row),
+            RegistryError::Csv(err) => err.position().map(|position| position.line() as usize),
+            _ => None,
+        }
+    }
+
+    pub fn field(&self) -> Option<&'static str> {
+        match self {
+            RegistryError::InvalidRecord { field, .. } => Some(field),
+            RegistryError::DuplicateName(_) => Some("name"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
 pub enum SelectionError {
-    #[error("validator '{0}' not found")]
-    UnknownValidator(String),
-    #[error("no validator available for location '{0}'")]
-    UnknownLocation(String),
+    #[error("validator '{name}' not found")]
+    UnknownValidator {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    #[error("no validator available for location '{location}'")]
+    UnknownLocation {
+        location: String,
+        suggestions: Vec<String>,
+    },
     #[error("no validators available")]
     Empty,
+    #[error("quorum of {required} needs more validators than the {available} available")]
+    NotEnoughValidators { required: usize, available: usize },
+}
+
+impl SelectionError {
+    // Close matches for a mistyped validator or location.
+    pub fn suggestions(&self) -> Option<&[String]> {
+        match self {
+            SelectionError::UnknownValidator { suggestions, .. }
+            | SelectionError::UnknownLocation { suggestions, .. } => Some(suggestions),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -227,6 +462,20 @@ struct ValidatorCsvRecord {
 
     #[serde(default)]
     location: Option<String>,
+
+    #[serde(default)]
+    rewrite_host: Option<bool>,
+
+    // Semicolon-separated, since the file itself is comma-separated.
+    #[serde(default)]
+    tags: Option<String>,
+
+    #[serde(default)]
+    archive: Option<bool>,
+
+    // Outbound requests per second, with one second's worth of burst.
+    #[serde(default)]
+    rate_limit_rps: Option<f64>,
 }
 
 impl Validator {
@@ -248,8 +497,9 @@ impl Validator {
             .to_ascii_lowercase();
 
         if protocol != "http" && protocol != "https" {
-            return Err(RegistryError::InvalidRecord(
+            return Err(RegistryError::invalid(
                 row_number,
+                "protocol",
                 format!("unsupported protocol '{protocol}'"),
             ));
         }
@@ -260,8 +510,11 @@ impl Validator {
             .map(|value| value.trim())
             .filter(|value| !value.is_empty())
         {
-            Url::parse(endpoint).map_err(|err| {
-                RegistryError::InvalidRecord(row_number, format!("invalid url '{endpoint}': {err}"))
+            Url::parse(endpoint).map_err(|err| RegistryError::InvalidRecord {
+                row: row_number,
+                field: "rpc_url",
+                message: format!("invalid url '{endpoint}': {err}"),
+                source: Some(err),
             })?
         } else if let Some(host) = record
             .endpoint_host
@@ -271,43 +524,49 @@ impl Validator {
         {
             let formatted_host = prepare_host_for_url(host);
             let candidate = format!("{protocol}://{formatted_host}");
-            let mut parsed = Url::parse(&candidate).map_err(|err| {
-                RegistryError::InvalidRecord(row_number, format!("invalid host '{host}': {err}"))
-            })?;
+            let mut parsed =
+                Url::parse(&candidate).map_err(|err| RegistryError::InvalidRecord {
+                    row: row_number,
+                    field: "host",
+                    message: format!("invalid host '{host}': {err}"),
+                    source: Some(err),
+                })?;
 
             if parsed.port().is_none() {
                 let port = record.endpoint_port.unwrap_or_else(default_rpc_port);
-                parsed.set_port(Some(port)).map_err(|_| {
-                    RegistryError::InvalidRecord(row_number, "invalid port".to_string())
-                })?;
+                parsed
+                    .set_port(Some(port))
+                    .map_err(|_| RegistryError::invalid(row_number, "port", "invalid port"))?;
             }
 
             parsed
         } else {
-            return Err(RegistryError::InvalidRecord(
+            return Err(RegistryError::invalid(
                 row_number,
-                "missing rpc_url or host/ip column".to_string(),
+                "rpc_url",
+                "missing rpc_url or host/ip column",
             ));
         };
 
         if url.scheme() != "http" && url.scheme() != "https" {
-            return Err(RegistryError::InvalidRecord(
+            return Err(RegistryError::invalid(
                 row_number,
+                "rpc_url",
                 format!("unsupported url scheme `{}`", url.scheme()),
             ));
         }
 
         if url.host().is_none() {
-            return Err(RegistryError::InvalidRecord(
+            return Err(RegistryError::invalid(
                 row_number,
-                "url is missing host".to_string(),
+                "rpc_url",
+                "url is missing host",
             ));
         }
 
         if url.port().is_none() {
-            url.set_port(Some(default_rpc_port())).map_err(|_| {
-                RegistryError::InvalidRecord(row_number, "invalid port".to_string())
-            })?;
+            url.set_port(Some(default_rpc_port()))
+                .map_err(|_| RegistryError::invalid(row_number, "port", "invalid port"))?;
         }
 
         let name = record
@@ -322,14 +581,77 @@ impl Validator {
             })
             .unwrap_or_else(|| generate_default_name(&location, ordinal));
 
-        Ok(Validator::new(name, location, url))
+        let rate_limit = match record.rate_limit_rps {
+            Some(rps) => Some(KeyRateLimit::new(rps, None).ok_or_else(|| {
+                RegistryError::invalid(
+                    row_number,
+                    "rate_limit_rps",
+                    format!("rate limit must be a positive number, got '{rps}'"),
+                )
+            })?),
+            None => None,
+        };
+
+        let mut validator = Validator::new(name, location, url)
+            .with_tags(record.tags.as_deref().unwrap_or_default().split(';'))
+            .with_archive(record.archive.unwrap_or(false))
+            .with_rate_limit(rate_limit);
+        validator.rewrite_host = record.rewrite_host;
+
+        Ok(validator)
     }
 }
 
-fn normalize_key(value: &str) -> String {
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|value| !value.is_empty())
+}
+
+pub(crate) fn normalize_key(value: &str) -> String {
     value.trim().to_ascii_lowercase()
 }
 
+// Suggestions listed for an unknown name, closest first.
+const MAX_SUGGESTIONS: usize = 3;
+
+// Known values within a few edits of `wanted`, or containing it, ignoring
+// case.
+pub fn suggestions<'a>(wanted: &str, known: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let wanted = normalize_key(wanted);
+    let max_distance = (wanted.chars().count() / 3).max(1);
+    // Case variants of one name are suggested once, as first listed.
+    let mut seen = HashSet::new();
+    let mut scored: Vec<(usize, &str)> = known
+        .filter(|value| seen.insert(normalize_key(value)))
+        .filter_map(|value| {
+            let key = normalize_key(value);
+            let distance = edit_distance(&wanted, &key);
+            let related = !wanted.is_empty() && (key.contains(&wanted) || wanted.contains(&key));
+            (distance <= max_distance || related).then_some((distance, value))
+        })
+        .collect();
+    scored.sort();
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, value)| value.to_string())
+        .collect()
+}
+
+// Levenshtein distance over chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
 fn default_protocol() -> String {
     "http".to_string()
 }
@@ -372,4 +694,4 @@ fn generate_default_name(location: &str, ordinal: usize) -> String {
     } else {
         format!("{cleaned}-{ordinal}")
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,53 @@
+use serde::Serialize;
+
+use crate::app_state::AppState;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_COMMIT: &str = env!("BUILD_GIT_COMMIT");
+const BUILD_TIMESTAMP_MS: &str = env!("BUILD_TIMESTAMP_MS");
+
+pub fn features() -> Vec<&'static str> {
+    [
+        ("otel", cfg!(feature = "otel")),
+        ("statsd", cfg!(feature = "statsd")),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+// Only build metadata and the registry's file path; nothing from the
+// environment that could hold a credential.
+#[derive(Debug, Serialize)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    pub built_at_unix_ms: u64,
+    pub features: Vec<&'static str>,
+    pub registry: RegistryInfo,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegistryInfo {
+    pub source: String,
+    pub validators: usize,
+    pub loaded_at_unix_ms: u64,
+}
+
+impl VersionInfo {
+    pub fn new(state: &AppState) -> Self {
+        let registry = state.registry();
+        Self {
+            version: VERSION,
+            git_commit: GIT_COMMIT,
+            built_at_unix_ms: BUILD_TIMESTAMP_MS.parse().unwrap_or_default(),
+            features: features(),
+            registry: RegistryInfo {
+                source: state.settings().registry_source(),
+                validators: registry.validators().len(),
+                loaded_at_unix_ms: registry.loaded_at_unix_ms(),
+            },
+        }
+    }
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use actix_web::{HttpResponse, test, web};
+use serde_json::json;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use url::Url;
+
+use the_solana_api::config::Settings;
+use the_solana_api::rate_limit::MethodRateLimits;
+use the_solana_api::{AppState, Validator, access_log, key_usage};
+
+type Lines = Arc<Mutex<Vec<HashMap<String, String>>>>;
+
+// Keeps the fields of every access log event.
+struct Capture(Lines);
+
+struct Fields(HashMap<String, String>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
+
+impl<S: Subscriber> Layer<S> for Capture {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        if event.metadata().target() == access_log::TARGET {
+            let mut fields = Fields(HashMap::new());
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+    }
+}
+
+fn capture() -> (Lines, tracing::subscriber::DefaultGuard) {
+    let lines = Lines::default();
+    let subscriber = tracing_subscriber::registry().with(Capture(lines.clone()));
+    (lines, tracing::subscriber::set_default(subscriber))
+}
+
+fn get_slot() -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/")
+        .peer_addr("10.0.0.7:4000".parse().unwrap())
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+}
+
+fn unreachable(name: &str) -> Validator {
+    Validator::new(
+        name.into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:1/").unwrap(),
+    )
+}
+
+#[actix_web::test]
+async fn logs_one_line_per_proxied_request_with_stable_fields() {
+    let node = common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 42}))
+            }),
+        );
+    });
+    let app = common::init_app(AppState::new(common::registry(vec![
+        node.validator("primary", "frankfurt"),
+    ])))
+    .await;
+
+    let (lines, _guard) = capture();
+    test::call_service(
+        &app,
+        get_slot()
+            .insert_header(("x-request-id", "req-1"))
+            .insert_header(("authorization", "Bearer client-key"))
+            .to_request(),
+    )
+    .await;
+
+    let lines = lines.lock().unwrap();
+    assert_eq!(lines.len(), 1);
+    let line = &lines[0];
+    for (field, value) in [
+        ("client_ip", "10.0.0.7"),
+        ("request_id", "req-1"),
+        ("key_id", key_usage::key_id("client-key").as_str()),
+        ("rpc_method", "getSlot"),
+        ("path", "/"),
+        ("status", "200"),
+        ("validator", "primary"),
+        ("location", "frankfurt"),
+        ("upstream_status", "200"),
+        ("retries", "0"),
+        ("outcome", "success"),
+    ] {
+        assert_eq!(line.get(field).map(String::as_str), Some(value), "{field}");
+    }
+    for field in ["upstream_ms", "total_ms", "response_bytes"] {
+        assert!(line.contains_key(field), "{field}");
+    }
+}
+
+#[actix_web::test]
+async fn logs_failures_and_rejections() {
+    let app = common::init_app(AppState::new(common::registry(vec![unreachable("a")]))).await;
+    let (lines, _guard) = capture();
+    test::call_service(&app, get_slot().to_request()).await;
+    {
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines[0]["outcome"], "failed");
+        assert_eq!(lines[0]["status"], "502");
+        assert_eq!(lines[0]["validator"], "a");
+        assert_eq!(lines[0]["retries"], "0");
+        assert!(!lines[0].contains_key("upstream_status"));
+    }
+
+    let settings = Settings::builder()
+        .method_rate_limits(
+            MethodRateLimits::from_json(r#"[{"method":"getSlot","per_second":1,"burst":1}]"#)
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    let app = common::init_app(AppState::from_settings(
+        common::registry(vec![unreachable("a")]),
+        settings,
+    ))
+    .await;
+    test::call_service(&app, get_slot().to_request()).await;
+    test::call_service(&app, get_slot().to_request()).await;
+
+    let lines = lines.lock().unwrap();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[2]["outcome"], "rejected");
+    assert_eq!(lines[2]["status"], "429");
+    assert_eq!(lines[2]["rpc_method"], "getSlot");
+    assert!(!lines[2].contains_key("validator"));
+}
@@ -0,0 +1,268 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use actix_web::{HttpResponse, http::StatusCode, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::AppState;
+use the_solana_api::account_cache::AccountCache;
+use the_solana_api::config::{Settings, SettingsBuilder};
+use the_solana_api::jsonrpc;
+
+const MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+const PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGYPyT1kRCmWxeh7hBw1JkEX";
+
+// Answers from slot 100 onwards, one slot per call.
+fn upstream(calls: Arc<AtomicU64>) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let calls = calls.clone();
+        cfg.route(
+            "/",
+            web::post().to(move |body: web::Json<Value>| {
+                let slot = 100 + calls.fetch_add(1, Ordering::SeqCst);
+                let account = |pubkey: &Value| json!({"owner": pubkey, "lamports": slot});
+                let value = match body["method"].as_str() {
+                    Some("getMultipleAccounts") => Value::Array(
+                        body["params"][0]
+                            .as_array()
+                            .unwrap()
+                            .iter()
+                            .map(account)
+                            .collect(),
+                    ),
+                    _ => account(&body["params"][0]),
+                };
+                let id = body["id"].clone();
+                async move {
+                    HttpResponse::Ok().json(json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "result": {"context": {"slot": slot, "apiVersion": "1.18.0"}, "value": value}
+                    }))
+                }
+            }),
+        );
+    })
+}
+
+fn settings() -> SettingsBuilder {
+    Settings::builder()
+        .account_cache(true)
+        .account_cache_ttl(Duration::from_secs(60))
+}
+
+fn state(node: &common::MockUpstream, settings: SettingsBuilder) -> AppState {
+    AppState::builder()
+        .validators(vec![node.validator("primary", "lab")])
+        .settings(settings)
+        .build()
+        .unwrap()
+}
+
+fn call(method: &str, params: Value, id: Value) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+}
+
+async fn send<S>(app: &S, request: Value) -> (Option<String>, Value)
+where
+    S: actix_web::dev::Service<
+            actix_http::Request,
+            Response = actix_web::dev::ServiceResponse,
+            Error = actix_web::Error,
+        >,
+{
+    let response = test::call_service(
+        app,
+        test::TestRequest::post()
+            .uri("/")
+            .set_json(request)
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let cache = response
+        .headers()
+        .get("x-cache")
+        .map(|value| value.to_str().unwrap().to_string());
+    (cache, test::read_body_json(response).await)
+}
+
+#[actix_web::test]
+async fn hits_keep_the_client_id_and_the_cached_context_slot() {
+    let calls = Arc::new(AtomicU64::new(0));
+    let node = upstream(calls.clone());
+    let app = common::init_app(state(&node, settings())).await;
+    let params = json!([MINT, {"encoding": "base64"}]);
+
+    let (cache, first) = send(&app, call("getAccountInfo", params.clone(), json!(1))).await;
+    assert_eq!(cache.as_deref(), Some("MISS"));
+    assert_eq!(first["id"], 1);
+
+    let (cache, second) = send(&app, call("getAccountInfo", params, json!("client-7"))).await;
+    assert_eq!(cache.as_deref(), Some("HIT"));
+    assert_eq!(second["id"], "client-7");
+    assert_eq!(second["result"]["context"]["slot"], 100);
+    assert_eq!(second["result"]["value"], first["result"]["value"]);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[actix_web::test]
+async fn keys_include_encoding_slice_and_commitment() {
+    let calls = Arc::new(AtomicU64::new(0));
+    let node = upstream(calls.clone());
+    let app = common::init_app(state(&node, settings())).await;
+
+    for params in [
+        json!([MINT]),
+        json!([MINT, {"encoding": "base64"}]),
+        json!([MINT, {"encoding": "base64", "dataSlice": {"offset": 0, "length": 8}}]),
+        json!([MINT, {"encoding": "base64", "commitment": "confirmed"}]),
+    ] {
+        let (cache, _) = send(&app, call("getAccountInfo", params, json!(1))).await;
+        assert_eq!(cache.as_deref(), Some("MISS"));
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+    // `finalized` is the default commitment.
+    let params = json!([MINT, {"commitment": "finalized"}]);
+    let (cache, _) = send(&app, call("getAccountInfo", params, json!(1))).await;
+    assert_eq!(cache.as_deref(), Some("HIT"));
+}
+
+#[actix_web::test]
+async fn multiple_accounts_share_entries_with_single_reads() {
+    let calls = Arc::new(AtomicU64::new(0));
+    let node = upstream(calls.clone());
+    let app = common::init_app(state(&node, settings())).await;
+    let config = json!({"encoding": "base64"});
+
+    send(
+        &app,
+        call(
+            "getMultipleAccounts",
+            json!([[MINT, PROGRAM], config]),
+            json!(1),
+        ),
+    )
+    .await;
+    send(
+        &app,
+        call("getAccountInfo", json!([MINT, config]), json!(2)),
+    )
+    .await;
+    let (cache, body) = send(
+        &app,
+        call(
+            "getMultipleAccounts",
+            json!([[PROGRAM, MINT], config]),
+            json!(3),
+        ),
+    )
+    .await;
+
+    assert_eq!(cache.as_deref(), Some("HIT"));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(body["id"], 3);
+    let owners: Vec<&Value> = body["result"]["value"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|account| &account["owner"])
+        .collect();
+    assert_eq!(owners, [PROGRAM, MINT]);
+}
+
+#[actix_web::test]
+async fn processed_reads_and_unknown_config_bypass_the_cache() {
+    let calls = Arc::new(AtomicU64::new(0));
+    let node = upstream(calls.clone());
+    let app = common::init_app(state(&node, settings())).await;
+
+    for params in [
+        json!([MINT, {"commitment": "processed"}]),
+        json!([MINT, {"minContextSlot": 5}]),
+    ] {
+        for _ in 0..2 {
+            let (cache, _) = send(&app, call("getAccountInfo", params.clone(), json!(1))).await;
+            assert_eq!(cache, None);
+        }
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+    let node = upstream(calls.clone());
+    let processed = settings().account_cache_processed(true);
+    let app = common::init_app(state(&node, processed)).await;
+    let params = json!([MINT, {"commitment": "processed"}]);
+    send(&app, call("getAccountInfo", params.clone(), json!(1))).await;
+    let (cache, _) = send(&app, call("getAccountInfo", params, json!(1))).await;
+    assert_eq!(cache.as_deref(), Some("HIT"));
+}
+
+#[actix_web::test]
+async fn entries_expire_when_the_cluster_slot_moves_on() {
+    let calls = Arc::new(AtomicU64::new(0));
+    let node = upstream(calls.clone());
+    let state = state(&node, settings());
+    let app = common::init_app(state.clone()).await;
+    let request = call("getAccountInfo", json!([MINT]), json!(1));
+
+    send(&app, request.clone()).await;
+    state.stats().validator("primary").observe_slot(104);
+    let (cache, _) = send(&app, request.clone()).await;
+    assert_eq!(cache.as_deref(), Some("HIT"));
+
+    state.stats().validator("primary").observe_slot(105);
+    let (cache, body) = send(&app, request).await;
+    assert_eq!(cache.as_deref(), Some("MISS"));
+    assert_eq!(body["result"]["context"]["slot"], 101);
+
+    let counters = state.account_cache().counters();
+    assert_eq!((counters.hits, counters.misses), (1, 2));
+}
+
+#[actix_web::test]
+async fn entries_expire_after_the_ttl() {
+    let calls = Arc::new(AtomicU64::new(0));
+    let node = upstream(calls.clone());
+    let short = settings().account_cache_ttl(Duration::from_millis(50));
+    let app = common::init_app(state(&node, short)).await;
+    let request = call("getAccountInfo", json!([MINT]), json!(1));
+
+    send(&app, request.clone()).await;
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    let (cache, _) = send(&app, request).await;
+    assert_eq!(cache.as_deref(), Some("MISS"));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[actix_web::test]
+async fn evicts_the_least_recently_used_account() {
+    let cache = AccountCache::new(&settings().account_cache_max_entries(2).build().unwrap());
+    let lookup = |pubkey: &str| {
+        let body = call("getAccountInfo", json!([pubkey]), json!(1)).to_string();
+        cache
+            .lookup(
+                &jsonrpc::extract_method(body.as_bytes()),
+                None,
+                body.as_bytes(),
+            )
+            .unwrap()
+    };
+    let fill = |pubkey: &str| {
+        let body = json!({"id": 1, "result": {"context": {"slot": 1}, "value": pubkey}});
+        cache.insert(&lookup(pubkey), body.to_string().as_bytes());
+    };
+
+    fill("a");
+    fill("b");
+    assert!(cache.get(&lookup("a"), None).is_some());
+    fill("c");
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(&lookup("a"), None).is_some());
+    assert!(cache.get(&lookup("b"), None).is_none());
+    assert!(cache.get(&lookup("c"), None).is_some());
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::Value;
+
+use the_solana_api::auth::{ApiKey, ApiKeys};
+use the_solana_api::{AppState, Settings, Validator};
+
+const ADMIN: &str = "operator-token";
+const ALICE: &str = "alice-secret";
+const ALICE_SHA256: &str = "0c848abb03307b06cf70cd4e29c157dc81af5e94ab3eb1d0c59a120269572376";
+
+fn state(admin_token: Option<&str>) -> AppState {
+    let mut settings = Settings::builder().api_keys(ApiKeys::new(vec![
+        ApiKey::new("alice", ALICE_SHA256, false).unwrap(),
+    ]));
+    if let Some(token) = admin_token {
+        settings = settings.admin_token(token.into());
+    }
+    AppState::builder()
+        // Never contacted.
+        .validators(vec![Validator::new(
+            "node".into(),
+            "lab".into(),
+            url::Url::parse("http://127.0.0.1:9/").unwrap(),
+        )])
+        .settings(settings)
+        .build()
+        .unwrap()
+}
+
+fn request(method: &str, uri: &str, bearer: Option<&str>) -> actix_http::Request {
+    let req = match method {
+        "POST" => test::TestRequest::post(),
+        _ => test::TestRequest::get(),
+    }
+    .uri(uri);
+    match bearer {
+        Some(token) => req.insert_header(("authorization", format!("Bearer {token}"))),
+        None => req,
+    }
+    .to_request()
+}
+
+async fn refusal<S>(app: &S, req: actix_http::Request) -> (StatusCode, String)
+where
+    S: actix_web::dev::Service<
+            actix_http::Request,
+            Response = actix_web::dev::ServiceResponse,
+            Error = actix_web::Error,
+        >,
+{
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    let body: Value = test::read_body_json(resp).await;
+    (
+        status,
+        body["error"].as_str().unwrap_or_default().to_string(),
+    )
+}
+
+#[actix_web::test]
+async fn client_keys_cannot_call_admin_routes() {
+    let app = common::init_app(state(Some(ADMIN))).await;
+
+    for (method, uri) in [
+        ("GET", "/admin/keys/alice/usage"),
+        ("GET", "/admin/anything"),
+        ("POST", "/stats/reset"),
+    ] {
+        assert_eq!(
+            refusal(&app, request(method, uri, Some(ALICE))).await,
+            (
+                StatusCode::FORBIDDEN,
+                "API keys cannot call admin endpoints".to_string()
+            ),
+            "{uri}"
+        );
+        for bearer in [None, Some("guess")] {
+            assert_eq!(
+                refusal(&app, request(method, uri, bearer)).await,
+                (StatusCode::UNAUTHORIZED, "invalid admin token".to_string()),
+                "{uri}"
+            );
+        }
+    }
+
+    let resp =
+        test::call_service(&app, request("GET", "/admin/keys/alice/usage", Some(ADMIN))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&app, request("POST", "/stats/reset", Some(ADMIN))).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+}
+
+#[actix_web::test]
+async fn the_admin_token_is_not_a_client_key() {
+    let app = common::init_app(state(Some(ADMIN))).await;
+    let (status, _) = refusal(&app, request("GET", "/usage", Some(ADMIN))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn admin_routes_are_off_without_a_token() {
+    let app = common::init_app(state(None)).await;
+    for bearer in [None, Some(ALICE)] {
+        let (status, body) = refusal(&app, request("GET", "/admin/keys/alice/usage", bearer)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body, "admin endpoints are disabled; set ADMIN_TOKEN");
+    }
+}
+
+#[actix_web::test]
+async fn serves_the_effective_config_without_secrets() {
+    let app = common::init_app(state(Some(ADMIN))).await;
+    let (status, _) = refusal(&app, request("GET", "/admin/config", None)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let resp = test::call_service(&app, request("GET", "/admin/config", Some(ADMIN))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["admin_token"]["value"], "<redacted>");
+    assert_eq!(body["api_keys"]["value"], "<redacted>");
+    assert_eq!(body["max_retries"]["source"], "default");
+    let body = body.to_string();
+    assert!(!body.contains(ADMIN));
+    assert!(!body.contains(ALICE_SHA256));
+}
+
+#[actix_web::test]
+async fn the_token_is_kept_out_of_logged_settings() {
+    let settings = Settings::builder()
+        .admin_token(ADMIN.into())
+        .build()
+        .unwrap();
+    let logged = format!("{settings:?}");
+    assert!(logged.contains("AdminToken(<redacted>)"));
+    assert!(!logged.contains(ADMIN));
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::errors::{AppError, AttemptDetail, ErrorFormat, UpstreamErrorKind};
+use the_solana_api::stats::FailureKind;
+use the_solana_api::{AppState, Settings};
+
+const TIMEOUT: Duration = Duration::from_millis(150);
+
+fn slow_node() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 1}))
+            }),
+        );
+    })
+}
+
+fn state(nodes: &[(&str, &common::MockUpstream)], jsonrpc_errors: bool) -> AppState {
+    let validators = nodes
+        .iter()
+        .map(|(name, node)| node.validator(name, "frankfurt"))
+        .collect();
+    AppState::from_settings(
+        common::registry(validators),
+        Settings::builder()
+            .upstream_timeout(TIMEOUT)
+            .max_retries(2)
+            .jsonrpc_errors(jsonrpc_errors)
+            .build()
+            .unwrap(),
+    )
+}
+
+fn get_slot() -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+        .to_request()
+}
+
+#[actix_web::test]
+async fn lists_every_failed_attempt() {
+    let (a, b, c) = (slow_node(), slow_node(), slow_node());
+    let app = common::init_app(state(&[("a", &a), ("b", &b), ("c", &c)], false)).await;
+
+    let resp = test::call_service(&app, get_slot()).await;
+    assert_eq!(resp.status(), StatusCode::GATEWAY_TIMEOUT);
+    let body: Value = test::read_body_json(resp).await;
+    let details = &body["details"];
+    let attempts = details["attempts"].as_array().unwrap();
+    assert_eq!(attempts.len(), 3);
+
+    let names: HashSet<&str> = attempts
+        .iter()
+        .map(|attempt| attempt["validator"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, HashSet::from(["a", "b", "c"]));
+    for attempt in attempts {
+        assert_eq!(attempt["error"], "timeout");
+        assert_eq!(attempt["status"], Value::Null);
+        assert!(attempt["duration_ms"].as_u64().unwrap() >= TIMEOUT.as_millis() as u64);
+    }
+    assert_eq!(details["validator"], attempts[2]["validator"]);
+    assert!(details["elapsed_ms"].as_u64().unwrap() >= 3 * TIMEOUT.as_millis() as u64);
+
+    // Registry names only, never where the validators live.
+    assert!(!body.to_string().contains("127.0.0.1"));
+}
+
+#[actix_web::test]
+async fn json_rpc_errors_carry_the_attempts() {
+    let (a, b) = (slow_node(), slow_node());
+    let app = common::init_app(state(&[("a", &a), ("b", &b)], true)).await;
+
+    let body: Value = test::call_and_read_body_json(&app, get_slot()).await;
+    let data = &body["error"]["data"];
+    assert_eq!(data["attempts"], 2);
+    assert_eq!(data["attemptDetails"].as_array().unwrap().len(), 2);
+    assert_eq!(data["attemptDetails"][0]["error"], "timeout");
+    assert!(data["elapsedMs"].as_u64().unwrap() >= 2 * TIMEOUT.as_millis() as u64);
+}
+
+#[actix_web::test]
+async fn the_final_error_is_blamed_on_the_last_attempt() {
+    let attempts = vec![
+        AttemptDetail {
+            validator: "a".into(),
+            error: Some(FailureKind::RateLimited),
+            status: Some(429),
+            duration_ms: 3,
+        },
+        AttemptDetail {
+            validator: "b".into(),
+            error: None,
+            status: Some(200),
+            duration_ms: 5,
+        },
+    ];
+    let err = AppError::upstream("b", UpstreamErrorKind::InvalidResponse, "bad body")
+        .with_attempts(attempts, Duration::from_millis(9));
+
+    let body = err.body(ErrorFormat::Plain);
+    assert_eq!(
+        body["details"],
+        json!({
+            "validator": "b",
+            "attempts": [
+                {"validator": "a", "error": "rate_limited", "status": 429, "duration_ms": 3},
+                {"validator": "b", "error": "invalid_response", "status": 200, "duration_ms": 5}
+            ],
+            "elapsed_ms": 9
+        })
+    );
+
+    // Only upstream failures have attempts.
+    let err = AppError::BadRequest("bad".into()).with_attempts(Vec::new(), Duration::ZERO);
+    assert!(err.body(ErrorFormat::Plain).get("details").is_none());
+}
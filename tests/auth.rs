@@ -0,0 +1,313 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::auth::{ApiKey, ApiKeys};
+use the_solana_api::config::Settings;
+use the_solana_api::rate_limit::KeyRateLimit;
+use the_solana_api::{AppState, auth, signature};
+
+const ALICE: &str = "alice-secret-key";
+const MALLORY: &str = "mallory-old-key";
+
+fn keys() -> ApiKeys {
+    let file = format!(
+        "# issued keys\nalice,{}\n\nmallory,{},revoked\n",
+        common::sha256(ALICE),
+        common::sha256(MALLORY)
+    );
+    ApiKeys::new(ApiKeys::parse_file(&file).unwrap())
+}
+
+fn upstream() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 7}))
+            }),
+        );
+    })
+}
+
+fn state(upstream: &common::MockUpstream, settings: Settings) -> AppState {
+    AppState::from_settings(
+        common::registry(vec![upstream.validator("node", "lab")]),
+        Settings {
+            api_keys: Some(keys()),
+            ..settings
+        },
+    )
+}
+
+fn get_slot() -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+}
+
+async fn unauthorized<S>(app: &S, req: test::TestRequest) -> String
+where
+    S: actix_web::dev::Service<
+            actix_http::Request,
+            Response = actix_web::dev::ServiceResponse,
+            Error = actix_web::Error,
+        >,
+{
+    let resp = test::call_service(
+        app,
+        req.insert_header(("x-request-id", "auth-1")).to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "unauthorized");
+    assert_eq!(body["request_id"], "auth-1");
+    body["error"].as_str().unwrap().to_string()
+}
+
+#[actix_web::test]
+async fn accepts_a_valid_key() {
+    let upstream = upstream();
+    let state = state(&upstream, Settings::default());
+    let app = common::init_app(state.clone()).await;
+
+    let bearer = get_slot().insert_header(("authorization", format!("Bearer {ALICE}")));
+    let resp = test::call_service(&app, bearer.to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let query = get_slot().uri(&format!("/?api_key={ALICE}"));
+    let resp = test::call_service(&app, query.to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Stats are kept under the key's id, never the key.
+    let usage = state.stats().key_usage("alice").unwrap();
+    assert_eq!(usage.total.requests, 2);
+    assert!(state.stats().key_usage(ALICE).is_none());
+}
+
+#[actix_web::test]
+async fn rejects_missing_malformed_unknown_and_revoked_keys() {
+    let upstream = upstream();
+    let app = common::init_app(state(&upstream, Settings::default())).await;
+
+    let missing = unauthorized(&app, get_slot()).await;
+    assert!(missing.starts_with("missing API key"), "{missing}");
+
+    let malformed = unauthorized(
+        &app,
+        get_slot().insert_header(("authorization", format!("Basic {ALICE}"))),
+    )
+    .await;
+    assert!(
+        malformed.starts_with("malformed Authorization header"),
+        "{malformed}"
+    );
+
+    let unknown = unauthorized(
+        &app,
+        get_slot().insert_header(("authorization", "Bearer not-a-key")),
+    )
+    .await;
+    assert_eq!(unknown, "invalid API key");
+
+    let revoked = unauthorized(
+        &app,
+        get_slot().insert_header(("authorization", format!("Bearer {MALLORY}"))),
+    )
+    .await;
+    assert_eq!(revoked, "API key has been revoked");
+}
+
+#[actix_web::test]
+async fn protects_the_convenience_endpoints() {
+    let upstream = upstream();
+    let app = common::init_app(state(&upstream, Settings::default())).await;
+
+    for uri in [
+        "/slot",
+        "/blockhash",
+        "/upstream/health",
+        "/validators/primary/ping",
+    ] {
+        unauthorized(&app, test::TestRequest::get().uri(uri)).await;
+    }
+}
+
+// Answers with whatever of the client's credentials reached it.
+async fn credential_leaks(req: HttpRequest) -> HttpResponse {
+    let mut leaked: Vec<String> = [
+        "authorization",
+        "x-api-key-id",
+        "x-timestamp",
+        "x-signature",
+    ]
+    .into_iter()
+    .filter(|name| req.headers().contains_key(*name))
+    .map(str::to_string)
+    .collect();
+    if req.query_string().contains("api_key") {
+        leaked.push(req.query_string().to_string());
+    }
+    HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": leaked}))
+}
+
+#[actix_web::test]
+async fn credentials_are_not_forwarded_upstream() {
+    let upstream = common::spawn_upstream(|cfg| {
+        cfg.route("/", web::post().to(credential_leaks))
+            .route("/rpc", web::post().to(credential_leaks));
+    });
+    let settings = Settings {
+        hmac_secret: Some("server-side-signing-secret".to_string()),
+        ..Settings::default()
+    };
+    let app = common::init_app(state(&upstream, settings)).await;
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        .to_string();
+    let body = r#"{"jsonrpc":"2.0","id":1,"method":"getSlot"}"#;
+    let target = "/upstream/rpc?commitment=finalized";
+    let canonical = signature::canonical_string(&timestamp, "POST", target, body.as_bytes());
+    let signed = test::TestRequest::post()
+        .uri(target)
+        .insert_header(("content-type", "application/json"))
+        .insert_header(("x-api-key-id", "alice"))
+        .insert_header(("x-timestamp", timestamp))
+        .insert_header((
+            "x-signature",
+            signature::sign(
+                &signature::signing_secret("server-side-signing-secret", ALICE),
+                &canonical,
+            ),
+        ))
+        .set_payload(body);
+
+    for req in [
+        get_slot().insert_header(("authorization", format!("Bearer {ALICE}"))),
+        get_slot().uri(&format!(
+            "/upstream/rpc?api_key={ALICE}&commitment=finalized"
+        )),
+        get_slot().uri(&format!(
+            "/upstream/rpc?commitment=finalized&api_key={ALICE}"
+        )),
+        signed,
+    ] {
+        let resp = test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["result"], json!([]));
+    }
+}
+
+#[actix_web::test]
+async fn optional_endpoints_are_public_by_default() {
+    let upstream = upstream();
+    let app = common::init_app(state(&upstream, Settings::default())).await;
+
+    for uri in ["/health", "/", "/validators"] {
+        let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+    }
+}
+
+#[actix_web::test]
+async fn optional_endpoints_can_be_protected() {
+    let upstream = upstream();
+    let app = common::init_app(state(
+        &upstream,
+        Settings {
+            api_keys_public: vec!["/health".to_string()],
+            ..Settings::default()
+        },
+    ))
+    .await;
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/health").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    unauthorized(&app, test::TestRequest::get().uri("/")).await;
+    unauthorized(&app, test::TestRequest::get().uri("/validators")).await;
+
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri("/validators")
+            .insert_header(("authorization", format!("Bearer {ALICE}")))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn parses_key_lists() {
+    let hash = common::sha256(ALICE);
+    let keys =
+        ApiKeys::parse_inline(&format!("alice:{hash}, bob:{}", common::sha256("bob"))).unwrap();
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys[0], ApiKey::new("alice", &hash, false).unwrap());
+
+    assert_eq!(keys[0].limit, None);
+
+    let limited = ApiKeys::parse_inline(&format!("alice:{hash}:5, bob:{hash}:0.5:10")).unwrap();
+    assert_eq!(limited[0].limit, KeyRateLimit::new(5.0, None));
+    assert_eq!(limited[1].limit, KeyRateLimit::new(0.5, Some(10)));
+
+    let file = format!("alice,{hash},,2\nbob,{hash},revoked,1,4\ncarol,{hash}\n");
+    let keys = ApiKeys::parse_file(&file).unwrap();
+    assert_eq!(keys[0].limit, KeyRateLimit::new(2.0, None));
+    assert!(keys[1].revoked);
+    assert_eq!(keys[1].limit, KeyRateLimit::new(1.0, Some(4)));
+    assert_eq!(keys[2].limit, None);
+
+    for raw in [
+        "alice",
+        "alice:abc",
+        &format!(":{hash}"),
+        &format!("alice:{hash}:0"),
+        &format!("alice:{hash}:1:0"),
+        &format!("alice:{hash}:1:2:3"),
+    ] {
+        assert!(ApiKeys::parse_inline(raw).is_err(), "{raw}");
+    }
+    // Errors never repeat the line, which holds the hash.
+    for (raw, error) in [
+        (
+            format!("alice,{hash},expired"),
+            "line 2 (key alice): the third column must be empty or 'revoked'",
+        ),
+        (
+            format!("alice,{hash},revoked,extra"),
+            "line 2 (key alice): invalid rate limit",
+        ),
+        (
+            format!("alice,{hash},,,5"),
+            "line 2 (key alice): invalid rate limit",
+        ),
+        (
+            format!("alice,{hash},,1,2,a,b,c,d"),
+            "line 2 (key alice): invalid quota",
+        ),
+        (
+            format!("alice,{hash},,1,2,a,b,c,,extra"),
+            "line 2 (key alice): too many columns",
+        ),
+        (format!(",{hash}"), "line 2: missing key id"),
+        (
+            format!("alice,{}", &hash[1..]),
+            "line 2 (key alice): sha256 must be 64 hex digits",
+        ),
+        (hash.clone(), "line 2: expected at least an id and a sha256"),
+    ] {
+        assert_eq!(
+            ApiKeys::parse_file(&format!("# keys\n{raw}\n")),
+            Err(error.to_string())
+        );
+    }
+    assert!(auth::OPTIONAL_PATHS.contains(&"/"));
+}
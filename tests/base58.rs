@@ -0,0 +1,54 @@
+use the_solana_api::base58::{self, Base58Error};
+
+#[test]
+fn decodes_known_vectors() {
+    assert_eq!(base58::decode("2g").unwrap(), b"a");
+    assert_eq!(base58::decode("StV1DL6CwTryKyV").unwrap(), b"hello world");
+    assert_eq!(base58::decode("1").unwrap(), [0]);
+    assert_eq!(base58::decode("11StV1DL6CwTryKyV").unwrap()[..2], [0, 0]);
+}
+
+#[test]
+fn decodes_solana_pubkeys() {
+    assert_eq!(
+        base58::decode_exact("11111111111111111111111111111111", 32).unwrap(),
+        [0; 32]
+    );
+    for pubkey in [
+        "Vote111111111111111111111111111111111111111",
+        "SysvarC1ock11111111111111111111111111111111",
+        "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+    ] {
+        assert_eq!(
+            base58::decode_exact(pubkey, 32).unwrap().len(),
+            32,
+            "{pubkey}"
+        );
+    }
+}
+
+#[test]
+fn rejects_invalid_input() {
+    assert_eq!(base58::decode(""), Err(Base58Error::Empty));
+    for (input, character, position) in [
+        ("0abc", '0', 0),
+        ("abOc", 'O', 2),
+        ("abcI", 'I', 3),
+        ("l", 'l', 0),
+    ] {
+        assert_eq!(
+            base58::decode(input),
+            Err(Base58Error::InvalidCharacter {
+                character,
+                position
+            })
+        );
+    }
+    assert_eq!(
+        base58::decode_exact("StV1DL6CwTryKyV", 32),
+        Err(Base58Error::WrongLength {
+            expected: 32,
+            actual: 11
+        })
+    );
+}
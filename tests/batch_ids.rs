@@ -0,0 +1,176 @@
+mod common;
+
+use actix_web::{HttpResponse, http::StatusCode, test, web};
+use rand::seq::SliceRandom;
+use serde_json::{Value, json};
+
+use the_solana_api::AppState;
+use the_solana_api::batch::{self, BatchIdCheck, BatchIdError};
+use the_solana_api::config::Settings;
+
+fn batch_request(ids: &[Value]) -> Value {
+    Value::Array(
+        ids.iter()
+            .map(|id| json!({"jsonrpc": "2.0", "id": id, "method": "getSlot"}))
+            .collect(),
+    )
+}
+
+fn answer(id: &Value) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "result": id.to_string()})
+}
+
+// Answers every batch entry, shuffled, optionally dropping the last one.
+fn shuffling_upstream(drop_last: bool) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        cfg.route(
+            "/",
+            web::post().to(move |body: web::Json<Vec<Value>>| async move {
+                let mut answers: Vec<Value> =
+                    body.iter().map(|entry| answer(&entry["id"])).collect();
+                if drop_last {
+                    answers.pop();
+                }
+                answers.shuffle(&mut rand::thread_rng());
+                HttpResponse::Ok().json(answers)
+            }),
+        );
+    })
+}
+
+fn state(node: &common::MockUpstream, check: BatchIdCheck) -> AppState {
+    let settings = Settings::builder().batch_id_check(check).build().unwrap();
+    AppState::from_settings(
+        common::registry(vec![node.validator("primary", "lab")]),
+        settings,
+    )
+}
+
+#[actix_web::test]
+async fn request_ids_skip_notifications() {
+    let body = json!([
+        {"jsonrpc": "2.0", "id": 1, "method": "getSlot"},
+        {"jsonrpc": "2.0", "method": "getSlot"},
+        {"jsonrpc": "2.0", "id": "two", "method": "getSlot"},
+        7
+    ]);
+    let ids = batch::request_ids(body.to_string().as_bytes()).unwrap();
+    assert_eq!(ids, vec![json!(1), json!("two"), Value::Null]);
+    assert_eq!(batch::request_ids(br#"{"id": 1}"#), None);
+}
+
+#[actix_web::test]
+async fn reorder_restores_request_order_for_any_shuffle() {
+    let mut rng = rand::thread_rng();
+    for size in 1..=12 {
+        let ids: Vec<Value> = (0..size)
+            .map(|n| {
+                if n % 3 == 0 {
+                    json!(format!("id-{n}"))
+                } else {
+                    json!(n)
+                }
+            })
+            .collect();
+        let expected: Vec<Value> = ids.iter().map(answer).collect();
+
+        for _ in 0..20 {
+            let mut shuffled = expected.clone();
+            shuffled.shuffle(&mut rng);
+            let body = serde_json::to_vec(&shuffled).unwrap();
+
+            let ordered = batch::reorder(&ids, &body).unwrap();
+            let ordered: Vec<Value> = serde_json::from_slice(&ordered).unwrap();
+            assert_eq!(ordered, expected);
+        }
+    }
+}
+
+#[actix_web::test]
+async fn reorder_keeps_duplicate_ids_in_sequence() {
+    let ids = vec![json!(1), json!(1), json!(2)];
+    let body = json!([
+        {"id": 2, "result": "c"},
+        {"id": 1, "result": "a"},
+        {"id": 1, "result": "b"}
+    ]);
+    let ordered = batch::reorder(&ids, body.to_string().as_bytes()).unwrap();
+    let ordered: Vec<Value> = serde_json::from_slice(&ordered).unwrap();
+    let results: Vec<&Value> = ordered.iter().map(|entry| &entry["result"]).collect();
+    assert_eq!(results, ["a", "b", "c"]);
+}
+
+#[actix_web::test]
+async fn reorder_reports_missing_and_unexpected_ids() {
+    let ids = vec![json!(1), json!(2), json!(3)];
+    let body = json!([{"id": 3}, {"id": "1"}, {"id": 2}, {"id": 4}]);
+
+    let Err(BatchIdError::Mismatch(mismatch)) = batch::reorder(&ids, body.to_string().as_bytes())
+    else {
+        panic!("expected a mismatch");
+    };
+    assert_eq!(mismatch.missing, vec![json!(1)]);
+    let mut unexpected = mismatch.unexpected.clone();
+    unexpected.sort_by_key(Value::to_string);
+    assert_eq!(unexpected, vec![json!("1"), json!(4)]);
+
+    assert_eq!(
+        batch::reorder(&ids, br#"{"error": {"code": -32600}}"#),
+        Err(BatchIdError::NotABatch)
+    );
+}
+
+#[actix_web::test]
+async fn shuffled_batch_answers_are_reassembled_in_request_order() {
+    let node = shuffling_upstream(false);
+    let app = common::init_app(state(&node, BatchIdCheck::Log)).await;
+
+    let ids: Vec<Value> = (0..16).map(|n| json!(n)).collect();
+    let response = test::call_service(
+        &app,
+        test::TestRequest::post()
+            .uri("/")
+            .set_json(batch_request(&ids))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body: Vec<Value> = test::read_body_json(response).await;
+    let returned: Vec<Value> = body.iter().map(|entry| entry["id"].clone()).collect();
+    assert_eq!(returned, ids);
+}
+
+#[actix_web::test]
+async fn missing_batch_answers_are_relayed_unless_strict() {
+    let node = shuffling_upstream(true);
+    let ids = vec![json!(1), json!(2), json!(3)];
+
+    let app = common::init_app(state(&node, BatchIdCheck::Log)).await;
+    let response = test::call_service(
+        &app,
+        test::TestRequest::post()
+            .uri("/")
+            .set_json(batch_request(&ids))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Vec<Value> = test::read_body_json(response).await;
+    assert_eq!(body.len(), 2);
+
+    let app = common::init_app(state(&node, BatchIdCheck::Strict)).await;
+    let response = test::call_service(
+        &app,
+        test::TestRequest::post()
+            .uri("/")
+            .set_json(batch_request(&ids))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    let body: Value = test::read_body_json(response).await;
+    let error = body["error"].as_str().unwrap();
+    assert!(error.contains("primary"), "{error}");
+    assert!(error.contains("missing [3]"), "{error}");
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::{AppState, Settings};
+
+struct Chain {
+    slot: AtomicU64,
+    blockhash_calls: AtomicUsize,
+}
+
+fn chain_upstream(chain: Arc<Chain>) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let chain = chain.clone();
+        cfg.route(
+            "/",
+            web::post().to(move |body: web::Json<Value>| {
+                let slot = chain.slot.load(Ordering::SeqCst);
+                let result = match body["method"].as_str() {
+                    Some("getSlot") => json!(slot),
+                    Some("getRecentBlockhash") => {
+                        chain.blockhash_calls.fetch_add(1, Ordering::SeqCst);
+                        json!({
+                            "context": {"slot": slot},
+                            "value": {"blockhash": format!("hash-{slot}"), "feeCalculator": {"lamportsPerSignature": 5000}},
+                        })
+                    }
+                    _ => {
+                        chain.blockhash_calls.fetch_add(1, Ordering::SeqCst);
+                        json!({
+                            "context": {"slot": slot},
+                            "value": {"blockhash": format!("hash-{slot}"), "lastValidBlockHeight": slot + 150},
+                        })
+                    }
+                };
+                async move { HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": body["id"], "result": result})) }
+            }),
+        );
+    })
+}
+
+fn setup(ttl: Duration) -> (Arc<Chain>, common::MockUpstream, AppState) {
+    let chain = Arc::new(Chain {
+        slot: AtomicU64::new(100),
+        blockhash_calls: AtomicUsize::new(0),
+    });
+    let upstream = chain_upstream(chain.clone());
+    let settings = Settings::builder()
+        .response_cache(true)
+        .cache_short_methods(Vec::new())
+        .cache_blockhash_ttl(ttl)
+        .cache_blockhash_max_slot_lag(10)
+        .build()
+        .unwrap();
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("primary", "lab")]),
+        settings,
+    );
+    (chain, upstream, state)
+}
+
+async fn rpc(
+    app: &impl actix_web::dev::Service<
+        actix_http::Request,
+        Response = actix_web::dev::ServiceResponse,
+        Error = actix_web::Error,
+    >,
+    method: &str,
+    params: Value,
+) -> Value {
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 9, "method": method, "params": params}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(app, req).await;
+    assert_eq!(body["id"], 9);
+    body["result"].clone()
+}
+
+async fn blockhash(
+    app: &impl actix_web::dev::Service<
+        actix_http::Request,
+        Response = actix_web::dev::ServiceResponse,
+        Error = actix_web::Error,
+    >,
+    method: &str,
+) -> Value {
+    rpc(app, method, json!([])).await["value"]["blockhash"].clone()
+}
+
+#[actix_web::test]
+async fn refreshes_once_the_observed_slot_moves_past_the_margin() {
+    let (chain, _upstream, state) = setup(Duration::from_secs(60));
+    let app = common::init_app(state).await;
+
+    assert_eq!(blockhash(&app, "getLatestBlockhash").await, "hash-100");
+
+    chain.slot.store(110, Ordering::SeqCst);
+    assert_eq!(rpc(&app, "getSlot", json!([])).await, 110);
+    assert_eq!(blockhash(&app, "getLatestBlockhash").await, "hash-100");
+    assert_eq!(chain.blockhash_calls.load(Ordering::SeqCst), 1);
+
+    chain.slot.store(111, Ordering::SeqCst);
+    assert_eq!(rpc(&app, "getSlot", json!([])).await, 111);
+    assert_eq!(blockhash(&app, "getLatestBlockhash").await, "hash-111");
+    assert_eq!(chain.blockhash_calls.load(Ordering::SeqCst), 2);
+}
+
+#[actix_web::test]
+async fn expires_after_the_ttl_and_keys_by_commitment() {
+    let (chain, _upstream, state) = setup(Duration::from_millis(100));
+    let app = common::init_app(state).await;
+
+    assert_eq!(blockhash(&app, "getLatestBlockhash").await, "hash-100");
+    chain.slot.store(102, Ordering::SeqCst);
+    assert_eq!(blockhash(&app, "getLatestBlockhash").await, "hash-100");
+
+    let processed = rpc(
+        &app,
+        "getLatestBlockhash",
+        json!([{"commitment": "processed"}]),
+    )
+    .await;
+    assert_eq!(processed["value"]["blockhash"], "hash-102");
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(blockhash(&app, "getLatestBlockhash").await, "hash-102");
+    assert_eq!(chain.blockhash_calls.load(Ordering::SeqCst), 3);
+}
+
+#[actix_web::test]
+async fn deprecated_recent_blockhash_shares_the_logic() {
+    let (chain, _upstream, state) = setup(Duration::from_secs(60));
+    let app = common::init_app(state).await;
+
+    assert_eq!(blockhash(&app, "getRecentBlockhash").await, "hash-100");
+    assert_eq!(blockhash(&app, "getRecentBlockhash").await, "hash-100");
+    assert_eq!(chain.blockhash_calls.load(Ordering::SeqCst), 1);
+
+    // The fresh getLatestBlockhash response advances the observed slot too.
+    chain.slot.store(120, Ordering::SeqCst);
+    assert_eq!(blockhash(&app, "getLatestBlockhash").await, "hash-120");
+    assert_eq!(blockhash(&app, "getRecentBlockhash").await, "hash-120");
+    assert_eq!(chain.blockhash_calls.load(Ordering::SeqCst), 3);
+}
+
+#[actix_web::test]
+async fn extra_config_fields_bypass_the_cache() {
+    let (chain, _upstream, state) = setup(Duration::from_secs(60));
+    let app = common::init_app(state).await;
+
+    let params = json!([{"commitment": "confirmed", "minContextSlot": 90}]);
+    rpc(&app, "getLatestBlockhash", params.clone()).await;
+    rpc(&app, "getLatestBlockhash", params).await;
+    assert_eq!(chain.blockhash_calls.load(Ordering::SeqCst), 2);
+}
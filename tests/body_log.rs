@@ -0,0 +1,230 @@
+mod common;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+use the_solana_api::AppState;
+use the_solana_api::body_log::{self, REDACTED, RedactRule};
+use the_solana_api::config::Settings;
+
+const SECRET_TX: &str = "4hXTCkRzt9WyecNzV1XPgCDfGAZzQKNxLXgynz5QDuWWPSAZBZSHptvWRL3BjCvzUXRdKvHL2b7yGrRQcWyaqsaBCncVG7BFggS8w9snUts67BSh3EqKpXLUm5UMHfD7ZBe9GhARjbNQMLJ1QD3Spr6oMTBU6EhdB4RD8CP2xUxr2u3d6fos36PD98XS6oX8TQjLpsMwncs5DAMiD4nNnR8NBfyghGCWvCVifVwvA8B8TJxE1aiyiv2L429BCWfyzAme5sZW8rDb14NeCQHhZbtNqfXhcp2tAnaAT";
+const TOKEN: &str = "Bearer top-secret-token";
+
+fn rules(extra: &[&str]) -> Vec<RedactRule> {
+    let mut rules = body_log::default_rules();
+    rules.extend(extra.iter().map(|rule| rule.parse().unwrap()));
+    rules
+}
+
+fn send_transaction() -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "sendTransaction",
+        "params": [SECRET_TX, {"encoding": "base58"}]
+    })
+}
+
+#[actix_web::test]
+async fn redacts_the_transaction_of_send_transaction() {
+    let body = send_transaction().to_string();
+    let rendered = body_log::render(body.as_bytes(), &rules(&[]), 4096);
+    assert!(!rendered.contains(SECRET_TX));
+
+    let value: Value = serde_json::from_str(&rendered).unwrap();
+    assert_eq!(value["params"][0], REDACTED);
+    assert_eq!(value["params"][1]["encoding"], "base58");
+    assert_eq!(value["method"], "sendTransaction");
+}
+
+#[actix_web::test]
+async fn method_rules_leave_other_methods_alone() {
+    let body = json!({"jsonrpc": "2.0", "id": 1, "method": "getBalance", "params": ["pubkey"]});
+    let rendered = body_log::render(body.to_string().as_bytes(), &rules(&[]), 4096);
+    let value: Value = serde_json::from_str(&rendered).unwrap();
+    assert_eq!(value["params"][0], "pubkey");
+}
+
+#[actix_web::test]
+async fn redacts_authorization_fields_at_any_depth() {
+    let body = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getSlot",
+        "params": [{"headers": {"Authorization": TOKEN}}],
+        "authorization": TOKEN
+    });
+    let rendered = body_log::render(body.to_string().as_bytes(), &rules(&[]), 4096);
+    assert!(!rendered.contains(TOKEN));
+    let value: Value = serde_json::from_str(&rendered).unwrap();
+    assert_eq!(value["authorization"], REDACTED);
+    assert_eq!(value["params"][0]["headers"]["Authorization"], REDACTED);
+}
+
+#[actix_web::test]
+async fn redacts_each_batch_element_by_its_own_method() {
+    let body = json!([
+        send_transaction(),
+        {"jsonrpc": "2.0", "id": 2, "method": "getBalance", "params": ["pubkey"]}
+    ]);
+    let rendered = body_log::render(body.to_string().as_bytes(), &rules(&[]), 4096);
+    assert!(!rendered.contains(SECRET_TX));
+    let value: Value = serde_json::from_str(&rendered).unwrap();
+    assert_eq!(value[0]["params"][0], REDACTED);
+    assert_eq!(value[1]["params"][0], "pubkey");
+}
+
+#[actix_web::test]
+async fn responses_are_redacted_with_the_request_method() {
+    let body = json!({"jsonrpc": "2.0", "id": 1, "result": {"value": {"data": "secret-data"}}});
+    let rules = rules(&["getAccountInfo:result.value.data"]);
+    let rendered =
+        body_log::render_for_method(body.to_string().as_bytes(), "getAccountInfo", &rules, 4096);
+    assert!(!rendered.contains("secret-data"));
+
+    let rendered =
+        body_log::render_for_method(body.to_string().as_bytes(), "getSlot", &rules, 4096);
+    assert!(rendered.contains("secret-data"));
+}
+
+#[actix_web::test]
+async fn truncates_after_redacting() {
+    let body = send_transaction().to_string();
+    let rendered = body_log::render(body.as_bytes(), &rules(&[]), 20);
+    let (kept, cut) = rendered.split_once("...[truncated ").unwrap();
+    assert_eq!(kept.len(), 20);
+    assert!(cut.ends_with(" bytes]"));
+    assert!(!rendered.contains(SECRET_TX));
+}
+
+#[actix_web::test]
+async fn falls_back_to_truncated_text_for_invalid_json() {
+    let body = "not json ééé and more text";
+    let rendered = body_log::render(body.as_bytes(), &rules(&[]), 11);
+    // Cut on a character boundary, never inside `é`.
+    assert_eq!(
+        rendered,
+        format!("not json é...[truncated {} bytes]", body.len() - 11)
+    );
+
+    let rendered = body_log::render(b"short", &rules(&[]), 4096);
+    assert_eq!(rendered, "short");
+}
+
+#[actix_web::test]
+async fn parses_rules() {
+    for valid in [
+        "authorization",
+        "params[0]",
+        "sendTransaction:params[0]",
+        "result.value.data",
+        "params[0][1].key",
+    ] {
+        assert!(valid.parse::<RedactRule>().is_ok(), "{valid}");
+    }
+    for invalid in [
+        "",
+        ":params",
+        "sendTransaction:",
+        "params[x]",
+        "params[0",
+        "params]",
+        "a..b",
+    ] {
+        assert!(invalid.parse::<RedactRule>().is_err(), "{invalid}");
+    }
+}
+
+type Lines = Arc<Mutex<Vec<HashMap<String, String>>>>;
+
+struct Capture(Lines);
+
+struct Fields(HashMap<String, String>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
+
+impl<S: Subscriber> Layer<S> for Capture {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        if event.metadata().target() == body_log::TARGET {
+            let mut fields = Fields(HashMap::new());
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+    }
+}
+
+fn echo() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                HttpResponse::Ok().json(json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "result": "signature",
+                    "authorization": TOKEN
+                }))
+            }),
+        );
+    })
+}
+
+async fn send(log_bodies: bool) -> Vec<HashMap<String, String>> {
+    let node = echo();
+    let state = AppState::from_settings(
+        common::registry(vec![node.validator("primary", "frankfurt")]),
+        Settings {
+            log_bodies,
+            ..Settings::default()
+        },
+    );
+    let lines = Lines::default();
+    let subscriber = tracing_subscriber::registry().with(Capture(lines.clone()));
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let app = common::init_app(state).await;
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_json(send_transaction())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    lines.lock().unwrap().clone()
+}
+
+#[actix_web::test]
+async fn logged_bodies_never_contain_secrets() {
+    let lines = send(true).await;
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["direction"], "request");
+    assert_eq!(lines[1]["direction"], "response");
+    for line in &lines {
+        assert_eq!(line["rpc_method"], "sendTransaction");
+        assert!(line["body"].contains(REDACTED));
+        for value in line.values() {
+            assert!(!value.contains(SECRET_TX));
+            assert!(!value.contains(TOKEN));
+        }
+    }
+}
+
+#[actix_web::test]
+async fn bodies_are_not_logged_by_default() {
+    assert!(send(false).await.is_empty());
+}
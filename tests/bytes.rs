@@ -0,0 +1,111 @@
+mod common;
+
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::config::Settings;
+use the_solana_api::{AppState, Validator};
+
+const ANSWER_SIZE: usize = 3_000;
+const STREAM_SIZE: usize = 256 * 1024;
+
+fn node() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                let padding = "x".repeat(ANSWER_SIZE - 36);
+                let body = format!(r#"{{"jsonrpc":"2.0","id":1,"result":"{padding}"}}"#);
+                assert_eq!(body.len(), ANSWER_SIZE);
+                HttpResponse::Ok()
+                    .content_type("application/json")
+                    .body(body)
+            }),
+        )
+        .route(
+            "/snapshot.tar.bz2",
+            web::get().to(|| async {
+                HttpResponse::Ok()
+                    .content_type("application/x-bzip2")
+                    .body(vec![7u8; STREAM_SIZE])
+            }),
+        );
+    })
+}
+
+fn state(validators: Vec<Validator>) -> AppState {
+    AppState::from_settings(
+        common::registry(validators),
+        Settings::builder().metrics_enabled(true).build().unwrap(),
+    )
+}
+
+async fn traffic<S>(app: &S) -> Value
+where
+    S: actix_web::dev::Service<
+            actix_http::Request,
+            Response = actix_web::dev::ServiceResponse,
+            Error = actix_web::Error,
+        >,
+{
+    let body: Value =
+        test::call_and_read_body_json(app, test::TestRequest::get().uri("/stats").to_request())
+            .await;
+    body["validators"][0].clone()
+}
+
+#[actix_web::test]
+async fn counts_exact_bytes_of_a_proxied_call() {
+    let upstream = node();
+    let app = common::init_app(state(vec![upstream.validator("primary", "frankfurt")])).await;
+
+    let request = json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}).to_string();
+    let req = test::TestRequest::post()
+        .uri("/")
+        .insert_header(("content-type", "application/json"))
+        .set_payload(request.clone())
+        .to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    assert_eq!(body.len(), ANSWER_SIZE);
+
+    let entry = traffic(&app).await;
+    assert_eq!(entry["bytes_sent"], request.len());
+    assert_eq!(entry["bytes_received"], ANSWER_SIZE);
+    assert_eq!(entry["bytes_per_sec"]["window_secs"], 300);
+    assert_eq!(
+        entry["bytes_per_sec"]["received"].as_f64().unwrap(),
+        ANSWER_SIZE as f64 / 300.0
+    );
+
+    let resp =
+        test::call_service(&app, test::TestRequest::get().uri("/metrics").to_request()).await;
+    let text = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(text.contains("# TYPE solana_api_upstream_bytes_sent_total counter"));
+    assert!(text.contains(&format!(
+        "solana_api_upstream_bytes_sent_total{{validator=\"primary\"}} {}\n",
+        request.len()
+    )));
+    assert!(text.contains(&format!(
+        "solana_api_upstream_bytes_received_total{{validator=\"primary\"}} {ANSWER_SIZE}\n"
+    )));
+}
+
+#[actix_web::test]
+async fn counts_streamed_bodies_as_they_are_relayed() {
+    let upstream = node();
+    let app = common::init_app(state(vec![upstream.validator("primary", "frankfurt")])).await;
+
+    let req = test::TestRequest::get()
+        .uri("/upstream/snapshot.tar.bz2")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    // Nothing is counted before the client reads the stream.
+    assert_eq!(traffic(&app).await["bytes_received"], 0);
+
+    let body = test::read_body(resp).await;
+    assert_eq!(body.len(), STREAM_SIZE);
+    let entry = traffic(&app).await;
+    assert_eq!(entry["bytes_received"], STREAM_SIZE);
+    assert_eq!(entry["bytes_sent"], 0);
+}
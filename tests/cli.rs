@@ -0,0 +1,379 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use the_solana_api::Settings;
+use the_solana_api::cli::{self, Args, CliError, Command};
+use the_solana_api::config::ConfigError;
+
+const INLINE_REGISTRY: &str = "name,ip,rpc_port,location,protocol\nnode,127.0.0.1,8899,lab,http\n";
+
+fn args(list: &[&str]) -> Args {
+    Args::parse(list.iter().copied()).unwrap()
+}
+
+fn env(vars: &[(&str, &str)]) -> BTreeMap<String, String> {
+    let csv = concat!(env!("CARGO_MANIFEST_DIR"), "/config/validators.example.csv");
+    let mut env = BTreeMap::from([("VALIDATORS_CSV".to_string(), csv.to_string())]);
+    env.extend(
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string())),
+    );
+    env
+}
+
+fn config_file(name: &str, contents: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("solana-api-cli-{name}-{}.toml", std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn parses_setting_flags() {
+    let parsed = args(&[
+        "--bind",
+        "127.0.0.1:8080",
+        "--timeout-ms=2500",
+        "--config",
+        "proxy.toml",
+        "--env-file",
+        "local.env",
+        "--set",
+        "metrics_enabled=false",
+        "--set=MAX_RETRIES=4",
+        "--strict-config",
+    ]);
+    assert_eq!(parsed.config, Some(PathBuf::from("proxy.toml")));
+    assert_eq!(parsed.env_file, Some(PathBuf::from("local.env")));
+    assert_eq!(
+        parsed.settings,
+        BTreeMap::from(
+            [
+                ("BIND_ADDRESS", "127.0.0.1:8080"),
+                ("UPSTREAM_TIMEOUT_MS", "2500"),
+                ("METRICS_ENABLED", "false"),
+                ("MAX_RETRIES", "4"),
+                ("CONFIG_STRICT", "true"),
+            ]
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+        )
+    );
+    assert!(!parsed.help && !parsed.version);
+    assert!(args(&["-h"]).help);
+    assert!(args(&["--version"]).version);
+    assert_eq!(args(&[]), Args::default());
+}
+
+#[test]
+fn rejects_invalid_command_lines() {
+    for (list, expected) in [
+        (
+            &["--port", "80"][..],
+            CliError::UnknownArgument("--port".to_string()),
+        ),
+        (
+            &["serve"][..],
+            CliError::UnknownArgument("serve".to_string()),
+        ),
+        (
+            &["--bind"][..],
+            CliError::MissingValue("--bind".to_string()),
+        ),
+        (
+            &["--bind", "a", "--bind=b"][..],
+            CliError::Repeated("--bind".to_string()),
+        ),
+        (
+            &["--bind", "a", "--set", "bind_address=b"][..],
+            CliError::Repeated("BIND_ADDRESS".to_string()),
+        ),
+        (
+            &[
+                "--validators",
+                "a.csv",
+                "--validators-inline",
+                INLINE_REGISTRY,
+            ][..],
+            CliError::Conflict(
+                "--validators".to_string(),
+                "--validators-inline".to_string(),
+            ),
+        ),
+        (
+            &["--validators", "https://example.com/validators.csv"][..],
+            CliError::InvalidValue {
+                flag: "--validators".to_string(),
+                value: "https://example.com/validators.csv".to_string(),
+            },
+        ),
+        (
+            &["--set", "MAX_RETRIES"][..],
+            CliError::InvalidValue {
+                flag: "--set".to_string(),
+                value: "MAX_RETRIES".to_string(),
+            },
+        ),
+        (
+            &["--set", "CONFIG_FILE=a.toml"][..],
+            CliError::InvalidValue {
+                flag: "--set".to_string(),
+                value: "CONFIG_FILE=a.toml".to_string(),
+            },
+        ),
+    ] {
+        assert_eq!(Args::parse(list.iter().copied()), Err(expected), "{list:?}");
+    }
+}
+
+#[test]
+fn parses_the_validate_subcommand() {
+    let parsed = args(&["validate", "--file", "validators.csv", "--json"]);
+    assert_eq!(
+        parsed.command,
+        Some(Command::Validate {
+            file: Some(PathBuf::from("validators.csv")),
+            json: true,
+        })
+    );
+    assert!(parsed.settings.is_empty());
+    assert_eq!(
+        args(&["validate"]).command,
+        Some(Command::Validate {
+            file: None,
+            json: false,
+        })
+    );
+    assert!(args(&["validate", "-h"]).help);
+
+    for (list, expected) in [
+        (
+            &["validate", "--bind", "a"][..],
+            CliError::UnknownArgument("--bind".to_string()),
+        ),
+        (
+            &["validate", "--file"][..],
+            CliError::MissingValue("--file".to_string()),
+        ),
+        (
+            &["validate", "--json", "--json"][..],
+            CliError::Repeated("--json".to_string()),
+        ),
+        // Only as the first argument.
+        (
+            &["--json", "validate"][..],
+            CliError::UnknownArgument("--json".to_string()),
+        ),
+    ] {
+        assert_eq!(Args::parse(list.iter().copied()), Err(expected), "{list:?}");
+    }
+}
+
+#[test]
+fn parses_the_probe_subcommand() {
+    let parsed = args(&[
+        "probe",
+        "--validators",
+        "fleet.csv",
+        "--node-timeout-ms=500",
+        "--concurrency",
+        "4",
+        "--min-healthy",
+        "3",
+        "--json",
+    ]);
+    assert_eq!(
+        parsed.command,
+        Some(Command::Probe {
+            json: true,
+            min_healthy: Some(3),
+        })
+    );
+    assert_eq!(parsed.settings["VALIDATORS_CSV"], "fleet.csv");
+    assert_eq!(parsed.settings["FLEET_NODE_TIMEOUT_MS"], "500");
+    assert_eq!(parsed.settings["FLEET_CONCURRENCY"], "4");
+
+    for (list, expected) in [
+        (
+            &["--concurrency", "4"][..],
+            CliError::UnknownArgument("--concurrency".to_string()),
+        ),
+        (
+            &["--json"][..],
+            CliError::UnknownArgument("--json".to_string()),
+        ),
+        (
+            &["probe", "--min-healthy", "most"][..],
+            CliError::InvalidValue {
+                flag: "--min-healthy".to_string(),
+                value: "most".to_string(),
+            },
+        ),
+    ] {
+        assert_eq!(Args::parse(list.iter().copied()), Err(expected), "{list:?}");
+    }
+}
+
+#[test]
+fn parses_the_print_config_subcommand() {
+    let parsed = args(&["print-config", "--max-retries", "2", "--toml"]);
+    assert_eq!(parsed.command, Some(Command::PrintConfig { toml: true }));
+    assert_eq!(parsed.settings["MAX_RETRIES"], "2");
+    assert_eq!(
+        args(&["print-config"]).command,
+        Some(Command::PrintConfig { toml: false })
+    );
+    assert_eq!(
+        Args::parse(["--toml"]),
+        Err(CliError::UnknownArgument("--toml".to_string()))
+    );
+}
+
+#[test]
+fn help_lists_every_flag() {
+    let help = cli::help();
+    for flag in [
+        "--config <FILE>",
+        "--env-file <FILE>",
+        "--strict-config",
+        "--bind <ADDR>",
+        "--validators <PATH>",
+        "--validators-inline <CSV>",
+        "--timeout-ms <MS>",
+        "--max-retries <N>",
+        "--log-format <FORMAT>",
+        "--set <NAME=VALUE>",
+        "-h, --help",
+        "-V, --version",
+        "validate",
+        "--json",
+        "probe",
+        "--node-timeout-ms <MS>",
+        "--min-healthy <N>",
+        "print-config",
+        "--toml",
+    ] {
+        assert!(help.contains(flag), "{flag} missing from\n{help}");
+    }
+    assert!(help.contains("[env: UPSTREAM_TIMEOUT_MS]"));
+}
+
+#[test]
+fn flags_win_over_the_environment_and_the_file() {
+    let path = config_file(
+        "precedence",
+        "bind_address = \"file:1\"\nmax_retries = 7\nupstream_timeout_ms = 100\n",
+    );
+    let file = Some(path.as_path());
+    let defaults = Settings::default();
+
+    // (flag, environment, file) -> the bind address that wins.
+    for (flag, env_value, file, expected) in [
+        (None, None, None, defaults.bind_address.as_str()),
+        (None, None, file, "file:1"),
+        (None, Some("env:1"), file, "env:1"),
+        (Some("flag:1"), Some("env:1"), file, "flag:1"),
+        (Some("flag:1"), None, None, "flag:1"),
+        (Some("flag:1"), Some("env:1"), None, "flag:1"),
+    ] {
+        let list: Vec<&str> = flag.map(|flag| vec!["--bind", flag]).unwrap_or_default();
+        let vars: Vec<_> = env_value
+            .map(|value| ("BIND_ADDRESS", value))
+            .into_iter()
+            .collect();
+        let settings = Settings::from_sources(&args(&list), &env(&vars), file).unwrap();
+        assert_eq!(
+            settings.bind_address, expected,
+            "{flag:?} {env_value:?} {file:?}"
+        );
+    }
+
+    // Each setting falls through on its own.
+    let settings = Settings::from_sources(
+        &args(&["--max-retries", "1"]),
+        &env(&[("UPSTREAM_TIMEOUT_MS", "200")]),
+        file,
+    )
+    .unwrap();
+    assert_eq!(settings.max_retries, 1);
+    assert_eq!(settings.upstream_timeout, Duration::from_millis(200));
+    assert_eq!(settings.bind_address, "file:1");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn flag_values_are_checked_like_the_environment() {
+    let err =
+        Settings::from_sources(&args(&["--timeout-ms", "soon"]), &env(&[]), None).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidNumber { ref name, .. } if name == "UPSTREAM_TIMEOUT_MS"),
+        "{err}"
+    );
+
+    let file = config_file("strict", "max_retires = 2\n");
+    let settings = Settings::from_sources(&args(&[]), &env(&[]), Some(&file)).unwrap();
+    assert_eq!(settings.unknown_config_keys, ["max_retires"]);
+    let err =
+        Settings::from_sources(&args(&["--strict-config"]), &env(&[]), Some(&file)).unwrap_err();
+    assert!(matches!(err, ConfigError::UnknownConfigKeys(_)), "{err}");
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn reads_an_inline_registry() {
+    let missing = [("VALIDATORS_CSV", "/nonexistent/validators.csv")];
+    let err = Settings::from_sources(&args(&[]), &env(&missing), None).unwrap_err();
+    assert!(matches!(err, ConfigError::MissingValidatorsCsv(_)));
+
+    let settings = Settings::from_sources(
+        &args(&["--validators-inline", INLINE_REGISTRY]),
+        &env(&missing),
+        None,
+    )
+    .unwrap();
+    assert_eq!(settings.registry_source(), "inline");
+    let registry = settings.load_registry().unwrap();
+    assert_eq!(registry.validators()[0].name(), "node");
+    assert_eq!(
+        Settings::default().registry_source(),
+        Path::new("config/validators.csv").display().to_string()
+    );
+}
+
+#[test]
+fn the_stronger_source_picks_the_registry() {
+    let csv = concat!(env!("CARGO_MANIFEST_DIR"), "/config/validators.example.csv");
+    let inline = [("VALIDATORS_INLINE", INLINE_REGISTRY)];
+
+    let settings =
+        Settings::from_sources(&args(&["--validators", csv]), &env(&inline), None).unwrap();
+    assert_eq!(
+        settings.registry_source(),
+        Path::new(csv).display().to_string()
+    );
+    assert!(settings.validators_inline.is_none());
+
+    let file = config_file(
+        "inline",
+        &format!("validators_inline = {INLINE_REGISTRY:?}\n"),
+    );
+    let settings =
+        Settings::from_sources(&args(&["--validators", csv]), &BTreeMap::new(), Some(&file))
+            .unwrap();
+    assert_eq!(
+        settings.registry_source(),
+        Path::new(csv).display().to_string()
+    );
+    std::fs::remove_file(&file).unwrap();
+
+    // From the same place, or the inline text set more directly, it wins.
+    let settings = Settings::from_sources(&args(&[]), &env(&inline), None).unwrap();
+    assert_eq!(settings.registry_source(), "inline");
+    let settings = Settings::from_sources(
+        &args(&["--validators-inline", INLINE_REGISTRY]),
+        &env(&[]),
+        None,
+    )
+    .unwrap();
+    assert_eq!(settings.registry_source(), "inline");
+}
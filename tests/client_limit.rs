@@ -0,0 +1,355 @@
+mod common;
+
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::http::StatusCode;
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::auth::{ApiKey, ApiKeys};
+use the_solana_api::client_limit::{self, IpRange};
+use the_solana_api::rate_limit::KeyRateLimit;
+use the_solana_api::{AppState, Settings};
+
+fn limit(requests_per_second: f64, burst: u32) -> KeyRateLimit {
+    KeyRateLimit::new(requests_per_second, Some(burst)).unwrap()
+}
+
+fn ip(raw: &str) -> IpAddr {
+    raw.parse().unwrap()
+}
+
+fn peer(raw: &str) -> Option<SocketAddr> {
+    Some(SocketAddr::new(ip(raw), 4000))
+}
+
+fn forwarded(chain: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        HeaderName::from_static("x-forwarded-for"),
+        HeaderValue::from_str(chain).unwrap(),
+    );
+    headers
+}
+
+fn ranges(raw: &[&str]) -> Vec<IpRange> {
+    raw.iter().map(|range| range.parse().unwrap()).collect()
+}
+
+fn setup(settings: Settings) -> (common::MockUpstream, AppState) {
+    let upstream = common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 7}))
+            }),
+        );
+    });
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("node", "lab")]),
+        settings,
+    );
+    (upstream, state)
+}
+
+fn get_slot(from: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/")
+        .peer_addr(SocketAddr::new(ip(from), 5000))
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+}
+
+#[actix_web::test]
+async fn parses_trusted_ranges() {
+    let range: IpRange = "10.0.0.0/8".parse().unwrap();
+    assert!(range.contains(ip("10.20.30.40")));
+    assert!(range.contains(ip("::ffff:10.1.1.1")));
+    assert!(!range.contains(ip("11.0.0.1")));
+
+    let range: IpRange = "2001:db8::/32".parse().unwrap();
+    assert!(range.contains(ip("2001:db8:ffff::1")));
+    assert!(!range.contains(ip("2001:db9::1")));
+    assert!(!range.contains(ip("10.0.0.1")));
+
+    let single: IpRange = "192.0.2.1".parse().unwrap();
+    assert!(single.contains(ip("192.0.2.1")));
+    assert!(!single.contains(ip("192.0.2.2")));
+    assert!(
+        "0.0.0.0/0"
+            .parse::<IpRange>()
+            .unwrap()
+            .contains(ip("8.8.8.8"))
+    );
+
+    for raw in ["10.0.0.0/33", "::/129", "proxy", "10.0.0.0/x", ""] {
+        assert!(raw.parse::<IpRange>().is_err(), "{raw}");
+    }
+}
+
+#[actix_web::test]
+async fn forwarded_for_is_only_believed_from_trusted_proxies() {
+    let chain = forwarded("203.0.113.9, 198.51.100.7, 10.0.0.2");
+
+    // Nobody is trusted: the peer is the client.
+    assert_eq!(
+        client_limit::client_ip(peer("10.0.0.1"), &chain, &[]),
+        Some(ip("10.0.0.1"))
+    );
+    // The nearest untrusted hop wins; what it claims is ignored.
+    let trusted = ranges(&["10.0.0.0/8"]);
+    assert_eq!(
+        client_limit::client_ip(peer("10.0.0.1"), &chain, &trusted),
+        Some(ip("198.51.100.7"))
+    );
+    // A direct caller cannot spoof its way past the limit.
+    assert_eq!(
+        client_limit::client_ip(peer("198.51.100.50"), &chain, &trusted),
+        Some(ip("198.51.100.50"))
+    );
+    // Garbage in the chain stops the walk at the last trusted hop.
+    assert_eq!(
+        client_limit::client_ip(peer("10.0.0.1"), &forwarded("unknown"), &trusted),
+        Some(ip("10.0.0.1"))
+    );
+    assert_eq!(
+        client_limit::client_ip(peer("10.0.0.1"), &HeaderMap::new(), &trusted),
+        Some(ip("10.0.0.1"))
+    );
+    assert_eq!(client_limit::client_ip(None, &chain, &trusted), None);
+}
+
+#[actix_web::test]
+async fn ipv6_clients_are_grouped_by_prefix() {
+    let normalize = |raw: &str| client_limit::normalize(ip(raw));
+    assert_eq!(
+        normalize("2001:db8:1:2:aaaa::1"),
+        normalize("2001:db8:1:2:bbbb::2")
+    );
+    assert_eq!(normalize("2001:db8:1:2:aaaa::1"), ip("2001:db8:1:2::"));
+    assert_ne!(normalize("2001:db8:1:2::1"), normalize("2001:db8:1:3::1"));
+    assert_eq!(normalize("::ffff:192.0.2.1"), ip("192.0.2.1"));
+    assert_eq!(normalize("192.0.2.1"), ip("192.0.2.1"));
+
+    let key = |raw: &str| client_limit::client_key(peer(raw), &HeaderMap::new(), &[]);
+    assert_eq!(key("2001:DB8:1:2:0:0:0:1"), "2001:db8:1:2::");
+    assert_eq!(key("::ffff:198.51.100.7"), "198.51.100.7");
+    assert_eq!(
+        client_limit::client_key(None, &HeaderMap::new(), &[]),
+        "unknown"
+    );
+}
+
+#[actix_web::test]
+async fn limits_each_client_with_rate_limit_headers() {
+    let (_upstream, state) = setup(
+        Settings::builder()
+            .client_rate_limit(limit(0.5, 2))
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    let resp = test::call_service(&app, get_slot("198.51.100.7").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("x-ratelimit-limit").unwrap(), "2");
+    assert_eq!(resp.headers().get("x-ratelimit-remaining").unwrap(), "1");
+    test::call_service(&app, get_slot("198.51.100.7").to_request()).await;
+
+    let resp = test::call_service(&app, get_slot("198.51.100.7").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(resp.headers().get("retry-after").unwrap(), "2");
+    assert_eq!(resp.headers().get("x-ratelimit-remaining").unwrap(), "0");
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "rate_limited");
+    assert_eq!(body["error"], "client rate limit exceeded");
+
+    // Another address of the same IPv6 /64 shares the bucket; others do not.
+    let resp = test::call_service(&app, get_slot("203.0.113.1").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    for (from, status) in [
+        ("2001:db8::1", StatusCode::OK),
+        ("2001:db8::2", StatusCode::OK),
+        ("2001:db8::3", StatusCode::TOO_MANY_REQUESTS),
+        ("2001:db8:0:1::1", StatusCode::OK),
+    ] {
+        let resp = test::call_service(&app, get_slot(from).to_request()).await;
+        assert_eq!(resp.status(), status, "{from}");
+    }
+}
+
+#[actix_web::test]
+async fn clients_behind_a_trusted_proxy_get_their_own_buckets() {
+    let (_upstream, state) = setup(
+        Settings::builder()
+            .client_rate_limit(limit(0.01, 1))
+            .trusted_proxies(ranges(&["127.0.0.1"]))
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    let via_proxy = |client: &str| {
+        get_slot("127.0.0.1")
+            .insert_header(("x-forwarded-for", client))
+            .to_request()
+    };
+    assert_eq!(
+        test::call_service(&app, via_proxy("198.51.100.7"))
+            .await
+            .status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        test::call_service(&app, via_proxy("198.51.100.8"))
+            .await
+            .status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        test::call_service(&app, via_proxy("198.51.100.7"))
+            .await
+            .status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
+
+#[actix_web::test]
+async fn global_limit_covers_every_client() {
+    let (_upstream, state) = setup(
+        Settings::builder()
+            .global_rate_limit(limit(0.01, 2))
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    for (from, remaining) in [("198.51.100.1", "1"), ("198.51.100.2", "0")] {
+        let resp = test::call_service(&app, get_slot(from).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("x-ratelimit-limit").unwrap(), "2");
+        assert_eq!(
+            resp.headers().get("x-ratelimit-remaining").unwrap(),
+            remaining
+        );
+    }
+    let resp = test::call_service(&app, get_slot("198.51.100.3").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(resp.headers().contains_key("retry-after"));
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "proxy is over its global rate limit");
+}
+
+#[actix_web::test]
+async fn refused_clients_do_not_spend_the_global_budget() {
+    let (_upstream, state) = setup(
+        Settings::builder()
+            .global_rate_limit(limit(0.01, 3))
+            .client_rate_limit(limit(0.01, 2))
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    for expected in [200, 200, 429, 429, 429] {
+        let resp = test::call_service(&app, get_slot("198.51.100.7").to_request()).await;
+        assert_eq!(resp.status(), expected);
+    }
+    let resp = test::call_service(&app, get_slot("198.51.100.8").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn monitoring_endpoints_have_their_own_budget() {
+    let (_upstream, state) = setup(
+        Settings::builder()
+            .global_rate_limit(limit(0.01, 1))
+            .client_rate_limit(limit(0.01, 1))
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+    let get = |uri: &str| {
+        test::TestRequest::get()
+            .uri(uri)
+            .peer_addr("198.51.100.7:5000".parse().unwrap())
+            .to_request()
+    };
+
+    test::call_service(&app, get_slot("198.51.100.7").to_request()).await;
+    let resp = test::call_service(&app, get_slot("198.51.100.7").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    // Exempt unless a monitoring limit is set.
+    for _ in 0..5 {
+        for uri in ["/health", "/validators"] {
+            assert_eq!(test::call_service(&app, get(uri)).await.status(), 200);
+        }
+    }
+
+    let (_upstream, state) = setup(
+        Settings::builder()
+            .monitoring_rate_limit(limit(0.01, 2))
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+    assert_eq!(test::call_service(&app, get("/health")).await.status(), 200);
+    assert_eq!(
+        test::call_service(&app, get("/validators")).await.status(),
+        200
+    );
+    assert_eq!(
+        test::call_service(&app, get("/health")).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
+
+#[actix_web::test]
+async fn requests_with_a_key_skip_the_client_bucket() {
+    const KEY: &str = "alice-secret-key";
+    let hash = common::sha256(KEY);
+    let (_upstream, state) = setup(
+        Settings::builder()
+            .api_keys(ApiKeys::new(vec![
+                ApiKey::new("alice", &hash, false).unwrap(),
+            ]))
+            .client_rate_limit(limit(0.01, 1))
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state.clone()).await;
+
+    for _ in 0..3 {
+        let resp = test::call_service(
+            &app,
+            get_slot("198.51.100.7")
+                .insert_header(("authorization", format!("Bearer {KEY}")))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    assert_eq!(state.client_limiters().tracked_clients(), 0);
+}
+
+#[actix_web::test]
+async fn client_table_is_bounded() {
+    let (_upstream, state) = setup(
+        Settings::builder()
+            .client_rate_limit(limit(0.01, 1))
+            .rate_limit_max_clients(2)
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state.clone()).await;
+
+    for from in ["198.51.100.1", "198.51.100.2", "198.51.100.3"] {
+        test::call_service(&app, get_slot(from).to_request()).await;
+    }
+    assert_eq!(state.client_limiters().tracked_clients(), 2);
+    // The least recently seen client was dropped and starts over.
+    let resp = test::call_service(&app, get_slot("198.51.100.1").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&app, get_slot("198.51.100.3").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+}
@@ -0,0 +1,191 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use actix_web::{HttpResponse, http::StatusCode, test, web};
+use serde_json::{Value, json};
+use url::Url;
+
+use the_solana_api::fleet::{self, ClusterNode, TimedCache};
+use the_solana_api::{AppState, Validator};
+
+fn validator(name: &str, url: &str) -> Validator {
+    Validator::new(name.into(), "lab".into(), Url::parse(url).unwrap())
+}
+
+fn addr(raw: &str) -> SocketAddr {
+    raw.parse().unwrap()
+}
+
+fn node(pubkey: &str, gossip: Option<&str>, rpc: Option<&str>) -> ClusterNode {
+    ClusterNode {
+        pubkey: pubkey.into(),
+        gossip: gossip.map(String::from),
+        rpc: rpc.map(String::from),
+        version: Some("2.0.0".into()),
+    }
+}
+
+#[actix_web::test]
+async fn matches_ip_and_resolved_hostnames() {
+    let validators = vec![
+        (
+            validator("by-ip", "http://10.0.0.1:8899"),
+            vec![addr("10.0.0.1:8899")],
+        ),
+        // Hostnames are compared through whatever they resolved to.
+        (
+            validator("by-host", "https://rpc.example.com"),
+            vec![addr("10.0.0.2:443"), addr("10.0.0.3:443")],
+        ),
+    ];
+    let nodes = vec![
+        node("A", Some("10.0.0.1:8001"), Some("10.0.0.1:8899")),
+        node("B", Some("10.0.0.3:8001"), Some("10.0.0.3:8899")),
+    ];
+
+    let report = fleet::cross_check(&validators, &nodes);
+
+    let by_ip = &report.configured[0];
+    assert!(by_ip.in_gossip);
+    assert_eq!(by_ip.pubkey.as_deref(), Some("A"));
+    assert!(by_ip.rpc_matches);
+
+    // Same IP, but gossip advertises 8899 while we connect on 443.
+    let by_host = &report.configured[1];
+    assert!(by_host.in_gossip);
+    assert_eq!(by_host.pubkey.as_deref(), Some("B"));
+    assert_eq!(by_host.advertised_rpc.as_deref(), Some("10.0.0.3:8899"));
+    assert!(!by_host.rpc_matches);
+
+    assert!(report.unconfigured_rpc_nodes.is_empty());
+}
+
+#[actix_web::test]
+async fn falls_back_to_the_gossip_address() {
+    let validators = vec![(
+        validator("private-rpc", "http://10.0.0.5:8899"),
+        vec![addr("10.0.0.5:8899")],
+    )];
+    let nodes = vec![node("C", Some("10.0.0.5:8001"), None)];
+
+    let report = fleet::cross_check(&validators, &nodes);
+    assert!(report.configured[0].in_gossip);
+    assert_eq!(report.configured[0].advertised_rpc, None);
+    assert!(!report.configured[0].rpc_matches);
+}
+
+#[actix_web::test]
+async fn lists_rpc_nodes_we_do_not_have() {
+    let validators = vec![
+        (
+            validator("known", "http://10.0.0.1:8899"),
+            vec![addr("10.0.0.1:8899")],
+        ),
+        (validator("unresolved", "https://gone.example.com"), vec![]),
+    ];
+    let nodes = vec![
+        node("A", Some("10.0.0.1:8001"), Some("10.0.0.1:8899")),
+        node("D", Some("10.9.9.9:8001"), Some("10.9.9.9:8899")),
+        node("E", Some("10.9.9.8:8001"), None),
+        node("F", None, Some("not an address")),
+    ];
+
+    let report = fleet::cross_check(&validators, &nodes);
+    assert!(!report.configured[1].in_gossip);
+
+    // Nodes without RPC aren't candidates; unparsable addresses still are.
+    let unconfigured: Vec<&str> = report
+        .unconfigured_rpc_nodes
+        .iter()
+        .map(|node| node.pubkey.as_str())
+        .collect();
+    assert_eq!(unconfigured, ["D", "F"]);
+}
+
+#[actix_web::test]
+async fn timed_cache_expires() {
+    let cache = TimedCache::new(Duration::from_millis(30));
+    assert_eq!(cache.get(), None);
+    cache.put(1);
+    assert_eq!(cache.get(), Some(1));
+    tokio::time::sleep(Duration::from_millis(40)).await;
+    assert_eq!(cache.get(), None);
+}
+
+#[actix_web::test]
+async fn endpoint_cross_checks_and_caches() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let rpc = Arc::new(std::sync::Mutex::new(String::new()));
+    let upstream = {
+        let hits = hits.clone();
+        let rpc = rpc.clone();
+        common::spawn_upstream(move |cfg| {
+            let hits = hits.clone();
+            let rpc = rpc.clone();
+            cfg.route(
+                "/",
+                web::post().to(move || {
+                    let hits = hits.clone();
+                    let rpc = rpc.lock().unwrap().clone();
+                    async move {
+                        hits.fetch_add(1, Ordering::SeqCst);
+                        HttpResponse::Ok().json(json!({
+                            "jsonrpc": "2.0",
+                            "id": 1,
+                            "result": [
+                                {"pubkey": "Self", "gossip": "127.0.0.1:8001", "rpc": rpc},
+                                {"pubkey": "Other", "gossip": "10.1.1.1:8001", "rpc": "10.1.1.1:8899", "version": "2.0.1"},
+                            ]
+                        }))
+                    }
+                }),
+            );
+        })
+    };
+    *rpc.lock().unwrap() = upstream.addr.to_string();
+
+    let state = AppState::new(common::registry(vec![upstream.validator("local", "lab")]));
+    let app = common::init_app(state).await;
+
+    let req = test::TestRequest::get()
+        .uri("/fleet/cluster-nodes")
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["gossip_nodes"], 2);
+    assert_eq!(
+        body["sources"],
+        json!([{"validator": "local", "error": null}])
+    );
+    assert_eq!(body["configured"][0]["pubkey"], "Self");
+    assert_eq!(body["configured"][0]["rpc_matches"], true);
+    assert_eq!(
+        body["unconfigured_rpc_nodes"],
+        json!([{"pubkey": "Other", "rpc": "10.1.1.1:8899", "version": "2.0.1"}])
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/fleet/cluster-nodes")
+        .to_request();
+    let cached: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(cached, body);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[actix_web::test]
+async fn fails_when_no_source_answers() {
+    let state = AppState::new(common::registry(vec![validator(
+        "down",
+        "http://127.0.0.1:1/",
+    )]));
+    let app = common::init_app(state).await;
+
+    let req = test::TestRequest::get()
+        .uri("/fleet/cluster-nodes")
+        .to_request();
+    let response = test::call_service(&app, req).await;
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+}
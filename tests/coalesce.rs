@@ -0,0 +1,146 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use futures_util::future::join_all;
+use serde_json::{Value, json};
+
+use the_solana_api::coalesce::Coalescer;
+use the_solana_api::{AppState, Settings};
+
+fn slow_upstream(hits: Arc<AtomicUsize>, status: StatusCode) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let hits = hits.clone();
+        cfg.route(
+            "/",
+            web::post().to(move |body: web::Json<Value>| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    HttpResponse::build(status).json(json!({
+                        "jsonrpc": "2.0",
+                        "id": body["id"],
+                        "result": 42,
+                    }))
+                }
+            }),
+        );
+    })
+}
+
+async fn fire_concurrent(state: AppState, count: usize) -> Vec<(StatusCode, Value)> {
+    let app = common::init_app(state).await;
+    let requests = (0..count).map(|id| {
+        let req = test::TestRequest::post()
+            .uri("/")
+            .set_json(json!({"jsonrpc": "2.0", "id": id, "method": "getSlot"}))
+            .to_request();
+        let app = &app;
+        async move {
+            let resp = test::call_service(app, req).await;
+            let status = resp.status();
+            let body: Value = test::read_body_json(resp).await;
+            (status, body)
+        }
+    });
+    join_all(requests).await
+}
+
+#[actix_web::test]
+async fn identical_concurrent_requests_share_one_upstream_call() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = slow_upstream(hits.clone(), StatusCode::OK);
+
+    let settings = Settings::builder().coalesce_requests(true).build().unwrap();
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("slow", "lab")]),
+        settings,
+    );
+
+    let responses = fire_concurrent(state.clone(), 20).await;
+
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+    for (id, (status, body)) in responses.into_iter().enumerate() {
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["id"], json!(id));
+        assert_eq!(body["result"], json!(42));
+    }
+    assert_eq!(state.coalescer().inflight_len(), 0);
+}
+
+#[actix_web::test]
+async fn coalescing_is_disabled_by_default() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = slow_upstream(hits.clone(), StatusCode::OK);
+    let state = AppState::new(common::registry(vec![upstream.validator("slow", "lab")]));
+
+    fire_concurrent(state, 5).await;
+
+    assert_eq!(hits.load(Ordering::SeqCst), 5);
+}
+
+#[actix_web::test]
+async fn failed_leader_does_not_leave_inflight_entries() {
+    let settings = Settings::builder().coalesce_requests(true).build().unwrap();
+    // Nothing listens on this port, so every attempt fails to connect.
+    let dead = common::registry(vec![the_solana_api::Validator::new(
+        "dead".into(),
+        "lab".into(),
+        url::Url::parse("http://127.0.0.1:9/").unwrap(),
+    )]);
+    let state = AppState::from_settings(dead, settings);
+
+    let responses = fire_concurrent(state.clone(), 5).await;
+
+    for (status, _) in responses {
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+    }
+    assert_eq!(state.coalescer().inflight_len(), 0);
+}
+
+#[actix_web::test]
+async fn key_ignores_id_but_not_params_or_target() {
+    let coalescer = Coalescer::new(true, &["getBalance".to_string()], Duration::from_secs(5));
+
+    let a = coalescer.key(
+        None,
+        None,
+        br#"{"jsonrpc":"2.0","id":1,"method":"getBalance","params":["a"]}"#,
+    );
+    let b = coalescer.key(
+        None,
+        None,
+        br#"{"jsonrpc":"2.0","id":2,"method":"getBalance","params":["a"]}"#,
+    );
+    let c = coalescer.key(
+        None,
+        None,
+        br#"{"jsonrpc":"2.0","id":1,"method":"getBalance","params":["b"]}"#,
+    );
+    let d = coalescer.key(
+        Some("frankfurt-1"),
+        None,
+        br#"{"jsonrpc":"2.0","id":1,"method":"getBalance","params":["a"]}"#,
+    );
+
+    assert!(a.is_some());
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+    assert_ne!(a, d);
+    assert_eq!(
+        coalescer.key(
+            None,
+            None,
+            br#"{"jsonrpc":"2.0","id":1,"method":"sendTransaction"}"#
+        ),
+        None
+    );
+    assert_eq!(
+        coalescer.key(None, None, br#"[{"method":"getBalance"}]"#),
+        None
+    );
+}
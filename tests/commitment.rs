@@ -0,0 +1,239 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+use url::Url;
+
+use the_solana_api::commitment::{self, Commitment};
+use the_solana_api::config::Settings;
+use the_solana_api::stats::StatsStore;
+use the_solana_api::{AppState, Validator};
+
+fn commitment_of(params: Value) -> Commitment {
+    Commitment::from_params(Some(&params))
+}
+
+fn validator(name: &str) -> Validator {
+    Validator::new(
+        name.into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:1/").unwrap(),
+    )
+}
+
+fn names(validators: &[Validator]) -> Vec<&str> {
+    validators.iter().map(Validator::name).collect()
+}
+
+#[actix_web::test]
+async fn parses_positional_config_objects() {
+    assert_eq!(
+        commitment_of(json!(["Vote111", {"commitment": "processed", "encoding": "base64"}])),
+        Commitment::Processed
+    );
+    assert_eq!(
+        commitment_of(json!([{"commitment": "confirmed"}])),
+        Commitment::Confirmed
+    );
+    // getSignaturesForAddress-style: address, then config with commitment.
+    assert_eq!(
+        commitment_of(json!(["Addr", {"limit": 10, "commitment": "processed"}])),
+        Commitment::Processed
+    );
+    // getMultipleAccounts: the first element is an array, not a config.
+    assert_eq!(
+        commitment_of(json!([["A", "B"], {"commitment": "confirmed"}])),
+        Commitment::Confirmed
+    );
+}
+
+#[actix_web::test]
+async fn parses_named_params() {
+    assert_eq!(
+        commitment_of(json!({"pubkey": "A", "commitment": "processed"})),
+        Commitment::Processed
+    );
+    assert_eq!(
+        commitment_of(json!({"pubkey": "A", "config": {"commitment": "confirmed"}})),
+        Commitment::Confirmed
+    );
+}
+
+#[actix_web::test]
+async fn defaults_to_finalized() {
+    assert_eq!(Commitment::from_params(None), Commitment::Finalized);
+    assert_eq!(commitment_of(json!([])), Commitment::Finalized);
+    assert_eq!(commitment_of(json!(["A"])), Commitment::Finalized);
+    assert_eq!(
+        commitment_of(json!(["A", {"encoding": "base64"}])),
+        Commitment::Finalized
+    );
+    assert_eq!(
+        commitment_of(json!([{"commitment": "bogus"}])),
+        Commitment::Finalized
+    );
+    // Only reads are routed by commitment; preflight settings don't count.
+    assert_eq!(
+        commitment_of(json!(["tx", {"preflightCommitment": "processed"}])),
+        Commitment::Finalized
+    );
+}
+
+#[actix_web::test]
+async fn accepts_deprecated_names() {
+    assert_eq!(Commitment::parse("recent"), Some(Commitment::Processed));
+    assert_eq!(
+        Commitment::parse("singleGossip"),
+        Some(Commitment::Confirmed)
+    );
+    assert_eq!(Commitment::parse("single"), Some(Commitment::Confirmed));
+    assert_eq!(Commitment::parse("max"), Some(Commitment::Finalized));
+    assert_eq!(Commitment::parse("root"), Some(Commitment::Finalized));
+}
+
+#[actix_web::test]
+async fn batches_use_the_least_settled_commitment() {
+    let body = json!([
+        {"jsonrpc": "2.0", "id": 1, "method": "getSlot", "params": [{"commitment": "finalized"}]},
+        {"jsonrpc": "2.0", "id": 2, "method": "getBalance", "params": ["A", {"commitment": "confirmed"}]},
+        {"jsonrpc": "2.0", "id": 3, "method": "getSlot"},
+    ]);
+    assert_eq!(
+        Commitment::for_request(body.to_string().as_bytes()),
+        Commitment::Confirmed
+    );
+    assert_eq!(Commitment::for_request(b"not json"), Commitment::Finalized);
+}
+
+#[actix_web::test]
+async fn thresholds_come_from_settings() {
+    let settings = Settings::builder()
+        .commitment_max_slot_lag_processed(1)
+        .commitment_max_slot_lag_confirmed(4)
+        .commitment_max_slot_lag_finalized(100)
+        .build()
+        .unwrap();
+    assert_eq!(Commitment::Processed.max_slot_lag(&settings), Some(1));
+    assert_eq!(Commitment::Confirmed.max_slot_lag(&settings), Some(4));
+    assert_eq!(Commitment::Finalized.max_slot_lag(&settings), Some(100));
+    assert_eq!(
+        Commitment::Finalized.max_slot_lag(&Settings::default()),
+        None
+    );
+}
+
+#[actix_web::test]
+async fn lagging_validators_are_filtered() {
+    let stats = StatsStore::new();
+    stats.validator("fresh").observe_slot(1_000);
+    stats.validator("close").observe_slot(998);
+    stats.validator("lagging").observe_slot(900);
+    let candidates = vec![
+        validator("fresh"),
+        validator("close"),
+        validator("lagging"),
+        validator("unknown"),
+    ];
+
+    let kept = commitment::filter_by_lag(&stats, Some(2), candidates.clone());
+    assert_eq!(names(&kept), ["fresh", "close", "unknown"]);
+
+    let kept = commitment::filter_by_lag(&stats, None, candidates);
+    assert_eq!(kept.len(), 4);
+}
+
+#[actix_web::test]
+async fn filtering_is_skipped_without_slot_data_or_survivors() {
+    let stats = StatsStore::new();
+    let kept = commitment::filter_by_lag(&stats, Some(0), vec![validator("a"), validator("b")]);
+    assert_eq!(names(&kept), ["a", "b"]);
+
+    stats.validator("leader").observe_slot(1_000);
+    stats.validator("a").observe_slot(10);
+    let kept = commitment::filter_by_lag(&stats, Some(0), vec![validator("a")]);
+    assert_eq!(names(&kept), ["a"]);
+}
+
+fn counting_upstream(hits: Arc<AtomicUsize>, slot: u64) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let hits = hits.clone();
+        cfg.route(
+            "/",
+            web::post().to(move || {
+                let hits = hits.clone();
+                async move {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    HttpResponse::Ok().json(json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": {"context": {"slot": slot}, "value": 5}
+                    }))
+                }
+            }),
+        );
+    })
+}
+
+fn balance(commitment: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "getBalance",
+            "params": ["A", {"commitment": commitment}]
+        }))
+        .to_request()
+}
+
+#[actix_web::test]
+async fn processed_reads_avoid_lagging_validators() {
+    let fresh_hits = Arc::new(AtomicUsize::new(0));
+    let lagging_hits = Arc::new(AtomicUsize::new(0));
+    let fresh = counting_upstream(fresh_hits.clone(), 1_000);
+    let lagging = counting_upstream(lagging_hits.clone(), 900);
+    let settings = Settings::builder()
+        .commitment_routing(true)
+        .build()
+        .unwrap();
+    let state = AppState::from_settings(
+        common::registry(vec![
+            fresh.validator("fresh", "lab"),
+            lagging.validator("lagging", "lab"),
+        ]),
+        settings,
+    );
+    state.stats().validator("fresh").observe_slot(1_000);
+    state.stats().validator("lagging").observe_slot(900);
+    let app = common::init_app(state).await;
+
+    for _ in 0..10 {
+        let body: Value = test::call_and_read_body_json(&app, balance("processed")).await;
+        assert_eq!(body["result"]["value"], 5);
+    }
+    assert_eq!(fresh_hits.load(Ordering::SeqCst), 10);
+    assert_eq!(lagging_hits.load(Ordering::SeqCst), 0);
+
+    for _ in 0..30 {
+        test::call_service(&app, balance("finalized")).await;
+    }
+    assert!(lagging_hits.load(Ordering::SeqCst) > 0);
+}
+
+#[actix_web::test]
+async fn slots_are_learned_from_responses() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = counting_upstream(hits, 1_234);
+    let state = AppState::new(common::registry(vec![upstream.validator("primary", "lab")]));
+    let app = common::init_app(state).await;
+
+    test::call_service(&app, balance("processed")).await;
+
+    let stats: Value =
+        test::call_and_read_body_json(&app, test::TestRequest::get().uri("/stats").to_request())
+            .await;
+    assert_eq!(stats["validators"][0]["latest_slot"], 1_234);
+}
@@ -0,0 +1,101 @@
+#![allow(dead_code)]
+
+use std::fmt::Write;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use actix_web::{App, HttpResponse, HttpServer, middleware::from_fn, web};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use the_solana_api::{
+    AppState, Validator, ValidatorRegistry, ip_filter, panic_guard, request_id, routes,
+};
+
+pub struct MockUpstream {
+    pub addr: SocketAddr,
+    handle: tokio::task::JoinHandle<std::io::Result<()>>,
+}
+
+impl MockUpstream {
+    pub fn url(&self) -> Url {
+        Url::parse(&format!("http://{}/", self.addr)).unwrap()
+    }
+
+    pub fn validator(&self, name: &str, location: &str) -> Validator {
+        Validator::new(name.into(), location.into(), self.url())
+    }
+}
+
+impl Drop for MockUpstream {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+pub fn spawn_upstream<F>(configure: F) -> MockUpstream
+where
+    F: Fn(&mut web::ServiceConfig) + Clone + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind upstream listener");
+    let addr = listener.local_addr().expect("upstream addr");
+
+    let server = HttpServer::new(move || App::new().configure(configure.clone()))
+        .workers(1)
+        .listen(listener)
+        .expect("listen")
+        .run();
+
+    MockUpstream {
+        addr,
+        handle: tokio::spawn(server),
+    }
+}
+
+// Hex SHA-256 of an API key, as `API_KEYS` stores it.
+pub fn sha256(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+// Answers `POST /` with a slot, counting the calls.
+pub fn counting_upstream(hits: Arc<AtomicUsize>) -> MockUpstream {
+    spawn_upstream(move |cfg| {
+        let hits = hits.clone();
+        cfg.route(
+            "/",
+            web::post().to(move || {
+                hits.fetch_add(1, Ordering::SeqCst);
+                async { HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 7})) }
+            }),
+        );
+    })
+}
+
+pub fn registry(validators: Vec<Validator>) -> ValidatorRegistry {
+    ValidatorRegistry::new(validators).expect("registry")
+}
+
+pub async fn init_app(
+    state: AppState,
+) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    actix_web::test::init_service(
+        App::new()
+            .wrap(from_fn(ip_filter::enforce))
+            .wrap(from_fn(panic_guard::catch))
+            .wrap(from_fn(request_id::assign))
+            .app_data(web::Data::new(state))
+            .configure(routes::configure),
+    )
+    .await
+}
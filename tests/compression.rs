@@ -0,0 +1,111 @@
+mod common;
+
+use std::io::Read;
+
+use actix_web::http::header::{self, ContentEncoding, HeaderMap};
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::compression::negotiate;
+use the_solana_api::{AppState, Settings};
+
+fn accept(value: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ACCEPT_ENCODING, value.parse().unwrap());
+    headers
+}
+
+#[actix_web::test]
+async fn negotiates_supported_encodings() {
+    assert_eq!(negotiate(&HeaderMap::new()), None);
+    assert_eq!(negotiate(&accept("gzip")), Some(ContentEncoding::Gzip));
+    assert_eq!(
+        negotiate(&accept("gzip, br")),
+        Some(ContentEncoding::Brotli)
+    );
+    assert_eq!(
+        negotiate(&accept("br;q=0.5, gzip;q=0.9")),
+        Some(ContentEncoding::Gzip)
+    );
+    assert_eq!(negotiate(&accept("br;q=0, gzip;q=0")), None);
+    assert_eq!(negotiate(&accept("deflate, identity")), None);
+    assert_eq!(negotiate(&accept("*")), Some(ContentEncoding::Brotli));
+}
+
+fn large_result() -> Value {
+    let accounts: Vec<Value> = (0..2_000)
+        .map(|idx| json!({ "pubkey": format!("Account{idx}"), "lamports": 2_039_280 }))
+        .collect();
+    json!({ "jsonrpc": "2.0", "id": 1, "result": accounts })
+}
+
+fn large_json_upstream() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async { HttpResponse::Ok().json(large_result()) }),
+        );
+    })
+}
+
+async fn fetch(settings: Settings, accept_encoding: Option<&str>) -> (Option<String>, Vec<u8>) {
+    let upstream = large_json_upstream();
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("gpa-1", "lab")]),
+        settings,
+    );
+    let app = common::init_app(state).await;
+
+    let mut request = test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({ "jsonrpc": "2.0", "id": 1, "method": "getProgramAccounts" }));
+    if let Some(value) = accept_encoding {
+        request = request.insert_header((header::ACCEPT_ENCODING, value));
+    }
+
+    let response = test::call_service(&app, request.to_request()).await;
+    let encoding = response
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .map(|value| value.to_str().unwrap().to_string());
+    let body = test::read_body(response).await.to_vec();
+    (encoding, body)
+}
+
+#[actix_web::test]
+async fn gzips_large_responses_for_accepting_clients() {
+    let (encoding, body) = fetch(Settings::default(), Some("gzip")).await;
+
+    assert_eq!(encoding.as_deref(), Some("gzip"));
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(&body[..])
+        .read_to_end(&mut decoded)
+        .unwrap();
+    let parsed: Value = serde_json::from_slice(&decoded).unwrap();
+    assert_eq!(parsed, large_result());
+    assert!(body.len() < decoded.len() / 4);
+}
+
+#[actix_web::test]
+async fn sends_identity_to_clients_without_accept_encoding() {
+    let (encoding, body) = fetch(Settings::default(), None).await;
+
+    assert_eq!(encoding, None);
+    let parsed: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(parsed, large_result());
+}
+
+#[actix_web::test]
+async fn skips_bodies_below_threshold_and_when_disabled() {
+    let below_threshold = Settings::builder()
+        .compression_min_bytes(10 * 1024 * 1024)
+        .build()
+        .unwrap();
+    assert_eq!(fetch(below_threshold, Some("gzip")).await.0, None);
+
+    let disabled = Settings::builder()
+        .compression_enabled(false)
+        .build()
+        .unwrap();
+    assert_eq!(fetch(disabled, Some("gzip, br")).await.0, None);
+}
@@ -0,0 +1,196 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::middleware::from_fn;
+use actix_web::{App, HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::{AppState, Settings, concurrency, panic_guard};
+
+// Answers after `delay`, recording the most calls it saw at once.
+fn slow_upstream(delay: Duration, peak: Arc<AtomicUsize>) -> common::MockUpstream {
+    let current = Arc::new(AtomicUsize::new(0));
+    common::spawn_upstream(move |cfg| {
+        let (current, peak) = (current.clone(), peak.clone());
+        cfg.route(
+            "/",
+            web::post().to(move || {
+                let (current, peak) = (current.clone(), peak.clone());
+                async move {
+                    let now = current.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(delay).await;
+                    current.fetch_sub(1, Ordering::SeqCst);
+                    HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 7}))
+                }
+            }),
+        );
+    })
+}
+
+fn setup(
+    delay: Duration,
+    max: usize,
+    queue_timeout: Duration,
+) -> (common::MockUpstream, Arc<AtomicUsize>, AppState) {
+    let peak = Arc::new(AtomicUsize::new(0));
+    let upstream = slow_upstream(delay, peak.clone());
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("node", "lab")]),
+        Settings::builder()
+            .max_concurrent_requests(max)
+            .concurrency_queue_timeout(queue_timeout)
+            .metrics_enabled(true)
+            .build()
+            .unwrap(),
+    );
+    (upstream, peak, state)
+}
+
+fn get_slot() -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+        .to_request()
+}
+
+// Reads the body through, since a request keeps its slot until then.
+async fn call<S>(app: &S, req: actix_http::Request) -> StatusCode
+where
+    S: actix_web::dev::Service<
+            actix_http::Request,
+            Response = actix_web::dev::ServiceResponse,
+            Error = actix_web::Error,
+        >,
+{
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    test::read_body(resp).await;
+    status
+}
+
+#[actix_web::test]
+async fn the_cap_holds_under_a_burst() {
+    let (_upstream, peak, state) = setup(Duration::from_millis(50), 3, Duration::from_secs(10));
+    let app = common::init_app(state.clone()).await;
+
+    let statuses = futures_util::future::join_all((0..24).map(|_| call(&app, get_slot()))).await;
+    assert!(statuses.iter().all(|status| *status == StatusCode::OK));
+    assert_eq!(peak.load(Ordering::SeqCst), 3);
+
+    let limit = state.concurrency_limit().unwrap();
+    assert_eq!((limit.in_flight(), limit.queued(), limit.shed()), (0, 0, 0));
+}
+
+#[actix_web::test]
+async fn requests_past_the_cap_are_shed_without_a_queue() {
+    let (_upstream, _peak, state) = setup(Duration::from_millis(300), 1, Duration::ZERO);
+    let app = common::init_app(state.clone()).await;
+
+    let held = call(&app, get_slot());
+    let shed = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        test::call_service(&app, get_slot()).await
+    };
+    let (held, shed) = tokio::join!(held, shed);
+    assert_eq!(held, StatusCode::OK);
+    assert_eq!(shed.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(shed.headers().get("retry-after").unwrap(), "1");
+    let body: Value = test::read_body_json(shed).await;
+    assert_eq!(body["code"], "overloaded");
+    assert_eq!(state.concurrency_limit().unwrap().shed(), 1);
+
+    // Monitoring is never held back.
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/health").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn queued_requests_give_up_after_the_timeout() {
+    let (_upstream, _peak, state) =
+        setup(Duration::from_millis(400), 1, Duration::from_millis(100));
+    let app = common::init_app(state.clone()).await;
+    let limit = state.concurrency_limit().unwrap();
+
+    let held = call(&app, get_slot());
+    let queued = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        call(&app, get_slot()).await
+    };
+    let watch = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let metrics =
+            test::call_service(&app, test::TestRequest::get().uri("/metrics").to_request()).await;
+        let body = String::from_utf8(test::read_body(metrics).await.to_vec()).unwrap();
+        (limit.in_flight(), limit.queued(), body)
+    };
+    let (held, queued, (in_flight, waiting, metrics)) = tokio::join!(held, queued, watch);
+    assert_eq!(held, StatusCode::OK);
+    assert_eq!(queued, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!((in_flight, waiting), (1, 1));
+    assert!(
+        metrics.contains("solana_api_requests_in_flight 1\n"),
+        "{metrics}"
+    );
+    assert!(
+        metrics.contains("solana_api_requests_queued 1\n"),
+        "{metrics}"
+    );
+    assert_eq!((limit.in_flight(), limit.queued()), (0, 0));
+}
+
+#[actix_web::test]
+async fn queued_requests_run_once_a_slot_frees() {
+    let (_upstream, peak, state) = setup(Duration::from_millis(100), 1, Duration::from_secs(5));
+    let app = common::init_app(state).await;
+
+    let (first, second) = tokio::join!(call(&app, get_slot()), call(&app, get_slot()));
+    assert_eq!(first, StatusCode::OK);
+    assert_eq!(second, StatusCode::OK);
+    assert_eq!(peak.load(Ordering::SeqCst), 1);
+}
+
+async fn explode() -> HttpResponse {
+    panic!("deliberate test panic");
+}
+
+#[actix_web::test]
+async fn disconnects_and_panics_give_the_slot_back() {
+    let (_upstream, _peak, state) = setup(Duration::from_millis(500), 1, Duration::from_secs(5));
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(panic_guard::catch))
+            .app_data(web::Data::new(state.clone()))
+            .service(
+                web::resource("/explode")
+                    .wrap(from_fn(concurrency::limit))
+                    .route(web::get().to(explode)),
+            )
+            .configure(the_solana_api::routes::configure),
+    )
+    .await;
+    let limit = state.concurrency_limit().unwrap();
+
+    // The client goes away while the upstream call is under way.
+    let abandoned = tokio::time::timeout(
+        Duration::from_millis(50),
+        test::call_service(&app, get_slot()),
+    )
+    .await;
+    assert!(abandoned.is_err());
+    assert_eq!(limit.in_flight(), 0);
+
+    let err = test::try_call_service(&app, test::TestRequest::get().uri("/explode").to_request())
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.error_response().status(),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+    assert_eq!(limit.in_flight(), 0);
+}
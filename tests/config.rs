@@ -0,0 +1,1437 @@
+use std::env;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use serde_json::{Value, json};
+
+use the_solana_api::Settings;
+use the_solana_api::batch::BatchIdCheck;
+use the_solana_api::body_log;
+use the_solana_api::cli::Args;
+use the_solana_api::config::ConfigError;
+use the_solana_api::cors::AllowedOrigins;
+use the_solana_api::effective_config;
+use the_solana_api::errors::ErrorFormat;
+use the_solana_api::ip_filter::Unlisted;
+use the_solana_api::jwt::JwtKey;
+use the_solana_api::listeners::Listener;
+use the_solana_api::log_file::Rotation;
+use the_solana_api::rate_limit::{KeyRateLimit, RateLimitHeaders};
+use the_solana_api::status_policy::{StatusPolicies, StatusPolicy};
+use the_solana_api::telemetry::LogFormat;
+
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+fn with_env<T>(vars: &[(&str, &str)], f: impl FnOnce() -> T) -> T {
+    let _guard = ENV_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let csv = concat!(env!("CARGO_MANIFEST_DIR"), "/config/validators.example.csv");
+
+    unsafe {
+        env::set_var("VALIDATORS_CSV", csv);
+        for (name, value) in vars {
+            env::set_var(name, value);
+        }
+    }
+
+    let result = f();
+
+    unsafe {
+        env::remove_var("VALIDATORS_CSV");
+        for (name, _) in vars {
+            env::remove_var(name);
+        }
+    }
+
+    result
+}
+
+#[test]
+fn reads_upstream_identification_headers() {
+    let settings = with_env(
+        &[
+            ("UPSTREAM_USER_AGENT", "acme-proxy/2"),
+            ("UPSTREAM_PROXIED_BY", "acme"),
+            ("PRESERVE_CLIENT_USER_AGENT", "true"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+
+    assert_eq!(settings.upstream_user_agent, "acme-proxy/2");
+    assert_eq!(settings.proxied_by.unwrap(), "acme");
+    assert!(settings.preserve_client_user_agent);
+}
+
+#[test]
+fn rejects_illegal_header_values() {
+    let error = with_env(&[("UPSTREAM_USER_AGENT", "bad\u{7f}agent")], Settings::load).unwrap_err();
+
+    assert!(matches!(
+        error,
+        ConfigError::InvalidValue { ref name, .. } if name == "UPSTREAM_USER_AGENT"
+    ));
+}
+
+#[test]
+fn rejects_unparsable_flags() {
+    let error = with_env(&[("RETRY_ON_429", "maybe")], Settings::load).unwrap_err();
+
+    assert!(matches!(
+        error,
+        ConfigError::InvalidValue { ref name, .. } if name == "RETRY_ON_429"
+    ));
+}
+
+#[test]
+fn reads_inline_routing_rules() {
+    let settings = with_env(
+        &[(
+            "ROUTING_RULES",
+            r#"[{"method":"sendTransaction","tags":["staked"]}]"#,
+        )],
+        Settings::load,
+    )
+    .unwrap();
+
+    let rule = settings
+        .routing_rules
+        .best_match("sendTransaction")
+        .unwrap();
+    assert_eq!(rule.tags, vec!["staked"]);
+
+    let error = with_env(&[("ROUTING_RULES", "[{\"tags\":[]}]")], Settings::load).unwrap_err();
+    assert!(matches!(error, ConfigError::RoutingRules(_)));
+}
+
+#[test]
+fn reads_method_rate_limits() {
+    let settings = with_env(
+        &[(
+            "METHOD_RATE_LIMITS",
+            r#"[{"method":"getProgramAccounts","per_second":2}]"#,
+        )],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(settings.method_rate_limits.limits().len(), 1);
+
+    let error = with_env(
+        &[(
+            "METHOD_RATE_LIMITS",
+            r#"[{"method":"getSlot","per_second":-1}]"#,
+        )],
+        Settings::load,
+    )
+    .unwrap_err();
+    assert!(
+        matches!(error, ConfigError::InvalidValue { name, .. } if name == "METHOD_RATE_LIMITS")
+    );
+}
+
+#[test]
+fn reads_shadow_sampling() {
+    let settings = with_env(
+        &[
+            ("SHADOW_SAMPLE_RATE", "0.01"),
+            (
+                "SHADOW_IGNORE_FIELDS",
+                "context.slot, value.confirmationStatus",
+            ),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(settings.shadow_sample_rate, 0.01);
+    assert_eq!(
+        settings.shadow_ignore_fields,
+        ["context.slot", "value.confirmationStatus"]
+    );
+
+    let error = with_env(&[("SHADOW_SAMPLE_RATE", "5")], Settings::load).unwrap_err();
+    assert!(
+        matches!(error, ConfigError::InvalidNumber { name, .. } if name == "SHADOW_SAMPLE_RATE")
+    );
+}
+
+#[test]
+fn reads_batch_id_check_mode() {
+    let settings = with_env(&[("BATCH_ID_CHECK", "Strict")], Settings::load).unwrap();
+    assert_eq!(settings.batch_id_check, BatchIdCheck::Strict);
+
+    let error = with_env(&[("BATCH_ID_CHECK", "sometimes")], Settings::load).unwrap_err();
+    assert!(matches!(error, ConfigError::InvalidValue { name, .. } if name == "BATCH_ID_CHECK"));
+}
+
+#[test]
+fn reads_latency_buckets() {
+    let settings = with_env(&[("LATENCY_BUCKETS_MS", "1, 2.5,10")], Settings::load).unwrap();
+    assert_eq!(settings.latency_buckets_ms, [1.0, 2.5, 10.0]);
+
+    for raw in ["10,5", "0,1", "fast", ""] {
+        let error = with_env(&[("LATENCY_BUCKETS_MS", raw)], Settings::load).unwrap_err();
+        assert!(
+            matches!(error, ConfigError::InvalidValue { name, .. } if name == "LATENCY_BUCKETS_MS")
+        );
+    }
+}
+
+#[test]
+fn reads_log_format() {
+    let settings = with_env(&[("LOG_FORMAT", "json")], Settings::load).unwrap();
+    assert_eq!(settings.log_format, LogFormat::Json);
+
+    let error = with_env(&[("LOG_FORMAT", "xml")], Settings::load).unwrap_err();
+    assert!(matches!(error, ConfigError::InvalidValue { name, .. } if name == "LOG_FORMAT"));
+}
+
+#[test]
+fn reads_statsd_settings() {
+    let settings = with_env(
+        &[
+            ("STATSD_ADDRESS", "127.0.0.1:8125"),
+            ("STATSD_PREFIX", "rpc"),
+            ("STATSD_TAGS", "env:prod, region:eu"),
+            ("STATSD_INTERVAL_MS", "2500"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(settings.statsd_address.as_deref(), Some("127.0.0.1:8125"));
+    assert_eq!(settings.statsd_prefix, "rpc");
+    assert_eq!(settings.statsd_tags, ["env:prod", "region:eu"]);
+    assert_eq!(settings.statsd_interval, Duration::from_millis(2500));
+
+    let error = with_env(&[("STATSD_TAGS", "env:prod,bad|tag")], Settings::load).unwrap_err();
+    assert!(
+        matches!(error, ConfigError::InvalidValue { name, value } if name == "STATSD_TAGS" && value == "bad|tag")
+    );
+}
+
+#[test]
+fn blank_sentry_dsn_is_unset() {
+    let settings = with_env(&[("SENTRY_DSN", "  ")], Settings::load).unwrap();
+    assert_eq!(settings.sentry_dsn, None);
+
+    let dsn = "https://key@o1.ingest.sentry.io/42";
+    let settings = with_env(&[("SENTRY_DSN", dsn)], Settings::load).unwrap();
+    assert_eq!(settings.sentry_dsn.as_deref(), Some(dsn));
+}
+
+#[test]
+fn reads_access_log_settings() {
+    let settings = with_env(
+        &[
+            ("ACCESS_LOG_PATH", "/var/log/solana-api/access.log"),
+            ("ACCESS_LOG_ROTATION", "size"),
+            ("ACCESS_LOG_MAX_BYTES", "1048576"),
+            ("ACCESS_LOG_MAX_FILES", "3"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(
+        settings.access_log_path.as_deref(),
+        Some(std::path::Path::new("/var/log/solana-api/access.log"))
+    );
+    assert_eq!(settings.access_log_rotation, Rotation::Size);
+    assert_eq!(settings.access_log_max_bytes, 1_048_576);
+    assert_eq!(settings.access_log_max_files, 3);
+
+    for (name, value) in [
+        ("ACCESS_LOG_ROTATION", "weekly"),
+        ("ACCESS_LOG_MAX_BYTES", "0"),
+    ] {
+        let error = with_env(&[(name, value)], Settings::load).unwrap_err();
+        assert_eq!(error.name(), Some(name), "{error}");
+    }
+}
+
+#[test]
+fn reads_body_logging_settings() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert!(!settings.log_bodies);
+    assert_eq!(settings.log_body_redact, body_log::default_rules());
+
+    let settings = with_env(
+        &[
+            ("LOG_BODIES", "true"),
+            ("LOG_BODY_MAX_BYTES", "512"),
+            (
+                "LOG_BODY_REDACT",
+                "getAccountInfo:result.value.data, secret",
+            ),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    assert!(settings.log_bodies);
+    assert_eq!(settings.log_body_max_bytes, 512);
+    let mut rules = body_log::default_rules();
+    rules.push("getAccountInfo:result.value.data".parse().unwrap());
+    rules.push("secret".parse().unwrap());
+    assert_eq!(settings.log_body_redact, rules);
+
+    let error = with_env(&[("LOG_BODY_REDACT", "params[x]")], Settings::load).unwrap_err();
+    assert!(
+        matches!(error, ConfigError::InvalidValue { name, value } if name == "LOG_BODY_REDACT" && value == "params[x]")
+    );
+}
+
+#[test]
+fn reads_key_usage_limits() {
+    let settings = with_env(
+        &[
+            ("KEY_USAGE_MAX_KEYS", "500"),
+            ("KEY_USAGE_RETENTION_SECS", "3600"),
+            ("KEY_METRICS_MAX_KEYS", "0"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(settings.key_usage_max_keys, 500);
+    assert_eq!(settings.key_usage_retention, Duration::from_secs(3600));
+    assert_eq!(settings.key_metrics_max_keys, 0);
+}
+
+#[test]
+fn ping_rate_limit_must_be_positive() {
+    let settings = with_env(&[("PING_RATE_LIMIT", "0.5")], Settings::load).unwrap();
+    assert_eq!(settings.ping_rate_limit, 0.5);
+
+    for value in ["0", "-1", "inf"] {
+        let error = with_env(&[("PING_RATE_LIMIT", value)], Settings::load).unwrap_err();
+        assert!(
+            matches!(error, ConfigError::InvalidNumber { name, .. } if name == "PING_RATE_LIMIT")
+        );
+    }
+}
+
+#[test]
+fn reads_stats_snapshot_settings() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert_eq!(settings.stats_snapshot_path, None);
+    assert_eq!(settings.stats_snapshot_interval, Duration::from_secs(60));
+    assert_eq!(settings.stats_snapshot_max_age, Duration::from_secs(3600));
+
+    let settings = with_env(
+        &[
+            ("STATS_SNAPSHOT_PATH", "/var/lib/solana-api/stats.json"),
+            ("STATS_SNAPSHOT_INTERVAL_SECS", "15"),
+            ("STATS_SNAPSHOT_MAX_AGE_SECS", "600"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(
+        settings.stats_snapshot_path.as_deref(),
+        Some(std::path::Path::new("/var/lib/solana-api/stats.json"))
+    );
+    assert_eq!(settings.stats_snapshot_interval, Duration::from_secs(15));
+    assert_eq!(settings.stats_snapshot_max_age, Duration::from_secs(600));
+}
+
+#[test]
+fn reads_error_format() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert_eq!(settings.error_format, ErrorFormat::Plain);
+
+    let settings = with_env(&[("ERROR_FORMAT", "jsonrpc")], Settings::load).unwrap();
+    assert_eq!(settings.error_format, ErrorFormat::JsonRpc);
+
+    let error = with_env(&[("ERROR_FORMAT", "xml")], Settings::load).unwrap_err();
+    assert!(matches!(error, ConfigError::InvalidValue { name, .. } if name == "ERROR_FORMAT"));
+}
+
+#[test]
+fn reads_rate_limit_headers() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert_eq!(settings.rate_limit_headers, RateLimitHeaders::Legacy);
+
+    for (raw, expected) in [
+        ("draft", RateLimitHeaders::Draft),
+        ("OFF", RateLimitHeaders::Off),
+        ("legacy", RateLimitHeaders::Legacy),
+    ] {
+        let settings = with_env(&[("RATE_LIMIT_HEADERS", raw)], Settings::load).unwrap();
+        assert_eq!(settings.rate_limit_headers, expected);
+    }
+
+    let error = with_env(&[("RATE_LIMIT_HEADERS", "both")], Settings::load).unwrap_err();
+    assert!(
+        matches!(error, ConfigError::InvalidValue { name, .. } if name == "RATE_LIMIT_HEADERS")
+    );
+}
+
+#[test]
+fn reads_concurrency_limit() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert_eq!(settings.max_concurrent_requests, None);
+    assert_eq!(settings.concurrency_queue_timeout, Duration::ZERO);
+    assert_eq!(settings.concurrency_queue_depth, 1000);
+    assert!(!settings.concurrency_fair_queues);
+
+    let settings = with_env(
+        &[
+            ("MAX_CONCURRENT_REQUESTS", "512"),
+            ("CONCURRENCY_QUEUE_TIMEOUT_MS", "2000"),
+            ("CONCURRENCY_QUEUE_DEPTH", "64"),
+            ("CONCURRENCY_FAIR_QUEUES", "true"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(settings.max_concurrent_requests, Some(512));
+    assert_eq!(settings.concurrency_queue_timeout, Duration::from_secs(2));
+    assert_eq!(settings.concurrency_queue_depth, 64);
+    assert!(settings.concurrency_fair_queues);
+
+    let settings = with_env(&[("MAX_CONCURRENT_REQUESTS", "0")], Settings::load).unwrap();
+    assert_eq!(settings.max_concurrent_requests, None);
+}
+
+#[test]
+fn reads_shed_when_cooling_down() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert!(!settings.shed_when_cooling_down);
+
+    let settings = with_env(&[("SHED_WHEN_COOLING_DOWN", "true")], Settings::load).unwrap();
+    assert!(settings.shed_when_cooling_down);
+}
+
+#[test]
+fn reads_upstream_rate_limit() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert_eq!(settings.upstream_rate_limit, None);
+    assert_eq!(settings.upstream_rate_limit_wait, Duration::ZERO);
+
+    let settings = with_env(
+        &[
+            ("UPSTREAM_RATE_LIMIT_RPS", "40"),
+            ("UPSTREAM_RATE_LIMIT_BURST", "80"),
+            ("UPSTREAM_RATE_LIMIT_WAIT_MS", "250"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    let limit = settings.upstream_rate_limit.unwrap();
+    assert_eq!((limit.requests_per_second, limit.burst), (40.0, 80));
+    assert_eq!(
+        settings.upstream_rate_limit_wait,
+        Duration::from_millis(250)
+    );
+}
+
+#[test]
+fn reads_upstream_timeout() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert_eq!(settings.upstream_timeout, Duration::from_secs(15));
+
+    let settings = with_env(&[("UPSTREAM_TIMEOUT_MS", "2500")], Settings::load).unwrap();
+    assert_eq!(settings.upstream_timeout, Duration::from_millis(2500));
+
+    for value in ["0", "600001", "5s", "-1"] {
+        let err = with_env(&[("UPSTREAM_TIMEOUT_MS", value)], Settings::load).unwrap_err();
+        assert!(
+            matches!(err, ConfigError::InvalidNumber { ref name, .. } if name == "UPSTREAM_TIMEOUT_MS"),
+            "{value}: {err}"
+        );
+    }
+    let err = with_env(&[("UPSTREAM_TIMEOUT_MS", "0")], Settings::load).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid value '0' for UPSTREAM_TIMEOUT_MS: expected between 1 and 600000 ms"
+    );
+}
+
+#[test]
+fn reads_the_tls_settings() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert!(settings.tls.is_none());
+    assert!(settings.health_bind_address.is_none());
+
+    let settings = with_env(
+        &[
+            ("TLS_CERT_FILE", "/etc/tls/fullchain.pem"),
+            ("TLS_KEY_FILE", "/etc/tls/privkey.pem"),
+            ("TLS_RELOAD_SECS", "0"),
+            ("HEALTH_BIND_ADDRESS", "127.0.0.1:8081"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    let tls = settings.tls.unwrap();
+    assert_eq!(tls.cert_file, Path::new("/etc/tls/fullchain.pem"));
+    assert_eq!(tls.key_file, Path::new("/etc/tls/privkey.pem"));
+    assert_eq!(tls.reload_interval, Duration::ZERO);
+    assert_eq!(
+        settings.health_bind_address.as_deref(),
+        Some("127.0.0.1:8081")
+    );
+
+    let err = with_env(
+        &[("TLS_CERT_FILE", "/etc/tls/fullchain.pem")],
+        Settings::load,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "TLS_CERT_FILE conflicts with TLS_KEY_FILE: set without TLS_KEY_FILE; give both or neither"
+    );
+}
+
+#[test]
+fn reads_the_worker_count() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert_eq!(
+        settings.workers,
+        std::thread::available_parallelism().unwrap().get()
+    );
+    let settings = with_env(&[("WORKERS", "2")], Settings::load).unwrap();
+    assert_eq!(settings.workers, 2);
+
+    for value in ["0", "-1", "many"] {
+        let err = with_env(&[("WORKERS", value)], Settings::load).unwrap_err();
+        assert!(
+            matches!(err, ConfigError::InvalidNumber { ref name, .. } if name == "WORKERS"),
+            "{value}: {err}"
+        );
+    }
+}
+
+#[test]
+fn reads_the_upstream_pool_settings() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert_eq!(settings.upstream_max_connections_per_host, 100);
+    assert_eq!(settings.upstream_keepalive, Duration::from_secs(15));
+    assert_eq!(settings.upstream_conn_lifetime, Duration::from_secs(75));
+    assert_eq!(
+        settings.upstream_disconnect_timeout,
+        Duration::from_millis(3000)
+    );
+
+    let settings = with_env(
+        &[
+            ("UPSTREAM_MAX_CONNECTIONS_PER_HOST", "8"),
+            ("UPSTREAM_KEEPALIVE_SECS", "0"),
+            ("UPSTREAM_CONN_LIFETIME_SECS", "300"),
+            ("UPSTREAM_DISCONNECT_TIMEOUT_MS", "500"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(settings.upstream_max_connections_per_host, 8);
+    assert_eq!(settings.upstream_keepalive, Duration::ZERO);
+    assert_eq!(settings.upstream_conn_lifetime, Duration::from_secs(300));
+    assert_eq!(
+        settings.upstream_disconnect_timeout,
+        Duration::from_millis(500)
+    );
+
+    for (name, value) in [
+        ("UPSTREAM_MAX_CONNECTIONS_PER_HOST", "0"),
+        ("UPSTREAM_MAX_CONNECTIONS_PER_HOST", "-2"),
+        ("UPSTREAM_KEEPALIVE_SECS", "soon"),
+        ("UPSTREAM_CONN_LIFETIME_SECS", "0"),
+        ("UPSTREAM_DISCONNECT_TIMEOUT_MS", "0"),
+        ("UPSTREAM_DISCONNECT_TIMEOUT_MS", "900000"),
+    ] {
+        let err = with_env(&[(name, value)], Settings::load).unwrap_err();
+        assert!(
+            matches!(err, ConfigError::InvalidNumber { name: ref found, .. } if found == name),
+            "{name}={value}: {err}"
+        );
+    }
+}
+
+#[test]
+fn reads_upstream_error_policy() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert_eq!(settings.upstream_error_policy, StatusPolicies::default());
+
+    let settings = with_env(
+        &[
+            ("UPSTREAM_ERROR_POLICY", "wrap"),
+            ("UPSTREAM_ERROR_POLICY_OVERRIDES", "429:passthrough"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(
+        settings.upstream_error_policy,
+        StatusPolicies::new(
+            StatusPolicy::Wrap,
+            vec![(StatusCode::TOO_MANY_REQUESTS, StatusPolicy::Passthrough)],
+        )
+    );
+
+    for (name, value) in [
+        ("UPSTREAM_ERROR_POLICY", "rewrite"),
+        ("UPSTREAM_ERROR_POLICY_OVERRIDES", "429=passthrough"),
+    ] {
+        let err = with_env(&[(name, value)], Settings::load).unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidValue { .. }), "{name}");
+    }
+}
+
+#[test]
+fn reads_api_keys() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert!(settings.api_keys.is_none());
+    assert_eq!(settings.api_keys_public, ["/health", "/", "/validators"]);
+
+    let hash = "a".repeat(64);
+    let path = env::temp_dir().join(format!("solana-api-keys-{}", std::process::id()));
+    std::fs::write(&path, format!("bob,{hash},revoked\n")).unwrap();
+    let settings = with_env(
+        &[
+            ("API_KEYS", &format!("alice:{hash}")),
+            ("API_KEYS_FILE", path.to_str().unwrap()),
+            ("API_KEYS_PUBLIC", "/health"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let keys = settings.api_keys.unwrap();
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys.file(), Some(path.as_path()));
+    assert_eq!(settings.api_keys_public, ["/health"]);
+    assert_eq!(settings.api_keys_reload_interval, Duration::from_secs(5));
+
+    let settings = with_env(&[("API_KEYS_RELOAD_SECS", "0")], Settings::load).unwrap();
+    assert!(settings.api_keys_reload_interval.is_zero());
+
+    for (name, value) in [
+        ("API_KEYS", "alice"),
+        ("API_KEYS_FILE", "/nonexistent/keys"),
+        ("API_KEYS_PUBLIC", "/stats"),
+    ] {
+        let err = with_env(&[(name, value)], Settings::load).unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidValue { .. }), "{name}");
+    }
+}
+
+#[test]
+fn reads_the_default_key_rate_limit() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert!(settings.api_key_rate_limit.is_none());
+
+    let settings = with_env(&[("API_KEY_RATE_LIMIT_RPS", "2.5")], Settings::load).unwrap();
+    assert_eq!(settings.api_key_rate_limit, KeyRateLimit::new(2.5, Some(3)));
+
+    let settings = with_env(
+        &[
+            ("API_KEY_RATE_LIMIT_RPS", "10"),
+            ("API_KEY_RATE_LIMIT_BURST", "50"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(settings.api_key_rate_limit.unwrap().burst, 50);
+
+    for (vars, name) in [
+        (
+            &[("API_KEY_RATE_LIMIT_RPS", "0")][..],
+            "API_KEY_RATE_LIMIT_RPS",
+        ),
+        (
+            &[("API_KEY_RATE_LIMIT_RPS", "fast")][..],
+            "API_KEY_RATE_LIMIT_RPS",
+        ),
+        (
+            &[("API_KEY_RATE_LIMIT_BURST", "5")][..],
+            "API_KEY_RATE_LIMIT_BURST",
+        ),
+        (
+            &[
+                ("API_KEY_RATE_LIMIT_RPS", "1"),
+                ("API_KEY_RATE_LIMIT_BURST", "0"),
+            ][..],
+            "API_KEY_RATE_LIMIT_BURST",
+        ),
+    ] {
+        let err = with_env(vars, Settings::load).unwrap_err();
+        assert_eq!(err.name(), Some(name), "{vars:?}");
+    }
+}
+
+#[test]
+fn reads_the_admin_token() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert!(settings.admin_token.is_none());
+
+    let settings = with_env(&[("ADMIN_TOKEN", " operator ")], Settings::load).unwrap();
+    assert_eq!(settings.admin_token, Some("operator".into()));
+
+    let settings = with_env(
+        &[("ADMIN_TOKEN", "operator"), ("ADMIN_ENABLED", "false")],
+        Settings::load,
+    )
+    .unwrap();
+    assert!(settings.admin_token.is_none());
+
+    // Admin routes switched on with nothing to protect them.
+    let err = with_env(&[("ADMIN_ENABLED", "true")], Settings::load).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::Conflict { ref name, .. } if name == "ADMIN_ENABLED"),
+        "{err}"
+    );
+
+    // A token a client also holds. The sha256 is that of "operator".
+    let err = with_env(
+        &[
+            ("ADMIN_TOKEN", "operator"),
+            (
+                "API_KEYS",
+                "ops:06e55b633481f7bb072957eabcf110c972e86691c3cfedabe088024bffe42f23",
+            ),
+        ],
+        Settings::load,
+    )
+    .unwrap_err();
+    assert!(
+        matches!(err, ConfigError::Conflict { ref name, .. } if name == "ADMIN_TOKEN"),
+        "{err}"
+    );
+    assert!(!err.to_string().contains("operator"), "{err}");
+}
+
+#[test]
+fn reads_the_signature_window() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert_eq!(settings.hmac_max_skew, Duration::from_secs(300));
+    assert_eq!(settings.hmac_secret, None);
+
+    let settings = with_env(
+        &[("HMAC_MAX_SKEW_SECS", "30"), ("HMAC_SECRET", "s3cret")],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(settings.hmac_max_skew, Duration::from_secs(30));
+    assert_eq!(settings.hmac_secret.as_deref(), Some("s3cret"));
+    let config = effective_config::render(&settings);
+    assert_eq!(config["hmac_secret"]["value"], "<redacted>");
+
+    let err = with_env(&[("HMAC_MAX_SKEW_SECS", "soon")], Settings::load).unwrap_err();
+    assert!(matches!(err, ConfigError::InvalidNumber { .. }));
+}
+
+#[test]
+fn reads_anonymous_rate_limits() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert!(settings.global_rate_limit.is_none());
+    assert!(settings.client_rate_limit.is_none());
+    assert!(settings.monitoring_rate_limit.is_none());
+    assert!(settings.trusted_proxies.is_empty());
+
+    let settings = with_env(
+        &[
+            ("GLOBAL_RATE_LIMIT_RPS", "1000"),
+            ("CLIENT_RATE_LIMIT_RPS", "20"),
+            ("CLIENT_RATE_LIMIT_BURST", "40"),
+            ("MONITORING_RATE_LIMIT_RPS", "1"),
+            ("TRUSTED_PROXIES", "10.0.0.0/8, 2001:db8::1"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(settings.global_rate_limit, KeyRateLimit::new(1000.0, None));
+    assert_eq!(
+        settings.client_rate_limit,
+        KeyRateLimit::new(20.0, Some(40))
+    );
+    assert_eq!(settings.monitoring_rate_limit, KeyRateLimit::new(1.0, None));
+    assert_eq!(settings.trusted_proxies.len(), 2);
+
+    for (name, value) in [
+        ("GLOBAL_RATE_LIMIT_RPS", "-1"),
+        ("CLIENT_RATE_LIMIT_BURST", "10"),
+        ("TRUSTED_PROXIES", "10.0.0.0/40"),
+    ] {
+        let err = with_env(&[(name, value)], Settings::load).unwrap_err();
+        assert_eq!(err.name(), Some(name), "{err}");
+    }
+}
+
+#[test]
+fn reads_ip_filter() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert!(!settings.ip_filter.is_active());
+
+    let settings = with_env(
+        &[
+            ("IP_ALLOWLIST", "10.0.0.0/8, fd00::/8"),
+            ("IP_DENYLIST", "10.9.0.0/16"),
+            ("IP_ALLOWLIST_UNLISTED", "allow"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(settings.ip_filter.allow.len(), 2);
+    assert_eq!(settings.ip_filter.deny.len(), 1);
+    assert_eq!(settings.ip_filter.unlisted, Unlisted::Allow);
+
+    for (name, value) in [
+        ("IP_ALLOWLIST", "10.0.0.0/8,backend"),
+        ("IP_DENYLIST", "2001:db8::/129"),
+        ("IP_ALLOWLIST_UNLISTED", "maybe"),
+    ] {
+        let err = with_env(&[(name, value)], Settings::load).unwrap_err();
+        assert!(
+            matches!(err, ConfigError::InvalidValue { name: ref got, .. } if got == name),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn reads_cors_settings() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert!(settings.cors.is_none());
+
+    let settings = with_env(
+        &[
+            ("CORS_ALLOWED_ORIGINS", "https://dapp.example"),
+            ("CORS_ALLOWED_HEADERS", "X-Api-Client, content-type"),
+            ("CORS_MAX_AGE_SECS", "3600"),
+            ("CORS_ALLOW_CREDENTIALS", "true"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    let cors = settings.cors.unwrap();
+    assert_eq!(
+        cors.origins,
+        AllowedOrigins::List(vec!["https://dapp.example".to_string()])
+    );
+    assert_eq!(cors.allowed_headers.last().unwrap(), "x-api-client");
+    assert_eq!(
+        cors.allowed_headers
+            .iter()
+            .filter(|name| *name == "content-type")
+            .count(),
+        1
+    );
+    assert_eq!(cors.max_age, Duration::from_secs(3600));
+    assert!(cors.allow_credentials);
+
+    let err = with_env(
+        &[
+            ("CORS_ALLOWED_ORIGINS", "*"),
+            ("CORS_ALLOW_CREDENTIALS", "true"),
+        ],
+        Settings::load,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        ConfigError::Conflict { ref name, .. } if name == "CORS_ALLOW_CREDENTIALS"
+    ));
+    let err = with_env(&[("CORS_ALLOWED_ORIGINS", "dapp.example")], Settings::load).unwrap_err();
+    assert!(matches!(
+        err,
+        ConfigError::InvalidValue { ref name, .. } if name == "CORS_ALLOWED_ORIGINS"
+    ));
+}
+
+#[test]
+fn reads_jwt_settings() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert!(settings.jwt.is_none());
+
+    let settings = with_env(
+        &[
+            ("JWT_HS256_SECRET", "shared-secret"),
+            ("JWT_ISSUER", "https://auth.example"),
+            ("JWT_AUDIENCE", "solana-api"),
+            ("JWT_LEEWAY_SECS", "5"),
+            ("JWT_CLIENT_CLAIM", "client_id"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    let jwt = settings.jwt.unwrap();
+    assert_eq!(jwt.key, JwtKey::Hs256(b"shared-secret".to_vec()));
+    assert!(!format!("{:?}", jwt.key).contains("shared-secret"));
+    assert_eq!(jwt.issuer.as_deref(), Some("https://auth.example"));
+    assert_eq!(jwt.audience.as_deref(), Some("solana-api"));
+    assert_eq!(jwt.leeway, Duration::from_secs(5));
+    assert_eq!(jwt.client_claim, "client_id");
+
+    let settings = with_env(
+        &[
+            ("JWT_JWKS_URL", "http://auth.internal/.well-known/jwks.json"),
+            ("JWT_JWKS_REFRESH_SECS", "60"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    let jwt = settings.jwt.unwrap();
+    assert!(matches!(jwt.key, JwtKey::Jwks(ref url) if url.path() == "/.well-known/jwks.json"));
+    assert_eq!(jwt.client_claim, "sub");
+    assert_eq!(jwt.leeway, Duration::from_secs(60));
+    assert_eq!(jwt.jwks_refresh, Duration::from_secs(60));
+
+    for (vars, bad) in [
+        (
+            &[
+                ("JWT_HS256_SECRET", "shared-secret"),
+                ("JWT_JWKS_URL", "http://auth.internal/jwks"),
+            ][..],
+            "JWT_JWKS_URL",
+        ),
+        (
+            &[("JWT_JWKS_URL", "ftp://auth.internal/jwks")][..],
+            "JWT_JWKS_URL",
+        ),
+        (
+            &[("JWT_HS256_SECRET", "s"), ("JWT_CLIENT_CLAIM", " ")][..],
+            "JWT_CLIENT_CLAIM",
+        ),
+    ] {
+        let err = with_env(vars, Settings::load).unwrap_err();
+        assert_eq!(err.name(), Some(bad), "{err:?}");
+    }
+}
+
+// Loads with `contents` as CONFIG_FILE, alongside `vars`.
+fn with_file<T>(contents: &str, vars: &[(&str, &str)], f: impl FnOnce() -> T) -> T {
+    let path = env::temp_dir().join(format!(
+        "solana-api-config-{}-{:?}.toml",
+        std::process::id(),
+        std::thread::current().id()
+    ));
+    std::fs::write(&path, contents).unwrap();
+    let file = path.to_str().unwrap().to_string();
+    let mut all = vec![("CONFIG_FILE", file.as_str())];
+    all.extend_from_slice(vars);
+    let result = with_env(&all, f);
+    std::fs::remove_file(&path).unwrap();
+    result
+}
+
+const CONFIG_FILE: &str = r#"
+bind_address = "127.0.0.1:8080"
+max_retries = 5
+shadow_sample_rate = 0.25
+latency_buckets_ms = [10, 50.5, 200]
+api_keys_public = ["/health"]
+metrics_enabled = false
+
+[upstream]
+timeout_ms = 2500
+
+[jwt]
+hs256_secret = "shared"
+leeway_secs = 5
+
+[cors]
+allowed_origins = "https://app.example"
+max_age_secs = 60
+"#;
+
+#[test]
+fn reads_settings_from_the_config_file() {
+    let settings = with_file(CONFIG_FILE, &[], Settings::load).unwrap();
+    assert_eq!(settings.bind_address, "127.0.0.1:8080");
+    assert_eq!(settings.max_retries, 5);
+    assert_eq!(settings.shadow_sample_rate, 0.25);
+    assert_eq!(settings.latency_buckets_ms, [10.0, 50.5, 200.0]);
+    assert_eq!(settings.api_keys_public, ["/health"]);
+    assert!(!settings.metrics_enabled);
+    assert_eq!(settings.upstream_timeout, Duration::from_millis(2500));
+    let jwt = settings.jwt.unwrap();
+    assert_eq!(jwt.key, JwtKey::Hs256(b"shared".to_vec()));
+    assert_eq!(jwt.leeway, Duration::from_secs(5));
+    assert_eq!(settings.cors.unwrap().max_age, Duration::from_secs(60));
+    assert!(settings.unknown_config_keys.is_empty());
+}
+
+#[test]
+fn environment_variables_override_the_config_file() {
+    let settings = with_file(
+        CONFIG_FILE,
+        &[
+            ("BIND_ADDRESS", "0.0.0.0:9000"),
+            ("UPSTREAM_TIMEOUT_MS", "700"),
+            // Set but empty still wins over the file.
+            ("API_KEYS_PUBLIC", ""),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(settings.bind_address, "0.0.0.0:9000");
+    assert_eq!(settings.upstream_timeout, Duration::from_millis(700));
+    assert!(settings.api_keys_public.is_empty());
+    // Untouched by the environment.
+    assert_eq!(settings.max_retries, 5);
+
+    // Without a file, defaults stand in for it.
+    let settings = with_env(&[("MAX_RETRIES", "1")], Settings::load).unwrap();
+    assert_eq!(settings.max_retries, 1);
+    assert_eq!(settings.bind_address, Settings::default().bind_address);
+}
+
+#[test]
+fn reads_listeners_from_the_bind_address_or_the_config_file() {
+    let listener = |address: &str, tls, auth| Listener {
+        address: address.to_string(),
+        tls,
+        auth,
+    };
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert_eq!(settings.listeners, [listener("0.0.0.0:80", false, true)]);
+
+    let settings = with_env(
+        &[("BIND_ADDRESS", "10.0.0.5:8080 auth=false, [::]:8443")],
+        Settings::load,
+    )
+    .unwrap();
+    assert_eq!(
+        settings.listeners,
+        [
+            listener("10.0.0.5:8080", false, false),
+            listener("[::]:8443", false, true),
+        ]
+    );
+
+    // With a certificate, listeners use TLS unless told otherwise.
+    let tls = [
+        ("TLS_CERT_FILE", "/etc/tls/fullchain.pem"),
+        ("TLS_KEY_FILE", "/etc/tls/privkey.pem"),
+    ];
+    let contents = r#"
+[[listeners]]
+address = "0.0.0.0:443"
+
+[[listeners]]
+address = "10.0.0.5:8080"
+tls = false
+auth = false
+"#;
+    let settings = with_file(contents, &tls, Settings::load).unwrap();
+    assert_eq!(
+        settings.listeners,
+        [
+            listener("0.0.0.0:443", true, true),
+            listener("10.0.0.5:8080", false, false),
+        ]
+    );
+    assert!(settings.unknown_config_keys.is_empty());
+
+    for value in [
+        "",
+        "0.0.0.0:80 tls=yes",
+        "0.0.0.0:80 0.0.0.0:81",
+        "0.0.0.0:80 secure=true",
+        "tls=false",
+        // No certificate to serve.
+        "0.0.0.0:443 tls=true",
+    ] {
+        let err = with_env(&[("BIND_ADDRESS", value)], Settings::load).unwrap_err();
+        assert_eq!(err.name(), Some("BIND_ADDRESS"), "{value}: {err}");
+    }
+}
+
+#[test]
+fn reads_the_shutdown_timeout() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert_eq!(settings.shutdown_timeout, Duration::from_secs(30));
+    let settings = with_env(&[("SHUTDOWN_TIMEOUT_SECS", "5")], Settings::load).unwrap();
+    assert_eq!(settings.shutdown_timeout, Duration::from_secs(5));
+}
+
+#[test]
+fn reads_the_registry_reload_interval() {
+    let settings = with_env(&[], Settings::load).unwrap();
+    assert!(settings.validators_reload_interval.is_zero());
+    let settings = with_env(&[("VALIDATORS_RELOAD_SECS", "2")], Settings::load).unwrap();
+    assert_eq!(settings.validators_reload_interval, Duration::from_secs(2));
+    let err = with_env(&[("VALIDATORS_RELOAD_SECS", "often")], Settings::load).unwrap_err();
+    assert_eq!(err.name(), Some("VALIDATORS_RELOAD_SECS"));
+}
+
+#[test]
+fn normalizes_bind_address_shorthands() {
+    let settings = with_env(
+        &[
+            ("BIND_ADDRESS", "8080, :8081 auth=false"),
+            ("HEALTH_BIND_ADDRESS", "9090"),
+        ],
+        Settings::load,
+    )
+    .unwrap();
+    let addresses: Vec<_> = settings
+        .listeners
+        .iter()
+        .map(|listener| listener.address.as_str())
+        .collect();
+    assert_eq!(addresses, ["0.0.0.0:8080", "0.0.0.0:8081"]);
+    assert_eq!(
+        settings.health_bind_address.as_deref(),
+        Some("0.0.0.0:9090")
+    );
+
+    for (name, value) in [
+        ("BIND_ADDRESS", "70000"),
+        ("HEALTH_BIND_ADDRESS", "host:port"),
+    ] {
+        let err = with_env(&[(name, value)], Settings::load).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ConfigError::InvalidBindAddress { name: ref got, ref input, ref attempted }
+                    if got == name && input == value && !attempted.is_empty()
+            ),
+            "{value}: {err}"
+        );
+    }
+}
+
+#[test]
+fn reports_unknown_config_file_keys() {
+    let contents = "max_retries = 1\nmax_retires = 2\n\n[upstream]\ntimeot_ms = 5\n";
+    let settings = with_file(contents, &[], Settings::load).unwrap();
+    assert_eq!(
+        settings.unknown_config_keys,
+        ["max_retires", "upstream_timeot_ms"]
+    );
+
+    // Keys that only matter once another one is set are still known.
+    let settings = with_file("jwt_issuer = \"idp\"\n", &[], Settings::load).unwrap();
+    assert!(settings.unknown_config_keys.is_empty());
+
+    for (contents, vars) in [
+        (contents.to_string(), &[("CONFIG_STRICT", "true")][..]),
+        (format!("config_strict = true\n{contents}"), &[][..]),
+    ] {
+        let err = with_file(&contents, vars, Settings::load).unwrap_err();
+        assert!(
+            matches!(err, ConfigError::UnknownConfigKeys(ref keys) if keys.len() == 2),
+            "{err}"
+        );
+    }
+}
+
+#[test]
+fn rejects_invalid_config_files() {
+    for contents in [
+        "max_retries = ",
+        "upstream_timeout_ms = 1\n[upstream]\ntimeout_ms = 2\n",
+        "[[validators]]\nname = \"a\"\n",
+        "when = 1979-05-27\n",
+    ] {
+        let err = with_file(contents, &[], Settings::load).unwrap_err();
+        assert!(
+            matches!(err, ConfigError::ConfigFile { .. }),
+            "{contents}: {err}"
+        );
+    }
+
+    // Values are checked as their environment variable would be.
+    let err = with_file("max_retries = \"many\"\n", &[], Settings::load).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidNumber { ref name, .. } if name == "MAX_RETRIES"),
+        "{err}"
+    );
+
+    let err = with_env(
+        &[("CONFIG_FILE", "/nonexistent/solana-api.toml")],
+        Settings::load,
+    )
+    .unwrap_err();
+    assert!(matches!(err, ConfigError::ConfigFile { .. }), "{err}");
+}
+
+#[test]
+fn reads_prefixed_variables_first() {
+    // Without a prefix, prefixed names mean nothing.
+    let settings = with_env(&[("TSA_MAX_RETRIES", "5")], Settings::load).unwrap();
+    assert_eq!(settings.max_retries, Settings::default().max_retries);
+
+    for prefix in ["TSA", "TSA_"] {
+        let settings = with_env(
+            &[
+                ("APP_ENV_PREFIX", prefix),
+                ("TSA_MAX_RETRIES", "5"),
+                ("MAX_RETRIES", "1"),
+                ("WORKERS", "3"),
+            ],
+            Settings::load,
+        )
+        .unwrap();
+        assert_eq!(settings.max_retries, 5, "{prefix}");
+        // Bare names still fill in what the prefixed ones leave out.
+        assert_eq!(settings.workers, 3, "{prefix}");
+    }
+
+    let err = with_env(
+        &[
+            ("APP_ENV_PREFIX", "TSA"),
+            ("TSA_WORKERS", "0"),
+            ("WORKERS", "2"),
+        ],
+        Settings::load,
+    )
+    .unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidNumber { ref name, .. } if name == "WORKERS"),
+        "{err}"
+    );
+}
+
+#[test]
+fn reads_the_env_file_under_the_environment() {
+    let path = env::temp_dir().join(format!("solana-api-{}.env", std::process::id()));
+    std::fs::write(
+        &path,
+        "# local overrides\n\
+         export MAX_RETRIES=4\n\
+         UPSTREAM_TIMEOUT_MS=\"900\"\n\
+         ADMIN_TOKEN='dotenv-admin-secret'\n\
+         dotenv-stray-secret\n\
+         WORKERS=2\n",
+    )
+    .unwrap();
+    let args = Args {
+        env_file: Some(path.clone()),
+        ..Args::default()
+    };
+    let settings = with_env(&[("WORKERS", "3")], || Settings::load_with(&args)).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(settings.max_retries, 4);
+    assert_eq!(settings.upstream_timeout, Duration::from_millis(900));
+    // The real environment wins.
+    assert_eq!(settings.workers, 3);
+    assert_eq!(settings.env_file_warnings.len(), 1);
+    assert!(settings.env_file_warnings[0].ends_with(":5: expected NAME=VALUE"));
+    // Neither the settings nor the warnings give secrets away.
+    let logged = format!("{settings:?}");
+    assert!(!logged.contains("dotenv-admin-secret"));
+    assert!(!logged.contains("dotenv-stray-secret"));
+
+    // A missing `./.env` is skipped, but not a file asked for by name.
+    let settings = with_env(&[], || Settings::load_with(&Args::default())).unwrap();
+    assert!(settings.env_file_warnings.is_empty());
+    let err = with_env(&[], || Settings::load_with(&args)).unwrap_err();
+    assert!(matches!(err, ConfigError::EnvFile { .. }), "{err}");
+}
+
+#[test]
+fn builds_settings_programmatically() {
+    let settings = Settings::builder()
+        .max_retries(5)
+        .upstream_timeout(Duration::from_millis(250))
+        .bind_address("127.0.0.1:9000,[::1]:9001")
+        .build()
+        .unwrap();
+    assert_eq!(settings.max_retries, 5);
+    assert_eq!(settings.upstream_timeout, Duration::from_millis(250));
+    assert_eq!(settings.listeners.len(), 2);
+
+    // The builder checks what loading from the environment would.
+    for (builder, expected) in [
+        (Settings::builder().workers(0), "WORKERS"),
+        (
+            Settings::builder().shadow_sample_rate(1.5),
+            "SHADOW_SAMPLE_RATE",
+        ),
+        (Settings::builder().ping_rate_limit(0.0), "PING_RATE_LIMIT"),
+        (
+            Settings::builder().bind_address("127.0.0.1:9443 tls=true"),
+            "BIND_ADDRESS",
+        ),
+    ] {
+        let err = builder.build().unwrap_err();
+        assert_eq!(err.name(), Some(expected), "{err}");
+    }
+}
+
+#[test]
+fn reports_where_each_setting_came_from() {
+    let args = Args::parse(["print-config", "--max-retries", "7"]).unwrap();
+    let settings = with_file(
+        CONFIG_FILE,
+        &[
+            ("WORKERS", "3"),
+            ("ADMIN_TOKEN", "print-admin-secret"),
+            ("GUARDRAIL_BYPASS_VALUE", "print-bypass-secret"),
+            ("SENTRY_DSN", "https://print-dsn-secret@sentry.example/1"),
+            ("TLS_CERT_FILE", "/etc/tls/cert.pem"),
+            ("TLS_KEY_FILE", "/etc/tls/key.pem"),
+        ],
+        || Settings::load_with(&args),
+    )
+    .unwrap();
+    let config = effective_config::render(&settings);
+
+    for (field, source) in [
+        ("max_retries", "flag"),
+        ("workers", "env"),
+        ("upstream_timeout", "file"),
+        ("shadow_sample_rate", "file"),
+        ("rewrite_host", "default"),
+    ] {
+        assert_eq!(config[field]["source"], source, "{field}");
+    }
+    assert_eq!(config["max_retries"]["value"], 7);
+    assert_eq!(config["upstream_timeout"]["value"], "2.5s");
+    assert_eq!(
+        config["upstream_timeout"]["variables"][0],
+        "UPSTREAM_TIMEOUT_MS"
+    );
+    assert_eq!(config["default_location"]["value"], Value::Null);
+
+    // Secrets show as set, never as given; TLS files by path only.
+    for field in [
+        "admin_token",
+        "guardrail_bypass_value",
+        "sentry_dsn",
+        "validators_inline",
+    ] {
+        let expected = if field == "validators_inline" {
+            Value::Null
+        } else {
+            json!("<redacted>")
+        };
+        assert_eq!(config[field]["value"], expected, "{field}");
+    }
+    assert!(
+        config["jwt"]["value"]
+            .as_str()
+            .unwrap()
+            .contains("<redacted>")
+    );
+    let tls = config["tls"]["value"].as_str().unwrap();
+    assert!(tls.contains("/etc/tls/key.pem"), "{tls}");
+    for rendered in [config.to_string(), effective_config::render_toml(&settings)] {
+        for secret in [
+            "print-admin-secret",
+            "print-bypass-secret",
+            "print-dsn-secret",
+            "shared",
+        ] {
+            assert!(!rendered.contains(secret), "{secret}");
+        }
+    }
+
+    let toml: toml::Table = effective_config::render_toml(&settings).parse().unwrap();
+    assert_eq!(toml["max_retries"]["value"].as_integer(), Some(7));
+    assert_eq!(toml["workers"]["source"].as_str(), Some("env"));
+    assert!(toml["default_location"].get("value").is_none());
+}
+
+#[test]
+fn reports_every_problem_at_once() {
+    let err = with_env(
+        &[
+            ("VALIDATORS_CSV", "/nonexistent/validators.csv"),
+            ("BIND_ADDRESS", "70000"),
+            ("MAX_RETRIES", "many"),
+            ("UPSTREAM_TIMEOUT_MS", "0"),
+            ("TLS_KEY_FILE", "/etc/tls/key.pem"),
+            ("LOG_FORMAT", "xml"),
+        ],
+        Settings::load,
+    )
+    .unwrap_err();
+    let problems = err.problems();
+    assert!(matches!(err, ConfigError::Multiple(_)), "{err}");
+    assert!(matches!(problems[0], ConfigError::MissingValidatorsCsv(_)));
+    let names: Vec<_> = problems.iter().filter_map(ConfigError::name).collect();
+    assert_eq!(
+        names,
+        [
+            "TLS_KEY_FILE",
+            "BIND_ADDRESS",
+            "MAX_RETRIES",
+            "UPSTREAM_TIMEOUT_MS",
+            "LOG_FORMAT"
+        ]
+    );
+    // A problem per line, under a count.
+    let lines: Vec<_> = err.to_string().lines().map(String::from).collect();
+    assert_eq!(lines.len(), 7, "{err}");
+    assert_eq!(lines[0], "6 problems in the configuration:");
+    assert_eq!(
+        lines[4],
+        "  invalid value 'many' for MAX_RETRIES: expected a whole number"
+    );
+
+    // Alone, a problem is returned as itself.
+    let err = with_env(&[("MAX_RETRIES", "many")], Settings::load).unwrap_err();
+    assert_eq!(err.problems().len(), 1);
+    assert!(matches!(err, ConfigError::InvalidNumber { .. }), "{err}");
+}
+
+#[test]
+fn describes_invalid_numbers() {
+    for (name, value, expected) in [
+        ("MAX_RETRIES", "-1", "a whole number"),
+        ("SHADOW_SAMPLE_RATE", "half", "a number"),
+        ("SHADOW_SAMPLE_RATE", "2", "a number between 0 and 1"),
+        ("WORKERS", "0", "a whole number of at least 1"),
+        ("CLIENT_RATE_LIMIT_RPS", "fast", "a number"),
+        ("CACHE_SHORT_TTL_MS", "1.5", "a whole number"),
+    ] {
+        let err = with_env(&[(name, value)], Settings::load).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("invalid value '{value}' for {name}: expected {expected}")
+        );
+    }
+}
+
+#[test]
+fn reports_conflicting_settings() {
+    for (vars, name, other) in [
+        (
+            &[("TLS_CERT_FILE", "/etc/tls/cert.pem")][..],
+            "TLS_CERT_FILE",
+            "TLS_KEY_FILE",
+        ),
+        (
+            &[("TLS_KEY_FILE", "/etc/tls/key.pem")][..],
+            "TLS_KEY_FILE",
+            "TLS_CERT_FILE",
+        ),
+        (
+            &[("BIND_ADDRESS", "0.0.0.0:443 tls=true")][..],
+            "BIND_ADDRESS",
+            "TLS_CERT_FILE",
+        ),
+        (
+            &[("GLOBAL_RATE_LIMIT_BURST", "10")][..],
+            "GLOBAL_RATE_LIMIT_BURST",
+            "GLOBAL_RATE_LIMIT_RPS",
+        ),
+        (
+            &[
+                ("JWT_HS256_SECRET", "s"),
+                ("JWT_JWKS_URL", "http://auth/jwks"),
+            ][..],
+            "JWT_JWKS_URL",
+            "JWT_HS256_SECRET",
+        ),
+        (
+            &[("ADMIN_ENABLED", "true")][..],
+            "ADMIN_ENABLED",
+            "ADMIN_TOKEN",
+        ),
+    ] {
+        let err = with_env(vars, Settings::load).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ConfigError::Conflict { name: ref got, other: ref found, .. }
+                    if got == name && found == other
+            ),
+            "{vars:?}: {err}"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use actix_web::http::{StatusCode, header};
+use actix_web::{HttpRequest, HttpResponse, test, web};
+use serde_json::Value;
+
+use the_solana_api::{AppState, Settings};
+
+const BODY: &str = r#"{"jsonrpc":"2.0","id":1,"method":"getSlot"}"#;
+
+fn echo_content_type_upstream() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|req: HttpRequest| async move {
+                let content_type = req
+                    .headers()
+                    .get(header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string();
+                HttpResponse::Ok().body(content_type)
+            }),
+        );
+    })
+}
+
+async fn post(settings: Settings, content_type: Option<&str>, body: &str) -> (StatusCode, String) {
+    let upstream = echo_content_type_upstream();
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("ct-1", "lab")]),
+        settings,
+    );
+    let app = common::init_app(state).await;
+
+    let mut request = test::TestRequest::post()
+        .uri("/")
+        .set_payload(body.to_string());
+    if let Some(content_type) = content_type {
+        request = request.insert_header((header::CONTENT_TYPE, content_type));
+    }
+
+    let response = test::call_service(&app, request.to_request()).await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+fn enforcing(lenient: bool) -> Settings {
+    Settings {
+        enforce_json_content_type: true,
+        lenient_content_type: lenient,
+        ..Settings::default()
+    }
+}
+
+#[actix_web::test]
+async fn accepts_any_content_type_by_default() {
+    let (status, forwarded) = post(Settings::default(), Some("text/plain"), BODY).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(forwarded, "text/plain");
+}
+
+#[actix_web::test]
+async fn accepts_json_with_parameters_when_enforcing() {
+    let (status, forwarded) = post(
+        enforcing(false),
+        Some("application/json; charset=utf-8"),
+        BODY,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(forwarded, "application/json; charset=utf-8");
+}
+
+#[actix_web::test]
+async fn rejects_other_content_types_when_enforcing() {
+    for content_type in [Some("text/plain"), Some("application/xml"), None] {
+        let (status, body) = post(enforcing(false), content_type, BODY).await;
+
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let error: Value = serde_json::from_str(&body).unwrap();
+        assert!(
+            error["error"]
+                .as_str()
+                .unwrap()
+                .contains("expected 'application/json'")
+        );
+    }
+}
+
+#[actix_web::test]
+async fn lenient_mode_accepts_plain_text_json() {
+    let (status, forwarded) = post(enforcing(true), Some("text/plain"), BODY).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(forwarded, "application/json");
+}
+
+#[actix_web::test]
+async fn lenient_mode_still_rejects_non_json_bodies() {
+    let (status, _) = post(enforcing(true), Some("text/plain"), "getSlot please").await;
+
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[actix_web::test]
+async fn lenient_mode_rejects_form_encoded_bodies() {
+    let (status, _) = post(
+        enforcing(true),
+        Some("application/x-www-form-urlencoded"),
+        BODY,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
@@ -0,0 +1,206 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::json;
+
+use the_solana_api::auth::ApiKeys;
+use the_solana_api::cors::{AllowedOrigins, CorsSettings};
+use the_solana_api::{AppState, Settings};
+
+const DAPP: &str = "https://dapp.example";
+
+fn state(upstream: &common::MockUpstream, settings: Settings) -> AppState {
+    AppState::from_settings(
+        common::registry(vec![upstream.validator("node", "lab")]),
+        settings,
+    )
+}
+
+fn listed() -> Option<CorsSettings> {
+    Some(CorsSettings::new(AllowedOrigins::List(vec![
+        DAPP.to_string(),
+    ])))
+}
+
+fn preflight(uri: &str, origin: &str) -> test::TestRequest {
+    test::TestRequest::default()
+        .method(actix_web::http::Method::OPTIONS)
+        .uri(uri)
+        .insert_header(("origin", origin))
+        .insert_header(("access-control-request-method", "POST"))
+        .insert_header(("access-control-request-headers", "content-type"))
+}
+
+fn get_slot() -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+}
+
+fn header<'a>(resp: &'a actix_web::dev::ServiceResponse, name: &str) -> Option<&'a str> {
+    resp.headers()
+        .get(name)
+        .map(|value| value.to_str().unwrap())
+}
+
+#[actix_web::test]
+async fn answers_preflight_without_calling_a_validator() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = common::counting_upstream(hits.clone());
+    let app = common::init_app(state(
+        &upstream,
+        Settings {
+            cors: listed(),
+            // Preflights carry no credentials, so they must get past auth.
+            api_keys: Some(ApiKeys::new(Vec::new())),
+            ..Settings::default()
+        },
+    ))
+    .await;
+
+    for uri in ["/", "/slot", "/upstream/health", "/validators"] {
+        let resp = test::call_service(&app, preflight(uri, DAPP).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT, "{uri}");
+        assert_eq!(header(&resp, "access-control-allow-origin"), Some(DAPP));
+        assert_eq!(
+            header(&resp, "access-control-allow-methods"),
+            Some("GET, POST, OPTIONS")
+        );
+        let allowed = header(&resp, "access-control-allow-headers").unwrap();
+        for name in ["content-type", "authorization", "x-validator", "x-no-retry"] {
+            assert!(allowed.contains(name), "{allowed}");
+        }
+        assert_eq!(header(&resp, "access-control-max-age"), Some("600"));
+        assert_eq!(header(&resp, "vary"), Some("Origin"));
+        assert!(header(&resp, "access-control-allow-credentials").is_none());
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
+
+#[actix_web::test]
+async fn refuses_preflight_from_unlisted_origins() {
+    let upstream = common::counting_upstream(Arc::default());
+    let app = common::init_app(state(
+        &upstream,
+        Settings {
+            cors: listed(),
+            ..Settings::default()
+        },
+    ))
+    .await;
+
+    let resp = test::call_service(&app, preflight("/", "https://evil.example").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert!(header(&resp, "access-control-allow-origin").is_none());
+}
+
+#[actix_web::test]
+async fn decorates_cross_origin_posts() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = common::counting_upstream(hits.clone());
+    let app = common::init_app(state(
+        &upstream,
+        Settings {
+            cors: listed(),
+            ..Settings::default()
+        },
+    ))
+    .await;
+
+    let resp = test::call_service(
+        &app,
+        get_slot().insert_header(("origin", DAPP)).to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(header(&resp, "access-control-allow-origin"), Some(DAPP));
+    assert!(
+        header(&resp, "access-control-expose-headers")
+            .unwrap()
+            .contains("x-request-id")
+    );
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    // Other origins are served, but the browser is not told it may read it.
+    let resp = test::call_service(
+        &app,
+        get_slot()
+            .insert_header(("origin", "https://evil.example"))
+            .to_request(),
+    )
+    .await;
+    assert!(header(&resp, "access-control-allow-origin").is_none());
+
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert!(header(&resp, "access-control-allow-origin").is_none());
+}
+
+#[actix_web::test]
+async fn wildcard_answers_any_origin_including_errors() {
+    let upstream = common::counting_upstream(Arc::default());
+    let cors = CorsSettings {
+        allowed_headers: vec!["content-type".to_string(), "x-custom".to_string()],
+        max_age: std::time::Duration::from_secs(60),
+        ..CorsSettings::new(AllowedOrigins::Any)
+    };
+    let app = common::init_app(state(
+        &upstream,
+        Settings::builder()
+            .cors(cors)
+            .api_keys(ApiKeys::new(Vec::new()))
+            .build()
+            .unwrap(),
+    ))
+    .await;
+
+    let resp =
+        test::call_service(&app, preflight("/", "https://anyone.example").to_request()).await;
+    assert_eq!(header(&resp, "access-control-allow-origin"), Some("*"));
+    assert_eq!(
+        header(&resp, "access-control-allow-headers"),
+        Some("content-type, x-custom")
+    );
+    assert_eq!(header(&resp, "access-control-max-age"), Some("60"));
+    assert!(header(&resp, "vary").is_none());
+
+    // A page can read why it was turned away.
+    let resp = test::call_service(
+        &app,
+        get_slot()
+            .insert_header(("origin", "https://anyone.example"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(header(&resp, "access-control-allow-origin"), Some("*"));
+}
+
+#[actix_web::test]
+async fn credentials_need_listed_origins() {
+    let wildcard = CorsSettings {
+        allow_credentials: true,
+        ..CorsSettings::new(AllowedOrigins::Any)
+    };
+    assert!(wildcard.validate().is_err());
+    let listed = CorsSettings {
+        allow_credentials: true,
+        ..listed().unwrap()
+    };
+    assert!(listed.validate().is_ok());
+
+    assert_eq!(AllowedOrigins::parse(" * "), Some(AllowedOrigins::Any));
+    assert_eq!(
+        AllowedOrigins::parse("https://A.example/, http://localhost:3000"),
+        Some(AllowedOrigins::List(vec![
+            "https://a.example".to_string(),
+            "http://localhost:3000".to_string(),
+        ]))
+    );
+    for raw in ["", "*, https://a.example", "a.example"] {
+        assert!(AllowedOrigins::parse(raw).is_none(), "{raw}");
+    }
+}
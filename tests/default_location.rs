@@ -0,0 +1,87 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::{AppState, Settings};
+
+fn named_upstream(name: &'static str) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        cfg.route(
+            "/",
+            web::post().to(move || async move {
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": name}))
+            }),
+        );
+    })
+}
+
+fn state(nodes: &[(&'static str, &str, &common::MockUpstream)]) -> AppState {
+    AppState::builder()
+        .validators(
+            nodes
+                .iter()
+                .map(|(name, location, node)| node.validator(name, location))
+                .collect(),
+        )
+        .settings(Settings::builder().default_location("Frankfurt".to_string()))
+        .build()
+        .unwrap()
+}
+
+// The validator that answered, and whether the default location applied.
+async fn call<S>(app: &S, uri: &str) -> (String, bool)
+where
+    S: actix_web::dev::Service<
+            actix_http::Request,
+            Response = actix_web::dev::ServiceResponse,
+            Error = actix_web::Error,
+        >,
+{
+    let req = test::TestRequest::post()
+        .uri(uri)
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let defaulted = resp.headers().get("x-location-defaulted").is_some();
+    let body: Value = test::read_body_json(resp).await;
+    (body["result"].as_str().unwrap().to_string(), defaulted)
+}
+
+#[actix_web::test]
+async fn unqualified_requests_go_to_the_default_location() {
+    let (fra, tyo) = (named_upstream("fra"), named_upstream("tyo"));
+    let app = common::init_app(state(&[("fra", "frankfurt", &fra), ("tyo", "tokyo", &tyo)])).await;
+
+    for _ in 0..10 {
+        assert_eq!(call(&app, "/").await, ("fra".to_string(), true));
+    }
+    // Whatever the client asks for wins, and is not flagged.
+    assert_eq!(
+        call(&app, "/?location=tokyo").await,
+        ("tyo".to_string(), false)
+    );
+    assert_eq!(call(&app, "/?server=tyo").await, ("tyo".to_string(), false));
+}
+
+#[actix_web::test]
+async fn falls_back_to_the_whole_fleet() {
+    // Nothing in the default location.
+    let tyo = named_upstream("tyo");
+    let app = common::init_app(state(&[("tyo", "tokyo", &tyo)])).await;
+    assert_eq!(call(&app, "/").await, ("tyo".to_string(), false));
+
+    // Everything there is cooling down.
+    let (fra, tyo) = (named_upstream("fra"), named_upstream("tyo"));
+    let state = state(&[("fra", "frankfurt", &fra), ("tyo", "tokyo", &tyo)]);
+    state
+        .stats()
+        .validator("fra")
+        .cool_down(Duration::from_secs(60));
+    let app = common::init_app(state).await;
+    for _ in 0..10 {
+        assert_eq!(call(&app, "/").await, ("tyo".to_string(), false));
+    }
+}
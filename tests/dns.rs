@@ -0,0 +1,215 @@
+mod common;
+
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{App, HttpResponse, HttpServer, test, web};
+use serde_json::{Value, json};
+use url::Url;
+
+use the_solana_api::dns::{DnsCache, HostResolver, ResolveFuture};
+use the_solana_api::{AppState, Settings, Validator};
+
+#[derive(Default)]
+struct StubResolver {
+    answers: Mutex<Vec<SocketAddr>>,
+    calls: Mutex<usize>,
+}
+
+impl StubResolver {
+    fn answer(&self, addrs: Vec<SocketAddr>) {
+        *self.answers.lock().unwrap() = addrs;
+    }
+
+    fn calls(&self) -> usize {
+        *self.calls.lock().unwrap()
+    }
+}
+
+impl HostResolver for StubResolver {
+    fn resolve<'a>(&'a self, _host: &'a str, _port: u16) -> ResolveFuture<'a> {
+        *self.calls.lock().unwrap() += 1;
+        let answers = self.answers.lock().unwrap().clone();
+        Box::pin(async move { Ok(answers) })
+    }
+}
+
+fn addr(value: &str) -> SocketAddr {
+    value.parse().unwrap()
+}
+
+#[actix_web::test]
+async fn serves_cached_answers_until_refreshed() {
+    let resolver = Arc::new(StubResolver::default());
+    resolver.answer(vec![addr("10.0.0.1:8899")]);
+
+    let cache = DnsCache::new(resolver.clone(), Duration::from_secs(300));
+    let first = cache.lookup("rpc.example", 8899).await.unwrap();
+    assert_eq!(first, vec![addr("10.0.0.1:8899")]);
+    assert_eq!(cache.generation(), 0);
+
+    resolver.answer(vec![addr("10.0.0.2:8899")]);
+    let cached = cache.lookup("rpc.example", 8899).await.unwrap();
+    assert_eq!(cached, first);
+    assert_eq!(resolver.calls(), 1);
+
+    cache.refresh(&[("rpc.example".to_string(), 8899)]).await;
+    let refreshed = cache.lookup("rpc.example", 8899).await.unwrap();
+    assert_eq!(refreshed, vec![addr("10.0.0.2:8899")]);
+    assert_eq!(cache.generation(), 1);
+    assert_eq!(
+        cache.snapshot().get("rpc.example:8899"),
+        Some(&vec![addr("10.0.0.2:8899")])
+    );
+}
+
+#[actix_web::test]
+async fn zero_ttl_resolves_every_time() {
+    let resolver = Arc::new(StubResolver::default());
+    resolver.answer(vec![addr("10.0.0.1:8899")]);
+
+    let cache = DnsCache::new(resolver.clone(), Duration::ZERO);
+    cache.lookup("rpc.example", 8899).await.unwrap();
+    cache.lookup("rpc.example", 8899).await.unwrap();
+
+    assert_eq!(resolver.calls(), 2);
+}
+
+#[actix_web::test]
+async fn keeps_stale_answer_when_resolution_fails() {
+    let resolver = Arc::new(StubResolver::default());
+    resolver.answer(vec![addr("10.0.0.1:8899")]);
+
+    let cache = DnsCache::new(resolver.clone(), Duration::ZERO);
+    cache.lookup("rpc.example", 8899).await.unwrap();
+
+    resolver.answer(Vec::new());
+    let addrs = cache.lookup("rpc.example", 8899).await.unwrap();
+    assert_eq!(addrs, vec![addr("10.0.0.1:8899")]);
+}
+
+#[actix_web::test]
+async fn proxy_follows_address_changes() {
+    let first = common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async { HttpResponse::Ok().body("first") }),
+        );
+    });
+    let second = common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async { HttpResponse::Ok().body("second") }),
+        );
+    });
+
+    let resolver = Arc::new(StubResolver::default());
+    resolver.answer(vec![first.addr]);
+
+    let validator = Validator::new(
+        "dns-1".into(),
+        "lab".into(),
+        Url::parse(&format!("http://validator.test:{}/", first.addr.port())).unwrap(),
+    );
+    let settings = Settings::builder()
+        .dns_cache_ttl(Duration::from_secs(300))
+        .build()
+        .unwrap();
+    let state = AppState::from_settings(common::registry(vec![validator]), settings)
+        .with_resolver(resolver.clone());
+    let app = common::init_app(state.clone()).await;
+
+    let payload = json!({ "jsonrpc": "2.0", "id": 1, "method": "getSlot" });
+    let request = test::TestRequest::post()
+        .uri("/?server=dns-1")
+        .set_json(&payload)
+        .to_request();
+    let body = test::call_and_read_body(&app, request).await;
+    assert_eq!(body, "first");
+
+    resolver.answer(vec![second.addr]);
+    state.dns().refresh(&state.registry().dns_hosts()).await;
+
+    let request = test::TestRequest::post()
+        .uri("/?server=dns-1")
+        .set_json(&payload)
+        .to_request();
+    let body = test::call_and_read_body(&app, request).await;
+    assert_eq!(body, "second");
+
+    let stats: Value =
+        test::call_and_read_body_json(&app, test::TestRequest::get().uri("/stats").to_request())
+            .await;
+    assert_eq!(
+        stats["validators"][0]["resolved_addresses"],
+        json!([second.addr.to_string()])
+    );
+}
+
+#[actix_web::test]
+async fn address_changes_drop_pooled_connections() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let node = listener.local_addr().unwrap();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = connections.clone();
+    let server = HttpServer::new(|| {
+        App::new().route(
+            "/",
+            // Reading the body lets the connection be kept alive.
+            web::post().to(|_: web::Json<Value>| async {
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 1}))
+            }),
+        )
+    })
+    .on_connect(move |_, _| {
+        accepted.fetch_add(1, Ordering::SeqCst);
+    })
+    .workers(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    let server = tokio::spawn(server);
+
+    let resolver = Arc::new(StubResolver::default());
+    resolver.answer(vec![node]);
+    let validator = Validator::new(
+        "dns-1".into(),
+        "lab".into(),
+        Url::parse(&format!("http://validator.test:{}/", node.port())).unwrap(),
+    );
+    let settings = Settings::builder()
+        .dns_cache_ttl(Duration::from_secs(300))
+        .build()
+        .unwrap();
+    let state = AppState::from_settings(common::registry(vec![validator]), settings)
+        .with_resolver(resolver.clone());
+    let app = common::init_app(state.clone()).await;
+    let get_slot = || {
+        test::TestRequest::post()
+            .uri("/")
+            .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+            .to_request()
+    };
+    // Lets each connection go back to the pool before it is counted.
+    let count = || async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        connections.load(Ordering::SeqCst)
+    };
+
+    test::call_and_read_body(&app, get_slot()).await;
+    assert_eq!(count().await, 1);
+    test::call_and_read_body(&app, get_slot()).await;
+    assert_eq!(count().await, 1);
+
+    // Still the same server, but the answer changed: dialled afresh.
+    resolver.answer(vec![node, addr("127.0.0.1:9")]);
+    state.dns().refresh(&state.registry().dns_hosts()).await;
+    assert_eq!(state.dns().generation(), 1);
+    test::call_and_read_body(&app, get_slot()).await;
+    assert_eq!(count().await, 2);
+    test::call_and_read_body(&app, get_slot()).await;
+    assert_eq!(count().await, 2);
+    server.abort();
+}
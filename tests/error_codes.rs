@@ -0,0 +1,213 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{Value, json};
+use url::Url;
+
+use the_solana_api::errors::{AppError, ErrorCode, ErrorFormat, UpstreamErrorKind};
+use the_solana_api::validators::SelectionError;
+use the_solana_api::{AppState, Settings, Validator};
+
+fn state(settings: Settings) -> AppState {
+    let validator = Validator::new(
+        "dead".into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:9/").unwrap(),
+    );
+    AppState::from_settings(common::registry(vec![validator]), settings)
+}
+
+// Clients match on these strings; renaming one is a breaking change.
+#[actix_web::test]
+async fn codes_are_stable() {
+    let codes: Vec<Value> = ErrorCode::ALL
+        .iter()
+        .map(|code| serde_json::to_value(code).unwrap())
+        .collect();
+    assert_eq!(
+        codes,
+        [
+            "bad_request",
+            "unsupported_media_type",
+            "unauthorized",
+            "forbidden",
+            "key_scope_violation",
+            "not_found",
+            "unknown_validator",
+            "unknown_location",
+            "selection_failed",
+            "upstream_connect_failed",
+            "upstream_dns_failed",
+            "upstream_tls_failed",
+            "upstream_timeout",
+            "upstream_rate_limited",
+            "upstream_bad_status",
+            "upstream_invalid_response",
+            "upstream_body_read_failed",
+            "payload_too_large",
+            "quorum_not_reached",
+            "invalid_registry",
+            "rate_limited",
+            "quota_exceeded",
+            "overloaded",
+            "internal_error",
+            "internal_panic",
+        ]
+    );
+    for code in ErrorCode::ALL {
+        assert_eq!(serde_json::to_value(code).unwrap(), code.as_str());
+    }
+}
+
+#[actix_web::test]
+async fn variants_map_to_codes() {
+    let cases = [
+        (AppError::BadRequest(String::new()), "bad_request"),
+        (
+            AppError::UnsupportedMediaType(String::new()),
+            "unsupported_media_type",
+        ),
+        (
+            AppError::PayloadTooLarge(String::new()),
+            "payload_too_large",
+        ),
+        (AppError::Unauthorized(String::new()), "unauthorized"),
+        (AppError::Forbidden(String::new()), "forbidden"),
+        (
+            AppError::KeyScopeViolation(String::new()),
+            "key_scope_violation",
+        ),
+        (
+            AppError::Selection(SelectionError::Empty),
+            "selection_failed",
+        ),
+        (
+            AppError::Selection(SelectionError::UnknownLocation {
+                location: String::new(),
+                suggestions: Vec::new(),
+            }),
+            "unknown_location",
+        ),
+        (AppError::Internal(String::new()), "internal_error"),
+        (AppError::Panicked, "internal_panic"),
+        (
+            AppError::RateLimited {
+                message: String::new(),
+                retry_after: Duration::from_secs(1),
+            },
+            "rate_limited",
+        ),
+        (
+            AppError::QuotaExceeded {
+                message: String::new(),
+                retry_after: Duration::from_secs(1),
+            },
+            "quota_exceeded",
+        ),
+        (
+            AppError::Overloaded {
+                message: String::new(),
+                retry_after: Duration::from_secs(1),
+            },
+            "overloaded",
+        ),
+    ];
+    for (err, code) in cases {
+        assert_eq!(err.code().as_str(), code);
+        assert_eq!(err.body(ErrorFormat::Plain)["code"], code);
+        assert_eq!(
+            err.body(ErrorFormat::JsonRpc)["error"]["data"]["code"],
+            code
+        );
+    }
+}
+
+#[actix_web::test]
+async fn upstream_codes_follow_the_error_kind() {
+    let cases = [
+        (UpstreamErrorKind::ConnectRefused, "upstream_connect_failed"),
+        (UpstreamErrorKind::DnsFailure, "upstream_dns_failed"),
+        (UpstreamErrorKind::TlsError, "upstream_tls_failed"),
+        (UpstreamErrorKind::Timeout, "upstream_timeout"),
+        (UpstreamErrorKind::BadStatus(429), "upstream_rate_limited"),
+        (UpstreamErrorKind::BadStatus(500), "upstream_bad_status"),
+        (
+            UpstreamErrorKind::InvalidResponse,
+            "upstream_invalid_response",
+        ),
+        (UpstreamErrorKind::ResponseTooLarge, "payload_too_large"),
+        (
+            UpstreamErrorKind::BodyReadError,
+            "upstream_body_read_failed",
+        ),
+    ];
+    for (kind, code) in cases {
+        let err = AppError::upstream("dead", kind, "failed");
+        let body = err.body(ErrorFormat::Plain);
+        assert_eq!(body["code"], code);
+        assert_eq!(body["details"]["validator"], json!("dead"));
+    }
+}
+
+#[actix_web::test]
+async fn responses_carry_codes() {
+    let app = common::init_app(state(Settings::builder().max_retries(0).build().unwrap())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "upstream_connect_failed");
+    assert_eq!(body["details"]["validator"], "dead");
+    assert!(body["error"].as_str().unwrap().contains("dead"));
+
+    let req = test::TestRequest::post()
+        .uri("/?server=deed")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "unknown_validator");
+
+    let req = test::TestRequest::get()
+        .uri("/validators/deed/ping")
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["code"], "unknown_validator");
+}
+
+#[actix_web::test]
+async fn json_rpc_errors_carry_codes() {
+    let app = common::init_app(state(
+        Settings::builder()
+            .jsonrpc_errors(true)
+            .max_retries(0)
+            .build()
+            .unwrap(),
+    ))
+    .await;
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 7, "method": "getSlot"}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["error"]["code"], -32010);
+    assert_eq!(body["error"]["data"]["code"], "upstream_connect_failed");
+}
+
+#[actix_web::test]
+async fn the_index_lists_every_code() {
+    let app = common::init_app(state(Settings::default())).await;
+    let req = test::TestRequest::get().uri("/").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let listed = body["error_codes"].as_array().unwrap();
+    assert_eq!(listed.len(), ErrorCode::ALL.len());
+    assert!(listed.contains(&json!("upstream_timeout")));
+}
@@ -0,0 +1,268 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+use url::Url;
+
+use the_solana_api::error_window::{ErrorCategory, ErrorCounts, ErrorWindow, WINDOW_MINUTES};
+use the_solana_api::{AppState, Validator};
+
+const WINDOW: u64 = WINDOW_MINUTES as u64;
+
+#[actix_web::test]
+async fn rates_cover_the_last_minutes_only() {
+    let mut window = ErrorWindow::default();
+    window.record_request(100);
+    window.record_error(100, ErrorCategory::Connect);
+    window.record_request(101);
+    window.record_request(101);
+    window.record_error(101, ErrorCategory::ServerError);
+    window.record_error(101, ErrorCategory::BodyLimit);
+
+    let rates = window.rates(101);
+    assert_eq!(rates.window_secs, WINDOW * 60);
+    assert_eq!(rates.requests, 3);
+    assert_eq!(
+        rates.errors,
+        ErrorCounts {
+            connect: 1,
+            timeout: 0,
+            server_error: 1,
+            body_limit: 1,
+        }
+    );
+    assert_eq!(rates.error_rate, 1.0);
+
+    // Minute 100 is still in the window at its last minute, then drops out.
+    assert_eq!(window.rates(100 + WINDOW - 1).requests, 3);
+    let rates = window.rates(100 + WINDOW);
+    assert_eq!(rates.requests, 2);
+    assert_eq!(rates.errors.connect, 0);
+    assert_eq!(window.rates(101 + WINDOW).requests, 0);
+    assert_eq!(window.rates(101 + WINDOW).error_rate, 0.0);
+}
+
+#[actix_web::test]
+async fn reused_buckets_start_from_zero() {
+    let mut window = ErrorWindow::default();
+    for _ in 0..4 {
+        window.record_request(7);
+        window.record_error(7, ErrorCategory::Timeout);
+    }
+
+    // Same slot in the ring, a full window later.
+    window.record_request(7 + WINDOW);
+    let rates = window.rates(7 + WINDOW);
+    assert_eq!(rates.requests, 1);
+    assert_eq!(rates.errors.timeout, 0);
+    assert_eq!(rates.error_rate, 0.0);
+}
+
+#[actix_web::test]
+async fn reset_clears_every_bucket() {
+    let mut window = ErrorWindow::default();
+    window.record_request(3);
+    window.record_error(3, ErrorCategory::Connect);
+    window.reset();
+    assert_eq!(window.rates(3).requests, 0);
+    assert_eq!(window.rates(3).errors, ErrorCounts::default());
+}
+
+#[actix_web::test]
+async fn byte_rates_average_over_the_window_without_overflowing() {
+    let mut window = ErrorWindow::default();
+    window.record_bytes(10, 600, 0);
+    window.record_bytes(11, 0, 1_200);
+    let rates = window.byte_rates(11);
+    assert_eq!(rates.window_secs, WINDOW * 60);
+    assert_eq!(rates.sent, 600.0 / (WINDOW * 60) as f64);
+    assert_eq!(rates.received, 1_200.0 / (WINDOW * 60) as f64);
+    assert_eq!(window.byte_rates(11 + WINDOW).received, 0.0);
+
+    window.record_bytes(12, u64::MAX, 0);
+    window.record_bytes(12, 1, 0);
+    assert_eq!(
+        window.byte_rates(12).sent,
+        u64::MAX as f64 / (WINDOW * 60) as f64
+    );
+}
+
+fn node(status: StatusCode) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        cfg.route(
+            "/",
+            web::post().to(move || async move {
+                HttpResponse::build(status).json(json!({"jsonrpc": "2.0", "id": 1, "result": 1}))
+            }),
+        );
+    })
+}
+
+fn down(name: &str) -> Validator {
+    Validator::new(
+        name.into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:1/").unwrap(),
+    )
+}
+
+async fn names<S>(app: &S, uri: &str) -> Vec<String>
+where
+    S: actix_web::dev::Service<
+            actix_http::Request,
+            Response = actix_web::dev::ServiceResponse,
+            Error = actix_web::Error,
+        >,
+{
+    let body: Value =
+        test::call_and_read_body_json(app, test::TestRequest::get().uri(uri).to_request()).await;
+    body["validators"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[actix_web::test]
+async fn validators_sort_by_error_rate_and_show_details() {
+    let healthy = node(StatusCode::OK);
+    let broken = node(StatusCode::INTERNAL_SERVER_ERROR);
+    let app = common::init_app(AppState::new(common::registry(vec![
+        healthy.validator("a-healthy", "frankfurt"),
+        broken.validator("b-broken", "frankfurt"),
+        down("c-down"),
+    ])))
+    .await;
+
+    for server in ["a-healthy", "b-broken", "b-broken", "c-down"] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/?server={server}"))
+            .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+            .to_request();
+        test::call_service(&app, req).await;
+    }
+
+    // c-down and b-broken both fail every call; the name breaks the tie.
+    assert_eq!(
+        names(&app, "/validators?sort=error_rate").await,
+        ["b-broken", "c-down", "a-healthy"]
+    );
+    assert_eq!(
+        names(&app, "/validators?sort=name").await,
+        ["a-healthy", "b-broken", "c-down"]
+    );
+
+    let body: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri("/validators?detail=true")
+            .to_request(),
+    )
+    .await;
+    let broken = &body["validators"][1];
+    assert_eq!(broken["name"], "b-broken");
+    assert_eq!(broken["errors"]["requests"], 2);
+    assert_eq!(broken["errors"]["errors"]["server_error"], 2);
+    assert_eq!(broken["errors"]["error_rate"], 1.0);
+    assert!(broken["latency_p50_ms"].is_number());
+    let down = &body["validators"][2];
+    assert_eq!(down["errors"]["errors"]["connect"], 1);
+    assert_eq!(down["latency_p50_ms"], Value::Null);
+
+    // Without `detail` the listing stays name and location only.
+    let body: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get().uri("/validators").to_request(),
+    )
+    .await;
+    assert_eq!(
+        body["validators"][0],
+        json!({"name": "a-healthy", "location": "frankfurt"})
+    );
+}
+
+#[actix_web::test]
+async fn rejects_unknown_sort_values() {
+    let app = common::init_app(AppState::new(common::registry(vec![down("c-down")]))).await;
+
+    let req = test::TestRequest::get()
+        .uri("/validators?sort=uptime")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(
+        body["error"],
+        "unsupported sort 'uptime', expected one of error_rate, latency, name"
+    );
+}
+
+#[actix_web::test]
+async fn last_minute_slides_over_the_previous_bucket() {
+    let mut window = ErrorWindow::default();
+    for _ in 0..60 {
+        window.record_request(10);
+    }
+    for _ in 0..6 {
+        window.record_request(11);
+    }
+
+    // At the start of minute 11 the whole previous minute still counts,
+    // halfway through only half of it.
+    assert_eq!(window.requests_last_minute(11 * 60), 66);
+    assert_eq!(window.requests_last_minute(11 * 60 + 30), 36);
+    assert_eq!(window.requests_last_minute(11 * 60 + 59), 7);
+    assert_eq!(window.requests_last_minute(12 * 60 + 30), 3);
+    assert_eq!(window.requests_last_minute(14 * 60), 0);
+}
+
+#[actix_web::test]
+async fn validators_include_live_counters_on_request() {
+    let healthy = node(StatusCode::OK);
+    let app = common::init_app(AppState::new(common::registry(vec![
+        healthy.validator("a-healthy", "frankfurt"),
+    ])))
+    .await;
+
+    // A fresh server reports zeros rather than leaving the fields out.
+    let body: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri("/validators?stats=true")
+            .to_request(),
+    )
+    .await;
+    assert_eq!(
+        body,
+        json!({"validators": [{
+            "name": "a-healthy",
+            "location": "frankfurt",
+            "requests_total": 0,
+            "requests_last_minute": 0,
+            "in_flight": 0
+        }]})
+    );
+
+    for _ in 0..2 {
+        let req = test::TestRequest::post()
+            .uri("/")
+            .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+            .to_request();
+        test::call_service(&app, req).await;
+    }
+
+    let body: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri("/validators?stats=true&detail=true")
+            .to_request(),
+    )
+    .await;
+    let entry = &body["validators"][0];
+    assert_eq!(entry["requests_total"], 2);
+    assert!(entry["requests_last_minute"].as_u64().unwrap() >= 1);
+    assert_eq!(entry["in_flight"], 0);
+    assert_eq!(entry["errors"]["requests"], 2);
+}
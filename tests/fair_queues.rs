@@ -0,0 +1,211 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::json;
+
+use the_solana_api::concurrency::ConcurrencyLimit;
+use the_solana_api::{AppState, Settings};
+
+fn upstream(delay: Duration) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        cfg.route(
+            "/",
+            web::post().to(move || async move {
+                tokio::time::sleep(delay).await;
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 7}))
+            }),
+        );
+    })
+}
+
+fn get_slot(location: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri(&format!("/?location={location}"))
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+        .to_request()
+}
+
+async fn call<S>(app: &S, req: actix_http::Request) -> (StatusCode, Instant)
+where
+    S: actix_web::dev::Service<
+            actix_http::Request,
+            Response = actix_web::dev::ServiceResponse,
+            Error = actix_web::Error,
+        >,
+{
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    test::read_body(resp).await;
+    (status, Instant::now())
+}
+
+// Two slots, queues of two, and six requests for the slow location
+// followed shortly by one for the fast location.
+async fn saturate_frankfurt(fair: bool) -> (Vec<StatusCode>, StatusCode, Duration, AppState) {
+    let (hot, cold) = (
+        upstream(Duration::from_millis(300)),
+        upstream(Duration::ZERO),
+    );
+    let state = AppState::from_settings(
+        common::registry(vec![
+            hot.validator("hot", "frankfurt"),
+            cold.validator("cold", "tokyo"),
+        ]),
+        Settings::builder()
+            .max_concurrent_requests(2)
+            .concurrency_queue_timeout(Duration::from_secs(5))
+            .concurrency_queue_depth(2)
+            .concurrency_fair_queues(fair)
+            .metrics_enabled(true)
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state.clone()).await;
+
+    let started = Instant::now();
+    let hot_calls =
+        futures_util::future::join_all((0..6).map(|_| call(&app, get_slot("frankfurt"))));
+    let cold_call = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        call(&app, get_slot("tokyo")).await
+    };
+    let (hot_results, (cold_status, cold_done)) = tokio::join!(hot_calls, cold_call);
+    let hot_statuses = hot_results.into_iter().map(|(status, _)| status).collect();
+    (hot_statuses, cold_status, cold_done - started, state)
+}
+
+#[actix_web::test]
+async fn a_saturated_location_does_not_block_another() {
+    let (hot, cold, cold_elapsed, state) = saturate_frankfurt(true).await;
+    let served = hot
+        .iter()
+        .filter(|status| **status == StatusCode::OK)
+        .count();
+    assert_eq!(served, 4);
+    assert_eq!(
+        hot.iter()
+            .filter(|status| **status == StatusCode::SERVICE_UNAVAILABLE)
+            .count(),
+        2
+    );
+    assert_eq!(cold, StatusCode::OK);
+    // Served with the first freed slots, not behind the queued Frankfurt
+    // requests.
+    assert!(
+        cold_elapsed < Duration::from_millis(550),
+        "{cold_elapsed:?}"
+    );
+
+    let queues = state.concurrency_limit().unwrap().queues();
+    let locations: Vec<_> = queues
+        .iter()
+        .map(|(location, _)| location.as_str())
+        .collect();
+    assert_eq!(locations, ["frankfurt", "tokyo"]);
+    let (_, frankfurt) = queues[0];
+    assert_eq!(
+        (frankfurt.depth, frankfurt.served, frankfurt.shed),
+        (0, 2, 2)
+    );
+
+    let metrics = test::call_service(
+        &common::init_app(state).await,
+        test::TestRequest::get().uri("/metrics").to_request(),
+    )
+    .await;
+    let body = String::from_utf8(test::read_body(metrics).await.to_vec()).unwrap();
+    assert!(
+        body.contains("solana_api_queue_depth{location=\"tokyo\"} 0\n"),
+        "{body}"
+    );
+    assert!(body.contains("solana_api_queue_wait_seconds_count{location=\"frankfurt\"} 2\n"));
+    assert!(body.contains("solana_api_queue_shed_total{location=\"frankfurt\"} 2\n"));
+}
+
+#[actix_web::test]
+async fn a_single_queue_lets_one_location_crowd_out_the_rest() {
+    let (_, cold, _, state) = saturate_frankfurt(false).await;
+    assert_eq!(cold, StatusCode::SERVICE_UNAVAILABLE);
+    let queues = state.concurrency_limit().unwrap().queues();
+    let locations: Vec<_> = queues
+        .iter()
+        .map(|(location, _)| location.as_str())
+        .collect();
+    assert_eq!(locations, ["any"]);
+}
+
+async fn wait_until(condition: impl Fn() -> bool) {
+    let started = Instant::now();
+    while !condition() {
+        assert!(started.elapsed() < Duration::from_secs(5), "timed out");
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+#[actix_web::test]
+async fn slots_go_round_robin_across_locations() {
+    let limit = Arc::new(ConcurrencyLimit::new(1, Duration::from_secs(5), 10, true));
+    let held = limit.acquire("a").await.unwrap();
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    let mut tasks = Vec::new();
+    for (queued, location) in ["a", "a", "a", "b", "b", "c"].into_iter().enumerate() {
+        let (task_limit, order) = (limit.clone(), order.clone());
+        tasks.push(tokio::spawn(async move {
+            let permit = task_limit.acquire(location).await.unwrap();
+            order.lock().unwrap().push(location);
+            drop(permit);
+        }));
+        wait_until(|| limit.queued() == queued as u64 + 1).await;
+    }
+    // A waiter that goes away leaves its queue.
+    tasks.pop().unwrap().abort();
+    wait_until(|| limit.queued() == 5).await;
+
+    drop(held);
+    for task in tasks {
+        task.await.unwrap();
+    }
+    assert_eq!(*order.lock().unwrap(), ["a", "b", "a", "b", "a"]);
+    assert_eq!((limit.in_flight(), limit.queued()), (0, 0));
+}
+
+#[actix_web::test]
+async fn unknown_locations_share_one_queue() {
+    let node = upstream(Duration::from_millis(200));
+    let state = AppState::from_settings(
+        common::registry(vec![node.validator("node", "lab")]),
+        Settings::builder()
+            .max_concurrent_requests(1)
+            .concurrency_queue_timeout(Duration::from_secs(5))
+            .concurrency_fair_queues(true)
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state.clone()).await;
+
+    let requests = [
+        "/",
+        "/?location=lab",
+        "/?location=nowhere",
+        "/?server=node",
+        "/?region=madeup",
+    ]
+    .map(|uri| {
+        test::TestRequest::post()
+            .uri(uri)
+            .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+            .to_request()
+    });
+    futures_util::future::join_all(requests.map(|req| call(&app, req))).await;
+    let queues = state.concurrency_limit().unwrap().queues();
+    let locations: Vec<_> = queues
+        .iter()
+        .map(|(location, _)| location.as_str())
+        .collect();
+    assert_eq!(locations, ["any", "lab"]);
+}
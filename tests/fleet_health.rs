@@ -0,0 +1,159 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use actix_web::{HttpResponse, http::StatusCode, test, web};
+use serde_json::{Value, json};
+use url::Url;
+
+use the_solana_api::config::Settings;
+use the_solana_api::fleet::{self, FanOutError, Throttle};
+use the_solana_api::{AppState, Validator};
+
+fn node(health: Value, slot: u64, delay: Duration) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let health = health.clone();
+        cfg.route(
+            "/",
+            web::post().to(move || {
+                let health = health.clone();
+                async move {
+                    tokio::time::sleep(delay).await;
+                    let mut first = json!({"jsonrpc": "2.0", "id": 1});
+                    first
+                        .as_object_mut()
+                        .unwrap()
+                        .extend(health.as_object().unwrap().clone());
+                    HttpResponse::Ok().json(json!([
+                        first,
+                        {"jsonrpc": "2.0", "id": 2, "result": slot},
+                    ]))
+                }
+            }),
+        );
+    })
+}
+
+fn unreachable(name: &str) -> Validator {
+    Validator::new(
+        name.into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:1/").unwrap(),
+    )
+}
+
+#[actix_web::test]
+async fn reports_every_node_without_failing_on_partial_timeouts() {
+    let healthy = node(json!({"result": "ok"}), 1_000, Duration::ZERO);
+    let behind = node(
+        json!({"error": {"code": -32005, "message": "Node is behind by 42 slots", "data": {"numSlotsBehind": 42}}}),
+        958,
+        Duration::ZERO,
+    );
+    let slow = node(json!({"result": "ok"}), 1_000, Duration::from_secs(3));
+    let settings = Settings::builder()
+        .fleet_node_timeout(Duration::from_millis(300))
+        .build()
+        .unwrap();
+    let state = AppState::from_settings(
+        common::registry(vec![
+            healthy.validator("healthy", "lab"),
+            behind.validator("behind", "lab"),
+            slow.validator("slow", "lab"),
+            unreachable("down"),
+        ]),
+        settings,
+    );
+    let app = common::init_app(state.clone()).await;
+
+    let req = test::TestRequest::get().uri("/fleet/health").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(
+        body["summary"],
+        json!({"total": 4, "ok": 1, "behind": 1, "unhealthy": 0, "timeout": 1, "unreachable": 1})
+    );
+    let nodes = body["validators"].as_array().unwrap();
+    let statuses: Vec<&str> = nodes
+        .iter()
+        .map(|node| node["status"].as_str().unwrap())
+        .collect();
+    assert_eq!(statuses, ["ok", "behind", "timeout", "unreachable"]);
+    assert_eq!(nodes[0]["slot"], 1_000);
+    assert_eq!(nodes[1]["slots_behind"], 42);
+    assert!(nodes[2]["slot"].is_null());
+    assert!(nodes[3]["error"].is_string());
+
+    // Slots learned here feed commitment-aware routing.
+    assert_eq!(state.stats().validator("behind").latest_slot(), Some(958));
+}
+
+#[actix_web::test]
+async fn live_checks_are_throttled() {
+    let healthy = node(json!({"result": "ok"}), 1, Duration::ZERO);
+    let state = AppState::new(common::registry(vec![healthy.validator("healthy", "lab")]));
+    let app = common::init_app(state).await;
+
+    let first = test::call_service(
+        &app,
+        test::TestRequest::get().uri("/fleet/health").to_request(),
+    )
+    .await;
+    assert_eq!(first.status(), StatusCode::OK);
+
+    let second = test::call_service(
+        &app,
+        test::TestRequest::get().uri("/fleet/health").to_request(),
+    )
+    .await;
+    assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(second.headers().contains_key("retry-after"));
+}
+
+#[actix_web::test]
+async fn throttle_allows_one_call_per_interval() {
+    let throttle = Throttle::new(Duration::from_millis(50));
+    assert!(throttle.try_acquire().is_ok());
+    assert!(throttle.try_acquire().is_err());
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert!(throttle.try_acquire().is_ok());
+
+    let disabled = Throttle::new(Duration::ZERO);
+    assert!(disabled.try_acquire().is_ok());
+    assert!(disabled.try_acquire().is_ok());
+}
+
+#[actix_web::test]
+async fn fan_out_bounds_concurrency_and_keeps_order() {
+    let validators: Vec<Validator> = (0..6).map(|i| unreachable(&format!("v{i}"))).collect();
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+
+    let outcomes = fleet::fan_out(&validators, 2, Duration::from_secs(1), |validator| {
+        let in_flight = in_flight.clone();
+        let peak = peak.clone();
+        async move {
+            let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            if validator.name() == "v3" {
+                Err("boom".to_string())
+            } else {
+                Ok(validator.name().to_string())
+            }
+        }
+    })
+    .await;
+
+    assert_eq!(peak.load(Ordering::SeqCst), 2);
+    let names: Vec<&str> = outcomes.iter().map(|o| o.validator.name()).collect();
+    assert_eq!(names, ["v0", "v1", "v2", "v3", "v4", "v5"]);
+    assert_eq!(outcomes[0].result, Ok("v0".to_string()));
+    assert_eq!(
+        outcomes[3].result,
+        Err(FanOutError::Failed("boom".to_string()))
+    );
+}
@@ -0,0 +1,171 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::guardrail::violation;
+use the_solana_api::{AppState, Settings};
+
+#[actix_web::test]
+async fn program_accounts_need_filters_or_a_data_slice() {
+    let gpa = |params: Value| violation("getProgramAccounts", Some(&params));
+
+    assert!(gpa(json!(["Prog"])).is_some());
+    assert!(gpa(json!(["Prog", {"encoding": "base64"}])).is_some());
+    assert!(gpa(json!(["Prog", {"filters": []}])).is_some());
+    assert!(gpa(json!(["Prog", {"filters": [{"dataSize": 165}]}])).is_none());
+    assert!(gpa(json!(["Prog", {"dataSlice": {"offset": 0, "length": 0}}])).is_none());
+    assert!(violation("getProgramAccounts", None).is_some());
+
+    // By-name encodings, with the config nested or flattened.
+    assert!(gpa(json!({"programId": "Prog"})).is_some());
+    assert!(gpa(json!({"programId": "Prog", "config": {"filters": [{"dataSize": 1}]}})).is_none());
+    assert!(gpa(json!({"programId": "Prog", "dataSlice": {"offset": 0, "length": 8}})).is_none());
+}
+
+#[actix_web::test]
+async fn token_accounts_are_only_guarded_in_json_parsed_mode() {
+    let by_owner = |params: Value| violation("getTokenAccountsByOwner", Some(&params));
+    let filter = json!({"programId": "Token"});
+
+    assert!(by_owner(json!(["Owner", filter, {"encoding": "jsonParsed"}])).is_some());
+    assert!(by_owner(json!(["Owner", filter, {"encoding": "base64"}])).is_none());
+    assert!(by_owner(json!(["Owner", filter])).is_none());
+    assert!(
+        by_owner(json!(["Owner", filter, {"encoding": "jsonParsed", "dataSlice": {"offset": 0, "length": 8}}]))
+            .is_none()
+    );
+    assert!(
+        by_owner(json!({"owner": "Owner", "filter": filter, "config": {"encoding": "jsonParsed"}}))
+            .is_some()
+    );
+    assert!(violation("getBalance", None).is_none());
+}
+
+fn counting_upstream(hits: Arc<AtomicUsize>) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let hits = hits.clone();
+        cfg.route(
+            "/",
+            web::post().to(move || {
+                hits.fetch_add(1, Ordering::SeqCst);
+                async { HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": []})) }
+            }),
+        );
+    })
+}
+
+fn guarded_state(upstream: &common::MockUpstream) -> AppState {
+    let settings = Settings::builder()
+        .guardrail_enabled(true)
+        .guardrail_allowlist(vec!["10.0.0.7".into()])
+        .guardrail_bypass_header(HeaderName::from_static("x-allow-heavy"))
+        .guardrail_bypass_value(HeaderValue::from_static("s3cret"))
+        .build()
+        .unwrap();
+    AppState::from_settings(
+        common::registry(vec![upstream.validator("primary", "lab")]),
+        settings,
+    )
+}
+
+fn unfiltered(id: u64) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "method": "getProgramAccounts", "params": ["Prog"]})
+}
+
+#[actix_web::test]
+async fn rejects_a_batch_with_a_single_offending_entry() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = counting_upstream(hits.clone());
+    let app = common::init_app(guarded_state(&upstream)).await;
+
+    let batch = json!([
+        {"jsonrpc": "2.0", "id": 1, "method": "getSlot"},
+        unfiltered(2),
+        {"jsonrpc": "2.0", "id": 3, "method": "getProgramAccounts", "params": ["Prog", {"filters": [{"dataSize": 165}]}]},
+    ]);
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_json(batch)
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    let responses = body.as_array().unwrap();
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[1]["id"], 2);
+    assert_eq!(responses[1]["error"]["code"], -32602);
+    assert!(
+        responses[1]["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("filters")
+    );
+    assert_eq!(responses[0]["id"], 1);
+    assert!(
+        responses[0]["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("entry 1")
+    );
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
+
+#[actix_web::test]
+async fn allowlisted_clients_and_bypass_header_go_through() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = counting_upstream(hits.clone());
+    let app = common::init_app(guarded_state(&upstream)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_json(unfiltered(1))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["error"]["code"], -32602);
+    assert_eq!(body["id"], 1);
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .insert_header(("x-allow-heavy", "wrong"))
+        .set_json(unfiltered(2))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["error"]["code"], -32602);
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .insert_header(("x-allow-heavy", "s3cret"))
+        .set_json(unfiltered(3))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body.get("error").is_none());
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .peer_addr("10.0.0.7:4000".parse().unwrap())
+        .set_json(unfiltered(4))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body.get("error").is_none());
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[actix_web::test]
+async fn guardrail_is_off_by_default() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = counting_upstream(hits.clone());
+    let state = AppState::new(common::registry(vec![upstream.validator("primary", "lab")]));
+    let app = common::init_app(state).await;
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_json(unfiltered(1))
+        .to_request();
+    test::call_service(&app, req).await;
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::test;
+use serde_json::Value;
+
+use the_solana_api::{AppState, Validator};
+
+fn validator(name: &str, port: u16) -> Validator {
+    Validator::new(
+        name.into(),
+        "lab".into(),
+        format!("http://127.0.0.1:{port}/").parse().unwrap(),
+    )
+}
+
+#[actix_web::test]
+async fn health_reports_uptime_and_registry_state() {
+    let state = AppState::new(common::registry(vec![
+        validator("first", 1),
+        validator("second", 2),
+    ]));
+    state
+        .stats()
+        .validator("second")
+        .cool_down(Duration::from_secs(60));
+    let loaded_at = state.registry().loaded_at_unix_ms();
+    let app = common::init_app(state).await;
+
+    let body: Value =
+        test::call_and_read_body_json(&app, test::TestRequest::get().uri("/health").to_request())
+            .await;
+
+    let mut fields: Vec<&str> = body
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    fields.sort();
+    assert_eq!(
+        fields,
+        [
+            "registry_loaded_at_unix_ms",
+            "status",
+            "uptime_secs",
+            "validators",
+            "validators_available",
+        ]
+    );
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["uptime_secs"], 0);
+    assert_eq!(body["registry_loaded_at_unix_ms"], loaded_at);
+    assert_eq!(body["validators"], 2);
+    assert_eq!(body["validators_available"], 1);
+}
+
+#[actix_web::test]
+async fn uptime_grows_with_the_process() {
+    let state = AppState::new(common::registry(vec![validator("first", 1)]));
+    let before = state.uptime();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(state.uptime() >= before + Duration::from_millis(20));
+}
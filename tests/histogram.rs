@@ -0,0 +1,130 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::{HttpResponse, test, web};
+use serde_json::json;
+
+use the_solana_api::config::Settings;
+use the_solana_api::histogram::LatencyHistogram;
+use the_solana_api::stats::StatsStore;
+use the_solana_api::{AppState, metrics};
+
+fn assert_close(actual: Option<f64>, expected: f64, tolerance: f64) {
+    let actual = actual.expect("quantile");
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "expected {expected} ± {tolerance}, got {actual}"
+    );
+}
+
+#[actix_web::test]
+async fn quantiles_are_estimated_within_a_bucket() {
+    let bounds: Vec<f64> = (1..=100).map(|step| step as f64 * 10.0).collect();
+    let histogram = LatencyHistogram::new(&bounds);
+    for ms in 1..=1000 {
+        histogram.record(Duration::from_millis(ms));
+    }
+
+    let snapshot = histogram.snapshot();
+    assert_eq!(snapshot.count(), 1000);
+    assert_close(snapshot.quantile(0.5), 500.0, 10.0);
+    assert_close(snapshot.quantile(0.9), 900.0, 10.0);
+    assert_close(snapshot.quantile(0.99), 990.0, 10.0);
+    assert_eq!(snapshot.sum_ms, 500_500.0);
+}
+
+#[actix_web::test]
+async fn default_buckets_bound_the_tail_error() {
+    let histogram = LatencyHistogram::default();
+    // 90 fast answers and a slow tail.
+    for _ in 0..90 {
+        histogram.record(Duration::from_millis(8));
+    }
+    for _ in 0..10 {
+        histogram.record(Duration::from_millis(800));
+    }
+
+    let summary = histogram.snapshot().summary();
+    assert_eq!(summary.count, 100);
+    assert_close(summary.p50, 8.0, 5.0);
+    assert_close(summary.p99, 800.0, 200.0);
+}
+
+#[actix_web::test]
+async fn latencies_above_every_bound_report_the_last_bound() {
+    let histogram = LatencyHistogram::new(&[10.0, 100.0]);
+    histogram.record(Duration::from_secs(3));
+    assert_eq!(histogram.snapshot().quantile(0.99), Some(100.0));
+    assert_eq!(LatencyHistogram::default().snapshot().quantile(0.5), None);
+}
+
+#[actix_web::test]
+async fn store_applies_configured_buckets() {
+    let stats = StatsStore::new().with_latency_buckets(&[1.0, 2.0]);
+    let snapshot = stats.validator("a").latency_histogram();
+    assert_eq!(snapshot.bounds_ms, [1.0, 2.0]);
+    assert_eq!(snapshot.counts, [0, 0, 0]);
+}
+
+#[actix_web::test]
+async fn metrics_export_prometheus_histograms_when_enabled() {
+    let node = common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 1}))
+            }),
+        );
+    });
+    let state = AppState::from_settings(
+        common::registry(vec![node.validator("primary", "frankfurt")]),
+        Settings::builder()
+            .metrics_enabled(true)
+            .latency_buckets_ms(vec![250.0, 5000.0])
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let resp =
+        test::call_service(&app, test::TestRequest::get().uri("/metrics").to_request()).await;
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        metrics::CONTENT_TYPE
+    );
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert!(lines.contains(&"# TYPE solana_api_upstream_latency_seconds histogram"));
+    assert!(lines.contains(
+        &"solana_api_upstream_latency_seconds_bucket{validator=\"primary\",le=\"0.25\"} 1"
+    ));
+    assert!(
+        lines.contains(
+            &"solana_api_upstream_latency_seconds_bucket{validator=\"primary\",le=\"5\"} 1"
+        )
+    );
+    assert!(lines.contains(
+        &"solana_api_upstream_latency_seconds_bucket{validator=\"primary\",le=\"+Inf\"} 1"
+    ));
+    assert!(lines.contains(&"solana_api_upstream_latency_seconds_count{validator=\"primary\"} 1"));
+}
+
+#[actix_web::test]
+async fn metrics_are_hidden_by_default() {
+    let node = common::spawn_upstream(|_| {});
+    let app = common::init_app(AppState::new(common::registry(vec![
+        node.validator("primary", "frankfurt"),
+    ])))
+    .await;
+
+    let resp =
+        test::call_service(&app, test::TestRequest::get().uri("/metrics").to_request()).await;
+    assert_eq!(resp.status(), 404);
+}
@@ -0,0 +1,208 @@
+mod common;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::auth::{ApiKey, ApiKeys};
+use the_solana_api::signature;
+use the_solana_api::{AppState, Settings};
+
+const ALICE: &str = "alice-secret";
+const ALICE_SHA256: &str = "0c848abb03307b06cf70cd4e29c157dc81af5e94ab3eb1d0c59a120269572376";
+const HMAC_SECRET: &str = "server-side-signing-secret";
+const BODY: &str = r#"{"jsonrpc":"2.0","id":1,"method":"getSlot"}"#;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+// The upstream answers with the method it was asked for, to show the body
+// reached it intact.
+fn setup(revoked: bool) -> (common::MockUpstream, AppState) {
+    setup_with(revoked, Some(HMAC_SECRET))
+}
+
+fn setup_with(revoked: bool, hmac_secret: Option<&str>) -> (common::MockUpstream, AppState) {
+    let upstream = common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|body: web::Json<Value>| async move {
+                HttpResponse::Ok()
+                    .json(json!({"jsonrpc": "2.0", "id": 1, "result": body["method"]}))
+            }),
+        );
+    });
+    let mut settings = Settings::builder().api_keys(ApiKeys::new(vec![
+        ApiKey::new("alice", ALICE_SHA256, revoked).unwrap(),
+    ]));
+    if let Some(secret) = hmac_secret {
+        settings = settings.hmac_secret(secret.to_string());
+    }
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("node", "lab")]),
+        settings.build().unwrap(),
+    );
+    (upstream, state)
+}
+
+fn signed(target: &str, signed_body: &str, sent_body: &str, timestamp: u64) -> test::TestRequest {
+    let secret = signature::signing_secret(HMAC_SECRET, ALICE);
+    signed_with(&secret, target, signed_body, sent_body, timestamp)
+}
+
+fn signed_with(
+    secret: &str,
+    target: &str,
+    signed_body: &str,
+    sent_body: &str,
+    timestamp: u64,
+) -> test::TestRequest {
+    let timestamp = timestamp.to_string();
+    let canonical = signature::canonical_string(&timestamp, "POST", target, signed_body.as_bytes());
+    test::TestRequest::post()
+        .uri(target)
+        .insert_header(("content-type", "application/json"))
+        .insert_header(("x-api-key-id", "alice"))
+        .insert_header(("x-timestamp", timestamp))
+        .insert_header(("x-signature", signature::sign(secret, &canonical)))
+        .set_payload(sent_body.to_string())
+}
+
+async fn rejection<S>(app: &S, req: test::TestRequest) -> String
+where
+    S: actix_web::dev::Service<
+            actix_http::Request,
+            Response = actix_web::dev::ServiceResponse,
+            Error = actix_web::Error,
+        >,
+{
+    let resp = test::call_service(app, req.to_request()).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = test::read_body_json(resp).await;
+    body["error"].as_str().unwrap().to_string()
+}
+
+#[actix_web::test]
+async fn signatures_match_the_documented_vectors() {
+    let secret = signature::signing_secret(HMAC_SECRET, ALICE);
+    assert_eq!(
+        secret,
+        "8940c9b864a511f4fb180ce18bc5c2d024b6c9d490af6062350abae118b3ae4b"
+    );
+    let canonical =
+        signature::canonical_string("1700000000", "POST", "/?server=node", BODY.as_bytes());
+    assert_eq!(
+        canonical,
+        "1700000000\nPOST\n/?server=node\n\
+         c2be0696b51f20ba4125714f6fe9688fa7f9134dc93d3b5ef8be501c59994dac"
+    );
+    assert_eq!(
+        signature::sign(&secret, &canonical),
+        "e2743e5aac149bff75582370bab02dd1d9beea2968a4225e5348e4aeeea3a982"
+    );
+
+    let canonical = signature::canonical_string("1700000000", "GET", "/slot", b"");
+    assert_eq!(
+        canonical,
+        "1700000000\nGET\n/slot\n\
+         e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+        signature::sign(&secret, &canonical),
+        "931df7073d0ba05637776e0e86c0895fad9bbf0edb778ededc1af63942e0f1ab"
+    );
+}
+
+#[actix_web::test]
+async fn signed_requests_are_proxied_with_their_body() {
+    let (_upstream, state) = setup(false);
+    let app = common::init_app(state).await;
+
+    let resp = test::call_service(
+        &app,
+        signed("/?server=node", BODY, BODY, now()).to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["result"], "getSlot");
+}
+
+#[actix_web::test]
+async fn tampered_requests_are_rejected() {
+    let (_upstream, state) = setup(false);
+    let app = common::init_app(state).await;
+    let tampered = BODY.replace("getSlot", "requestAirdrop");
+
+    for req in [
+        // The body changed after signing.
+        signed("/?server=node", BODY, &tampered, now()),
+        // So did the query.
+        signed("/?server=node", BODY, BODY, now()).uri("/?server=other"),
+        // A signature over a different timestamp.
+        signed("/", BODY, BODY, now()).insert_header(("x-timestamp", (now() + 1).to_string())),
+        // Another key id.
+        signed("/", BODY, BODY, now()).insert_header(("x-api-key-id", "mallory")),
+        signed("/", BODY, BODY, now()).insert_header(("x-signature", "zz")),
+    ] {
+        assert_eq!(rejection(&app, req).await, "invalid request signature");
+    }
+}
+
+#[actix_web::test]
+async fn stale_and_incomplete_requests_are_rejected() {
+    let (_upstream, state) = setup(false);
+    let app = common::init_app(state).await;
+
+    for timestamp in [now() - 301, now() + 301] {
+        assert_eq!(
+            rejection(&app, signed("/", BODY, BODY, timestamp)).await,
+            "request timestamp is outside the allowed window"
+        );
+    }
+    // Within the default five minutes either way.
+    let resp = test::call_service(&app, signed("/", BODY, BODY, now() - 290).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .insert_header(("x-signature", "00"))
+        .set_payload(BODY);
+    assert_eq!(
+        rejection(&app, req).await,
+        "signed requests need X-Api-Key-Id, X-Timestamp and X-Signature"
+    );
+}
+
+#[actix_web::test]
+async fn revoked_keys_cannot_sign() {
+    let (_upstream, state) = setup(true);
+    let app = common::init_app(state).await;
+    assert_eq!(
+        rejection(&app, signed("/", BODY, BODY, now())).await,
+        "API key has been revoked"
+    );
+}
+
+#[actix_web::test]
+async fn the_stored_hash_cannot_sign() {
+    let (_upstream, state) = setup(false);
+    let app = common::init_app(state).await;
+    let req = signed_with(ALICE_SHA256, "/", BODY, BODY, now());
+    assert_eq!(rejection(&app, req).await, "invalid request signature");
+    let req = signed_with(ALICE, "/", BODY, BODY, now());
+    assert_eq!(rejection(&app, req).await, "invalid request signature");
+
+    let (_upstream, state) = setup_with(false, None);
+    let app = common::init_app(state).await;
+    assert_eq!(
+        rejection(&app, signed("/", BODY, BODY, now())).await,
+        "signed requests need HMAC_SECRET to be configured"
+    );
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse, test, web};
+use serde_json::json;
+
+use the_solana_api::{AppState, Settings, Validator, ValidatorRegistry};
+
+fn echo_host_upstream() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|req: HttpRequest| async move {
+                let host = req
+                    .headers()
+                    .get(header::HOST)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string();
+                HttpResponse::Ok().body(host)
+            }),
+        );
+    })
+}
+
+async fn upstream_host(
+    settings: Settings,
+    validator: Validator,
+    inbound_host: Option<&str>,
+) -> String {
+    let state = AppState::from_settings(common::registry(vec![validator]), settings);
+    let app = common::init_app(state).await;
+
+    let mut request = test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({ "jsonrpc": "2.0", "id": 1, "method": "getSlot" }));
+    if let Some(host) = inbound_host {
+        request = request.insert_header((header::HOST, host));
+    }
+
+    let body = test::call_and_read_body(&app, request.to_request()).await;
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[actix_web::test]
+async fn rewrites_host_by_default() {
+    let upstream = echo_host_upstream();
+    let host = upstream_host(
+        Settings::default(),
+        upstream.validator("gw-1", "lab"),
+        Some("rpc.client.example"),
+    )
+    .await;
+
+    assert_eq!(host, upstream.addr.to_string());
+}
+
+#[actix_web::test]
+async fn passes_inbound_host_through_when_disabled() {
+    let upstream = echo_host_upstream();
+    let settings = Settings::builder().rewrite_host(false).build().unwrap();
+    let host = upstream_host(
+        settings,
+        upstream.validator("gw-1", "lab"),
+        Some("rpc.client.example"),
+    )
+    .await;
+
+    assert_eq!(host, "rpc.client.example");
+}
+
+#[actix_web::test]
+async fn falls_back_to_url_host_without_inbound_host() {
+    let upstream = echo_host_upstream();
+    let settings = Settings::builder().rewrite_host(false).build().unwrap();
+    let host = upstream_host(settings, upstream.validator("gw-1", "lab"), None).await;
+
+    assert_eq!(host, upstream.addr.to_string());
+}
+
+#[actix_web::test]
+async fn validator_switch_overrides_deployment_default() {
+    let upstream = echo_host_upstream();
+    let host = upstream_host(
+        Settings::default(),
+        upstream.validator("gw-1", "lab").with_rewrite_host(false),
+        Some("rpc.client.example"),
+    )
+    .await;
+
+    assert_eq!(host, "rpc.client.example");
+}
+
+#[actix_web::test]
+async fn reads_rewrite_host_column() {
+    let csv = "name,ip,location,rewrite_host\n\
+               gw-1,10.0.0.1,lab,false\n\
+               node-1,10.0.0.2,lab,\n";
+    let registry = ValidatorRegistry::from_reader(csv.as_bytes()).unwrap();
+
+    assert_eq!(
+        registry.get_by_name("gw-1").unwrap().rewrite_host(),
+        Some(false)
+    );
+    assert_eq!(registry.get_by_name("node-1").unwrap().rewrite_host(), None);
+}
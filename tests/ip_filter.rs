@@ -0,0 +1,162 @@
+mod common;
+
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::Value;
+use url::Url;
+
+use the_solana_api::client_limit::IpRange;
+use the_solana_api::ip_filter::{IpFilter, Unlisted};
+use the_solana_api::{AppState, Settings, Validator};
+
+fn ip(raw: &str) -> IpAddr {
+    raw.parse().unwrap()
+}
+
+fn ranges(raw: &[&str]) -> Vec<IpRange> {
+    raw.iter().map(|range| range.parse().unwrap()).collect()
+}
+
+fn filter(allow: &[&str], deny: &[&str]) -> IpFilter {
+    IpFilter {
+        allow: ranges(allow),
+        deny: ranges(deny),
+        unlisted: Unlisted::Deny,
+    }
+}
+
+#[actix_web::test]
+async fn ranges_match_up_to_their_edges() {
+    let cases = [
+        (
+            "10.0.0.0/8",
+            "10.0.0.0",
+            "10.255.255.255",
+            "9.255.255.255",
+            "11.0.0.0",
+        ),
+        (
+            "192.168.1.128/25",
+            "192.168.1.128",
+            "192.168.1.255",
+            "192.168.1.127",
+            "192.168.2.0",
+        ),
+        (
+            "203.0.113.7/32",
+            "203.0.113.7",
+            "203.0.113.7",
+            "203.0.113.6",
+            "203.0.113.8",
+        ),
+        (
+            "2001:db8::/32",
+            "2001:db8::",
+            "2001:db8:ffff:ffff:ffff:ffff:ffff:ffff",
+            "2001:db7:ffff:ffff:ffff:ffff:ffff:ffff",
+            "2001:db9::",
+        ),
+        (
+            "fd00::/127",
+            "fd00::",
+            "fd00::1",
+            "fcff:ffff:ffff:ffff:ffff:ffff:ffff:ffff",
+            "fd00::2",
+        ),
+    ];
+    for (range, first, last, before, after) in cases {
+        let parsed: IpRange = range.parse().unwrap();
+        assert!(parsed.contains(ip(first)), "{range} {first}");
+        assert!(parsed.contains(ip(last)), "{range} {last}");
+        assert!(!parsed.contains(ip(before)), "{range} {before}");
+        assert!(!parsed.contains(ip(after)), "{range} {after}");
+    }
+
+    let everything: IpRange = "::/0".parse().unwrap();
+    assert!(everything.contains(ip("::")));
+    assert!(everything.contains(ip("ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff")));
+    // IPv4-mapped addresses match IPv4 ranges only.
+    assert!(!everything.contains(ip("::ffff:10.0.0.1")));
+}
+
+#[actix_web::test]
+async fn denylist_is_evaluated_before_allowlist() {
+    let filter = filter(&["10.0.0.0/8"], &["10.9.0.0/16"]);
+    assert!(filter.permits(Some(ip("10.1.2.3"))));
+    assert!(!filter.permits(Some(ip("10.9.0.1"))));
+    assert!(!filter.permits(Some(ip("192.0.2.1"))));
+    assert!(!filter.permits(None));
+
+    let unlisted_allowed = IpFilter {
+        unlisted: Unlisted::Allow,
+        ..filter.clone()
+    };
+    assert!(unlisted_allowed.permits(Some(ip("192.0.2.1"))));
+    assert!(!unlisted_allowed.permits(Some(ip("10.9.0.1"))));
+
+    let deny_only = self::filter(&[], &["2001:db8::/32"]);
+    assert!(!deny_only.permits(Some(ip("2001:db8::1"))));
+    assert!(deny_only.permits(Some(ip("2001:db9::1"))));
+    assert!(deny_only.permits(None));
+    assert!(!IpFilter::default().is_active());
+}
+
+#[actix_web::test]
+async fn blocked_addresses_get_a_403() {
+    let state = AppState::from_settings(
+        common::registry(vec![Validator::new(
+            "node".into(),
+            "lab".into(),
+            Url::parse("http://127.0.0.1:9/").unwrap(),
+        )]),
+        Settings::builder()
+            .ip_filter(filter(&["10.0.0.0/8"], &[]))
+            .trusted_proxies(ranges(&["127.0.0.1"]))
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+    let get = |peer: &str| {
+        test::TestRequest::get()
+            .uri("/health")
+            .peer_addr(SocketAddr::new(ip(peer), 4000))
+    };
+
+    let resp = test::call_service(&app, get("10.0.0.1").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    for _ in 0..3 {
+        let resp = test::call_service(
+            &app,
+            get("192.0.2.1")
+                .insert_header(("x-request-id", "blocked-1"))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "forbidden");
+        assert_eq!(body["error"], "client address is not allowed");
+        assert_eq!(body["request_id"], "blocked-1");
+    }
+
+    // Behind a trusted proxy the forwarded address is the one checked.
+    let resp = test::call_service(
+        &app,
+        get("127.0.0.1")
+            .insert_header(("x-forwarded-for", "10.4.4.4"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(
+        &app,
+        get("127.0.0.1")
+            .insert_header(("x-forwarded-for", "192.0.2.1"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,60 @@
+use the_solana_api::jsonrpc::{RpcMethod, extract_method};
+
+#[test]
+fn extracts_single_method() {
+    let method = extract_method(br#"{"jsonrpc":"2.0","id":1,"method":"getSlot"}"#);
+    assert_eq!(method, RpcMethod::Single("getSlot".into()));
+    assert_eq!(method.to_string(), "getSlot");
+    assert_eq!(method.name(), Some("getSlot"));
+}
+
+#[test]
+fn ignores_params_regardless_of_shape() {
+    let body = br#"{"params":[{"deeply":{"nested":[1,2,3]}},"x"],"method":"getBalance","id":"a"}"#;
+    assert_eq!(extract_method(body), RpcMethod::Single("getBalance".into()));
+}
+
+#[test]
+fn handles_escaped_method_names() {
+    let body = br#"{"method":"get\u0053lot"}"#;
+    assert_eq!(extract_method(body), RpcMethod::Single("getSlot".into()));
+}
+
+#[test]
+fn summarizes_batches() {
+    let body = br#" [{"method":"getSlot"},{"method":"getBalance","params":["x"]},42,{"id":3}]"#;
+    let method = extract_method(body);
+    assert_eq!(
+        method,
+        RpcMethod::Batch(vec![
+            Some("getSlot".into()),
+            Some("getBalance".into()),
+            None,
+            None,
+        ])
+    );
+    assert_eq!(method.to_string(), "batch[4]");
+    assert_eq!(method.name(), None);
+}
+
+#[test]
+fn malformed_bodies_are_invalid() {
+    for body in [
+        &b""[..],
+        b"not json",
+        br#"{"method":"getSlot""#,
+        br#"{"id":1}"#,
+        br#"{"method":7}"#,
+        br#""getSlot""#,
+        br#"[{"method":"getSlot"}"#,
+    ] {
+        let method = extract_method(body);
+        assert_eq!(
+            method,
+            RpcMethod::Invalid,
+            "{}",
+            String::from_utf8_lossy(body)
+        );
+        assert_eq!(method.to_string(), "invalid");
+    }
+}
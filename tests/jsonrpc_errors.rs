@@ -0,0 +1,209 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{Value, json};
+
+use the_solana_api::errors::{AppError, ErrorFormat, UpstreamErrorKind};
+use the_solana_api::validators::SelectionError;
+use the_solana_api::{AppState, Settings, Validator};
+
+fn unreachable_state(jsonrpc_errors: bool) -> AppState {
+    // Nothing listens on the discard port, so every attempt fails to connect.
+    let validator = Validator::new(
+        "dead".into(),
+        "lab".into(),
+        "http://127.0.0.1:9/".parse().unwrap(),
+    );
+    let settings = Settings {
+        jsonrpc_errors,
+        ..Settings::default()
+    };
+    AppState::from_settings(common::registry(vec![validator]), settings)
+}
+
+async fn call(state: AppState, uri: &str) -> (StatusCode, Value) {
+    let app = common::init_app(state).await;
+    let req = test::TestRequest::post()
+        .uri(uri)
+        .set_json(json!({"jsonrpc": "2.0", "id": "abc", "method": "getSlot"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+#[actix_web::test]
+async fn wraps_transport_failures_as_json_rpc_errors() {
+    let (status, body) = call(unreachable_state(true), "/").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["jsonrpc"], "2.0");
+    assert_eq!(body["id"], "abc");
+    assert_eq!(body["error"]["code"], -32010);
+    assert!(
+        body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("upstream request failed")
+    );
+    assert_eq!(body["error"]["data"]["validator"], "dead");
+    assert_eq!(body["error"]["data"]["attempts"], 1);
+    assert_eq!(body["error"]["data"]["maxRetries"], 2);
+}
+
+#[actix_web::test]
+async fn wraps_selection_failures_as_json_rpc_errors() {
+    let (status, body) = call(unreachable_state(true), "/?server=missing").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["id"], "abc");
+    assert_eq!(body["error"]["code"], -32016);
+    assert_eq!(body["error"]["data"]["validator"], Value::Null);
+    assert_eq!(body["error"]["data"]["attempts"], 0);
+}
+
+#[actix_web::test]
+async fn keeps_plain_errors_by_default() {
+    let (status, body) = call(unreachable_state(false), "/").await;
+
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert!(body["error"].is_string());
+    assert!(body.get("jsonrpc").is_none());
+}
+
+fn error_format_state(error_format: ErrorFormat) -> AppState {
+    let validator = Validator::new(
+        "dead".into(),
+        "lab".into(),
+        "http://127.0.0.1:9/".parse().unwrap(),
+    );
+    let settings = Settings {
+        error_format,
+        max_retries: 0,
+        ..Settings::default()
+    };
+    AppState::from_settings(common::registry(vec![validator]), settings)
+}
+
+#[actix_web::test]
+async fn serializes_plain_error_bodies() {
+    let body = AppError::BadRequest("bad input".into()).body(ErrorFormat::Plain);
+    assert_eq!(body, json!({"error": "bad input", "code": "bad_request"}));
+
+    let body = AppError::upstream(
+        "dead",
+        UpstreamErrorKind::ConnectRefused,
+        "node 'dead' is unavailable",
+    )
+    .body(ErrorFormat::Plain);
+    assert_eq!(
+        body,
+        json!({
+            "error": "upstream request failed: node 'dead' is unavailable",
+            "code": "upstream_connect_failed",
+            "details": {"validator": "dead"}
+        })
+    );
+}
+
+#[actix_web::test]
+async fn serializes_json_rpc_error_bodies() {
+    let body = AppError::upstream(
+        "dead",
+        UpstreamErrorKind::ConnectRefused,
+        "node 'dead' is unavailable",
+    )
+    .body(ErrorFormat::JsonRpc);
+    assert_eq!(
+        body,
+        json!({
+            "jsonrpc": "2.0",
+            "error": {
+                "code": -32010,
+                "message": "upstream request failed: node 'dead' is unavailable",
+                "data": {
+                    "code": "upstream_connect_failed",
+                    "requestId": null,
+                    "validator": "dead"
+                }
+            },
+            "id": null
+        })
+    );
+
+    // Only upstream failures name a validator.
+    let body = AppError::Unauthorized("missing key".into()).body(ErrorFormat::JsonRpc);
+    assert_eq!(body["error"]["code"], -32014);
+    assert_eq!(
+        body["error"]["data"],
+        json!({"code": "unauthorized", "requestId": null})
+    );
+}
+
+#[actix_web::test]
+async fn every_variant_has_its_own_code() {
+    let codes = [
+        AppError::BadRequest(String::new()),
+        AppError::UnsupportedMediaType(String::new()),
+        AppError::Unauthorized(String::new()),
+        AppError::Forbidden(String::new()),
+        AppError::Selection(SelectionError::Empty),
+        AppError::Selection(SelectionError::UnknownValidator {
+            name: String::new(),
+            suggestions: Vec::new(),
+        }),
+        AppError::upstream("dead", UpstreamErrorKind::ConnectRefused, ""),
+        AppError::Internal(String::new()),
+    ]
+    .map(|err| err.rpc_code());
+    assert_eq!(
+        codes,
+        [
+            -32600, -32013, -32014, -32015, -32011, -32016, -32010, -32603
+        ]
+    );
+}
+
+#[actix_web::test]
+async fn renders_proxy_failures_in_the_configured_format() {
+    let app = common::init_app(error_format_state(ErrorFormat::JsonRpc)).await;
+    let req = test::TestRequest::post()
+        .uri("/")
+        .insert_header(("x-request-id", "req-1"))
+        .set_json(json!({"jsonrpc": "2.0", "id": "abc", "method": "getSlot"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["jsonrpc"], "2.0");
+    assert_eq!(body["id"], Value::Null);
+    assert_eq!(body["error"]["code"], -32010);
+    assert_eq!(body["error"]["data"]["requestId"], "req-1");
+    assert_eq!(body["error"]["data"]["validator"], "dead");
+    assert!(body.get("request_id").is_none());
+
+    // Not only on the proxy route.
+    let req = test::TestRequest::get()
+        .uri("/slot?server=missing")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"]["code"], -32016);
+    assert_eq!(body["error"]["data"]["suggestions"], json!([]));
+    assert!(body["error"]["data"].get("validator").is_none());
+}
+
+#[actix_web::test]
+async fn plain_format_is_the_default() {
+    let app = common::init_app(error_format_state(ErrorFormat::default())).await;
+    let req = test::TestRequest::post()
+        .uri("/")
+        .insert_header(("x-request-id", "req-1"))
+        .set_json(json!({"jsonrpc": "2.0", "id": "abc", "method": "getSlot"}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["error"].is_string());
+    assert_eq!(body["request_id"], "req-1");
+}
@@ -0,0 +1,488 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use ring::hmac;
+use ring::rand::SystemRandom;
+use ring::signature::{self, RsaKeyPair, RsaPublicKeyComponents};
+use serde_json::{Map, Value, json};
+
+use the_solana_api::auth::ApiKeys;
+use the_solana_api::jwt::{self, Jwks, JwtKey, JwtSettings};
+use the_solana_api::key_usage;
+use the_solana_api::rate_limit::KeyRateLimit;
+use the_solana_api::{AppState, Settings};
+
+const SECRET: &[u8] = b"shared-secret";
+const NOW: u64 = 1_700_000_000;
+
+fn rsa_key() -> RsaKeyPair {
+    RsaKeyPair::from_pkcs8(include_bytes!("fixtures/jwt_rs256.pk8")).unwrap()
+}
+
+fn encode(value: &Value) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(value).unwrap())
+}
+
+fn hs256(claims: Value) -> String {
+    let signed = format!("{}.{}", encode(&json!({"alg": "HS256"})), encode(&claims));
+    let tag = hmac::sign(
+        &hmac::Key::new(hmac::HMAC_SHA256, SECRET),
+        signed.as_bytes(),
+    );
+    format!("{signed}.{}", URL_SAFE_NO_PAD.encode(tag.as_ref()))
+}
+
+fn rs256(kid: &str, claims: Value) -> String {
+    let header = json!({"alg": "RS256", "kid": kid, "typ": "JWT"});
+    let signed = format!("{}.{}", encode(&header), encode(&claims));
+    let key = rsa_key();
+    let mut sig = vec![0; key.public().modulus_len()];
+    key.sign(
+        &signature::RSA_PKCS1_SHA256,
+        &SystemRandom::new(),
+        signed.as_bytes(),
+        &mut sig,
+    )
+    .unwrap();
+    format!("{signed}.{}", URL_SAFE_NO_PAD.encode(sig))
+}
+
+fn jwk(kid: &str) -> Value {
+    let public = RsaPublicKeyComponents::<Vec<u8>>::from(rsa_key().public());
+    json!({
+        "kty": "RSA",
+        "kid": kid,
+        "alg": "RS256",
+        "use": "sig",
+        "n": URL_SAFE_NO_PAD.encode(public.n),
+        "e": URL_SAFE_NO_PAD.encode(public.e),
+    })
+}
+
+fn claims(value: Value) -> Map<String, Value> {
+    value.as_object().unwrap().clone()
+}
+
+fn strict() -> JwtSettings {
+    JwtSettings {
+        issuer: Some("https://auth.example".to_string()),
+        audience: Some("solana-api".to_string()),
+        leeway: Duration::from_secs(30),
+        ..JwtSettings::new(JwtKey::Hs256(SECRET.to_vec()))
+    }
+}
+
+fn valid() -> Value {
+    json!({
+        "sub": "customer-1",
+        "iss": "https://auth.example",
+        "aud": "solana-api",
+        "exp": NOW + 60,
+        "nbf": NOW - 60,
+    })
+}
+
+fn with(changes: Value) -> Map<String, Value> {
+    let mut claims = claims(valid());
+    for (name, value) in changes.as_object().unwrap() {
+        if value.is_null() {
+            claims.remove(name);
+        } else {
+            claims.insert(name.clone(), value.clone());
+        }
+    }
+    claims
+}
+
+fn rejection(result: Result<String, the_solana_api::AppError>) -> String {
+    match result {
+        Err(the_solana_api::AppError::Unauthorized(message)) => message,
+        other => panic!("expected a 401, got {other:?}"),
+    }
+}
+
+#[actix_web::test]
+async fn checks_each_claim() {
+    let settings = strict();
+    assert_eq!(
+        jwt::check_claims(&settings, &claims(valid()), NOW).unwrap(),
+        "customer-1"
+    );
+
+    let cases = [
+        (json!({"exp": null}), "token has no 'exp' claim"),
+        (
+            json!({"exp": "tomorrow"}),
+            "token 'exp' claim is not a number",
+        ),
+        (json!({"exp": NOW - 30}), "token has expired"),
+        (json!({"nbf": NOW + 31}), "token is not valid yet"),
+        (json!({"nbf": "now"}), "token 'nbf' claim is not a number"),
+        (
+            json!({"iss": "https://evil.example"}),
+            "token issuer is not accepted",
+        ),
+        (json!({"iss": null}), "token issuer is not accepted"),
+        (
+            json!({"aud": "other-api"}),
+            "token audience is not accepted",
+        ),
+        (json!({"aud": ["a", "b"]}), "token audience is not accepted"),
+        (json!({"aud": null}), "token audience is not accepted"),
+        (json!({"sub": null}), "token has no 'sub' claim"),
+        (json!({"sub": ""}), "token has no 'sub' claim"),
+    ];
+    for (changes, message) in cases {
+        assert_eq!(
+            rejection(jwt::check_claims(&settings, &with(changes.clone()), NOW)),
+            message,
+            "{changes}"
+        );
+    }
+}
+
+#[actix_web::test]
+async fn tolerates_clock_skew_within_the_leeway() {
+    let settings = strict();
+    // Expired 29s ago, or valid from 30s on: both inside the 30s leeway.
+    for changes in [json!({"exp": NOW - 29}), json!({"nbf": NOW + 30})] {
+        assert!(
+            jwt::check_claims(&settings, &with(changes.clone()), NOW).is_ok(),
+            "{changes}"
+        );
+    }
+    let exact = JwtSettings {
+        leeway: Duration::ZERO,
+        ..strict()
+    };
+    assert!(jwt::check_claims(&exact, &with(json!({"exp": NOW})), NOW).is_err());
+    assert!(jwt::check_claims(&exact, &with(json!({"exp": NOW + 1})), NOW).is_ok());
+
+    // Unconfigured issuer and audience are not checked.
+    let lax = JwtSettings::new(JwtKey::Hs256(SECRET.to_vec()));
+    let claims = claims(json!({"sub": "x", "exp": NOW + 1, "aud": "anyone"}));
+    assert!(jwt::check_claims(&lax, &claims, NOW).is_ok());
+}
+
+#[actix_web::test]
+async fn maps_a_configured_claim_to_the_client() {
+    let settings = JwtSettings {
+        client_claim: "client_id".to_string(),
+        ..strict()
+    };
+    let mut claims = claims(valid());
+    claims.insert("client_id".to_string(), json!(42));
+    assert_eq!(jwt::check_claims(&settings, &claims, NOW).unwrap(), "42");
+    claims.remove("client_id");
+    assert_eq!(
+        rejection(jwt::check_claims(&settings, &claims, NOW)),
+        "token has no 'client_id' claim"
+    );
+}
+
+#[actix_web::test]
+async fn verifies_hs256_signatures() {
+    let settings = JwtSettings::new(JwtKey::Hs256(SECRET.to_vec()));
+    let jwks = Jwks::default();
+    let token = hs256(valid());
+    assert_eq!(
+        jwt::authenticate(&settings, &jwks, &token, NOW)
+            .await
+            .unwrap(),
+        "customer-1"
+    );
+
+    // Payload swapped under the same signature.
+    let (header, rest) = token.split_once('.').unwrap();
+    let signature = rest.rsplit_once('.').unwrap().1;
+    let forged = format!(
+        "{header}.{}.{signature}",
+        encode(&json!({"sub": "admin", "exp": NOW + 60}))
+    );
+    let unsigned = format!("{}.{}.", encode(&json!({"alg": "none"})), encode(&valid()));
+    let wrong_secret = JwtSettings::new(JwtKey::Hs256(b"another-secret".to_vec()));
+    let cases = [
+        (&settings, forged, "invalid token signature"),
+        (&wrong_secret, token.clone(), "invalid token signature"),
+        (&settings, unsigned, "unsupported token algorithm 'none'"),
+        (&settings, "a.b.c".to_string(), "malformed token"),
+        (&settings, format!("{token}x"), "invalid token signature"),
+    ];
+    for (settings, token, message) in cases {
+        assert_eq!(
+            rejection(jwt::authenticate(settings, &jwks, &token, NOW).await),
+            message,
+            "{token}"
+        );
+    }
+}
+
+struct IdentityProvider {
+    keys: Arc<Mutex<Value>>,
+    fetches: Arc<AtomicUsize>,
+    upstream: common::MockUpstream,
+}
+
+impl IdentityProvider {
+    fn serve(&self, kids: &[&str]) {
+        let keys: Vec<Value> = kids.iter().map(|kid| jwk(kid)).collect();
+        *self.keys.lock().unwrap() = json!({ "keys": keys });
+    }
+
+    fn settings(&self) -> JwtSettings {
+        let url = self.upstream.url().join("jwks.json").unwrap();
+        JwtSettings::new(JwtKey::Jwks(url))
+    }
+
+    fn fetches(&self) -> usize {
+        self.fetches.load(Ordering::SeqCst)
+    }
+}
+
+fn identity_provider(kids: &[&str]) -> IdentityProvider {
+    let keys = Arc::new(Mutex::new(Value::Null));
+    let fetches = Arc::new(AtomicUsize::new(0));
+    let upstream = {
+        let (keys, fetches) = (keys.clone(), fetches.clone());
+        common::spawn_upstream(move |cfg| {
+            let (keys, fetches) = (keys.clone(), fetches.clone());
+            cfg.route(
+                "/jwks.json",
+                web::get().to(move || {
+                    fetches.fetch_add(1, Ordering::SeqCst);
+                    let body = keys.lock().unwrap().clone();
+                    async move { HttpResponse::Ok().json(body) }
+                }),
+            );
+        })
+    };
+    let provider = IdentityProvider {
+        keys,
+        fetches,
+        upstream,
+    };
+    provider.serve(kids);
+    provider
+}
+
+#[actix_web::test]
+async fn verifies_rs256_against_the_jwks() {
+    let idp = identity_provider(&["k1"]);
+    let settings = idp.settings();
+    let jwks = Jwks::default();
+
+    let token = rs256("k1", valid());
+    for _ in 0..3 {
+        assert_eq!(
+            jwt::authenticate(&settings, &jwks, &token, NOW)
+                .await
+                .unwrap(),
+            "customer-1"
+        );
+    }
+    assert_eq!(idp.fetches(), 1);
+    assert_eq!(jwks.len(), 1);
+
+    // An HS256 token signed with the public key must not pass as RS256.
+    let confused = hs256(valid());
+    assert_eq!(
+        rejection(jwt::authenticate(&settings, &jwks, &confused, NOW).await),
+        "unsupported token algorithm 'HS256'"
+    );
+    let tampered = format!("{}A", &token[..token.len() - 1]);
+    assert_eq!(
+        rejection(jwt::authenticate(&settings, &jwks, &tampered, NOW).await),
+        "invalid token signature"
+    );
+
+    // Unknown kids refetch the set, but not more than once per interval.
+    for _ in 0..3 {
+        assert_eq!(
+            rejection(jwt::authenticate(&settings, &jwks, &rs256("nope", valid()), NOW).await),
+            "unknown token key 'nope'"
+        );
+    }
+    assert_eq!(idp.fetches(), 1);
+}
+
+#[actix_web::test]
+async fn refetches_the_jwks_when_keys_rotate() {
+    let idp = identity_provider(&["k1"]);
+    let settings = idp.settings();
+    let jwks = Jwks::with_min_refresh_interval(Duration::ZERO);
+
+    assert!(
+        jwt::authenticate(&settings, &jwks, &rs256("k1", valid()), NOW)
+            .await
+            .is_ok()
+    );
+    idp.serve(&["k2"]);
+    assert!(
+        jwt::authenticate(&settings, &jwks, &rs256("k2", valid()), NOW)
+            .await
+            .is_ok()
+    );
+    assert_eq!(idp.fetches(), 2);
+    // Known keys are served from the cache until the refresh period.
+    assert!(
+        jwt::authenticate(&settings, &jwks, &rs256("k2", valid()), NOW)
+            .await
+            .is_ok()
+    );
+    assert_eq!(idp.fetches(), 2);
+
+    // Past the refresh period the set is fetched again.
+    let expiring = JwtSettings {
+        jwks_refresh: Duration::ZERO,
+        ..settings.clone()
+    };
+    assert!(
+        jwt::authenticate(&expiring, &jwks, &rs256("k2", valid()), NOW)
+            .await
+            .is_ok()
+    );
+    assert_eq!(idp.fetches(), 3);
+    // The old key was rotated out.
+    assert!(
+        jwt::authenticate(&settings, &jwks, &rs256("k1", valid()), NOW)
+            .await
+            .is_err()
+    );
+}
+
+#[actix_web::test]
+async fn an_unreachable_jwks_is_a_server_error() {
+    let settings = JwtSettings::new(JwtKey::Jwks(
+        url::Url::parse("http://127.0.0.1:9/jwks.json").unwrap(),
+    ));
+    let err = jwt::authenticate(&settings, &Jwks::default(), &rs256("k1", valid()), NOW)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, the_solana_api::AppError::Internal(_)),
+        "{err:?}"
+    );
+}
+
+fn now_claims(sub: &str) -> Value {
+    json!({"sub": sub, "exp": key_usage::now_secs() + 300})
+}
+
+fn get_slot(token: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/")
+        .insert_header(("authorization", format!("Bearer {token}")))
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+        .to_request()
+}
+
+fn jsonrpc_upstream() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 7}))
+            }),
+        );
+    })
+}
+
+#[actix_web::test]
+async fn tokens_name_the_client_for_limits_and_stats() {
+    let upstream = jsonrpc_upstream();
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("node", "lab")]),
+        Settings {
+            jwt: Some(JwtSettings::new(JwtKey::Hs256(SECRET.to_vec()))),
+            api_key_rate_limit: KeyRateLimit::new(0.001, Some(2)),
+            admin_token: Some("admin-secret".into()),
+            ..Settings::default()
+        },
+    );
+    let app = common::init_app(state).await;
+
+    for _ in 0..2 {
+        let resp = test::call_service(&app, get_slot(&hs256(now_claims("alice")))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    // Every token for the same subject shares its bucket.
+    let resp = test::call_service(&app, get_slot(&hs256(now_claims("alice")))).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let resp = test::call_service(&app, get_slot(&hs256(now_claims("bob")))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri("/admin/keys/alice/usage")
+            .insert_header(("authorization", "Bearer admin-secret"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(body["total"]["requests"], 2);
+
+    let expired = hs256(json!({"sub": "alice", "exp": key_usage::now_secs() - 3600}));
+    for (request, message) in [
+        (get_slot(&expired), "token has expired"),
+        (get_slot("not-a-jwt"), "malformed token; expected a JWT"),
+        (
+            test::TestRequest::post()
+                .uri("/")
+                .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+                .to_request(),
+            "missing token; send 'Authorization: Bearer <token>'",
+        ),
+    ] {
+        let resp = test::call_service(&app, request).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "unauthorized");
+        assert_eq!(body["error"], message);
+    }
+}
+
+#[actix_web::test]
+async fn api_keys_keep_working_next_to_jwts() {
+    let upstream = jsonrpc_upstream();
+    let idp = identity_provider(&["k1"]);
+    let hash = common::sha256("issued-key");
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("node", "lab")]),
+        Settings::builder()
+            .jwt(idp.settings())
+            .api_keys(ApiKeys::new(
+                ApiKeys::parse_inline(&format!("partner:{hash}")).unwrap(),
+            ))
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    let resp = test::call_service(&app, get_slot(&rs256("k1", now_claims("alice")))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&app, get_slot("issued-key")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&app, get_slot("unknown-key")).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "invalid API key");
+
+    let body: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri("/usage")
+            .insert_header((
+                "authorization",
+                format!("Bearer {}", rs256("k1", now_claims("carol"))),
+            ))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(body["key_id"], "carol");
+    assert_eq!(body["quota"]["limit"], Value::Null);
+}
@@ -0,0 +1,314 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::auth::ApiKeys;
+use the_solana_api::quota::{QuotaPeriod, QuotaTable, RequestQuota};
+use the_solana_api::snapshot::{self, StatsSnapshot};
+use the_solana_api::{AppState, Settings};
+
+const DAY: u64 = 86_400;
+// UTC midnights.
+const DEC_1_2023: u64 = 19_692 * DAY;
+const JAN_1_2024: u64 = 19_723 * DAY;
+const FEB_1_2024: u64 = 19_754 * DAY;
+const FEB_29_2024: u64 = 19_782 * DAY;
+const MAR_1_2024: u64 = 19_783 * DAY;
+
+fn per_day(limit: u64) -> Option<RequestQuota> {
+    Some(RequestQuota {
+        limit,
+        period: QuotaPeriod::Day,
+    })
+}
+
+fn per_month(limit: u64) -> Option<RequestQuota> {
+    Some(RequestQuota {
+        limit,
+        period: QuotaPeriod::Month,
+    })
+}
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("solana-api-quota-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[actix_web::test]
+async fn parses_quotas() {
+    for (raw, limit, period) in [
+        ("500", 500, QuotaPeriod::Day),
+        ("500/day", 500, QuotaPeriod::Day),
+        (" 9000 / Month ", 9000, QuotaPeriod::Month),
+    ] {
+        assert_eq!(raw.parse(), Ok(RequestQuota { limit, period }), "{raw}");
+    }
+    for raw in ["", "0", "-1", "10/week", "ten/day", "/day"] {
+        assert!(raw.parse::<RequestQuota>().is_err(), "{raw}");
+    }
+
+    let hash = common::sha256("quota-key");
+    let keys = ApiKeys::parse_file(&format!(
+        "a,{hash},,,,,,,100/month\nb,{hash},,,,,,,50\nc,{hash}\n"
+    ))
+    .unwrap();
+    assert_eq!(keys[0].quota, per_month(100));
+    assert_eq!(keys[1].quota, per_day(50));
+    assert_eq!(keys[2].quota, None);
+    let line = format!("a,{hash},,,,,,,100/week");
+    assert_eq!(
+        ApiKeys::parse_file(&line),
+        Err("line 1 (key a): invalid quota".to_string())
+    );
+}
+
+#[actix_web::test]
+async fn daily_quotas_roll_over_at_utc_midnight() {
+    let mut table = QuotaTable::default();
+    let evening = JAN_1_2024 + DAY - 1;
+    assert_eq!(
+        table.consume("a", per_day(2), JAN_1_2024).unwrap().consumed,
+        1
+    );
+    let usage = table.consume("a", per_day(2), evening).unwrap();
+    assert_eq!(usage.remaining, Some(0));
+    assert_eq!(usage.resets_at_unix_ms, (JAN_1_2024 + DAY) * 1000);
+
+    let refused = table.consume("a", per_day(2), evening).unwrap_err();
+    assert_eq!(refused.consumed, 2);
+    assert_eq!(refused.retry_after(evening), Duration::from_secs(1));
+    // Other keys have their own count.
+    assert!(table.consume("b", per_day(2), evening).is_ok());
+
+    let usage = table.consume("a", per_day(2), JAN_1_2024 + DAY).unwrap();
+    assert_eq!(usage.consumed, 1);
+    assert_eq!(usage.remaining, Some(1));
+}
+
+#[actix_web::test]
+async fn monthly_quotas_follow_the_calendar() {
+    let mut table = QuotaTable::default();
+    table
+        .consume("a", per_month(3), DEC_1_2023 + 5 * DAY)
+        .unwrap();
+    table
+        .consume("a", per_month(3), DEC_1_2023 + 20 * DAY)
+        .unwrap();
+    let usage = table.consume("a", per_month(3), JAN_1_2024 - 1).unwrap();
+    assert_eq!(usage.consumed, 3);
+    assert_eq!(usage.resets_at_unix_ms, JAN_1_2024 * 1000);
+    assert!(table.consume("a", per_month(3), JAN_1_2024 - 1).is_err());
+
+    // A new year starts a new month.
+    let usage = table.consume("a", per_month(3), JAN_1_2024).unwrap();
+    assert_eq!(usage.consumed, 1);
+    assert_eq!(usage.resets_at_unix_ms, FEB_1_2024 * 1000);
+
+    // February 2024 has 29 days.
+    let usage = table
+        .consume("a", per_month(3), FEB_29_2024 + 3600)
+        .unwrap();
+    assert_eq!(usage.consumed, 1);
+    assert_eq!(usage.resets_at_unix_ms, MAR_1_2024 * 1000);
+
+    // The same counts, read as a daily quota.
+    let usage = table.usage("a", per_day(10), FEB_29_2024 + 3600);
+    assert_eq!(usage.period, QuotaPeriod::Day);
+    assert_eq!(usage.consumed, 1);
+    assert_eq!(usage.resets_at_unix_ms, MAR_1_2024 * 1000);
+    let unlimited = table.usage("a", None, FEB_29_2024);
+    assert_eq!((unlimited.limit, unlimited.remaining), (None, None));
+}
+
+#[actix_web::test]
+async fn a_clock_stepped_back_does_not_reopen_a_period() {
+    let mut table = QuotaTable::default();
+    let tomorrow = JAN_1_2024 + DAY;
+    table.consume("a", per_day(1), tomorrow).unwrap();
+
+    // The clock jumps back a day: still the day that was used up.
+    let refused = table.consume("a", per_day(1), JAN_1_2024).unwrap_err();
+    assert_eq!(refused.resets_at_unix_ms, (tomorrow + DAY) * 1000);
+    assert_eq!(
+        refused.retry_after(JAN_1_2024),
+        Duration::from_secs(2 * DAY)
+    );
+    assert!(table.consume("a", per_day(1), tomorrow + DAY).is_ok());
+
+    // Restored counts from a later day move the clock forward too.
+    let mut restored = QuotaTable::default();
+    restored.restore(&table.days());
+    assert!(restored.consume("a", per_day(1), JAN_1_2024).is_err());
+}
+
+async fn quota_app(settings: Settings) -> (AppState, common::MockUpstream) {
+    let upstream = common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 7}))
+            }),
+        );
+    });
+    let file = format!(
+        "capped,{},,,,,,,2/day\nopen,{}\n",
+        common::sha256("capped-key"),
+        common::sha256("open-key")
+    );
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("node", "lab")]),
+        Settings {
+            api_keys: Some(ApiKeys::new(ApiKeys::parse_file(&file).unwrap())),
+            admin_token: Some("admin-secret".into()),
+            ..settings
+        },
+    );
+    (state, upstream)
+}
+
+fn get_slot(key: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/")
+        .insert_header(("authorization", format!("Bearer {key}")))
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+        .to_request()
+}
+
+fn get(uri: &str, token: &str) -> actix_http::Request {
+    test::TestRequest::get()
+        .uri(uri)
+        .insert_header(("authorization", format!("Bearer {token}")))
+        .to_request()
+}
+
+#[actix_web::test]
+async fn exhausted_keys_get_a_429_until_the_period_ends() {
+    let (state, _upstream) = quota_app(Settings::default()).await;
+    let app = common::init_app(state).await;
+
+    for _ in 0..2 {
+        let resp = test::call_service(&app, get_slot("capped-key")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    let resp = test::call_service(&app, get_slot("capped-key")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = resp
+        .headers()
+        .get("retry-after")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=DAY).contains(&retry_after), "{retry_after}");
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "quota_exceeded");
+    assert_eq!(
+        body["error"],
+        "API key quota of 2 requests per day exceeded"
+    );
+
+    // Reading the usage is free, and other keys are unaffected.
+    for _ in 0..2 {
+        let body: Value = test::call_and_read_body_json(&app, get("/usage", "capped-key")).await;
+        assert_eq!(body["key_id"], "capped");
+        assert_eq!(
+            body["quota"],
+            json!({
+                "period": "day",
+                "limit": 2,
+                "consumed": 2,
+                "remaining": 0,
+                "resets_at_unix_ms": body["quota"]["resets_at_unix_ms"],
+            })
+        );
+        assert_eq!(
+            body["quota"]["resets_at_unix_ms"].as_u64().unwrap() % (DAY * 1000),
+            0
+        );
+    }
+    let resp = test::call_service(&app, get_slot("open-key")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: Value =
+        test::call_and_read_body_json(&app, get("/admin/keys/capped/usage", "admin-secret")).await;
+    assert_eq!(body["quota"]["consumed"], 2);
+    assert_eq!(body["total"]["requests"], 2);
+    let body: Value =
+        test::call_and_read_body_json(&app, get("/admin/keys/open/usage", "admin-secret")).await;
+    assert_eq!(body["quota"]["consumed"], 1);
+    assert_eq!(body["quota"]["limit"], Value::Null);
+    assert_eq!(body["quota"]["remaining"], Value::Null);
+
+    let resp = test::call_service(&app, get("/admin/keys/nobody/usage", "admin-secret")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = test::call_service(&app, get("/usage", "wrong-key")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn issued_keys_report_usage_before_their_first_request() {
+    let (state, _upstream) = quota_app(Settings::default()).await;
+    let app = common::init_app(state).await;
+
+    let body: Value =
+        test::call_and_read_body_json(&app, get("/admin/keys/capped/usage", "admin-secret")).await;
+    assert_eq!(body["total"]["requests"], 0);
+    assert_eq!(body["quota"]["remaining"], 2);
+}
+
+#[actix_web::test]
+async fn usage_needs_api_keys() {
+    let upstream = common::spawn_upstream(|_| {});
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("node", "lab")]),
+        Settings::default(),
+    );
+    let app = common::init_app(state).await;
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/usage").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn quotas_survive_a_restart() {
+    let dir = scratch_dir("restart");
+    let path = dir.join("stats.json");
+    let settings = || Settings {
+        stats_snapshot_path: Some(path.clone()),
+        ..Settings::default()
+    };
+
+    let (before, _upstream) = quota_app(settings()).await;
+    let app = common::init_app(before.clone()).await;
+    for _ in 0..2 {
+        test::call_service(&app, get_slot("capped-key")).await;
+    }
+    snapshot::persist(&before);
+
+    let (after, _upstream) = quota_app(settings()).await;
+    snapshot::restore_on_startup(&after);
+    let app = common::init_app(after).await;
+    let resp = test::call_service(&app, get_slot("capped-key")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // A snapshot too old for validator stats still carries the quotas.
+    let mut saved = snapshot::load(&path, Duration::from_secs(3600)).unwrap();
+    saved.saved_at_unix_ms -= 7 * DAY * 1000;
+    fs::write(&path, serde_json::to_vec(&saved).unwrap()).unwrap();
+    let (stale, _upstream) = quota_app(settings()).await;
+    snapshot::restore_on_startup(&stale);
+    assert_eq!(stale.stats().quota_usage("capped", per_day(2)).consumed, 2);
+
+    // Snapshots written before quotas existed still load.
+    fs::write(&path, r#"{"saved_at_unix_ms": 1, "validators": {}}"#).unwrap();
+    let old: StatsSnapshot = snapshot::read(&path).unwrap();
+    assert!(old.quotas.is_empty());
+}
@@ -0,0 +1,205 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::auth::{ApiKey, ApiKeys};
+use the_solana_api::rate_limit::{KeyRateLimit, KeyRateLimiter};
+use the_solana_api::{AppState, Settings};
+
+const ALICE: &str = "alice-secret-key";
+const BOB: &str = "bob-secret-key";
+
+fn limit(requests_per_second: f64, burst: u32) -> KeyRateLimit {
+    KeyRateLimit::new(requests_per_second, Some(burst)).unwrap()
+}
+
+fn get_slot(key: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/")
+        .insert_header(("authorization", format!("Bearer {key}")))
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+}
+
+fn header(resp: &actix_web::dev::ServiceResponse, name: &str) -> String {
+    resp.headers()
+        .get(name)
+        .unwrap_or_else(|| panic!("missing {name}"))
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[actix_web::test]
+async fn buckets_drain_and_refill() {
+    let limiter = KeyRateLimiter::new(10);
+    let start = Instant::now();
+    let limit = limit(2.0, 3);
+
+    for remaining in [2, 1, 0] {
+        let quota = limiter.check_at("alice", limit, start);
+        assert_eq!(quota.retry_after, None);
+        assert_eq!(quota.limit, 3);
+        assert_eq!(quota.remaining, remaining);
+    }
+    let refused = limiter.check_at("alice", limit, start);
+    assert_eq!(refused.retry_after, Some(Duration::from_millis(500)));
+    assert_eq!(refused.reset, Duration::from_millis(1500));
+
+    // Other keys have buckets of their own.
+    assert_eq!(limiter.check_at("bob", limit, start).retry_after, None);
+
+    let later = start + Duration::from_millis(500);
+    let quota = limiter.check_at("alice", limit, later);
+    assert_eq!(quota.retry_after, None);
+    assert_eq!(quota.remaining, 0);
+
+    // Never more than the burst, however long the key was idle.
+    let quota = limiter.check_at("alice", limit, start + Duration::from_secs(30));
+    assert_eq!(quota.remaining, 2);
+}
+
+#[actix_web::test]
+async fn idle_keys_are_swept_and_the_table_is_bounded() {
+    let limiter = KeyRateLimiter::new(3);
+    let start = Instant::now();
+    let limit = limit(1.0, 1);
+
+    for key in ["a", "b", "c", "d"] {
+        limiter.check_at(key, limit, start);
+    }
+    assert_eq!(limiter.tracked_keys(), 3);
+    // "a" was evicted, so it starts over with a full bucket.
+    assert_eq!(limiter.check_at("a", limit, start).retry_after, None);
+    assert!(limiter.check_at("d", limit, start).retry_after.is_some());
+
+    // Every bucket has refilled a minute on, so the sweep drops them all
+    // before tracking the caller.
+    limiter.check_at("e", limit, start + Duration::from_secs(61));
+    assert_eq!(limiter.tracked_keys(), 1);
+}
+
+#[actix_web::test]
+async fn concurrent_callers_never_exceed_the_burst() {
+    let limiter = Arc::new(KeyRateLimiter::new(100));
+    let allowed = Arc::new(AtomicUsize::new(0));
+
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let limiter = limiter.clone();
+            let allowed = allowed.clone();
+            thread::spawn(move || {
+                for _ in 0..25 {
+                    if limiter
+                        .check("shared", limit(0.001, 50))
+                        .retry_after
+                        .is_none()
+                    {
+                        allowed.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(allowed.load(Ordering::SeqCst), 50);
+}
+
+#[actix_web::test]
+async fn rejects_with_429_and_rate_limit_headers() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = {
+        let hits = hits.clone();
+        common::spawn_upstream(move |cfg| {
+            let hits = hits.clone();
+            cfg.route(
+                "/",
+                web::post().to(move || {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    async {
+                        HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 7}))
+                    }
+                }),
+            );
+        })
+    };
+    let keys = ApiKeys::new(vec![
+        ApiKey::new("alice", &common::sha256(ALICE), false).unwrap(),
+        ApiKey::new("bob", &common::sha256(BOB), false)
+            .unwrap()
+            .with_limit(Some(limit(0.01, 1))),
+    ]);
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("node", "lab")]),
+        Settings::builder()
+            .api_keys(keys)
+            .api_key_rate_limit(limit(0.5, 2))
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    let resp = test::call_service(&app, get_slot(ALICE).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(header(&resp, "x-ratelimit-limit"), "2");
+    assert_eq!(header(&resp, "x-ratelimit-remaining"), "1");
+    assert_eq!(header(&resp, "x-ratelimit-reset"), "2");
+
+    let resp = test::call_service(&app, get_slot(ALICE).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(header(&resp, "x-ratelimit-remaining"), "0");
+
+    let resp = test::call_service(&app, get_slot(ALICE).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&resp, "retry-after"), "2");
+    assert_eq!(header(&resp, "x-ratelimit-limit"), "2");
+    assert_eq!(header(&resp, "x-ratelimit-remaining"), "0");
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "rate_limited");
+    assert_eq!(body["error"], "API key rate limit exceeded");
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    // A key's own limit wins over the default.
+    let resp = test::call_service(&app, get_slot(BOB).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(header(&resp, "x-ratelimit-limit"), "1");
+    let resp = test::call_service(&app, get_slot(BOB).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+}
+
+#[actix_web::test]
+async fn keys_are_unlimited_without_a_limit() {
+    let upstream = common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 7}))
+            }),
+        );
+    });
+    let keys = ApiKeys::new(vec![
+        ApiKey::new("alice", &common::sha256(ALICE), false).unwrap(),
+    ]);
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("node", "lab")]),
+        Settings::builder().api_keys(keys).build().unwrap(),
+    );
+    let app = common::init_app(state.clone()).await;
+
+    for _ in 0..20 {
+        let resp = test::call_service(&app, get_slot(ALICE).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get("x-ratelimit-limit").is_none());
+    }
+    assert_eq!(state.key_limiter().tracked_keys(), 0);
+}
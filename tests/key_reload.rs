@@ -0,0 +1,228 @@
+mod common;
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::auth::ApiKeys;
+use the_solana_api::key_store::{self, KeyChanges};
+use the_solana_api::{AppState, Settings};
+
+const ALICE: &str = "alice-secret";
+const BOB: &str = "bob-secret";
+const CAROL: &str = "carol-secret";
+
+// Removed when dropped.
+struct KeyFile(PathBuf);
+
+impl KeyFile {
+    fn new(name: &str, contents: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("solana-api-{name}-{}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        Self(path)
+    }
+
+    fn write(&self, contents: &str) {
+        std::fs::write(&self.0, contents).unwrap();
+    }
+}
+
+impl Drop for KeyFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn lines(entries: &[(&str, &str, &str)]) -> String {
+    entries
+        .iter()
+        .map(|(id, key, rest)| format!("{id},{}{rest}\n", common::sha256(key)))
+        .collect()
+}
+
+fn setup(file: &KeyFile, upstream_delay: Duration) -> (common::MockUpstream, AppState) {
+    let upstream = common::spawn_upstream(move |cfg| {
+        cfg.route(
+            "/",
+            web::post().to(move || async move {
+                tokio::time::sleep(upstream_delay).await;
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 7}))
+            }),
+        );
+    });
+    let keys = ApiKeys::default().with_file(&file.0).unwrap();
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("node", "lab")]),
+        Settings::builder()
+            .api_keys(keys)
+            .api_keys_reload_interval(Duration::from_millis(20))
+            .build()
+            .unwrap(),
+    );
+    (upstream, state)
+}
+
+fn get_slot(key: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/")
+        .insert_header(("authorization", format!("Bearer {key}")))
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+        .to_request()
+}
+
+#[actix_web::test]
+async fn revocation_applies_to_the_next_request_only() {
+    let file = KeyFile::new("revoke", &lines(&[("alice", ALICE, ""), ("bob", BOB, "")]));
+    let (_upstream, state) = setup(&file, Duration::from_millis(300));
+    let app = common::init_app(state.clone()).await;
+
+    let resp = test::call_service(&app, get_slot(ALICE)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // A request already past auth finishes while the key is revoked.
+    let in_flight = test::call_service(&app, get_slot(ALICE));
+    let revoke = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        file.write(&lines(&[("alice", ALICE, ",revoked"), ("bob", BOB, "")]));
+        state.api_keys().reload().unwrap()
+    };
+    let (resp, changes) = tokio::join!(in_flight, revoke);
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(changes.changed, ["alice"]);
+
+    let resp = test::call_service(&app, get_slot(ALICE)).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "API key has been revoked");
+    let resp = test::call_service(&app, get_slot(BOB)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn reloads_apply_additions_changes_and_removals() {
+    let file = KeyFile::new("changes", &lines(&[("alice", ALICE, ""), ("bob", BOB, "")]));
+    let (_upstream, state) = setup(&file, Duration::ZERO);
+    let app = common::init_app(state.clone()).await;
+
+    file.write(&lines(&[("bob", BOB, ",,5"), ("carol", CAROL, "")]));
+    assert_eq!(
+        state.api_keys().reload().unwrap(),
+        KeyChanges {
+            added: vec!["carol".to_string()],
+            changed: vec!["bob".to_string()],
+            removed: vec!["alice".to_string()],
+        }
+    );
+
+    for (key, status) in [
+        (ALICE, StatusCode::UNAUTHORIZED),
+        (BOB, StatusCode::OK),
+        (CAROL, StatusCode::OK),
+    ] {
+        let resp = test::call_service(&app, get_slot(key)).await;
+        assert_eq!(resp.status(), status, "{key}");
+    }
+    let resp = test::call_service(&app, get_slot(BOB)).await;
+    assert_eq!(resp.headers().get("x-ratelimit-limit").unwrap(), "5");
+
+    // Inline keys survive every reload.
+    let inline =
+        ApiKeys::new(ApiKeys::parse_inline(&format!("ops:{}", common::sha256("ops"))).unwrap());
+    let keys = inline.with_file(&file.0).unwrap();
+    assert_eq!(keys.len(), 3);
+    file.write("");
+    assert_eq!(keys.with_file(&file.0).unwrap().len(), 1);
+}
+
+#[actix_web::test]
+async fn a_broken_file_keeps_the_previous_keys() {
+    let file = KeyFile::new("broken", &lines(&[("alice", ALICE, "")]));
+    let (_upstream, state) = setup(&file, Duration::ZERO);
+    let app = common::init_app(state.clone()).await;
+
+    file.write("alice,not-a-hash\n");
+    assert_eq!(
+        state.api_keys().reload(),
+        Err("line 1 (key alice): sha256 must be 64 hex digits".to_string())
+    );
+    // Not retried until the file changes again.
+    assert_eq!(state.api_keys().reload_if_changed(), None);
+    let resp = test::call_service(&app, get_slot(ALICE)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    file.write(&lines(&[("alice", ALICE, ",revoked")]));
+    assert!(state.api_keys().reload_if_changed().unwrap().is_ok());
+    let resp = test::call_service(&app, get_slot(ALICE)).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn the_watch_picks_up_edits() {
+    let file = KeyFile::new("watch", &lines(&[("alice", ALICE, "")]));
+    let (_upstream, state) = setup(&file, Duration::ZERO);
+    let app = common::init_app(state.clone()).await;
+    key_store::spawn_watch(state.clone());
+    assert_eq!(state.api_keys().reload_if_changed(), None);
+
+    file.write(&lines(&[("alice", ALICE, ",revoked"), ("bob", BOB, "")]));
+    let started = Instant::now();
+    while state.api_keys().current().unwrap().get("bob").is_none() {
+        assert!(started.elapsed() < Duration::from_secs(5), "never reloaded");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let resp = test::call_service(&app, get_slot(ALICE)).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = test::call_service(&app, get_slot(BOB)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn reads_json_key_files() {
+    let file = KeyFile::new(
+        "json",
+        &json!([
+            {"id": "alice", "sha256": common::sha256(ALICE)},
+            {
+                "id": "bob",
+                "sha256": common::sha256(BOB),
+                "enabled": false,
+                "rps": 2.5,
+                "burst": 5,
+                "locations": ["europe"],
+                "methods": ["get*"],
+                "quota": "1000/month",
+            },
+        ])
+        .to_string(),
+    );
+    let keys = ApiKeys::default().with_file(&file.0).unwrap();
+    let [alice, bob] = keys.keys() else {
+        panic!("expected two keys");
+    };
+    assert!(!alice.revoked && alice.limit.is_none() && alice.scope.is_unrestricted());
+    assert!(bob.revoked);
+    assert_eq!(bob.limit.unwrap().burst, 5);
+    assert_eq!(bob.scope.locations, ["europe"]);
+    assert_eq!(bob.quota.unwrap().limit, 1000);
+
+    for (entries, message) in [
+        (json!([{"id": "a", "sha256": "00"}]), "key 1: a"),
+        (
+            json!([{"id": "a", "sha256": common::sha256(ALICE)}, {"id": "b", "sha256": common::sha256(BOB), "burst": 2}]),
+            "key 2: b",
+        ),
+        (
+            json!([{"id": "a", "sha256": common::sha256(ALICE), "quota": "lots"}]),
+            "key 1: a",
+        ),
+    ] {
+        assert_eq!(
+            ApiKeys::parse_json(&entries.to_string()),
+            Err(message.to_string())
+        );
+    }
+    assert!(ApiKeys::parse_json(r#"[{"id": "a", "sha256": "00", "admin": true}]"#).is_err());
+}
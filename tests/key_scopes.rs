@@ -0,0 +1,225 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{Value, json};
+
+use the_solana_api::auth::{ApiKeys, KeyScope};
+use the_solana_api::jsonrpc::RpcMethod;
+use the_solana_api::{AppState, Settings, Validator};
+
+// Europe, reads only.
+const EU_READER: &str = "eu-reader-key";
+const EVERYTHING: &str = "everything-key";
+const SLOT_ONLY: &str = "slot-only-key";
+
+struct Fleet {
+    app_state: AppState,
+    europe: Arc<AtomicUsize>,
+    asia: Arc<AtomicUsize>,
+    _upstreams: [common::MockUpstream; 2],
+}
+
+fn fleet() -> Fleet {
+    let (europe, asia) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    let eu = common::counting_upstream(europe.clone());
+    let ap = common::counting_upstream(asia.clone());
+    let file = format!(
+        "eu-reader,{},,,,europe,,get*\neverything,{}\nslot-only,{},,,,,,getSlot\n",
+        common::sha256(EU_READER),
+        common::sha256(EVERYTHING),
+        common::sha256(SLOT_ONLY),
+    );
+    let app_state = AppState::from_settings(
+        common::registry(vec![
+            eu.validator("eu-1", "europe"),
+            ap.validator("asia-1", "asia"),
+        ]),
+        Settings::builder()
+            .api_keys(ApiKeys::new(ApiKeys::parse_file(&file).unwrap()))
+            .max_retries(0)
+            .build()
+            .unwrap(),
+    );
+    Fleet {
+        app_state,
+        europe,
+        asia,
+        _upstreams: [eu, ap],
+    }
+}
+
+fn call(key: &str, uri: &str, body: Value) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri(uri)
+        .insert_header(("authorization", format!("Bearer {key}")))
+        .set_json(body)
+        .to_request()
+}
+
+fn rpc(method: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": 1, "method": method})
+}
+
+async fn violation(resp: actix_web::dev::ServiceResponse) -> String {
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "key_scope_violation");
+    body["error"].as_str().unwrap().to_string()
+}
+
+#[actix_web::test]
+async fn parses_scope_columns() {
+    let hash = common::sha256(EU_READER);
+    let keys = ApiKeys::parse_file(&format!(
+        "a,{hash},,5,,Europe; asia,,get*;sendTransaction\nb,{hash},,,,,eu-1\nc,{hash}\n"
+    ))
+    .unwrap();
+    assert_eq!(
+        keys[0].scope,
+        KeyScope {
+            locations: vec!["Europe".to_string(), "asia".to_string()],
+            validators: Vec::new(),
+            methods: vec!["get*".to_string(), "sendTransaction".to_string()],
+        }
+    );
+    assert_eq!(keys[0].limit.unwrap().requests_per_second, 5.0);
+    assert_eq!(keys[1].scope.validators, ["eu-1"]);
+    assert!(keys[1].scope.methods.is_empty());
+    assert!(keys[2].scope.is_unrestricted());
+
+    let line = format!("a,{hash},,,,europe,eu-1,getSlot,,extra");
+    assert_eq!(
+        ApiKeys::parse_file(&line),
+        Err("line 1 (key a): too many columns".to_string())
+    );
+}
+
+#[actix_web::test]
+async fn scope_checks_methods_and_selection() {
+    let scope = KeyScope {
+        locations: vec!["Europe".to_string()],
+        validators: Vec::new(),
+        methods: vec!["get*".to_string()],
+    };
+    assert!(scope.allows_method("getSlot"));
+    assert!(!scope.allows_method("sendTransaction"));
+    assert!(
+        scope
+            .check_method(&RpcMethod::Batch(vec![Some("getSlot".to_string()), None]))
+            .is_ok()
+    );
+    assert!(
+        scope
+            .check_method(&RpcMethod::Batch(vec![
+                Some("getSlot".to_string()),
+                Some("sendTransaction".to_string()),
+            ]))
+            .is_err()
+    );
+    assert!(scope.check_method(&RpcMethod::Invalid).is_err());
+    assert!(
+        KeyScope::default()
+            .check_method(&RpcMethod::Invalid)
+            .is_ok()
+    );
+
+    assert!(scope.check_selection(None, Some("europe")).is_ok());
+    assert!(scope.check_selection(None, Some("asia")).is_err());
+    assert!(scope.check_selection(Some("asia-1"), None).is_ok());
+
+    let url = url::Url::parse("http://127.0.0.1:9/").unwrap();
+    let eu = Validator::new("eu-1".into(), "europe".into(), url.clone());
+    let asia = Validator::new("asia-1".into(), "asia".into(), url);
+    assert_eq!(
+        scope.narrow(vec![eu.clone(), asia.clone()]).unwrap().len(),
+        1
+    );
+    assert!(scope.narrow(vec![asia]).is_err());
+
+    let named = KeyScope {
+        validators: vec!["EU-1".to_string()],
+        ..KeyScope::default()
+    };
+    assert!(named.allows_validator(&eu));
+    assert!(named.check_selection(Some("asia-1"), None).is_err());
+}
+
+#[actix_web::test]
+async fn restricted_keys_stay_within_their_scope() {
+    let fleet = fleet();
+    let app = common::init_app(fleet.app_state.clone()).await;
+
+    for _ in 0..6 {
+        let resp = test::call_service(&app, call(EU_READER, "/", rpc("getSlot"))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    assert_eq!(fleet.europe.load(Ordering::SeqCst), 6);
+    assert_eq!(fleet.asia.load(Ordering::SeqCst), 0);
+
+    let resp = test::call_service(&app, call(EU_READER, "/", rpc("sendTransaction"))).await;
+    assert_eq!(
+        violation(resp).await,
+        "API key may not use method 'sendTransaction'"
+    );
+
+    let batch = json!([rpc("getSlot"), rpc("requestAirdrop")]);
+    let resp = test::call_service(&app, call(EU_READER, "/", batch)).await;
+    violation(resp).await;
+
+    let resp = test::call_service(&app, call(EU_READER, "/?location=asia", rpc("getSlot"))).await;
+    assert_eq!(violation(resp).await, "API key may not use location 'asia'");
+
+    // The client's own constraint intersects with the key's.
+    let resp =
+        test::call_service(&app, call(EU_READER, "/?validator=asia-1", rpc("getSlot"))).await;
+    violation(resp).await;
+    let resp = test::call_service(&app, call(EU_READER, "/?location=Europe", rpc("getSlot"))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(fleet.asia.load(Ordering::SeqCst), 0);
+}
+
+#[actix_web::test]
+async fn unrestricted_keys_reach_everything() {
+    let fleet = fleet();
+    let app = common::init_app(fleet.app_state.clone()).await;
+
+    let resp = test::call_service(
+        &app,
+        call(EVERYTHING, "/?location=asia", rpc("sendTransaction")),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(fleet.asia.load(Ordering::SeqCst), 1);
+}
+
+#[actix_web::test]
+async fn convenience_and_sub_path_endpoints_respect_the_scope() {
+    let fleet = fleet();
+    let app = common::init_app(fleet.app_state.clone()).await;
+    let get = |key: &str, uri: &str| {
+        test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("authorization", format!("Bearer {key}")))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, get(SLOT_ONLY, "/slot")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&app, get(SLOT_ONLY, "/blockhash")).await;
+    assert_eq!(
+        violation(resp).await,
+        "API key may not use method 'getLatestBlockhash'"
+    );
+    let resp = test::call_service(&app, get(EU_READER, "/slot?location=asia")).await;
+    violation(resp).await;
+
+    let resp = test::call_service(&app, get(SLOT_ONLY, "/upstream/health")).await;
+    assert_eq!(
+        violation(resp).await,
+        "API key may not use /upstream sub-paths"
+    );
+}
@@ -0,0 +1,250 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::AppState;
+use the_solana_api::config::Settings;
+use the_solana_api::key_usage::{self, KeyUsageTable, OTHER_KEY, USAGE_HOURS};
+
+const HOUR: u64 = 3600;
+
+#[actix_web::test]
+async fn key_ids_are_digests_of_the_key() {
+    let id = key_usage::key_id("customer-a-secret");
+    assert_eq!(id.len(), 16);
+    assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(id, key_usage::key_id("customer-a-secret"));
+    assert_ne!(id, key_usage::key_id("customer-b-secret"));
+}
+
+#[actix_web::test]
+async fn usage_is_split_into_hours() {
+    let mut table = KeyUsageTable::default();
+    let start = 1_000 * HOUR;
+    table.record("a", "getSlot", true, 10, 100, start);
+    table.record("a", "getSlot", false, 10, 0, start + 60);
+    table.record("a", "getBalance", true, 20, 50, start + HOUR);
+
+    let report = table.usage("a", start + HOUR).unwrap();
+    assert_eq!(report.total.requests, 3);
+    assert_eq!(report.total.errors, 1);
+    assert_eq!(report.total.request_bytes, 40);
+    assert_eq!(report.total.response_bytes, 150);
+    assert_eq!(report.total.methods["getSlot"], 2);
+    assert_eq!(report.total.methods["getBalance"], 1);
+    assert_eq!(report.first_seen_unix_ms, start * 1000);
+    assert_eq!(report.last_seen_unix_ms, (start + HOUR) * 1000);
+
+    assert_eq!(report.hours.len(), 2);
+    assert_eq!(report.hours[0].hour_start_unix_ms, start * 1000);
+    assert_eq!(report.hours[0].usage.requests, 2);
+    assert_eq!(report.hours[0].usage.error_rate, 0.5);
+    assert_eq!(report.hours[1].usage.methods["getBalance"], 1);
+
+    // The first hour leaves the window; the totals keep it.
+    let later = start + USAGE_HOURS as u64 * HOUR;
+    let report = table.usage("a", later).unwrap();
+    assert_eq!(report.hours.len(), 1);
+    assert_eq!(report.total.requests, 3);
+    assert!(table.usage("b", later).is_none());
+}
+
+#[actix_web::test]
+async fn idle_keys_are_evicted_after_the_retention_period() {
+    let mut table = KeyUsageTable::new(100, Duration::from_secs(HOUR), 100);
+    table.record("idle", "getSlot", true, 0, 0, 0);
+    table.record("busy", "getSlot", true, 0, 0, 0);
+    table.record("busy", "getSlot", true, 0, 0, HOUR);
+
+    assert_eq!(table.evict_idle(HOUR + 1), 1);
+    assert!(table.usage("idle", HOUR + 1).is_none());
+    assert!(table.usage("busy", HOUR + 1).is_some());
+
+    // Recording sweeps on its own.
+    table.record("other", "getSlot", true, 0, 0, 3 * HOUR);
+    assert_eq!(table.len(), 1);
+}
+
+#[actix_web::test]
+async fn a_full_table_drops_the_least_recently_seen_key() {
+    let mut table = KeyUsageTable::new(2, Duration::from_secs(10 * HOUR), 100);
+    table.record("a", "getSlot", true, 0, 0, 10);
+    table.record("b", "getSlot", true, 0, 0, 20);
+    table.record("a", "getSlot", true, 0, 0, 30);
+    table.record("c", "getSlot", true, 0, 0, 40);
+
+    assert_eq!(table.len(), 2);
+    assert!(table.usage("b", 40).is_none());
+    assert!(table.usage("a", 40).is_some());
+    assert!(table.usage("c", 40).is_some());
+}
+
+#[actix_web::test]
+async fn keys_past_the_label_cap_share_a_series() {
+    let mut table = KeyUsageTable::new(100, Duration::from_secs(HOUR), 1);
+    table.record("a", "getSlot", true, 1, 1, 0);
+    table.record("b", "getSlot", false, 2, 2, 0);
+    table.record("c", "getSlot", true, 3, 3, 0);
+
+    let totals = table.metric_totals();
+    assert_eq!(totals.len(), 2);
+    assert_eq!(totals[0].0, "a");
+    assert_eq!(totals[0].1.requests, 1);
+    assert_eq!(totals[1].0, OTHER_KEY);
+    assert_eq!(totals[1].1.requests, 2);
+    assert_eq!(totals[1].1.errors, 1);
+    assert_eq!(totals[1].1.request_bytes, 5);
+}
+
+fn node() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|body: web::Json<Value>| async move {
+                // Lets a test pick which calls fail.
+                let status = if body["params"] == json!(["fail"]) {
+                    StatusCode::INTERNAL_SERVER_ERROR
+                } else {
+                    StatusCode::OK
+                };
+                HttpResponse::build(status).json(json!({
+                    "jsonrpc": "2.0",
+                    "id": body["id"],
+                    "result": 1
+                }))
+            }),
+        );
+    })
+}
+
+fn call(method: &str, params: Value) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params}))
+}
+
+#[actix_web::test]
+async fn tracks_each_key_separately_and_reports_it_to_admins() {
+    let node = node();
+    let state = AppState::from_settings(
+        common::registry(vec![node.validator("primary", "frankfurt")]),
+        Settings::builder()
+            .admin_token("admin-secret".into())
+            .metrics_enabled(true)
+            .key_metrics_max_keys(1)
+            .max_retries(0)
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    // Key A: steady successful reads, sent as a bearer token.
+    for _ in 0..3 {
+        let req = call("getSlot", json!([]))
+            .insert_header(("authorization", "Bearer key-a-secret"))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+    }
+    // Key B: a mixed batch through `?api_key=`, half of it failing.
+    for params in [json!(["fail"]), json!(["pubkey"])] {
+        let req = call("getBalance", params)
+            .uri("/?api_key=key-b-secret")
+            .to_request();
+        test::call_service(&app, req).await;
+    }
+    // No key: not tracked.
+    test::call_service(&app, call("getSlot", json!([])).to_request()).await;
+    // A header auth would refuse: not tracked either, whatever the query says.
+    let req = call("getSlot", json!([]))
+        .uri("/?api_key=key-c-secret")
+        .insert_header(("authorization", "Basic a2V5LWMtc2VjcmV0"))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let usage = |id: String| {
+        test::TestRequest::get()
+            .uri(&format!("/admin/keys/{id}/usage"))
+            .insert_header(("authorization", "Bearer admin-secret"))
+            .to_request()
+    };
+
+    let body: Value =
+        test::call_and_read_body_json(&app, usage(key_usage::key_id("key-a-secret"))).await;
+    assert_eq!(body["key_id"], key_usage::key_id("key-a-secret"));
+    assert_eq!(body["total"]["requests"], 3);
+    assert_eq!(body["total"]["errors"], 0);
+    assert_eq!(body["total"]["methods"], json!({"getSlot": 3}));
+    assert!(body["total"]["response_bytes"].as_u64().unwrap() > 0);
+    assert_eq!(body["hours"].as_array().unwrap().len(), 1);
+    assert_eq!(body["hours"][0]["requests"], 3);
+    assert!(!body.to_string().contains("key-a-secret"));
+
+    let body: Value =
+        test::call_and_read_body_json(&app, usage(key_usage::key_id("key-b-secret"))).await;
+    assert_eq!(body["total"]["requests"], 2);
+    assert_eq!(body["total"]["errors"], 1);
+    assert_eq!(body["total"]["error_rate"], 0.5);
+    assert_eq!(body["total"]["methods"], json!({"getBalance": 2}));
+
+    for id in ["unknown".to_string(), key_usage::key_id("key-c-secret")] {
+        let resp = test::call_service(&app, usage(id)).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/admin/keys/{}/usage",
+            key_usage::key_id("key-a-secret")
+        ))
+        .insert_header(("authorization", "Bearer key-a-secret"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // With a cap of one labeled key, key B falls into `other`.
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    let body = std::str::from_utf8(&body).unwrap();
+    let id_a = key_usage::key_id("key-a-secret");
+    assert!(body.contains(&format!(
+        "solana_api_key_requests_total{{key_id=\"{id_a}\"}} 3"
+    )));
+    assert!(body.contains("solana_api_key_requests_total{key_id=\"other\"} 2"));
+    assert!(body.contains("solana_api_key_errors_total{key_id=\"other\"} 1"));
+    assert!(!body.contains(&key_usage::key_id("key-b-secret")));
+}
+
+#[actix_web::test]
+async fn stats_reset_clears_key_usage() {
+    let node = node();
+    let state = AppState::from_settings(
+        common::registry(vec![node.validator("primary", "frankfurt")]),
+        Settings::builder()
+            .admin_token("admin-secret".into())
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state.clone()).await;
+    let req = call("getSlot", json!([]))
+        .insert_header(("authorization", "Bearer key-a-secret"))
+        .to_request();
+    test::call_service(&app, req).await;
+    assert!(
+        state
+            .stats()
+            .key_usage(&key_usage::key_id("key-a-secret"))
+            .is_some()
+    );
+
+    state.stats().reset();
+    assert!(
+        state
+            .stats()
+            .key_usage(&key_usage::key_id("key-a-secret"))
+            .is_none()
+    );
+}
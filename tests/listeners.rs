@@ -0,0 +1,153 @@
+mod common;
+
+use std::net::TcpListener;
+
+use actix_web::http::StatusCode;
+use actix_web::{App, HttpServer, web};
+use url::Url;
+
+use the_solana_api::auth::{ApiKey, ApiKeys};
+use the_solana_api::listeners::{self, Listener};
+use the_solana_api::{AppState, Settings, Validator, ValidatorRegistry, routes};
+
+const ALICE: &str = "alice-secret-key";
+
+fn state() -> AppState {
+    let registry = ValidatorRegistry::new(vec![Validator::new(
+        "node".into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:9/").unwrap(),
+    )])
+    .unwrap();
+    AppState::from_settings(
+        registry,
+        Settings::builder()
+            .api_keys(ApiKeys::new(vec![
+                ApiKey::new("alice", &common::sha256(ALICE), false).unwrap(),
+            ]))
+            .api_keys_public(vec!["/health".to_string()])
+            .build()
+            .unwrap(),
+    )
+}
+
+#[actix_web::test]
+async fn auth_is_enforced_per_listener() {
+    let sockets = listeners::bind_all(&[
+        Listener {
+            address: "127.0.0.1:0".to_string(),
+            tls: false,
+            auth: true,
+        },
+        Listener {
+            address: "127.0.0.1:0".to_string(),
+            tls: false,
+            auth: false,
+        },
+    ])
+    .unwrap();
+    let addrs: Vec<_> = sockets
+        .iter()
+        .map(|(_, socket)| socket.local_addr().unwrap())
+        .collect();
+    let state = state().with_open_listeners([addrs[1]]);
+
+    let mut server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(state.clone()))
+            .configure(routes::configure)
+    })
+    .workers(1);
+    for (_, socket) in sockets {
+        server = server.listen(socket).unwrap();
+    }
+    let server = tokio::spawn(server.run());
+
+    let client = awc::Client::default();
+    let get = |addr| client.get(format!("http://{addr}/validators")).send();
+    assert_eq!(
+        get(addrs[0]).await.unwrap().status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(get(addrs[1]).await.unwrap().status(), StatusCode::OK);
+
+    let authorized = client
+        .get(format!("http://{}/validators", addrs[0]))
+        .insert_header(("authorization", format!("Bearer {ALICE}")))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(authorized.status(), StatusCode::OK);
+    server.abort();
+}
+
+#[actix_web::test]
+async fn a_listener_that_cannot_bind_names_its_address() {
+    let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = taken.local_addr().unwrap().to_string();
+    let err = listeners::bind_all(&[
+        Listener {
+            address: "127.0.0.1:0".to_string(),
+            tls: false,
+            auth: true,
+        },
+        Listener {
+            address: address.clone(),
+            tls: false,
+            auth: true,
+        },
+    ])
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with(&format!("cannot listen on {address}: ")),
+        "{err}"
+    );
+
+    let err = listeners::bind_all(&[Listener {
+        address: "not-an-address".to_string(),
+        tls: false,
+        auth: true,
+    }])
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("cannot listen on not-an-address: "),
+        "{err}"
+    );
+}
+
+#[test]
+fn spells_out_address_shorthands() {
+    for (raw, expected) in [
+        ("8080", "0.0.0.0:8080"),
+        (":8080", "0.0.0.0:8080"),
+        ("::1", "[::1]:80"),
+        ("[::]", "[::]:80"),
+        ("[::1]:8443", "[::1]:8443"),
+        ("localhost", "localhost:80"),
+        ("10.0.0.5", "10.0.0.5:80"),
+        ("10.0.0.5:8080", "10.0.0.5:8080"),
+    ] {
+        assert_eq!(
+            listeners::normalize_address(raw).as_deref(),
+            Ok(expected),
+            "{raw}"
+        );
+    }
+
+    for (raw, attempted) in [
+        ("99999", "0.0.0.0:99999"),
+        (":", ":"),
+        ("localhost:http", "localhost:http"),
+        ("[::1", "[::1"),
+        ("bad_host", "bad_host:80"),
+        ("10.0.0.5:80:80", "10.0.0.5:80:80"),
+    ] {
+        assert_eq!(
+            listeners::normalize_address(raw),
+            Err(attempted.to_string()),
+            "{raw}"
+        );
+    }
+}
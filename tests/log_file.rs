@@ -0,0 +1,98 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use serde_json::Value;
+use tracing::info;
+use tracing_subscriber::layer::SubscriberExt;
+
+use the_solana_api::log_file::{self, Rotation, SizeRotatingFile};
+use the_solana_api::telemetry;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("solana-api-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn size_rotation_keeps_a_bounded_number_of_files() {
+    let dir = scratch_dir("size");
+    let path = dir.join("access.log");
+    let mut file = SizeRotatingFile::open(path.clone(), 20, 2).unwrap();
+    for line in [
+        "line-1 ....\n",
+        "line-2 ....\n",
+        "line-3 ....\n",
+        "line-4 ....\n",
+    ] {
+        file.write_all(line.as_bytes()).unwrap();
+    }
+    file.flush().unwrap();
+
+    let read = |suffix: &str| fs::read_to_string(dir.join(format!("access.log{suffix}"))).ok();
+    assert_eq!(read("").as_deref(), Some("line-4 ....\n"));
+    assert_eq!(read(".1").as_deref(), Some("line-3 ....\n"));
+    assert_eq!(read(".2").as_deref(), Some("line-2 ....\n"));
+    assert_eq!(read(".3"), None);
+}
+
+#[test]
+fn size_rotation_resumes_from_the_existing_file_size() {
+    let dir = scratch_dir("resume");
+    let path = dir.join("access.log");
+    fs::write(&path, "0123456789012345\n").unwrap();
+
+    let mut file = SizeRotatingFile::open(path.clone(), 20, 0).unwrap();
+    file.write_all(b"next\n").unwrap();
+    file.flush().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "next\n");
+    assert_eq!(
+        fs::read_to_string(dir.join("access.log.1")).unwrap(),
+        "0123456789012345\n"
+    );
+}
+
+#[test]
+fn missing_directories_are_reported() {
+    let dir = scratch_dir("missing");
+    let path = dir.join("nope").join("access.log");
+    let err = log_file::open(&path, Rotation::Daily, 1024, 7)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("does not exist"), "{err}");
+
+    let err = log_file::open(&dir, Rotation::Size, 1024, 7).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::IsADirectory);
+}
+
+#[test]
+fn access_log_file_gets_only_access_events_and_is_flushed_by_the_guard() {
+    let dir = scratch_dir("layer");
+    let path = dir.join("access.log");
+    let file = log_file::open(&path, Rotation::Never, 1024, 0).unwrap();
+    let (writer, guard) = tracing_appender::non_blocking(file);
+    let subscriber = tracing_subscriber::registry().with(telemetry::access_log_layer(writer));
+
+    tracing::subscriber::with_default(subscriber, || {
+        info!(target: "access_log", status = 200, validator = "primary", "access");
+        info!("starting server");
+    });
+    drop(guard);
+
+    let contents = fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = contents.lines().collect();
+    assert_eq!(lines.len(), 1, "{contents}");
+    let line: Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(line["target"], "access_log");
+    assert_eq!(line["status"], 200);
+    assert_eq!(line["validator"], "primary");
+}
+
+#[test]
+fn parses_rotation_names() {
+    assert_eq!("Daily".parse::<Rotation>(), Ok(Rotation::Daily));
+    assert_eq!("size".parse::<Rotation>(), Ok(Rotation::Size));
+    assert!("weekly".parse::<Rotation>().is_err());
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::AppState;
+use the_solana_api::jsonrpc;
+use the_solana_api::stats::ValidatorStats;
+
+fn upstream(hits: Arc<AtomicUsize>, response: Value) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let hits = hits.clone();
+        let response = response.clone();
+        cfg.route(
+            "/",
+            web::post().to(move || {
+                let hits = hits.clone();
+                let response = response.clone();
+                async move {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    HttpResponse::Ok().json(response)
+                }
+            }),
+        );
+    })
+}
+
+fn not_found() -> Value {
+    json!({"jsonrpc": "2.0", "id": 1, "error": {"code": -32601, "message": "Method not found"}})
+}
+
+fn call(method: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": []}))
+        .to_request()
+}
+
+#[actix_web::test]
+async fn detects_unavailable_methods() {
+    assert!(jsonrpc::is_method_unavailable(
+        not_found().to_string().as_bytes()
+    ));
+    assert!(jsonrpc::is_method_unavailable(
+        br#"{"error":{"code":-32000,"message":"Method getBlock is disabled on this node"}}"#
+    ));
+    assert!(!jsonrpc::is_method_unavailable(
+        br#"{"error":{"code":-32000,"message":"Blockhash not found"}}"#
+    ));
+    assert!(!jsonrpc::is_method_unavailable(br#"{"result":1}"#));
+}
+
+#[actix_web::test]
+async fn capability_misses_are_remembered() {
+    let stats = ValidatorStats::default();
+    assert!(stats.supports("getBlock"));
+    stats.mark_unsupported("getBlock");
+    assert!(!stats.supports("getBlock"));
+    assert!(stats.supports("getSlot"));
+    assert_eq!(stats.unsupported_methods(), ["getBlock"]);
+}
+
+#[actix_web::test]
+async fn retries_at_a_validator_that_serves_the_method() {
+    let light_hits = Arc::new(AtomicUsize::new(0));
+    let full_hits = Arc::new(AtomicUsize::new(0));
+    let light = upstream(light_hits.clone(), not_found());
+    let full = upstream(
+        full_hits.clone(),
+        json!({"jsonrpc": "2.0", "id": 1, "result": {"blockHeight": 1}}),
+    );
+    let state = AppState::new(common::registry(vec![
+        light.validator("light", "lab"),
+        full.validator("full", "lab"),
+    ]));
+    let app = common::init_app(state).await;
+
+    for _ in 0..10 {
+        let body: Value = test::call_and_read_body_json(&app, call("getBlock")).await;
+        assert_eq!(body["result"]["blockHeight"], 1);
+    }
+
+    // Once the miss is recorded the light node is only tried last.
+    assert!(light_hits.load(Ordering::SeqCst) <= 1);
+    assert_eq!(full_hits.load(Ordering::SeqCst), 10);
+}
+
+#[actix_web::test]
+async fn returns_the_upstream_error_when_no_validator_serves_the_method() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let first = upstream(hits.clone(), not_found());
+    let second = upstream(hits.clone(), not_found());
+    let state = AppState::new(common::registry(vec![
+        first.validator("first", "lab"),
+        second.validator("second", "lab"),
+    ]));
+    let app = common::init_app(state).await;
+
+    let body: Value = test::call_and_read_body_json(&app, call("getBlock")).await;
+    assert_eq!(body["error"]["code"], -32601);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    let stats: Value =
+        test::call_and_read_body_json(&app, test::TestRequest::get().uri("/stats").to_request())
+            .await;
+    for validator in stats["validators"].as_array().unwrap() {
+        assert_eq!(validator["unsupported_methods"], json!(["getBlock"]));
+    }
+}
+
+#[actix_web::test]
+async fn non_idempotent_methods_are_not_retried() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let first = upstream(hits.clone(), not_found());
+    let second = upstream(hits.clone(), not_found());
+    let state = AppState::new(common::registry(vec![
+        first.validator("first", "lab"),
+        second.validator("second", "lab"),
+    ]));
+    let app = common::init_app(state).await;
+
+    let body: Value = test::call_and_read_body_json(&app, call("sendTransaction")).await;
+    assert_eq!(body["error"]["code"], -32601);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[actix_web::test]
+async fn no_retry_header_returns_the_first_answer() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let first = upstream(hits.clone(), not_found());
+    let second = upstream(hits.clone(), not_found());
+    let state = AppState::new(common::registry(vec![
+        first.validator("first", "lab"),
+        second.validator("second", "lab"),
+    ]));
+    let app = common::init_app(state).await;
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .insert_header(("X-No-Retry", "true"))
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getBlock", "params": []}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["error"]["code"], -32601);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::jsonrpc::RpcMethod;
+use the_solana_api::rate_limit::{MethodRateLimiter, MethodRateLimits};
+use the_solana_api::{AppState, Settings};
+
+fn limits(raw: Value) -> MethodRateLimits {
+    MethodRateLimits::from_json(&raw.to_string()).expect("limits")
+}
+
+fn single(method: &str) -> RpcMethod {
+    RpcMethod::Single(method.into())
+}
+
+#[actix_web::test]
+async fn parses_and_validates_limits() {
+    let parsed = limits(json!([
+        {"method": "getProgramAccounts", "per_second": 2},
+        {"method": "get*", "per_second": 50.5, "burst": 100},
+    ]));
+    assert_eq!(parsed.limits().len(), 2);
+    assert_eq!(parsed.best_match("getProgramAccounts"), Some(0));
+    assert_eq!(parsed.best_match("getBalance"), Some(1));
+    assert_eq!(parsed.best_match("sendTransaction"), None);
+
+    assert!(MethodRateLimits::from_json(r#"[{"method":"x","per_second":0}]"#).is_err());
+    assert!(MethodRateLimits::from_json(r#"[{"method":"x","per_second":1,"burst":0}]"#).is_err());
+    assert!(MethodRateLimits::from_json(r#"[{"per_second":1}]"#).is_err());
+}
+
+#[actix_web::test]
+async fn buckets_are_per_client_and_per_method() {
+    let limiter = MethodRateLimiter::new(
+        limits(json!([{"method": "getProgramAccounts", "per_second": 2}])),
+        100,
+    );
+
+    let first = limiter
+        .check("a", &single("getProgramAccounts"))
+        .unwrap()
+        .unwrap();
+    assert_eq!((first.limit, first.remaining), (2, 1));
+    let second = limiter
+        .check("a", &single("getProgramAccounts"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(second.remaining, 0);
+    let refused = limiter
+        .check("a", &single("getProgramAccounts"))
+        .unwrap_err();
+    let retry_after = refused.retry_after.unwrap();
+    assert!(retry_after.as_millis() > 0 && retry_after.as_millis() <= 500);
+
+    assert!(limiter.check("b", &single("getProgramAccounts")).is_ok());
+    for _ in 0..100 {
+        assert_eq!(limiter.check("a", &single("getSlot")), Ok(None));
+    }
+}
+
+#[actix_web::test]
+async fn batches_count_every_contained_call() {
+    let limiter = MethodRateLimiter::new(
+        limits(json!([{"method": "getProgramAccounts", "per_second": 1, "burst": 2}])),
+        100,
+    );
+    let batch = |count: usize| {
+        RpcMethod::Batch(
+            std::iter::repeat_n(Some("getProgramAccounts".to_string()), count)
+                .chain([Some("getSlot".to_string())])
+                .collect(),
+        )
+    };
+
+    assert!(limiter.check("a", &batch(3)).is_err());
+    // The rejected batch did not consume anything.
+    assert!(limiter.check("a", &batch(2)).is_ok());
+    assert!(limiter.check("a", &single("getProgramAccounts")).is_err());
+}
+
+#[actix_web::test]
+async fn client_state_is_bounded() {
+    let limiter = MethodRateLimiter::new(limits(json!([{"method": "*", "per_second": 1}])), 3);
+
+    for client in ["a", "b", "c", "d", "e"] {
+        limiter.check(client, &single("getSlot")).unwrap();
+    }
+    assert_eq!(limiter.tracked_clients(), 3);
+
+    // "a" was evicted, so it starts over with a full bucket.
+    assert!(limiter.check("a", &single("getSlot")).is_ok());
+    assert!(limiter.check("e", &single("getSlot")).is_err());
+}
+
+#[actix_web::test]
+async fn concurrent_callers_never_exceed_the_burst() {
+    let limiter = Arc::new(MethodRateLimiter::new(
+        limits(json!([{"method": "getProgramAccounts", "per_second": 0.001, "burst": 50}])),
+        100,
+    ));
+    let allowed = Arc::new(AtomicUsize::new(0));
+
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let limiter = limiter.clone();
+            let allowed = allowed.clone();
+            thread::spawn(move || {
+                for _ in 0..25 {
+                    if limiter
+                        .check("shared", &single("getProgramAccounts"))
+                        .is_ok()
+                    {
+                        allowed.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(allowed.load(Ordering::SeqCst), 50);
+}
+
+#[actix_web::test]
+async fn rejects_with_429_retry_after_and_json_rpc_body() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = {
+        let hits = hits.clone();
+        common::spawn_upstream(move |cfg| {
+            let hits = hits.clone();
+            cfg.route(
+                "/",
+                web::post().to(move || {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    async {
+                        HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": []}))
+                    }
+                }),
+            );
+        })
+    };
+    let settings = Settings::builder()
+        .method_rate_limits(limits(
+            json!([{"method": "getProgramAccounts", "per_second": 1}]),
+        ))
+        .build()
+        .unwrap();
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("primary", "lab")]),
+        settings,
+    );
+    let app = common::init_app(state).await;
+
+    let call = |id: u64| {
+        test::TestRequest::post()
+            .uri("/")
+            .peer_addr("10.1.2.3:5000".parse().unwrap())
+            .set_json(json!({"jsonrpc": "2.0", "id": id, "method": "getProgramAccounts", "params": ["prog"]}))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, call(1)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = test::call_service(&app, call(2)).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(resp.headers().get("retry-after").unwrap(), "1");
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["id"], 2);
+    assert_eq!(body["error"]["code"], -32012);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::AppState;
+use the_solana_api::jsonrpc::RpcMethod;
+use the_solana_api::stats::StatsStore;
+
+fn single(method: &str) -> RpcMethod {
+    RpcMethod::Single(method.into())
+}
+
+#[actix_web::test]
+async fn counts_and_latency_are_tracked_per_method_and_validator() {
+    let stats = StatsStore::new();
+    stats.record_call(&single("getSlot"), "a", Duration::from_millis(10), true);
+    stats.record_call(&single("getSlot"), "a", Duration::from_millis(30), false);
+    stats.record_call(&single("getSlot"), "b", Duration::from_millis(5), true);
+
+    let snapshots = stats.method_snapshots();
+    assert_eq!(snapshots.len(), 2);
+    assert_eq!(snapshots[0].validator, "a");
+    assert_eq!(snapshots[0].requests, 2);
+    assert_eq!(snapshots[0].errors, 1);
+    assert_eq!(snapshots[0].mean_latency_ms, 20.0);
+    assert_eq!(snapshots[0].max_latency_ms, 30.0);
+    assert_eq!(snapshots[1].validator, "b");
+}
+
+#[actix_web::test]
+async fn cardinality_is_bounded() {
+    let stats = StatsStore::with_method_cap(2);
+    for method in ["getSlot", "getBalance", "madeUp1", "madeUp2"] {
+        stats.record_call(&single(method), "a", Duration::ZERO, true);
+    }
+    stats.record_call(&RpcMethod::Invalid, "a", Duration::ZERO, false);
+    stats.record_call(&RpcMethod::Batch(vec![]), "a", Duration::ZERO, true);
+
+    let snapshots = stats.method_snapshots();
+    let names: Vec<&str> = snapshots.iter().map(|s| s.method.as_str()).collect();
+    assert_eq!(names, ["getBalance", "getSlot", "other"]);
+    assert_eq!(snapshots[2].requests, 4);
+}
+
+#[actix_web::test]
+async fn survives_concurrent_updates() {
+    let stats = Arc::new(StatsStore::new());
+    let methods = ["getSlot", "getBalance", "sendTransaction", "getAccountInfo"];
+
+    let tasks: Vec<_> = (0..32)
+        .map(|task| {
+            let stats = stats.clone();
+            tokio::spawn(async move {
+                for call in 0..500 {
+                    let method = single(methods[(task + call) % methods.len()]);
+                    let validator = if call % 2 == 0 { "a" } else { "b" };
+                    stats.record_call(&method, validator, Duration::from_micros(100), true);
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let snapshots = stats.method_snapshots();
+    assert_eq!(snapshots.len(), 8);
+    assert_eq!(snapshots.iter().map(|s| s.requests).sum::<u64>(), 32 * 500);
+    assert!(snapshots.iter().all(|s| s.mean_latency_ms == 0.1));
+}
+
+#[actix_web::test]
+async fn proxied_calls_show_up_in_stats() {
+    let upstream = common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 1}))
+            }),
+        );
+    });
+    let state = AppState::new(common::registry(vec![upstream.validator("primary", "lab")]));
+    let app = common::init_app(state).await;
+
+    for _ in 0..3 {
+        let req = test::TestRequest::post()
+            .uri("/")
+            .set_json(
+                json!({"jsonrpc": "2.0", "id": 1, "method": "sendTransaction", "params": ["tx"]}),
+            )
+            .to_request();
+        test::call_service(&app, req).await;
+    }
+
+    let stats: Value =
+        test::call_and_read_body_json(&app, test::TestRequest::get().uri("/stats").to_request())
+            .await;
+    let methods = stats["methods"].as_array().unwrap();
+    assert_eq!(methods.len(), 1);
+    assert_eq!(methods[0]["method"], "sendTransaction");
+    assert_eq!(methods[0]["validator"], "primary");
+    assert_eq!(methods[0]["requests"], 3);
+    assert_eq!(methods[0]["errors"], 0);
+}
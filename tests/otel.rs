@@ -0,0 +1,145 @@
+#![cfg(feature = "otel")]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use actix_web::{HttpRequest, HttpResponse, test, web};
+use opentelemetry::Value;
+use opentelemetry::trace::{SpanKind, Status, TracerProvider as _};
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+use serde_json::json;
+use tracing_subscriber::layer::SubscriberExt;
+use url::Url;
+
+use the_solana_api::{AppState, Validator};
+
+const INBOUND: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+fn exporter() -> (
+    InMemorySpanExporter,
+    SdkTracerProvider,
+    tracing::subscriber::DefaultGuard,
+) {
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+    let guard = tracing::subscriber::set_default(subscriber);
+    (exporter, provider, guard)
+}
+
+fn span<'a>(spans: &'a [SpanData], name: &str) -> &'a SpanData {
+    spans
+        .iter()
+        .find(|span| span.name == name)
+        .unwrap_or_else(|| panic!("no '{name}' span in {spans:?}"))
+}
+
+fn attribute(span: &SpanData, key: &str) -> Option<Value> {
+    span.attributes
+        .iter()
+        .find(|kv| kv.key.as_str() == key)
+        .map(|kv| kv.value.clone())
+}
+
+fn get_slot() -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/")
+        .insert_header(("traceparent", INBOUND))
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+}
+
+#[actix_web::test]
+async fn exports_request_and_upstream_client_spans() {
+    let seen = Arc::new(Mutex::new(None::<String>));
+    let node = common::spawn_upstream({
+        let seen = seen.clone();
+        move |cfg| {
+            let seen = seen.clone();
+            cfg.route(
+                "/",
+                web::post().to(move |req: HttpRequest| {
+                    let seen = seen.clone();
+                    async move {
+                        *seen.lock().unwrap() = req
+                            .headers()
+                            .get("traceparent")
+                            .and_then(|value| value.to_str().ok())
+                            .map(str::to_string);
+                        HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 7}))
+                    }
+                }),
+            );
+        }
+    });
+    let (exporter, provider, _guard) = exporter();
+    let app = common::init_app(AppState::new(common::registry(vec![
+        node.validator("primary", "frankfurt"),
+    ])))
+    .await;
+
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert!(resp.status().is_success());
+    let echoed = resp
+        .headers()
+        .get("traceparent")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    provider.force_flush().unwrap();
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let request = span(&spans, "request");
+    let upstream = span(&spans, "upstream");
+
+    // The inbound traceparent is the remote parent of the request span.
+    let trace_id = request.span_context.trace_id().to_string();
+    assert_eq!(trace_id, "0af7651916cd43dd8448eb211c80319c");
+    assert_eq!(request.parent_span_id.to_string(), "b7ad6b7169203331");
+    assert!(request.parent_span_is_remote);
+    assert_eq!(
+        echoed,
+        format!("00-{trace_id}-{}-01", request.span_context.span_id())
+    );
+
+    assert_eq!(upstream.span_kind, SpanKind::Client);
+    assert_eq!(upstream.parent_span_id, request.span_context.span_id());
+    assert_eq!(
+        attribute(upstream, "validator"),
+        Some(Value::from("primary"))
+    );
+    assert_eq!(attribute(upstream, "host"), Some(Value::from("127.0.0.1")));
+    assert_eq!(attribute(upstream, "attempt"), Some(Value::I64(0)));
+    assert_eq!(attribute(upstream, "status"), Some(Value::I64(200)));
+
+    // The validator sees the attempt span as its parent.
+    assert_eq!(
+        seen.lock().unwrap().as_deref(),
+        Some(format!("00-{trace_id}-{}-01", upstream.span_context.span_id()).as_str())
+    );
+}
+
+#[actix_web::test]
+async fn marks_failed_attempts_as_errors() {
+    let (exporter, provider, _guard) = exporter();
+    let app = common::init_app(AppState::new(common::registry(vec![Validator::new(
+        "down".into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:1/").unwrap(),
+    )])))
+    .await;
+
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert!(resp.status().is_server_error());
+    provider.force_flush().unwrap();
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let upstream = span(&spans, "upstream");
+    assert_eq!(attribute(upstream, "validator"), Some(Value::from("down")));
+    assert_eq!(attribute(upstream, "status"), None);
+    assert!(matches!(upstream.status, Status::Error { .. }));
+}
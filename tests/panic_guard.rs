@@ -0,0 +1,70 @@
+mod common;
+
+use actix_web::body::to_bytes;
+use actix_web::http::StatusCode;
+use actix_web::middleware::from_fn;
+use actix_web::{App, HttpResponse, test, web};
+use serde_json::Value;
+use url::Url;
+
+use the_solana_api::config::Settings;
+use the_solana_api::{AppState, Validator, panic_guard, request_id, routes};
+
+fn state() -> AppState {
+    let validator = Validator::new(
+        "primary".into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:1/").unwrap(),
+    );
+    AppState::from_settings(
+        common::registry(vec![validator]),
+        Settings::builder().metrics_enabled(true).build().unwrap(),
+    )
+}
+
+async fn explode() -> HttpResponse {
+    panic!("deliberate test panic");
+}
+
+#[actix_web::test]
+async fn panicking_handlers_answer_a_json_500() {
+    let state = state();
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(panic_guard::catch))
+            .wrap(from_fn(request_id::assign))
+            .app_data(web::Data::new(state.clone()))
+            .route("/explode", web::get().to(explode))
+            .configure(routes::configure),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/explode")
+        .insert_header(("x-request-id", "boom-1"))
+        .to_request();
+    // Rendered by actix itself, as a server would.
+    let err = test::try_call_service(&app, req).await.err().unwrap();
+    let resp = err.error_response();
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(resp.headers().get("x-request-id").unwrap(), "boom-1");
+    let bytes = to_bytes(resp.into_body()).await.unwrap();
+    let body: Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["code"], "internal_panic");
+    assert_eq!(body["request_id"], "boom-1");
+    // The panic message stays in the logs.
+    assert!(!body["error"].as_str().unwrap().contains("deliberate"));
+    assert_eq!(state.stats().panics(), 1);
+
+    // The worker keeps serving.
+    let req = test::TestRequest::get().uri("/health").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    assert!(
+        std::str::from_utf8(&body)
+            .unwrap()
+            .contains("solana_api_panics_total 1")
+    );
+}
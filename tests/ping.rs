@@ -0,0 +1,175 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use actix_web::http::{StatusCode, header};
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+use url::Url;
+
+use the_solana_api::config::Settings;
+use the_solana_api::fleet::MAX_PINGS;
+use the_solana_api::{AppState, Validator};
+
+fn node(calls: Arc<AtomicUsize>, delay: Duration) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let calls = calls.clone();
+        cfg.route(
+            "/",
+            web::post().to(move |body: web::Json<Value>| {
+                let calls = calls.clone();
+                async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(delay).await;
+                    assert_eq!(body["method"], "getVersion");
+                    HttpResponse::Ok().json(json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": {"solana-core": "2.0.0"}
+                    }))
+                }
+            }),
+        );
+    })
+}
+
+fn settings() -> Settings {
+    Settings {
+        // Plenty for the tests that are not about the limit.
+        ping_rate_limit: 1000.0,
+        fleet_node_timeout: Duration::from_millis(200),
+        ..Settings::default()
+    }
+}
+
+async fn ping(state: AppState, uri: &str) -> (StatusCode, Value) {
+    let app = common::init_app(state).await;
+    let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+#[actix_web::test]
+async fn pings_three_times_by_default() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let node = node(calls.clone(), Duration::ZERO);
+    let state = AppState::from_settings(
+        common::registry(vec![node.validator("primary", "frankfurt")]),
+        settings(),
+    );
+
+    let (status, body) = ping(state, "/validators/primary/ping").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    assert_eq!(body["validator"], "primary");
+    assert_eq!(body["location"], "frankfurt");
+    assert_eq!(body["count"], 3);
+    assert_eq!(body["successes"], 3);
+    assert_eq!(body["probes"].as_array().unwrap().len(), 3);
+    let (min, avg, max) = (
+        body["min_ms"].as_f64().unwrap(),
+        body["avg_ms"].as_f64().unwrap(),
+        body["max_ms"].as_f64().unwrap(),
+    );
+    assert!(min <= avg && avg <= max);
+    assert!(body["probes"][0].get("error").is_none());
+}
+
+#[actix_web::test]
+async fn caps_the_probe_count() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let node = node(calls.clone(), Duration::ZERO);
+    let state = AppState::from_settings(
+        common::registry(vec![node.validator("primary", "frankfurt")]),
+        settings(),
+    );
+
+    let (_, body) = ping(state.clone(), "/validators/primary/ping?count=500").await;
+    assert_eq!(body["count"], MAX_PINGS);
+    assert_eq!(calls.load(Ordering::SeqCst), MAX_PINGS);
+
+    let (_, body) = ping(state, "/validators/primary/ping?count=0").await;
+    assert_eq!(body["count"], 1);
+}
+
+#[actix_web::test]
+async fn reports_timeouts_and_unreachable_validators() {
+    let slow = node(Arc::default(), Duration::from_millis(500));
+    let state = AppState::from_settings(
+        common::registry(vec![
+            slow.validator("slow", "frankfurt"),
+            Validator::new(
+                "down".into(),
+                "lab".into(),
+                Url::parse("http://127.0.0.1:1/").unwrap(),
+            ),
+        ]),
+        settings(),
+    );
+
+    let (status, body) = ping(state.clone(), "/validators/slow/ping?count=1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["successes"], 0);
+    assert_eq!(body["probes"][0]["error"], "timed out");
+    assert!(body["probes"][0]["latency_ms"].as_f64().unwrap() < 500.0);
+    assert_eq!(body["min_ms"], Value::Null);
+    assert_eq!(body["avg_ms"], Value::Null);
+
+    let (_, body) = ping(state, "/validators/down/ping?count=2").await;
+    assert_eq!(body["successes"], 0);
+    assert_eq!(body["probes"].as_array().unwrap().len(), 2);
+    assert!(body["probes"][1]["error"].is_string());
+}
+
+#[actix_web::test]
+async fn unknown_validators_are_not_found() {
+    let node = node(Arc::default(), Duration::ZERO);
+    let state = AppState::from_settings(
+        common::registry(vec![node.validator("primary", "frankfurt")]),
+        settings(),
+    );
+    let (status, body) = ping(state, "/validators/nope/ping").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"], "unknown validator 'nope'");
+}
+
+#[actix_web::test]
+async fn limits_pings_per_client() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let node = node(calls.clone(), Duration::ZERO);
+    let state = AppState::from_settings(
+        common::registry(vec![node.validator("primary", "frankfurt")]),
+        Settings {
+            ping_rate_limit: 0.1,
+            ..settings()
+        },
+    );
+    let app = common::init_app(state).await;
+    let ping = |ip: &str| {
+        test::TestRequest::get()
+            .uri("/validators/primary/ping?count=1")
+            .peer_addr(format!("{ip}:4000").parse().unwrap())
+            .to_request()
+    };
+
+    assert!(
+        test::call_service(&app, ping("10.0.0.1"))
+            .await
+            .status()
+            .is_success()
+    );
+    let resp = test::call_service(&app, ping("10.0.0.1")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(resp.headers().contains_key(header::RETRY_AFTER));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // Another client has its own budget.
+    assert!(
+        test::call_service(&app, ping("10.0.0.2"))
+            .await
+            .status()
+            .is_success()
+    );
+}
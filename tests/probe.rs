@@ -0,0 +1,100 @@
+mod common;
+
+use std::process::Command;
+use std::time::Duration;
+
+use actix_web::{HttpResponse, web};
+use serde_json::{Value, json};
+use url::Url;
+
+use the_solana_api::fleet::{self, HealthStatus};
+use the_solana_api::{AppState, Validator};
+
+fn node(version: &'static str, slot: u64) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        cfg.route(
+            "/",
+            web::post().to(move || async move {
+                HttpResponse::Ok().json(json!([
+                    {"jsonrpc": "2.0", "id": 1, "result": "ok"},
+                    {"jsonrpc": "2.0", "id": 2, "result": slot},
+                    {"jsonrpc": "2.0", "id": 3, "result": {"solana-core": version, "feature-set": 1}},
+                ]))
+            }),
+        );
+    })
+}
+
+fn unreachable(name: &str) -> Validator {
+    Validator::new(
+        name.into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:1/").unwrap(),
+    )
+}
+
+#[actix_web::test]
+async fn reports_version_slot_and_totals() {
+    let alpha = node("2.0.14", 1_000);
+    let state = AppState::new(common::registry(vec![
+        alpha.validator("alpha", "lab"),
+        unreachable("down"),
+    ]));
+
+    let report = fleet::check_health(
+        &state.build_client(),
+        state.registry().validators(),
+        4,
+        Duration::from_secs(2),
+    )
+    .await;
+    assert_eq!(report.summary.total, 2);
+    assert_eq!(report.summary.ok, 1);
+    assert_eq!(report.summary.unreachable, 1);
+
+    let alpha = &report.validators[0];
+    assert_eq!(alpha.status, HealthStatus::Ok);
+    assert_eq!(alpha.version.as_deref(), Some("2.0.14"));
+    assert_eq!(alpha.slot, Some(1_000));
+    assert!(alpha.reachable());
+    assert!(!report.validators[1].reachable());
+
+    let table = report.render();
+    assert!(table.starts_with("NAME   LOCATION  STATUS"), "{table}");
+    assert!(table.contains("2.0.14"), "{table}");
+    assert!(
+        table.ends_with("2 validators: 1 ok, 0 behind, 0 unhealthy, 0 timeout, 1 unreachable\n"),
+        "{table}"
+    );
+}
+
+#[actix_web::test]
+async fn the_binary_exits_non_zero_below_the_threshold() {
+    let alpha = node("2.0.14", 1_000);
+    let registry = format!(
+        "name,rpc_url,location\nalpha,{},lab\ndown,http://127.0.0.1:1/,lab\n",
+        alpha.url()
+    );
+    let run = |extra: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_TheSolanaApi"));
+        command
+            .args(["probe", "--validators-inline", &registry])
+            .args(["--node-timeout-ms", "1000"])
+            .args(extra)
+            .env_clear();
+        // The mock upstream is served from this thread's runtime.
+        tokio::task::spawn_blocking(move || command.output().unwrap())
+    };
+
+    let output = run(&["--json"]).await.unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["summary"]["ok"], 1);
+    assert_eq!(report["validators"][0]["version"], "2.0.14");
+    assert_eq!(report["validators"][1]["status"], "unreachable");
+
+    let output = run(&["--min-healthy", "1"]).await.unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("alpha"), "{stdout}");
+}
@@ -0,0 +1,189 @@
+mod common;
+
+use actix_web::{HttpResponse, http::StatusCode, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::AppState;
+use the_solana_api::config::Settings;
+use the_solana_api::quorum::{self, QuorumVote};
+
+fn upstream(slot: u64, value: u64) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        cfg.route(
+            "/",
+            web::post().to(move || async move {
+                HttpResponse::Ok().json(json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "result": {"context": {"slot": slot}, "value": value}
+                }))
+            }),
+        );
+    })
+}
+
+fn balance(query: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri(&format!("/?{query}"))
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getBalance", "params": ["A"]}))
+        .to_request()
+}
+
+fn vote(validator: &str, digest: Option<&str>) -> QuorumVote {
+    QuorumVote {
+        validator: validator.into(),
+        digest: digest.map(String::from),
+        error: None,
+    }
+}
+
+fn header(response: &actix_web::dev::ServiceResponse, name: &str) -> String {
+    response
+        .headers()
+        .get(name)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[actix_web::test]
+async fn digests_ignore_the_context_slot() {
+    let a = quorum::result_digest(br#"{"id":1,"result":{"context":{"slot":10},"value":5}}"#);
+    let b = quorum::result_digest(br#"{"id":2,"result":{"value":5,"context":{"slot":11}}}"#);
+    let c = quorum::result_digest(br#"{"id":1,"result":{"context":{"slot":10},"value":6}}"#);
+    assert!(a.is_some());
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+    assert_eq!(quorum::result_digest(br#"{"error":{"code":-1}}"#), None);
+}
+
+#[actix_web::test]
+async fn agreement_needs_enough_matching_votes() {
+    let votes = [
+        vote("a", Some("x")),
+        vote("b", Some("y")),
+        vote("c", Some("x")),
+    ];
+    assert_eq!(quorum::agreement(&votes, 2), Some(0));
+    assert_eq!(quorum::agreement(&votes, 3), None);
+
+    let failed = [vote("a", None), vote("b", None)];
+    assert_eq!(quorum::agreement(&failed, 2), None);
+}
+
+#[actix_web::test]
+async fn agreeing_validators_answer() {
+    let first = upstream(100, 5);
+    let second = upstream(101, 5);
+    let state = AppState::new(common::registry(vec![
+        first.validator("first", "lab"),
+        second.validator("second", "lab"),
+    ]));
+    let app = common::init_app(state).await;
+
+    let response = test::call_service(&app, balance("quorum=2")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut participants: Vec<String> = header(&response, "x-quorum-validators")
+        .split(", ")
+        .map(String::from)
+        .collect();
+    participants.sort();
+    assert_eq!(participants, ["first", "second"]);
+
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["result"]["value"], 5);
+}
+
+#[actix_web::test]
+async fn conflicting_results_are_rejected() {
+    let first = upstream(100, 5);
+    let second = upstream(100, 6);
+    let state = AppState::new(common::registry(vec![
+        first.validator("first", "lab"),
+        second.validator("second", "lab"),
+    ]));
+    let app = common::init_app(state).await;
+
+    let response = test::call_service(&app, balance("quorum=2")).await;
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    assert!(header(&response, "x-quorum-validators").contains("first"));
+
+    let body: Value = test::read_body_json(response).await;
+    let votes = body["votes"].as_array().unwrap();
+    assert_eq!(votes.len(), 2);
+    assert_ne!(votes[0]["digest"], votes[1]["digest"]);
+    let mut validators: Vec<&str> = votes
+        .iter()
+        .map(|vote| vote["validator"].as_str().unwrap())
+        .collect();
+    validators.sort();
+    assert_eq!(validators, ["first", "second"]);
+}
+
+#[actix_web::test]
+async fn unreachable_validators_count_as_disagreement() {
+    let first = upstream(100, 5);
+    let state = AppState::new(common::registry(vec![
+        first.validator("first", "lab"),
+        the_solana_api::Validator::new(
+            "down".into(),
+            "lab".into(),
+            url::Url::parse("http://127.0.0.1:1/").unwrap(),
+        ),
+    ]));
+    let app = common::init_app(state).await;
+
+    let response = test::call_service(&app, balance("quorum=2")).await;
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    let body: Value = test::read_body_json(response).await;
+    let down = body["votes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|vote| vote["validator"] == "down")
+        .unwrap();
+    assert!(down["digest"].is_null());
+    assert!(down["error"].is_string());
+}
+
+#[actix_web::test]
+async fn quorum_is_validated() {
+    let first = upstream(100, 5);
+    let second = upstream(100, 5);
+    let settings = Settings::builder()
+        .quorum_max_validators(2)
+        .build()
+        .unwrap();
+    let state = AppState::from_settings(
+        common::registry(vec![
+            first.validator("first", "lab"),
+            second.validator("second", "lab"),
+        ]),
+        settings,
+    );
+    let app = common::init_app(state).await;
+
+    for query in ["quorum=0", "quorum=3", "quorum=2&validator=first"] {
+        let response = test::call_service(&app, balance(query)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{query}");
+    }
+
+    let send = test::TestRequest::post()
+        .uri("/?quorum=2")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "sendTransaction", "params": ["tx"]}))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, send).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    let batch = test::TestRequest::post()
+        .uri("/?quorum=2")
+        .set_json(json!([{"jsonrpc": "2.0", "id": 1, "method": "getSlot"}]))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, batch).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
@@ -0,0 +1,201 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::dev::ServiceResponse;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::auth::{ApiKey, ApiKeys};
+use the_solana_api::rate_limit::{KeyQuota, KeyRateLimit, MethodRateLimits, RateLimitHeaders};
+use the_solana_api::{AppState, Settings};
+
+const KEY: &str = "alice-key";
+
+fn limit(requests_per_second: f64, burst: u32) -> KeyRateLimit {
+    KeyRateLimit::new(requests_per_second, Some(burst)).unwrap()
+}
+
+fn setup(settings: Settings) -> (common::MockUpstream, AppState) {
+    let upstream = common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 7}))
+            }),
+        );
+    });
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("node", "lab")]),
+        settings,
+    );
+    (upstream, state)
+}
+
+fn rpc(method: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/")
+        .peer_addr("198.51.100.7:4000".parse().unwrap())
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": method}))
+}
+
+fn header<'a>(resp: &'a ServiceResponse, name: &str) -> Option<&'a str> {
+    resp.headers()
+        .get(name)
+        .map(|value| value.to_str().unwrap())
+}
+
+#[actix_web::test]
+async fn remaining_counts_down_on_consecutive_requests() {
+    let (_upstream, state) = setup(
+        Settings::builder()
+            .client_rate_limit(limit(0.01, 4))
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    for remaining in ["3", "2", "1", "0"] {
+        let resp = test::call_service(&app, rpc("getSlot").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(header(&resp, "x-ratelimit-limit"), Some("4"));
+        assert_eq!(header(&resp, "x-ratelimit-remaining"), Some(remaining));
+    }
+    let resp = test::call_service(&app, rpc("getSlot").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&resp, "x-ratelimit-remaining"), Some("0"));
+}
+
+#[actix_web::test]
+async fn the_tightest_bucket_is_reported() {
+    let hash = common::sha256(KEY);
+    let (_upstream, state) = setup(
+        Settings::builder()
+            .api_keys(ApiKeys::new(vec![
+                ApiKey::new("alice", &hash, false)
+                    .unwrap()
+                    .with_limit(Some(limit(0.01, 10))),
+            ]))
+            .global_rate_limit(limit(0.01, 5))
+            .method_rate_limits(
+                MethodRateLimits::from_json(
+                    r#"[{"method": "getProgramAccounts", "per_second": 0.01, "burst": 2}]"#,
+                )
+                .unwrap(),
+            )
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+    let keyed = |method: &str| {
+        rpc(method)
+            .insert_header(("authorization", format!("Bearer {KEY}")))
+            .to_request()
+    };
+
+    // The global bucket (5) is tighter than the key's (10).
+    let resp = test::call_service(&app, keyed("getSlot")).await;
+    assert_eq!(header(&resp, "x-ratelimit-limit"), Some("5"));
+    assert_eq!(header(&resp, "x-ratelimit-remaining"), Some("4"));
+
+    // The method bucket (2) is tighter still.
+    let resp = test::call_service(&app, keyed("getProgramAccounts")).await;
+    assert_eq!(header(&resp, "x-ratelimit-limit"), Some("2"));
+    assert_eq!(header(&resp, "x-ratelimit-remaining"), Some("1"));
+    test::call_service(&app, keyed("getProgramAccounts")).await;
+
+    let resp = test::call_service(&app, keyed("getProgramAccounts")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&resp, "x-ratelimit-limit"), Some("2"));
+    assert_eq!(header(&resp, "x-ratelimit-remaining"), Some("0"));
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"]["code"], -32012);
+
+    // Other methods are back to the global bucket, which the refusal spent
+    // a token of.
+    let resp = test::call_service(&app, keyed("getSlot")).await;
+    assert_eq!(header(&resp, "x-ratelimit-remaining"), Some("0"));
+}
+
+#[actix_web::test]
+async fn draft_headers_can_be_chosen() {
+    let (_upstream, state) = setup(
+        Settings::builder()
+            .client_rate_limit(limit(1.0, 3))
+            .rate_limit_headers(RateLimitHeaders::Draft)
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    for remaining in ["2", "1"] {
+        let resp = test::call_service(&app, rpc("getSlot").to_request()).await;
+        assert_eq!(header(&resp, "ratelimit-limit"), Some("3"));
+        assert_eq!(header(&resp, "ratelimit-remaining"), Some(remaining));
+        assert!(header(&resp, "ratelimit-reset").is_some());
+        assert_eq!(header(&resp, "x-ratelimit-limit"), None);
+    }
+}
+
+#[actix_web::test]
+async fn headers_can_be_turned_off() {
+    let (_upstream, state) = setup(
+        Settings::builder()
+            .client_rate_limit(limit(0.01, 1))
+            .rate_limit_headers(RateLimitHeaders::Off)
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    let resp = test::call_service(&app, rpc("getSlot").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&app, rpc("getSlot").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(header(&resp, "retry-after").is_some());
+    for name in ["x-ratelimit-limit", "ratelimit-limit"] {
+        assert_eq!(header(&resp, name), None);
+    }
+}
+
+#[actix_web::test]
+async fn unlimited_routes_carry_no_headers() {
+    let (_upstream, state) = setup(Settings::default());
+    let app = common::init_app(state).await;
+
+    let resp = test::call_service(&app, rpc("getSlot").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(header(&resp, "x-ratelimit-limit"), None);
+}
+
+#[actix_web::test]
+async fn pings_report_their_own_bucket() {
+    let (_upstream, state) = setup(Settings::default());
+    let app = common::init_app(state).await;
+
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri("/validators/node/ping?count=1")
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(header(&resp, "x-ratelimit-limit"), Some("1"));
+    assert_eq!(header(&resp, "x-ratelimit-remaining"), Some("0"));
+}
+
+#[actix_web::test]
+async fn refusals_and_lower_counts_are_tighter() {
+    let quota = |remaining, reset_secs, refused: bool| KeyQuota {
+        limit: 10,
+        remaining,
+        reset: Duration::from_secs(reset_secs),
+        retry_after: refused.then_some(Duration::from_secs(1)),
+    };
+    assert!(quota(0, 1, true).is_tighter_than(&quota(0, 9, false)));
+    assert!(quota(1, 1, false).is_tighter_than(&quota(2, 9, false)));
+    assert!(quota(1, 9, false).is_tighter_than(&quota(1, 1, false)));
+    assert!(!quota(1, 1, false).is_tighter_than(&quota(1, 1, false)));
+}
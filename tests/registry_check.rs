@@ -0,0 +1,131 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+use serde_json::Value;
+
+use the_solana_api::registry_check::RegistryReport;
+
+fn registry_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "solana-api-validate-{name}-{}.csv",
+        std::process::id()
+    ));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn reports_parsed_rows_and_warnings() {
+    let report = RegistryReport::from_reader(
+        "name,ip,rpc_port,location,protocol\n\
+         alpha,10.0.0.1,8899,frankfurt,http\n\
+         ,10.0.0.2,8899,frankfurt,http\n\
+         gamma,10.0.0.1,8899,,http\n"
+            .as_bytes(),
+    );
+    assert!(report.valid);
+    assert!(report.errors.is_empty());
+    let rows: Vec<_> = report
+        .validators
+        .iter()
+        .map(|v| (v.row, v.name.as_str(), v.location.as_str(), v.url.as_str()))
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            (2, "alpha", "frankfurt", "http://10.0.0.1:8899/"),
+            (3, "frankfurt-2", "frankfurt", "http://10.0.0.2:8899/"),
+            (4, "gamma", "unspecified", "http://10.0.0.1:8899/"),
+        ]
+    );
+    let warnings: Vec<_> = report
+        .warnings
+        .iter()
+        .map(|w| (w.row, w.message.as_str()))
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            (Some(3), "no name; generated 'frankfurt-2'"),
+            (
+                Some(4),
+                "duplicate url http://10.0.0.1:8899/ (first on row 2)"
+            ),
+            (Some(4), "no location; using 'unspecified'"),
+        ]
+    );
+
+    let table = report.render();
+    assert!(table.starts_with("ROW  NAME"), "{table}");
+    assert!(table.contains("warning: row 3: no name; generated 'frankfurt-2'"));
+    assert!(
+        table.ends_with("3 validators, 3 warnings, 0 errors\n"),
+        "{table}"
+    );
+}
+
+#[test]
+fn reports_every_bad_row() {
+    let report = RegistryReport::from_reader(
+        "name,ip,rpc_port,location,protocol\n\
+         alpha,10.0.0.1,8899,frankfurt,http\n\
+         beta,10.0.0.2,8899,frankfurt,gopher\n\
+         Alpha,10.0.0.3,8899,paris,http\n\
+         delta,10.0.0.4,not-a-port,paris,http\n"
+            .as_bytes(),
+    );
+    assert!(!report.valid);
+    assert_eq!(report.validators.len(), 2);
+    let rows: Vec<_> = report.errors.iter().map(|e| e.row).collect();
+    assert_eq!(rows, [Some(3), Some(4), Some(5)]);
+    assert_eq!(
+        report.errors[0].message,
+        "protocol: unsupported protocol 'gopher'"
+    );
+    assert_eq!(
+        report.errors[1].message,
+        "name: duplicate validator name 'Alpha' (first on row 2)"
+    );
+    assert!(report.render().contains("error: row 4: name: duplicate"));
+
+    let empty = RegistryReport::from_reader("name,ip,rpc_port,location,protocol\n".as_bytes());
+    assert!(!empty.valid);
+    assert_eq!(empty.errors[0].message, "no validators configured");
+
+    let missing = RegistryReport::from_file(&PathBuf::from("/nonexistent/validators.csv"));
+    assert!(!missing.valid);
+    assert_eq!(missing.errors[0].row, None);
+}
+
+#[test]
+fn the_binary_exits_non_zero_on_errors() {
+    let good = registry_file("good", "name,ip,location\nalpha,10.0.0.1,lab\n");
+    let bad = registry_file("bad", "name,ip,location,protocol\nalpha,10.0.0.1,lab,ftp\n");
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_TheSolanaApi"))
+            .arg("validate")
+            .args(args)
+            // Nothing else from the server's configuration is needed.
+            .env_clear()
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["--file", good.to_str().unwrap()]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("http://10.0.0.1:8899/"), "{stdout}");
+
+    let output = run(&["--json", "--file", bad.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["valid"], false);
+    assert_eq!(report["errors"][0]["row"], 2);
+    assert_eq!(
+        report["errors"][0]["message"],
+        "protocol: unsupported protocol 'ftp'"
+    );
+
+    std::fs::remove_file(&good).unwrap();
+    std::fs::remove_file(&bad).unwrap();
+}
@@ -0,0 +1,82 @@
+use std::error::Error;
+use std::path::Path;
+
+use actix_web::ResponseError;
+use actix_web::http::StatusCode;
+use serde_json::json;
+
+use the_solana_api::ValidatorRegistry;
+use the_solana_api::errors::{AppError, ErrorFormat};
+
+fn load(csv: &str) -> AppError {
+    AppError::from(ValidatorRegistry::from_reader(csv.as_bytes()).unwrap_err())
+}
+
+#[actix_web::test]
+async fn invalid_rows_are_pinpointed() {
+    let err =
+        load("name,rpc_url,location\nok-1,http://10.0.0.1:8899,paris\nbad-1,http://[::1,paris\n");
+    assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let body = err.body(ErrorFormat::Plain);
+    assert_eq!(body["code"], "invalid_registry");
+    assert_eq!(body["details"], json!({"row": 3, "field": "rpc_url"}));
+    assert!(
+        body["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid validator registry: invalid record at row 3: invalid url")
+    );
+
+    let body = err.body(ErrorFormat::JsonRpc);
+    assert_eq!(body["error"]["code"], -32019);
+    assert_eq!(body["error"]["data"]["row"], 3);
+    assert_eq!(body["error"]["data"]["field"], "rpc_url");
+}
+
+#[actix_web::test]
+async fn the_source_chain_reaches_the_root_cause() {
+    let err = load("name,rpc_url\nbad-1,not a url\n");
+    let registry = err.source().expect("registry error");
+    assert!(registry.to_string().starts_with("invalid record at row 2"));
+    let root = registry.source().expect("parse error");
+    assert!(root.is::<url::ParseError>());
+}
+
+#[actix_web::test]
+async fn duplicates_and_empty_files_are_unprocessable() {
+    let err = load("name,ip\ntwin,10.0.0.1\ntwin,10.0.0.2\n");
+    assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = err.body(ErrorFormat::Plain);
+    assert_eq!(
+        body["error"],
+        "invalid validator registry: duplicate validator name 'twin'"
+    );
+    assert_eq!(body["details"], json!({"field": "name"}));
+
+    let err = load("name,ip\n");
+    assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        err.body(ErrorFormat::Plain)["error"],
+        "invalid validator registry: the file lists no validators"
+    );
+}
+
+#[actix_web::test]
+async fn malformed_csv_reports_the_line() {
+    let err = load("name,ip,location\nok-1,10.0.0.1,paris\nshort-row\n");
+    assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(err.body(ErrorFormat::Plain)["details"]["row"], 3);
+}
+
+#[actix_web::test]
+async fn unreadable_files_are_internal_errors() {
+    let err = AppError::from(
+        ValidatorRegistry::from_csv(Path::new("/nonexistent/validators.csv")).unwrap_err(),
+    );
+    assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body = err.body(ErrorFormat::Plain);
+    assert_eq!(body["code"], "internal_error");
+    assert!(body.get("details").is_none());
+    assert!(err.source().unwrap().source().is_some());
+}
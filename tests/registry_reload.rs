@@ -0,0 +1,245 @@
+mod common;
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::{AppState, Settings, ValidatorRegistry, registry_store};
+
+const ADMIN: &str = "operator-token";
+
+// Removed when dropped.
+struct RegistryFile(PathBuf);
+
+impl RegistryFile {
+    fn new(name: &str, contents: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "solana-api-registry-{name}-{}.csv",
+            std::process::id()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        Self(path)
+    }
+
+    fn write(&self, contents: &str) {
+        std::fs::write(&self.0, contents).unwrap();
+    }
+}
+
+impl Drop for RegistryFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+// Answers `getSlot` with its own name, after `delay`.
+fn named_upstream(name: &'static str, delay: Duration) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        cfg.route(
+            "/",
+            web::post().to(move || async move {
+                tokio::time::sleep(delay).await;
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": name}))
+            }),
+        );
+    })
+}
+
+fn csv(rows: &[(&str, &common::MockUpstream, &str)]) -> String {
+    let mut csv = "name,rpc_url,location\n".to_string();
+    for (name, node, location) in rows {
+        csv.push_str(&format!("{name},{},{location}\n", node.url()));
+    }
+    csv
+}
+
+fn state(file: &RegistryFile) -> AppState {
+    AppState::from_settings(
+        ValidatorRegistry::from_csv(&file.0).unwrap(),
+        Settings::builder()
+            .validators_csv(file.0.clone())
+            .admin_token(ADMIN.into())
+            .validators_reload_interval(Duration::from_millis(20))
+            .build()
+            .unwrap(),
+    )
+}
+
+fn reload(bearer: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/admin/reload")
+        .insert_header(("authorization", format!("Bearer {bearer}")))
+        .to_request()
+}
+
+fn get_slot(uri: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri(uri)
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+        .to_request()
+}
+
+#[actix_web::test]
+async fn swaps_in_the_registry_as_the_file_now_reads() {
+    let (fra, tyo, ams) = (
+        named_upstream("fra", Duration::ZERO),
+        named_upstream("tyo", Duration::ZERO),
+        named_upstream("ams", Duration::ZERO),
+    );
+    let file = RegistryFile::new(
+        "swap",
+        &csv(&[("fra", &fra, "frankfurt"), ("tyo", &tyo, "tokyo")]),
+    );
+    let app = common::init_app(state(&file)).await;
+    let resp = test::call_service(&app, get_slot("/?server=ams")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    file.write(&csv(&[("fra", &fra, "europe"), ("ams", &ams, "amsterdam")]));
+    let resp = test::call_service(&app, reload("guess")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = test::call_service(&app, reload(ADMIN)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(
+        body,
+        json!({"validators": 2, "added": ["ams"], "changed": ["fra"], "removed": ["tyo"]})
+    );
+
+    let resp = test::call_service(&app, get_slot("/?server=ams")).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["result"], "ams");
+    let resp = test::call_service(&app, get_slot("/?server=tyo")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn a_broken_file_leaves_the_registry_in_use() {
+    let fra = named_upstream("fra", Duration::ZERO);
+    let file = RegistryFile::new("broken", &csv(&[("fra", &fra, "frankfurt")]));
+    let app = common::init_app(state(&file)).await;
+
+    file.write(
+        "name,rpc_url,location\n\
+         fra,not a url,frankfurt\n\
+         tyo,http://127.0.0.1:9/,tokyo\n\
+         tyo,http://127.0.0.1:10/,tokyo\n",
+    );
+    let resp = test::call_service(&app, reload(ADMIN)).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "invalid_registry");
+    let rows: Vec<_> = body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["row"].clone())
+        .collect();
+    assert_eq!(rows, [json!(2), json!(4)]);
+
+    let resp = test::call_service(&app, get_slot("/?server=fra")).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["result"], "fra");
+
+    std::fs::remove_file(&file.0).unwrap();
+    let resp = test::call_service(&app, reload(ADMIN)).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let resp = test::call_service(&app, get_slot("/")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn requests_in_flight_finish_on_a_removed_validator() {
+    let slow = named_upstream("slow", Duration::from_millis(300));
+    let fra = named_upstream("fra", Duration::ZERO);
+    let file = RegistryFile::new("in-flight", &csv(&[("slow", &slow, "lab")]));
+    let state = state(&file);
+    let app = common::init_app(state.clone()).await;
+
+    let in_flight = async {
+        let resp = test::call_service(&app, get_slot("/")).await;
+        let status = resp.status();
+        let body: Value = test::read_body_json(resp).await;
+        (status, body)
+    };
+    let swap = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        file.write(&csv(&[("fra", &fra, "lab")]));
+        state.registry_store().reload(state.settings()).unwrap()
+    };
+    let ((status, body), changes) = tokio::join!(in_flight, swap);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["result"], "slow");
+    assert_eq!(changes.removed, ["slow"]);
+
+    let resp = test::call_service(&app, get_slot("/")).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["result"], "fra");
+}
+
+#[actix_web::test]
+async fn edits_are_read_once_the_file_settles() {
+    let (fra, ams) = (
+        named_upstream("fra", Duration::ZERO),
+        named_upstream("ams", Duration::ZERO),
+    );
+    let file = RegistryFile::new("settle", &csv(&[("fra", &fra, "frankfurt")]));
+    let state = state(&file);
+    let store = state.registry_store();
+    assert!(store.reload_if_changed(state.settings()).is_none());
+
+    // Seen changed first, read on the next check if it held still.
+    file.write(&csv(&[
+        ("fra", &fra, "frankfurt"),
+        ("ams", &ams, "amsterdam"),
+    ]));
+    assert!(store.reload_if_changed(state.settings()).is_none());
+    assert_eq!(state.registry().validators().len(), 1);
+    let changes = store.reload_if_changed(state.settings()).unwrap().unwrap();
+    assert_eq!(changes.added, ["ams"]);
+    assert!(store.reload_if_changed(state.settings()).is_none());
+
+    let app = common::init_app(state.clone()).await;
+    registry_store::spawn_watch(state.clone());
+    file.write(&csv(&[("ams", &ams, "amsterdam")]));
+    let started = Instant::now();
+    while state.registry().get_by_name("fra").is_some() {
+        assert!(started.elapsed() < Duration::from_secs(5), "never reloaded");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let resp = test::call_service(&app, get_slot("/")).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["result"], "ams");
+}
+
+#[actix_web::test]
+async fn a_broken_write_never_replaces_the_registry() {
+    let (fra, ams) = (
+        named_upstream("fra", Duration::ZERO),
+        named_upstream("ams", Duration::ZERO),
+    );
+    let file = RegistryFile::new("broken-write", &csv(&[("fra", &fra, "frankfurt")]));
+    let state = state(&file);
+    let store = state.registry_store();
+
+    // Half written.
+    file.write("name,rpc_url,location\nams,http");
+    assert!(store.reload_if_changed(state.settings()).is_none());
+    assert!(store.reload_if_changed(state.settings()).unwrap().is_err());
+    // Not retried until the file changes again.
+    assert!(store.reload_if_changed(state.settings()).is_none());
+    assert!(state.registry().get_by_name("fra").is_some());
+
+    // Missing while being replaced.
+    std::fs::remove_file(&file.0).unwrap();
+    assert!(store.reload_if_changed(state.settings()).is_none());
+    assert!(store.reload_if_changed(state.settings()).is_none());
+    assert!(state.registry().get_by_name("fra").is_some());
+
+    file.write(&csv(&[("ams", &ams, "amsterdam")]));
+    assert!(store.reload_if_changed(state.settings()).is_none());
+    assert!(store.reload_if_changed(state.settings()).unwrap().is_ok());
+    assert!(state.registry().get_by_name("fra").is_none());
+}
@@ -0,0 +1,208 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use actix_web::{HttpRequest, HttpResponse, http::StatusCode, test, web};
+use serde_json::{Value, json};
+
+use url::Url;
+
+use the_solana_api::config::Settings;
+use the_solana_api::request_id::RequestId;
+use the_solana_api::{AppState, Validator};
+
+// Remembers the `X-Request-Id` the proxy sent upstream.
+fn upstream(seen: Arc<Mutex<Option<String>>>) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let seen = seen.clone();
+        cfg.route(
+            "/",
+            web::post().to(move |req: HttpRequest| {
+                *seen.lock().unwrap() = req
+                    .headers()
+                    .get("x-request-id")
+                    .map(|value| value.to_str().unwrap().to_string());
+                async { HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 1})) }
+            }),
+        );
+    })
+}
+
+fn registry() -> the_solana_api::ValidatorRegistry {
+    common::registry(vec![Validator::new(
+        "primary".into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:1/").unwrap(),
+    )])
+}
+
+fn get_slot() -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+}
+
+fn echoed(response: &actix_web::dev::ServiceResponse) -> String {
+    response
+        .headers()
+        .get("x-request-id")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[actix_web::test]
+async fn parses_and_generates_ids() {
+    assert_eq!(RequestId::parse(" abc-123 ").unwrap().as_str(), "abc-123");
+    assert_eq!(RequestId::parse("a b"), None);
+    assert_eq!(RequestId::parse("caf\u{e9}"), None);
+    assert_eq!(RequestId::parse(""), None);
+    assert_eq!(
+        RequestId::parse(&"x".repeat(300)).unwrap().as_str().len(),
+        128
+    );
+
+    let id = RequestId::generate();
+    let parts: Vec<&str> = id.as_str().split('-').collect();
+    assert_eq!(
+        parts.iter().map(|part| part.len()).collect::<Vec<_>>(),
+        [8, 4, 4, 4, 12]
+    );
+    assert!(parts[2].starts_with('4'));
+    assert_ne!(RequestId::generate(), id);
+}
+
+#[actix_web::test]
+async fn inbound_ids_are_echoed_and_forwarded() {
+    let seen = Arc::new(Mutex::new(None));
+    let node = upstream(seen.clone());
+    let app = common::init_app(AppState::new(common::registry(vec![
+        node.validator("primary", "lab"),
+    ])))
+    .await;
+
+    let response = test::call_service(
+        &app,
+        get_slot()
+            .insert_header(("x-request-id", "client-42"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(echoed(&response), "client-42");
+    assert_eq!(seen.lock().unwrap().as_deref(), Some("client-42"));
+}
+
+#[actix_web::test]
+async fn invalid_or_missing_ids_are_replaced() {
+    let seen = Arc::new(Mutex::new(None));
+    let node = upstream(seen.clone());
+    let app = common::init_app(AppState::new(common::registry(vec![
+        node.validator("primary", "lab"),
+    ])))
+    .await;
+
+    for request in [
+        get_slot(),
+        get_slot().insert_header(("x-request-id", "has spaces")),
+    ] {
+        let response = test::call_service(&app, request.to_request()).await;
+        let id = echoed(&response);
+        assert_eq!(id.len(), 36);
+        assert_eq!(seen.lock().unwrap().as_deref(), Some(id.as_str()));
+    }
+
+    let response =
+        test::call_service(&app, test::TestRequest::get().uri("/health").to_request()).await;
+    assert_eq!(echoed(&response).len(), 36);
+}
+
+#[actix_web::test]
+async fn error_bodies_carry_the_request_id() {
+    let app = common::init_app(AppState::new(registry())).await;
+    let response = test::call_service(
+        &app,
+        get_slot()
+            .uri("/?server=nowhere")
+            .insert_header(("x-request-id", "trace-me"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(echoed(&response), "trace-me");
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["request_id"], "trace-me");
+    assert!(body["error"].as_str().is_some());
+
+    let settings = Settings::builder().jsonrpc_errors(true).build().unwrap();
+    let app = common::init_app(AppState::from_settings(registry(), settings)).await;
+    let response = test::call_service(
+        &app,
+        get_slot()
+            .uri("/?server=nowhere")
+            .insert_header(("x-request-id", "trace-me"))
+            .to_request(),
+    )
+    .await;
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["error"]["data"]["requestId"], "trace-me");
+}
+
+#[actix_web::test]
+async fn errors_raised_before_the_handler_carry_the_request_id() {
+    let app = common::init_app(AppState::new(registry())).await;
+
+    // Past the default 256 KiB payload limit.
+    let response = test::call_service(
+        &app,
+        test::TestRequest::post()
+            .uri("/")
+            .insert_header(("x-request-id", "too-big"))
+            .insert_header(("content-type", "application/json"))
+            .set_payload(vec![b' '; 300 * 1024])
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(echoed(&response), "too-big");
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["request_id"], "too-big");
+    assert_eq!(body["code"], "payload_too_large");
+    assert!(body["error"].as_str().is_some());
+
+    let response = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri("/validators/primary/ping?count=many")
+            .insert_header(("x-request-id", "bad-query"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["request_id"], "bad-query");
+    assert_eq!(body["code"], "bad_request");
+}
+
+#[actix_web::test]
+async fn errors_raised_before_the_handler_follow_the_error_format() {
+    let settings = Settings::builder()
+        .error_format(the_solana_api::errors::ErrorFormat::JsonRpc)
+        .build()
+        .unwrap();
+    let app = common::init_app(AppState::from_settings(registry(), settings)).await;
+    let response = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri("/validators?stats=perhaps")
+            .insert_header(("x-request-id", "rpc-shaped"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["error"]["code"], -32600);
+    assert_eq!(body["error"]["data"]["requestId"], "rpc-shaped");
+    assert_eq!(body["error"]["data"]["code"], "bad_request");
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::{AppState, Settings};
+
+fn counting_upstream(hits: Arc<AtomicUsize>) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let hits = hits.clone();
+        cfg.route(
+            "/",
+            web::post().to(move |body: web::Json<Value>| {
+                let count = hits.fetch_add(1, Ordering::SeqCst) + 1;
+                async move {
+                    let response = if body["method"] == "getBalance" {
+                        json!({"jsonrpc": "2.0", "id": body["id"], "error": {"code": -32000, "message": "boom"}})
+                    } else {
+                        json!({"jsonrpc": "2.0", "id": body["id"], "result": {"solana-core": format!("1.{count}")}})
+                    };
+                    HttpResponse::Ok().json(response)
+                }
+            }),
+        );
+    })
+}
+
+fn cached_state(upstream: &common::MockUpstream, settings: Settings) -> AppState {
+    AppState::from_settings(
+        common::registry(vec![upstream.validator("primary", "lab")]),
+        settings,
+    )
+}
+
+fn cache_enabled() -> Settings {
+    Settings {
+        response_cache: true,
+        ..Settings::default()
+    }
+}
+
+async fn call(
+    app: &impl actix_web::dev::Service<
+        actix_http::Request,
+        Response = actix_web::dev::ServiceResponse,
+        Error = actix_web::Error,
+    >,
+    id: u64,
+    method: &str,
+    no_cache: bool,
+) -> (StatusCode, Option<String>, Value) {
+    let mut req = test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": id, "method": method}));
+    if no_cache {
+        req = req.insert_header(("cache-control", "no-cache"));
+    }
+    let resp = test::call_service(app, req.to_request()).await;
+    let status = resp.status();
+    let cache = resp
+        .headers()
+        .get("x-cache")
+        .map(|value| value.to_str().unwrap().to_string());
+    (status, cache, test::read_body_json(resp).await)
+}
+
+#[actix_web::test]
+async fn second_identical_request_is_served_from_cache() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = counting_upstream(hits.clone());
+    let app = common::init_app(cached_state(&upstream, cache_enabled())).await;
+
+    let (status, cache, first) = call(&app, 1, "getVersion", false).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cache.as_deref(), Some("MISS"));
+    assert_eq!(first["id"], 1);
+
+    let (status, cache, second) = call(&app, 7, "getVersion", false).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cache.as_deref(), Some("HIT"));
+    assert_eq!(second["id"], 7);
+    assert_eq!(second["result"], first["result"]);
+
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[actix_web::test]
+async fn no_cache_bypasses_and_refreshes_the_entry() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = counting_upstream(hits.clone());
+    let app = common::init_app(cached_state(&upstream, cache_enabled())).await;
+
+    call(&app, 1, "getVersion", false).await;
+    let (_, cache, fresh) = call(&app, 2, "getVersion", true).await;
+    assert_eq!(cache.as_deref(), Some("MISS"));
+    assert_eq!(fresh["result"]["solana-core"], "1.2");
+
+    let (_, cache, cached) = call(&app, 3, "getVersion", false).await;
+    assert_eq!(cache.as_deref(), Some("HIT"));
+    assert_eq!(cached["result"]["solana-core"], "1.2");
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[actix_web::test]
+async fn errors_and_other_methods_are_not_cached() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = counting_upstream(hits.clone());
+    let app = common::init_app(cached_state(&upstream, cache_enabled())).await;
+
+    let settings = Settings {
+        cache_immutable_methods: vec!["getVersion".into(), "getBalance".into()],
+        ..cache_enabled()
+    };
+    let error_app = common::init_app(cached_state(&upstream, settings)).await;
+    call(&error_app, 1, "getBalance", false).await;
+    call(&error_app, 2, "getBalance", false).await;
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    let (_, cache, _) = call(&app, 3, "getAccountInfo", false).await;
+    call(&app, 4, "getAccountInfo", false).await;
+    assert_eq!(cache, None);
+    assert_eq!(hits.load(Ordering::SeqCst), 4);
+}
+
+#[actix_web::test]
+async fn cache_is_disabled_by_default_and_bounded() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = counting_upstream(hits.clone());
+    let app = common::init_app(cached_state(&upstream, Settings::default())).await;
+
+    call(&app, 1, "getVersion", false).await;
+    call(&app, 2, "getVersion", false).await;
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    let settings = Settings {
+        cache_immutable_methods: vec!["getVersion".into(), "getGenesisHash".into()],
+        cache_max_entries: 1,
+        ..cache_enabled()
+    };
+    let state = cached_state(&upstream, settings);
+    let app = common::init_app(state.clone()).await;
+    call(&app, 3, "getVersion", false).await;
+    call(&app, 4, "getGenesisHash", false).await;
+    assert_eq!(state.response_cache().len(), 1);
+}
@@ -0,0 +1,499 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{HttpResponse, http::StatusCode, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::AppState;
+use the_solana_api::config::Settings;
+
+// Answers every call with `result` and remembers the last request body.
+fn upstream(result: Value, seen: Arc<Mutex<Option<Value>>>) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let result = result.clone();
+        let seen = seen.clone();
+        cfg.route(
+            "/",
+            web::post().to(move |body: web::Json<Value>| {
+                let result = result.clone();
+                *seen.lock().unwrap() = Some(body.into_inner());
+                async move {
+                    HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": result}))
+                }
+            }),
+        );
+    })
+}
+
+// A 200 carries a JSON-RPC error; any other status has an empty body.
+fn failing(status: StatusCode) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        cfg.route(
+            "/",
+            web::post().to(move || async move {
+                if status.is_success() {
+                    HttpResponse::Ok().json(json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "error": {"code": -32005, "message": "Node is unhealthy"}
+                    }))
+                } else {
+                    HttpResponse::build(status).finish()
+                }
+            }),
+        );
+    })
+}
+
+async fn get(state: AppState, uri: &str) -> (StatusCode, Value) {
+    let app = common::init_app(state).await;
+    let response = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    let status = response.status();
+    // Extractor rejections come back as plain text.
+    let body = test::read_body(response).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[actix_web::test]
+async fn slot_returns_the_slot_and_validator() {
+    let seen = Arc::new(Mutex::new(None));
+    let node = upstream(json!(12345), seen.clone());
+    let state = AppState::new(common::registry(vec![node.validator("frankfurt-1", "eu")]));
+
+    let (status, body) = get(state, "/slot?location=eu&commitment=processed").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"slot": 12345, "validator": "frankfurt-1"}));
+
+    let sent = seen.lock().unwrap().clone().unwrap();
+    assert_eq!(sent["method"], "getSlot");
+    assert_eq!(sent["params"], json!([{"commitment": "processed"}]));
+}
+
+#[actix_web::test]
+async fn slot_without_commitment_sends_no_config() {
+    let seen = Arc::new(Mutex::new(None));
+    let node = upstream(json!(1), seen.clone());
+    let state = AppState::new(common::registry(vec![node.validator("primary", "lab")]));
+
+    let (status, _) = get(state, "/slot").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(seen.lock().unwrap().clone().unwrap()["params"], json!([]));
+}
+
+#[actix_web::test]
+async fn slot_rejects_bad_selection_and_commitment() {
+    let node = upstream(json!(1), Arc::new(Mutex::new(None)));
+    let registry = || common::registry(vec![node.validator("primary", "lab")]);
+
+    let (status, body) = get(AppState::new(registry()), "/slot?commitment=soon").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("soon"));
+
+    let (status, _) = get(AppState::new(registry()), "/slot?location=mars").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn slot_maps_upstream_failures_to_bad_gateway() {
+    let down = failing(StatusCode::INTERNAL_SERVER_ERROR);
+    let state = AppState::new(common::registry(vec![down.validator("down", "lab")]));
+    let (status, body) = get(state, "/slot").await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert!(body["error"].as_str().unwrap().contains("down"));
+
+    let erroring = failing(StatusCode::OK);
+    let state = AppState::new(common::registry(vec![erroring.validator("sick", "lab")]));
+    let (status, body) = get(state, "/slot").await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert!(
+        body["error"]
+            .as_str()
+            .unwrap()
+            .contains("Node is unhealthy")
+    );
+}
+
+const PUBKEY: &str = "Vote111111111111111111111111111111111111111";
+
+#[actix_web::test]
+async fn balance_returns_lamports_and_sol() {
+    let seen = Arc::new(Mutex::new(None));
+    let node = upstream(
+        json!({"context": {"slot": 321}, "value": 1_500_000_000u64}),
+        seen.clone(),
+    );
+    let state = AppState::new(common::registry(vec![node.validator("primary", "lab")]));
+
+    let (status, body) = get(
+        state,
+        &format!("/balance/{PUBKEY}?server=primary&commitment=confirmed"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({"lamports": 1_500_000_000u64, "sol": 1.5, "slot": 321, "validator": "primary"})
+    );
+
+    let sent = seen.lock().unwrap().clone().unwrap();
+    assert_eq!(sent["method"], "getBalance");
+    assert_eq!(sent["params"], json!([PUBKEY, {"commitment": "confirmed"}]));
+}
+
+#[actix_web::test]
+async fn balance_rejects_invalid_pubkeys_before_going_upstream() {
+    let seen = Arc::new(Mutex::new(None));
+    let node = upstream(json!({"value": 1}), seen.clone());
+    let registry = || common::registry(vec![node.validator("primary", "lab")]);
+
+    for (pubkey, problem) in [
+        ("Vote0111111111111111111111111111111111111111", "'0'"),
+        ("abc", "expected 32"),
+    ] {
+        let (status, body) = get(AppState::new(registry()), &format!("/balance/{pubkey}")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error = body["error"].as_str().unwrap();
+        assert!(error.contains(pubkey) && error.contains(problem), "{error}");
+    }
+    assert!(seen.lock().unwrap().is_none());
+}
+
+#[actix_web::test]
+async fn balance_maps_upstream_errors_to_bad_gateway() {
+    let erroring = failing(StatusCode::OK);
+    let state = AppState::new(common::registry(vec![erroring.validator("sick", "lab")]));
+    let (status, body) = get(state, &format!("/balance/{PUBKEY}")).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert!(
+        body["error"]
+            .as_str()
+            .unwrap()
+            .contains("Node is unhealthy")
+    );
+
+    let malformed = upstream(json!("oops"), Arc::new(Mutex::new(None)));
+    let state = AppState::new(common::registry(vec![malformed.validator("odd", "lab")]));
+    let (status, _) = get(state, &format!("/balance/{PUBKEY}")).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+}
+
+const SIGNATURE: &str =
+    "2Ana1pUpv2ZbMVkwF5FXapYeBEjdxDatLn7nvJkhgTSXbs59SyZSx866bXirPgj8QQVB57uxHJBG1YFvkRbFj4T";
+
+#[actix_web::test]
+async fn transaction_returns_the_result_directly() {
+    let seen = Arc::new(Mutex::new(None));
+    let node = upstream(json!({"slot": 9, "meta": {"fee": 5000}}), seen.clone());
+    let state = AppState::new(common::registry(vec![node.validator("primary", "lab")]));
+    let app = common::init_app(state).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/tx/{SIGNATURE}?encoding=jsonParsed&commitment=confirmed"
+        ))
+        .to_request();
+    let response = test::call_service(&app, req).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("x-validator").unwrap(), "primary");
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body, json!({"slot": 9, "meta": {"fee": 5000}}));
+
+    let sent = seen.lock().unwrap().clone().unwrap();
+    assert_eq!(sent["method"], "getTransaction");
+    assert_eq!(
+        sent["params"],
+        json!([SIGNATURE, {
+            "commitment": "confirmed",
+            "encoding": "jsonParsed",
+            "maxSupportedTransactionVersion": 0
+        }])
+    );
+}
+
+#[actix_web::test]
+async fn transaction_defaults_to_json_encoding() {
+    let seen = Arc::new(Mutex::new(None));
+    let node = upstream(json!({"slot": 9}), seen.clone());
+    let state = AppState::new(common::registry(vec![node.validator("primary", "lab")]));
+
+    let (status, _) = get(state, &format!("/tx/{SIGNATURE}")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        seen.lock().unwrap().clone().unwrap()["params"],
+        json!([SIGNATURE, {"encoding": "json", "maxSupportedTransactionVersion": 0}])
+    );
+}
+
+#[actix_web::test]
+async fn missing_transactions_are_not_found() {
+    let node = upstream(Value::Null, Arc::new(Mutex::new(None)));
+    let state = AppState::new(common::registry(vec![node.validator("primary", "lab")]));
+
+    let (status, body) = get(state, &format!("/tx/{SIGNATURE}")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["error"].as_str().unwrap().contains(SIGNATURE));
+}
+
+#[actix_web::test]
+async fn transaction_rejects_bad_input_before_going_upstream() {
+    let seen = Arc::new(Mutex::new(None));
+    let node = upstream(json!({}), seen.clone());
+    let registry = || common::registry(vec![node.validator("primary", "lab")]);
+
+    for uri in [
+        format!("/tx/{PUBKEY}"),
+        format!("/tx/{SIGNATURE}?encoding=base32"),
+        format!("/tx/{SIGNATURE}?commitment=processed"),
+    ] {
+        let (status, _) = get(AppState::new(registry()), &uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+    }
+    assert!(seen.lock().unwrap().is_none());
+}
+
+#[actix_web::test]
+async fn transaction_prefers_archive_validators() {
+    let archive_seen = Arc::new(Mutex::new(None));
+    let recent_seen = Arc::new(Mutex::new(None));
+    let archive = upstream(json!({"slot": 1}), archive_seen.clone());
+    let recent = upstream(json!({"slot": 1}), recent_seen.clone());
+    let state = AppState::new(common::registry(vec![
+        recent.validator("recent", "lab"),
+        archive.validator("archive", "lab").with_archive(true),
+    ]));
+    let app = common::init_app(state).await;
+
+    for _ in 0..10 {
+        let req = test::TestRequest::get()
+            .uri(&format!("/tx/{SIGNATURE}"))
+            .to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.headers().get("x-validator").unwrap(), "archive");
+    }
+    assert!(recent_seen.lock().unwrap().is_none());
+}
+
+fn account_value(space: u64) -> Value {
+    json!({
+        "context": {"slot": 77},
+        "value": {
+            "data": ["AAAA", "base64"],
+            "executable": false,
+            "lamports": 1,
+            "owner": "11111111111111111111111111111111",
+            "rentEpoch": 0,
+            "space": space
+        }
+    })
+}
+
+#[actix_web::test]
+async fn account_translates_query_params() {
+    let cases = [
+        (
+            "?encoding=base64&dataSlice.offset=8&dataSlice.length=32&commitment=processed",
+            json!({"commitment": "processed", "encoding": "base64", "dataSlice": {"offset": 8, "length": 32}}),
+        ),
+        (
+            "?dataSlice.length=16",
+            json!({"encoding": "base64", "dataSlice": {"offset": 0, "length": 16}}),
+        ),
+        ("?encoding=jsonParsed", json!({"encoding": "jsonParsed"})),
+        // No slice given: binary encodings get the default one.
+        (
+            "",
+            json!({"encoding": "base64", "dataSlice": {"offset": 0, "length": 65536}}),
+        ),
+    ];
+
+    for (query, config) in cases {
+        let seen = Arc::new(Mutex::new(None));
+        let node = upstream(account_value(3), seen.clone());
+        let state = AppState::new(common::registry(vec![node.validator("primary", "lab")]));
+
+        let (status, body) = get(state, &format!("/account/{PUBKEY}{query}")).await;
+        assert_eq!(status, StatusCode::OK, "{query}");
+        assert_eq!(body["validator"], "primary");
+        assert_eq!(body["slot"], 77);
+        assert_eq!(body["value"]["lamports"], 1);
+        assert_eq!(
+            seen.lock().unwrap().clone().unwrap()["params"],
+            json!([PUBKEY, config]),
+            "{query}"
+        );
+    }
+}
+
+#[actix_web::test]
+async fn account_reports_default_slice_truncation() {
+    let seen = Arc::new(Mutex::new(None));
+    let node = upstream(account_value(1_000), seen.clone());
+    let settings = the_solana_api::config::Settings {
+        account_default_slice_bytes: 100,
+        ..Default::default()
+    };
+    let state = AppState::from_settings(
+        common::registry(vec![node.validator("primary", "lab")]),
+        settings,
+    );
+
+    let (status, body) = get(state, &format!("/account/{PUBKEY}")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["truncated"], true);
+}
+
+#[actix_web::test]
+async fn account_rejects_bad_params() {
+    let seen = Arc::new(Mutex::new(None));
+    let node = upstream(account_value(3), seen.clone());
+    let registry = || common::registry(vec![node.validator("primary", "lab")]);
+
+    for query in [
+        "?encoding=hex",
+        "?dataSlice.offset=4",
+        "?encoding=jsonParsed&dataSlice.length=4",
+        "?dataSlice.length=many",
+    ] {
+        let (status, _) = get(
+            AppState::new(registry()),
+            &format!("/account/{PUBKEY}{query}"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{query}");
+    }
+    let (status, _) = get(AppState::new(registry()), "/account/nope").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(seen.lock().unwrap().is_none());
+}
+
+#[actix_web::test]
+async fn missing_accounts_are_not_found() {
+    let node = upstream(
+        json!({"context": {"slot": 1}, "value": null}),
+        Arc::new(Mutex::new(None)),
+    );
+    let state = AppState::new(common::registry(vec![node.validator("primary", "lab")]));
+
+    let (status, body) = get(state, &format!("/account/{PUBKEY}")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["error"].as_str().unwrap().contains(PUBKEY));
+}
+
+// Serves the modern or the pre-1.9 blockhash API, recording every method asked.
+fn blockhash_node(legacy: bool, methods: Arc<Mutex<Vec<String>>>) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let methods = methods.clone();
+        cfg.route(
+            "/",
+            web::post().to(move |body: web::Json<Value>| {
+                let method = body["method"].as_str().unwrap_or_default().to_string();
+                methods.lock().unwrap().push(method.clone());
+                async move {
+                    let response = match (legacy, method.as_str()) {
+                        (false, "getLatestBlockhash") => json!({"result": {
+                            "context": {"slot": 900},
+                            "value": {"blockhash": "modernHash", "lastValidBlockHeight": 1050}
+                        }}),
+                        (true, "getRecentBlockhash") => json!({"result": {
+                            "context": {"slot": 800},
+                            "value": {
+                                "blockhash": "legacyHash",
+                                "feeCalculator": {"lamportsPerSignature": 5000}
+                            }
+                        }}),
+                        _ => json!({"error": {"code": -32601, "message": "Method not found"}}),
+                    };
+                    let mut response = response;
+                    response["jsonrpc"] = json!("2.0");
+                    response["id"] = json!(1);
+                    HttpResponse::Ok().json(response)
+                }
+            }),
+        );
+    })
+}
+
+#[actix_web::test]
+async fn blockhash_returns_the_latest_blockhash() {
+    let methods = Arc::new(Mutex::new(Vec::new()));
+    let node = blockhash_node(false, methods.clone());
+    let state = AppState::new(common::registry(vec![node.validator("primary", "lab")]));
+
+    let (status, body) = get(state, "/blockhash?commitment=confirmed").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({
+            "blockhash": "modernHash",
+            "lastValidBlockHeight": 1050,
+            "slot": 900,
+            "validator": "primary"
+        })
+    );
+    assert_eq!(*methods.lock().unwrap(), vec!["getLatestBlockhash"]);
+}
+
+#[actix_web::test]
+async fn blockhash_falls_back_to_get_recent_blockhash() {
+    let methods = Arc::new(Mutex::new(Vec::new()));
+    let node = blockhash_node(true, methods.clone());
+    let state = AppState::new(common::registry(vec![node.validator("old-node", "lab")]));
+
+    let (status, body) = get(state, "/blockhash").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({
+            "blockhash": "legacyHash",
+            "lastValidBlockHeight": null,
+            "slot": 800,
+            "validator": "old-node"
+        })
+    );
+    assert_eq!(
+        *methods.lock().unwrap(),
+        vec!["getLatestBlockhash", "getRecentBlockhash"]
+    );
+}
+
+#[actix_web::test]
+async fn blockhash_is_served_from_the_response_cache() {
+    let methods = Arc::new(Mutex::new(Vec::new()));
+    let node = blockhash_node(false, methods.clone());
+    let settings = Settings::builder()
+        .response_cache(true)
+        .cache_blockhash_ttl(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let state = AppState::from_settings(
+        common::registry(vec![node.validator("primary", "lab")]),
+        settings,
+    );
+    let app = common::init_app(state).await;
+
+    for _ in 0..2 {
+        let response = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/blockhash").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["blockhash"], "modernHash");
+        assert_eq!(body["validator"], "primary");
+    }
+    assert_eq!(methods.lock().unwrap().len(), 1);
+}
+
+#[actix_web::test]
+async fn blockhash_rejects_bad_commitment() {
+    let methods = Arc::new(Mutex::new(Vec::new()));
+    let node = blockhash_node(false, methods.clone());
+    let state = AppState::new(common::registry(vec![node.validator("primary", "lab")]));
+
+    let (status, _) = get(state, "/blockhash?commitment=soon").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(methods.lock().unwrap().is_empty());
+}
@@ -0,0 +1,196 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::http::{StatusCode, header};
+use actix_web::test;
+use serde_json::{Value, json};
+use url::Url;
+
+use the_solana_api::config::Settings;
+use the_solana_api::rate_limit::MethodRateLimits;
+use the_solana_api::{AppState, Validator};
+
+fn down(name: &str) -> Validator {
+    Validator::new(
+        name.into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:1/").unwrap(),
+    )
+}
+
+fn state(settings: Settings) -> AppState {
+    AppState::from_settings(
+        common::registry(vec![down("first"), down("second")]),
+        settings,
+    )
+}
+
+fn get_slot() -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+}
+
+// The header in seconds and the body's wait in milliseconds.
+async fn retry_after(resp: actix_web::dev::ServiceResponse, field: &[&str]) -> (u64, u64) {
+    let secs = resp
+        .headers()
+        .get(header::RETRY_AFTER)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    let body: Value = test::read_body_json(resp).await;
+    let millis = field
+        .iter()
+        .fold(&body, |value, key| &value[*key])
+        .as_u64()
+        .unwrap();
+    (secs, millis)
+}
+
+#[actix_web::test]
+async fn sheds_when_every_candidate_is_cooling_down() {
+    let state = state(
+        Settings::builder()
+            .shed_when_cooling_down(true)
+            .build()
+            .unwrap(),
+    );
+    state
+        .stats()
+        .validator("first")
+        .cool_down(Duration::from_secs(30));
+    state
+        .stats()
+        .validator("second")
+        .cool_down(Duration::from_secs(5));
+    let app = common::init_app(state).await;
+
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let (secs, millis) = retry_after(resp, &["retry_after_ms"]).await;
+    assert_eq!(secs, 5);
+    assert!(millis > 4000 && millis <= 5000, "{millis}");
+}
+
+#[actix_web::test]
+async fn sends_anyway_while_a_candidate_is_available() {
+    let state = state(
+        Settings::builder()
+            .shed_when_cooling_down(true)
+            .max_retries(0)
+            .build()
+            .unwrap(),
+    );
+    state
+        .stats()
+        .validator("first")
+        .cool_down(Duration::from_secs(30));
+    let app = common::init_app(state).await;
+
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    assert!(!resp.headers().contains_key(header::RETRY_AFTER));
+}
+
+#[actix_web::test]
+async fn does_not_shed_by_default() {
+    let state = state(Settings::default());
+    for name in ["first", "second"] {
+        state
+            .stats()
+            .validator(name)
+            .cool_down(Duration::from_secs(30));
+    }
+    let app = common::init_app(state).await;
+
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+}
+
+#[actix_web::test]
+async fn shedding_keeps_the_header_with_json_rpc_errors() {
+    let state = state(
+        Settings::builder()
+            .shed_when_cooling_down(true)
+            .jsonrpc_errors(true)
+            .build()
+            .unwrap(),
+    );
+    for name in ["first", "second"] {
+        state
+            .stats()
+            .validator(name)
+            .cool_down(Duration::from_secs(10));
+    }
+    let app = common::init_app(state).await;
+
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let (secs, millis) = retry_after(resp, &["error", "data", "retryAfterMs"]).await;
+    assert_eq!(secs, 10);
+    assert!(millis > 9000 && millis <= 10_000, "{millis}");
+}
+
+#[actix_web::test]
+async fn method_rate_limits_report_the_refill_time() {
+    let limits =
+        MethodRateLimits::from_json(r#"[{"method": "getSlot", "per_second": 0.5, "burst": 1}]"#)
+            .unwrap();
+    let app = common::init_app(state(
+        Settings::builder()
+            .method_rate_limits(limits)
+            .max_retries(0)
+            .build()
+            .unwrap(),
+    ))
+    .await;
+
+    test::call_service(&app, get_slot().to_request()).await;
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let (secs, millis) = retry_after(resp, &["error", "data", "retryAfterMs"]).await;
+    assert_eq!(secs, 2);
+    assert!(millis > 1500 && millis <= 2000, "{millis}");
+}
+
+#[actix_web::test]
+async fn throttled_endpoints_report_the_wait() {
+    let app = common::init_app(state(
+        Settings::builder()
+            .fleet_min_interval(Duration::from_secs(60))
+            .fleet_node_timeout(Duration::from_millis(100))
+            .ping_rate_limit(0.25)
+            .build()
+            .unwrap(),
+    ))
+    .await;
+
+    let fleet = || test::TestRequest::get().uri("/fleet/health").to_request();
+    assert!(
+        test::call_service(&app, fleet())
+            .await
+            .status()
+            .is_success()
+    );
+    let resp = test::call_service(&app, fleet()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let (secs, millis) = retry_after(resp, &["retry_after_ms"]).await;
+    assert_eq!(secs, 60);
+    assert!(millis > 59_000 && millis <= 60_000, "{millis}");
+
+    let ping = || {
+        test::TestRequest::get()
+            .uri("/validators/first/ping?count=1")
+            .to_request()
+    };
+    assert!(test::call_service(&app, ping()).await.status().is_success());
+    let resp = test::call_service(&app, ping()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let (secs, millis) = retry_after(resp, &["retry_after_ms"]).await;
+    assert_eq!(secs, 4);
+    assert!(millis > 3000 && millis <= 4000, "{millis}");
+}
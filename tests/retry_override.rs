@@ -0,0 +1,109 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use actix_web::http::StatusCode;
+use actix_web::http::header::HeaderValue;
+use actix_web::{HttpResponse, test, web};
+use serde_json::json;
+
+use the_solana_api::proxy::retry_budget;
+use the_solana_api::{AppState, Settings};
+
+#[actix_web::test]
+async fn retry_budget_prefers_the_most_conservative_option() {
+    let yes = HeaderValue::from_static("true");
+    let no = HeaderValue::from_static("false");
+
+    assert_eq!(retry_budget(2, None, None), 2);
+    assert_eq!(retry_budget(2, None, Some(0)), 0);
+    assert_eq!(retry_budget(2, None, Some(1)), 1);
+    assert_eq!(retry_budget(2, None, Some(5)), 2);
+    assert_eq!(retry_budget(2, Some(&yes), None), 0);
+    assert_eq!(retry_budget(2, Some(&yes), Some(3)), 0);
+    assert_eq!(retry_budget(2, Some(&no), Some(1)), 1);
+    assert_eq!(
+        retry_budget(2, Some(&HeaderValue::from_static("1")), None),
+        0
+    );
+    assert_eq!(retry_budget(0, None, Some(3)), 0);
+}
+
+async fn attempts_for(uri: &str, no_retry: Option<&str>) -> (usize, String) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let limited = |hits: Arc<AtomicUsize>| {
+        common::spawn_upstream(move |cfg| {
+            let hits = hits.clone();
+            cfg.route(
+                "/",
+                web::post().to(move || {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    async { HttpResponse::TooManyRequests().finish() }
+                }),
+            );
+        })
+    };
+    let first = limited(hits.clone());
+    let second = limited(hits.clone());
+
+    let settings = Settings::builder().cooldown_on_429(false).build().unwrap();
+    let state = AppState::from_settings(
+        common::registry(vec![
+            first.validator("limited-1", "lab"),
+            second.validator("limited-2", "lab"),
+        ]),
+        settings,
+    );
+    let app = common::init_app(state).await;
+
+    let mut request = test::TestRequest::post()
+        .uri(uri)
+        .set_json(json!({ "jsonrpc": "2.0", "id": 1, "method": "getSlot" }));
+    if let Some(value) = no_retry {
+        request = request.insert_header(("x-no-retry", value));
+    }
+    let response = test::call_service(&app, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let retries = response
+        .headers()
+        .get("x-retries")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    (hits.load(Ordering::SeqCst), retries)
+}
+
+#[actix_web::test]
+async fn retries_by_default() {
+    assert_eq!(
+        attempts_for("/?location=lab", None).await,
+        (2, "2".to_string())
+    );
+}
+
+#[actix_web::test]
+async fn header_disables_retries() {
+    assert_eq!(
+        attempts_for("/?location=lab", Some("true")).await,
+        (1, "0".to_string())
+    );
+}
+
+#[actix_web::test]
+async fn query_disables_retries() {
+    assert_eq!(
+        attempts_for("/?location=lab&retries=0", None).await,
+        (1, "0".to_string())
+    );
+}
+
+#[actix_web::test]
+async fn conflicting_values_prefer_no_retry() {
+    assert_eq!(
+        attempts_for("/?location=lab&retries=3", Some("true")).await,
+        (1, "0".to_string())
+    );
+}
@@ -0,0 +1,186 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::json;
+
+use the_solana_api::jsonrpc::RpcMethod;
+use the_solana_api::routing::{RoutingError, RoutingRules};
+use the_solana_api::{AppState, Settings, Validator, ValidatorRegistry};
+
+fn rules(raw: serde_json::Value) -> RoutingRules {
+    RoutingRules::from_json(&raw.to_string()).expect("rules")
+}
+
+fn named_upstream(name: &'static str) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        cfg.route(
+            "/",
+            web::post().to(move || async move {
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": name}))
+            }),
+        );
+    })
+}
+
+#[actix_web::test]
+async fn exact_method_beats_globs() {
+    let rules = rules(json!([
+        {"method": "get*", "locations": ["a"]},
+        {"method": "getProgramAccounts", "locations": ["b"]},
+    ]));
+
+    let rule = rules.best_match("getProgramAccounts").unwrap();
+    assert_eq!(rule.locations, vec!["b"]);
+    let rule = rules.best_match("getSlot").unwrap();
+    assert_eq!(rule.locations, vec!["a"]);
+    assert!(rules.best_match("sendTransaction").is_none());
+}
+
+#[actix_web::test]
+async fn more_specific_glob_wins_and_order_breaks_ties() {
+    let rules = rules(json!([
+        {"method": "*", "locations": ["any"]},
+        {"method": "get*", "locations": ["first"]},
+        {"method": "get*", "locations": ["second"]},
+        {"method": "getBlock*", "locations": ["blocks"]},
+        {"method": "get?lot", "locations": ["single-char"]},
+    ]));
+
+    assert_eq!(
+        rules.best_match("getBlocks").unwrap().locations,
+        vec!["blocks"]
+    );
+    assert_eq!(
+        rules.best_match("getBalance").unwrap().locations,
+        vec!["first"]
+    );
+    assert_eq!(
+        rules.best_match("getSlot").unwrap().locations,
+        vec!["single-char"]
+    );
+    assert_eq!(
+        rules.best_match("sendTransaction").unwrap().locations,
+        vec!["any"]
+    );
+}
+
+#[actix_web::test]
+async fn validation_reports_typos() {
+    let registry = common::registry(vec![
+        Validator::new(
+            "big-1".into(),
+            "frankfurt".into(),
+            "http://127.0.0.1:8899".parse().unwrap(),
+        )
+        .with_tags(["big"]),
+    ]);
+
+    assert!(
+        rules(json!([{"method": "get*", "tags": ["big"]}]))
+            .validate(&registry)
+            .is_ok()
+    );
+    assert!(matches!(
+        rules(json!([{"method": "get*", "validators": ["big-2"]}])).validate(&registry),
+        Err(RoutingError::UnknownValidator { .. })
+    ));
+    assert!(matches!(
+        rules(json!([{"method": "get*", "locations": ["frankfrut"]}])).validate(&registry),
+        Err(RoutingError::UnknownLocation { .. })
+    ));
+    assert!(matches!(
+        rules(json!([{"method": "get*", "tags": ["staked"]}])).validate(&registry),
+        Err(RoutingError::UnknownTag { .. })
+    ));
+    assert!(matches!(
+        rules(json!([{"method": "get*", "archive": true}])).validate(&registry),
+        Err(RoutingError::NoMatch(_))
+    ));
+}
+
+#[actix_web::test]
+async fn reads_tags_and_archive_from_csv() {
+    let csv = "name,ip,location,tags,archive\nbig-1,10.0.0.1,frankfurt,Big;staked,true\nsmall-1,10.0.0.2,paris,,\n";
+    let registry = ValidatorRegistry::from_reader(csv.as_bytes()).expect("registry");
+
+    let big = registry.get_by_name("big-1").unwrap();
+    assert_eq!(big.tags(), ["big", "staked"]);
+    assert!(big.is_archive());
+
+    let small = registry.get_by_name("small-1").unwrap();
+    assert!(small.tags().is_empty());
+    assert!(!small.is_archive());
+}
+
+#[actix_web::test]
+async fn batches_must_satisfy_every_matched_rule() {
+    let rules = rules(json!([
+        {"method": "getProgramAccounts", "tags": ["big"]},
+        {"method": "getBlock", "archive": true},
+    ]));
+
+    let batch = RpcMethod::Batch(vec![
+        Some("getProgramAccounts".into()),
+        Some("getBlock".into()),
+        Some("getProgramAccounts".into()),
+    ]);
+    assert_eq!(rules.rules_for(&batch).len(), 2);
+}
+
+async fn proxied_result(
+    state: AppState,
+    uri: &str,
+    method: &str,
+) -> (StatusCode, serde_json::Value) {
+    let app = common::init_app(state).await;
+    let req = test::TestRequest::post()
+        .uri(uri)
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": method}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+#[actix_web::test]
+async fn routes_methods_according_to_rules() {
+    let big = named_upstream("big-1");
+    let small = named_upstream("small-1");
+
+    let settings = Settings::builder()
+        .routing_rules(rules(
+            json!([{"method": "getProgramAccounts", "tags": ["big"]}]),
+        ))
+        .build()
+        .unwrap();
+    let registry = common::registry(vec![
+        big.validator("big-1", "frankfurt").with_tags(["big"]),
+        small.validator("small-1", "frankfurt"),
+    ]);
+    let state = AppState::from_settings(registry, settings);
+
+    for _ in 0..10 {
+        let (status, body) = proxied_result(state.clone(), "/", "getProgramAccounts").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["result"], "big-1");
+    }
+
+    let (status, body) =
+        proxied_result(state.clone(), "/?location=frankfurt", "getProgramAccounts").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["result"], "big-1");
+
+    let (status, body) = proxied_result(state.clone(), "/?server=small-1", "getSlot").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["result"], "small-1");
+
+    let (status, body) = proxied_result(state, "/?server=small-1", "getProgramAccounts").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(
+        body["error"]
+            .as_str()
+            .unwrap()
+            .contains("getProgramAccounts")
+    );
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::{ResponseError, test};
+use serde_json::{Value, json};
+use url::Url;
+
+use the_solana_api::errors::{AppError, ErrorFormat};
+use the_solana_api::validators::{self, SelectionError};
+use the_solana_api::{AppState, Validator};
+
+fn validator(name: &str, location: &str) -> Validator {
+    Validator::new(
+        name.into(),
+        location.into(),
+        Url::parse("http://127.0.0.1:1/").unwrap(),
+    )
+}
+
+fn state() -> AppState {
+    AppState::new(common::registry(vec![
+        validator("frankfurt-1", "frankfurt"),
+        validator("frankfurt-2", "Frankfurt"),
+        validator("tokyo-1", "tokyo"),
+    ]))
+}
+
+#[actix_web::test]
+async fn suggests_close_names() {
+    let known = ["frankfurt-1", "frankfurt-2", "tokyo-1", "amsterdam-1"];
+    assert_eq!(
+        validators::suggestions("frankfurt-3", known.into_iter()),
+        ["frankfurt-1", "frankfurt-2"]
+    );
+    assert_eq!(
+        validators::suggestions("TOKYO1", known.into_iter()),
+        ["tokyo-1"]
+    );
+    assert_eq!(
+        validators::suggestions("amsterdam", known.into_iter()),
+        ["amsterdam-1"]
+    );
+    assert!(validators::suggestions("sydney", known.into_iter()).is_empty());
+
+    // "EU-3" sorts between the two spellings of "eu-2".
+    assert_eq!(
+        validators::suggestions("eu-1", ["EU-2", "EU-3", "eu-2"].into_iter()),
+        ["EU-2", "EU-3"]
+    );
+}
+
+#[actix_web::test]
+async fn selection_errors_carry_suggestions() {
+    let registry = common::registry(vec![
+        validator("frankfurt-1", "frankfurt"),
+        validator("tokyo-1", "tokyo"),
+    ]);
+    match registry.candidates(Some("tokyo-2"), None) {
+        Err(SelectionError::UnknownValidator { name, suggestions }) => {
+            assert_eq!(name, "tokyo-2");
+            assert_eq!(suggestions, ["tokyo-1"]);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    match registry.candidates(None, Some("tokio")) {
+        Err(SelectionError::UnknownLocation { suggestions, .. }) => {
+            assert_eq!(suggestions, ["tokyo"]);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+async fn post(uri: &str) -> (StatusCode, Value) {
+    let app = common::init_app(state()).await;
+    let req = test::TestRequest::post()
+        .uri(uri)
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+#[actix_web::test]
+async fn unknown_validators_are_not_found() {
+    let (status, body) = post("/?server=frankfurt-9").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"], "validator 'frankfurt-9' not found");
+    assert_eq!(body["suggestions"], json!(["frankfurt-1", "frankfurt-2"]));
+
+    let (status, body) = post("/upstream/health?server=nowhere").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["suggestions"], json!([]));
+}
+
+#[actix_web::test]
+async fn unknown_locations_are_not_found() {
+    let (status, body) = post("/?location=frankfrt").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(
+        body["error"],
+        "no validator available for location 'frankfrt'"
+    );
+    // Locations differing only in case are listed once, as first seen.
+    assert_eq!(body["suggestions"], json!(["frankfurt"]));
+}
+
+#[actix_web::test]
+async fn every_selection_error_has_its_status_and_body() {
+    let cases = [
+        (
+            SelectionError::UnknownValidator {
+                name: "tokio-1".into(),
+                suggestions: vec!["tokyo-1".into()],
+            },
+            StatusCode::NOT_FOUND,
+            json!({
+                "error": "validator 'tokio-1' not found",
+                "code": "unknown_validator",
+                "suggestions": ["tokyo-1"]
+            }),
+        ),
+        (
+            SelectionError::UnknownLocation {
+                location: "mars".into(),
+                suggestions: Vec::new(),
+            },
+            StatusCode::NOT_FOUND,
+            json!({
+                "error": "no validator available for location 'mars'",
+                "code": "unknown_location",
+                "suggestions": []
+            }),
+        ),
+        (
+            SelectionError::Empty,
+            StatusCode::BAD_REQUEST,
+            json!({
+                "error": "validator selection failed: no validators available",
+                "code": "selection_failed"
+            }),
+        ),
+        (
+            SelectionError::NotEnoughValidators {
+                required: 3,
+                available: 2,
+            },
+            StatusCode::BAD_REQUEST,
+            json!({
+                "error": "validator selection failed: quorum of 3 needs more validators than the 2 available",
+                "code": "selection_failed"
+            }),
+        ),
+    ];
+    for (selection, status, body) in cases {
+        let err = AppError::from(selection.clone());
+        assert!(matches!(&err, AppError::Selection(inner) if *inner == selection));
+        assert_eq!(err.status_code(), status);
+        assert_eq!(err.body(ErrorFormat::Plain), body);
+
+        let resp = err.error_response();
+        assert_eq!(resp.status(), status);
+        let bytes = actix_web::body::to_bytes(resp.into_body()).await.unwrap();
+        let rendered: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(rendered, body);
+    }
+}
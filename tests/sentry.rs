@@ -0,0 +1,103 @@
+#![cfg(feature = "sentry")]
+
+mod common;
+
+use std::sync::Arc;
+
+use actix_web::{App, ResponseError, middleware::from_fn, test, web};
+use sentry::protocol::Level;
+use sentry::test::TestTransport;
+use sentry::{Hub, Scope};
+use serde_json::json;
+use url::Url;
+
+use the_solana_api::error_reporting::{self, SPIKE_THRESHOLD};
+use the_solana_api::errors::UpstreamErrorKind;
+use the_solana_api::{AppError, AppState, Validator, request_id, routes};
+
+fn hub() -> (Arc<TestTransport>, Arc<Hub>) {
+    let transport = TestTransport::new();
+    let mut options = error_reporting::client_options();
+    options.dsn = Some("https://public@sentry.invalid/1".parse().unwrap());
+    options.transport = Some(Arc::new(transport.clone()));
+    let hub = Hub::new(Some(Arc::new(options.into())), Arc::new(Scope::default()));
+    (transport, Arc::new(hub))
+}
+
+fn down() -> Validator {
+    Validator::new(
+        "down".into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:1/").unwrap(),
+    )
+}
+
+#[actix_web::test]
+async fn repeated_upstream_failures_are_reported_once_with_tags() {
+    let (transport, hub) = hub();
+    let state = AppState::new(common::registry(vec![down()]));
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(request_id::assign))
+            .wrap(
+                error_reporting::middleware()
+                    .into_builder()
+                    .with_hub(hub)
+                    .finish(),
+            )
+            .app_data(web::Data::new(state))
+            .configure(routes::configure),
+    )
+    .await;
+
+    for attempt in 1..=SPIKE_THRESHOLD + 2 {
+        let req = test::TestRequest::post()
+            .uri("/")
+            .insert_header(("x-request-id", format!("req-{attempt}")))
+            .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot", "params": ["secret"]}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_server_error());
+        if attempt < SPIKE_THRESHOLD {
+            assert!(transport.fetch_and_clear_events().is_empty());
+        }
+    }
+
+    let events = transport.fetch_and_clear_events();
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event.level, Level::Warning);
+    assert!(
+        event
+            .message
+            .as_deref()
+            .unwrap()
+            .starts_with("5 upstream failures for validator 'down' within 60s")
+    );
+    assert_eq!(event.tags["validator"], "down");
+    assert_eq!(event.tags["rpc_method"], "getSlot");
+    assert_eq!(event.tags["request_id"], format!("req-{SPIKE_THRESHOLD}"));
+    let request = event.request.as_ref().unwrap();
+    assert_eq!(request.data, None);
+    assert!(!format!("{event:?}").contains("secret"));
+}
+
+#[actix_web::test]
+async fn internal_errors_are_captured_and_others_are_not() {
+    let events = sentry::test::with_captured_events(|| {
+        AppError::upstream(
+            "down",
+            UpstreamErrorKind::ConnectRefused,
+            "node 'down' is unavailable",
+        )
+        .error_response();
+        AppError::BadRequest("bad".into()).error_response();
+        AppError::Internal("no validator attempted".into()).error_response();
+    });
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].level, Level::Error);
+    assert_eq!(
+        events[0].exception.last().unwrap().value.as_deref(),
+        Some("internal error: no validator attempted")
+    );
+}
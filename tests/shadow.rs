@@ -0,0 +1,168 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::AppState;
+use the_solana_api::config::Settings;
+use the_solana_api::jsonrpc;
+
+fn upstream(result: Value, delay: Duration) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let result = result.clone();
+        cfg.route(
+            "/",
+            web::post().to(move || {
+                let result = result.clone();
+                async move {
+                    tokio::time::sleep(delay).await;
+                    HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": result}))
+                }
+            }),
+        );
+    })
+}
+
+fn sampled() -> Settings {
+    Settings {
+        shadow_sample_rate: 1.0,
+        ..Settings::default()
+    }
+}
+
+fn call(method: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/?validator=primary")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": ["A"]}))
+        .to_request()
+}
+
+async fn shadow_stats<S>(app: &S) -> Value
+where
+    S: actix_web::dev::Service<
+            actix_http::Request,
+            Response = actix_web::dev::ServiceResponse,
+            Error = actix_web::Error,
+        >,
+{
+    for _ in 0..100 {
+        let stats: Value =
+            test::call_and_read_body_json(app, test::TestRequest::get().uri("/stats").to_request())
+                .await;
+        if !stats["shadow"].as_array().unwrap().is_empty() {
+            return stats["shadow"].clone();
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("shadow check never completed");
+}
+
+#[actix_web::test]
+async fn normalized_results_drop_ignored_fields() {
+    let body = br#"{"result":{"context":{"slot":5,"apiVersion":"2.0"},"value":[{"a":1,"status":"x"},{"a":2,"status":"y"}]}}"#;
+    let normalized = jsonrpc::normalized_result(body, &["context.slot", "value.status"]).unwrap();
+    assert_eq!(
+        normalized,
+        json!({"context": {"apiVersion": "2.0"}, "value": [{"a": 1}, {"a": 2}]})
+    );
+    assert_eq!(
+        jsonrpc::normalized_result(br#"{"error":{}}"#, &["context.slot"]),
+        None
+    );
+}
+
+#[actix_web::test]
+async fn divergent_results_are_counted_per_pair() {
+    let primary = upstream(json!({"context": {"slot": 10}, "value": 5}), Duration::ZERO);
+    let shadow = upstream(json!({"context": {"slot": 12}, "value": 6}), Duration::ZERO);
+    let state = AppState::from_settings(
+        common::registry(vec![
+            primary.validator("primary", "lab"),
+            shadow.validator("shadow", "lab"),
+        ]),
+        sampled(),
+    );
+    let app = common::init_app(state).await;
+
+    let body: Value = test::call_and_read_body_json(&app, call("getBalance")).await;
+    assert_eq!(body["result"]["value"], 5);
+
+    let pairs = shadow_stats(&app).await;
+    assert_eq!(
+        pairs,
+        json!([{"primary": "primary", "shadow": "shadow", "checks": 1, "divergences": 1}])
+    );
+}
+
+#[actix_web::test]
+async fn ignored_fields_do_not_diverge() {
+    let primary = upstream(
+        json!({"context": {"slot": 10, "apiVersion": "1.18"}, "value": 5}),
+        Duration::ZERO,
+    );
+    let shadow = upstream(
+        json!({"context": {"slot": 11, "apiVersion": "2.0"}, "value": 5}),
+        Duration::ZERO,
+    );
+    let state = AppState::from_settings(
+        common::registry(vec![
+            primary.validator("primary", "lab"),
+            shadow.validator("shadow", "lab"),
+        ]),
+        sampled(),
+    );
+    let app = common::init_app(state).await;
+
+    test::call_service(&app, call("getBalance")).await;
+
+    let pairs = shadow_stats(&app).await;
+    assert_eq!(pairs[0]["checks"], 1);
+    assert_eq!(pairs[0]["divergences"], 0);
+}
+
+#[actix_web::test]
+async fn shadow_reads_do_not_delay_the_client() {
+    let primary = upstream(json!(1), Duration::ZERO);
+    let shadow = upstream(json!(1), Duration::from_secs(2));
+    let state = AppState::from_settings(
+        common::registry(vec![
+            primary.validator("primary", "lab"),
+            shadow.validator("shadow", "lab"),
+        ]),
+        sampled(),
+    );
+    let app = common::init_app(state).await;
+
+    let started = Instant::now();
+    let body: Value = test::call_and_read_body_json(&app, call("getBalance")).await;
+    assert_eq!(body["result"], 1);
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[actix_web::test]
+async fn only_sampled_idempotent_calls_are_shadowed() {
+    let primary = upstream(json!(1), Duration::ZERO);
+    let shadow = upstream(json!(2), Duration::ZERO);
+    let registry = || {
+        common::registry(vec![
+            primary.validator("primary", "lab"),
+            shadow.validator("shadow", "lab"),
+        ])
+    };
+
+    let app = common::init_app(AppState::from_settings(registry(), sampled())).await;
+    test::call_service(&app, call("sendTransaction")).await;
+
+    let disabled = common::init_app(AppState::new(registry())).await;
+    test::call_service(&disabled, call("getBalance")).await;
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    for app in [&app, &disabled] {
+        let stats: Value =
+            test::call_and_read_body_json(app, test::TestRequest::get().uri("/stats").to_request())
+                .await;
+        assert_eq!(stats["shadow"], json!([]));
+    }
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::{AppState, Settings};
+
+// Every call advances the slot, so a repeated value proves a cache hit.
+fn advancing_slot_upstream(slot: Arc<AtomicU64>) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let slot = slot.clone();
+        cfg.route(
+            "/",
+            web::post().to(move |body: web::Json<Value>| {
+                let current = slot.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    HttpResponse::Ok()
+                        .json(json!({"jsonrpc": "2.0", "id": body["id"], "result": current}))
+                }
+            }),
+        );
+    })
+}
+
+fn settings(ttl: Duration) -> Settings {
+    Settings {
+        response_cache: true,
+        cache_short_ttl: ttl,
+        ..Settings::default()
+    }
+}
+
+async fn get_slot(
+    app: &impl actix_web::dev::Service<
+        actix_http::Request,
+        Response = actix_web::dev::ServiceResponse,
+        Error = actix_web::Error,
+    >,
+    uri: &str,
+    id: u64,
+    params: Value,
+) -> Value {
+    let req = test::TestRequest::post()
+        .uri(uri)
+        .set_json(json!({"jsonrpc": "2.0", "id": id, "method": "getSlot", "params": params}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(app, req).await;
+    assert_eq!(body["id"], id);
+    body["result"].clone()
+}
+
+#[actix_web::test]
+async fn serves_cached_slot_until_ttl_expires() {
+    let slot = Arc::new(AtomicU64::new(100));
+    let upstream = advancing_slot_upstream(slot.clone());
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("primary", "lab")]),
+        settings(Duration::from_millis(150)),
+    );
+    let app = common::init_app(state.clone()).await;
+
+    assert_eq!(get_slot(&app, "/", 1, json!([])).await, 100);
+    assert_eq!(get_slot(&app, "/", 2, json!(null)).await, 100);
+    assert_eq!(
+        get_slot(&app, "/", 3, json!([{"commitment": "finalized"}])).await,
+        100
+    );
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(get_slot(&app, "/", 4, json!([])).await, 101);
+
+    let counters = state.response_cache().counters();
+    assert_eq!(counters.hits, 2);
+    assert_eq!(counters.misses, 2);
+
+    let stats: Value =
+        test::call_and_read_body_json(&app, test::TestRequest::get().uri("/stats").to_request())
+            .await;
+    assert_eq!(stats["cache"]["hits"], 2);
+    assert_eq!(stats["cache"]["misses"], 2);
+}
+
+#[actix_web::test]
+async fn non_default_commitment_skips_the_cache() {
+    let slot = Arc::new(AtomicU64::new(100));
+    let upstream = advancing_slot_upstream(slot.clone());
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("primary", "lab")]),
+        settings(Duration::from_secs(60)),
+    );
+    let app = common::init_app(state).await;
+
+    let processed = json!([{"commitment": "processed"}]);
+    assert_eq!(get_slot(&app, "/", 1, processed.clone()).await, 100);
+    assert_eq!(get_slot(&app, "/", 2, processed).await, 101);
+
+    let min_context = json!([{"minContextSlot": 5}]);
+    assert_eq!(get_slot(&app, "/", 3, min_context.clone()).await, 102);
+    assert_eq!(get_slot(&app, "/", 4, min_context).await, 103);
+}
+
+#[actix_web::test]
+async fn entries_are_scoped_per_location() {
+    let frankfurt = advancing_slot_upstream(Arc::new(AtomicU64::new(100)));
+    let tokyo = advancing_slot_upstream(Arc::new(AtomicU64::new(900)));
+    let state = AppState::from_settings(
+        common::registry(vec![
+            frankfurt.validator("fra-1", "frankfurt"),
+            tokyo.validator("tyo-1", "tokyo"),
+        ]),
+        settings(Duration::from_secs(60)),
+    );
+    let app = common::init_app(state).await;
+
+    assert_eq!(
+        get_slot(&app, "/?location=frankfurt", 1, json!([])).await,
+        100
+    );
+    assert_eq!(get_slot(&app, "/?location=tokyo", 2, json!([])).await, 900);
+    assert_eq!(
+        get_slot(&app, "/?location=frankfurt", 3, json!([])).await,
+        100
+    );
+    assert_eq!(get_slot(&app, "/?location=tokyo", 4, json!([])).await, 900);
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::AppState;
+
+// Answers `getSlot` after 300 ms.
+fn slow_node() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 42}))
+            }),
+        );
+    })
+}
+
+fn get_slot() -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+        .to_request()
+}
+
+fn readyz() -> actix_http::Request {
+    test::TestRequest::get().uri("/readyz").to_request()
+}
+
+#[actix_web::test]
+async fn draining_finishes_requests_in_flight_and_refuses_new_ones() {
+    let node = slow_node();
+    let state = AppState::builder()
+        .validators(vec![node.validator("primary", "lab")])
+        .build()
+        .unwrap();
+    let app = common::init_app(state.clone()).await;
+    let resp = test::call_service(&app, readyz()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let in_flight = async {
+        let resp = test::call_service(&app, get_slot()).await;
+        let status = resp.status();
+        let body: Value = test::read_body_json(resp).await;
+        (status, body)
+    };
+    let drained = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        state.start_draining();
+        let refused = test::call_service(&app, get_slot()).await;
+        let ready = test::call_service(&app, readyz()).await;
+        (refused, ready.status())
+    };
+    let ((status, body), (refused, ready)) = tokio::join!(in_flight, drained);
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["result"], 42);
+    assert_eq!(ready, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(refused.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(refused.headers().get("retry-after").unwrap(), "1");
+    let body: Value = test::read_body_json(refused).await;
+    assert_eq!(body["code"], "overloaded");
+}
+
+#[actix_web::test]
+async fn draining_refuses_every_route_to_the_validators() {
+    let node = slow_node();
+    let state = AppState::builder()
+        .validators(vec![node.validator("primary", "lab")])
+        .build()
+        .unwrap();
+    let app = common::init_app(state.clone()).await;
+    state.start_draining();
+
+    for req in [
+        test::TestRequest::post()
+            .uri("/upstream/rpc?server=primary")
+            .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"})),
+        test::TestRequest::get().uri("/upstream/snapshot.tar.bz2"),
+        test::TestRequest::get().uri("/slot"),
+        test::TestRequest::get().uri("/validators/primary/ping"),
+        test::TestRequest::get().uri("/fleet/health"),
+    ] {
+        let resp = test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers().get("retry-after").unwrap(), "1");
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "overloaded");
+    }
+    // What does not reach a validator keeps answering.
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get().uri("/validators").to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
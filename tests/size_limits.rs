@@ -0,0 +1,111 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::jsonrpc::RpcMethod;
+use the_solana_api::size_limits::{DEFAULT_MAX_RESPONSE_BYTES, ResponseSizeLimits};
+use the_solana_api::{AppState, Settings};
+
+fn limits() -> ResponseSizeLimits {
+    ResponseSizeLimits::from_json(
+        DEFAULT_MAX_RESPONSE_BYTES,
+        r#"[{"method":"getBalance","max_bytes":1024},{"method":"getBlock*","max_bytes":67108864}]"#,
+    )
+    .unwrap()
+}
+
+#[actix_web::test]
+async fn resolves_limits_per_method() {
+    let limits = limits();
+
+    assert_eq!(limits.limit_for("getBalance"), 1024);
+    assert_eq!(limits.limit_for("getBlocks"), 64 * 1024 * 1024);
+    assert_eq!(limits.limit_for("getSlot"), DEFAULT_MAX_RESPONSE_BYTES);
+    assert_eq!(
+        limits.for_method(&RpcMethod::Batch(vec![
+            Some("getBalance".into()),
+            Some("getBlock".into()),
+        ])),
+        64 * 1024 * 1024
+    );
+    assert_eq!(
+        limits.for_method(&RpcMethod::Batch(vec![Some("getBalance".into())])),
+        1024
+    );
+    assert_eq!(
+        limits.for_method(&RpcMethod::Invalid),
+        DEFAULT_MAX_RESPONSE_BYTES
+    );
+}
+
+// Answers every call with roughly 4 KiB of JSON.
+fn padded_upstream() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                HttpResponse::Ok()
+                    .json(json!({"jsonrpc": "2.0", "id": 1, "result": "x".repeat(4096)}))
+            }),
+        );
+    })
+}
+
+async fn call(state: AppState, body: Value) -> (StatusCode, Value) {
+    let app = common::init_app(state).await;
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_json(body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+fn limited_state(upstream: &common::MockUpstream) -> AppState {
+    let settings = Settings::builder()
+        .response_size_limits(limits())
+        .build()
+        .unwrap();
+    AppState::from_settings(
+        common::registry(vec![upstream.validator("primary", "lab")]),
+        settings,
+    )
+}
+
+#[actix_web::test]
+async fn method_limit_below_the_global_one_aborts_with_502() {
+    let upstream = padded_upstream();
+
+    let (status, body) = call(
+        limited_state(&upstream),
+        json!({"jsonrpc": "2.0", "id": 1, "method": "getBalance", "params": ["addr"]}),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    let message = body["error"].as_str().unwrap();
+    assert!(message.contains("getBalance"), "{message}");
+    assert!(message.contains("1024"), "{message}");
+}
+
+#[actix_web::test]
+async fn other_methods_use_their_own_or_the_default_limit() {
+    let upstream = padded_upstream();
+
+    let (status, _) = call(
+        limited_state(&upstream),
+        json!({"jsonrpc": "2.0", "id": 1, "method": "getBlock", "params": [1]}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = call(
+        limited_state(&upstream),
+        json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
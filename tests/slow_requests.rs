@@ -0,0 +1,178 @@
+mod common;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::json;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+use the_solana_api::config::Settings;
+use the_solana_api::{AppState, slow_request};
+
+type Lines = Arc<Mutex<Vec<HashMap<String, String>>>>;
+
+// Keeps the fields of every slow-request warning.
+struct Capture(Lines);
+
+struct Fields(HashMap<String, String>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
+
+impl<S: Subscriber> Layer<S> for Capture {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        if event.metadata().target() == slow_request::TARGET {
+            assert_eq!(*event.metadata().level(), Level::WARN);
+            let mut fields = Fields(HashMap::new());
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+    }
+}
+
+fn capture() -> (Lines, tracing::subscriber::DefaultGuard) {
+    let lines = Lines::default();
+    let subscriber = tracing_subscriber::registry().with(Capture(lines.clone()));
+    (lines, tracing::subscriber::set_default(subscriber))
+}
+
+fn delayed(status: StatusCode, delay: Duration) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        cfg.route(
+            "/",
+            web::post().to(move || async move {
+                tokio::time::sleep(delay).await;
+                HttpResponse::build(status).json(json!({"jsonrpc": "2.0", "id": 1, "result": 1}))
+            }),
+        );
+    })
+}
+
+fn settings(threshold_ms: u64) -> Settings {
+    Settings {
+        slow_request_threshold: Some(Duration::from_millis(threshold_ms)),
+        cooldown_on_429: false,
+        ..Settings::default()
+    }
+}
+
+fn get_slot() -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+}
+
+#[actix_web::test]
+async fn warns_about_slow_answers_and_counts_them_per_validator() {
+    let node = delayed(StatusCode::OK, Duration::from_millis(120));
+    let state = AppState::from_settings(
+        common::registry(vec![node.validator("primary", "frankfurt")]),
+        settings(50),
+    );
+    let (lines, _guard) = capture();
+    let app = common::init_app(state.clone()).await;
+
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert!(resp.status().is_success());
+
+    let lines = lines.lock().unwrap();
+    assert_eq!(lines.len(), 1);
+    let line = &lines[0];
+    assert_eq!(line["validator"], "primary");
+    assert_eq!(line["rpc_method"], "getSlot");
+    assert_eq!(line["threshold_ms"], "50");
+    assert!(line["duration_ms"].parse::<u64>().unwrap() >= 120);
+    assert_eq!(
+        line["request_bytes"],
+        json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"})
+            .to_string()
+            .len()
+            .to_string()
+    );
+    assert_eq!(line["status"], "200");
+    assert_eq!(line["failed"], "false");
+    assert_eq!(line["attempt"], "0");
+    assert!(!line.contains_key("attempted"));
+
+    assert_eq!(
+        state.stats().validator("primary").traffic().slow_requests,
+        1
+    );
+}
+
+#[actix_web::test]
+async fn fast_answers_are_not_reported() {
+    let node = delayed(StatusCode::OK, Duration::ZERO);
+    let state = AppState::from_settings(
+        common::registry(vec![node.validator("primary", "frankfurt")]),
+        settings(1000),
+    );
+    let (lines, _guard) = capture();
+    let app = common::init_app(state.clone()).await;
+
+    test::call_service(&app, get_slot().to_request()).await;
+
+    assert!(lines.lock().unwrap().is_empty());
+    assert_eq!(
+        state.stats().validator("primary").traffic().slow_requests,
+        0
+    );
+}
+
+#[actix_web::test]
+async fn slow_failures_include_the_failover_attempts() {
+    let first = delayed(StatusCode::TOO_MANY_REQUESTS, Duration::from_millis(40));
+    let second = delayed(StatusCode::TOO_MANY_REQUESTS, Duration::from_millis(40));
+    let state = AppState::from_settings(
+        common::registry(vec![
+            first.validator("first", "frankfurt"),
+            second.validator("second", "frankfurt"),
+        ]),
+        settings(50),
+    );
+    let (lines, _guard) = capture();
+    let app = common::init_app(state.clone()).await;
+
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let lines = lines.lock().unwrap();
+    assert_eq!(lines.len(), 1);
+    let line = &lines[0];
+    assert_eq!(line["failed"], "true");
+    assert_eq!(line["status"], "429");
+    assert_eq!(line["attempt"], "1");
+    let mut attempted: Vec<&str> = line["attempted"].split(',').collect();
+    attempted.sort();
+    assert_eq!(attempted, ["first", "second"]);
+
+    let charged = &line["validator"];
+    assert_eq!(state.stats().validator(charged).traffic().slow_requests, 1);
+}
+
+#[actix_web::test]
+async fn disabled_without_a_threshold() {
+    let node = delayed(StatusCode::OK, Duration::from_millis(20));
+    let (lines, _guard) = capture();
+    let app = common::init_app(AppState::new(common::registry(vec![
+        node.validator("primary", "frankfurt"),
+    ])))
+    .await;
+
+    test::call_service(&app, get_slot().to_request()).await;
+    assert!(lines.lock().unwrap().is_empty());
+}
@@ -0,0 +1,194 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::http::StatusCode;
+use serde_json::json;
+use url::Url;
+
+use the_solana_api::config::Settings;
+use the_solana_api::errors::UpstreamErrorKind;
+use the_solana_api::snapshot::{self, StatsSnapshot};
+use the_solana_api::stats::FailureKind;
+use the_solana_api::{AppState, Validator};
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("solana-api-snapshot-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn state(names: &[&str], settings: Settings) -> AppState {
+    let validators = names
+        .iter()
+        .map(|name| {
+            Validator::new(
+                name.to_string(),
+                "lab".into(),
+                Url::parse("http://127.0.0.1:1/").unwrap(),
+            )
+        })
+        .collect();
+    AppState::from_settings(common::registry(validators), settings)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[actix_web::test]
+async fn round_trips_validator_stats() {
+    let dir = scratch_dir("round-trip");
+    let path = dir.join("stats.json");
+
+    let before = state(&["primary"], Settings::default());
+    let stats = before.stats().validator("primary");
+    stats.record_response(StatusCode::OK, Duration::from_millis(12));
+    stats.record_response(StatusCode::BAD_GATEWAY, Duration::from_millis(40));
+    stats.record_no_response(UpstreamErrorKind::Timeout, "timed out");
+    stats.record_slow_request();
+    stats.record_received(512);
+    stats.observe_slot(250_000_000);
+    stats.cool_down(Duration::from_secs(60));
+    snapshot::save(&before, &path).unwrap();
+    assert!(!dir.join("stats.json.tmp").exists());
+
+    let after = state(&["primary"], Settings::default());
+    let loaded = snapshot::load(&path, HOUR).unwrap();
+    assert_eq!(snapshot::restore(&after, &loaded), 1);
+
+    let (old, new) = (stats, after.stats().validator("primary"));
+    assert_eq!(new.traffic().requests, 3);
+    assert_eq!(new.traffic(), old.traffic());
+    assert_eq!(new.latency_histogram(), old.latency_histogram());
+    assert_eq!(new.error_rates(), old.error_rates());
+    assert_eq!(new.failures(FailureKind::Timeout), 1);
+    assert_eq!(new.latest_slot(), Some(250_000_000));
+    assert!(new.is_cooling_down());
+    assert_eq!(new.traffic().last_error.unwrap().message, "timed out");
+}
+
+#[actix_web::test]
+async fn loads_snapshots_with_missing_and_unknown_fields() {
+    let dir = scratch_dir("schema");
+    let path = dir.join("stats.json");
+    // As written by a version with fewer fields, or a newer one with more.
+    let body = json!({
+        "saved_at_unix_ms": now_ms(),
+        "format": 7,
+        "validators": {
+            "primary": {"requests": 7, "failures": {"timeout": 2}, "added_later": [1, 2]}
+        }
+    });
+    fs::write(&path, body.to_string()).unwrap();
+
+    let state = state(&["primary"], Settings::default());
+    snapshot::restore(&state, &snapshot::load(&path, HOUR).unwrap());
+    let stats = state.stats().validator("primary");
+    assert_eq!(stats.requests_total(), 7);
+    assert_eq!(stats.failures(FailureKind::Timeout), 2);
+    assert_eq!(stats.failures(FailureKind::Unavailable), 0);
+    assert_eq!(stats.traffic().latency_ms.samples, 0);
+    assert_eq!(stats.latest_slot(), None);
+    assert!(!stats.is_cooling_down());
+}
+
+#[actix_web::test]
+async fn ignores_corrupt_and_missing_files() {
+    let dir = scratch_dir("corrupt");
+    let path = dir.join("stats.json");
+    assert!(snapshot::load(&path, HOUR).is_none());
+
+    for body in [
+        "{\"saved_at_unix_ms\": 1, \"validators\": {",
+        "not json",
+        "",
+    ] {
+        fs::write(&path, body).unwrap();
+        assert!(snapshot::load(&path, HOUR).is_none());
+    }
+
+    // Startup carries on with empty stats.
+    let state = state(
+        &["primary"],
+        Settings::builder()
+            .stats_snapshot_path(path)
+            .build()
+            .unwrap(),
+    );
+    snapshot::restore_on_startup(&state);
+    assert_eq!(state.stats().validator("primary").requests_total(), 0);
+}
+
+#[actix_web::test]
+async fn ignores_stale_snapshots() {
+    let dir = scratch_dir("stale");
+    let path = dir.join("stats.json");
+    let saved = StatsSnapshot {
+        saved_at_unix_ms: now_ms() - 2 * HOUR.as_millis() as u64,
+        ..StatsSnapshot::default()
+    };
+    fs::write(&path, serde_json::to_vec(&saved).unwrap()).unwrap();
+
+    assert!(snapshot::load(&path, HOUR).is_none());
+    assert!(snapshot::load(&path, 3 * HOUR).is_some());
+}
+
+#[actix_web::test]
+async fn skips_removed_validators_and_changed_buckets() {
+    let dir = scratch_dir("changed");
+    let path = dir.join("stats.json");
+    let before = state(&["primary", "retired"], Settings::default());
+    for name in ["primary", "retired"] {
+        before
+            .stats()
+            .validator(name)
+            .record_response(StatusCode::OK, Duration::from_millis(3));
+    }
+    snapshot::save(&before, &path).unwrap();
+
+    let after = state(
+        &["primary"],
+        Settings::builder()
+            .latency_buckets_ms(vec![1.0, 2.0])
+            .build()
+            .unwrap(),
+    );
+    let loaded = snapshot::load(&path, HOUR).unwrap();
+    assert_eq!(snapshot::restore(&after, &loaded), 1);
+
+    let stats = after.stats().validator("primary");
+    assert_eq!(stats.requests_total(), 1);
+    // Counts for other bounds would land in the wrong buckets.
+    assert_eq!(stats.latency_histogram().count(), 0);
+}
+
+#[actix_web::test]
+async fn persists_to_the_configured_path() {
+    let dir = scratch_dir("persist");
+    let path = dir.join("stats.json");
+    let state = state(
+        &["primary"],
+        Settings::builder()
+            .stats_snapshot_path(path.clone())
+            .build()
+            .unwrap(),
+    );
+    state
+        .stats()
+        .validator("primary")
+        .record_response(StatusCode::OK, Duration::from_millis(3));
+
+    snapshot::persist(&state);
+    let saved = snapshot::load(&path, HOUR).unwrap();
+    assert_eq!(saved.validators["primary"].requests, 1);
+}
@@ -0,0 +1,155 @@
+#![cfg(feature = "statsd")]
+
+mod common;
+
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use actix_web::{HttpResponse, test, web};
+use serde_json::json;
+use url::Url;
+
+use the_solana_api::config::Settings;
+use the_solana_api::{AppState, Validator};
+
+fn agent() -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    socket
+}
+
+fn receive(agent: &UdpSocket) -> Vec<String> {
+    let mut buf = [0u8; 2048];
+    let len = agent.recv(&mut buf).unwrap();
+    String::from_utf8(buf[..len].to_vec())
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+fn state(agent: &UdpSocket, validators: Vec<Validator>) -> AppState {
+    AppState::from_settings(
+        common::registry(validators),
+        Settings::builder()
+            .statsd_address(agent.local_addr().unwrap().to_string())
+            .statsd_prefix("proxy".into())
+            .statsd_tags(vec!["env:ci".into()])
+            .build()
+            .unwrap(),
+    )
+}
+
+fn get_slot(server: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri(&format!("/?server={server}"))
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+}
+
+#[actix_web::test]
+async fn sends_timings_per_call_and_counters_on_flush() {
+    let upstream = common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                HttpResponse::Ok()
+                    .content_type("application/json")
+                    .body(r#"{"jsonrpc":"2.0","id":1,"result":42}"#)
+            }),
+        );
+    });
+    let agent = agent();
+    let state = state(&agent, vec![upstream.validator("primary", "frankfurt")]);
+    let app = common::init_app(state.clone()).await;
+
+    let resp = test::call_service(&app, get_slot("primary").to_request()).await;
+    assert!(resp.status().is_success());
+
+    let timing = receive(&agent);
+    assert_eq!(timing.len(), 1);
+    let (value, tags) = timing[0]
+        .strip_prefix("proxy.upstream.latency:")
+        .unwrap()
+        .split_once("|ms|#")
+        .unwrap();
+    assert!(value.parse::<f64>().unwrap() > 0.0);
+    assert_eq!(tags, "env:ci,validator:primary");
+
+    let statsd = state.statsd().unwrap();
+    statsd.flush(&state);
+    let sent = json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"})
+        .to_string()
+        .len();
+    assert_eq!(
+        receive(&agent),
+        [
+            "proxy.upstream.requests:1|c|#env:ci,validator:primary".to_string(),
+            "proxy.upstream.successes:1|c|#env:ci,validator:primary".to_string(),
+            format!("proxy.upstream.bytes_sent:{sent}|c|#env:ci,validator:primary"),
+            "proxy.upstream.bytes_received:36|c|#env:ci,validator:primary".to_string(),
+            "proxy.upstream.in_flight:0|g|#env:ci,validator:primary".to_string(),
+        ]
+    );
+
+    // Counters only carry what happened since the last flush.
+    statsd.flush(&state);
+    assert_eq!(
+        receive(&agent),
+        ["proxy.upstream.in_flight:0|g|#env:ci,validator:primary"]
+    );
+}
+
+#[actix_web::test]
+async fn reports_failures_by_kind() {
+    let agent = agent();
+    let state = state(
+        &agent,
+        vec![Validator::new(
+            "down".into(),
+            "lab".into(),
+            Url::parse("http://127.0.0.1:1/").unwrap(),
+        )],
+    );
+    let app = common::init_app(state.clone()).await;
+
+    let resp = test::call_service(&app, get_slot("down").to_request()).await;
+    assert!(resp.status().is_server_error());
+
+    state.statsd().unwrap().flush(&state);
+    let lines = receive(&agent);
+    assert!(
+        lines.contains(
+            &"proxy.upstream.failures:1|c|#env:ci,validator:down,kind:unavailable".into()
+        ),
+        "{lines:?}"
+    );
+    assert!(!lines.iter().any(|line| line.contains("latency")));
+}
+
+#[actix_web::test]
+async fn a_missing_agent_never_fails_requests() {
+    let upstream = common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async { HttpResponse::Ok().json(json!({"result": 1})) }),
+        );
+    });
+    // Nothing listens there once the socket is dropped.
+    let address = agent().local_addr().unwrap();
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("primary", "frankfurt")]),
+        Settings::builder()
+            .statsd_address(address.to_string())
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state.clone()).await;
+
+    for _ in 0..3 {
+        let resp = test::call_service(&app, get_slot("primary").to_request()).await;
+        assert!(resp.status().is_success());
+        state.statsd().unwrap().flush(&state);
+    }
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::config::Settings;
+use the_solana_api::stats::FailureKind;
+use the_solana_api::status_policy::{EXCERPT_BYTES, StatusPolicies, StatusPolicy};
+use the_solana_api::{AppState, status_policy};
+
+const PAGE: &str = "<html><body>502 Bad Gateway</body></html>";
+
+fn answering(status: StatusCode, body: &'static str) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        cfg.route(
+            "/",
+            web::post().to(move || async move {
+                HttpResponse::build(status)
+                    .content_type("text/html")
+                    .body(body)
+            }),
+        );
+    })
+}
+
+fn state(upstream: &common::MockUpstream, policy: StatusPolicies) -> AppState {
+    AppState::from_settings(
+        common::registry(vec![upstream.validator("node", "lab")]),
+        Settings::builder()
+            .max_retries(0)
+            .upstream_error_policy(policy)
+            .build()
+            .unwrap(),
+    )
+}
+
+fn get_slot() -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+}
+
+#[actix_web::test]
+async fn passes_errors_through_by_default() {
+    let upstream = answering(StatusCode::INTERNAL_SERVER_ERROR, PAGE);
+    let app = common::init_app(state(&upstream, StatusPolicies::default())).await;
+
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "text/html");
+    assert_eq!(test::read_body(resp).await, PAGE);
+}
+
+#[actix_web::test]
+async fn wraps_errors_into_our_body() {
+    let upstream = answering(StatusCode::INTERNAL_SERVER_ERROR, PAGE);
+    let state = state(
+        &upstream,
+        StatusPolicies::new(StatusPolicy::Wrap, Vec::new()),
+    );
+    let app = common::init_app(state.clone()).await;
+
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "upstream_bad_status");
+    assert_eq!(
+        body["error"],
+        "upstream request failed: node 'node' returned 500 Internal Server Error"
+    );
+    assert_eq!(body["details"]["validator"], "node");
+    assert_eq!(body["details"]["status"], 500);
+    assert_eq!(body["details"]["body_excerpt"], PAGE);
+    assert_eq!(
+        state
+            .stats()
+            .validator("node")
+            .failures(FailureKind::HttpStatus),
+        1
+    );
+}
+
+#[actix_web::test]
+async fn overrides_pass_chosen_statuses_through() {
+    let upstream = answering(StatusCode::TOO_MANY_REQUESTS, "slow down");
+    let policy = StatusPolicies::new(
+        StatusPolicy::Wrap,
+        vec![(StatusCode::TOO_MANY_REQUESTS, StatusPolicy::Passthrough)],
+    );
+    let app = common::init_app(state(&upstream, policy)).await;
+
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(test::read_body(resp).await, "slow down");
+}
+
+#[actix_web::test]
+async fn overrides_wrap_chosen_statuses() {
+    let upstream = answering(StatusCode::SERVICE_UNAVAILABLE, PAGE);
+    let policy = StatusPolicies::new(
+        StatusPolicy::Passthrough,
+        vec![(StatusCode::SERVICE_UNAVAILABLE, StatusPolicy::Wrap)],
+    );
+    let app = common::init_app(state(&upstream, policy)).await;
+
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["details"]["status"], 503);
+}
+
+#[actix_web::test]
+async fn successes_are_never_wrapped() {
+    let policy = StatusPolicies::new(StatusPolicy::Wrap, Vec::new());
+    assert!(!policy.wraps(StatusCode::OK));
+    assert!(!policy.wraps(StatusCode::NOT_MODIFIED));
+    assert!(policy.wraps(StatusCode::NOT_FOUND));
+}
+
+#[actix_web::test]
+async fn long_bodies_are_cut_short() {
+    let body = "x".repeat(EXCERPT_BYTES * 4);
+    let excerpt = status_policy::excerpt(body.as_bytes());
+    assert_eq!(excerpt, format!("{}…", "x".repeat(EXCERPT_BYTES)));
+    assert_eq!(status_policy::excerpt(b"  short\n"), "short");
+}
+
+#[actix_web::test]
+async fn parses_overrides() {
+    assert_eq!(
+        StatusPolicies::parse_overrides("429:passthrough, 502:wrap"),
+        Some(vec![
+            (StatusCode::TOO_MANY_REQUESTS, StatusPolicy::Passthrough),
+            (StatusCode::BAD_GATEWAY, StatusPolicy::Wrap),
+        ])
+    );
+    assert_eq!(StatusPolicies::parse_overrides(""), Some(Vec::new()));
+    for raw in ["429", "200:wrap", "abc:wrap", "500:drop"] {
+        assert!(StatusPolicies::parse_overrides(raw).is_none(), "{raw}");
+    }
+}
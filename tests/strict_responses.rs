@@ -0,0 +1,108 @@
+mod common;
+
+use actix_web::{HttpResponse, http::StatusCode, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::AppState;
+use the_solana_api::config::Settings;
+use the_solana_api::jsonrpc;
+
+const HTML: &str = "<html><body>502 Bad Gateway</body></html>";
+
+fn upstream(body: &'static str) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        cfg.route(
+            "/",
+            web::post()
+                .to(move || async move { HttpResponse::Ok().content_type("text/html").body(body) }),
+        );
+    })
+}
+
+fn state(node: &common::MockUpstream, strict: bool, max_bytes: usize) -> AppState {
+    let settings = Settings::builder()
+        .strict_responses(strict)
+        .strict_response_max_bytes(max_bytes)
+        .build()
+        .unwrap();
+    AppState::from_settings(
+        common::registry(vec![node.validator("primary", "lab")]),
+        settings,
+    )
+}
+
+async fn get_slot(state: AppState) -> (StatusCode, String) {
+    let app = common::init_app(state).await;
+    let response = test::call_service(
+        &app,
+        test::TestRequest::post()
+            .uri("/")
+            .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+            .to_request(),
+    )
+    .await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+#[actix_web::test]
+async fn recognizes_well_formed_responses() {
+    assert!(jsonrpc::is_well_formed(
+        br#"{"jsonrpc":"2.0","id":1,"result":null}"#
+    ));
+    assert!(jsonrpc::is_well_formed(
+        br#"{"id":1,"error":{"code":-32600}}"#
+    ));
+    assert!(jsonrpc::is_well_formed(
+        br#"[{"id":1,"result":1},{"id":2,"error":{}}]"#
+    ));
+
+    assert!(!jsonrpc::is_well_formed(HTML.as_bytes()));
+    assert!(!jsonrpc::is_well_formed(br#"{"id":1}"#));
+    assert!(!jsonrpc::is_well_formed(b"[]"));
+    assert!(!jsonrpc::is_well_formed(
+        br#"[{"id":1,"result":1},{"id":2}]"#
+    ));
+    assert!(!jsonrpc::is_well_formed(b"42"));
+}
+
+#[actix_web::test]
+async fn malformed_responses_are_relayed_by_default() {
+    let node = upstream(HTML);
+    let (status, body) = get_slot(AppState::new(common::registry(vec![
+        node.validator("primary", "lab"),
+    ])))
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, HTML);
+}
+
+#[actix_web::test]
+async fn strict_mode_turns_malformed_responses_into_bad_gateway() {
+    let node = upstream(HTML);
+    let state = state(&node, true, 1024);
+    let (status, body) = get_slot(state.clone()).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    let error = body["error"].as_str().unwrap();
+    assert!(error.contains("'primary'"), "{error}");
+    assert!(error.contains("malformed"), "{error}");
+
+    assert_eq!(state.stats().validator("primary").invalid_responses(), 1);
+}
+
+#[actix_web::test]
+async fn strict_mode_passes_valid_and_oversized_responses() {
+    let node = upstream(r#"{"jsonrpc":"2.0","id":1,"result":7}"#);
+    let (status, body) = get_slot(state(&node, true, 1024)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["result"], 7);
+
+    let node = upstream(HTML);
+    let state = state(&node, true, 16);
+    let (status, body) = get_slot(state.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, HTML);
+    assert_eq!(state.stats().validator("primary").invalid_responses(), 0);
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use actix_web::http::{StatusCode, header};
+use actix_web::{HttpRequest, HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::AppState;
+use the_solana_api::proxy::normalize_sub_path;
+
+const SNAPSHOT_SIZE: usize = 4 * 1024 * 1024;
+
+fn sub_path_upstream() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/rpc/v2",
+            web::post().to(|req: HttpRequest, body: web::Bytes| async move {
+                let payload: Value = serde_json::from_slice(&body).unwrap();
+                HttpResponse::Ok().json(json!({
+                    "path": req.path(),
+                    "query": req.query_string(),
+                    "method": payload["method"],
+                }))
+            }),
+        )
+        .route(
+            "/snapshot.tar.bz2",
+            web::get().to(|| async {
+                HttpResponse::Ok()
+                    .content_type("application/x-bzip2")
+                    .body(vec![7u8; SNAPSHOT_SIZE])
+            }),
+        );
+    })
+}
+
+#[actix_web::test]
+async fn normalizes_and_rejects_sub_paths() {
+    assert_eq!(normalize_sub_path("").unwrap(), "/");
+    assert_eq!(
+        normalize_sub_path("/snapshot.tar.bz2").unwrap(),
+        "/snapshot.tar.bz2"
+    );
+    assert_eq!(normalize_sub_path("//rpc//v2/").unwrap(), "/rpc/v2/");
+    assert_eq!(normalize_sub_path("/a%20b").unwrap(), "/a%20b");
+
+    for raw in [
+        "/../etc/passwd",
+        "/rpc/./v2",
+        "/%2e%2e/secret",
+        "/%2E./secret",
+        "/rpc%2Fv2",
+        "/rpc%5cv2",
+        "/rpc\\v2",
+    ] {
+        assert!(normalize_sub_path(raw).is_err(), "accepted {raw:?}");
+    }
+}
+
+#[actix_web::test]
+async fn forwards_json_rpc_to_sub_path() {
+    let upstream = sub_path_upstream();
+    let state = AppState::new(common::registry(vec![upstream.validator("sub-1", "lab")]));
+    let app = common::init_app(state).await;
+
+    let request = test::TestRequest::post()
+        .uri("/upstream/rpc/v2?server=sub-1&commitment=finalized&location=lab")
+        .set_json(json!({ "jsonrpc": "2.0", "id": 1, "method": "getSlot" }))
+        .to_request();
+    let response: Value = test::call_and_read_body_json(&app, request).await;
+
+    assert_eq!(response["path"], "/rpc/v2");
+    assert_eq!(response["query"], "commitment=finalized");
+    assert_eq!(response["method"], "getSlot");
+}
+
+#[actix_web::test]
+async fn streams_file_downloads() {
+    let upstream = sub_path_upstream();
+    let state = AppState::new(common::registry(vec![upstream.validator("sub-1", "lab")]));
+    let app = common::init_app(state).await;
+
+    let request = test::TestRequest::get()
+        .uri("/upstream/snapshot.tar.bz2")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(header::CONTENT_TYPE).unwrap(),
+        "application/x-bzip2"
+    );
+    let body = test::read_body(response).await;
+    assert_eq!(body.len(), SNAPSHOT_SIZE);
+}
+
+#[actix_web::test]
+async fn rejects_path_traversal() {
+    let upstream = sub_path_upstream();
+    let state = AppState::new(common::registry(vec![upstream.validator("sub-1", "lab")]));
+    let app = common::init_app(state).await;
+
+    for uri in ["/upstream/%2e%2e/secret", "/upstream/rpc%2Fv2"] {
+        let request = test::TestRequest::get().uri(uri).to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+    }
+}
@@ -0,0 +1,211 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+
+use serde_json::{Value, json};
+
+use the_solana_api::subscriptions::{Attach, Member, SubscriptionHub, SubscriptionKey};
+
+fn account_key(commitment: &str, validator: Option<&str>) -> SubscriptionKey {
+    SubscriptionKey::new(
+        "accountSubscribe",
+        &json!(["Vote111111111111111111111111111111111111111", {"encoding": "base64", "commitment": commitment}]),
+        validator,
+    )
+}
+
+fn notification(upstream_id: u64) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "accountNotification",
+        "params": {"result": {"context": {"slot": 5}, "value": null}, "subscription": upstream_id}
+    })
+}
+
+#[test]
+fn identical_subscriptions_share_one_upstream_subscription() {
+    let hub = SubscriptionHub::new();
+    let first = hub.subscribe(1, account_key("confirmed", None));
+    let second = hub.subscribe(2, account_key("confirmed", None));
+    assert_eq!(first.attach, Attach::Open);
+    assert_eq!(second.attach, Attach::Shared);
+    assert_ne!(first.subscription, second.subscription);
+
+    let reordered = SubscriptionKey::new(
+        "accountSubscribe",
+        &serde_json::from_str(
+            r#"["Vote111111111111111111111111111111111111111", {"commitment": "confirmed", "encoding": "base64"}]"#,
+        )
+        .unwrap(),
+        None,
+    );
+    assert_eq!(hub.subscribe(3, reordered).attach, Attach::Shared);
+
+    for key in [
+        account_key("finalized", None),
+        account_key("confirmed", Some("frankfurt-1")),
+    ] {
+        assert_eq!(hub.subscribe(4, key).attach, Attach::Open);
+    }
+    assert_eq!(hub.upstream_subscriptions(), 3);
+    assert_eq!(hub.client_subscriptions(), 5);
+}
+
+#[test]
+fn notifications_are_translated_per_client() {
+    let hub = SubscriptionHub::new();
+    let key = account_key("confirmed", None);
+    let first = hub.subscribe(1, key.clone());
+    let second = hub.subscribe(2, key.clone());
+    assert!(hub.confirm(&key, 77));
+
+    let copies = hub.fan_out(&notification(77));
+    assert_eq!(copies.len(), 2);
+    assert_eq!(copies[0].0, 1);
+    assert_eq!(copies[0].1["params"]["subscription"], first.subscription);
+    assert_eq!(copies[1].0, 2);
+    assert_eq!(copies[1].1["params"]["subscription"], second.subscription);
+    assert_eq!(
+        copies[1].1["params"]["result"],
+        notification(77)["params"]["result"]
+    );
+
+    assert!(hub.fan_out(&notification(78)).is_empty());
+    assert!(hub.fan_out(&json!({"params": {}})).is_empty());
+}
+
+#[test]
+fn upstream_is_closed_when_the_last_client_leaves() {
+    let hub = SubscriptionHub::new();
+    let key = account_key("confirmed", None);
+    let first = hub.subscribe(1, key.clone());
+    let second = hub.subscribe(2, key.clone());
+    hub.confirm(&key, 9);
+
+    assert_eq!(hub.unsubscribe(1, second.subscription), None);
+    assert_eq!(hub.unsubscribe(1, first.subscription), Some(None));
+    assert_eq!(hub.unsubscribe(1, first.subscription), None);
+    assert_eq!(hub.unsubscribe(2, second.subscription), Some(Some(9)));
+    assert_eq!(hub.upstream_subscriptions(), 0);
+    assert!(hub.fan_out(&notification(9)).is_empty());
+}
+
+#[test]
+fn disconnecting_a_client_releases_its_subscriptions() {
+    let hub = SubscriptionHub::new();
+    let shared = account_key("confirmed", None);
+    let own = account_key("finalized", None);
+    hub.subscribe(1, shared.clone());
+    hub.subscribe(1, own.clone());
+    hub.subscribe(2, shared.clone());
+    hub.confirm(&shared, 10);
+    hub.confirm(&own, 11);
+
+    assert_eq!(hub.disconnect(1), vec![11]);
+    assert_eq!(hub.fan_out(&notification(10)).len(), 1);
+    assert_eq!(hub.disconnect(2), vec![10]);
+    assert_eq!(hub.client_subscriptions(), 0);
+}
+
+#[test]
+fn late_confirmation_and_failures_are_reported() {
+    let hub = SubscriptionHub::new();
+    let key = account_key("confirmed", None);
+    let first = hub.subscribe(1, key.clone());
+    assert_eq!(hub.unsubscribe(1, first.subscription), Some(None));
+    assert!(!hub.confirm(&key, 3));
+    assert!(hub.fan_out(&notification(3)).is_empty());
+
+    let first = hub.subscribe(1, key.clone());
+    let second = hub.subscribe(2, key.clone());
+    assert_eq!(
+        hub.fail(&key),
+        vec![
+            Member {
+                client: 1,
+                subscription: first.subscription
+            },
+            Member {
+                client: 2,
+                subscription: second.subscription
+            },
+        ]
+    );
+    assert_eq!(hub.client_subscriptions(), 0);
+    assert_eq!(hub.subscribe(3, key).attach, Attach::Open);
+}
+
+#[test]
+fn lost_upstreams_are_resubscribed_with_the_same_client_ids() {
+    let hub = SubscriptionHub::new();
+    let pinned = account_key("confirmed", Some("frankfurt-1"));
+    let other = account_key("confirmed", Some("tokyo-1"));
+    let member = hub.subscribe(1, pinned.clone());
+    hub.subscribe(2, other.clone());
+    hub.confirm(&pinned, 20);
+    hub.confirm(&other, 21);
+
+    let lost = hub.upstream_lost(Some("Frankfurt-1"));
+    assert_eq!(lost.len(), 1);
+    assert_eq!(lost[0].key, pinned);
+    assert_eq!(
+        lost[0].members,
+        vec![Member {
+            client: 1,
+            subscription: member.subscription
+        }]
+    );
+    assert!(hub.fan_out(&notification(20)).is_empty());
+    assert_eq!(hub.fan_out(&notification(21)).len(), 1);
+
+    assert!(hub.confirm(&pinned, 30));
+    let copies = hub.fan_out(&notification(30));
+    assert_eq!(copies[0].1["params"]["subscription"], member.subscription);
+
+    assert_eq!(hub.upstream_lost(None).len(), 2);
+    assert!(hub.upstream_lost(None).is_empty());
+}
+
+#[test]
+fn concurrent_clients_open_and_close_each_upstream_once() {
+    let hub = Arc::new(SubscriptionHub::new());
+    let upstream_ids = Arc::new(AtomicU64::new(1));
+    let opened = Arc::new(AtomicU64::new(0));
+    let closed = Arc::new(AtomicU64::new(0));
+    let keys = ["processed", "confirmed", "finalized"];
+
+    let workers: Vec<_> = (0..8)
+        .map(|client| {
+            let hub = hub.clone();
+            let upstream_ids = upstream_ids.clone();
+            let opened = opened.clone();
+            let closed = closed.clone();
+            thread::spawn(move || {
+                for round in 0..200 {
+                    let key = account_key(keys[(client + round) % keys.len()], None);
+                    let subscribed = hub.subscribe(client as u64, key.clone());
+                    if subscribed.attach == Attach::Open {
+                        opened.fetch_add(1, Ordering::SeqCst);
+                        assert!(hub.confirm(&key, upstream_ids.fetch_add(1, Ordering::SeqCst)));
+                    }
+                    hub.fan_out(&notification(round as u64));
+                    match hub.unsubscribe(client as u64, subscribed.subscription) {
+                        Some(Some(_)) => {
+                            closed.fetch_add(1, Ordering::SeqCst);
+                        }
+                        Some(None) => {}
+                        None => panic!("subscription vanished"),
+                    }
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    assert!(opened.load(Ordering::SeqCst) > 0);
+    assert_eq!(opened.load(Ordering::SeqCst), closed.load(Ordering::SeqCst));
+    assert_eq!(hub.upstream_subscriptions(), 0);
+    assert_eq!(hub.client_subscriptions(), 0);
+}
@@ -0,0 +1,138 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use actix_web::{App, HttpResponse, test as actix_test, web};
+use serde_json::Value;
+use tracing::{info, info_span};
+use tracing_subscriber::layer::SubscriberExt;
+
+use the_solana_api::telemetry::{self, LogFormat};
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Formats one access-style event inside a request span.
+fn emit(format: LogFormat, ansi: bool) -> String {
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let subscriber = tracing_subscriber::registry().with(telemetry::fmt_layer(
+        format,
+        move || writer.clone(),
+        ansi,
+    ));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = info_span!("request", request_id = "abc-123");
+        let _entered = span.enter();
+        info!(target: "access_log", status = 200, validator = "primary", "access");
+    });
+
+    String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap()
+}
+
+#[test]
+fn json_lines_flatten_event_fields_and_carry_spans() {
+    let output = emit(LogFormat::Json, false);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 1);
+
+    let line: Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(line["level"], "INFO");
+    assert_eq!(line["target"], "access_log");
+    assert_eq!(line["message"], "access");
+    assert_eq!(line["status"], 200);
+    assert_eq!(line["validator"], "primary");
+    assert_eq!(line["span"]["name"], "request");
+    assert_eq!(line["span"]["request_id"], "abc-123");
+    assert_eq!(line["spans"][0]["request_id"], "abc-123");
+    assert!(!output.contains('\u{1b}'));
+}
+
+#[test]
+fn human_formats_follow_the_ansi_switch() {
+    for format in [LogFormat::Compact, LogFormat::Pretty] {
+        let plain = emit(format, false);
+        assert!(plain.contains("access"), "{plain}");
+        assert!(plain.contains("abc-123"), "{plain}");
+        assert!(!plain.contains('\u{1b}'), "{plain}");
+
+        assert!(emit(format, true).contains('\u{1b}'));
+    }
+}
+
+#[test]
+fn json_never_uses_colors() {
+    assert!(!LogFormat::Json.ansi());
+}
+
+#[test]
+fn parses_format_names() {
+    assert_eq!("JSON".parse::<LogFormat>(), Ok(LogFormat::Json));
+    assert_eq!("pretty".parse::<LogFormat>(), Ok(LogFormat::Pretty));
+    assert_eq!("compact".parse::<LogFormat>(), Ok(LogFormat::Compact));
+    assert!("xml".parse::<LogFormat>().is_err());
+}
+
+// Keeps every message logged through the `log` facade, which is where
+// actix's request logger writes.
+struct LogLines(Mutex<Vec<String>>);
+
+impl log::Log for LogLines {
+    fn enabled(&self, _: &log::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        self.0.lock().unwrap().push(record.args().to_string());
+    }
+
+    fn flush(&self) {}
+}
+
+fn log_lines() -> &'static LogLines {
+    static LINES: OnceLock<&'static LogLines> = OnceLock::new();
+    LINES.get_or_init(|| {
+        let lines: &'static LogLines = Box::leak(Box::new(LogLines(Mutex::default())));
+        log::set_logger(lines).unwrap();
+        log::set_max_level(log::LevelFilter::Info);
+        lines
+    })
+}
+
+#[actix_web::test]
+async fn request_logger_redacts_api_keys_in_the_query() {
+    let lines = log_lines();
+    let app = actix_test::init_service(
+        App::new()
+            .wrap(telemetry::request_logger())
+            .route("/", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    let req = actix_test::TestRequest::get()
+        .uri("/?commitment=finalized&api_key=s3cret-key")
+        .to_request();
+    actix_test::call_and_read_body(&app, req).await;
+
+    let lines = lines.0.lock().unwrap();
+    let line = lines
+        .iter()
+        .find(|line| line.contains("GET /?"))
+        .expect("request line logged");
+    assert!(!line.contains("s3cret-key"), "{line}");
+    assert!(
+        line.contains("GET /?commitment=finalized&api_key=<redacted> HTTP/1.1"),
+        "{line}"
+    );
+}
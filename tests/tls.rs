@@ -0,0 +1,162 @@
+mod common;
+
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{App, HttpServer, web};
+use rustls::pki_types::CertificateDer;
+use rustls::pki_types::pem::PemObject;
+use serde_json::Value;
+use url::Url;
+
+use the_solana_api::tls::{self, CertStore, TlsError, TlsSettings};
+use the_solana_api::{AppState, Validator, routes};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn settings(cert: &str, key: &str) -> TlsSettings {
+    TlsSettings::new(fixture(cert), fixture(key))
+}
+
+// A client trusting only `cert`.
+fn client_trusting(cert: &str) -> awc::Client {
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(fixture(cert)).unwrap() {
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    awc::Client::builder()
+        .connector(awc::Connector::new().rustls_0_23(Arc::new(config)))
+        .finish()
+}
+
+fn state() -> AppState {
+    AppState::new(common::registry(vec![Validator::new(
+        "node".into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:9/").unwrap(),
+    )]))
+}
+
+#[actix_web::test]
+async fn serves_https_with_the_configured_certificate() {
+    let certs = Arc::new(CertStore::load(settings("tls_cert.pem", "tls_key.pem")).unwrap());
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let app_state = state();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(app_state.clone()))
+            .configure(routes::configure)
+    })
+    .workers(1)
+    .listen_rustls_0_23(listener, certs.server_config())
+    .unwrap()
+    .run();
+    let server = tokio::spawn(server);
+
+    let mut response = client_trusting("tls_cert.pem")
+        .get(format!("https://localhost:{port}/health"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "ok");
+
+    // A client that trusts another certificate refuses the handshake.
+    let refused = client_trusting("tls_other_cert.pem")
+        .get(format!("https://localhost:{port}/health"))
+        .send()
+        .await;
+    assert!(refused.is_err());
+    server.abort();
+}
+
+#[actix_web::test]
+async fn bad_files_are_reported_clearly() {
+    let err = tls::load_certified_key(&settings("tls_cert.pem", "tls_other_key.pem")).unwrap_err();
+    assert!(matches!(err, TlsError::KeyMismatch), "{err}");
+    assert_eq!(
+        err.to_string(),
+        "TLS_KEY_FILE does not match the certificate in TLS_CERT_FILE"
+    );
+
+    // The certificate where the key should be, and the other way round.
+    let err = tls::load_certified_key(&settings("tls_cert.pem", "tls_cert.pem")).unwrap_err();
+    assert!(matches!(err, TlsError::NoPrivateKey(_)), "{err}");
+    let err = tls::load_certified_key(&settings("tls_key.pem", "tls_key.pem")).unwrap_err();
+    assert!(matches!(err, TlsError::NoCertificate(_)), "{err}");
+
+    let err = tls::load_certified_key(&settings("missing.pem", "tls_key.pem")).unwrap_err();
+    assert!(matches!(err, TlsError::Read { .. }), "{err}");
+    assert!(err.to_string().contains("missing.pem"), "{err}");
+
+    let dir = std::env::temp_dir().join(format!("solana-api-tls-bad-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let truncated = dir.join("cert.pem");
+    std::fs::write(&truncated, "-----BEGIN CERTIFICATE-----\nMIIB\n").unwrap();
+    let err =
+        tls::load_certified_key(&TlsSettings::new(&truncated, fixture("tls_key.pem"))).unwrap_err();
+    assert!(matches!(err, TlsError::Pem { .. }), "{err}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[actix_web::test]
+async fn renewed_certificates_are_picked_up() {
+    let dir = std::env::temp_dir().join(format!("solana-api-tls-reload-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (cert, key) = (dir.join("cert.pem"), dir.join("key.pem"));
+    std::fs::copy(fixture("tls_cert.pem"), &cert).unwrap();
+    std::fs::copy(fixture("tls_key.pem"), &key).unwrap();
+
+    let store = CertStore::load(TlsSettings::new(&cert, &key)).unwrap();
+    let first = store.current();
+    assert!(store.reload_if_changed().is_none());
+
+    // A half-written renewal keeps the working certificate.
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    std::fs::copy(fixture("tls_other_cert.pem"), &cert).unwrap();
+    assert!(matches!(
+        store.reload_if_changed(),
+        Some(Err(TlsError::KeyMismatch))
+    ));
+    assert_eq!(store.current().cert, first.cert);
+
+    std::fs::copy(fixture("tls_other_key.pem"), &key).unwrap();
+    assert!(matches!(store.reload_if_changed(), Some(Ok(()))));
+    assert_ne!(store.current().cert, first.cert);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[actix_web::test]
+async fn the_health_listener_serves_health_alone() {
+    let app = actix_web::test::init_service(
+        App::new()
+            .app_data(web::Data::new(state()))
+            .configure(routes::configure_health),
+    )
+    .await;
+    for (uri, status) in [
+        ("/health", StatusCode::OK),
+        ("/", StatusCode::NOT_FOUND),
+        ("/stats", StatusCode::NOT_FOUND),
+    ] {
+        let req = actix_web::test::TestRequest::get().uri(uri).to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status, "{uri}");
+    }
+}
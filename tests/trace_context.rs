@@ -0,0 +1,126 @@
+mod common;
+
+use actix_web::http::header::HeaderMap;
+use actix_web::{HttpRequest, HttpResponse, test, web};
+use serde_json::json;
+
+use the_solana_api::AppState;
+use the_solana_api::trace_context::TraceParent;
+
+const INBOUND: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+#[actix_web::test]
+async fn parses_valid_traceparent() {
+    let parsed = TraceParent::parse(INBOUND).unwrap();
+
+    assert_eq!(parsed.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(parsed.parent_id(), "00f067aa0ba902b7");
+    assert!(parsed.sampled());
+    assert_eq!(parsed.to_string(), INBOUND);
+}
+
+#[actix_web::test]
+async fn rejects_malformed_traceparent() {
+    for value in [
+        "",
+        "garbage",
+        "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        "00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01",
+    ] {
+        assert!(TraceParent::parse(value).is_none(), "accepted {value:?}");
+    }
+}
+
+#[actix_web::test]
+async fn accepts_future_versions_with_extra_fields() {
+    let parsed =
+        TraceParent::parse("cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-what").unwrap();
+    assert_eq!(parsed.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+}
+
+#[actix_web::test]
+async fn child_extends_the_trace() {
+    let parent = TraceParent::parse(INBOUND).unwrap();
+    let child = parent.child();
+
+    assert_eq!(child.trace_id(), parent.trace_id());
+    assert_ne!(child.parent_id(), parent.parent_id());
+    assert_eq!(child.sampled(), parent.sampled());
+}
+
+#[actix_web::test]
+async fn generates_fresh_context_for_missing_or_malformed_headers() {
+    let generated = TraceParent::from_headers(&HeaderMap::new());
+    assert_eq!(TraceParent::parse(&generated.to_string()), Some(generated));
+
+    let mut headers = HeaderMap::new();
+    headers.insert("traceparent".parse().unwrap(), "nonsense".parse().unwrap());
+    let fresh = TraceParent::from_headers(&headers);
+    assert_ne!(fresh.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+}
+
+fn echo_traceparent_upstream() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|req: HttpRequest| async move {
+                let traceparent = req
+                    .headers()
+                    .get("traceparent")
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string();
+                HttpResponse::Ok().body(traceparent)
+            }),
+        );
+    })
+}
+
+#[actix_web::test]
+async fn propagates_inbound_trace_to_upstream_and_client() {
+    let upstream = echo_traceparent_upstream();
+    let state = AppState::new(common::registry(vec![upstream.validator("trace-1", "lab")]));
+    let app = common::init_app(state).await;
+
+    let request = test::TestRequest::post()
+        .uri("/")
+        .insert_header(("traceparent", INBOUND))
+        .set_json(json!({ "jsonrpc": "2.0", "id": 1, "method": "getSlot" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+
+    let returned = response
+        .headers()
+        .get("traceparent")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    let returned = TraceParent::parse(returned).unwrap();
+    let forwarded = test::read_body(response).await;
+    let forwarded = TraceParent::parse(std::str::from_utf8(&forwarded).unwrap()).unwrap();
+
+    assert_eq!(forwarded.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_ne!(forwarded.parent_id(), "00f067aa0ba902b7");
+    assert_eq!(returned, forwarded);
+}
+
+#[actix_web::test]
+async fn starts_a_trace_when_client_sends_none() {
+    let upstream = echo_traceparent_upstream();
+    let state = AppState::new(common::registry(vec![upstream.validator("trace-1", "lab")]));
+    let app = common::init_app(state).await;
+
+    let request = test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({ "jsonrpc": "2.0", "id": 1, "method": "getSlot" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+
+    let returned = response.headers().get("traceparent").unwrap().clone();
+    let forwarded = test::read_body(response).await;
+    assert_eq!(returned.as_bytes(), &forwarded[..]);
+}
@@ -0,0 +1,203 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use actix_web::http::{StatusCode, header};
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::proxy::retry_after;
+use the_solana_api::{AppState, Settings};
+
+fn rate_limited_upstream(
+    hits: Arc<AtomicUsize>,
+    retry_after: Option<&'static str>,
+) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let hits = hits.clone();
+        cfg.route(
+            "/",
+            web::post().to(move || {
+                hits.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    let mut response = HttpResponse::TooManyRequests();
+                    if let Some(value) = retry_after {
+                        response.insert_header((header::RETRY_AFTER, value));
+                    }
+                    response.json(json!({ "error": "slow down" }))
+                }
+            }),
+        );
+    })
+}
+
+fn healthy_upstream() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async { HttpResponse::Ok().json(json!({ "result": "ok" })) }),
+        );
+    })
+}
+
+fn rpc_request(uri: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri(uri)
+        .set_json(json!({ "jsonrpc": "2.0", "id": 1, "method": "getSlot" }))
+        .to_request()
+}
+
+async fn cooldown_ms(
+    app: &impl actix_web::dev::Service<
+        actix_http::Request,
+        Response = actix_web::dev::ServiceResponse,
+        Error = actix_web::Error,
+    >,
+    name: &str,
+) -> Value {
+    let stats: Value =
+        test::call_and_read_body_json(app, test::TestRequest::get().uri("/stats").to_request())
+            .await;
+    stats["validators"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["name"] == name)
+        .map(|entry| entry["cooldown_remaining_ms"].clone())
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn fails_over_and_avoids_rate_limited_node() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let limited = rate_limited_upstream(hits.clone(), Some("30"));
+    let healthy = healthy_upstream();
+
+    let state = AppState::new(common::registry(vec![
+        limited.validator("limited-1", "lab"),
+        healthy.validator("healthy-1", "lab"),
+    ]));
+    let app = common::init_app(state).await;
+
+    for _ in 0..10 {
+        let response = test::call_service(&app, rpc_request("/?location=lab")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    assert!(hits.load(Ordering::SeqCst) <= 1);
+}
+
+#[actix_web::test]
+async fn passes_429_through_with_retry_after_when_no_alternative() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let limited = rate_limited_upstream(hits.clone(), Some("30"));
+
+    let state = AppState::new(common::registry(vec![
+        limited.validator("limited-1", "lab"),
+    ]));
+    let app = common::init_app(state).await;
+
+    let response = test::call_service(&app, rpc_request("/?server=limited-1")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "30");
+
+    let cooldown = cooldown_ms(&app, "limited-1").await.as_u64().unwrap();
+    assert!(cooldown > 25_000 && cooldown <= 30_000);
+}
+
+#[actix_web::test]
+async fn uses_default_cooldown_without_retry_after() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let limited = rate_limited_upstream(hits.clone(), None);
+
+    let settings = Settings::builder()
+        .default_429_cooldown(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let state = AppState::from_settings(
+        common::registry(vec![limited.validator("limited-1", "lab")]),
+        settings,
+    );
+    let app = common::init_app(state).await;
+
+    let response = test::call_service(&app, rpc_request("/?server=limited-1")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().get(header::RETRY_AFTER).is_none());
+
+    let cooldown = cooldown_ms(&app, "limited-1").await.as_u64().unwrap();
+    assert!(cooldown > 55_000 && cooldown <= 60_000);
+}
+
+#[actix_web::test]
+async fn switches_disable_cooldown_and_header_forwarding() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let limited = rate_limited_upstream(hits.clone(), Some("30"));
+
+    let settings = Settings::builder()
+        .cooldown_on_429(false)
+        .forward_retry_after(false)
+        .build()
+        .unwrap();
+    let state = AppState::from_settings(
+        common::registry(vec![limited.validator("limited-1", "lab")]),
+        settings,
+    );
+    let app = common::init_app(state).await;
+
+    let response = test::call_service(&app, rpc_request("/?server=limited-1")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().get(header::RETRY_AFTER).is_none());
+    assert!(cooldown_ms(&app, "limited-1").await.is_null());
+}
+
+#[actix_web::test]
+async fn retry_switch_returns_first_429() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let limited = rate_limited_upstream(hits.clone(), Some("30"));
+    let healthy = healthy_upstream();
+
+    let settings = Settings::builder()
+        .retry_on_429(false)
+        .cooldown_on_429(false)
+        .build()
+        .unwrap();
+    let state = AppState::from_settings(
+        common::registry(vec![
+            limited.validator("limited-1", "lab"),
+            healthy.validator("healthy-1", "lab"),
+        ]),
+        settings,
+    );
+    let app = common::init_app(state).await;
+
+    let mut rate_limited = 0;
+    for _ in 0..40 {
+        let response = test::call_service(&app, rpc_request("/?location=lab")).await;
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            rate_limited += 1;
+        }
+    }
+
+    assert_eq!(rate_limited, hits.load(Ordering::SeqCst));
+    assert!(rate_limited > 0);
+}
+
+#[actix_web::test]
+async fn parses_retry_after_values() {
+    let mut headers = header::HeaderMap::new();
+    assert_eq!(retry_after(&headers), None);
+
+    headers.insert(header::RETRY_AFTER, "12".parse().unwrap());
+    assert_eq!(retry_after(&headers), Some(Duration::from_secs(12)));
+
+    headers.insert(header::RETRY_AFTER, "soon".parse().unwrap());
+    assert_eq!(retry_after(&headers), None);
+
+    let future = std::time::SystemTime::now() + Duration::from_secs(120);
+    let date = header::HttpDate::from(future).to_string();
+    headers.insert(header::RETRY_AFTER, date.parse().unwrap());
+    let parsed = retry_after(&headers).unwrap();
+    assert!(parsed > Duration::from_secs(110) && parsed <= Duration::from_secs(120));
+}
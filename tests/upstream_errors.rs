@@ -0,0 +1,176 @@
+mod common;
+
+use std::io;
+
+use actix_http::error::ParseError;
+use actix_web::test;
+use awc::error::{ConnectError, PayloadError, SendRequestError};
+use serde_json::{Value, json};
+use url::Url;
+
+use the_solana_api::errors::{self, UpstreamErrorKind};
+use the_solana_api::stats::FailureKind;
+use the_solana_api::{AppState, Settings, Validator};
+
+fn connect(err: ConnectError) -> SendRequestError {
+    SendRequestError::Connect(err)
+}
+
+fn io_error(kind: io::ErrorKind) -> io::Error {
+    io::Error::new(kind, "boom")
+}
+
+#[actix_web::test]
+async fn send_errors_map_to_their_kind() {
+    let cases = [
+        (SendRequestError::Timeout, UpstreamErrorKind::Timeout),
+        (connect(ConnectError::Timeout), UpstreamErrorKind::Timeout),
+        (
+            connect(ConnectError::Io(io_error(io::ErrorKind::TimedOut))),
+            UpstreamErrorKind::Timeout,
+        ),
+        (
+            SendRequestError::Send(io_error(io::ErrorKind::TimedOut)),
+            UpstreamErrorKind::Timeout,
+        ),
+        (
+            connect(ConnectError::Io(io_error(io::ErrorKind::ConnectionRefused))),
+            UpstreamErrorKind::ConnectRefused,
+        ),
+        (
+            SendRequestError::Send(io_error(io::ErrorKind::ConnectionReset)),
+            UpstreamErrorKind::ConnectRefused,
+        ),
+        (
+            connect(ConnectError::Disconnected),
+            UpstreamErrorKind::ConnectRefused,
+        ),
+        (
+            SendRequestError::TunnelNotSupported,
+            UpstreamErrorKind::ConnectRefused,
+        ),
+        (
+            connect(ConnectError::Resolver(Box::new(io_error(
+                io::ErrorKind::NotFound,
+            )))),
+            UpstreamErrorKind::DnsFailure,
+        ),
+        (
+            connect(ConnectError::NoRecords),
+            UpstreamErrorKind::DnsFailure,
+        ),
+        (
+            connect(ConnectError::Unresolved),
+            UpstreamErrorKind::DnsFailure,
+        ),
+        (
+            connect(ConnectError::SslIsNotSupported),
+            UpstreamErrorKind::TlsError,
+        ),
+        (
+            connect(ConnectError::Io(io_error(io::ErrorKind::InvalidData))),
+            UpstreamErrorKind::TlsError,
+        ),
+        (
+            SendRequestError::Response(ParseError::Header),
+            UpstreamErrorKind::InvalidResponse,
+        ),
+    ];
+    for (err, kind) in cases {
+        assert_eq!(errors::send_failure(&err), kind, "{err}");
+    }
+}
+
+#[actix_web::test]
+async fn payload_errors_map_to_their_kind() {
+    let cases = [
+        (PayloadError::Overflow, UpstreamErrorKind::ResponseTooLarge),
+        (
+            PayloadError::Io(io_error(io::ErrorKind::TimedOut)),
+            UpstreamErrorKind::Timeout,
+        ),
+        (
+            PayloadError::Io(io_error(io::ErrorKind::ConnectionReset)),
+            UpstreamErrorKind::BodyReadError,
+        ),
+        (
+            PayloadError::Incomplete(None),
+            UpstreamErrorKind::BodyReadError,
+        ),
+        (
+            PayloadError::EncodingCorrupted,
+            UpstreamErrorKind::BodyReadError,
+        ),
+    ];
+    for (err, kind) in cases {
+        assert_eq!(errors::payload_failure(&err), kind, "{err}");
+    }
+}
+
+#[actix_web::test]
+async fn kinds_keep_the_coarser_health_accounting() {
+    let cases = [
+        (UpstreamErrorKind::ConnectRefused, FailureKind::Unavailable),
+        (UpstreamErrorKind::DnsFailure, FailureKind::Unavailable),
+        (UpstreamErrorKind::TlsError, FailureKind::Unavailable),
+        (UpstreamErrorKind::Timeout, FailureKind::Timeout),
+        (UpstreamErrorKind::ResponseTooLarge, FailureKind::TooLarge),
+        (UpstreamErrorKind::BodyReadError, FailureKind::Unreadable),
+        (UpstreamErrorKind::BadStatus(429), FailureKind::RateLimited),
+        (UpstreamErrorKind::BadStatus(503), FailureKind::HttpStatus),
+        (
+            UpstreamErrorKind::InvalidResponse,
+            FailureKind::InvalidResponse,
+        ),
+    ];
+    for (kind, failure) in cases {
+        assert_eq!(kind.failure(), failure, "{kind:?}");
+        assert_eq!(UpstreamErrorKind::LABELS[kind.index()], kind.as_str());
+    }
+    assert_eq!(UpstreamErrorKind::BadStatus(503).as_str(), "bad_status");
+}
+
+#[actix_web::test]
+async fn refused_connections_are_counted_by_kind() {
+    let validator = Validator::new(
+        "dead".into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:9/").unwrap(),
+    );
+    let state = AppState::from_settings(
+        common::registry(vec![validator]),
+        Settings::builder()
+            .max_retries(0)
+            .metrics_enabled(true)
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state.clone()).await;
+
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::post()
+            .uri("/")
+            .insert_header(("content-type", "application/json"))
+            .set_payload(r#"{"jsonrpc":"2.0","id":1,"method":"getSlot"}"#)
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), 502);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], json!("upstream_connect_failed"));
+
+    let stats = state.stats().validator("dead");
+    assert_eq!(stats.upstream_errors(UpstreamErrorKind::ConnectRefused), 1);
+    assert_eq!(stats.failures(FailureKind::Unavailable), 1);
+
+    let metrics =
+        test::call_and_read_body(&app, test::TestRequest::get().uri("/metrics").to_request()).await;
+    let metrics = String::from_utf8(metrics.to_vec()).unwrap();
+    assert!(metrics.contains(
+        "solana_api_upstream_errors_total{validator=\"dead\",kind=\"connect_refused\"} 1"
+    ));
+    assert!(
+        metrics.contains("solana_api_upstream_errors_total{validator=\"dead\",kind=\"timeout\"} 0")
+    );
+}
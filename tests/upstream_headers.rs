@@ -0,0 +1,74 @@
+mod common;
+
+use actix_web::http::header::HeaderValue;
+use actix_web::{HttpRequest, HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use the_solana_api::{AppState, Settings};
+
+fn echo_headers_upstream() -> common::MockUpstream {
+    common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|req: HttpRequest| async move {
+                let header = |name: &str| {
+                    req.headers()
+                        .get(name)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string)
+                };
+                HttpResponse::Ok().json(json!({
+                    "user_agent": header("user-agent"),
+                    "proxied_by": header("x-proxied-by"),
+                    "original_user_agent": header("x-original-user-agent"),
+                }))
+            }),
+        );
+    })
+}
+
+async fn forwarded_headers(settings: Settings) -> Value {
+    let upstream = echo_headers_upstream();
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("echo-1", "lab")]),
+        settings,
+    );
+    let app = common::init_app(state).await;
+
+    let request = test::TestRequest::post()
+        .uri("/")
+        .insert_header(("user-agent", "curl/8.0"))
+        // Spoofed; never passed on.
+        .insert_header(("x-proxied-by", "someone else"))
+        .insert_header(("x-original-user-agent", "not curl"))
+        .set_json(json!({ "jsonrpc": "2.0", "id": 1, "method": "getSlot" }))
+        .to_request();
+    test::call_and_read_body_json(&app, request).await
+}
+
+#[actix_web::test]
+async fn identifies_proxy_with_default_user_agent() {
+    let headers = forwarded_headers(Settings::default()).await;
+
+    assert_eq!(
+        headers["user_agent"],
+        format!("the-solana-api/{}", env!("CARGO_PKG_VERSION"))
+    );
+    assert!(headers["proxied_by"].is_null());
+    assert!(headers["original_user_agent"].is_null());
+}
+
+#[actix_web::test]
+async fn sends_configured_identification_headers() {
+    let settings = Settings::builder()
+        .upstream_user_agent(HeaderValue::from_static("acme-proxy/2"))
+        .proxied_by(HeaderValue::from_static("acme rpc gateway"))
+        .preserve_client_user_agent(true)
+        .build()
+        .unwrap();
+    let headers = forwarded_headers(settings).await;
+
+    assert_eq!(headers["user_agent"], "acme-proxy/2");
+    assert_eq!(headers["proxied_by"], "acme rpc gateway");
+    assert_eq!(headers["original_user_agent"], "curl/8.0");
+}
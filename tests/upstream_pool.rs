@@ -0,0 +1,209 @@
+mod common;
+
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{App, HttpResponse, HttpServer, test, web};
+use futures_util::future::join_all;
+use serde_json::{Value, json};
+use url::Url;
+
+use the_solana_api::{AppState, Settings, Validator, routes};
+
+#[derive(Default)]
+struct Counters {
+    connections: AtomicUsize,
+    in_flight: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+// Counts the TCP connections it accepts and the most requests it served at
+// once.
+fn counting_upstream(
+    counters: Arc<Counters>,
+) -> (Url, tokio::task::JoinHandle<std::io::Result<()>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+    let accepted = counters.clone();
+    let server = HttpServer::new(move || {
+        let counters = counters.clone();
+        App::new().route(
+            "/",
+            web::post().to(move |body: web::Json<Value>| {
+                let counters = counters.clone();
+                async move {
+                    let now = counters.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    counters.peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    counters.in_flight.fetch_sub(1, Ordering::SeqCst);
+                    HttpResponse::Ok()
+                        .json(json!({"jsonrpc": "2.0", "id": body["id"], "result": 1}))
+                }
+            }),
+        )
+    })
+    .on_connect(move |_, _| {
+        accepted.connections.fetch_add(1, Ordering::SeqCst);
+    })
+    .workers(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    (url, tokio::spawn(server))
+}
+
+async fn burst(state: AppState, count: usize) {
+    let app = common::init_app(state).await;
+    let requests = (0..count).map(|id| {
+        let req = test::TestRequest::post()
+            .uri("/")
+            .set_json(json!({"jsonrpc": "2.0", "id": id, "method": "getSlot"}))
+            .to_request();
+        let app = &app;
+        async move { test::call_service(app, req).await.status() }
+    });
+    for status in join_all(requests).await {
+        assert_eq!(status, StatusCode::OK);
+    }
+}
+
+#[actix_web::test]
+async fn connections_per_host_are_capped_and_reused() {
+    let counters = Arc::new(Counters::default());
+    let (url, server) = counting_upstream(counters.clone());
+    let state = AppState::from_settings(
+        common::registry(vec![Validator::new("node".into(), "lab".into(), url)]),
+        Settings::builder()
+            .upstream_max_connections_per_host(2)
+            .build()
+            .unwrap(),
+    );
+
+    burst(state.clone(), 8).await;
+    assert_eq!(counters.peak.load(Ordering::SeqCst), 2);
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 2);
+
+    // Later requests go over the idle connections.
+    burst(state, 4).await;
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 2);
+    server.abort();
+}
+
+// Serves the proxy over real sockets with `workers` threads and sends it
+// `count` requests at once.
+async fn burst_through_workers(state: AppState, workers: usize, count: usize) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(state.clone()))
+            .configure(routes::configure)
+    })
+    .workers(workers)
+    .listen(listener)
+    .unwrap()
+    .run();
+    let server = tokio::spawn(server);
+
+    let client = awc::Client::default();
+    let requests = (0..count).map(|id| {
+        client
+            .post(format!("http://{addr}/"))
+            .send_json(&json!({"jsonrpc": "2.0", "id": id, "method": "getSlot"}))
+    });
+    for response in join_all(requests).await {
+        assert_eq!(response.unwrap().status(), StatusCode::OK);
+    }
+    server.abort();
+}
+
+#[actix_web::test]
+async fn each_worker_keeps_its_own_pool() {
+    for workers in [1, 4] {
+        let counters = Arc::new(Counters::default());
+        let (url, upstream) = counting_upstream(counters.clone());
+        let state = AppState::from_settings(
+            common::registry(vec![Validator::new("node".into(), "lab".into(), url)]),
+            Settings {
+                workers,
+                upstream_max_connections_per_host: 1,
+                ..Settings::default()
+            },
+        );
+
+        burst_through_workers(state, workers, 12).await;
+        let peak = counters.peak.load(Ordering::SeqCst);
+        let connections = counters.connections.load(Ordering::SeqCst);
+        assert!((1..=workers).contains(&peak), "{workers} workers: {peak}");
+        assert!(
+            (1..=workers).contains(&connections),
+            "{workers} workers: {connections}"
+        );
+        upstream.abort();
+    }
+}
+
+#[actix_web::test]
+async fn bursts_open_connections_up_to_the_default_cap() {
+    let counters = Arc::new(Counters::default());
+    let (url, server) = counting_upstream(counters.clone());
+    let state = AppState::from_settings(
+        common::registry(vec![Validator::new("node".into(), "lab".into(), url)]),
+        Settings::default(),
+    );
+
+    burst(state, 8).await;
+    assert_eq!(counters.peak.load(Ordering::SeqCst), 8);
+    server.abort();
+}
+
+#[actix_web::test]
+async fn reloads_drop_the_pools_of_removed_hosts() {
+    let counters = Arc::new(Counters::default());
+    let (url, server) = counting_upstream(counters.clone());
+    let other = common::spawn_upstream(|cfg| {
+        cfg.route(
+            "/",
+            web::post().to(|| async {
+                HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": 1, "result": 2}))
+            }),
+        );
+    });
+    let path = std::env::temp_dir().join(format!(
+        "solana-api-pool-registry-{}.csv",
+        std::process::id()
+    ));
+    let with_node = format!("name,rpc_url,location\nnode,{url},lab\n");
+    let without_node = format!("name,rpc_url,location\nother,{},lab\n", other.url());
+    std::fs::write(&path, &with_node).unwrap();
+    let state = AppState::from_settings(
+        the_solana_api::ValidatorRegistry::from_csv(&path).unwrap(),
+        Settings::builder()
+            .validators_csv(path.clone())
+            .build()
+            .unwrap(),
+    );
+
+    burst(state.clone(), 1).await;
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 1);
+
+    // Its idle connection goes with the host...
+    std::fs::write(&path, &without_node).unwrap();
+    state.registry_store().reload(state.settings()).unwrap();
+    burst(state.clone(), 1).await;
+    // ...so it is dialled afresh once the host is back.
+    std::fs::write(&path, &with_node).unwrap();
+    state.registry_store().reload(state.settings()).unwrap();
+    burst(state.clone(), 1).await;
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 2);
+
+    // Hosts that stay keep their pool across a reload.
+    state.registry_store().reload(state.settings()).unwrap();
+    burst(state, 1).await;
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 2);
+    let _ = std::fs::remove_file(&path);
+    server.abort();
+}
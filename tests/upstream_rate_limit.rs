@@ -0,0 +1,143 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{Value, json};
+
+use the_solana_api::rate_limit::KeyRateLimit;
+use the_solana_api::{AppState, Settings, ValidatorRegistry};
+
+fn limit(requests_per_second: f64, burst: u32) -> KeyRateLimit {
+    KeyRateLimit::new(requests_per_second, Some(burst)).unwrap()
+}
+
+fn upstream() -> common::MockUpstream {
+    common::counting_upstream(Arc::default())
+}
+
+fn get_slot(uri: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri(uri)
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+        .to_request()
+}
+
+#[actix_web::test]
+async fn load_spills_over_to_validators_with_budget_left() {
+    let hits: [Arc<AtomicUsize>; 3] = Default::default();
+    let upstreams = hits.clone().map(common::counting_upstream);
+    let state = AppState::from_settings(
+        common::registry(
+            upstreams
+                .iter()
+                .zip(["a", "b", "c"])
+                .map(|(upstream, name)| upstream.validator(name, "lab"))
+                .collect(),
+        ),
+        Settings::builder()
+            .upstream_rate_limit(limit(0.01, 3))
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    let responses =
+        futures_util::future::join_all((0..10).map(|_| test::call_service(&app, get_slot("/"))))
+            .await;
+    let (served, mut refused): (Vec<_>, Vec<_>) = responses
+        .into_iter()
+        .partition(|resp| resp.status() == StatusCode::OK);
+    assert_eq!(served.len(), 9);
+    assert_eq!(
+        hits.each_ref().map(|hits| hits.load(Ordering::SeqCst)),
+        [3, 3, 3]
+    );
+
+    assert_eq!(refused.len(), 1);
+    let resp = refused.pop().unwrap();
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = resp
+        .headers()
+        .get("retry-after")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "rate_limited");
+}
+
+#[actix_web::test]
+async fn a_validator_column_overrides_the_default() {
+    let (tight, roomy) = (upstream(), upstream());
+    let state = AppState::from_settings(
+        common::registry(vec![
+            tight
+                .validator("tight", "lab")
+                .with_rate_limit(Some(limit(0.01, 1))),
+            roomy.validator("roomy", "lab"),
+        ]),
+        Settings::builder()
+            .upstream_rate_limit(limit(0.01, 5))
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    let resp = test::call_service(&app, get_slot("/?server=tight")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&app, get_slot("/?server=tight")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    for _ in 0..5 {
+        let resp = test::call_service(&app, get_slot("/?server=roomy")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    let resp = test::call_service(&app, get_slot("/")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[actix_web::test]
+async fn pinned_requests_wait_when_allowed() {
+    let node = upstream();
+    let state = AppState::from_settings(
+        common::registry(vec![node.validator("node", "lab")]),
+        Settings::builder()
+            .upstream_rate_limit(limit(10.0, 1))
+            .upstream_rate_limit_wait(Duration::from_millis(500))
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    let resp = test::call_service(&app, get_slot("/?server=node")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let started = Instant::now();
+    let resp = test::call_service(&app, get_slot("/?server=node")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(started.elapsed() >= Duration::from_millis(50));
+
+    // Unpinned requests never wait.
+    let resp = test::call_service(&app, get_slot("/")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[actix_web::test]
+async fn reads_rate_limits_from_csv() {
+    let registry = ValidatorRegistry::from_reader(
+        "name,ip,rate_limit_rps\nslow,10.0.0.1,2.5\nfree,10.0.0.2,\n".as_bytes(),
+    )
+    .unwrap();
+    let slow = registry.get_by_name("slow").unwrap();
+    assert_eq!(slow.rate_limit(), Some(limit(2.5, 3)));
+    assert_eq!(registry.get_by_name("free").unwrap().rate_limit(), None);
+
+    let err = ValidatorRegistry::from_reader("name,ip,rate_limit_rps\nbad,10.0.0.1,0\n".as_bytes())
+        .unwrap_err();
+    assert!(err.to_string().contains("row 2"), "{err}");
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+use url::Url;
+
+use the_solana_api::stats::FailureKind;
+use the_solana_api::{AppState, Validator};
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+// Answers after `delay`, counting the calls it received.
+fn node(calls: Arc<AtomicUsize>, delay: Duration) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        let calls = calls.clone();
+        cfg.route(
+            "/",
+            web::post().to(move |body: web::Json<Value>| {
+                let calls = calls.clone();
+                async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(delay).await;
+                    HttpResponse::Ok()
+                        .json(json!({"jsonrpc": "2.0", "id": body["id"], "result": 7}))
+                }
+            }),
+        );
+    })
+}
+
+fn state(validators: Vec<Validator>) -> AppState {
+    AppState::builder()
+        .validators(validators)
+        .request_timeout(TIMEOUT)
+        .max_retries(1)
+        .build()
+        .unwrap()
+}
+
+fn call(method: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": method}))
+        .to_request()
+}
+
+#[actix_web::test]
+async fn slow_validators_answer_504() {
+    let slow = node(Arc::default(), Duration::from_secs(2));
+    let state = state(vec![slow.validator("slow", "frankfurt")]);
+    let app = common::init_app(state.clone()).await;
+
+    let resp = test::call_service(&app, call("getSlot")).await;
+    assert_eq!(resp.status(), StatusCode::GATEWAY_TIMEOUT);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "upstream_timeout");
+    assert_eq!(body["details"]["validator"], "slow");
+    assert_eq!(
+        state
+            .stats()
+            .validator("slow")
+            .failures(FailureKind::Timeout),
+        1
+    );
+}
+
+#[actix_web::test]
+async fn refused_connections_stay_502() {
+    let down = Validator::new(
+        "down".into(),
+        "lab".into(),
+        Url::parse("http://127.0.0.1:1/").unwrap(),
+    );
+    let app = common::init_app(state(vec![down])).await;
+
+    let resp = test::call_service(&app, call("getSlot")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "upstream_connect_failed");
+}
+
+#[actix_web::test]
+async fn reads_fail_over_after_a_timeout() {
+    let slow = node(Arc::default(), Duration::from_secs(2));
+    let fast = node(Arc::default(), Duration::ZERO);
+    let app = common::init_app(state(vec![
+        slow.validator("slow", "frankfurt"),
+        fast.validator("fast", "frankfurt"),
+    ]))
+    .await;
+
+    // Whichever comes first, the read ends up on the fast validator.
+    for _ in 0..4 {
+        let resp = test::call_service(&app, call("getSlot")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["result"], 7);
+    }
+}
+
+#[actix_web::test]
+async fn writes_are_not_retried_after_a_timeout() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let first = node(calls.clone(), Duration::from_secs(2));
+    let second = node(calls.clone(), Duration::from_secs(2));
+    let app = common::init_app(state(vec![
+        first.validator("first", "frankfurt"),
+        second.validator("second", "frankfurt"),
+    ]))
+    .await;
+
+    let resp = test::call_service(&app, call("sendTransaction")).await;
+    assert_eq!(resp.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    let resp = test::call_service(&app, call("getSlot")).await;
+    assert_eq!(resp.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,286 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, test, web};
+use serde_json::{Value, json};
+use url::Url;
+
+use the_solana_api::config::Settings;
+use the_solana_api::errors::UpstreamErrorKind;
+use the_solana_api::stats::{FailureKind, ValidatorStats};
+use the_solana_api::{AppState, Validator};
+
+const ANSWER: &str = r#"{"jsonrpc":"2.0","id":1,"result":42}"#;
+
+fn get_slot() -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/")
+        .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+}
+
+fn node(status: StatusCode, body: &'static str) -> common::MockUpstream {
+    common::spawn_upstream(move |cfg| {
+        cfg.route(
+            "/",
+            web::post().to(move || async move {
+                HttpResponse::build(status)
+                    .content_type("application/json")
+                    .body(body)
+            }),
+        );
+    })
+}
+
+fn with_admin_token(validators: Vec<Validator>) -> AppState {
+    AppState::from_settings(
+        common::registry(validators),
+        Settings::builder()
+            .admin_token("s3cret".into())
+            .build()
+            .unwrap(),
+    )
+}
+
+async fn validator_stats<S>(app: &S, name: &str) -> Value
+where
+    S: actix_web::dev::Service<
+            actix_http::Request,
+            Response = actix_web::dev::ServiceResponse,
+            Error = actix_web::Error,
+        >,
+{
+    let body: Value =
+        test::call_and_read_body_json(app, test::TestRequest::get().uri("/stats").to_request())
+            .await;
+    body["validators"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["name"] == name)
+        .cloned()
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn reports_traffic_per_validator_with_stable_field_names() {
+    let upstream = node(StatusCode::OK, ANSWER);
+    let app = common::init_app(AppState::new(common::registry(vec![
+        upstream.validator("primary", "frankfurt"),
+    ])))
+    .await;
+
+    let request = get_slot().to_request();
+    let sent = json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"})
+        .to_string()
+        .len() as u64;
+    let resp = test::call_service(&app, request).await;
+    assert!(resp.status().is_success());
+
+    let entry = validator_stats(&app, "primary").await;
+    let mut fields: Vec<&str> = entry
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    fields.sort();
+    assert_eq!(
+        fields,
+        [
+            "bytes_per_sec",
+            "bytes_received",
+            "bytes_sent",
+            "cooldown_remaining_ms",
+            "failures",
+            "in_flight",
+            "invalid_responses",
+            "last_error",
+            "latency_histogram_ms",
+            "latency_ms",
+            "latest_slot",
+            "location",
+            "name",
+            "requests",
+            "resolved_addresses",
+            "slow_requests",
+            "state",
+            "successes",
+            "unsupported_methods",
+        ]
+    );
+    assert_eq!(
+        entry["failures"],
+        json!({
+            "unavailable": 0,
+            "timeout": 0,
+            "rate_limited": 0,
+            "http_status": 0,
+            "invalid_response": 0,
+            "too_large": 0,
+            "unreadable": 0,
+        })
+    );
+    let latency = entry["latency_ms"].as_object().unwrap();
+    let mut latency_fields: Vec<&str> = latency.keys().map(String::as_str).collect();
+    latency_fields.sort();
+    assert_eq!(latency_fields, ["p50", "p95", "p99", "samples"]);
+    assert_eq!(latency["samples"], 1);
+    assert!(latency["p99"].is_number());
+
+    assert_eq!(entry["state"], "available");
+    assert_eq!(entry["requests"], 1);
+    assert_eq!(entry["successes"], 1);
+    assert_eq!(entry["bytes_sent"], sent);
+    assert_eq!(entry["bytes_received"], ANSWER.len());
+    assert_eq!(entry["in_flight"], 0);
+    assert_eq!(entry["last_error"], Value::Null);
+}
+
+#[actix_web::test]
+async fn counts_failures_by_category_and_keeps_the_last_error() {
+    let broken = node(StatusCode::SERVICE_UNAVAILABLE, "{}");
+    let app = common::init_app(AppState::new(common::registry(vec![
+        broken.validator("broken", "frankfurt"),
+        Validator::new(
+            "down".into(),
+            "lab".into(),
+            Url::parse("http://127.0.0.1:1/").unwrap(),
+        ),
+    ])))
+    .await;
+
+    for server in ["broken", "down"] {
+        let req = get_slot().uri(&format!("/?server={server}")).to_request();
+        test::call_service(&app, req).await;
+    }
+
+    let broken = validator_stats(&app, "broken").await;
+    assert_eq!(broken["requests"], 1);
+    assert_eq!(broken["successes"], 0);
+    assert_eq!(broken["failures"]["http_status"], 1);
+    assert_eq!(broken["last_error"]["kind"], "http_status");
+    assert_eq!(
+        broken["last_error"]["message"],
+        "upstream returned 503 Service Unavailable"
+    );
+    assert!(broken["last_error"]["at_unix_ms"].as_u64().unwrap() > 0);
+
+    let down = validator_stats(&app, "down").await;
+    assert_eq!(down["failures"]["unavailable"], 1);
+    assert_eq!(down["last_error"]["kind"], "unavailable");
+    assert_eq!(down["latency_ms"]["samples"], 0);
+    assert_eq!(down["latency_ms"]["p50"], Value::Null);
+}
+
+#[actix_web::test]
+async fn rejected_answers_move_from_successes_to_failures() {
+    let upstream = node(StatusCode::OK, "<html>oops</html>");
+    let state = AppState::from_settings(
+        common::registry(vec![upstream.validator("primary", "frankfurt")]),
+        Settings::builder().strict_responses(true).build().unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    let resp = test::call_service(&app, get_slot().to_request()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+
+    let entry = validator_stats(&app, "primary").await;
+    assert_eq!(entry["requests"], 1);
+    assert_eq!(entry["successes"], 0);
+    assert_eq!(entry["failures"]["invalid_response"], 1);
+    assert_eq!(entry["invalid_responses"], 1);
+}
+
+#[actix_web::test]
+async fn reset_requires_the_admin_token() {
+    let upstream = node(StatusCode::OK, ANSWER);
+    let app = common::init_app(with_admin_token(vec![
+        upstream.validator("primary", "frankfurt"),
+    ]))
+    .await;
+    test::call_service(&app, get_slot().to_request()).await;
+
+    let reset = |token: Option<&str>| {
+        let mut req = test::TestRequest::post().uri("/stats/reset");
+        if let Some(token) = token {
+            req = req.insert_header(("Authorization", format!("Bearer {token}")));
+        }
+        req.to_request()
+    };
+
+    let resp = test::call_service(&app, reset(None)).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = test::call_service(&app, reset(Some("wrong"))).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "invalid admin token");
+    assert_eq!(validator_stats(&app, "primary").await["requests"], 1);
+
+    let resp = test::call_service(&app, reset(Some("s3cret"))).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let body: Value =
+        test::call_and_read_body_json(&app, test::TestRequest::get().uri("/stats").to_request())
+            .await;
+    let entry = &body["validators"][0];
+    assert_eq!(entry["requests"], 0);
+    assert_eq!(entry["bytes_sent"], 0);
+    assert_eq!(entry["latency_ms"]["samples"], 0);
+    assert_eq!(body["methods"], json!([]));
+}
+
+#[actix_web::test]
+async fn reset_is_refused_without_a_configured_token() {
+    let upstream = node(StatusCode::OK, ANSWER);
+    let app = common::init_app(AppState::new(common::registry(vec![
+        upstream.validator("primary", "frankfurt"),
+    ])))
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/stats/reset")
+        .insert_header(("Authorization", "Bearer anything"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn latency_window_is_bounded_and_percentiles_use_nearest_rank() {
+    let stats = ValidatorStats::default();
+    for ms in 1..=100 {
+        stats.record_response(StatusCode::OK, Duration::from_millis(ms));
+    }
+    let latency = stats.traffic().latency_ms;
+    assert_eq!(latency.samples, 100);
+    assert_eq!(latency.p50, Some(50.0));
+    assert_eq!(latency.p95, Some(95.0));
+    assert_eq!(latency.p99, Some(99.0));
+
+    // Old samples are overwritten once the window is full.
+    for _ in 0..5000 {
+        stats.record_response(StatusCode::OK, Duration::from_millis(7));
+    }
+    let latency = stats.traffic().latency_ms;
+    assert_eq!(latency.samples, 1024);
+    assert_eq!(latency.p99, Some(7.0));
+}
+
+#[actix_web::test]
+async fn in_flight_counts_open_calls() {
+    let stats = Arc::new(ValidatorStats::default());
+    let first = stats.begin(10);
+    let second = stats.begin(5);
+    assert_eq!(stats.in_flight(), 2);
+    drop(first);
+    assert_eq!(stats.in_flight(), 1);
+    drop(second);
+    assert_eq!(stats.in_flight(), 0);
+    assert_eq!(stats.traffic().bytes_sent, 15);
+
+    stats.record_no_response(UpstreamErrorKind::ConnectRefused, "connection refused");
+    assert_eq!(stats.failures(FailureKind::Unavailable), 1);
+}
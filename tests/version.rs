@@ -0,0 +1,84 @@
+mod common;
+
+use std::path::PathBuf;
+
+use actix_web::test;
+use serde_json::{Value, json};
+
+use the_solana_api::config::Settings;
+use the_solana_api::version;
+use the_solana_api::{AppState, Validator};
+
+#[actix_web::test]
+async fn reports_build_and_registry_information() {
+    let validators = vec![
+        Validator::new(
+            "frankfurt-1".into(),
+            "frankfurt".into(),
+            "http://127.0.0.1:1/".parse().unwrap(),
+        ),
+        Validator::new(
+            "paris-1".into(),
+            "paris".into(),
+            "http://127.0.0.1:2/".parse().unwrap(),
+        ),
+    ];
+    let state = AppState::from_settings(
+        common::registry(validators),
+        Settings::builder()
+            .validators_csv(PathBuf::from("config/validators.csv"))
+            .admin_token("s3cret".into())
+            .build()
+            .unwrap(),
+    );
+    let app = common::init_app(state).await;
+
+    let body: Value =
+        test::call_and_read_body_json(&app, test::TestRequest::get().uri("/version").to_request())
+            .await;
+
+    let mut fields: Vec<&str> = body
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    fields.sort();
+    assert_eq!(
+        fields,
+        [
+            "built_at_unix_ms",
+            "features",
+            "git_commit",
+            "registry",
+            "version"
+        ]
+    );
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(body["git_commit"], version::GIT_COMMIT);
+    assert!(!version::GIT_COMMIT.is_empty());
+    assert!(body["built_at_unix_ms"].as_u64().unwrap() > 0);
+    assert_eq!(body["features"], json!(version::features()));
+
+    let registry = &body["registry"];
+    assert_eq!(registry["source"], "config/validators.csv");
+    assert_eq!(registry["validators"], 2);
+    assert!(registry["loaded_at_unix_ms"].as_u64().unwrap() > 0);
+    assert_eq!(registry.as_object().unwrap().len(), 3);
+
+    assert!(!body.to_string().contains("s3cret"));
+}
+
+#[actix_web::test]
+async fn index_points_to_the_version_endpoint() {
+    let state = AppState::new(common::registry(vec![Validator::new(
+        "frankfurt-1".into(),
+        "frankfurt".into(),
+        "http://127.0.0.1:1/".parse().unwrap(),
+    )]));
+    let app = common::init_app(state).await;
+
+    let body: Value =
+        test::call_and_read_body_json(&app, test::TestRequest::get().uri("/").to_request()).await;
+    assert_eq!(body["version"], "/version");
+}